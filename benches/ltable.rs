@@ -0,0 +1,190 @@
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Insertion and lookup throughput of the Rust tables against the C ones.
+//!
+//! The C 'ltable.c' is linked a second time with its functions renamed to
+//! 'c_luaH_*', so both implementations run on the same state and allocator.
+//! Run with 'cargo bench --bench ltable'.
+
+#![allow(non_snake_case)]
+
+extern crate libc;
+extern crate lua_rs;
+
+use libc::{c_char, c_int, c_void, size_t};
+use lua_rs::ffi::lauxlib::luaL_newstate;
+use lua_rs::ffi::lua::{self, lua_Integer, lua_State};
+use std::time::Instant;
+
+enum Table {}
+enum TString {}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+union Value {
+    i: lua_Integer,
+    gc: *mut c_void,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct TValue {
+    value_: Value,
+    tt_: c_int,
+}
+
+const LUA_TNUMINT: c_int = lua::LUA_TNUMBER | (1 << 4);
+const LUA_TSHRSTR: c_int = lua::LUA_TSTRING | (1 << 6);  /* marked collectable */
+
+fn ivalue(i: lua_Integer) -> TValue { TValue { value_: Value { i }, tt_: LUA_TNUMINT } }
+
+fn svalue(s: *mut TString) -> TValue {
+    TValue { value_: Value { gc: s as *mut c_void }, tt_: LUA_TSHRSTR }
+}
+
+extern "C-unwind" {
+    fn luaS_newlstr(L: *mut lua_State, str: *const c_char, l: size_t) -> *mut TString;
+
+    fn luaH_new(L: *mut lua_State) -> *mut Table;
+    fn luaH_getint(t: *mut Table, key: lua_Integer) -> *const TValue;
+    fn luaH_setint(L: *mut lua_State, t: *mut Table, key: lua_Integer, value: *mut TValue);
+    fn luaH_get(t: *mut Table, key: *const TValue) -> *const TValue;
+    fn luaH_set(L: *mut lua_State, t: *mut Table, key: *const TValue) -> *mut TValue;
+
+    fn c_luaH_new(L: *mut lua_State) -> *mut Table;
+    fn c_luaH_getint(t: *mut Table, key: lua_Integer) -> *const TValue;
+    fn c_luaH_setint(L: *mut lua_State, t: *mut Table, key: lua_Integer, value: *mut TValue);
+    fn c_luaH_get(t: *mut Table, key: *const TValue) -> *const TValue;
+    fn c_luaH_set(L: *mut lua_State, t: *mut Table, key: *const TValue) -> *mut TValue;
+}
+
+struct Impl {
+    name: &'static str,
+    new: unsafe extern "C-unwind" fn(*mut lua_State) -> *mut Table,
+    getint: unsafe extern "C-unwind" fn(*mut Table, lua_Integer) -> *const TValue,
+    setint: unsafe extern "C-unwind" fn(*mut lua_State, *mut Table, lua_Integer, *mut TValue),
+    get: unsafe extern "C-unwind" fn(*mut Table, *const TValue) -> *const TValue,
+    set: unsafe extern "C-unwind" fn(*mut lua_State, *mut Table, *const TValue) -> *mut TValue,
+}
+
+const IMPLS: [Impl; 2] = [
+    Impl { name: "rust", new: luaH_new, getint: luaH_getint, setint: luaH_setint,
+           get: luaH_get, set: luaH_set },
+    Impl { name: "c", new: c_luaH_new, getint: c_luaH_getint, setint: c_luaH_setint,
+           get: c_luaH_get, set: c_luaH_set },
+];
+
+const NINTS: lua_Integer = 1 << 20;  /* keys per table for the integer cases */
+const NSTRINGS: usize = 1 << 16;  /* distinct keys for the string case */
+const ROUNDS: usize = 8;  /* tables built (and lookup passes) per case */
+
+/* nanoseconds per operation of running 'f', which performs 'ops' operations */
+fn time<F: FnMut()>(ops: usize, mut f: F) -> f64 {
+    let start = Instant::now();
+    f();
+    let d = start.elapsed();
+    (d.as_secs() as f64 * 1e9 + d.subsec_nanos() as f64) / ops as f64
+}
+
+/*
+** Every table gets a key in its hash part: the state frees them all with
+** the Rust 'luaH_free', which must not see the (static) C dummy node.
+*/
+unsafe fn anchor(L: *mut lua_State, imp: &Impl, t: *mut Table, key: &TValue) {
+    *(imp.set)(L, t, key) = ivalue(0);
+}
+
+unsafe fn run(L: *mut lua_State, imp: &Impl, strings: &[TValue]) -> [(&'static str, f64); 6] {
+    let anchor_key = strings[0];
+    let mut sum: lua_Integer = 0;
+    let mut tables = Vec::new();
+    /* sequential integer keys: the array part */
+    let seq_insert = time(ROUNDS * NINTS as usize, || {
+        for _ in 0..ROUNDS {
+            let t = (imp.new)(L);
+            for i in 1..NINTS + 1 {
+                (imp.setint)(L, t, i, &mut ivalue(i));
+            }
+            tables.push(t);
+        }
+    });
+    let t = tables[0];
+    let seq_lookup = time(ROUNDS * NINTS as usize, || {
+        for _ in 0..ROUNDS {
+            for i in 1..NINTS + 1 {
+                sum = sum.wrapping_add((*(imp.getint)(t, i)).value_.i);
+            }
+        }
+    });
+    /* scattered integer keys: the hash part */
+    let scatter = |i: lua_Integer| i.wrapping_mul(0x9E3779B97F4A7C15u64 as lua_Integer);
+    let sparse_insert = time(ROUNDS * NINTS as usize, || {
+        for _ in 0..ROUNDS {
+            let t = (imp.new)(L);
+            for i in 1..NINTS + 1 {
+                (imp.setint)(L, t, scatter(i), &mut ivalue(i));
+            }
+            tables.push(t);
+        }
+    });
+    let t = tables[ROUNDS];
+    let sparse_lookup = time(ROUNDS * NINTS as usize, || {
+        for _ in 0..ROUNDS {
+            for i in 1..NINTS + 1 {
+                sum = sum.wrapping_add((*(imp.getint)(t, scatter(i))).value_.i);
+            }
+        }
+    });
+    /* short string keys */
+    let string_rounds = ROUNDS * (NINTS as usize / NSTRINGS);
+    let string_insert = time(string_rounds * NSTRINGS, || {
+        for _ in 0..string_rounds {
+            let t = (imp.new)(L);
+            for (i, k) in strings.iter().enumerate() {
+                *(imp.set)(L, t, k) = ivalue(i as lua_Integer);
+            }
+            tables.push(t);
+        }
+    });
+    let t = tables[2 * ROUNDS];
+    let string_lookup = time(string_rounds * NSTRINGS, || {
+        for _ in 0..string_rounds {
+            for k in strings {
+                sum = sum.wrapping_add((*(imp.get)(t, k)).value_.i);
+            }
+        }
+    });
+    for &t in &tables {
+        anchor(L, imp, t, &anchor_key);
+    }
+    assert!(sum != 0);  /* keep the lookups alive */
+    [("insert, sequential integer keys", seq_insert),
+     ("lookup, sequential integer keys", seq_lookup),
+     ("insert, scattered integer keys", sparse_insert),
+     ("lookup, scattered integer keys", sparse_lookup),
+     ("insert, short string keys", string_insert),
+     ("lookup, short string keys", string_lookup)]
+}
+
+fn main() {
+    unsafe {
+        let L = luaL_newstate();
+        lua::lua_gc(L, lua::LUA_GCSTOP, 0);  /* tables are unreachable; keep them */
+        let strings: Vec<TValue> = (0..NSTRINGS).map(|i| {
+            let s = format!("k{}", i);
+            svalue(luaS_newlstr(L, s.as_ptr() as *const c_char, s.len()))
+        }).collect();
+        for imp in &IMPLS {  /* warm up; whichever runs first is otherwise penalized */
+            run(L, imp, &strings);
+        }
+        let results: Vec<_> = IMPLS.iter().map(|imp| run(L, imp, &strings)).collect();
+        println!("{:<34}{:>10}{:>10}{:>8}", "ns/op", IMPLS[0].name, IMPLS[1].name, "ratio");
+        for (r, c) in results[0].iter().zip(results[1].iter()) {
+            println!("{:<34}{:>10.2}{:>10.2}{:>8.2}", r.0, r.1, c.1, r.1 / c.1);
+        }
+        lua::lua_close(L);
+    }
+}
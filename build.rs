@@ -6,42 +6,177 @@
 use std::process::Command;
 
 #[cfg(target_os = "macos")]
-fn os(cmd: &mut Command) -> &mut Command { cmd.arg("macos") }
+const SYSCFLAGS: &[&str] = &["-DLUA_USE_MACOSX"];
 
 #[cfg(target_os = "linux")]
-fn os(cmd: &mut Command) -> &mut Command { cmd.arg("linux").arg("MYCFLAGS=-fPIC") }
+const SYSCFLAGS: &[&str] = &["-DLUA_USE_LINUX"];
 
 #[cfg(not(any(target_os = "macos", target_os = "linux")))]
-fn os(cmd: &mut Command) -> &mut Command { cmd.arg("generic") }
+const SYSCFLAGS: &[&str] = &[];
+
+/* which implementation of a module of 'puc-lua/src' gets linked */
+#[derive(Clone, Copy)]
+enum Implementation {
+    C,
+    Rust,
+    Feature(&'static str),  /* Rust with the given cargo feature, C without it */
+}
+
+use Implementation::*;
+
+/* the modules of 'liblua.a', in the order of 'puc-lua/src/Makefile' */
+const MODULES: &[(&str, Implementation)] = &[
+    ("lapi", Rust),
+    ("lcode", Rust),
+    ("lctype", C),
+    ("ldebug", Rust),
+    ("ldo", Rust),
+    ("ldump", Rust),
+    ("lfunc", Rust),
+    ("lgc", Rust),
+    ("llex", Rust),
+    ("lmem", C),
+    ("lobject", Rust),
+    ("lopcodes", Rust),
+    ("lparser", Rust),
+    ("lstate", Rust),
+    ("lstring", Rust),
+    ("ltable", Rust),
+    ("ltm", Rust),
+    ("lundump", Rust),
+    ("lvm", Feature("rust-vm")),  /* 'luaV_execute' and friends */
+    ("lzio", C),
+    ("lauxlib", Rust),
+    ("lbaselib", Rust),
+    ("lcorolib", Rust),
+    ("ldblib", Rust),
+    ("liolib", Rust),
+    ("lmathlib", C),
+    ("loslib", Rust),
+    ("lstrlib", C),
+    ("ltablib", C),
+    ("lutf8lib", Rust),
+    ("loadlib", Rust),
+    ("linit", C),
+];
+
+/*
+** C objects kept on top of Rust modules, for what Rust cannot define:
+** while 'module' is in Rust, 'src' is compiled and added to 'liblua.a' as
+** 'name'.
+*/
+const SHIMS: &[(&str, &str, &str)] = &[
+    /* 'luaL_error' is variadic, so it stays in C on top of the Rust 'luaL_where' */
+    ("lauxlib", "src/lauxlib.c", "lauxlib_error"),
+    /* the same goes for 'luaO_pushfstring', on top of the Rust 'luaO_pushfargs' */
+    ("lobject", "src/lobject.c", "lobject_fstring"),
+    /* and for 'lua_pushfstring', on top of it */
+    ("lapi", "src/lapi.c", "lapi_fstring"),
+    /* and for 'luaG_runerror', on top of the Rust 'luaG_runerrormsg' */
+    ("ldebug", "src/ldebug.c", "ldebug_runerror"),
+];
+
+fn feature(name: &str) -> bool {
+    std::env::var(format!("CARGO_FEATURE_{}", name.to_uppercase().replace('-', "_"))).is_ok()
+}
+
+impl Implementation {
+    fn is_c(self) -> bool {
+        match self {
+            C => true,
+            Rust => false,
+            Feature(name) => !feature(name),
+        }
+    }
+}
+
+/* 'LUAI_FUNC's of 'ltable.c' */
+const C_TABLE_FUNCTIONS: &[&str] = &[
+    "getint", "setint", "getshortstr", "getstr", "get", "newkey", "set", "new",
+    "resize", "resizearray", "free", "next", "getn", "mainposition", "isdummy",
+];
+
+/* compile 'src' against the Lua headers and add it to 'liblua.a' as 'name.o' */
+fn add_object(out_dir: &str, debug: bool, src: &str, name: &str, defines: &[String]) {
+    let obj = format!("{}/{}.o", out_dir, name);
+    let mut cc = Command::new(std::env::var("CC").unwrap_or("cc".to_string()));
+    cc.arg("-c").arg("-std=gnu99").arg("-O2").arg("-fexceptions")  /* Lua errors unwind through C frames */
+      .arg("-Ipuc-lua/src").args(SYSCFLAGS).args(defines).arg(src).arg("-o").arg(&obj);
+    if cfg!(target_os = "linux") {
+        cc.arg("-fPIC");
+    }
+    if debug {
+        cc.arg("-Ipuc-lua/src/tests/ltests").arg(r#"-DLUA_USER_H="ltests.h""#);
+    }
+    assert!(cc.status().unwrap().success());
+    assert!(Command::new("ar").arg("r").arg(format!("{}/liblua.a", out_dir))
+                              .arg(&obj).status().unwrap().success());
+}
 
 fn main() {
     let out_dir = std::env::var("OUT_DIR").unwrap();
     let debug = std::env::var("PROFILE").unwrap() == "debug";
 
-    if debug {
-        assert!(Command::new("cp").arg("puc-lua/src/tests/ltests/ltests.c")
-                                  .arg("puc-lua/src/tests/ltests/ltests.h")
-                                  .arg("puc-lua/src").status().unwrap().success());
+    /* ported modules to link from C anyway, e.g. 'LUA_RS_C_MODULES=lutf8lib,lcorolib' */
+    let forced = std::env::var("LUA_RS_C_MODULES").unwrap_or_default();
+    let forced: Vec<&str> = forced.split(',').filter(|m| !m.is_empty()).collect();
+    for m in &forced {
+        assert!(MODULES.iter().any(|&(name, _)| name == *m), "LUA_RS_C_MODULES: unknown module '{}'", m);
     }
-    let mut cmd = Command::new("make");
-    os(cmd.arg("-C").arg("puc-lua"));
-    if debug {
-        cmd.arg(r#"MYCFLAGS+=-DLUA_USER_H='"ltests.h"'"#).arg("MYOBJS=ltests.o");
+    let mut c_modules = Vec::new();
+    for &(name, implementation) in MODULES {
+        println!("cargo:rustc-check-cfg=cfg(rust_{})", name);
+        if implementation.is_c() || forced.contains(&name) {
+            c_modules.push(name);
+        } else {  /* 'src/lib.rs' declares the Rust module only then */
+            println!("cargo:rustc-cfg=rust_{}", name);
+        }
     }
-    assert!(cmd.status().unwrap().success());
-    assert!(Command::new("cp").arg("puc-lua/src/liblua.a").arg(&out_dir).status().unwrap().success());
-    assert!(Command::new("cp").arg("puc-lua/src/luac").arg(&out_dir).status().unwrap().success());
-    assert!(Command::new("make").arg("-C").arg("puc-lua").arg("clean")
-                                .arg("MYOBJS=ltests.o").status().unwrap().success());
-    if debug {
-        assert!(Command::new("rm").arg("puc-lua/src/ltests.c")
-                                  .arg("puc-lua/src/ltests.h").status().unwrap().success());
+    let shims: Vec<_> = SHIMS.iter().filter(|s| !c_modules.contains(&s.0)).collect();
+    println!("cargo:rustc-check-cfg=cfg(ltests)");
+    if debug {  /* the 'T' library of the test suite, whose 'ltests.h' changes the C layouts */
+        c_modules.push("ltests");
+        println!("cargo:rustc-cfg=ltests");  /* for the Rust layouts to follow */
     }
+    if feature("pure-rust") {
+        let mut missing = c_modules.clone();
+        missing.extend(shims.iter().map(|s| s.2));
+        if !missing.is_empty() {
+            panic!("feature 'pure-rust' needs every module in Rust, but these are still C: {}",
+                   missing.join(", "));
+        }
+        return;
+    }
+
+    let _ = std::fs::remove_file(format!("{}/liblua.a", out_dir));  /* drop objects of earlier builds */
+    for module in &c_modules {
+        let src = if *module == "ltests" {
+            "puc-lua/src/tests/ltests/ltests.c".to_string()
+        } else {
+            format!("puc-lua/src/{}.c", module)
+        };
+        add_object(&out_dir, debug, &src, module, &[]);
+    }
+    for &&(_, src, name) in &shims {
+        add_object(&out_dir, debug, src, name, &[]);
+    }
+    /* the C tables, renamed to 'c_luaH_*', for 'benches/ltable.rs' to compare against */
+    let renames: Vec<String> = C_TABLE_FUNCTIONS.iter()
+        .map(|f| format!("-DluaH_{}=c_luaH_{}", f, f)).collect();
+    add_object(&out_dir, debug, "puc-lua/src/ltable.c", "ltable_c", &renames);
 
     println!("cargo:rustc-link-lib=static=lua");
+    if cfg!(target_os = "linux") {  /* export the Lua API to C libraries loaded by 'require' */
+        println!("cargo:rustc-link-arg-bins=-Wl,-E");
+        println!("cargo:rustc-link-arg-tests=-Wl,-E");
+    }
     println!("cargo:rustc-link-search=native={}", out_dir);
 
     for entry in std::fs::read_dir("puc-lua/src").unwrap() {
         println!("cargo:rerun-if-changed={}", entry.unwrap().path().display());
     }
+    for &(_, src, _) in SHIMS {
+        println!("cargo:rerun-if-changed={}", src);
+    }
+    println!("cargo:rerun-if-env-changed=LUA_RS_C_MODULES");
 }
@@ -0,0 +1,1433 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Abstract syntax tree of Lua chunks.
+//!
+//! `parse` reads a chunk into a typed tree, keeping the byte spans of
+//! its statements and expressions and the comments found around them.
+//! It follows the rules of `lparser.c` one by one, so it accepts the same
+//! programs as `load` and rejects the others with the same messages:
+//!
+//! ```
+//! use lua_rs::ast::{self, StatKind};
+//!
+//! let chunk = ast::parse(b"-- greet\nprint('hi')").unwrap();
+//! let stat = &chunk.block.stats[0];
+//! assert!(match stat.kind { StatKind::Call(_) => true, _ => false });
+//! assert_eq!(stat.comments[0].text, b" greet");
+//!
+//! let err = ast::parse_with_name(b"function f()", "stdin").unwrap_err();
+//! assert_eq!(err.to_string(), "stdin:1: 'end' expected near <eof>");
+//! assert!(err.is_incomplete());
+//! ```
+//!
+//! The checks made while generating code (such as the number of
+//! registers a function needs) are not done here.
+
+use lexer::{LexError, Lexer, Reserved, Spanned, Token, FIRST_RESERVED, LUA_ENV};
+use std::fmt;
+use std::ops::Range;
+
+pub use lexer::Comment;
+
+
+/// A range of bytes in the source.
+pub type Span = Range<usize>;
+
+
+/// A parsed chunk.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chunk {
+    pub block: Block,
+}
+
+/// A sequence of statements. `comments` has those that follow the last
+/// statement, up to the end of the block.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+    pub stats: Vec<Stat>,
+    pub comments: Vec<Comment>,
+}
+
+/// A statement, with the line where it starts. `comments` has those that
+/// precede it, those inside it but outside any nested statement, and
+/// those after it on its last line, in source order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stat {
+    pub kind: StatKind,
+    pub span: Span,
+    pub line: i32,
+    pub comments: Vec<Comment>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum StatKind {
+    Local { names: Vec<Name>, exprs: Vec<Expr> },
+    LocalFunction { name: Name, func: Function },
+    Function { name: FuncName, func: Function },
+    /// An assignment; each target is a `Name`, `Field` or `Index`.
+    Assign { targets: Vec<Expr>, exprs: Vec<Expr> },
+    /// A function call used as a statement; always a `Call` or `Method`.
+    Call(Expr),
+    Do(Block),
+    While { cond: Expr, body: Block },
+    Repeat { body: Block, cond: Expr },
+    /// `if`, with the conditions and blocks of it and its `elseif` parts.
+    If { conds: Vec<(Expr, Block)>, else_block: Option<Block> },
+    NumericFor { var: Name, start: Expr, limit: Expr, step: Option<Expr>, body: Block },
+    GenericFor { names: Vec<Name>, exprs: Vec<Expr>, body: Block },
+    Goto(Name),
+    Break,
+    Label(Name),
+    Return(Vec<Expr>),
+}
+
+/// The name in a function statement, as `a.b.c:m`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FuncName {
+    pub path: Vec<Name>,
+    pub method: Option<Name>,
+}
+
+/// A function body. The implicit `self` of methods is not in `params`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Function {
+    pub params: Vec<Name>,
+    pub is_vararg: bool,
+    pub body: Block,
+    /// The lines where the definition starts and ends, as kept in the
+    /// debug information.
+    pub line: i32,
+    pub lastline: i32,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Name {
+    pub name: String,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExprKind {
+    Nil,
+    True,
+    False,
+    Vararg,
+    Int(i64),
+    Flt(f64),
+    String(Vec<u8>),
+    Function(Box<Function>),
+    Table(Vec<Field>),
+    /// A variable, local or global.
+    Name(String),
+    /// `obj.name`
+    Field { obj: Box<Expr>, name: Name },
+    /// `obj[key]`
+    Index { obj: Box<Expr>, key: Box<Expr> },
+    Call { func: Box<Expr>, args: Vec<Expr> },
+    /// `obj:name(args)`
+    Method { obj: Box<Expr>, name: Name, args: Vec<Expr> },
+    /// An expression in parentheses, which truncates it to one value.
+    Paren(Box<Expr>),
+    Binary { op: BinOp, lhs: Box<Expr>, rhs: Box<Expr> },
+    Unary { op: UnOp, expr: Box<Expr> },
+}
+
+/// A field in a table constructor.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Field {
+    /// `exp`
+    List(Expr),
+    /// `name = exp`
+    Named(Name, Expr),
+    /// `[exp] = exp`
+    Keyed(Expr, Expr),
+}
+
+/*
+** grep "ORDER OPR" if you change these enums
+*/
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BinOp {
+    Add, Sub, Mul, Mod, Pow, Div, IDiv,
+    BAnd, BOr, BXor, Shl, Shr,
+    Concat,
+    Eq, Lt, Le, Ne, Gt, Ge,
+    And, Or,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UnOp {
+    Minus, BNot, Not, Len,
+}
+
+impl BinOp {
+    /// The operator as written in the source.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BinOp::Add => "+", BinOp::Sub => "-", BinOp::Mul => "*", BinOp::Mod => "%",
+            BinOp::Pow => "^", BinOp::Div => "/", BinOp::IDiv => "//",
+            BinOp::BAnd => "&", BinOp::BOr => "|", BinOp::BXor => "~",
+            BinOp::Shl => "<<", BinOp::Shr => ">>", BinOp::Concat => "..",
+            BinOp::Eq => "==", BinOp::Lt => "<", BinOp::Le => "<=",
+            BinOp::Ne => "~=", BinOp::Gt => ">", BinOp::Ge => ">=",
+            BinOp::And => "and", BinOp::Or => "or",
+        }
+    }
+}
+
+impl UnOp {
+    /// The operator as written in the source.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UnOp::Minus => "-", UnOp::BNot => "~", UnOp::Not => "not", UnOp::Len => "#",
+        }
+    }
+}
+
+
+/// A syntax error. Its message reads as the one of the C core, as in
+/// `chunk:1: 'end' expected near <eof>`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyntaxError {
+    pub message: String,
+    /// Name of the chunk, as given to `parse_with_name`.
+    pub source: String,
+    pub line: i32,
+    /// What the error is near: the quoted text of the offending token,
+    /// or `<eof>`.
+    pub near: Option<String>,
+}
+
+/* mark in error messages for incomplete statements */
+const EOFMARK: &str = "<eof>";
+
+impl SyntaxError {
+    /// Whether the error was found at the end of the chunk, so that more
+    /// text could complete it; the interpreter reads another line then.
+    pub fn is_incomplete(&self) -> bool {
+        self.near.as_ref().is_some_and(|near| near == EOFMARK)
+    }
+}
+
+impl From<LexError> for SyntaxError {
+    fn from(e: LexError) -> SyntaxError {
+        SyntaxError { message: e.kind.to_string(), source: e.source, line: e.line, near: e.near }
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.source, self.line, self.message)?;
+        if let Some(ref near) = self.near {
+            write!(f, " near {}", near)?;
+        }
+        Ok(())
+    }
+}
+
+impl ::std::error::Error for SyntaxError {}
+
+
+/// Parses `source`, whose errors refer to it as `?`.
+pub fn parse(source: &[u8]) -> ::std::result::Result<Chunk, SyntaxError> {
+    parse_with_name(source, "?")
+}
+
+/// Parses `source`, whose errors refer to it as `name`.
+pub fn parse_with_name(source: &[u8], name: &str) -> ::std::result::Result<Chunk, SyntaxError> {
+    let lex = Lexer::with_name(source, name);
+    let t = Spanned { token: Token::Reserved(Reserved::Eos), span: 0..0, line: 1 };
+    let mut p = Parser {
+        lex, name: name.to_string(), t, lookahead: None, lastline: 1, lastend: 0, buff: String::new(),
+        comments: Vec::new(), nccalls: 0, fs: Vec::new(),
+        actvar: Vec::new(), gt: Vec::new(), label: Vec::new(),
+    };
+    p.mainfunc()
+}
+
+
+/* maximum number of local variables per function */
+const MAXVARS: usize = 200;
+
+/* maximum number of upvalues per function */
+const MAXUPVAL: usize = 255;
+
+/* maximum depth for nested C calls and syntactical nested non-terminals */
+const LUAI_MAXCCALLS: usize = 200;
+
+/* priority for unary operators */
+const UNARY_PRIORITY: u8 = 12;
+
+/* ORDER OPR */
+const PRIORITY: [(u8, u8); 21] = [  /* left, right */
+    (10, 10), (10, 10),  /* '+' '-' */
+    (11, 11), (11, 11),  /* '*' '%' */
+    (14, 13),  /* '^' (right associative) */
+    (11, 11), (11, 11),  /* '/' '//' */
+    (6, 6), (4, 4), (5, 5),  /* '&' '|' '~' */
+    (7, 7), (7, 7),  /* '<<' '>>' */
+    (9, 8),  /* '..' (right associative) */
+    (3, 3), (3, 3), (3, 3),  /* ==, <, <= */
+    (3, 3), (3, 3), (3, 3),  /* ~=, >, >= */
+    (2, 2), (1, 1),  /* and, or */
+];
+
+type Result<T> = ::std::result::Result<T, SyntaxError>;
+
+
+/* description of pending goto statements and label statements */
+#[derive(Clone)]
+struct Labeldesc {
+    name: String,  /* label identifier */
+    line: i32,  /* line where it appeared */
+    nactvar: usize,  /* local level where it appears in current block */
+}
+
+/* nodes for block list (list of active blocks) */
+struct BlockCnt {
+    firstlabel: usize,  /* index of first label in this block */
+    firstgoto: usize,  /* index of first pending goto in this block */
+    nactvar: usize,  /* # active locals outside the block */
+    isloop: bool,  /* true if 'block' is a loop */
+}
+
+/* state needed to check a function */
+struct FuncState {
+    linedefined: i32,
+    is_vararg: bool,
+    firstlocal: usize,  /* index of first local var (in Parser::actvar) */
+    nactvar: usize,  /* number of active local variables */
+    upvalues: Vec<String>,
+    bl: Vec<BlockCnt>,  /* chain of current blocks */
+}
+
+/* kinds of variables, as 'singlevaraux' finds them */
+#[derive(PartialEq)]
+enum VarKind {
+    Global,
+    Local,
+    Upval,
+}
+
+struct Parser<'a> {
+    lex: Lexer<'a>,
+    name: String,  /* chunk name for error messages */
+    t: Spanned,  /* current token */
+    lookahead: Option<Spanned>,  /* look ahead token */
+    lastline: i32,  /* line of last token 'consumed' */
+    lastend: usize,  /* where the last token 'consumed' ends */
+    buff: String,  /* text of the last token read, for 'txttoken' */
+    comments: Vec<Comment>,  /* comments read and not yet attached */
+    nccalls: usize,  /* nesting of syntactical non-terminals */
+    fs: Vec<FuncState>,  /* chain of functions being parsed */
+    /* dynamic structures used by the parser */
+    actvar: Vec<String>,  /* list of active local variables */
+    gt: Vec<Labeldesc>,  /* list of pending gotos */
+    label: Vec<Labeldesc>,  /* list of active labels */
+}
+
+impl<'a> Parser<'a> {
+    /*
+    ** {======================================================
+    ** Tokens
+    ** =======================================================
+    */
+
+    fn read(&mut self) -> Result<Spanned> {
+        let t = self.lex.lex()?;
+        self.buff = self.lex.buffer_text();
+        self.comments.extend(self.lex.take_comments());
+        Ok(t)
+    }
+
+    fn next(&mut self) -> Result<()> {
+        self.lastline = self.lex.line();
+        self.lastend = self.t.span.end;
+        self.t = match self.lookahead.take() {  /* is there a look-ahead token? */
+            Some(t) => t,  /* use this one */
+            None => self.read()?,  /* read next token */
+        };
+        Ok(())
+    }
+
+    fn lookahead(&mut self) -> Result<i32> {
+        debug_assert!(self.lookahead.is_none());
+        let t = self.read()?;
+        let code = t.token.code();
+        self.lookahead = Some(t);
+        Ok(code)
+    }
+
+    fn token(&self) -> i32 { self.t.token.code() }
+
+    fn linenumber(&self) -> i32 { self.lex.line() }
+
+    fn txttoken(&self, token: i32) -> String {
+        if token == Reserved::Name as i32 || token == Reserved::String as i32 ||
+           token == Reserved::Flt as i32 || token == Reserved::Int as i32 {
+            format!("'{}'", self.buff)
+        } else {
+            token2str(token)
+        }
+    }
+
+    fn error(&self, msg: String, token: i32) -> SyntaxError {
+        SyntaxError {
+            message: msg,
+            source: self.name.clone(),
+            line: self.linenumber(),
+            near: if token != 0 { Some(self.txttoken(token)) } else { None },
+        }
+    }
+
+    fn syntaxerror<T>(&self, msg: &str) -> Result<T> {
+        Err(self.error(msg.to_string(), self.token()))
+    }
+
+    /*
+    ** semantic error
+    */
+    fn semerror<T>(&self, msg: String) -> Result<T> {
+        Err(self.error(msg, 0))  /* remove "near <token>" from final message */
+    }
+
+    fn error_expected<T>(&self, token: i32) -> Result<T> {
+        self.syntaxerror(&format!("{} expected", token2str(token)))
+    }
+
+    fn errorlimit<T>(&self, linedefined: i32, limit: usize, what: &str) -> Result<T> {
+        let where_ = if linedefined == 0 { "main function".to_string() }
+                     else { format!("function at line {}", linedefined) };
+        self.syntaxerror(&format!("too many {} (limit is {}) in {}", what, limit, where_))
+    }
+
+    fn checklimit(&self, linedefined: i32, v: usize, l: usize, what: &str) -> Result<()> {
+        if v > l { self.errorlimit(linedefined, l, what) } else { Ok(()) }
+    }
+
+    fn testnext(&mut self, c: i32) -> Result<bool> {
+        if self.token() == c {
+            self.next()?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn check(&self, c: i32) -> Result<()> {
+        if self.token() != c { self.error_expected(c) } else { Ok(()) }
+    }
+
+    fn checknext(&mut self, c: i32) -> Result<()> {
+        self.check(c)?;
+        self.next()
+    }
+
+    fn check_condition(&self, c: bool, msg: &str) -> Result<()> {
+        if !c { self.syntaxerror(msg) } else { Ok(()) }
+    }
+
+    fn check_match(&mut self, what: i32, who: i32, where_: i32) -> Result<()> {
+        if !self.testnext(what)? {
+            if where_ == self.linenumber() {
+                return self.error_expected(what);
+            } else {
+                return self.syntaxerror(&format!("{} expected (to close {} at line {})",
+                                                 token2str(what), token2str(who), where_));
+            }
+        }
+        Ok(())
+    }
+
+    fn str_checkname(&mut self) -> Result<Name> {
+        self.check(Reserved::Name as i32)?;
+        let name = match self.t.token {
+            Token::Name(ref s) => s.clone(),
+            _ => unreachable!(),
+        };
+        let span = self.t.span.clone();
+        self.next()?;
+        Ok(Name { name, span })
+    }
+
+    /* comments read so far that start before 'pos' */
+    fn comments_before(&mut self, pos: usize) -> Vec<Comment> {
+        let n = self.comments.iter().take_while(|c| c.span.start < pos).count();
+        self.comments.drain(..n).collect()
+    }
+
+    /* }====================================================== */
+
+
+    /*
+    ** {======================================================
+    ** Variables and scopes
+    ** =======================================================
+    */
+
+    fn fs(&mut self) -> &mut FuncState { self.fs.last_mut().unwrap() }
+
+    fn new_localvar(&mut self, name: &str) -> Result<()> {
+        let (linedefined, firstlocal) = { let fs = self.fs(); (fs.linedefined, fs.firstlocal) };
+        self.checklimit(linedefined, self.actvar.len() + 1 - firstlocal, MAXVARS, "local variables")?;
+        self.actvar.push(name.to_string());
+        Ok(())
+    }
+
+    fn adjustlocalvars(&mut self, nvars: usize) {
+        self.fs().nactvar += nvars;
+    }
+
+    fn removevars(&mut self, tolevel: usize) {
+        let n = self.fs().nactvar - tolevel;
+        let len = self.actvar.len();
+        self.actvar.truncate(len - n);
+        self.fs().nactvar = tolevel;
+    }
+
+    fn getlocvar(&self, fs: usize, i: usize) -> &str {
+        &self.actvar[self.fs[fs].firstlocal + i]
+    }
+
+    fn searchupvalue(&self, fs: usize, name: &str) -> Option<usize> {
+        self.fs[fs].upvalues.iter().position(|n| n == name)
+    }
+
+    fn newupvalue(&mut self, fs: usize, name: &str) -> Result<()> {
+        let (linedefined, n) = (self.fs[fs].linedefined, self.fs[fs].upvalues.len());
+        self.checklimit(linedefined, n + 1, MAXUPVAL, "upvalues")?;
+        self.fs[fs].upvalues.push(name.to_string());
+        Ok(())
+    }
+
+    fn searchvar(&self, fs: usize, n: &str) -> bool {
+        (0..self.fs[fs].nactvar).rev().any(|i| self.getlocvar(fs, i) == n)
+    }
+
+    /*
+    ** Find variable with given name 'n'. If it is an upvalue, add this
+    ** upvalue into all intermediate functions.
+    */
+    fn singlevaraux(&mut self, fs: usize, n: &str) -> Result<VarKind> {
+        if self.searchvar(fs, n) {  /* look up locals at current level */
+            return Ok(VarKind::Local);
+        }
+        if self.searchupvalue(fs, n).is_some() {  /* try existing upvalues */
+            return Ok(VarKind::Upval);
+        }
+        if fs == 0 {  /* no more levels? */
+            return Ok(VarKind::Global);  /* default is global */
+        }
+        /* not found as local at current level; try upvalues */
+        if self.singlevaraux(fs - 1, n)? == VarKind::Global {  /* try upper levels */
+            return Ok(VarKind::Global);  /* not found; is a global */
+        }
+        /* else was LOCAL or UPVAL */
+        self.newupvalue(fs, n)?;  /* will be a new upvalue */
+        Ok(VarKind::Upval)
+    }
+
+    fn singlevar(&mut self) -> Result<Expr> {
+        let varname = self.str_checkname()?;
+        let fs = self.fs.len() - 1;
+        if self.singlevaraux(fs, &varname.name)? == VarKind::Global {  /* global name? */
+            /* get environment variable */
+            let kind = self.singlevaraux(fs, LUA_ENV)?;
+            debug_assert!(kind != VarKind::Global);  /* this one must exist */
+        }
+        Ok(Expr { kind: ExprKind::Name(varname.name), span: varname.span })
+    }
+
+    fn enterlevel(&mut self) -> Result<()> {
+        self.nccalls += 1;
+        let linedefined = self.fs().linedefined;
+        self.checklimit(linedefined, self.nccalls, LUAI_MAXCCALLS, "C levels")
+    }
+
+    fn leavelevel(&mut self) { self.nccalls -= 1; }
+
+    /*
+    ** Solves the goto at index 'g' to given 'label' and removes it
+    ** from the list of pending goto's.
+    */
+    fn closegoto(&mut self, g: usize, label: &Labeldesc) -> Result<()> {
+        let gt = self.gt[g].clone();
+        debug_assert!(gt.name == label.name);
+        if gt.nactvar < label.nactvar {
+            let vname = self.getlocvar(self.fs.len() - 1, gt.nactvar).to_string();
+            return self.semerror(format!("<goto {}> at line {} jumps into the scope of local '{}'",
+                                         gt.name, gt.line, vname));
+        }
+        self.gt.remove(g);  /* remove goto from pending list */
+        Ok(())
+    }
+
+    /*
+    ** try to close a goto with existing labels; this solves backward jumps
+    */
+    fn findlabel(&mut self, g: usize) -> Result<bool> {
+        let firstlabel = self.fs().bl.last().unwrap().firstlabel;
+        /* check labels in current block for a match */
+        for i in firstlabel..self.label.len() {
+            if self.label[i].name == self.gt[g].name {  /* correct label? */
+                let lb = self.label[i].clone();
+                self.closegoto(g, &lb)?;  /* close it */
+                return Ok(true);
+            }
+        }
+        Ok(false)  /* label not found; cannot close goto */
+    }
+
+    fn newlabelentry(l: &mut Vec<Labeldesc>, name: &str, line: i32, nactvar: usize) -> usize {
+        l.push(Labeldesc { name: name.to_string(), line, nactvar });
+        l.len() - 1
+    }
+
+    /*
+    ** check whether new label 'lb' matches any pending gotos in current
+    ** block; solves forward jumps
+    */
+    fn findgotos(&mut self, lb: usize) -> Result<()> {
+        let mut i = self.fs().bl.last().unwrap().firstgoto;
+        while i < self.gt.len() {
+            if self.gt[i].name == self.label[lb].name {
+                let lb = self.label[lb].clone();
+                self.closegoto(i, &lb)?;
+            } else {
+                i += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /*
+    ** export pending gotos to outer level, to check them against
+    ** outer labels; if the block being exited has upvalues, and
+    ** the goto exits the scope of any variable (which can be the
+    ** upvalue), close those variables being exited.
+    */
+    fn movegotosout(&mut self, bl: &BlockCnt) -> Result<()> {
+        let mut i = bl.firstgoto;
+        /* correct pending gotos to current block and try to close it
+           with visible labels */
+        while i < self.gt.len() {
+            if self.gt[i].nactvar > bl.nactvar {
+                self.gt[i].nactvar = bl.nactvar;
+            }
+            if !self.findlabel(i)? {
+                i += 1;  /* move to next one */
+            }
+        }
+        Ok(())
+    }
+
+    fn enterblock(&mut self, isloop: bool) {
+        let (firstlabel, firstgoto) = (self.label.len(), self.gt.len());
+        let fs = self.fs();
+        let nactvar = fs.nactvar;
+        fs.bl.push(BlockCnt { firstlabel, firstgoto, nactvar, isloop });
+    }
+
+    /*
+    ** create a label named 'break' to resolve break statements
+    */
+    fn breaklabel(&mut self) -> Result<()> {
+        let nactvar = self.fs().nactvar;
+        let l = Parser::newlabelentry(&mut self.label, "break", 0, nactvar);
+        self.findgotos(l)
+    }
+
+    /*
+    ** generates an error for an undefined 'goto'; choose appropriate
+    ** message when label name is a reserved word (which can only be 'break')
+    */
+    fn undefgoto<T>(&self, gt: &Labeldesc) -> Result<T> {
+        if Reserved::keyword(gt.name.as_bytes()).is_some() {
+            self.semerror(format!("<{}> at line {} not inside a loop", gt.name, gt.line))
+        } else {
+            self.semerror(format!("no visible label '{}' for <goto> at line {}", gt.name, gt.line))
+        }
+    }
+
+    fn leaveblock(&mut self) -> Result<()> {
+        if self.fs().bl.last().unwrap().isloop {
+            self.breaklabel()?;  /* close pending breaks */
+        }
+        let bl = self.fs().bl.pop().unwrap();
+        self.removevars(bl.nactvar);
+        self.label.truncate(bl.firstlabel);  /* remove local labels */
+        if !self.fs().bl.is_empty() {  /* inner block? */
+            self.movegotosout(&bl)?;  /* update pending gotos to outer block */
+        } else if bl.firstgoto < self.gt.len() {  /* pending gotos in outer block? */
+            let gt = self.gt[bl.firstgoto].clone();
+            return self.undefgoto(&gt);  /* error */
+        }
+        Ok(())
+    }
+
+    fn open_func(&mut self, linedefined: i32) {
+        let firstlocal = self.actvar.len();
+        self.fs.push(FuncState {
+            linedefined, is_vararg: false, firstlocal, nactvar: 0,
+            upvalues: Vec::new(), bl: Vec::new(),
+        });
+        self.enterblock(false);
+    }
+
+    fn close_func(&mut self) -> Result<()> {
+        self.leaveblock()?;
+        self.fs.pop();
+        Ok(())
+    }
+
+    /* }====================================================== */
+
+
+    /*============================================================*/
+    /* GRAMMAR RULES */
+    /*============================================================*/
+
+
+    /*
+    ** check whether current token is in the follow set of a block.
+    ** 'until' closes syntactical blocks, but do not close scope,
+    ** so it is handled in separate.
+    */
+    fn block_follow(&self, withuntil: bool) -> bool {
+        match Reserved::from_code(self.token()) {
+            Some(Reserved::Else) | Some(Reserved::Elseif) |
+            Some(Reserved::End) | Some(Reserved::Eos) => true,
+            Some(Reserved::Until) => withuntil,
+            _ => false,
+        }
+    }
+
+    fn statlist(&mut self) -> Result<Block> {
+        let mut stats = Vec::new();
+        self.statlist_into(&mut stats)?;
+        let start = self.t.span.start;
+        Ok(Block { stats, comments: self.comments_before(start) })
+    }
+
+    fn statlist_into(&mut self, stats: &mut Vec<Stat>) -> Result<()> {
+        /* statlist -> { stat [';'] } */
+        while !self.block_follow(true) {
+            if self.token() == Reserved::Return as i32 {
+                stats.extend(self.statement()?);
+                return Ok(());  /* 'return' must be last statement */
+            }
+            stats.extend(self.statement()?);
+        }
+        Ok(())
+    }
+
+    fn fieldsel(&mut self, v: Expr) -> Result<Expr> {
+        /* fieldsel -> ['.' | ':'] NAME */
+        self.next()?;  /* skip the dot or colon */
+        let name = self.str_checkname()?;
+        let span = v.span.start..self.lastend;
+        Ok(Expr { kind: ExprKind::Field { obj: Box::new(v), name }, span })
+    }
+
+    fn yindex(&mut self) -> Result<Expr> {
+        /* index -> '[' expr ']' */
+        self.next()?;  /* skip the '[' */
+        let v = self.expr()?;
+        self.checknext(']' as i32)?;
+        Ok(v)
+    }
+
+    /*
+    ** {======================================================================
+    ** Rules for Constructors
+    ** =======================================================================
+    */
+
+    fn recfield(&mut self) -> Result<Field> {
+        /* recfield -> (NAME | '['exp1']') = exp1 */
+        if self.token() == Reserved::Name as i32 {
+            let name = self.str_checkname()?;
+            self.checknext('=' as i32)?;
+            Ok(Field::Named(name, self.expr()?))
+        } else {  /* ls->t.token == '[' */
+            let key = self.yindex()?;
+            self.checknext('=' as i32)?;
+            Ok(Field::Keyed(key, self.expr()?))
+        }
+    }
+
+    fn field(&mut self) -> Result<Field> {
+        /* field -> listfield | recfield */
+        match self.token() {
+            t if t == Reserved::Name as i32 => {  /* may be 'listfield' or 'recfield' */
+                if self.lookahead()? != '=' as i32 {  /* expression? */
+                    Ok(Field::List(self.expr()?))
+                } else {
+                    self.recfield()
+                }
+            }
+            t if t == '[' as i32 => self.recfield(),
+            _ => Ok(Field::List(self.expr()?)),
+        }
+    }
+
+    fn constructor(&mut self) -> Result<Expr> {
+        /* constructor -> '{' [ field { sep field } [sep] ] '}'
+           sep -> ',' | ';' */
+        let line = self.linenumber();
+        let start = self.t.span.start;
+        let mut fields = Vec::new();
+        self.checknext('{' as i32)?;
+        loop {
+            if self.token() == '}' as i32 { break; }
+            fields.push(self.field()?);
+            if !(self.testnext(',' as i32)? || self.testnext(';' as i32)?) { break; }
+        }
+        self.check_match('}' as i32, '{' as i32, line)?;
+        Ok(Expr { kind: ExprKind::Table(fields), span: start..self.lastend })
+    }
+
+    /* }====================================================================== */
+
+
+    fn parlist(&mut self) -> Result<Vec<Name>> {
+        /* parlist -> [ param { ',' param } ] */
+        let mut params = Vec::new();
+        self.fs().is_vararg = false;
+        if self.token() != ')' as i32 {  /* is 'parlist' not empty? */
+            loop {
+                match Reserved::from_code(self.token()) {
+                    Some(Reserved::Name) => {  /* param -> NAME */
+                        let name = self.str_checkname()?;
+                        self.new_localvar(&name.name)?;
+                        params.push(name);
+                    }
+                    Some(Reserved::Dots) => {  /* param -> '...' */
+                        self.next()?;
+                        self.fs().is_vararg = true;
+                    }
+                    _ => return self.syntaxerror("<name> or '...' expected"),
+                }
+                if self.fs().is_vararg || !self.testnext(',' as i32)? { break; }
+            }
+        }
+        self.adjustlocalvars(params.len());
+        Ok(params)
+    }
+
+    fn body(&mut self, ismethod: bool, line: i32, start: usize) -> Result<Function> {
+        /* body ->  '(' parlist ')' block END */
+        self.open_func(line);
+        self.checknext('(' as i32)?;
+        if ismethod {
+            self.new_localvar("self")?;  /* create 'self' parameter */
+            self.adjustlocalvars(1);
+        }
+        let params = self.parlist()?;
+        self.checknext(')' as i32)?;
+        let is_vararg = self.fs().is_vararg;
+        let body = self.statlist()?;
+        let lastline = self.linenumber();
+        self.check_match(Reserved::End as i32, Reserved::Function as i32, line)?;
+        self.close_func()?;
+        Ok(Function { params, is_vararg, body, line, lastline, span: start..self.lastend })
+    }
+
+    fn explist(&mut self) -> Result<Vec<Expr>> {
+        /* explist -> expr { ',' expr } */
+        let mut l = vec![self.expr()?];
+        while self.testnext(',' as i32)? {
+            l.push(self.expr()?);
+        }
+        Ok(l)
+    }
+
+    fn funcargs(&mut self, line: i32) -> Result<Vec<Expr>> {
+        match self.token() {
+            t if t == '(' as i32 => {  /* funcargs -> '(' [ explist ] ')' */
+                self.next()?;
+                let args = if self.token() == ')' as i32 { Vec::new() }  /* arg list is empty? */
+                           else { self.explist()? };
+                self.check_match(')' as i32, '(' as i32, line)?;
+                Ok(args)
+            }
+            t if t == '{' as i32 => {  /* funcargs -> constructor */
+                Ok(vec![self.constructor()?])
+            }
+            t if t == Reserved::String as i32 => {  /* funcargs -> STRING */
+                let s = self.simpleexp()?;
+                Ok(vec![s])
+            }
+            _ => self.syntaxerror("function arguments expected"),
+        }
+    }
+
+    /*
+    ** {======================================================================
+    ** Expression parsing
+    ** =======================================================================
+    */
+
+    fn primaryexp(&mut self) -> Result<Expr> {
+        /* primaryexp -> NAME | '(' expr ')' */
+        match self.token() {
+            t if t == '(' as i32 => {
+                let line = self.linenumber();
+                let start = self.t.span.start;
+                self.next()?;
+                let v = self.expr()?;
+                self.check_match(')' as i32, '(' as i32, line)?;
+                Ok(Expr { kind: ExprKind::Paren(Box::new(v)), span: start..self.lastend })
+            }
+            t if t == Reserved::Name as i32 => self.singlevar(),
+            _ => self.syntaxerror("unexpected symbol"),
+        }
+    }
+
+    fn suffixedexp(&mut self) -> Result<Expr> {
+        /* suffixedexp ->
+             primaryexp { '.' NAME | '[' exp ']' | ':' NAME funcargs | funcargs } */
+        let line = self.linenumber();
+        let mut v = self.primaryexp()?;
+        loop {
+            let start = v.span.start;
+            v = match self.token() {
+                t if t == '.' as i32 => self.fieldsel(v)?,  /* fieldsel */
+                t if t == '[' as i32 => {  /* '[' exp1 ']' */
+                    let key = self.yindex()?;
+                    Expr { kind: ExprKind::Index { obj: Box::new(v), key: Box::new(key) },
+                           span: start..self.lastend }
+                }
+                t if t == ':' as i32 => {  /* ':' NAME funcargs */
+                    self.next()?;
+                    let name = self.str_checkname()?;
+                    let args = self.funcargs(line)?;
+                    Expr { kind: ExprKind::Method { obj: Box::new(v), name, args },
+                           span: start..self.lastend }
+                }
+                t if t == '(' as i32 || t == '{' as i32 || t == Reserved::String as i32 => {
+                    let args = self.funcargs(line)?;
+                    Expr { kind: ExprKind::Call { func: Box::new(v), args },
+                           span: start..self.lastend }
+                }
+                _ => return Ok(v),
+            };
+        }
+    }
+
+    fn simpleexp(&mut self) -> Result<Expr> {
+        /* simpleexp -> FLT | INT | STRING | NIL | TRUE | FALSE | ... |
+                        constructor | FUNCTION body | suffixedexp */
+        let start = self.t.span.start;
+        let kind = match self.t.token {
+            Token::Flt(n) => ExprKind::Flt(n),
+            Token::Int(i) => ExprKind::Int(i),
+            Token::String(ref s) => ExprKind::String(s.clone()),
+            Token::Reserved(Reserved::Nil) => ExprKind::Nil,
+            Token::Reserved(Reserved::True) => ExprKind::True,
+            Token::Reserved(Reserved::False) => ExprKind::False,
+            Token::Reserved(Reserved::Dots) => {  /* vararg */
+                let is_vararg = self.fs().is_vararg;
+                self.check_condition(is_vararg, "cannot use '...' outside a vararg function")?;
+                ExprKind::Vararg
+            }
+            Token::Char(b'{') => {  /* constructor */
+                return self.constructor();
+            }
+            Token::Reserved(Reserved::Function) => {
+                self.next()?;
+                let line = self.linenumber();
+                let f = self.body(false, line, start)?;
+                return Ok(Expr { kind: ExprKind::Function(Box::new(f)), span: start..self.lastend });
+            }
+            _ => {
+                return self.suffixedexp();
+            }
+        };
+        self.next()?;
+        Ok(Expr { kind, span: start..self.lastend })
+    }
+
+    /*
+    ** subexpr -> (simpleexp | unop subexpr) { binop subexpr }
+    ** where 'binop' is any binary operator with a priority higher than 'limit'
+    */
+    fn subexpr(&mut self, limit: u8) -> Result<(Expr, Option<BinOp>)> {
+        self.enterlevel()?;
+        let start = self.t.span.start;
+        let mut v = match getunopr(self.token()) {
+            Some(op) => {
+                self.next()?;
+                let (e, _) = self.subexpr(UNARY_PRIORITY)?;
+                Expr { kind: ExprKind::Unary { op, expr: Box::new(e) }, span: start..self.lastend }
+            }
+            None => self.simpleexp()?,
+        };
+        /* expand while operators have priorities higher than 'limit' */
+        let mut op = getbinopr(self.token());
+        while let Some(o) = op {
+            if PRIORITY[o as usize].0 <= limit { break; }
+            self.next()?;
+            /* read sub-expression with higher priority */
+            let (v2, nextop) = self.subexpr(PRIORITY[o as usize].1)?;
+            v = Expr { kind: ExprKind::Binary { op: o, lhs: Box::new(v), rhs: Box::new(v2) },
+                       span: start..self.lastend };
+            op = nextop;
+        }
+        self.leavelevel();
+        Ok((v, op))  /* return first untreated operator */
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        Ok(self.subexpr(0)?.0)
+    }
+
+    /* }==================================================================== */
+
+
+    /*
+    ** {======================================================================
+    ** Rules for Statements
+    ** =======================================================================
+    */
+
+    fn block(&mut self) -> Result<Block> {
+        /* block -> statlist */
+        self.enterblock(false);
+        let b = self.statlist()?;
+        self.leaveblock()?;
+        Ok(b)
+    }
+
+    /*
+    ** Parse and check a multiple assignment; 'lh' has the first target.
+    */
+    fn assignment(&mut self, lh: Expr) -> Result<StatKind> {
+        let mut targets = vec![lh];
+        loop {
+            let isvar = matches!(targets.last().unwrap().kind,
+                                 ExprKind::Name(_) | ExprKind::Field { .. } | ExprKind::Index { .. });
+            self.check_condition(isvar, "syntax error")?;
+            if self.testnext(',' as i32)? {  /* assignment -> ',' suffixedexp assignment */
+                let nv = self.suffixedexp()?;
+                let (linedefined, nvars) = (self.fs().linedefined, targets.len());
+                self.checklimit(linedefined, nvars + self.nccalls, LUAI_MAXCCALLS, "C levels")?;
+                targets.push(nv);
+            } else {  /* assignment -> '=' explist */
+                self.checknext('=' as i32)?;
+                let exprs = self.explist()?;
+                return Ok(StatKind::Assign { targets, exprs });
+            }
+        }
+    }
+
+    fn cond(&mut self) -> Result<Expr> {
+        /* cond -> exp */
+        self.expr()
+    }
+
+    fn gotostat(&mut self, line: i32) -> Result<StatKind> {
+        let (label, kind) = if self.testnext(Reserved::Goto as i32)? {
+            let name = self.str_checkname()?;
+            (name.name.clone(), StatKind::Goto(name))
+        } else {
+            self.next()?;  /* skip break */
+            ("break".to_string(), StatKind::Break)
+        };
+        let g = Parser::newlabelentry(&mut self.gt, &label, line, self.fs.last().unwrap().nactvar);
+        self.findlabel(g)?;  /* close it if label already defined */
+        Ok(kind)
+    }
+
+    /* check for repeated labels on the same block */
+    fn checkrepeated(&self, label: &str) -> Result<()> {
+        let firstlabel = self.fs.last().unwrap().bl.last().unwrap().firstlabel;
+        for lb in &self.label[firstlabel..] {
+            if lb.name == label {
+                return self.semerror(format!("label '{}' already defined on line {}",
+                                             label, lb.line));
+            }
+        }
+        Ok(())
+    }
+
+    /* skip no-op statements */
+    fn skipnoopstat(&mut self, stats: &mut Vec<Stat>) -> Result<()> {
+        while self.token() == ';' as i32 || self.token() == Reserved::DbColon as i32 {
+            stats.extend(self.statement()?);
+        }
+        Ok(())
+    }
+
+    fn labelstat(&mut self, name: Name, line: i32, mut stat: Stat,
+                 stats: &mut Vec<Stat>) -> Result<()> {
+        /* label -> '::' NAME '::' */
+        self.checkrepeated(&name.name)?;  /* check for repeated labels */
+        self.checknext(Reserved::DbColon as i32)?;
+        /* create new entry for this label */
+        let nactvar = self.fs().nactvar;
+        let l = Parser::newlabelentry(&mut self.label, &name.name, line, nactvar);
+        stat.span.end = self.lastend;
+        stat.comments.extend(self.trailing_comments(stat.span.end));
+        stat.kind = StatKind::Label(name);
+        stats.push(stat);
+        self.skipnoopstat(stats)?;  /* skip other no-op statements */
+        if self.block_follow(false) {  /* label is last no-op statement in the block? */
+            /* assume that locals are already out of scope */
+            self.label[l].nactvar = self.fs().bl.last().unwrap().nactvar;
+        }
+        self.findgotos(l)
+    }
+
+    fn whilestat(&mut self, line: i32) -> Result<StatKind> {
+        /* whilestat -> WHILE cond DO block END */
+        self.next()?;  /* skip WHILE */
+        let cond = self.cond()?;
+        self.enterblock(true);
+        self.checknext(Reserved::Do as i32)?;
+        let body = self.block()?;
+        self.check_match(Reserved::End as i32, Reserved::While as i32, line)?;
+        self.leaveblock()?;
+        Ok(StatKind::While { cond, body })
+    }
+
+    fn repeatstat(&mut self, line: i32) -> Result<StatKind> {
+        /* repeatstat -> REPEAT block UNTIL cond */
+        self.enterblock(true);  /* loop block */
+        self.enterblock(false);  /* scope block */
+        self.next()?;  /* skip REPEAT */
+        let body = self.statlist()?;
+        self.check_match(Reserved::Until as i32, Reserved::Repeat as i32, line)?;
+        let cond = self.cond()?;  /* read condition (inside scope block) */
+        self.leaveblock()?;  /* finish scope */
+        self.leaveblock()?;  /* finish loop */
+        Ok(StatKind::Repeat { body, cond })
+    }
+
+    fn forbody(&mut self, nvars: usize) -> Result<Block> {
+        /* forbody -> DO block */
+        self.adjustlocalvars(3);  /* control variables */
+        self.checknext(Reserved::Do as i32)?;
+        self.enterblock(false);  /* scope for declared variables */
+        self.adjustlocalvars(nvars);
+        let b = self.block()?;
+        self.leaveblock()?;  /* end of scope for declared variables */
+        Ok(b)
+    }
+
+    fn fornum(&mut self, var: Name) -> Result<StatKind> {
+        /* fornum -> NAME = exp1,exp1[,exp1] forbody */
+        self.new_localvar("(for index)")?;
+        self.new_localvar("(for limit)")?;
+        self.new_localvar("(for step)")?;
+        self.new_localvar(&var.name)?;
+        self.checknext('=' as i32)?;
+        let start = self.expr()?;  /* initial value */
+        self.checknext(',' as i32)?;
+        let limit = self.expr()?;
+        let step = if self.testnext(',' as i32)? { Some(self.expr()?) }  /* optional step */
+                   else { None };
+        let body = self.forbody(1)?;
+        Ok(StatKind::NumericFor { var, start, limit, step, body })
+    }
+
+    fn forlist(&mut self, indexname: Name) -> Result<StatKind> {
+        /* forlist -> NAME {,NAME} IN explist forbody */
+        /* create control variables */
+        self.new_localvar("(for generator)")?;
+        self.new_localvar("(for state)")?;
+        self.new_localvar("(for control)")?;
+        /* create declared variables */
+        self.new_localvar(&indexname.name)?;
+        let mut names = vec![indexname];
+        while self.testnext(',' as i32)? {
+            let name = self.str_checkname()?;
+            self.new_localvar(&name.name)?;
+            names.push(name);
+        }
+        self.checknext(Reserved::In as i32)?;
+        let exprs = self.explist()?;
+        let body = self.forbody(names.len())?;
+        Ok(StatKind::GenericFor { names, exprs, body })
+    }
+
+    fn forstat(&mut self, line: i32) -> Result<StatKind> {
+        /* forstat -> FOR (fornum | forlist) END */
+        self.enterblock(true);  /* scope for loop and control variables */
+        self.next()?;  /* skip 'for' */
+        let varname = self.str_checkname()?;  /* first variable name */
+        let kind = match self.token() {
+            t if t == '=' as i32 => self.fornum(varname)?,
+            t if t == ',' as i32 || t == Reserved::In as i32 => self.forlist(varname)?,
+            _ => return self.syntaxerror("'=' or 'in' expected"),
+        };
+        self.check_match(Reserved::End as i32, Reserved::For as i32, line)?;
+        self.leaveblock()?;  /* loop scope ('break' jumps to this point) */
+        Ok(kind)
+    }
+
+    fn test_then_block(&mut self) -> Result<(Expr, Block)> {
+        /* test_then_block -> [IF | ELSEIF] cond THEN block */
+        self.next()?;  /* skip IF or ELSEIF */
+        let cond = self.expr()?;  /* read condition */
+        self.checknext(Reserved::Then as i32)?;
+        let mut stats = Vec::new();
+        self.enterblock(false);
+        if self.token() == Reserved::Break as i32 || self.token() == Reserved::Goto as i32 {
+            stats.extend(self.statement()?);  /* handle goto/break */
+            self.skipnoopstat(&mut stats)?;  /* skip other no-op statements */
+            if self.block_follow(false) {  /* 'goto' is the entire block? */
+                let start = self.t.span.start;
+                let comments = self.comments_before(start);
+                self.leaveblock()?;
+                return Ok((cond, Block { stats, comments }));  /* and that is it */
+            }
+        }
+        self.statlist_into(&mut stats)?;  /* 'then' part */
+        let start = self.t.span.start;
+        let comments = self.comments_before(start);
+        self.leaveblock()?;
+        Ok((cond, Block { stats, comments }))
+    }
+
+    fn ifstat(&mut self, line: i32) -> Result<StatKind> {
+        /* ifstat -> IF cond THEN block {ELSEIF cond THEN block} [ELSE block] END */
+        let mut conds = vec![self.test_then_block()?];  /* IF cond THEN block */
+        while self.token() == Reserved::Elseif as i32 {
+            conds.push(self.test_then_block()?);  /* ELSEIF cond THEN block */
+        }
+        let else_block = if self.testnext(Reserved::Else as i32)? {
+            Some(self.block()?)  /* 'else' part */
+        } else {
+            None
+        };
+        self.check_match(Reserved::End as i32, Reserved::If as i32, line)?;
+        Ok(StatKind::If { conds, else_block })
+    }
+
+    fn localfunc(&mut self, start: usize) -> Result<StatKind> {
+        let name = self.str_checkname()?;
+        self.new_localvar(&name.name)?;  /* new local variable */
+        self.adjustlocalvars(1);  /* enter its scope */
+        let line = self.linenumber();
+        let func = self.body(false, line, start)?;  /* function created in next register */
+        Ok(StatKind::LocalFunction { name, func })
+    }
+
+    fn localstat(&mut self) -> Result<StatKind> {
+        /* stat -> LOCAL NAME {',' NAME} ['=' explist] */
+        let mut names = Vec::new();
+        loop {
+            let name = self.str_checkname()?;
+            self.new_localvar(&name.name)?;
+            names.push(name);
+            if !self.testnext(',' as i32)? { break; }
+        }
+        let exprs = if self.testnext('=' as i32)? { self.explist()? }
+                    else { Vec::new() };
+        self.adjustlocalvars(names.len());
+        Ok(StatKind::Local { names, exprs })
+    }
+
+    fn funcname(&mut self) -> Result<FuncName> {
+        /* funcname -> NAME {fieldsel} [':' NAME] */
+        let first = self.t.span.clone();
+        let v = self.singlevar()?;
+        let name = match v.kind { ExprKind::Name(name) => name, _ => unreachable!() };
+        let mut path = vec![Name { name, span: first }];
+        let mut method = None;
+        while self.testnext('.' as i32)? {
+            path.push(self.str_checkname()?);
+        }
+        if self.testnext(':' as i32)? {
+            method = Some(self.str_checkname()?);
+        }
+        Ok(FuncName { path, method })
+    }
+
+    fn funcstat(&mut self, line: i32, start: usize) -> Result<StatKind> {
+        /* funcstat -> FUNCTION funcname body */
+        self.next()?;  /* skip FUNCTION */
+        let name = self.funcname()?;
+        let func = self.body(name.method.is_some(), line, start)?;
+        Ok(StatKind::Function { name, func })
+    }
+
+    fn exprstat(&mut self) -> Result<StatKind> {
+        /* stat -> func | assignment */
+        let v = self.suffixedexp()?;
+        if self.token() == '=' as i32 || self.token() == ',' as i32 {  /* stat -> assignment ? */
+            self.assignment(v)
+        } else {  /* stat -> func */
+            let iscall = matches!(v.kind, ExprKind::Call { .. } | ExprKind::Method { .. });
+            self.check_condition(iscall, "syntax error")?;
+            Ok(StatKind::Call(v))
+        }
+    }
+
+    fn retstat(&mut self) -> Result<StatKind> {
+        /* stat -> RETURN [explist] [';'] */
+        let exprs = if self.block_follow(true) || self.token() == ';' as i32 {
+            Vec::new()  /* return no values */
+        } else {
+            self.explist()?  /* optional return values */
+        };
+        self.testnext(';' as i32)?;  /* skip optional semicolon */
+        Ok(StatKind::Return(exprs))
+    }
+
+    /*
+    ** Parse a statement; empty statements give nothing, and labels
+    ** followed by no-op statements give these too.
+    */
+    fn statement(&mut self) -> Result<Vec<Stat>> {
+        let line = self.linenumber();  /* may be needed for error messages */
+        let (start, startline) = (self.t.span.start, self.t.line);
+        let mut stats = Vec::new();
+        self.enterlevel()?;
+        if self.token() == ';' as i32 {  /* stat -> ';' (empty statement) */
+            self.next()?;  /* skip ';' */
+            self.leavelevel();
+            return Ok(stats);
+        }
+        let mut comments = self.comments_before(start);
+        let kind = match Reserved::from_code(self.token()) {
+            Some(Reserved::If) => self.ifstat(line)?,  /* stat -> ifstat */
+            Some(Reserved::While) => self.whilestat(line)?,  /* stat -> whilestat */
+            Some(Reserved::Do) => {  /* stat -> DO block END */
+                self.next()?;  /* skip DO */
+                let b = self.block()?;
+                self.check_match(Reserved::End as i32, Reserved::Do as i32, line)?;
+                StatKind::Do(b)
+            }
+            Some(Reserved::For) => self.forstat(line)?,  /* stat -> forstat */
+            Some(Reserved::Repeat) => self.repeatstat(line)?,  /* stat -> repeatstat */
+            Some(Reserved::Function) => self.funcstat(line, start)?,  /* stat -> funcstat */
+            Some(Reserved::Local) => {  /* stat -> localstat */
+                self.next()?;  /* skip LOCAL */
+                let fstart = self.t.span.start;
+                if self.testnext(Reserved::Function as i32)? {  /* local function? */
+                    self.localfunc(fstart)?
+                } else {
+                    self.localstat()?
+                }
+            }
+            Some(Reserved::DbColon) => {  /* stat -> label */
+                self.next()?;  /* skip double colon */
+                let name = self.str_checkname()?;
+                let stat = Stat { kind: StatKind::Break, span: start..start, line: startline, comments };
+                self.labelstat(name, line, stat, &mut stats)?;
+                self.leavelevel();
+                return Ok(stats);
+            }
+            Some(Reserved::Return) => {  /* stat -> retstat */
+                self.next()?;  /* skip RETURN */
+                self.retstat()?
+            }
+            Some(Reserved::Break) |  /* stat -> breakstat */
+            Some(Reserved::Goto) => {  /* stat -> 'goto' NAME */
+                self.gotostat(line)?
+            }
+            _ => self.exprstat()?,  /* stat -> func | assignment */
+        };
+        let span = start..self.lastend;
+        comments.extend(self.trailing_comments(span.end));
+        stats.push(Stat { kind, span, line: startline, comments });
+        self.leavelevel();
+        Ok(stats)
+    }
+
+    /* comments left inside a statement ending at 'end', and those after
+       it on its last line */
+    fn trailing_comments(&mut self, end: usize) -> Vec<Comment> {
+        let mut comments = self.comments_before(end);
+        let n = self.comments.iter()
+                    .take_while(|c| c.line == self.lastline && c.span.start < self.t.span.start)
+                    .count();
+        comments.extend(self.comments.drain(..n));
+        comments
+    }
+
+    /* }====================================================================== */
+
+
+    /*
+    ** compiles the main function, which is a regular vararg function with an
+    ** upvalue named LUA_ENV
+    */
+    fn mainfunc(&mut self) -> Result<Chunk> {
+        self.open_func(0);
+        self.fs().is_vararg = true;  /* main function is always declared vararg */
+        self.newupvalue(0, LUA_ENV)?;  /* ...set environment upvalue */
+        self.next()?;  /* read first token */
+        let block = self.statlist()?;  /* parse main body */
+        self.check(Reserved::Eos as i32)?;
+        self.close_func()?;
+        Ok(Chunk { block })
+    }
+}
+
+
+/* as 'luaX_token2str' */
+fn token2str(token: i32) -> String {
+    if token < FIRST_RESERVED {  /* single-byte symbols? */
+        Token::Char(token as u8).to_string()
+    } else {  /* names, strings, and numerals are shown by their kind */
+        let r = Reserved::from_code(token).unwrap();
+        if r as i32 <= Reserved::Eos as i32 { Token::Reserved(r).to_string() }
+        else { r.as_str().to_string() }
+    }
+}
+
+fn getunopr(op: i32) -> Option<UnOp> {
+    match op {
+        op if op == Reserved::Not as i32 => Some(UnOp::Not),
+        op if op == '-' as i32 => Some(UnOp::Minus),
+        op if op == '~' as i32 => Some(UnOp::BNot),
+        op if op == '#' as i32 => Some(UnOp::Len),
+        _ => None,
+    }
+}
+
+fn getbinopr(op: i32) -> Option<BinOp> {
+    if op < FIRST_RESERVED {
+        return match op as u8 {
+            b'+' => Some(BinOp::Add),
+            b'-' => Some(BinOp::Sub),
+            b'*' => Some(BinOp::Mul),
+            b'%' => Some(BinOp::Mod),
+            b'^' => Some(BinOp::Pow),
+            b'/' => Some(BinOp::Div),
+            b'&' => Some(BinOp::BAnd),
+            b'|' => Some(BinOp::BOr),
+            b'~' => Some(BinOp::BXor),
+            b'<' => Some(BinOp::Lt),
+            b'>' => Some(BinOp::Gt),
+            _ => None,
+        };
+    }
+    match Reserved::from_code(op) {
+        Some(Reserved::IDiv) => Some(BinOp::IDiv),
+        Some(Reserved::Shl) => Some(BinOp::Shl),
+        Some(Reserved::Shr) => Some(BinOp::Shr),
+        Some(Reserved::Concat) => Some(BinOp::Concat),
+        Some(Reserved::Ne) => Some(BinOp::Ne),
+        Some(Reserved::Eq) => Some(BinOp::Eq),
+        Some(Reserved::Le) => Some(BinOp::Le),
+        Some(Reserved::Ge) => Some(BinOp::Ge),
+        Some(Reserved::And) => Some(BinOp::And),
+        Some(Reserved::Or) => Some(BinOp::Or),
+        _ => None,
+    }
+}
@@ -75,7 +75,7 @@ static mut global_l: *mut ffi::lua::lua_State = 0 as *mut _;
 /*
 ** Hook set by signal function to stop the interpreter.
 */
-extern "C" fn stop(l: *mut ffi::lua::lua_State, _: *mut ffi::lua::lua_Debug) {
+extern "C-unwind" fn stop(l: *mut ffi::lua::lua_State, _: *mut ffi::lua::lua_Debug) {
     unsafe { ffi::lua::lua_sethook(l, None, 0, 0); }  /* reset hook */
     let s = std::ffi::CString::new("interrupted!").unwrap();
     unsafe { ffi::lauxlib::luaL_error(l, s.as_ptr()); }
@@ -169,7 +169,7 @@ fn msghandler_(l: *mut ffi::lua::lua_State) -> libc::c_int {
     1  /* return the traceback */
 }
 
-unsafe extern "C" fn msghandler(l: *mut ffi::lua::lua_State) -> libc::c_int {
+unsafe extern "C-unwind" fn msghandler(l: *mut ffi::lua::lua_State) -> libc::c_int {
     msghandler_(l)
 }
 
@@ -283,23 +283,18 @@ fn get_prompt(l: *mut ffi::lua::lua_State, firstline: bool) -> String {
     }
 }
 
-/* mark in error messages for incomplete statements */
-const EOFMARK: &'static str = "<eof>";
-
-
 /*
-** Check whether 'status' signals a syntax error and the error
-** message at the top of the stack ends with the above mark for
-** incomplete statements.
+** Check whether 'status' signals a syntax error and 'chunk' is an
+** incomplete statement, that is, it fails to parse at its end. The
+** error message is at the top of the stack.
 */
-fn incomplete(l: *mut ffi::lua::lua_State, status: libc::c_int) -> bool {
+fn incomplete(l: *mut ffi::lua::lua_State, status: libc::c_int, chunk: &[u8]) -> bool {
     if status == ffi::lua::LUA_ERRSYNTAX {
-        let mut lmsg: libc::size_t = 0;
-        let msg = unsafe { ffi::lua::lua_tolstring(l, -1, &mut lmsg) };
-        let s = unsafe { std::ffi::CStr::from_ptr(msg) };
-        if s.to_str().unwrap().ends_with(EOFMARK) {
-            unsafe { ffi::lua::lua_pop(l, 1); }
-            return true;
+        if let Err(e) = lua_rs::ast::parse_with_name(chunk, "stdin") {
+            if e.is_incomplete() {
+                unsafe { ffi::lua::lua_pop(l, 1); }
+                return true;
+            }
         }
     }
     false  /* else... */
@@ -360,7 +355,8 @@ fn multiline(l: *mut ffi::lua::lua_State) -> libc::c_int {
         let line = unsafe { ffi::lua::lua_tolstring(l, 1, &mut len) };  /* get what it has */
         let s = std::ffi::CString::new("=stdin").unwrap();
         let status = unsafe { ffi::lauxlib::luaL_loadbuffer(l, line, len, s.as_ptr()) };  /* try it */
-        if !incomplete(l, status) || !pushline(l, false) {
+        let chunk = unsafe { std::slice::from_raw_parts(line as *const u8, len) };
+        if !incomplete(l, status, chunk) || !pushline(l, false) {
             let s = unsafe { std::ffi::CStr::from_ptr(line) };
             saveline(s.to_str().unwrap());  /* keep history */
             return status;  /* cannot or should not try to add continuation line */
@@ -617,14 +613,14 @@ fn handle_luainit(l: *mut ffi::lua::lua_State) -> libc::c_int {
 }
 
 
-#[cfg(debug_assertions)]
+#[cfg(ltests)]
 fn openlibs(l: *mut ffi::lua::lua_State) {
     unsafe { ffi::lualib::luaL_openlibs(l); }
-    extern { fn luaB_opentests(l: *mut ffi::lua::lua_State) -> libc::c_int; }
+    extern "C-unwind" { fn luaB_opentests(l: *mut ffi::lua::lua_State) -> libc::c_int; }
     let s = std::ffi::CString::new("T").unwrap();
     unsafe { ffi::lauxlib::luaL_requiref(l, s.as_ptr(), Some(luaB_opentests), 1); }
 }
-#[cfg(not(debug_assertions))]
+#[cfg(not(ltests))]
 fn openlibs(l: *mut ffi::lua::lua_State) {
     unsafe { ffi::lualib::luaL_openlibs(l); }
 }
@@ -681,12 +677,12 @@ fn pmain_(l: *mut ffi::lua::lua_State) -> libc::c_int {
     1
 }
 
-unsafe extern "C" fn pmain(l: *mut ffi::lua::lua_State) -> libc::c_int {
+unsafe extern "C-unwind" fn pmain(l: *mut ffi::lua::lua_State) -> libc::c_int {
     pmain_(l)
 }
 
 
-#[cfg(debug_assertions)]
+#[cfg(ltests)]
 fn newstate() -> *mut ffi::lua::lua_State {
     #[repr(C)]
     struct Memcontrol {
@@ -697,15 +693,15 @@ fn newstate() -> *mut ffi::lua::lua_State {
       objcount: [libc::c_ulong; ffi::lua::LUA_NUMTAGS as usize],
     }
 
-    extern { static mut l_memcontrol: Memcontrol; }
+    extern "C-unwind" { static mut l_memcontrol: Memcontrol; }
 
-    extern {
+    extern "C-unwind" {
         fn debug_realloc(ud: *mut libc::c_void, ptr: *mut libc::c_void,
                          osize: libc::size_t, osize: libc::size_t) -> *mut libc::c_void;
     }
     unsafe { ffi::lua::lua_newstate(Some(debug_realloc), &mut l_memcontrol as *mut Memcontrol as *mut _) }
 }
-#[cfg(not(debug_assertions))]
+#[cfg(not(ltests))]
 fn newstate() -> *mut ffi::lua::lua_State {
     unsafe { ffi::lauxlib::luaL_newstate() }
 }
@@ -0,0 +1,239 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+// Lua compiler (saves bytecodes to files; also lists bytecodes)
+
+extern crate libc;
+extern crate lua_rs;
+
+use lua_rs::bytecode::Chunk;
+use lua_rs::ffi;
+use std::ffi::{CStr, CString};
+use std::fs::File;
+use std::io::{self, Write};
+use std::process;
+
+
+const PROGNAME: &str = "luac";  /* default program name */
+const OUTPUT: &str = "luac.out";  /* default output file */
+
+
+struct Options {
+    listing: u32,  /* list bytecodes? */
+    dumping: bool,  /* dump bytecodes? */
+    stripping: bool,  /* strip debug information? */
+    output: Option<String>,  /* actual output file name; None for stdout */
+    progname: String,  /* actual program name */
+    files: Vec<String>,  /* input files; "-" is stdin */
+}
+
+
+fn fatal(o: &Options, message: &str) -> ! {
+    eprintln!("{}: {}", o.progname, message);
+    process::exit(libc::EXIT_FAILURE);
+}
+
+
+fn cannot(o: &Options, what: &str, e: &io::Error) -> ! {
+    let output = o.output.as_ref().map_or("stdout", |s| &s[..]);
+    let msg = match e.raw_os_error() {  /* as 'strerror' words it */
+        Some(errno) => unsafe { CStr::from_ptr(libc::strerror(errno)).to_string_lossy().into_owned() },
+        None => e.to_string(),
+    };
+    eprintln!("{}: cannot {} {}: {}", o.progname, what, output, msg);
+    process::exit(libc::EXIT_FAILURE);
+}
+
+
+fn usage(progname: &str, message: &str) -> ! {
+    if message.starts_with('-') {
+        eprintln!("{}: unrecognized option '{}'", progname, message);
+    } else {
+        eprintln!("{}: {}", progname, message);
+    }
+    eprint!("usage: {} [options] [filenames]\n\
+             Available options are:\n  \
+             -l       list (use -l -l for full listing)\n  \
+             -o name  output to file 'name' (default is \"{}\")\n  \
+             -p       parse only\n  \
+             -s       strip debug information\n  \
+             -v       show version information\n  \
+             --       stop handling options\n  \
+             -        stop handling options and process stdin\n", progname, OUTPUT);
+    process::exit(libc::EXIT_FAILURE);
+}
+
+
+fn doargs(args: Vec<String>) -> Options {
+    let mut o = Options {
+        listing: 0,
+        dumping: true,
+        stripping: false,
+        output: Some(OUTPUT.to_string()),
+        progname: PROGNAME.to_string(),
+        files: Vec::new(),
+    };
+    let mut version = 0;
+    let argc = args.len();
+    if let Some(arg0) = args.first() {
+        if !arg0.is_empty() { o.progname = arg0.clone(); }
+    }
+    let mut i = 1;
+    while i < args.len() {
+        match &args[i][..] {
+            "--" => {  /* end of options; skip it */
+                i += 1;
+                if version > 0 { version += 1; }
+                break;
+            }
+            "-" => break,  /* end of options; use stdin */
+            a if !a.starts_with('-') => break,  /* end of options; keep it */
+            "-l" => o.listing += 1,  /* list */
+            "-o" => {  /* output file */
+                i += 1;
+                match args.get(i) {
+                    Some(s) if s == "-" => o.output = None,
+                    Some(s) if !s.is_empty() && !s.starts_with('-') => o.output = Some(s.clone()),
+                    _ => usage(&o.progname, "'-o' needs argument"),
+                }
+            }
+            "-p" => o.dumping = false,  /* parse only */
+            "-s" => o.stripping = true,  /* strip debug information */
+            "-v" => version += 1,  /* show version */
+            a => usage(&o.progname, a),  /* unknown option */
+        }
+        i += 1;
+    }
+    if i >= args.len() && (o.listing > 0 || !o.dumping) {
+        o.dumping = false;
+        o.files.push(OUTPUT.to_string());
+    } else {
+        o.files.extend(args.into_iter().skip(i));
+    }
+    if version > 0 {
+        println!("{}", ffi::lua::LUA_COPYRIGHT);
+        if version == argc - 1 { process::exit(libc::EXIT_SUCCESS); }
+    }
+    o
+}
+
+
+const FUNCTION: &[u8] = b"(function()end)();";
+const CHUNKNAME: &[u8] = b"=(luac)\0";
+
+unsafe extern "C-unwind" fn reader(_: *mut ffi::lua::lua_State, ud: *mut libc::c_void,
+                                   size: *mut libc::size_t) -> *const libc::c_char {
+    let n = ud as *mut usize;
+    if *n > 0 {
+        *n -= 1;
+        *size = FUNCTION.len();
+        FUNCTION.as_ptr() as *const libc::c_char
+    } else {
+        *size = 0;
+        std::ptr::null()
+    }
+}
+
+
+unsafe extern "C-unwind" fn writer(_: *mut ffi::lua::lua_State, p: *const libc::c_void,
+                                   size: libc::size_t, u: *mut libc::c_void) -> libc::c_int {
+    let v = &mut *(u as *mut Vec<u8>);
+    v.extend_from_slice(std::slice::from_raw_parts(p as *const u8, size));
+    0
+}
+
+
+/* the chunk of the function on the top of the stack, as 'lua_dump' writes it */
+unsafe fn dump(l: *mut ffi::lua::lua_State, strip: bool) -> Vec<u8> {
+    let mut v = Vec::new();
+    ffi::lua::lua_dump(l, Some(writer), &mut v as *mut Vec<u8> as *mut _, strip as libc::c_int);
+    v
+}
+
+
+unsafe fn tostring(l: *mut ffi::lua::lua_State, idx: libc::c_int) -> String {
+    CStr::from_ptr(ffi::lua::lua_tolstring(l, idx, std::ptr::null_mut())).to_string_lossy().into_owned()
+}
+
+
+/*
+** Leave on the top of the stack one function running the 'n' functions
+** below it in order, sharing their '_ENV'.
+*/
+unsafe fn combine(o: &Options, l: *mut ffi::lua::lua_State, n: usize) -> Chunk {
+    if n > 1 {
+        let mut i = n;
+        let chunkname = CHUNKNAME.as_ptr() as *const libc::c_char;
+        if ffi::lua::lua_load(l, Some(reader), &mut i as *mut usize as *mut _, chunkname,
+                              std::ptr::null()) != ffi::lua::LUA_OK {
+            fatal(o, &tostring(l, -1));
+        }
+        let mut f = Chunk::read(&dump(l, false)).unwrap();
+        for (i, p) in f.main.protos.iter_mut().enumerate() {
+            ffi::lua::lua_pushvalue(l, i as libc::c_int - n as libc::c_int - 1);
+            *p = Chunk::read(&dump(l, false)).unwrap().main;
+            ffi::lua::lua_pop(l, 1);
+            if let Some(u) = p.upvalues.first_mut() { u.instack = false; }
+        }
+        f.main.lineinfo.clear();
+        let bytes = f.to_bytes();
+        if ffi::lauxlib::luaL_loadbufferx(l, bytes.as_ptr() as *const libc::c_char, bytes.len(),
+                                          chunkname, b"b\0".as_ptr() as *const libc::c_char) != ffi::lua::LUA_OK {
+            fatal(o, &tostring(l, -1));
+        }
+        f
+    } else {
+        Chunk::read(&dump(l, false)).unwrap()
+    }
+}
+
+
+unsafe extern "C-unwind" fn pmain(l: *mut ffi::lua::lua_State) -> libc::c_int {
+    let o = &*(ffi::lua::lua_touserdata(l, 1) as *const Options);
+    if ffi::lua::lua_checkstack(l, o.files.len() as libc::c_int) == 0 {
+        fatal(o, "too many input files");
+    }
+    for file in &o.files {
+        let filename = if file == "-" { None } else { Some(CString::new(&file[..]).unwrap()) };
+        let filename = filename.as_ref().map_or(std::ptr::null(), |s| s.as_ptr());
+        if ffi::lauxlib::luaL_loadfile(l, filename) != ffi::lua::LUA_OK {
+            fatal(o, &tostring(l, -1));
+        }
+    }
+    let f = combine(o, l, o.files.len());
+    if o.listing > 0 {
+        let stdout = io::stdout();
+        let _ = f.list(&mut stdout.lock(), o.listing > 1);  /* like 'printf', ignore errors */
+    }
+    if o.dumping {
+        let bytes = dump(l, o.stripping);
+        let mut d: Box<dyn Write> = match o.output {
+            None => Box::new(io::stdout()),
+            Some(ref output) => match File::create(output) {
+                Ok(file) => Box::new(file),
+                Err(e) => cannot(o, "open", &e),
+            },
+        };
+        if let Err(e) = d.write_all(&bytes).and_then(|_| d.flush()) {
+            cannot(o, "write", &e);
+        }
+    }
+    0
+}
+
+
+fn main() {
+    let o = doargs(std::env::args().collect());
+    if o.files.is_empty() { usage(&o.progname, "no input files given"); }
+    unsafe {
+        let l = ffi::lauxlib::luaL_newstate();
+        if l.is_null() { fatal(&o, "cannot create state: not enough memory"); }
+        ffi::lua::lua_pushcfunction(l, Some(pmain));
+        ffi::lua::lua_pushlightuserdata(l, &o as *const Options as *mut _);
+        if ffi::lua::lua_pcall(l, 1, 0, 0) != ffi::lua::LUA_OK { fatal(&o, &tostring(l, -1)); }
+        ffi::lua::lua_close(l);
+    }
+}
@@ -0,0 +1,807 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Precompiled chunks.
+//!
+//! `Chunk` models the binary format written by `string.dump` and read by
+//! `load`. Reading a chunk checks that its code only refers to registers,
+//! constants, upvalues, functions and jump targets that exist, the same
+//! checks the core makes before running a precompiled chunk:
+//!
+//! ```
+//! use lua_rs::bytecode::{Chunk, Constant, Proto};
+//!
+//! let mut main = Proto::default();
+//! main.is_vararg = 2;
+//! main.maxstacksize = 2;
+//! main.code = vec![0x0000_0001, 0x0100_0026];  /* LOADK 0 0; RETURN 0 2 */
+//! main.constants = vec![Constant::Integer(42)];
+//! let chunk = Chunk { main };
+//! let bytes = chunk.to_bytes();
+//! assert_eq!(Chunk::read(&bytes).unwrap(), chunk);
+//!
+//! let mut bad = chunk.clone();
+//! bad.main.code[0] = 0x0000_4001;  /* LOADK 0 1 */
+//! let err = Chunk::read(&bad.to_bytes()).unwrap_err();
+//! assert_eq!(err.to_string(), "bad code in precompiled chunk (constant out of range)");
+//! ```
+
+use ffi::lua;
+use lobject::{TValue, LUAI_MAXSHORTLEN, LUA_TLNGSTR, LUA_TNUMFLT, LUA_TNUMINT, LUA_TSHRSTR};
+use lopcodes::*;
+use opcodes::{self, disassemble};
+use lopcodes::OpCode::*;
+use std::fmt;
+use std::io::{self, Write};
+use std::cmp;
+use std::mem;
+
+
+/* mark for precompiled code ('<esc>Lua') */
+pub const LUA_SIGNATURE: &[u8] = b"\x1bLua";
+
+pub const LUAC_VERSION: u8 = 0x53;
+pub const LUAC_FORMAT: u8 = 0;  /* this is the official format */
+
+/* data to catch conversion errors */
+pub const LUAC_DATA: &[u8] = b"\x19\x93\r\n\x1a\n";
+pub const LUAC_INT: i64 = 0x5678;
+pub const LUAC_NUM: f64 = 370.5;
+
+/* maximum nesting of functions in a chunk, as allowed by the parser */
+pub(crate) const MAXNESTING: usize = 200;
+
+const TNIL: u8 = 0;
+const TBOOLEAN: u8 = 1;
+
+
+/// A precompiled chunk: the main function of a chunk, with the header
+/// that identifies the format.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Chunk {
+    pub main: Proto,
+}
+
+/// A function prototype, with the fields of `Proto` in `lobject.h`.
+///
+/// The debug information (`source`, `lineinfo`, `locvars` and
+/// `upvalue_names`) is empty in stripped chunks. `source` is also absent
+/// in functions with the same source as the function enclosing them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Proto {
+    pub source: Option<Vec<u8>>,
+    pub linedefined: i32,
+    pub lastlinedefined: i32,
+    pub numparams: u8,
+    /// 2: declared vararg; 1: uses vararg
+    pub is_vararg: u8,
+    pub maxstacksize: u8,
+    pub code: Vec<Instruction>,
+    pub constants: Vec<Constant>,
+    pub upvalues: Vec<Upvalue>,
+    pub protos: Vec<Proto>,
+    pub lineinfo: Vec<i32>,
+    pub locvars: Vec<LocVar>,
+    pub upvalue_names: Vec<Option<Vec<u8>>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Constant {
+    Nil,
+    Boolean(bool),
+    Number(f64),
+    Integer(i64),
+    String(Vec<u8>),
+}
+
+/// Where a closure finds an upvalue: a register of the enclosing function
+/// (`instack`) or one of its upvalues.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Upvalue {
+    pub instack: bool,
+    pub idx: u8,
+}
+
+/// A local variable, active from instruction `startpc` up to `endpc`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocVar {
+    pub name: Option<Vec<u8>>,
+    pub startpc: i32,
+    pub endpc: i32,
+}
+
+
+/// Why a chunk could not be read, with the messages of `lundump.c`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    Truncated,
+    NotAChunk,
+    VersionMismatch,
+    FormatMismatch,
+    Corrupted,
+    /// The size of the named C type differs from the one of this build.
+    SizeMismatch(&'static str),
+    EndiannessMismatch,
+    FloatFormatMismatch,
+    /// The chunk is well formed but its contents are not valid, for the
+    /// given reason.
+    BadCode(&'static str),
+}
+
+impl Error {
+    /* 'why' of 'lundump.c', put before "precompiled chunk" */
+    fn why(self) -> &'static str {
+        match self {
+            Error::Truncated => "truncated",
+            Error::NotAChunk => "not a",
+            Error::VersionMismatch => "version mismatch in",
+            Error::FormatMismatch => "format mismatch in",
+            Error::Corrupted => "corrupted",
+            Error::SizeMismatch(_) => "size mismatch in",
+            Error::EndiannessMismatch => "endianness mismatch in",
+            Error::FloatFormatMismatch => "float format mismatch in",
+            Error::BadCode(_) => "bad code in",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Error::SizeMismatch(tname) = *self {
+            write!(f, "{} ", tname)?;
+        }
+        write!(f, "{} precompiled chunk", self.why())?;
+        if let Error::BadCode(why) = *self {
+            write!(f, " ({})", why)?;
+        }
+        Ok(())
+    }
+}
+
+impl ::std::error::Error for Error {}
+
+
+impl Chunk {
+    /// Reads and verifies a chunk. Bytes after its end are ignored, as
+    /// `load` does.
+    pub fn read(b: &[u8]) -> Result<Chunk, Error> {
+        let mut s = LoadState { b, pos: 0 };
+        s.check_header()?;
+        let nupvalues = s.byte()?;
+        let main = s.function(0)?;
+        if nupvalues as usize != main.upvalues.len() {
+            return Err(Error::BadCode("wrong number of upvalues"));
+        }
+        let chunk = Chunk { main };
+        chunk.verify()?;
+        Ok(chunk)
+    }
+
+    /// Checks the code of all functions in the chunk.
+    pub fn verify(&self) -> Result<(), Error> {
+        verify_proto(&self.main, None, 0).map_err(Error::BadCode)
+    }
+
+    /// Writes the chunk, as `string.dump` does.
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut d = DumpState { w };
+        d.header()?;
+        d.byte(self.main.upvalues.len() as u8)?;
+        d.function(&self.main)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut v = Vec::new();
+        self.write(&mut v).unwrap();  /* writing to a vector cannot fail */
+        v
+    }
+
+    /// Removes the debug information, as `string.dump(f, true)` does.
+    pub fn strip(&mut self) {
+        self.main.strip();
+    }
+
+    /// Writes the listing of every function in the chunk, as `luac -l`
+    /// does (`luac -l -l` with `full`); see `opcodes::disassemble`.
+    pub fn list<W: Write>(&self, w: &mut W, full: bool) -> io::Result<()> {
+        disassemble(w, &self.main, full)
+    }
+}
+
+impl Proto {
+    fn strip(&mut self) {
+        self.source = None;
+        self.lineinfo.clear();
+        self.locvars.clear();
+        self.upvalue_names.clear();
+        for p in &mut self.protos {
+            p.strip();
+        }
+    }
+
+    fn shape(&self) -> Shape<'_> {
+        Shape {
+            code: &self.code,
+            numparams: self.numparams,
+            maxstacksize: self.maxstacksize,
+            k: Constants::Model(&self.constants),
+            nups: self.upvalues.len(),
+            np: self.protos.len(),
+        }
+    }
+}
+
+
+/*
+** {======================================================
+** Load
+** =======================================================
+*/
+
+struct LoadState<'a> {
+    b: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> LoadState<'a> {
+    fn block(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        if self.b.len() - self.pos < n {
+            return Err(Error::Truncated);
+        }
+        self.pos += n;
+        Ok(&self.b[self.pos - n..self.pos])
+    }
+
+    fn byte(&mut self) -> Result<u8, Error> {
+        Ok(self.block(1)?[0])
+    }
+
+    fn int(&mut self) -> Result<i32, Error> {
+        let mut x = [0; 4];
+        x.copy_from_slice(self.block(4)?);
+        Ok(i32::from_ne_bytes(x))
+    }
+
+    fn size(&mut self) -> Result<usize, Error> {
+        let mut x = [0; mem::size_of::<usize>()];
+        x.copy_from_slice(self.block(mem::size_of::<usize>())?);
+        Ok(usize::from_ne_bytes(x))
+    }
+
+    fn number(&mut self) -> Result<f64, Error> {
+        let mut x = [0; 8];
+        x.copy_from_slice(self.block(8)?);
+        Ok(f64::from_ne_bytes(x))
+    }
+
+    fn integer(&mut self) -> Result<i64, Error> {
+        let mut x = [0; 8];
+        x.copy_from_slice(self.block(8)?);
+        Ok(i64::from_ne_bytes(x))
+    }
+
+    /* a count of items of 'size' bytes each */
+    fn count(&mut self, size: usize) -> Result<usize, Error> {
+        let n = self.int()?;
+        if n < 0 {
+            return Err(Error::BadCode("negative size"));
+        }
+        if (self.b.len() - self.pos) / size < n as usize {
+            return Err(Error::Truncated);  /* cannot be there */
+        }
+        Ok(n as usize)
+    }
+
+    fn string(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        let mut size = self.byte()? as usize;
+        if size == 0xFF {
+            size = self.size()?;
+        }
+        if size == 0 {
+            return Ok(None);
+        }
+        Ok(Some(self.block(size - 1)?.to_vec()))
+    }
+
+    fn code(&mut self) -> Result<Vec<Instruction>, Error> {
+        let n = self.count(4)?;
+        (0..n).map(|_| self.int().map(|i| i as Instruction)).collect()
+    }
+
+    fn constants(&mut self) -> Result<Vec<Constant>, Error> {
+        let n = self.count(1)?;
+        (0..n).map(|_| {
+            Ok(match self.byte()? {
+                TNIL => Constant::Nil,
+                TBOOLEAN => Constant::Boolean(self.byte()? != 0),
+                t if t as i32 == LUA_TNUMFLT => Constant::Number(self.number()?),
+                t if t as i32 == LUA_TNUMINT => Constant::Integer(self.integer()?),
+                t if t as i32 == LUA_TSHRSTR || t as i32 == LUA_TLNGSTR => {
+                    match self.string()? {
+                        Some(s) => Constant::String(s),
+                        None => return Err(Error::BadCode("missing string")),
+                    }
+                }
+                _ => return Err(Error::BadCode("invalid constant")),
+            })
+        }).collect()
+    }
+
+    fn upvalues(&mut self) -> Result<Vec<Upvalue>, Error> {
+        let n = self.count(2)?;
+        (0..n).map(|_| {
+            let instack = self.byte()? != 0;
+            Ok(Upvalue { instack, idx: self.byte()? })
+        }).collect()
+    }
+
+    fn protos(&mut self, level: usize) -> Result<Vec<Proto>, Error> {
+        let n = self.count(1)?;
+        if n > 0 && level + 1 >= MAXNESTING {
+            return Err(Error::BadCode("too many nested functions"));
+        }
+        (0..n).map(|_| self.function(level + 1)).collect()
+    }
+
+    fn debug(&mut self, f: &mut Proto) -> Result<(), Error> {
+        let n = self.count(4)?;
+        f.lineinfo = (0..n).map(|_| self.int()).collect::<Result<_, _>>()?;
+        let n = self.count(1)?;
+        f.locvars = (0..n).map(|_| {
+            let name = self.string()?;
+            let startpc = self.int()?;
+            Ok(LocVar { name, startpc, endpc: self.int()? })
+        }).collect::<Result<_, _>>()?;
+        let n = self.count(1)?;
+        f.upvalue_names = (0..n).map(|_| self.string()).collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    fn function(&mut self, level: usize) -> Result<Proto, Error> {
+        let mut f = Proto {
+            source: self.string()?,
+            linedefined: self.int()?,
+            lastlinedefined: self.int()?,
+            numparams: self.byte()?,
+            is_vararg: self.byte()?,
+            maxstacksize: self.byte()?,
+            code: self.code()?,
+            constants: self.constants()?,
+            upvalues: self.upvalues()?,
+            protos: self.protos(level)?,
+            ..Proto::default()
+        };
+        self.debug(&mut f)?;
+        Ok(f)
+    }
+
+    fn checkliteral(&mut self, s: &[u8], err: Error) -> Result<(), Error> {
+        if self.block(s.len())? != s { Err(err) } else { Ok(()) }
+    }
+
+    fn checksize(&mut self, size: usize, tname: &'static str) -> Result<(), Error> {
+        if self.byte()? as usize != size { Err(Error::SizeMismatch(tname)) } else { Ok(()) }
+    }
+
+    fn check_header(&mut self) -> Result<(), Error> {
+        self.checkliteral(LUA_SIGNATURE, Error::NotAChunk)?;
+        if self.byte()? != LUAC_VERSION {
+            return Err(Error::VersionMismatch);
+        }
+        if self.byte()? != LUAC_FORMAT {
+            return Err(Error::FormatMismatch);
+        }
+        self.checkliteral(LUAC_DATA, Error::Corrupted)?;
+        self.checksize(4, "int")?;
+        self.checksize(mem::size_of::<usize>(), "size_t")?;
+        self.checksize(4, "Instruction")?;
+        self.checksize(8, "lua_Integer")?;
+        self.checksize(8, "lua_Number")?;
+        if self.integer()? != LUAC_INT {
+            return Err(Error::EndiannessMismatch);
+        }
+        if self.number()? != LUAC_NUM {
+            return Err(Error::FloatFormatMismatch);
+        }
+        Ok(())
+    }
+}
+
+/* }====================================================== */
+
+
+/*
+** {======================================================
+** Dump
+** =======================================================
+*/
+
+struct DumpState<'a, W: 'a> {
+    w: &'a mut W,
+}
+
+impl<'a, W: Write> DumpState<'a, W> {
+    fn byte(&mut self, x: u8) -> io::Result<()> { self.w.write_all(&[x]) }
+
+    fn int(&mut self, x: i32) -> io::Result<()> { self.w.write_all(&x.to_ne_bytes()) }
+
+    fn size(&mut self, x: usize) -> io::Result<()> { self.w.write_all(&x.to_ne_bytes()) }
+
+    fn number(&mut self, x: f64) -> io::Result<()> { self.w.write_all(&x.to_ne_bytes()) }
+
+    fn integer(&mut self, x: i64) -> io::Result<()> { self.w.write_all(&x.to_ne_bytes()) }
+
+    fn string(&mut self, s: Option<&[u8]>) -> io::Result<()> {
+        match s {
+            None => self.byte(0),
+            Some(s) => {
+                let size = s.len() + 1;  /* include trailing '\0' */
+                if size < 0xFF {
+                    self.byte(size as u8)?;
+                } else {
+                    self.byte(0xFF)?;
+                    self.size(size)?;
+                }
+                self.w.write_all(s)  /* no need to save '\0' */
+            }
+        }
+    }
+
+    fn function(&mut self, f: &Proto) -> io::Result<()> {
+        self.string(f.source.as_ref().map(|s| &s[..]))?;
+        self.int(f.linedefined)?;
+        self.int(f.lastlinedefined)?;
+        self.byte(f.numparams)?;
+        self.byte(f.is_vararg)?;
+        self.byte(f.maxstacksize)?;
+        self.int(f.code.len() as i32)?;
+        for &i in &f.code {
+            self.int(i as i32)?;
+        }
+        self.int(f.constants.len() as i32)?;
+        for k in &f.constants {
+            match *k {
+                Constant::Nil => self.byte(TNIL)?,
+                Constant::Boolean(b) => { self.byte(TBOOLEAN)?; self.byte(b as u8)?; }
+                Constant::Number(n) => { self.byte(LUA_TNUMFLT as u8)?; self.number(n)?; }
+                Constant::Integer(i) => { self.byte(LUA_TNUMINT as u8)?; self.integer(i)?; }
+                Constant::String(ref s) => {
+                    let t = if s.len() <= LUAI_MAXSHORTLEN { LUA_TSHRSTR } else { LUA_TLNGSTR };
+                    self.byte(t as u8)?;
+                    self.string(Some(s))?;
+                }
+            }
+        }
+        self.int(f.upvalues.len() as i32)?;
+        for u in &f.upvalues {
+            self.byte(u.instack as u8)?;
+            self.byte(u.idx)?;
+        }
+        self.int(f.protos.len() as i32)?;
+        for p in &f.protos {
+            self.function(p)?;
+        }
+        self.int(f.lineinfo.len() as i32)?;
+        for &l in &f.lineinfo {
+            self.int(l)?;
+        }
+        self.int(f.locvars.len() as i32)?;
+        for v in &f.locvars {
+            self.string(v.name.as_ref().map(|s| &s[..]))?;
+            self.int(v.startpc)?;
+            self.int(v.endpc)?;
+        }
+        self.int(f.upvalue_names.len() as i32)?;
+        for n in &f.upvalue_names {
+            self.string(n.as_ref().map(|s| &s[..]))?;
+        }
+        Ok(())
+    }
+
+    fn header(&mut self) -> io::Result<()> {
+        self.w.write_all(LUA_SIGNATURE)?;
+        self.byte(LUAC_VERSION)?;
+        self.byte(LUAC_FORMAT)?;
+        self.w.write_all(LUAC_DATA)?;
+        self.byte(4)?;  /* int */
+        self.byte(mem::size_of::<usize>() as u8)?;  /* size_t */
+        self.byte(4)?;  /* Instruction */
+        self.byte(8)?;  /* lua_Integer */
+        self.byte(8)?;  /* lua_Number */
+        self.integer(LUAC_INT)?;
+        self.number(LUAC_NUM)
+    }
+}
+
+/* }====================================================== */
+
+
+/*
+** {======================================================
+** Verification, shared with the undumper of the core
+** =======================================================
+*/
+
+/* what the verifier needs to know about a function */
+pub(crate) struct Shape<'a> {
+    pub code: &'a [Instruction],
+    pub numparams: u8,
+    pub maxstacksize: u8,
+    pub k: Constants<'a>,
+    pub nups: usize,  /* number of upvalues */
+    pub np: usize,  /* number of nested functions */
+}
+
+/* the constants of a function, from a 'Chunk' or loaded by the core */
+pub(crate) enum Constants<'a> {
+    Model(&'a [Constant]),
+    Core(&'a [TValue]),
+}
+
+impl<'a> Constants<'a> {
+    fn len(&self) -> usize {
+        match *self {
+            Constants::Model(k) => k.len(),
+            Constants::Core(k) => k.len(),
+        }
+    }
+
+    fn isstring(&self, x: usize) -> bool {
+        match *self {
+            Constants::Model(k) => matches!(k.get(x), Some(&Constant::String(_))),
+            Constants::Core(k) => k.get(x).is_some_and(|o| o.ttype() & 0xF == lua::LUA_TSTRING),
+        }
+    }
+}
+
+fn opcode(i: Instruction) -> Option<OpCode> { opcodes::Instruction(i).opcode() }
+
+/*
+** Check that the code of a function only uses its own registers,
+** constants, upvalues and nested functions, that its jumps land on
+** instructions, and that instructions that take the next one as an
+** argument (or skip it) have it.
+*/
+pub(crate) fn check_code(f: &Shape) -> Result<(), &'static str> {
+    let code = f.code;
+    let n = code.len();
+    if f.numparams > f.maxstacksize {
+        return Err("too many parameters");
+    }
+    if code.last().and_then(|&i| opcode(i)) != Some(OP_RETURN) {
+        return Err("missing final return");  /* execution could run past the end */
+    }
+    let reg = |r: i32| if r < f.maxstacksize as i32 { Ok(()) } else { Err("register out of range") };
+    let kst = |k: i32| if (k as usize) < f.k.len() { Ok(()) } else { Err("constant out of range") };
+    let rk = |x: i32| if ISK(x) { kst(INDEXK(x)) } else { reg(x) };
+    let upv = |u: i32| if (u as usize) < f.nups { Ok(()) } else { Err("upvalue out of range") };
+    /* a size as 'luaO_int2fb' encodes it, which 'luaO_fb2int' must decode to an 'int' */
+    let fb = |x: i32| if x < 8 || (x >> 3) - 1 <= 27 { Ok(()) } else { Err("table size out of range") };
+    let dest = |t: i64| {
+        if t >= 0 && (t as usize) < n && opcode(code[t as usize]) != Some(OP_EXTRAARG) { Ok(()) }
+        else { Err("jump out of range") }
+    };
+    for pc in 0..n {
+        let i = code[pc];
+        let op = match opcode(i) {
+            Some(op) => op,
+            None => return Err("invalid opcode"),
+        };
+        let (a, b, c) = (GETARG_A(i), GETARG_B(i), GETARG_C(i));
+        let next = code.get(pc + 1).and_then(|&i| opcode(i));
+        let jump = || dest(pc as i64 + 1 + GETARG_sBx(i) as i64);
+        let testjump = || if next == Some(OP_JMP) { Ok(()) } else { Err("test without a jump") };
+        match op {
+            OP_MOVE | OP_UNM | OP_BNOT | OP_NOT | OP_LEN => { reg(a)?; reg(b)?; }
+            OP_LOADK => { reg(a)?; kst(GETARG_Bx(i))?; }
+            OP_LOADKX => {
+                reg(a)?;
+                if next != Some(OP_EXTRAARG) { return Err("missing extra argument"); }
+                kst(GETARG_Ax(code[pc + 1]))?;
+            }
+            OP_LOADBOOL => {
+                reg(a)?;
+                if c != 0 { dest(pc as i64 + 2)?; }  /* skips next instruction */
+            }
+            OP_LOADNIL => reg(a + b)?,
+            OP_GETUPVAL | OP_SETUPVAL => { reg(a)?; upv(b)?; }
+            OP_GETTABUP => { reg(a)?; upv(b)?; rk(c)?; }
+            OP_GETTABLE => { reg(a)?; reg(b)?; rk(c)?; }
+            OP_SETTABUP => { upv(a)?; rk(b)?; rk(c)?; }
+            OP_SETTABLE => { reg(a)?; rk(b)?; rk(c)?; }
+            OP_NEWTABLE => { reg(a)?; fb(b)?; fb(c)?; }
+            OP_SELF => { reg(a + 1)?; reg(b)?; rk(c)?; }
+            OP_ADD | OP_SUB | OP_MUL | OP_MOD | OP_POW | OP_DIV | OP_IDIV |
+            OP_BAND | OP_BOR | OP_BXOR | OP_SHL | OP_SHR => { reg(a)?; rk(b)?; rk(c)?; }
+            OP_CONCAT => {
+                reg(a)?;
+                if b >= c { return Err("bad concatenation"); }
+                reg(c)?;
+            }
+            OP_JMP => {
+                if a != 0 { reg(a - 1)?; }  /* upvalues to close */
+                jump()?;
+            }
+            OP_EQ | OP_LT | OP_LE => { rk(b)?; rk(c)?; testjump()?; }
+            OP_TEST => { reg(a)?; testjump()?; }
+            OP_TESTSET => { reg(a)?; reg(b)?; testjump()?; }
+            OP_CALL => {
+                reg(a)?;
+                if b > 0 { reg(a + b - 1)?; }  /* arguments */
+                if c > 1 { reg(a + c - 2)?; }  /* results */
+            }
+            OP_TAILCALL => {
+                reg(a)?;
+                if b > 0 { reg(a + b - 1)?; }
+                if c != 0 { return Err("bad tail call"); }  /* returns all results */
+            }
+            OP_RETURN => {
+                if b != 1 { reg(a)?; }
+                if b > 1 { reg(a + b - 2)?; }
+            }
+            OP_FORLOOP | OP_FORPREP => { reg(a + 3)?; jump()?; }
+            OP_TFORCALL => {
+                reg(a + 2 + c)?;
+                if next != Some(OP_TFORLOOP) { return Err("generic for without loop"); }
+            }
+            OP_TFORLOOP => { reg(a + 1)?; jump()?; }
+            OP_SETLIST => {
+                reg(a + b)?;
+                if c == 0 && next != Some(OP_EXTRAARG) { return Err("missing extra argument"); }
+            }
+            OP_CLOSURE => {
+                reg(a)?;
+                if GETARG_Bx(i) as usize >= f.np { return Err("function out of range"); }
+            }
+            OP_VARARG => {
+                reg(a)?;
+                if b > 1 { reg(a + b - 2)?; }
+            }
+            OP_EXTRAARG => {  /* only as the argument of the previous instruction */
+                let prev = if pc > 0 { code[pc - 1] } else { 0 };
+                match opcode(prev) {
+                    Some(OP_LOADKX) if pc > 0 => {}
+                    Some(OP_SETLIST) if pc > 0 && GETARG_C(prev) == 0 => {}
+                    _ => return Err("misplaced extra argument"),
+                }
+            }
+        }
+    }
+    check_types(f)
+}
+
+/* registers known to hold a table or a string at some point of the code */
+#[derive(Clone, Copy, PartialEq)]
+struct Known {
+    tables: [u64; 4],
+    strings: [u64; 4],
+}
+
+fn has(set: &[u64; 4], r: i32) -> bool { set[r as usize / 64] & (1 << (r % 64)) != 0 }
+
+fn add(set: &mut [u64; 4], r: i32) { set[r as usize / 64] |= 1 << (r % 64); }
+
+impl Known {
+    /* registers 'from' up to 'to' (inclusive) get unknown values */
+    fn clear(&mut self, from: i32, to: i32) {
+        for r in from..cmp::min(to + 1, 256) {
+            self.tables[r as usize / 64] &= !(1 << (r % 64));
+            self.strings[r as usize / 64] &= !(1 << (r % 64));
+        }
+    }
+
+    /* keep what is known on both paths; return whether anything changed */
+    fn meet(&mut self, other: &Known) -> bool {
+        let old = *self;
+        for i in 0..4 {
+            self.tables[i] &= other.tables[i];
+            self.strings[i] &= other.strings[i];
+        }
+        *self != old
+    }
+}
+
+/*
+** The VM takes for granted that the list of OP_SETLIST is a table and
+** that the key of OP_SELF is a string, as the code generator ensures.
+** Follow the flow of the code to check that no path breaks that.
+*/
+fn check_types(f: &Shape) -> Result<(), &'static str> {
+    let code = f.code;
+    let mut known: Vec<Option<Known>> = vec![None; code.len()];
+    known[0] = Some(Known { tables: [0; 4], strings: [0; 4] });
+    let mut pending = vec![0];
+    while let Some(pc) = pending.pop() {
+        let mut k = known[pc].unwrap();
+        let i = code[pc];
+        let op = opcode(i).unwrap();  /* checked by 'check_code' */
+        let (a, b, c) = (GETARG_A(i), GETARG_B(i), GETARG_C(i));
+        let isstring = |x: usize| f.k.isstring(x);
+        match op {
+            OP_SETLIST if !has(&k.tables, a) => return Err("list is not a table"),
+            OP_SELF if ISK(c) && !isstring(INDEXK(c) as usize) => return Err("method name is not a string"),
+            OP_SELF if !ISK(c) && !has(&k.strings, c) => return Err("method name is not a string"),
+            _ => {}
+        }
+        let jump = (pc as i64 + 1 + GETARG_sBx(i) as i64) as usize;
+        let next: &[usize] = match op {
+            OP_MOVE => {
+                let (t, s) = (has(&k.tables, b), has(&k.strings, b));
+                k.clear(a, a);
+                if t { add(&mut k.tables, a); }
+                if s { add(&mut k.strings, a); }
+                &[pc + 1]
+            }
+            OP_LOADK | OP_LOADKX => {
+                k.clear(a, a);
+                let x = if op == OP_LOADK { GETARG_Bx(i) } else { GETARG_Ax(code[pc + 1]) };
+                if isstring(x as usize) { add(&mut k.strings, a); }
+                if op == OP_LOADK { &[pc + 1] } else { &[pc + 2] }
+            }
+            OP_NEWTABLE => { k.clear(a, a); add(&mut k.tables, a); &[pc + 1] }
+            OP_LOADBOOL => { k.clear(a, a); if c != 0 { &[pc + 2] } else { &[pc + 1] } }
+            OP_LOADNIL => { k.clear(a, a + b); &[pc + 1] }
+            OP_SELF => { k.clear(a, a + 1); &[pc + 1] }
+            OP_SETTABUP | OP_SETTABLE | OP_SETUPVAL => &[pc + 1],
+            OP_EQ | OP_LT | OP_LE | OP_TEST => &[pc + 1, pc + 2],
+            OP_TESTSET => { k.clear(a, a); &[pc + 1, pc + 2] }
+            OP_JMP => &[jump],
+            OP_CALL | OP_VARARG => { k.clear(a, 255); &[pc + 1] }  /* callee uses the registers above */
+            OP_TAILCALL => &[pc + 1],  /* a C function returns here */
+            OP_RETURN | OP_EXTRAARG => &[],
+            OP_FORLOOP => { k.clear(a, a + 3); &[pc + 1, jump] }
+            OP_FORPREP => { k.clear(a, a + 3); &[jump] }
+            OP_TFORCALL => { k.clear(a + 3, 255); &[pc + 1] }
+            OP_TFORLOOP => { k.clear(a, a); &[pc + 1, jump] }
+            OP_SETLIST => if c == 0 { &[pc + 2] } else { &[pc + 1] },
+            _ => { k.clear(a, a); &[pc + 1] }
+        };
+        for &n in next {
+            let changed = match known[n] {
+                None => { known[n] = Some(k); true }
+                Some(ref mut old) => old.meet(&k),
+            };
+            if changed {
+                pending.push(n);
+            }
+        }
+    }
+    Ok(())
+}
+
+/* check an upvalue of a closure created by function 'parent' */
+pub(crate) fn check_upvalue(instack: bool, idx: u8, parent: &Shape) -> Result<(), &'static str> {
+    let n = if instack { parent.maxstacksize as usize } else { parent.nups };
+    if (idx as usize) < n { Ok(()) } else { Err("upvalue out of range") }
+}
+
+fn verify_proto(f: &Proto, parent: Option<&Shape>, level: usize) -> Result<(), &'static str> {
+    if level >= MAXNESTING {
+        return Err("too many nested functions");
+    }
+    let shape = f.shape();
+    check_code(&shape)?;
+    if let Some(parent) = parent {
+        for u in &f.upvalues {
+            check_upvalue(u.instack, u.idx, parent)?;
+        }
+    }
+    if !f.lineinfo.is_empty() && f.lineinfo.len() != f.code.len() {
+        return Err("wrong size of line information");
+    }
+    if f.locvars.iter().any(|v| v.name.is_none()) {
+        return Err("missing variable name");
+    }
+    if f.upvalue_names.len() > f.upvalues.len() {
+        return Err("too many upvalue names");
+    }
+    for p in &f.protos {
+        verify_proto(p, Some(&shape), level + 1)?;
+    }
+    Ok(())
+}
+
+/* }====================================================== */
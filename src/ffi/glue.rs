@@ -4,6 +4,9 @@ use libc::*;
 /* luaconf.h */
 pub const LUA_PATH_DEFAULT: &'static str = "/usr/local/share/lua/5.3/?.lua;/usr/local/share/lua/5.3/?/init.lua;/usr/local/lib/lua/5.3/?.lua;/usr/local/lib/lua/5.3/?/init.lua;./?.lua;./?/init.lua";
 pub const LUA_CPATH_DEFAULT: &'static str = "/usr/local/lib/lua/5.3/?.so;/usr/local/lib/lua/5.3/loadall.so;./?.so";
+#[cfg(ltests)]
+pub const LUA_EXTRASPACE: c_int = 16;
+#[cfg(not(ltests))]
 pub const LUA_EXTRASPACE: c_int = 8;
 pub const LUA_IDSIZE: c_int = 60;
 pub type LUA_KCONTEXT = ptrdiff_t;
@@ -22,9 +25,9 @@ pub const LUA_VERSION: &'static str = "Lua 5.3";
 pub const LUA_RELEASE: &'static str = "Lua 5.3.3";
 pub const LUA_COPYRIGHT: &'static str = "Lua 5.3.3  Copyright (C) 1994-2016 Lua.org, PUC-Rio";
 pub const LUA_AUTHORS: &'static str = "R. Ierusalimschy, L. H. de Figueiredo, W. Celes";
-#[cfg(debug_assertions)]
+#[cfg(ltests)]
 pub const LUA_REGISTRYINDEX: c_int = -51000;
-#[cfg(not(debug_assertions))]
+#[cfg(not(ltests))]
 pub const LUA_REGISTRYINDEX: c_int = -1001000;
 /* lauxlib.h */
 pub const LUAL_NUMSIZES: c_int = 136;
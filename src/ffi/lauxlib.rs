@@ -46,7 +46,7 @@ pub unsafe fn luaL_checkversion(L: *mut lua_State) {
   luaL_checkversion_(L, lua::LUA_VERSION_NUM as lua_Number, LUAL_NUMSIZES as size_t)
 }
 
-extern {
+extern "C-unwind" {
   pub fn luaL_checkversion_(L: *mut lua_State, ver: lua_Number, sz: size_t);
 
   pub fn luaL_getmetafield(L: *mut lua_State, obj: c_int, e: *const c_char) -> c_int;
@@ -83,7 +83,7 @@ extern {
 pub const LUA_NOREF: c_int = -2;
 pub const LUA_REFNIL: c_int = -1;
 
-extern {
+extern "C-unwind" {
   pub fn luaL_ref(L: *mut lua_State, t: c_int) -> c_int;
   pub fn luaL_unref(L: *mut lua_State, t: c_int, r: c_int);
 
@@ -95,7 +95,7 @@ pub unsafe fn luaL_loadfile(L: *mut lua_State, f: *const c_char) -> c_int {
   luaL_loadfilex(L, f, ptr::null())
 }
 
-extern {
+extern "C-unwind" {
   pub fn luaL_loadbufferx(L: *mut lua_State, buff: *const c_char, sz: size_t, name: *const c_char, mode: *const c_char) -> c_int;
   pub fn luaL_loadstring(L: *mut lua_State, s: *const c_char) -> c_int;
 
@@ -226,7 +226,7 @@ pub struct luaL_Buffer {
 #[inline(always)]
 pub unsafe fn luaL_addchar(B: *mut luaL_Buffer, c: c_char) {
   // (B)->n < (B) -> size || luaL_prepbuffsize((B), 1)
-  if (*B).n < (*B).size {
+  if (*B).n >= (*B).size {
     luaL_prepbuffsize(B, 1);
   }
   // (B)->b[(B)->n++] = (c)
@@ -240,7 +240,7 @@ pub unsafe fn luaL_addsize(B: *mut luaL_Buffer, s: size_t) {
   (*B).n += s;
 }
 
-extern {
+extern "C-unwind" {
   pub fn luaL_buffinit(L: *mut lua_State, B: *mut luaL_Buffer);
   pub fn luaL_prepbuffsize(B: *mut luaL_Buffer, sz: size_t) -> *mut c_char;
   pub fn luaL_addlstring(B: *mut luaL_Buffer, s: *const c_char, l: size_t);
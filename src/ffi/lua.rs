@@ -49,7 +49,7 @@ pub const LUA_ERRMEM: c_int = 4;
 pub const LUA_ERRGCMM: c_int = 5;
 pub const LUA_ERRERR: c_int = 6;
 
-pub type lua_State = c_void;
+pub use lstate::lua_State;
 
 // basic types
 pub const LUA_TNONE: c_int = -1;
@@ -87,19 +87,19 @@ pub type lua_Unsigned = luaconf::LUA_UNSIGNED;
 pub type lua_KContext = luaconf::LUA_KCONTEXT;
 
 /// Type for native functions that can be passed to Lua.
-pub type lua_CFunction = Option<unsafe extern "C" fn(L: *mut lua_State) -> c_int>;
+pub type lua_CFunction = Option<unsafe extern "C-unwind" fn(L: *mut lua_State) -> c_int>;
 
 // Type for continuation functions
-pub type lua_KFunction = Option<unsafe extern "C" fn(L: *mut lua_State, status: c_int, ctx: lua_KContext) -> c_int>;
+pub type lua_KFunction = Option<unsafe extern "C-unwind" fn(L: *mut lua_State, status: c_int, ctx: lua_KContext) -> c_int>;
 
 // Type for functions that read/write blocks when loading/dumping Lua chunks
-pub type lua_Reader = Option<unsafe extern "C" fn(L: *mut lua_State, ud: *mut c_void, sz: *mut size_t) -> *const c_char>;
-pub type lua_Writer = Option<unsafe extern "C" fn(L: *mut lua_State, p: *const c_void, sz: size_t, ud: *mut c_void) -> c_int>;
+pub type lua_Reader = Option<unsafe extern "C-unwind" fn(L: *mut lua_State, ud: *mut c_void, sz: *mut size_t) -> *const c_char>;
+pub type lua_Writer = Option<unsafe extern "C-unwind" fn(L: *mut lua_State, p: *const c_void, sz: size_t, ud: *mut c_void) -> c_int>;
 
 /// Type for memory-allocation functions.
-pub type lua_Alloc = Option<unsafe extern "C" fn(ud: *mut c_void, ptr: *mut c_void, osize: size_t, nsize: size_t) -> *mut c_void>;
+pub type lua_Alloc = Option<unsafe extern "C-unwind" fn(ud: *mut c_void, ptr: *mut c_void, osize: size_t, nsize: size_t) -> *mut c_void>;
 
-extern {
+extern "C-unwind" {
   // state manipulation
   pub fn lua_newstate(f: lua_Alloc, ud: *mut c_void) -> *mut lua_State;
   pub fn lua_close(L: *mut lua_State);
@@ -156,7 +156,7 @@ pub const LUA_OPSHR: c_int = 11;
 pub const LUA_OPUNM: c_int = 12;
 pub const LUA_OPBNOT: c_int = 13;
 
-extern {
+extern "C-unwind" {
   pub fn lua_arith(L: *mut lua_State, op: c_int);
 }
 
@@ -164,20 +164,20 @@ pub const LUA_OPEQ: c_int = 0;
 pub const LUA_OPLT: c_int = 1;
 pub const LUA_OPLE: c_int = 2;
 
-extern {
+extern "C-unwind" {
   pub fn lua_rawequal(L: *mut lua_State, idx1: c_int, idx2: c_int) -> c_int;
   pub fn lua_compare(L: *mut lua_State, idx1: c_int, idx2: c_int, op: c_int) -> c_int;
 }
 
 // push functions (C -> stack)
-extern {
+extern "C-unwind" {
   pub fn lua_pushnil(L: *mut lua_State);
   pub fn lua_pushnumber(L: *mut lua_State, n: lua_Number);
   pub fn lua_pushinteger(L: *mut lua_State, n: lua_Integer);
   pub fn lua_pushlstring(L: *mut lua_State, s: *const c_char, l: size_t) -> *const c_char;
   pub fn lua_pushstring(L: *mut lua_State, s: *const c_char) -> *const c_char;
-  // TODO: omitted:
-  // lua_pushvfstring
+  // lua_pushvfstring is omitted, as stable Rust cannot build a 'va_list';
+  // use lua_pushfargs (or the push_fstring! macro) instead
   pub fn lua_pushfstring(L: *mut lua_State, fmt: *const c_char, ...) -> *const c_char;
   pub fn lua_pushcclosure(L: *mut lua_State, f: lua_CFunction, n: c_int);
   pub fn lua_pushboolean(L: *mut lua_State, b: c_int);
@@ -185,8 +185,11 @@ extern {
   pub fn lua_pushthread(L: *mut lua_State) -> c_int;
 }
 
+pub use lapi::lua_pushfargs;
+pub use lobject::FArg;
+
 // get functions (Lua -> stack)
-extern {
+extern "C-unwind" {
   pub fn lua_getglobal(L: *mut lua_State, var: *const c_char) -> c_int;
   pub fn lua_gettable(L: *mut lua_State, idx: c_int) -> c_int;
   pub fn lua_getfield(L: *mut lua_State, idx: c_int, k: *const c_char) -> c_int;
@@ -202,7 +205,7 @@ extern {
 }
 
 // set functions (stack -> Lua)
-extern {
+extern "C-unwind" {
   pub fn lua_setglobal(L: *mut lua_State, var: *const c_char);
   pub fn lua_settable(L: *mut lua_State, idx: c_int);
   pub fn lua_setfield(L: *mut lua_State, idx: c_int, k: *const c_char);
@@ -215,7 +218,7 @@ extern {
 }
 
 // 'load' and 'call' functions (load and run Lua code)
-extern {
+extern "C-unwind" {
   pub fn lua_callk(L: *mut lua_State, nargs: c_int, nresults: c_int, ctx: lua_KContext, k: lua_KFunction);
   pub fn lua_pcallk(L: *mut lua_State, nargs: c_int, nresults: c_int, errfunc: c_int, ctx: lua_KContext, k: lua_KFunction) -> c_int;
   pub fn lua_load(L: *mut lua_State, reader: lua_Reader, dt: *mut c_void, chunkname: *const c_char, mode: *const c_char) -> c_int;
@@ -233,7 +236,7 @@ pub unsafe fn lua_pcall(L: *mut lua_State, n: c_int, r: c_int, f: c_int) -> c_in
 }
 
 // coroutine functions
-extern {
+extern "C-unwind" {
   pub fn lua_yieldk(L: *mut lua_State, nresults: c_int, ctx: lua_KContext, k: lua_KFunction) -> c_int;
   pub fn lua_resume(L: *mut lua_State, from: *mut lua_State, narg: c_int) -> c_int;
   pub fn lua_status(L: *mut lua_State) -> c_int;
@@ -256,12 +259,12 @@ pub const LUA_GCSETPAUSE: c_int = 6;
 pub const LUA_GCSETSTEPMUL: c_int = 7;
 pub const LUA_GCISRUNNING: c_int = 9;
 
-extern {
+extern "C-unwind" {
   pub fn lua_gc(L: *mut lua_State, what: c_int, data: c_int) -> c_int;
 }
 
 // miscellaneous functions
-extern {
+extern "C-unwind" {
   pub fn lua_error(L: *mut lua_State) -> c_int;
   pub fn lua_next(L: *mut lua_State, idx: c_int) -> c_int;
   pub fn lua_concat(L: *mut lua_State, n: c_int);
@@ -275,7 +278,7 @@ extern {
 // here, implemented as Rust functions
 #[inline(always)]
 pub unsafe fn lua_getextraspace(L: *mut lua_State) -> *mut c_void {
-  L.offset(-super::glue::LUA_EXTRASPACE as isize) as *mut c_void
+  (L as *mut u8).offset(-luaconf::LUA_EXTRASPACE as isize) as *mut c_void
 }
 
 #[inline(always)]
@@ -399,9 +402,9 @@ pub const LUA_MASKLINE: c_int = 1 << (LUA_HOOKLINE as usize);
 pub const LUA_MASKCOUNT: c_int = 1 << (LUA_HOOKCOUNT as usize);
 
 /// Type for functions to be called on debug events.
-pub type lua_Hook = Option<extern "C" fn(L: *mut lua_State, ar: *mut lua_Debug)>;
+pub type lua_Hook = Option<extern "C-unwind" fn(L: *mut lua_State, ar: *mut lua_Debug)>;
 
-extern {
+extern "C-unwind" {
   pub fn lua_getstack(L: *mut lua_State, level: c_int, ar: *mut lua_Debug) -> c_int;
   pub fn lua_getinfo(L: *mut lua_State, what: *const c_char, ar: *mut lua_Debug) -> c_int;
   pub fn lua_getlocal(L: *mut lua_State, ar: *const lua_Debug, n: c_int) -> *const c_char;
@@ -434,6 +437,6 @@ pub struct lua_Debug {
   pub istailcall: c_char,
   pub short_src: [c_char; luaconf::LUA_IDSIZE as usize],
   // lua.h mentions this is for private use
-  i_ci: *mut c_void,
+  pub(crate) i_ci: *mut c_void,
 }
 
@@ -22,11 +22,16 @@
 
 //! Contains definitions from `luaconf.h`.
 
+#[cfg_attr(not(rust_loadlib), allow(unused_imports))]
+pub use super::glue::{LUA_PATH_DEFAULT, LUA_CPATH_DEFAULT};
+
 pub use super::glue::LUAL_BUFFERSIZE;
 pub use super::glue::LUA_NUMBER;
 pub use super::glue::LUA_INTEGER;
 pub use super::glue::LUA_UNSIGNED;
 
+pub use super::glue::LUA_EXTRASPACE;
+
 pub use super::glue::{LUA_IDSIZE};
 pub use super::glue::{LUA_MININTEGER, LUA_MAXINTEGER};
 
@@ -30,7 +30,7 @@ pub use super::glue::{
   LUA_UTF8LIBNAME, LUA_BITLIBNAME, LUA_MATHLIBNAME, LUA_DBLIBNAME, LUA_LOADLIBNAME
 };
 
-extern {
+extern "C-unwind" {
   pub fn luaopen_base(L: *mut lua_State) -> c_int;
   pub fn luaopen_coroutine(L: *mut lua_State) -> c_int;
   pub fn luaopen_table(L: *mut lua_State) -> c_int;
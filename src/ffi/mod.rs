@@ -28,7 +28,7 @@
 // exception of constants, which appear scattered throughout the manual text.
 
 mod glue;
-mod luaconf;
+pub(crate) mod luaconf;
 pub mod lua;
 pub mod lauxlib;
 pub mod lualib;
@@ -0,0 +1,67 @@
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Access to the host system shared by the ports of the standard
+//! libraries, kept apart from them as any one of them may be linked
+//! from C instead (see 'build.rs').
+
+use ffi::lua::lua_State;
+use std::ffi::CStr;
+use std::path::Path;
+use userdata;
+
+
+/// A provider of environment variables for `os.getenv`.
+pub trait Environment {
+    /// Returns the value of variable `name`, if it is defined.
+    fn getenv(&self, name: &[u8]) -> Option<Vec<u8>>;
+}
+
+/// The environment of the running process.
+pub struct ProcessEnvironment;
+
+impl Environment for ProcessEnvironment {
+    #[cfg(unix)]
+    fn getenv(&self, name: &[u8]) -> Option<Vec<u8>> {
+        use std::os::unix::ffi::{OsStrExt, OsStringExt};
+        ::std::env::var_os(::std::ffi::OsStr::from_bytes(name)).map(|v| v.into_vec())
+    }
+
+    #[cfg(not(unix))]
+    fn getenv(&self, name: &[u8]) -> Option<Vec<u8>> {
+        let name = match ::std::str::from_utf8(name) { Ok(n) => n, Err(_) => return None };
+        ::std::env::var(name).ok().map(|v| v.into_bytes())
+    }
+}
+
+
+/* key, in the registry, for the environment set by 'loslib::set_environment' */
+pub static ENVKEY: u8 = 0;
+
+/*
+** The environment of state 'l': the one given to the 'os' library, or
+** that of the process
+*/
+pub unsafe fn getenvironment<'a>(l: *mut lua_State) -> &'a dyn Environment {
+    match userdata::get::<Box<dyn Environment>>(l, &ENVKEY) {
+        Some(e) => &**e,
+        None => &ProcessEnvironment,
+    }
+}
+
+
+/*
+** The path named by the C string 'name'
+*/
+#[cfg(unix)]
+pub fn topath(name: &CStr) -> &Path {
+    use std::os::unix::ffi::OsStrExt;
+    Path::new(::std::ffi::OsStr::from_bytes(name.to_bytes()))
+}
+
+#[cfg(not(unix))]
+pub fn topath(name: &CStr) -> &Path {
+    Path::new(name.to_str().unwrap_or(""))
+}
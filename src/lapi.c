@@ -0,0 +1,44 @@
+/*
+** Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+** Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+** Distributed under the MIT License.
+**    (See accompanying file LICENSE.txt or copy at
+**          http://opensource.org/licenses/MIT)
+**
+** The variadic part of 'lapi.c', which stable Rust cannot define.
+** Everything else of the Lua API lives in 'lapi.rs'.
+*/
+
+#include <stdarg.h>
+
+#define LUA_CORE
+
+#include "lua.h"
+
+#include "lgc.h"
+#include "lobject.h"
+#include "lstate.h"
+
+
+LUA_API const char *lua_pushvfstring (lua_State *L, const char *fmt,
+                                      va_list argp) {
+  const char *ret;
+  lua_lock(L);
+  ret = luaO_pushvfstring(L, fmt, argp);
+  luaC_checkGC(L);
+  lua_unlock(L);
+  return ret;
+}
+
+
+LUA_API const char *lua_pushfstring (lua_State *L, const char *fmt, ...) {
+  const char *ret;
+  va_list argp;
+  lua_lock(L);
+  va_start(argp, fmt);
+  ret = luaO_pushvfstring(L, fmt, argp);
+  va_end(argp);
+  luaC_checkGC(L);
+  lua_unlock(L);
+  return ret;
+}
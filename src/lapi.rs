@@ -0,0 +1,1598 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Lua API.
+//!
+//! Every `lua_*` function of `lua.h` is defined here with its C name, so
+//! the declarations in `ffi::lua` (and C code using `lua.h`) resolve into
+//! Rust. The `api_check`s of the C sources panic with their message in
+//! debug builds.
+
+#![allow(non_snake_case, non_upper_case_globals)]
+
+use ffi::lua::{self, lua_Alloc, lua_CFunction, lua_Integer, lua_KContext, lua_KFunction,
+               lua_Number, lua_Reader, lua_Writer, LUA_MULTRET, LUA_OK, LUA_REGISTRYINDEX,
+               LUA_RIDX_GLOBALS};
+use ldebug::luaG_errormsg;
+use ldo::{luaD_call, luaD_callnoyield, luaD_growstack, luaD_protectedparser, pcall,
+          rawrunprotected, savestack, status, LUAI_MAXSTACK};
+use lfunc::{luaF_newCclosure, MAXUPVAL};
+use lgc::{luaC_barrier, luaC_barrierback, luaC_checkGC, luaC_checkfinalizer, luaC_fullgc,
+          luaC_objbarrier, luaC_step, luaC_upvalbarrier, luaC_upvdeccount, GCSpause, GCSTEPSIZE};
+use lobject::{getstr, getudatamem, getuservalue, l_isfalse, luaO_arith, luaO_nilobject,
+              luaO_str2num, luaO_tostring, pushfstring, setbvalue, setclCvalue, setfltvalue,
+              setfvalue, sethvalue, setivalue, setnilvalue, setpvalue, setsvalue, setthvalue,
+              setuservalue, setuvalue, svalue, tointeger, tonumber, upisopen, vslen, CClosure,
+              FArg, GCObject, LClosure, TValue, Table, UpVal, LUA_TCCL, LUA_TLCF, LUA_TLCL,
+              LUA_TLNGSTR, LUA_TSHRSTR};
+use lstate::{api_unlock, gettotalbytes, isLua, lua_lock, lua_State, lua_unlock, luaE_setdebt, CallInfo,
+             StkId, CIST_YPCALL, CIST_OAH, EXTRA_STACK, G};
+use lstring::{luaS_new, luaS_newlstr, luaS_newudata};
+use ltable::{luaH_get, luaH_getint, luaH_getn, luaH_getstr, luaH_new, luaH_next, luaH_resize,
+             luaH_set, luaH_setint};
+use ltm::ttypename;
+use ldump::luaU_dump;
+use lzio::{luaZ_init, Zio};
+use libc::{c_char, c_int, c_uint, c_void, size_t};
+use std::mem;
+use std::ptr;
+
+
+/* the exported functions below, from 'lapi.c' when it is linked instead (see 'build.rs') */
+#[cfg(not(rust_lapi))]
+extern "C-unwind" {
+    pub fn lua_checkstack(L: *mut lua_State, n: c_int) -> c_int;
+    pub fn lua_xmove(from: *mut lua_State, to: *mut lua_State, n: c_int);
+    pub fn lua_atpanic(L: *mut lua_State, panicf: lua_CFunction) -> lua_CFunction;
+    pub fn lua_version(L: *mut lua_State) -> *const lua_Number;
+    pub fn lua_absindex(L: *mut lua_State, idx: c_int) -> c_int;
+    pub fn lua_gettop(L: *mut lua_State) -> c_int;
+    pub fn lua_settop(L: *mut lua_State, idx: c_int);
+    pub fn lua_rotate(L: *mut lua_State, idx: c_int, n: c_int);
+    pub fn lua_copy(L: *mut lua_State, fromidx: c_int, toidx: c_int);
+    pub fn lua_pushvalue(L: *mut lua_State, idx: c_int);
+    pub fn lua_type(L: *mut lua_State, idx: c_int) -> c_int;
+    pub fn lua_typename(L: *mut lua_State, t: c_int) -> *const c_char;
+    pub fn lua_iscfunction(L: *mut lua_State, idx: c_int) -> c_int;
+    pub fn lua_isinteger(L: *mut lua_State, idx: c_int) -> c_int;
+    pub fn lua_isnumber(L: *mut lua_State, idx: c_int) -> c_int;
+    pub fn lua_isstring(L: *mut lua_State, idx: c_int) -> c_int;
+    pub fn lua_isuserdata(L: *mut lua_State, idx: c_int) -> c_int;
+    pub fn lua_rawequal(L: *mut lua_State, index1: c_int, index2: c_int) -> c_int;
+    pub fn lua_arith(L: *mut lua_State, op: c_int);
+    pub fn lua_compare(L: *mut lua_State, index1: c_int, index2: c_int, op: c_int) -> c_int;
+    pub fn lua_stringtonumber(L: *mut lua_State, s: *const c_char) -> size_t;
+    pub fn lua_tonumberx(L: *mut lua_State, idx: c_int, pisnum: *mut c_int) -> lua_Number;
+    pub fn lua_tointegerx(L: *mut lua_State, idx: c_int, pisnum: *mut c_int) -> lua_Integer;
+    pub fn lua_toboolean(L: *mut lua_State, idx: c_int) -> c_int;
+    pub fn lua_tolstring(L: *mut lua_State, idx: c_int, len: *mut size_t) -> *const c_char;
+    pub fn lua_rawlen(L: *mut lua_State, idx: c_int) -> size_t;
+    pub fn lua_tocfunction(L: *mut lua_State, idx: c_int) -> lua_CFunction;
+    pub fn lua_touserdata(L: *mut lua_State, idx: c_int) -> *mut c_void;
+    pub fn lua_tothread(L: *mut lua_State, idx: c_int) -> *mut lua_State;
+    pub fn lua_topointer(L: *mut lua_State, idx: c_int) -> *const c_void;
+    pub fn lua_pushnil(L: *mut lua_State);
+    pub fn lua_pushnumber(L: *mut lua_State, n: lua_Number);
+    pub fn lua_pushinteger(L: *mut lua_State, n: lua_Integer);
+    pub fn lua_pushlstring(L: *mut lua_State, s: *const c_char, len: size_t) -> *const c_char;
+    pub fn lua_pushstring(L: *mut lua_State, s: *const c_char) -> *const c_char;
+    pub fn lua_pushcclosure(L: *mut lua_State, f: lua_CFunction, n: c_int);
+    pub fn lua_pushboolean(L: *mut lua_State, b: c_int);
+    pub fn lua_pushlightuserdata(L: *mut lua_State, p: *mut c_void);
+    pub fn lua_pushthread(L: *mut lua_State) -> c_int;
+    pub fn lua_getglobal(L: *mut lua_State, name: *const c_char) -> c_int;
+    pub fn lua_gettable(L: *mut lua_State, idx: c_int) -> c_int;
+    pub fn lua_getfield(L: *mut lua_State, idx: c_int, k: *const c_char) -> c_int;
+    pub fn lua_geti(L: *mut lua_State, idx: c_int, n: lua_Integer) -> c_int;
+    pub fn lua_rawget(L: *mut lua_State, idx: c_int) -> c_int;
+    pub fn lua_rawgeti(L: *mut lua_State, idx: c_int, n: lua_Integer) -> c_int;
+    pub fn lua_rawgetp(L: *mut lua_State, idx: c_int, p: *const c_void) -> c_int;
+    pub fn lua_createtable(L: *mut lua_State, narray: c_int, nrec: c_int);
+    pub fn lua_getmetatable(L: *mut lua_State, objindex: c_int) -> c_int;
+    pub fn lua_getuservalue(L: *mut lua_State, idx: c_int) -> c_int;
+    pub fn lua_setglobal(L: *mut lua_State, name: *const c_char);
+    pub fn lua_settable(L: *mut lua_State, idx: c_int);
+    pub fn lua_setfield(L: *mut lua_State, idx: c_int, k: *const c_char);
+    pub fn lua_seti(L: *mut lua_State, idx: c_int, n: lua_Integer);
+    pub fn lua_rawset(L: *mut lua_State, idx: c_int);
+    pub fn lua_rawseti(L: *mut lua_State, idx: c_int, n: lua_Integer);
+    pub fn lua_rawsetp(L: *mut lua_State, idx: c_int, p: *const c_void);
+    pub fn lua_setmetatable(L: *mut lua_State, objindex: c_int) -> c_int;
+    pub fn lua_setuservalue(L: *mut lua_State, idx: c_int);
+    pub fn lua_callk(L: *mut lua_State, nargs: c_int, nresults: c_int, ctx: lua_KContext,
+                     k: lua_KFunction);
+    pub fn lua_pcallk(L: *mut lua_State, nargs: c_int, nresults: c_int, errfunc: c_int,
+                      ctx: lua_KContext, k: lua_KFunction) -> c_int;
+    pub fn lua_load(L: *mut lua_State, reader: lua_Reader, data: *mut c_void,
+                    chunkname: *const c_char, mode: *const c_char) -> c_int;
+    pub fn lua_dump(L: *mut lua_State, writer: lua_Writer, data: *mut c_void,
+                    strip: c_int) -> c_int;
+    pub fn lua_status(L: *mut lua_State) -> c_int;
+    pub fn lua_gc(L: *mut lua_State, what: c_int, data: c_int) -> c_int;
+    pub fn lua_error(L: *mut lua_State) -> c_int;
+    pub fn lua_next(L: *mut lua_State, idx: c_int) -> c_int;
+    pub fn lua_concat(L: *mut lua_State, n: c_int);
+    pub fn lua_len(L: *mut lua_State, idx: c_int);
+    pub fn lua_getallocf(L: *mut lua_State, ud: *mut *mut c_void) -> lua_Alloc;
+    pub fn lua_setallocf(L: *mut lua_State, f: lua_Alloc, ud: *mut c_void);
+    pub fn lua_newuserdata(L: *mut lua_State, size: size_t) -> *mut c_void;
+    pub fn lua_getupvalue(L: *mut lua_State, funcindex: c_int, n: c_int) -> *const c_char;
+    pub fn lua_setupvalue(L: *mut lua_State, funcindex: c_int, n: c_int) -> *const c_char;
+    pub fn lua_upvalueid(L: *mut lua_State, fidx: c_int, n: c_int) -> *mut c_void;
+    pub fn lua_upvaluejoin(L: *mut lua_State, fidx1: c_int, n1: c_int, fidx2: c_int, n2: c_int);
+}
+
+
+extern "C-unwind" {
+    fn luaV_equalobj(L: *mut lua_State, t1: *const TValue, t2: *const TValue) -> c_int;
+    fn luaV_lessthan(L: *mut lua_State, l: *const TValue, r: *const TValue) -> c_int;
+    fn luaV_lessequal(L: *mut lua_State, l: *const TValue, r: *const TValue) -> c_int;
+    fn luaV_concat(L: *mut lua_State, total: c_int);
+    fn luaV_objlen(L: *mut lua_State, ra: StkId, rb: *const TValue);
+    fn luaV_finishget(L: *mut lua_State, t: *const TValue, key: *mut TValue, val: StkId,
+                      slot: *const TValue);
+    fn luaV_finishset(L: *mut lua_State, t: *const TValue, key: *mut TValue, val: StkId,
+                      slot: *const TValue);
+}
+
+
+/* "$LuaVersion: " LUA_COPYRIGHT " $" "$LuaAuthors: " LUA_AUTHORS " $" */
+const IDENT: [&str; 6] = ["$LuaVersion: ", lua::LUA_COPYRIGHT, " $",
+                          "$LuaAuthors: ", lua::LUA_AUTHORS, " $"];
+
+const fn identlen() -> usize {
+    let mut n = 1;  /* final '\0' */
+    let mut i = 0;
+    while i < IDENT.len() { n += IDENT[i].len(); i += 1; }
+    n
+}
+
+const fn ident() -> [u8; identlen()] {
+    let mut buff = [0; identlen()];
+    let mut n = 0;
+    let mut i = 0;
+    while i < IDENT.len() {
+        let s = IDENT[i].as_bytes();
+        let mut j = 0;
+        while j < s.len() { buff[n] = s[j]; n += 1; j += 1; }
+        i += 1;
+    }
+    buff
+}
+
+#[cfg_attr(rust_lapi, no_mangle)]  /* the same data as the C one otherwise */
+pub static lua_ident: [u8; identlen()] = ident();
+
+
+/*
+** API checks: assertions on the arguments of the API functions, which
+** panic with their message in debug builds, leaving 'L' unlocked so that
+** it can still be closed
+*/
+macro_rules! api_check {
+    ($L:expr, $e:expr, $($msg:tt)+) => {
+        if cfg!(debug_assertions) && !$e {
+            api_unlock($L);
+            panic!($($msg)+);
+        }
+    }
+}
+
+
+#[track_caller]
+pub unsafe fn api_incr_top(L: *mut lua_State) {
+    (*L).top = (*L).top.add(1);
+    api_check!(L, (*L).top <= (*(*L).ci).top, "stack overflow");
+}
+
+unsafe fn adjustresults(L: *mut lua_State, nres: c_int) {
+    if nres == LUA_MULTRET && (*(*L).ci).top < (*L).top {
+        (*(*L).ci).top = (*L).top;
+    }
+}
+
+#[track_caller]
+unsafe fn api_checknelems(L: *mut lua_State, n: c_int) {
+    api_check!(L, (n as isize) < (*L).top.offset_from((*(*L).ci).func),
+               "not enough elements in the stack (need {})", n);
+}
+
+
+unsafe fn setobj(obj1: *mut TValue, obj2: *const TValue) { *obj1 = *obj2; }
+
+unsafe fn invalidateTMcache(t: *mut Table) { (*t).flags = 0; }
+
+
+/* value at a non-valid index */
+fn NONVALIDVALUE() -> *mut TValue { luaO_nilobject() as *mut TValue }
+
+/* corresponding test */
+fn isvalid(o: *const TValue) -> bool { o != luaO_nilobject() }
+
+/* test for pseudo index */
+fn ispseudo(i: c_int) -> bool { i <= LUA_REGISTRYINDEX }
+
+/* test for upvalue */
+fn isupvalue(i: c_int) -> bool { i < LUA_REGISTRYINDEX }
+
+/* test for valid but not pseudo index */
+fn isstackindex(i: c_int, o: *const TValue) -> bool { isvalid(o) && !ispseudo(i) }
+
+#[track_caller]
+unsafe fn api_checkvalidindex(L: *mut lua_State, o: *const TValue) {
+    api_check!(L, isvalid(o), "invalid index");
+}
+
+#[track_caller]
+unsafe fn api_checkstackindex(L: *mut lua_State, i: c_int, o: *const TValue) {
+    api_check!(L, isstackindex(i, o), "index {} not in the stack", i);
+}
+
+
+#[track_caller]
+unsafe fn index2addr(L: *mut lua_State, idx: c_int) -> *mut TValue {
+    let ci = (*L).ci;
+    if idx > 0 {
+        let o = (*ci).func.offset(idx as isize);
+        api_check!(L, idx as isize <= (*ci).top.offset_from((*ci).func.add(1)),
+                   "unacceptable index {}", idx);
+        if o >= (*L).top { NONVALIDVALUE() } else { o }
+    } else if !ispseudo(idx) {  /* negative index */
+        api_check!(L, idx != 0 && -idx as isize <= (*L).top.offset_from((*ci).func.add(1)),
+                   "invalid index {}", idx);
+        (*L).top.offset(idx as isize)
+    } else if idx == LUA_REGISTRYINDEX {
+        &mut (*G(L)).l_registry
+    } else {  /* upvalues */
+        let idx = LUA_REGISTRYINDEX - idx;
+        api_check!(L, idx <= MAXUPVAL + 1, "upvalue index {} too large", idx);
+        if (*(*ci).func).ttislcf() {  /* light C function? */
+            NONVALIDVALUE()  /* it has no upvalues */
+        } else {
+            let func = (*(*ci).func).clCvalue();
+            if idx <= (*func).nupvalues as c_int {
+                (*func).upvalue.as_mut_ptr().add(idx as usize - 1)
+            } else {
+                NONVALIDVALUE()
+            }
+        }
+    }
+}
+
+
+/*
+** 'luaV_fastget' and 'luaV_fastset' (from 'lvm.h')
+*/
+unsafe fn fastget(t: *const TValue, slot: &mut *const TValue,
+                  get: impl FnOnce(*mut Table) -> *const TValue) -> bool {
+    if !(*t).ttistable() {
+        *slot = ptr::null();  /* not a table; 'slot' is NULL and result is 0 */
+        false
+    } else {
+        *slot = get((*t).hvalue());  /* else, do raw access */
+        !(**slot).ttisnil()  /* result not nil? */
+    }
+}
+
+unsafe fn fastset(L: *mut lua_State, t: *const TValue, slot: &mut *const TValue,
+                  get: impl FnOnce(*mut Table) -> *const TValue, v: *const TValue) -> bool {
+    if !fastget(t, slot, get) {
+        false
+    } else {
+        luaC_barrierback(L, (*t).hvalue(), v);
+        setobj(*slot as *mut TValue, v);
+        true
+    }
+}
+
+unsafe fn luaV_gettable(L: *mut lua_State, t: *const TValue, k: *mut TValue, v: StkId) {
+    let mut slot = ptr::null();
+    if fastget(t, &mut slot, |h| luaH_get(h, k)) {
+        setobj(v, slot);
+    } else {
+        luaV_finishget(L, t, k, v, slot);
+    }
+}
+
+unsafe fn luaV_settable(L: *mut lua_State, t: *const TValue, k: *mut TValue, v: StkId) {
+    let mut slot = ptr::null();
+    if !fastset(L, t, &mut slot, |h| luaH_get(h, k), v) {
+        luaV_finishset(L, t, k, v, slot);
+    }
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_checkstack(L: *mut lua_State, n: c_int) -> c_int {
+    let ci = (*L).ci;
+    lua_lock(L);
+    api_check!(L, n >= 0, "negative 'n'");
+    let res = if (*L).stack_last.offset_from((*L).top) > n as isize {  /* stack large enough? */
+        true  /* yes; check is OK */
+    } else {  /* no; need to grow stack */
+        let inuse = (*L).top.offset_from((*L).stack) as c_int + EXTRA_STACK;
+        if inuse > LUAI_MAXSTACK - n {  /* can grow without overflow? */
+            false  /* no */
+        } else {  /* try to grow stack, capturing memory errors */
+            rawrunprotected(L, || luaD_growstack(L, n)).is_ok()
+        }
+    };
+    if res && (*ci).top < (*L).top.offset(n as isize) {
+        (*ci).top = (*L).top.offset(n as isize);  /* adjust frame top */
+    }
+    lua_unlock(L);
+    res as c_int
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_xmove(from: *mut lua_State, to: *mut lua_State, n: c_int) {
+    if from == to { return; }
+    lua_lock(to);
+    api_checknelems(from, n);
+    api_check!(to, G(from) == G(to), "moving among independent states");
+    api_check!(to, (*(*to).ci).top.offset_from((*to).top) >= n as isize, "stack overflow");
+    (*from).top = (*from).top.offset(-n as isize);
+    for i in 0..n as usize {
+        setobj((*to).top, (*from).top.add(i));
+        (*to).top = (*to).top.add(1);  /* stack already checked by previous 'api_check' */
+    }
+    lua_unlock(to);
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_atpanic(L: *mut lua_State, panicf: lua_CFunction) -> lua_CFunction {
+    lua_lock(L);
+    let old = (*G(L)).panic;
+    (*G(L)).panic = panicf;
+    lua_unlock(L);
+    old
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_version(L: *mut lua_State) -> *const lua_Number {
+    static version: lua_Number = lua::LUA_VERSION_NUM as lua_Number;
+    if L.is_null() { &version } else { (*G(L)).version }
+}
+
+
+
+/*
+** basic stack manipulation
+*/
+
+
+/*
+** convert an acceptable stack index into an absolute index
+*/
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_absindex(L: *mut lua_State, idx: c_int) -> c_int {
+    if idx > 0 || ispseudo(idx) {
+        idx
+    } else {
+        (*L).top.offset_from((*(*L).ci).func) as c_int + idx
+    }
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_gettop(L: *mut lua_State) -> c_int {
+    (*L).top.offset_from((*(*L).ci).func.add(1)) as c_int
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_settop(L: *mut lua_State, idx: c_int) {
+    let func = (*(*L).ci).func;
+    lua_lock(L);
+    if idx >= 0 {
+        api_check!(L, idx as isize <= (*L).stack_last.offset_from(func.add(1)),
+                   "new top {} too large", idx);
+        while (*L).top < func.add(1 + idx as usize) {
+            setnilvalue((*L).top);
+            (*L).top = (*L).top.add(1);
+        }
+        (*L).top = func.add(1 + idx as usize);
+    } else {
+        api_check!(L, -(idx + 1) as isize <= (*L).top.offset_from(func.add(1)),
+                   "invalid new top {}", idx);
+        (*L).top = (*L).top.offset(idx as isize + 1);  /* 'subtract' index (index is negative) */
+    }
+    lua_unlock(L);
+}
+
+
+/*
+** Reverse the stack segment from 'from' to 'to'
+** (auxiliary to 'lua_rotate')
+*/
+unsafe fn reverse(mut from: StkId, mut to: StkId) {
+    while from < to {
+        ptr::swap(from, to);
+        from = from.add(1);
+        to = to.sub(1);
+    }
+}
+
+
+/*
+** Let x = AB, where A is a prefix of length 'n'. Then,
+** rotate x n == BA. But BA == (A^r . B^r)^r.
+*/
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_rotate(L: *mut lua_State, idx: c_int, n: c_int) {
+    lua_lock(L);
+    let t = (*L).top.sub(1);  /* end of stack segment being rotated */
+    let p = index2addr(L, idx);  /* start of segment */
+    api_checkstackindex(L, idx, p);
+    api_check!(L, n.abs() as isize <= t.offset_from(p) + 1, "invalid 'n' ({})", n);
+    let m = if n >= 0 { t.offset(-n as isize) } else { p.offset(-n as isize - 1) };  /* end of prefix */
+    reverse(p, m);  /* reverse the prefix with length 'n' */
+    reverse(m.add(1), t);  /* reverse the suffix */
+    reverse(p, t);  /* reverse the entire segment */
+    lua_unlock(L);
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_copy(L: *mut lua_State, fromidx: c_int, toidx: c_int) {
+    lua_lock(L);
+    let fr = index2addr(L, fromidx);
+    let to = index2addr(L, toidx);
+    api_checkvalidindex(L, to);
+    setobj(to, fr);
+    if isupvalue(toidx) {  /* function upvalue? */
+        luaC_barrier(L, (*(*(*L).ci).func).clCvalue() as *mut GCObject, fr);
+    }
+    /* LUA_REGISTRYINDEX does not need gc barrier
+       (collector revisits it before finishing collection) */
+    lua_unlock(L);
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_pushvalue(L: *mut lua_State, idx: c_int) {
+    lua_lock(L);
+    setobj((*L).top, index2addr(L, idx));
+    api_incr_top(L);
+    lua_unlock(L);
+}
+
+
+
+/*
+** access functions (stack -> C)
+*/
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_type(L: *mut lua_State, idx: c_int) -> c_int {
+    let o = index2addr(L, idx);
+    if isvalid(o) { (*o).ttnov() } else { lua::LUA_TNONE }
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_typename(L: *mut lua_State, t: c_int) -> *const c_char {
+    api_check!(L, (lua::LUA_TNONE..lua::LUA_NUMTAGS).contains(&t), "invalid tag {}", t);
+    ttypename(t)
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_iscfunction(L: *mut lua_State, idx: c_int) -> c_int {
+    let o = index2addr(L, idx);
+    ((*o).ttislcf() || (*o).ttisCclosure()) as c_int
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_isinteger(L: *mut lua_State, idx: c_int) -> c_int {
+    let o = index2addr(L, idx);
+    (*o).ttisinteger() as c_int
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_isnumber(L: *mut lua_State, idx: c_int) -> c_int {
+    let mut n = 0.0;
+    let o = index2addr(L, idx);
+    tonumber(o, &mut n) as c_int
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_isstring(L: *mut lua_State, idx: c_int) -> c_int {
+    let o = index2addr(L, idx);
+    ((*o).ttisstring() || (*o).ttisnumber()) as c_int  /* 'cvt2str' */
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_isuserdata(L: *mut lua_State, idx: c_int) -> c_int {
+    let o = index2addr(L, idx);
+    ((*o).ttisfulluserdata() || (*o).ttislightuserdata()) as c_int
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_rawequal(L: *mut lua_State, index1: c_int, index2: c_int) -> c_int {
+    let o1 = index2addr(L, index1);
+    let o2 = index2addr(L, index2);
+    if isvalid(o1) && isvalid(o2) { luaV_equalobj(ptr::null_mut(), o1, o2) } else { 0 }
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_arith(L: *mut lua_State, op: c_int) {
+    lua_lock(L);
+    if op != lua::LUA_OPUNM && op != lua::LUA_OPBNOT {
+        api_checknelems(L, 2);  /* all other operations expect two operands */
+    } else {  /* for unary operations, add fake 2nd operand */
+        api_checknelems(L, 1);
+        setobj((*L).top, (*L).top.sub(1));
+        api_incr_top(L);
+    }
+    /* first operand at top - 2, second at top - 1; result go to top - 2 */
+    luaO_arith(L, op, (*L).top.sub(2), (*L).top.sub(1), (*L).top.sub(2));
+    (*L).top = (*L).top.sub(1);  /* remove second operand */
+    lua_unlock(L);
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_compare(L: *mut lua_State, index1: c_int, index2: c_int,
+                                            op: c_int) -> c_int {
+    let mut i = 0;
+    lua_lock(L);  /* may call tag method */
+    let o1 = index2addr(L, index1);
+    let o2 = index2addr(L, index2);
+    if isvalid(o1) && isvalid(o2) {
+        match op {
+            lua::LUA_OPEQ => i = luaV_equalobj(L, o1, o2),
+            lua::LUA_OPLT => i = luaV_lessthan(L, o1, o2),
+            lua::LUA_OPLE => i = luaV_lessequal(L, o1, o2),
+            _ => api_check!(L, false, "invalid option {}", op),
+        }
+    }
+    lua_unlock(L);
+    i
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_stringtonumber(L: *mut lua_State, s: *const c_char) -> size_t {
+    let sz = luaO_str2num(s, (*L).top);
+    if sz != 0 {
+        api_incr_top(L);
+    }
+    sz
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_tonumberx(L: *mut lua_State, idx: c_int,
+                                              pisnum: *mut c_int) -> lua_Number {
+    let mut n = 0.0;
+    let o = index2addr(L, idx);
+    let isnum = tonumber(o, &mut n);
+    if !isnum {
+        n = 0.0;  /* call to 'tonumber' may change 'n' even if it fails */
+    }
+    if !pisnum.is_null() { *pisnum = isnum as c_int; }
+    n
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_tointegerx(L: *mut lua_State, idx: c_int,
+                                               pisnum: *mut c_int) -> lua_Integer {
+    let mut res = 0;
+    let o = index2addr(L, idx);
+    let isnum = tointeger(o, &mut res);
+    if !isnum {
+        res = 0;  /* call to 'tointeger' may change 'n' even if it fails */
+    }
+    if !pisnum.is_null() { *pisnum = isnum as c_int; }
+    res
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_toboolean(L: *mut lua_State, idx: c_int) -> c_int {
+    let o = index2addr(L, idx);
+    !l_isfalse(o) as c_int
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_tolstring(L: *mut lua_State, idx: c_int,
+                                              len: *mut size_t) -> *const c_char {
+    let mut o = index2addr(L, idx);
+    if !(*o).ttisstring() {
+        if !(*o).ttisnumber() {  /* not convertible? */
+            if !len.is_null() { *len = 0; }
+            return ptr::null();
+        }
+        lua_lock(L);  /* 'luaO_tostring' may create a new string */
+        luaO_tostring(L, o);
+        luaC_checkGC(L);
+        o = index2addr(L, idx);  /* previous call may reallocate the stack */
+        lua_unlock(L);
+    }
+    if !len.is_null() {
+        *len = vslen(o);
+    }
+    svalue(o)
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_rawlen(L: *mut lua_State, idx: c_int) -> size_t {
+    let o = index2addr(L, idx);
+    match (*o).ttype() {
+        LUA_TSHRSTR => (*(*o).tsvalue()).shrlen as size_t,
+        LUA_TLNGSTR => (*(*o).tsvalue()).u.lnglen,
+        lua::LUA_TUSERDATA => (*(*o).uvalue()).len,
+        lua::LUA_TTABLE => luaH_getn((*o).hvalue()) as c_uint as size_t,
+        _ => 0,
+    }
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_tocfunction(L: *mut lua_State, idx: c_int) -> lua_CFunction {
+    let o = index2addr(L, idx);
+    if (*o).ttislcf() {
+        (*o).fvalue()
+    } else if (*o).ttisCclosure() {
+        (*(*o).clCvalue()).f
+    } else {
+        None  /* not a C function */
+    }
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_touserdata(L: *mut lua_State, idx: c_int) -> *mut c_void {
+    let o = index2addr(L, idx);
+    match (*o).ttnov() {
+        lua::LUA_TUSERDATA => getudatamem((*o).uvalue()),
+        lua::LUA_TLIGHTUSERDATA => (*o).pvalue(),
+        _ => ptr::null_mut(),
+    }
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_tothread(L: *mut lua_State, idx: c_int) -> *mut lua_State {
+    let o = index2addr(L, idx);
+    if !(*o).ttisthread() { ptr::null_mut() } else { (*o).thvalue() }
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_topointer(L: *mut lua_State, idx: c_int) -> *const c_void {
+    let o = index2addr(L, idx);
+    match (*o).ttype() {
+        lua::LUA_TTABLE => (*o).hvalue() as *const c_void,
+        LUA_TLCL => (*o).clLvalue() as *const c_void,
+        LUA_TCCL => (*o).clCvalue() as *const c_void,
+        LUA_TLCF => (*o).fvalue().map_or(ptr::null(), |f| f as *const c_void),
+        lua::LUA_TTHREAD => (*o).thvalue() as *const c_void,
+        lua::LUA_TUSERDATA => getudatamem((*o).uvalue()),
+        lua::LUA_TLIGHTUSERDATA => (*o).pvalue(),
+        _ => ptr::null(),
+    }
+}
+
+
+
+/*
+** push functions (C -> stack)
+*/
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_pushnil(L: *mut lua_State) {
+    lua_lock(L);
+    setnilvalue((*L).top);
+    api_incr_top(L);
+    lua_unlock(L);
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_pushnumber(L: *mut lua_State, n: lua_Number) {
+    lua_lock(L);
+    setfltvalue((*L).top, n);
+    api_incr_top(L);
+    lua_unlock(L);
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_pushinteger(L: *mut lua_State, n: lua_Integer) {
+    lua_lock(L);
+    setivalue((*L).top, n);
+    api_incr_top(L);
+    lua_unlock(L);
+}
+
+
+/*
+** Pushes on the stack a string with given length. Avoid using 's' when
+** 'len' == 0 (as 's' can be NULL in that case), due to later use of
+** 'memcmp' and 'memcpy'.
+*/
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_pushlstring(L: *mut lua_State, s: *const c_char,
+                                                len: size_t) -> *const c_char {
+    lua_lock(L);
+    let ts = if len == 0 { luaS_new(L, c_str!("")) } else { luaS_newlstr(L, s, len) };
+    setsvalue((*L).top, ts);
+    api_incr_top(L);
+    luaC_checkGC(L);
+    lua_unlock(L);
+    getstr(ts)
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_pushstring(L: *mut lua_State, mut s: *const c_char) -> *const c_char {
+    lua_lock(L);
+    if s.is_null() {
+        setnilvalue((*L).top);
+    } else {
+        let ts = luaS_new(L, s);
+        setsvalue((*L).top, ts);
+        s = getstr(ts);  /* internal copy's address */
+    }
+    api_incr_top(L);
+    luaC_checkGC(L);
+    lua_unlock(L);
+    s
+}
+
+
+/// Pushes onto the stack of `L` a string formatted like `lua_pushfstring`
+/// does and returns a pointer to it; see the `push_fstring!` macro. Raises
+/// a Lua error if an argument is missing or does not fit its option.
+///
+/// # Safety
+///
+/// `L` must be a valid Lua state with room for one more stack slot.
+pub unsafe fn lua_pushfargs(L: *mut lua_State, fmt: &str, args: &[FArg]) -> *const c_char {
+    lua_lock(L);
+    let ret = pushfstring(L, fmt.as_bytes(), args);
+    luaC_checkGC(L);
+    lua_unlock(L);
+    ret
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_pushcclosure(L: *mut lua_State, f: lua_CFunction, mut n: c_int) {
+    lua_lock(L);
+    if n == 0 {
+        setfvalue((*L).top, f);
+    } else {
+        api_checknelems(L, n);
+        api_check!(L, n <= MAXUPVAL, "upvalue index {} too large", n);
+        let cl = luaF_newCclosure(L, n);
+        (*cl).f = f;
+        (*L).top = (*L).top.offset(-n as isize);
+        while n > 0 {
+            n -= 1;
+            setobj((*cl).upvalue.as_mut_ptr().add(n as usize), (*L).top.add(n as usize));
+            /* does not need barrier because closure is white */
+        }
+        setclCvalue((*L).top, cl);
+    }
+    api_incr_top(L);
+    luaC_checkGC(L);
+    lua_unlock(L);
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_pushboolean(L: *mut lua_State, b: c_int) {
+    lua_lock(L);
+    setbvalue((*L).top, (b != 0) as c_int);  /* ensure that true is 1 */
+    api_incr_top(L);
+    lua_unlock(L);
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_pushlightuserdata(L: *mut lua_State, p: *mut c_void) {
+    lua_lock(L);
+    setpvalue((*L).top, p);
+    api_incr_top(L);
+    lua_unlock(L);
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_pushthread(L: *mut lua_State) -> c_int {
+    lua_lock(L);
+    setthvalue((*L).top, L);
+    api_incr_top(L);
+    lua_unlock(L);
+    ((*G(L)).mainthread == L) as c_int
+}
+
+
+
+/*
+** get functions (Lua -> stack)
+*/
+
+
+unsafe fn auxgetstr(L: *mut lua_State, t: *const TValue, k: *const c_char) -> c_int {
+    let mut slot = ptr::null();
+    let str = luaS_new(L, k);
+    if fastget(t, &mut slot, |h| luaH_getstr(h, str)) {
+        setobj((*L).top, slot);
+        api_incr_top(L);
+    } else {
+        setsvalue((*L).top, str);
+        api_incr_top(L);
+        luaV_finishget(L, t, (*L).top.sub(1), (*L).top.sub(1), slot);
+    }
+    lua_unlock(L);
+    (*(*L).top.sub(1)).ttnov()
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_getglobal(L: *mut lua_State, name: *const c_char) -> c_int {
+    let reg = (*G(L)).l_registry.hvalue();
+    lua_lock(L);
+    auxgetstr(L, luaH_getint(reg, LUA_RIDX_GLOBALS), name)
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_gettable(L: *mut lua_State, idx: c_int) -> c_int {
+    lua_lock(L);
+    let t = index2addr(L, idx);
+    luaV_gettable(L, t, (*L).top.sub(1), (*L).top.sub(1));
+    lua_unlock(L);
+    (*(*L).top.sub(1)).ttnov()
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_getfield(L: *mut lua_State, idx: c_int, k: *const c_char) -> c_int {
+    lua_lock(L);
+    auxgetstr(L, index2addr(L, idx), k)
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_geti(L: *mut lua_State, idx: c_int, n: lua_Integer) -> c_int {
+    let mut slot = ptr::null();
+    lua_lock(L);
+    let t = index2addr(L, idx);
+    if fastget(t, &mut slot, |h| luaH_getint(h, n)) {
+        setobj((*L).top, slot);
+        api_incr_top(L);
+    } else {
+        setivalue((*L).top, n);
+        api_incr_top(L);
+        luaV_finishget(L, t, (*L).top.sub(1), (*L).top.sub(1), slot);
+    }
+    lua_unlock(L);
+    (*(*L).top.sub(1)).ttnov()
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_rawget(L: *mut lua_State, idx: c_int) -> c_int {
+    lua_lock(L);
+    let t = index2addr(L, idx);
+    api_check!(L, (*t).ttistable(), "table expected");
+    setobj((*L).top.sub(1), luaH_get((*t).hvalue(), (*L).top.sub(1)));
+    lua_unlock(L);
+    (*(*L).top.sub(1)).ttnov()
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_rawgeti(L: *mut lua_State, idx: c_int, n: lua_Integer) -> c_int {
+    lua_lock(L);
+    let t = index2addr(L, idx);
+    api_check!(L, (*t).ttistable(), "table expected");
+    setobj((*L).top, luaH_getint((*t).hvalue(), n));
+    api_incr_top(L);
+    lua_unlock(L);
+    (*(*L).top.sub(1)).ttnov()
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_rawgetp(L: *mut lua_State, idx: c_int, p: *const c_void) -> c_int {
+    let mut k = TValue::nil();
+    lua_lock(L);
+    let t = index2addr(L, idx);
+    api_check!(L, (*t).ttistable(), "table expected");
+    setpvalue(&mut k, p as *mut c_void);
+    setobj((*L).top, luaH_get((*t).hvalue(), &k));
+    api_incr_top(L);
+    lua_unlock(L);
+    (*(*L).top.sub(1)).ttnov()
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_createtable(L: *mut lua_State, narray: c_int, nrec: c_int) {
+    lua_lock(L);
+    let t = luaH_new(L);
+    sethvalue((*L).top, t);
+    api_incr_top(L);
+    if narray > 0 || nrec > 0 {
+        luaH_resize(L, t, narray as c_uint, nrec as c_uint);
+    }
+    luaC_checkGC(L);
+    lua_unlock(L);
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_getmetatable(L: *mut lua_State, objindex: c_int) -> c_int {
+    let mut res = 0;
+    lua_lock(L);
+    let obj = index2addr(L, objindex);
+    let mt = match (*obj).ttnov() {
+        lua::LUA_TTABLE => (*(*obj).hvalue()).metatable,
+        lua::LUA_TUSERDATA => (*(*obj).uvalue()).metatable,
+        _ => (*G(L)).mt[(*obj).ttnov() as usize],
+    };
+    if !mt.is_null() {
+        sethvalue((*L).top, mt);
+        api_incr_top(L);
+        res = 1;
+    }
+    lua_unlock(L);
+    res
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_getuservalue(L: *mut lua_State, idx: c_int) -> c_int {
+    lua_lock(L);
+    let o = index2addr(L, idx);
+    api_check!(L, (*o).ttisfulluserdata(), "full userdata expected");
+    getuservalue((*o).uvalue(), (*L).top);
+    api_incr_top(L);
+    lua_unlock(L);
+    (*(*L).top.sub(1)).ttnov()
+}
+
+
+/*
+** set functions (stack -> Lua)
+*/
+
+/*
+** t[k] = value at the top of the stack (where 'k' is a string)
+*/
+unsafe fn auxsetstr(L: *mut lua_State, t: *const TValue, k: *const c_char) {
+    let mut slot = ptr::null();
+    let str = luaS_new(L, k);
+    api_checknelems(L, 1);
+    if fastset(L, t, &mut slot, |h| luaH_getstr(h, str), (*L).top.sub(1)) {
+        (*L).top = (*L).top.sub(1);  /* pop value */
+    } else {
+        setsvalue((*L).top, str);  /* push 'str' (to make it a TValue) */
+        api_incr_top(L);
+        luaV_finishset(L, t, (*L).top.sub(1), (*L).top.sub(2), slot);
+        (*L).top = (*L).top.sub(2);  /* pop value and key */
+    }
+    lua_unlock(L);  /* lock done by caller */
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_setglobal(L: *mut lua_State, name: *const c_char) {
+    let reg = (*G(L)).l_registry.hvalue();
+    lua_lock(L);  /* unlock done in 'auxsetstr' */
+    auxsetstr(L, luaH_getint(reg, LUA_RIDX_GLOBALS), name);
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_settable(L: *mut lua_State, idx: c_int) {
+    lua_lock(L);
+    api_checknelems(L, 2);
+    let t = index2addr(L, idx);
+    luaV_settable(L, t, (*L).top.sub(2), (*L).top.sub(1));
+    (*L).top = (*L).top.sub(2);  /* pop index and value */
+    lua_unlock(L);
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_setfield(L: *mut lua_State, idx: c_int, k: *const c_char) {
+    lua_lock(L);  /* unlock done in 'auxsetstr' */
+    auxsetstr(L, index2addr(L, idx), k);
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_seti(L: *mut lua_State, idx: c_int, n: lua_Integer) {
+    let mut slot = ptr::null();
+    lua_lock(L);
+    api_checknelems(L, 1);
+    let t = index2addr(L, idx);
+    if fastset(L, t, &mut slot, |h| luaH_getint(h, n), (*L).top.sub(1)) {
+        (*L).top = (*L).top.sub(1);  /* pop value */
+    } else {
+        setivalue((*L).top, n);
+        api_incr_top(L);
+        luaV_finishset(L, t, (*L).top.sub(1), (*L).top.sub(2), slot);
+        (*L).top = (*L).top.sub(2);  /* pop value and key */
+    }
+    lua_unlock(L);
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_rawset(L: *mut lua_State, idx: c_int) {
+    lua_lock(L);
+    api_checknelems(L, 2);
+    let o = index2addr(L, idx);
+    api_check!(L, (*o).ttistable(), "table expected");
+    let slot = luaH_set(L, (*o).hvalue(), (*L).top.sub(2));
+    setobj(slot, (*L).top.sub(1));
+    invalidateTMcache((*o).hvalue());
+    luaC_barrierback(L, (*o).hvalue(), (*L).top.sub(1));
+    (*L).top = (*L).top.sub(2);
+    lua_unlock(L);
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_rawseti(L: *mut lua_State, idx: c_int, n: lua_Integer) {
+    lua_lock(L);
+    api_checknelems(L, 1);
+    let o = index2addr(L, idx);
+    api_check!(L, (*o).ttistable(), "table expected");
+    luaH_setint(L, (*o).hvalue(), n, (*L).top.sub(1));
+    luaC_barrierback(L, (*o).hvalue(), (*L).top.sub(1));
+    (*L).top = (*L).top.sub(1);
+    lua_unlock(L);
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_rawsetp(L: *mut lua_State, idx: c_int, p: *const c_void) {
+    let mut k = TValue::nil();
+    lua_lock(L);
+    api_checknelems(L, 1);
+    let o = index2addr(L, idx);
+    api_check!(L, (*o).ttistable(), "table expected");
+    setpvalue(&mut k, p as *mut c_void);
+    let slot = luaH_set(L, (*o).hvalue(), &k);
+    setobj(slot, (*L).top.sub(1));
+    luaC_barrierback(L, (*o).hvalue(), (*L).top.sub(1));
+    (*L).top = (*L).top.sub(1);
+    lua_unlock(L);
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_setmetatable(L: *mut lua_State, objindex: c_int) -> c_int {
+    lua_lock(L);
+    api_checknelems(L, 1);
+    let obj = index2addr(L, objindex);
+    let mt = if (*(*L).top.sub(1)).ttisnil() {
+        ptr::null_mut()
+    } else {
+        api_check!(L, (*(*L).top.sub(1)).ttistable(), "table expected");
+        (*(*L).top.sub(1)).hvalue()
+    };
+    match (*obj).ttnov() {
+        lua::LUA_TTABLE => {
+            (*(*obj).hvalue()).metatable = mt;
+            if !mt.is_null() {
+                luaC_objbarrier(L, (*obj).gcvalue(), mt as *mut GCObject);
+                luaC_checkfinalizer(L, (*obj).gcvalue(), mt);
+            }
+        }
+        lua::LUA_TUSERDATA => {
+            (*(*obj).uvalue()).metatable = mt;
+            if !mt.is_null() {
+                luaC_objbarrier(L, (*obj).gcvalue(), mt as *mut GCObject);
+                luaC_checkfinalizer(L, (*obj).gcvalue(), mt);
+            }
+        }
+        _ => {
+            (*G(L)).mt[(*obj).ttnov() as usize] = mt;
+        }
+    }
+    (*L).top = (*L).top.sub(1);
+    lua_unlock(L);
+    1
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_setuservalue(L: *mut lua_State, idx: c_int) {
+    lua_lock(L);
+    api_checknelems(L, 1);
+    let o = index2addr(L, idx);
+    api_check!(L, (*o).ttisfulluserdata(), "full userdata expected");
+    setuservalue((*o).uvalue(), (*L).top.sub(1));
+    luaC_barrier(L, (*o).gcvalue(), (*L).top.sub(1));
+    (*L).top = (*L).top.sub(1);
+    lua_unlock(L);
+}
+
+
+/*
+** 'load' and 'call' functions (run Lua code)
+*/
+
+
+#[track_caller]
+unsafe fn checkresults(L: *mut lua_State, na: c_int, nr: c_int) {
+    api_check!(L, nr == LUA_MULTRET || (*(*L).ci).top.offset_from((*L).top) >= (nr - na) as isize,
+               "results from function overflow current stack size");
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_callk(L: *mut lua_State, nargs: c_int, nresults: c_int,
+                                          ctx: lua_KContext, k: lua_KFunction) {
+    lua_lock(L);
+    api_check!(L, k.is_none() || !isLua((*L).ci), "cannot use continuations inside hooks");
+    api_checknelems(L, nargs + 1);
+    api_check!(L, (*L).status as c_int == LUA_OK, "cannot do calls on non-normal thread");
+    checkresults(L, nargs, nresults);
+    let func = (*L).top.offset(-(nargs as isize + 1));
+    if k.is_some() && (*L).nny == 0 {  /* need to prepare continuation? */
+        (*(*L).ci).u.c.k = k;  /* save continuation */
+        (*(*L).ci).u.c.ctx = ctx;  /* save context */
+        luaD_call(L, func, nresults);  /* do the call */
+    } else {  /* no continuation or no yieldable */
+        luaD_callnoyield(L, func, nresults);  /* just do the call */
+    }
+    adjustresults(L, nresults);
+    lua_unlock(L);
+}
+
+
+
+/*
+** Execute a protected call.
+*/
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_pcallk(L: *mut lua_State, nargs: c_int, nresults: c_int,
+                                           errfunc: c_int, ctx: lua_KContext,
+                                           k: lua_KFunction) -> c_int {
+    lua_lock(L);
+    api_check!(L, k.is_none() || !isLua((*L).ci), "cannot use continuations inside hooks");
+    api_checknelems(L, nargs + 1);
+    api_check!(L, (*L).status as c_int == LUA_OK, "cannot do calls on non-normal thread");
+    checkresults(L, nargs, nresults);
+    let func = if errfunc == 0 {
+        0
+    } else {
+        let o = index2addr(L, errfunc);
+        api_checkstackindex(L, errfunc, o);
+        savestack(L, o)
+    };
+    let cfunc = (*L).top.offset(-(nargs as isize + 1));  /* function to be called */
+    let status = if k.is_none() || (*L).nny > 0 {  /* no continuation or no yieldable? */
+        /* do a 'conventional' protected call */
+        status(pcall(L, || luaD_callnoyield(L, cfunc, nresults), savestack(L, cfunc), func))
+    } else {  /* prepare continuation (call is already protected by 'resume') */
+        let ci: *mut CallInfo = (*L).ci;
+        (*ci).u.c.k = k;  /* save continuation */
+        (*ci).u.c.ctx = ctx;  /* save context */
+        /* save information for error recovery */
+        (*ci).extra = savestack(L, cfunc);
+        (*ci).u.c.old_errfunc = (*L).errfunc;
+        (*L).errfunc = func;
+        (*ci).callstatus = ((*ci).callstatus & !CIST_OAH) | (*L).allowhook;  /* save value of 'allowhook' */
+        (*ci).callstatus |= CIST_YPCALL;  /* function can do error recovery */
+        luaD_call(L, cfunc, nresults);  /* do the call */
+        (*ci).callstatus &= !CIST_YPCALL;
+        (*L).errfunc = (*ci).u.c.old_errfunc;
+        LUA_OK  /* if it is here, there were no errors */
+    };
+    adjustresults(L, nresults);
+    lua_unlock(L);
+    status
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_load(L: *mut lua_State, reader: lua_Reader, data: *mut c_void,
+                                         mut chunkname: *const c_char, mode: *const c_char) -> c_int {
+    let mut z: Zio = mem::zeroed();
+    lua_lock(L);
+    if chunkname.is_null() { chunkname = c_str!("?"); }
+    luaZ_init(L, &mut z, reader, data);
+    let status = luaD_protectedparser(L, &mut z, chunkname, mode);
+    if status == LUA_OK {  /* no errors? */
+        let f: *mut LClosure = (*(*L).top.sub(1)).clLvalue();  /* get newly created function */
+        if (*f).nupvalues >= 1 {  /* does it have an upvalue? */
+            /* get global table from registry */
+            let reg = (*G(L)).l_registry.hvalue();
+            let gt = luaH_getint(reg, LUA_RIDX_GLOBALS);
+            /* set global table as 1st upvalue of 'f' (may be LUA_ENV) */
+            let uv: *mut UpVal = (*f).upvals[0];
+            setobj((*uv).v, gt);
+            luaC_upvalbarrier(L, uv);
+        }
+    }
+    lua_unlock(L);
+    status
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_dump(L: *mut lua_State, writer: lua_Writer, data: *mut c_void,
+                                         strip: c_int) -> c_int {
+    lua_lock(L);
+    api_checknelems(L, 1);
+    let o = (*L).top.sub(1);
+    let status = if (*o).ttisLclosure() {
+        luaU_dump(L, (*(*o).clLvalue()).p, writer, data, strip)
+    } else {
+        1
+    };
+    lua_unlock(L);
+    status
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_status(L: *mut lua_State) -> c_int {
+    (*L).status as c_int
+}
+
+
+/*
+** Garbage-collection function
+*/
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_gc(L: *mut lua_State, what: c_int, mut data: c_int) -> c_int {
+    let mut res = 0;
+    lua_lock(L);
+    let g = G(L);
+    match what {
+        lua::LUA_GCSTOP => {
+            (*g).gcrunning = 0;
+        }
+        lua::LUA_GCRESTART => {
+            luaE_setdebt(g, 0);
+            (*g).gcrunning = 1;
+        }
+        lua::LUA_GCCOLLECT => {
+            luaC_fullgc(L, 0);
+        }
+        lua::LUA_GCCOUNT => {
+            /* GC values are expressed in Kbytes: #bytes/2^10 */
+            res = (gettotalbytes(g) >> 10) as c_int;
+        }
+        lua::LUA_GCCOUNTB => {
+            res = (gettotalbytes(g) & 0x3ff) as c_int;
+        }
+        lua::LUA_GCSTEP => {
+            let mut debt = 1;  /* =1 to signal that it did an actual step */
+            let oldrunning = (*g).gcrunning;
+            (*g).gcrunning = 1;  /* allow GC to run */
+            if data == 0 {
+                luaE_setdebt(g, -GCSTEPSIZE);  /* to do a "small" step */
+                luaC_step(L);
+            } else {  /* add 'data' to total debt */
+                debt = data as isize * 1024 + (*g).GCdebt;
+                luaE_setdebt(g, debt);
+                luaC_checkGC(L);
+            }
+            (*g).gcrunning = oldrunning;  /* restore previous state */
+            if debt > 0 && (*g).gcstate == GCSpause {  /* end of cycle? */
+                res = 1;  /* signal it */
+            }
+        }
+        lua::LUA_GCSETPAUSE => {
+            res = (*g).gcpause;
+            (*g).gcpause = data;
+        }
+        lua::LUA_GCSETSTEPMUL => {
+            res = (*g).gcstepmul;
+            if data < 40 { data = 40; }  /* avoid ridiculous low values (and 0) */
+            (*g).gcstepmul = data;
+        }
+        lua::LUA_GCISRUNNING => {
+            res = (*g).gcrunning as c_int;
+        }
+        _ => res = -1,  /* invalid option */
+    }
+    lua_unlock(L);
+    res
+}
+
+
+
+/*
+** miscellaneous functions
+*/
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_error(L: *mut lua_State) -> c_int {
+    lua_lock(L);
+    api_checknelems(L, 1);
+    luaG_errormsg(L);
+    /* code unreachable; will unlock when control actually leaves the kernel */
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_next(L: *mut lua_State, idx: c_int) -> c_int {
+    lua_lock(L);
+    let t = index2addr(L, idx);
+    api_check!(L, (*t).ttistable(), "table expected");
+    let more = luaH_next(L, (*t).hvalue(), (*L).top.sub(1));
+    if more != 0 {
+        api_incr_top(L);
+    } else {  /* no more elements */
+        (*L).top = (*L).top.sub(1);  /* remove key */
+    }
+    lua_unlock(L);
+    more
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_concat(L: *mut lua_State, n: c_int) {
+    lua_lock(L);
+    api_checknelems(L, n);
+    if n >= 2 {
+        luaV_concat(L, n);
+    } else if n == 0 {  /* push empty string */
+        setsvalue((*L).top, luaS_newlstr(L, c_str!(""), 0));
+        api_incr_top(L);
+    }
+    /* else n == 1; nothing to do */
+    luaC_checkGC(L);
+    lua_unlock(L);
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_len(L: *mut lua_State, idx: c_int) {
+    lua_lock(L);
+    let t = index2addr(L, idx);
+    luaV_objlen(L, (*L).top, t);
+    api_incr_top(L);
+    lua_unlock(L);
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_getallocf(L: *mut lua_State, ud: *mut *mut c_void) -> lua_Alloc {
+    lua_lock(L);
+    if !ud.is_null() { *ud = (*G(L)).ud; }
+    let f = (*G(L)).frealloc;
+    lua_unlock(L);
+    f
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_setallocf(L: *mut lua_State, f: lua_Alloc, ud: *mut c_void) {
+    lua_lock(L);
+    (*G(L)).ud = ud;
+    (*G(L)).frealloc = f;
+    lua_unlock(L);
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_newuserdata(L: *mut lua_State, size: size_t) -> *mut c_void {
+    lua_lock(L);
+    let u = luaS_newudata(L, size);
+    setuvalue((*L).top, u);
+    api_incr_top(L);
+    luaC_checkGC(L);
+    lua_unlock(L);
+    getudatamem(u)
+}
+
+
+
+/* where 'aux_upvalue' found the upvalue, for the barrier of 'lua_setupvalue' */
+enum Owner {
+    C(*mut CClosure),
+    Lua(*mut UpVal),
+}
+
+unsafe fn aux_upvalue(fi: StkId, n: c_int) -> Option<(*const c_char, *mut TValue, Owner)> {
+    match (*fi).ttype() {
+        LUA_TCCL => {  /* C closure */
+            let f = (*fi).clCvalue();
+            if !(1 <= n && n <= (*f).nupvalues as c_int) { return None; }
+            Some((c_str!(""), (*f).upvalue.as_mut_ptr().add(n as usize - 1), Owner::C(f)))
+        }
+        LUA_TLCL => {  /* Lua closure */
+            let f = (*fi).clLvalue();
+            let p = (*f).p;
+            if !(1 <= n && n <= (*p).sizeupvalues) { return None; }
+            let uv = *(*f).upvals.as_ptr().add(n as usize - 1);
+            let name = (*(*p).upvalues.add(n as usize - 1)).name;
+            Some((if name.is_null() { c_str!("(*no name)") } else { getstr(name) },
+                  (*uv).v, Owner::Lua(uv)))
+        }
+        _ => None,  /* not a closure */
+    }
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_getupvalue(L: *mut lua_State, funcindex: c_int,
+                                               n: c_int) -> *const c_char {
+    lua_lock(L);
+    let name = match aux_upvalue(index2addr(L, funcindex), n) {
+        Some((name, val, _)) => {
+            setobj((*L).top, val);
+            api_incr_top(L);
+            name
+        }
+        None => ptr::null(),
+    };
+    lua_unlock(L);
+    name
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_setupvalue(L: *mut lua_State, funcindex: c_int,
+                                               n: c_int) -> *const c_char {
+    lua_lock(L);
+    let fi = index2addr(L, funcindex);
+    api_checknelems(L, 1);
+    let name = match aux_upvalue(fi, n) {
+        Some((name, val, owner)) => {
+            (*L).top = (*L).top.sub(1);
+            setobj(val, (*L).top);
+            match owner {
+                Owner::C(f) => luaC_barrier(L, f as *mut GCObject, (*L).top),
+                Owner::Lua(uv) => luaC_upvalbarrier(L, uv),
+            }
+            name
+        }
+        None => ptr::null(),
+    };
+    lua_unlock(L);
+    name
+}
+
+
+#[track_caller]
+unsafe fn getupvalref(L: *mut lua_State, fidx: c_int, n: c_int) -> (*mut *mut UpVal, *mut LClosure) {
+    let fi = index2addr(L, fidx);
+    api_check!(L, (*fi).ttisLclosure(), "Lua function expected");
+    let f = (*fi).clLvalue();
+    api_check!(L, 1 <= n && n <= (*(*f).p).sizeupvalues, "invalid upvalue index {}", n);
+    ((*f).upvals.as_mut_ptr().add(n as usize - 1), f)  /* get its upvalue pointer */
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_upvalueid(L: *mut lua_State, fidx: c_int, n: c_int) -> *mut c_void {
+    let fi = index2addr(L, fidx);
+    match (*fi).ttype() {
+        LUA_TLCL => {  /* lua closure */
+            *getupvalref(L, fidx, n).0 as *mut c_void
+        }
+        LUA_TCCL => {  /* C closure */
+            let f = (*fi).clCvalue();
+            api_check!(L, 1 <= n && n <= (*f).nupvalues as c_int, "invalid upvalue index {}", n);
+            (*f).upvalue.as_mut_ptr().add(n as usize - 1) as *mut c_void
+        }
+        _ => {
+            api_check!(L, false, "closure expected");
+            ptr::null_mut()
+        }
+    }
+}
+
+
+#[cfg(rust_lapi)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_upvaluejoin(L: *mut lua_State, fidx1: c_int, n1: c_int,
+                                                fidx2: c_int, n2: c_int) {
+    let (up1, _) = getupvalref(L, fidx1, n1);
+    let (up2, _) = getupvalref(L, fidx2, n2);
+    luaC_upvdeccount(L, *up1);
+    *up1 = *up2;
+    (**up1).refcount += 1;
+    if upisopen(*up1) { (**up1).u.open.touched = 1; }
+    luaC_upvalbarrier(L, *up1);
+}
@@ -0,0 +1,31 @@
+/*
+** Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+** Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+** Distributed under the MIT License.
+**    (See accompanying file LICENSE.txt or copy at
+**          http://opensource.org/licenses/MIT)
+**
+** The variadic part of the auxiliary library, which stable Rust cannot
+** define. Everything else in 'lauxlib.h' is implemented in 'lauxlib.rs'.
+*/
+
+#include <stdarg.h>
+
+#include "lua.h"
+#include "lauxlib.h"
+
+
+/*
+** Again, the use of 'lua_pushvfstring' ensures this function does
+** not need reserved stack space when called. (At worst, it generates
+** an error with "stack overflow" instead of the given message.)
+*/
+LUALIB_API int luaL_error (lua_State *L, const char *fmt, ...) {
+  va_list argp;
+  va_start(argp, fmt);
+  luaL_where(L, 1);
+  lua_pushvfstring(L, fmt, argp);
+  va_end(argp);
+  lua_concat(L, 2);
+  return lua_error(L);
+}
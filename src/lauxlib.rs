@@ -0,0 +1,1090 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Auxiliary functions for building Lua libraries.
+//!
+//! Every function keeps the C name and signature of `lauxlib.h`, so C
+//! modules compiled against that header link against these definitions.
+//! The only exception is the variadic `luaL_error`, which stable Rust
+//! cannot define; it lives in `src/lauxlib.c` on top of `luaL_where`.
+
+#![allow(non_snake_case)]
+#![allow(clippy::missing_safety_doc)]  /* the contracts of the C API, as the manual states them */
+
+use ffi::lua::{self, lua_Debug, lua_State, lua_CFunction, lua_Integer, lua_Number};
+use ffi::lauxlib::{self, luaL_Buffer, luaL_Reg, LUA_ERRFILE, LUA_REFNIL};
+use ffi::lauxlib::{LUAL_NUMSIZES, luaL_getmetatable, luaL_typename, luaL_addsize};
+use libc::{c_char, c_int, c_void, size_t};
+use std::ffi::CStr;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::{mem, ptr};
+
+
+/* must agree with the value the C core was compiled with */
+#[cfg(ltests)]
+const LUAL_BUFFERSIZE: size_t = 23;  /* as set by 'ltests.h' */
+#[cfg(not(ltests))]
+const LUAL_BUFFERSIZE: size_t = ::ffi::luaconf::LUAL_BUFFERSIZE as size_t;
+
+
+/*
+** {======================================================
+** Traceback
+** =======================================================
+*/
+
+
+const LEVELS1: c_int = 10;  /* size of the first part of the stack */
+const LEVELS2: c_int = 11;  /* size of the second part of the stack */
+
+
+/*
+** search for 'objidx' in table at index -1.
+** return 1 + string at top if find a good name.
+*/
+unsafe fn findfield(l: *mut lua_State, objidx: c_int, level: c_int) -> bool {
+    if level == 0 || lua::lua_istable(l, -1) == 0 {
+        return false;  /* not found */
+    }
+    lua::lua_pushnil(l);  /* start 'next' loop */
+    while lua::lua_next(l, -2) != 0 {  /* for each pair in table */
+        if lua::lua_type(l, -2) == lua::LUA_TSTRING {  /* ignore non-string keys */
+            if lua::lua_rawequal(l, objidx, -1) != 0 {  /* found object? */
+                lua::lua_pop(l, 1);  /* remove value (but keep name) */
+                return true;
+            } else if findfield(l, objidx, level - 1) {  /* try recursively */
+                lua::lua_remove(l, -2);  /* remove table (but keep name) */
+                lua::lua_pushstring(l, c_str!("."));
+                lua::lua_insert(l, -2);  /* place '.' between the two names */
+                lua::lua_concat(l, 3);
+                return true;
+            }
+        }
+        lua::lua_pop(l, 1);  /* remove value */
+    }
+    false  /* not found */
+}
+
+
+/*
+** Search for a name for a function in all loaded modules
+** (registry._LOADED).
+*/
+unsafe fn pushglobalfuncname(l: *mut lua_State, ar: *mut lua_Debug) -> bool {
+    let top = lua::lua_gettop(l);
+    lua::lua_getinfo(l, c_str!("f"), ar);  /* push function */
+    lua::lua_getfield(l, lua::LUA_REGISTRYINDEX, c_str!("_LOADED"));
+    if findfield(l, top + 1, 2) {
+        let name = lua::lua_tostring(l, -1);
+        if CStr::from_ptr(name).to_bytes().starts_with(b"_G.") {  /* name start with '_G.'? */
+            lua::lua_pushstring(l, name.add(3));  /* push name without prefix */
+            lua::lua_remove(l, -2);  /* remove original name */
+        }
+        lua::lua_copy(l, -1, top + 1);  /* move name to proper place */
+        lua::lua_pop(l, 2);  /* remove pushed values */
+        true
+    } else {
+        lua::lua_settop(l, top);  /* remove function and global table */
+        false
+    }
+}
+
+
+unsafe fn pushfuncname(l: *mut lua_State, ar: *mut lua_Debug) {
+    if pushglobalfuncname(l, ar) {  /* try first a global name */
+        lua::lua_pushfstring(l, c_str!("function '%s'"), lua::lua_tostring(l, -1));
+        lua::lua_remove(l, -2);  /* remove name */
+    } else if *(*ar).namewhat != 0 {  /* is there a name from code? */
+        lua::lua_pushfstring(l, c_str!("%s '%s'"), (*ar).namewhat, (*ar).name);  /* use it */
+    } else if *(*ar).what == b'm' as c_char {  /* main? */
+        lua::lua_pushstring(l, c_str!("main chunk"));
+    } else if *(*ar).what != b'C' as c_char {  /* for Lua functions, use <file:line> */
+        lua::lua_pushfstring(l, c_str!("function <%s:%d>"),
+                             (*ar).short_src.as_ptr(), (*ar).linedefined);
+    } else {  /* nothing left... */
+        lua::lua_pushstring(l, c_str!("?"));
+    }
+}
+
+
+unsafe fn lastlevel(l: *mut lua_State) -> c_int {
+    let mut ar: lua_Debug = mem::zeroed();
+    let (mut li, mut le) = (1, 1);
+    /* find an upper bound */
+    while lua::lua_getstack(l, le, &mut ar) != 0 { li = le; le *= 2; }
+    /* do a binary search */
+    while li < le {
+        let m = (li + le) / 2;
+        if lua::lua_getstack(l, m, &mut ar) != 0 { li = m + 1; }
+        else { le = m; }
+    }
+    le - 1
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_traceback(l: *mut lua_State, l1: *mut lua_State,
+                                        msg: *const c_char, mut level: c_int) {
+    let mut ar: lua_Debug = mem::zeroed();
+    let top = lua::lua_gettop(l);
+    let last = lastlevel(l1);
+    let mut n1 = if last - level > LEVELS1 + LEVELS2 { LEVELS1 } else { -1 };
+    if !msg.is_null() {
+        lua::lua_pushfstring(l, c_str!("%s\n"), msg);
+    }
+    luaL_checkstack(l, 10, ptr::null());
+    lua::lua_pushstring(l, c_str!("stack traceback:"));
+    while lua::lua_getstack(l1, level, &mut ar) != 0 {
+        level += 1;
+        n1 -= 1;
+        if n1 == -1 {  /* too many levels? */
+            lua::lua_pushstring(l, c_str!("\n\t..."));  /* add a '...' */
+            level = last - LEVELS2 + 1;  /* and skip to last ones */
+        } else {
+            lua::lua_getinfo(l1, c_str!("Slnt"), &mut ar);
+            lua::lua_pushfstring(l, c_str!("\n\t%s:"), ar.short_src.as_ptr());
+            if ar.currentline > 0 {
+                lua::lua_pushfstring(l, c_str!("%d:"), ar.currentline);
+            }
+            lua::lua_pushstring(l, c_str!(" in "));
+            pushfuncname(l, &mut ar);
+            if ar.istailcall != 0 {
+                lua::lua_pushstring(l, c_str!("\n\t(...tail calls...)"));
+            }
+            lua::lua_concat(l, lua::lua_gettop(l) - top);
+        }
+    }
+    lua::lua_concat(l, lua::lua_gettop(l) - top);
+}
+
+/* }====================================================== */
+
+
+/*
+** {======================================================
+** Error-report functions
+** =======================================================
+*/
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_argerror(l: *mut lua_State, mut arg: c_int,
+                                       extramsg: *const c_char) -> c_int {
+    let mut ar: lua_Debug = mem::zeroed();
+    if lua::lua_getstack(l, 0, &mut ar) == 0 {  /* no stack frame? */
+        return lauxlib::luaL_error(l, c_str!("bad argument #%d (%s)"), arg, extramsg);
+    }
+    lua::lua_getinfo(l, c_str!("n"), &mut ar);
+    if CStr::from_ptr(ar.namewhat).to_bytes() == b"method" {
+        arg -= 1;  /* do not count 'self' */
+        if arg == 0 {  /* error is in the self argument itself? */
+            return lauxlib::luaL_error(l, c_str!("calling '%s' on bad self (%s)"),
+                                       ar.name, extramsg);
+        }
+    }
+    if ar.name.is_null() {
+        ar.name = if pushglobalfuncname(l, &mut ar) { lua::lua_tostring(l, -1) }
+                  else { c_str!("?") };
+    }
+    lauxlib::luaL_error(l, c_str!("bad argument #%d to '%s' (%s)"), arg, ar.name, extramsg)
+}
+
+
+unsafe fn typeerror(l: *mut lua_State, arg: c_int, tname: *const c_char) -> c_int {
+    /* name for the type of the actual argument */
+    let typearg = if luaL_getmetafield(l, arg, c_str!("__name")) == lua::LUA_TSTRING {
+        lua::lua_tostring(l, -1)  /* use the given type name */
+    } else if lua::lua_type(l, arg) == lua::LUA_TLIGHTUSERDATA {
+        c_str!("light userdata")  /* special name for messages */
+    } else {
+        luaL_typename(l, arg)  /* standard name */
+    };
+    let msg = lua::lua_pushfstring(l, c_str!("%s expected, got %s"), tname, typearg);
+    luaL_argerror(l, arg, msg)
+}
+
+
+unsafe fn tag_error(l: *mut lua_State, arg: c_int, tag: c_int) {
+    typeerror(l, arg, lua::lua_typename(l, tag));
+}
+
+
+/*
+** The use of 'lua_pushfstring' ensures this function does not
+** need reserved stack space when called.
+*/
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_where(l: *mut lua_State, level: c_int) {
+    let mut ar: lua_Debug = mem::zeroed();
+    if lua::lua_getstack(l, level, &mut ar) != 0 {  /* check function at level */
+        lua::lua_getinfo(l, c_str!("Sl"), &mut ar);  /* get info about it */
+        if ar.currentline > 0 {  /* is there info? */
+            lua::lua_pushfstring(l, c_str!("%s:%d: "), ar.short_src.as_ptr(), ar.currentline);
+            return;
+        }
+    }
+    lua::lua_pushfstring(l, c_str!(""));  /* else, no information available... */
+}
+
+
+/* pushes the message of error 'e', using 'strerror' for system errors */
+unsafe fn pusherror(l: *mut lua_State, e: &io::Error) {
+    match e.raw_os_error() {
+        Some(code) => { lua::lua_pushstring(l, libc::strerror(code)); }
+        None => {
+            let msg = e.to_string();
+            lua::lua_pushlstring(l, msg.as_ptr() as *const c_char, msg.len());
+        }
+    }
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_fileresult(l: *mut lua_State, stat: c_int,
+                                         fname: *const c_char) -> c_int {
+    /* calls to Lua API may change this value */
+    let en = io::Error::last_os_error().raw_os_error().unwrap_or(0);
+    if stat != 0 {
+        lua::lua_pushboolean(l, 1);
+        1
+    } else {
+        lua::lua_pushnil(l);
+        if !fname.is_null() {
+            lua::lua_pushfstring(l, c_str!("%s: %s"), fname, libc::strerror(en));
+        } else {
+            lua::lua_pushstring(l, libc::strerror(en));
+        }
+        lua::lua_pushinteger(l, en as lua_Integer);
+        3
+    }
+}
+
+
+/*
+** use appropriate macros to interpret 'pclose' return status
+*/
+#[cfg(unix)]
+fn inspectstat(stat: c_int) -> (c_int, *const c_char) {
+    if libc::WIFEXITED(stat) { (libc::WEXITSTATUS(stat), c_str!("exit")) }
+    else if libc::WIFSIGNALED(stat) { (libc::WTERMSIG(stat), c_str!("signal")) }
+    else { (stat, c_str!("exit")) }
+}
+
+#[cfg(not(unix))]
+fn inspectstat(stat: c_int) -> (c_int, *const c_char) {
+    (stat, c_str!("exit"))  /* no op */
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_execresult(l: *mut lua_State, stat: c_int) -> c_int {
+    if stat == -1 {  /* error? */
+        return luaL_fileresult(l, 0, ptr::null());
+    }
+    let (stat, what) = inspectstat(stat);  /* interpret result */
+    if *what == b'e' as c_char && stat == 0 {  /* successful termination? */
+        lua::lua_pushboolean(l, 1);
+    } else {
+        lua::lua_pushnil(l);
+    }
+    lua::lua_pushstring(l, what);
+    lua::lua_pushinteger(l, stat as lua_Integer);
+    3  /* return true/nil,what,code */
+}
+
+/* }====================================================== */
+
+
+/*
+** {======================================================
+** Userdata's metatable manipulation
+** =======================================================
+*/
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_newmetatable(l: *mut lua_State, tname: *const c_char) -> c_int {
+    luaL_getmetatable(l, tname);
+    if lua::lua_type(l, -1) != lua::LUA_TNIL {  /* name already in use? */
+        return 0;  /* leave previous value on top, but return 0 */
+    }
+    lua::lua_pop(l, 1);
+    lua::lua_createtable(l, 0, 2);  /* create metatable */
+    lua::lua_pushstring(l, tname);
+    lua::lua_setfield(l, -2, c_str!("__name"));  /* metatable.__name = tname */
+    lua::lua_pushvalue(l, -1);
+    lua::lua_setfield(l, lua::LUA_REGISTRYINDEX, tname);  /* registry.name = metatable */
+    1
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_setmetatable(l: *mut lua_State, tname: *const c_char) {
+    luaL_getmetatable(l, tname);
+    lua::lua_setmetatable(l, -2);
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_testudata(l: *mut lua_State, ud: c_int,
+                                        tname: *const c_char) -> *mut c_void {
+    let mut p = lua::lua_touserdata(l, ud);
+    if !p.is_null() {  /* value is a userdata? */
+        if lua::lua_getmetatable(l, ud) != 0 {  /* does it have a metatable? */
+            luaL_getmetatable(l, tname);  /* get correct metatable */
+            if lua::lua_rawequal(l, -1, -2) == 0 {  /* not the same? */
+                p = ptr::null_mut();  /* value is a userdata with wrong metatable */
+            }
+            lua::lua_pop(l, 2);  /* remove both metatables */
+            return p;
+        }
+    }
+    ptr::null_mut()  /* value is not a userdata with a metatable */
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_checkudata(l: *mut lua_State, ud: c_int,
+                                         tname: *const c_char) -> *mut c_void {
+    let p = luaL_testudata(l, ud, tname);
+    if p.is_null() { typeerror(l, ud, tname); }
+    p
+}
+
+/* }====================================================== */
+
+
+/*
+** {======================================================
+** Argument check functions
+** =======================================================
+*/
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_checkoption(l: *mut lua_State, arg: c_int, def: *const c_char,
+                                          lst: *const *const c_char) -> c_int {
+    let name = if !def.is_null() { luaL_optlstring(l, arg, def, ptr::null_mut()) }
+               else { luaL_checklstring(l, arg, ptr::null_mut()) };
+    let mut i = 0;
+    while !(*lst.offset(i)).is_null() {
+        if libc::strcmp(*lst.offset(i), name) == 0 {
+            return i as c_int;
+        }
+        i += 1;
+    }
+    luaL_argerror(l, arg, lua::lua_pushfstring(l, c_str!("invalid option '%s'"), name))
+}
+
+
+/*
+** Ensures the stack has at least 'space' extra slots, raising an error
+** if it cannot fulfill the request. (The error handling needs a few
+** extra slots to format the error message. In case of an error without
+** this extra space, Lua will generate the same 'stack overflow' error,
+** but without 'msg'.)
+*/
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_checkstack(l: *mut lua_State, space: c_int, msg: *const c_char) {
+    if lua::lua_checkstack(l, space) == 0 {
+        if !msg.is_null() {
+            lauxlib::luaL_error(l, c_str!("stack overflow (%s)"), msg);
+        } else {
+            lauxlib::luaL_error(l, c_str!("stack overflow"));
+        }
+    }
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_checktype(l: *mut lua_State, arg: c_int, t: c_int) {
+    if lua::lua_type(l, arg) != t {
+        tag_error(l, arg, t);
+    }
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_checkany(l: *mut lua_State, arg: c_int) {
+    if lua::lua_type(l, arg) == lua::LUA_TNONE {
+        luaL_argerror(l, arg, c_str!("value expected"));
+    }
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_checklstring(l: *mut lua_State, arg: c_int,
+                                           len: *mut size_t) -> *const c_char {
+    let s = lua::lua_tolstring(l, arg, len);
+    if s.is_null() { tag_error(l, arg, lua::LUA_TSTRING); }
+    s
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_optlstring(l: *mut lua_State, arg: c_int, def: *const c_char,
+                                         len: *mut size_t) -> *const c_char {
+    if lua::lua_isnoneornil(l, arg) != 0 {
+        if !len.is_null() {
+            *len = if !def.is_null() { libc::strlen(def) } else { 0 };
+        }
+        def
+    } else {
+        luaL_checklstring(l, arg, len)
+    }
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_checknumber(l: *mut lua_State, arg: c_int) -> lua_Number {
+    let mut isnum = 0;
+    let d = lua::lua_tonumberx(l, arg, &mut isnum);
+    if isnum == 0 {
+        tag_error(l, arg, lua::LUA_TNUMBER);
+    }
+    d
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_optnumber(l: *mut lua_State, arg: c_int,
+                                        def: lua_Number) -> lua_Number {
+    if lua::lua_isnoneornil(l, arg) != 0 { def } else { luaL_checknumber(l, arg) }
+}
+
+
+unsafe fn interror(l: *mut lua_State, arg: c_int) {
+    if lua::lua_isnumber(l, arg) != 0 {
+        luaL_argerror(l, arg, c_str!("number has no integer representation"));
+    } else {
+        tag_error(l, arg, lua::LUA_TNUMBER);
+    }
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_checkinteger(l: *mut lua_State, arg: c_int) -> lua_Integer {
+    let mut isnum = 0;
+    let d = lua::lua_tointegerx(l, arg, &mut isnum);
+    if isnum == 0 {
+        interror(l, arg);
+    }
+    d
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_optinteger(l: *mut lua_State, arg: c_int,
+                                         def: lua_Integer) -> lua_Integer {
+    if lua::lua_isnoneornil(l, arg) != 0 { def } else { luaL_checkinteger(l, arg) }
+}
+
+/* }====================================================== */
+
+
+/*
+** {======================================================
+** Generic Buffer manipulation
+** =======================================================
+*/
+
+/* userdata to box arbitrary data */
+#[repr(C)]
+struct UBox {
+    box_: *mut c_void,
+    bsize: size_t,
+}
+
+
+unsafe fn resizebox(l: *mut lua_State, idx: c_int, newsize: size_t) -> *mut c_void {
+    let mut ud = ptr::null_mut();
+    let allocf = lua::lua_getallocf(l, &mut ud).expect("state without allocator");
+    let b = lua::lua_touserdata(l, idx) as *mut UBox;
+    let temp = allocf(ud, (*b).box_, (*b).bsize, newsize);
+    if temp.is_null() && newsize > 0 {  /* allocation error? */
+        resizebox(l, idx, 0);  /* free buffer */
+        lauxlib::luaL_error(l, c_str!("not enough memory for buffer allocation"));
+    }
+    (*b).box_ = temp;
+    (*b).bsize = newsize;
+    temp
+}
+
+
+unsafe extern "C-unwind" fn boxgc(l: *mut lua_State) -> c_int {
+    resizebox(l, 1, 0);
+    0
+}
+
+
+unsafe fn newbox(l: *mut lua_State, newsize: size_t) -> *mut c_void {
+    let b = lua::lua_newuserdata(l, mem::size_of::<UBox>()) as *mut UBox;
+    (*b).box_ = ptr::null_mut();
+    (*b).bsize = 0;
+    if luaL_newmetatable(l, c_str!("LUABOX")) != 0 {  /* creating metatable? */
+        lua::lua_pushcfunction(l, Some(boxgc));
+        lua::lua_setfield(l, -2, c_str!("__gc"));  /* metatable.__gc = boxgc */
+    }
+    lua::lua_setmetatable(l, -2);
+    resizebox(l, -1, newsize)
+}
+
+
+/*
+** check whether buffer is using a userdata on the stack as a temporary
+** buffer
+*/
+unsafe fn buffonstack(b: *mut luaL_Buffer) -> bool {
+    (*b).b != (*b).initb.as_mut_ptr()
+}
+
+
+/*
+** returns a pointer to a free area with at least 'sz' bytes
+*/
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_prepbuffsize(b: *mut luaL_Buffer, sz: size_t) -> *mut c_char {
+    let l = (*b).L;
+    if (*b).size - (*b).n < sz {  /* not enough space? */
+        let mut newsize = (*b).size.wrapping_mul(2);  /* double buffer size */
+        if newsize.wrapping_sub((*b).n) < sz {  /* not big enough? */
+            newsize = (*b).n.wrapping_add(sz);
+        }
+        if newsize < (*b).n || newsize - (*b).n < sz {
+            lauxlib::luaL_error(l, c_str!("buffer too large"));
+        }
+        /* create larger buffer */
+        let newbuff = if buffonstack(b) {
+            resizebox(l, -1, newsize) as *mut c_char
+        } else {  /* no buffer yet */
+            let newbuff = newbox(l, newsize) as *mut c_char;
+            ptr::copy_nonoverlapping((*b).b, newbuff, (*b).n);  /* copy original content */
+            newbuff
+        };
+        (*b).b = newbuff;
+        (*b).size = newsize;
+    }
+    (*b).b.add((*b).n)
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_addlstring(b: *mut luaL_Buffer, s: *const c_char, l: size_t) {
+    if l > 0 {  /* avoid 'memcpy' when 's' can be NULL */
+        let p = luaL_prepbuffsize(b, l);
+        ptr::copy_nonoverlapping(s, p, l);
+        luaL_addsize(b, l);
+    }
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_addstring(b: *mut luaL_Buffer, s: *const c_char) {
+    luaL_addlstring(b, s, libc::strlen(s));
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_pushresult(b: *mut luaL_Buffer) {
+    let l = (*b).L;
+    lua::lua_pushlstring(l, (*b).b, (*b).n);
+    if buffonstack(b) {
+        resizebox(l, -2, 0);  /* delete old buffer */
+        lua::lua_remove(l, -2);  /* remove its header from the stack */
+    }
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_pushresultsize(b: *mut luaL_Buffer, sz: size_t) {
+    luaL_addsize(b, sz);
+    luaL_pushresult(b);
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_addvalue(b: *mut luaL_Buffer) {
+    let l = (*b).L;
+    let mut len = 0;
+    let s = lua::lua_tolstring(l, -1, &mut len);
+    if buffonstack(b) {
+        lua::lua_insert(l, -2);  /* put value below buffer */
+    }
+    luaL_addlstring(b, s, len);
+    lua::lua_remove(l, if buffonstack(b) { -2 } else { -1 });  /* remove value */
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_buffinit(l: *mut lua_State, b: *mut luaL_Buffer) {
+    (*b).L = l;
+    (*b).b = (*b).initb.as_mut_ptr();
+    (*b).n = 0;
+    (*b).size = LUAL_BUFFERSIZE;
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_buffinitsize(l: *mut lua_State, b: *mut luaL_Buffer,
+                                           sz: size_t) -> *mut c_char {
+    luaL_buffinit(l, b);
+    luaL_prepbuffsize(b, sz)
+}
+
+/* }====================================================== */
+
+
+/*
+** {======================================================
+** Reference system
+** =======================================================
+*/
+
+/* index of free-list header */
+const FREELIST: lua_Integer = 0;
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_ref(l: *mut lua_State, mut t: c_int) -> c_int {
+    if lua::lua_isnil(l, -1) != 0 {
+        lua::lua_pop(l, 1);  /* remove from stack */
+        return LUA_REFNIL;  /* 'nil' has a unique fixed reference */
+    }
+    t = lua::lua_absindex(l, t);
+    lua::lua_rawgeti(l, t, FREELIST);  /* get first free element */
+    let mut r = lua::lua_tointeger(l, -1) as c_int;  /* ref = t[freelist] */
+    lua::lua_pop(l, 1);  /* remove it from stack */
+    if r != 0 {  /* any free element? */
+        lua::lua_rawgeti(l, t, r as lua_Integer);  /* remove it from list */
+        lua::lua_rawseti(l, t, FREELIST);  /* (t[freelist] = t[ref]) */
+    } else {  /* no free elements */
+        r = lua::lua_rawlen(l, t) as c_int + 1;  /* get a new reference */
+    }
+    lua::lua_rawseti(l, t, r as lua_Integer);
+    r
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_unref(l: *mut lua_State, mut t: c_int, r: c_int) {
+    if r >= 0 {
+        t = lua::lua_absindex(l, t);
+        lua::lua_rawgeti(l, t, FREELIST);
+        lua::lua_rawseti(l, t, r as lua_Integer);  /* t[ref] = t[freelist] */
+        lua::lua_pushinteger(l, r as lua_Integer);
+        lua::lua_rawseti(l, t, FREELIST);  /* t[freelist] = ref */
+    }
+}
+
+/* }====================================================== */
+
+
+/*
+** {======================================================
+** Load functions
+** =======================================================
+*/
+
+enum Source {
+    Stdin(io::Stdin),
+    File(File),
+}
+
+impl Read for Source {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            Source::Stdin(ref mut f) => f.read(buf),
+            Source::File(ref mut f) => f.read(buf),
+        }
+    }
+}
+
+
+struct LoadF {
+    n: usize,  /* number of pre-read characters */
+    f: Source,  /* file being read */
+    eof: bool,  /* end of file seen, as 'feof' */
+    err: Option<io::Error>,  /* first read error, as 'ferror' */
+    buff: [u8; libc::BUFSIZ as usize],  /* area for reading file */
+}
+
+impl LoadF {
+    fn new(f: Source) -> LoadF {
+        LoadF { n: 0, f, eof: false, err: None, buff: [0; libc::BUFSIZ as usize] }
+    }
+
+    /* next byte of the file, or None at its end or on a read error */
+    fn getc(&mut self) -> Option<u8> {
+        let mut c = [0u8];
+        loop {
+            match self.f.read(&mut c) {
+                Ok(0) => { self.eof = true; return None; }
+                Ok(_) => return Some(c[0]),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => { self.err = Some(e); return None; }
+            }
+        }
+    }
+
+    /* fills the buffer as much as possible, returning how much was read */
+    fn fread(&mut self) -> usize {
+        let mut n = 0;
+        while n < self.buff.len() {
+            match self.f.read(&mut self.buff[n..]) {
+                Ok(0) => { self.eof = true; break; }
+                Ok(k) => n += k,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => { self.err = Some(e); break; }
+            }
+        }
+        n
+    }
+}
+
+
+unsafe extern "C-unwind" fn getF(_l: *mut lua_State, ud: *mut c_void, size: *mut size_t) -> *const c_char {
+    let lf = &mut *(ud as *mut LoadF);
+    if lf.n > 0 {  /* are there pre-read characters to be read? */
+        *size = lf.n;  /* return them (chars already in buffer) */
+        lf.n = 0;  /* no more pre-read characters */
+    } else {  /* read a block from file */
+        /* A read can return data *and* reach the end of the file. If the
+           next call read again, it might still wait for user input.
+           The next check avoids this problem. */
+        if lf.eof || lf.err.is_some() { return ptr::null(); }
+        *size = lf.fread();  /* read block */
+    }
+    lf.buff.as_ptr() as *const c_char
+}
+
+
+unsafe fn errfile(l: *mut lua_State, what: *const c_char, fnameindex: c_int, e: io::Error) -> c_int {
+    pusherror(l, &e);
+    drop(e);
+    let filename = lua::lua_tostring(l, fnameindex).add(1);
+    lua::lua_pushfstring(l, c_str!("cannot %s %s: %s"), what, filename, lua::lua_tostring(l, -1));
+    lua::lua_remove(l, -2);  /* remove error message */
+    lua::lua_remove(l, fnameindex);
+    LUA_ERRFILE
+}
+
+
+fn skipBOM(lf: &mut LoadF) -> Option<u8> {
+    lf.n = 0;
+    for &p in b"\xEF\xBB\xBF" {  /* UTF-8 BOM mark */
+        let c = lf.getc();
+        if c != Some(p) { return c; }
+        lf.buff[lf.n] = p;  /* to be read by the parser */
+        lf.n += 1;
+    }
+    lf.n = 0;  /* prefix matched; discard it */
+    lf.getc()  /* return next character */
+}
+
+
+/*
+** reads the first character of file 'f' and skips an optional BOM mark
+** in its beginning plus its first line if it starts with '#'. Returns
+** true if it skipped the first line.  In any case, '*cp' has the
+** first "valid" character of the file (after the optional BOM and
+** a first-line comment).
+*/
+fn skipcomment(lf: &mut LoadF, cp: &mut Option<u8>) -> bool {
+    let mut c = skipBOM(lf);
+    *cp = c;
+    if c == Some(b'#') {  /* first line is a comment (Unix exec. file)? */
+        loop {  /* skip first line */
+            c = lf.getc();
+            if c.is_none() || c == Some(b'\n') { break; }
+        }
+        *cp = lf.getc();  /* skip end-of-line, if present */
+        true  /* there was a comment */
+    } else {
+        false  /* no comment */
+    }
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_loadfilex(l: *mut lua_State, filename: *const c_char,
+                                        mode: *const c_char) -> c_int {
+    let fnameindex = lua::lua_gettop(l) + 1;  /* index of filename on the stack */
+    let f = if filename.is_null() {
+        lua::lua_pushstring(l, c_str!("=stdin"));
+        Source::Stdin(io::stdin())
+    } else {
+        lua::lua_pushfstring(l, c_str!("@%s"), filename);
+        match File::open(::host::topath(CStr::from_ptr(filename))) {
+            Ok(f) => Source::File(f),
+            Err(e) => return errfile(l, c_str!("open"), fnameindex, e),
+        }
+    };
+    let mut lf = Box::new(LoadF::new(f));
+    let mut c = None;
+    if skipcomment(&mut lf, &mut c) {  /* read initial portion */
+        lf.buff[lf.n] = b'\n';  /* add line to correct line numbers */
+        lf.n += 1;
+    }
+    if c == Some(lua::LUA_SIGNATURE[0]) {  /* binary file? */
+        /* go back to the start (C reopens the file in binary mode here) */
+        let rewound = match lf.f {
+            Source::File(ref mut f) => Some(f.seek(SeekFrom::Start(0))),
+            Source::Stdin(_) => None,
+        };
+        match rewound {
+            Some(Ok(_)) => { skipcomment(&mut lf, &mut c); }  /* re-read initial portion */
+            Some(Err(e)) => {
+                drop(lf);
+                return errfile(l, c_str!("reopen"), fnameindex, e);
+            }
+            None => {}
+        }
+    }
+    if let Some(c) = c {
+        lf.buff[lf.n] = c;  /* 'c' is the first character of the stream */
+        lf.n += 1;
+    }
+    let lfp: *mut LoadF = &mut *lf;
+    let status = lua::lua_load(l, Some(getF), lfp as *mut c_void, lua::lua_tostring(l, -1), mode);
+    let readstatus = lf.err.take();
+    drop(lf);  /* close file (even in case of errors) */
+    if let Some(e) = readstatus {
+        lua::lua_settop(l, fnameindex);  /* ignore results from 'lua_load' */
+        return errfile(l, c_str!("read"), fnameindex, e);
+    }
+    lua::lua_remove(l, fnameindex);
+    status
+}
+
+
+struct LoadS {
+    s: *const c_char,
+    size: size_t,
+}
+
+
+unsafe extern "C-unwind" fn getS(_l: *mut lua_State, ud: *mut c_void, size: *mut size_t) -> *const c_char {
+    let ls = &mut *(ud as *mut LoadS);
+    if ls.size == 0 { return ptr::null(); }
+    *size = ls.size;
+    ls.size = 0;
+    ls.s
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_loadbufferx(l: *mut lua_State, buff: *const c_char, size: size_t,
+                                          name: *const c_char, mode: *const c_char) -> c_int {
+    let mut ls = LoadS { s: buff, size };
+    lua::lua_load(l, Some(getS), &mut ls as *mut LoadS as *mut c_void, name, mode)
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_loadstring(l: *mut lua_State, s: *const c_char) -> c_int {
+    luaL_loadbufferx(l, s, libc::strlen(s), s, ptr::null())
+}
+
+/* }====================================================== */
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_getmetafield(l: *mut lua_State, obj: c_int,
+                                           event: *const c_char) -> c_int {
+    if lua::lua_getmetatable(l, obj) == 0 {  /* no metatable? */
+        return lua::LUA_TNIL;
+    }
+    lua::lua_pushstring(l, event);
+    let tt = lua::lua_rawget(l, -2);
+    if tt == lua::LUA_TNIL {  /* is metafield nil? */
+        lua::lua_pop(l, 2);  /* remove metatable and metafield */
+    } else {
+        lua::lua_remove(l, -2);  /* remove only metatable */
+    }
+    tt  /* return metafield type */
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_callmeta(l: *mut lua_State, obj: c_int,
+                                       event: *const c_char) -> c_int {
+    let obj = lua::lua_absindex(l, obj);
+    if luaL_getmetafield(l, obj, event) == lua::LUA_TNIL {  /* no metafield? */
+        return 0;
+    }
+    lua::lua_pushvalue(l, obj);
+    lua::lua_call(l, 1, 1);
+    1
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_len(l: *mut lua_State, idx: c_int) -> lua_Integer {
+    let mut isnum = 0;
+    lua::lua_len(l, idx);
+    let len = lua::lua_tointegerx(l, -1, &mut isnum);
+    if isnum == 0 {
+        lauxlib::luaL_error(l, c_str!("object length is not an integer"));
+    }
+    lua::lua_pop(l, 1);  /* remove object */
+    len
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_tolstring(l: *mut lua_State, idx: c_int,
+                                        len: *mut size_t) -> *const c_char {
+    if luaL_callmeta(l, idx, c_str!("__tostring")) == 0 {  /* no metafield? */
+        match lua::lua_type(l, idx) {
+            lua::LUA_TNUMBER => {
+                if lua::lua_isinteger(l, idx) != 0 {
+                    lua::lua_pushfstring(l, c_str!("%I"), lua::lua_tointeger(l, idx));
+                } else {
+                    lua::lua_pushfstring(l, c_str!("%f"), lua::lua_tonumber(l, idx));
+                }
+            }
+            lua::LUA_TSTRING => lua::lua_pushvalue(l, idx),
+            lua::LUA_TBOOLEAN => {
+                lua::lua_pushstring(l, if lua::lua_toboolean(l, idx) != 0 { c_str!("true") }
+                                       else { c_str!("false") });
+            }
+            lua::LUA_TNIL => { lua::lua_pushstring(l, c_str!("nil")); }
+            _ => {
+                lua::lua_pushfstring(l, c_str!("%s: %p"), luaL_typename(l, idx),
+                                     lua::lua_topointer(l, idx));
+            }
+        }
+    }
+    lua::lua_tolstring(l, -1, len)
+}
+
+
+/*
+** set functions from list 'l' into table at top - 'nup'; each
+** function gets the 'nup' elements at the top as upvalues.
+** Returns with only the table at the stack.
+*/
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_setfuncs(l: *mut lua_State, mut r: *const luaL_Reg, nup: c_int) {
+    luaL_checkstack(l, nup, c_str!("too many upvalues"));
+    while !(*r).name.is_null() {  /* fill the table with given functions */
+        for _ in 0..nup {  /* copy upvalues to the top */
+            lua::lua_pushvalue(l, -nup);
+        }
+        lua::lua_pushcclosure(l, (*r).func, nup);  /* closure with those upvalues */
+        lua::lua_setfield(l, -(nup + 2), (*r).name);
+        r = r.add(1);
+    }
+    lua::lua_pop(l, nup);  /* remove upvalues */
+}
+
+
+/*
+** ensure that stack[idx][fname] has a table and push that table
+** into the stack
+*/
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_getsubtable(l: *mut lua_State, idx: c_int,
+                                          fname: *const c_char) -> c_int {
+    if lua::lua_getfield(l, idx, fname) == lua::LUA_TTABLE {
+        1  /* table already there */
+    } else {
+        lua::lua_pop(l, 1);  /* remove previous result */
+        let idx = lua::lua_absindex(l, idx);
+        lua::lua_newtable(l);
+        lua::lua_pushvalue(l, -1);  /* copy to be left at top */
+        lua::lua_setfield(l, idx, fname);  /* assign new table to field */
+        0  /* false, because did not find table there */
+    }
+}
+
+
+/*
+** Stripped-down 'require': After checking "loaded" table, calls 'openf'
+** to open a module, registers the result in 'package.loaded' table and,
+** if 'glb' is true, also registers the result in the global table.
+** Leaves resulting module on the top.
+*/
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_requiref(l: *mut lua_State, modname: *const c_char,
+                                       openf: lua_CFunction, glb: c_int) {
+    luaL_getsubtable(l, lua::LUA_REGISTRYINDEX, c_str!("_LOADED"));
+    lua::lua_getfield(l, -1, modname);  /* _LOADED[modname] */
+    if lua::lua_toboolean(l, -1) == 0 {  /* package not already loaded? */
+        lua::lua_pop(l, 1);  /* remove field */
+        lua::lua_pushcfunction(l, openf);
+        lua::lua_pushstring(l, modname);  /* argument to open function */
+        lua::lua_call(l, 1, 1);  /* call 'openf' to open module */
+        lua::lua_pushvalue(l, -1);  /* make copy of module (call result) */
+        lua::lua_setfield(l, -3, modname);  /* _LOADED[modname] = module */
+    }
+    lua::lua_remove(l, -2);  /* remove _LOADED table */
+    if glb != 0 {
+        lua::lua_pushvalue(l, -1);  /* copy of module */
+        lua::lua_setglobal(l, modname);  /* _G[modname] = module */
+    }
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_gsub(l: *mut lua_State, mut s: *const c_char, p: *const c_char,
+                                   r: *const c_char) -> *const c_char {
+    let len = libc::strlen(p);
+    let mut b: luaL_Buffer = mem::zeroed();
+    luaL_buffinit(l, &mut b);
+    loop {
+        let wild = libc::strstr(s, p);
+        if wild.is_null() { break; }
+        luaL_addlstring(&mut b, s, wild.offset_from(s) as size_t);  /* push prefix */
+        luaL_addstring(&mut b, r);  /* push replacement in place of pattern */
+        s = wild.add(len);  /* continue after 'p' */
+    }
+    luaL_addstring(&mut b, s);  /* push last suffix */
+    luaL_pushresult(&mut b);
+    lua::lua_tostring(l, -1)
+}
+
+
+unsafe extern "C-unwind" fn l_alloc(_ud: *mut c_void, ptr: *mut c_void,
+                             _osize: size_t, nsize: size_t) -> *mut c_void {
+    if nsize == 0 {
+        libc::free(ptr);
+        ptr::null_mut()
+    } else {
+        libc::realloc(ptr, nsize)
+    }
+}
+
+
+unsafe extern "C-unwind" fn panic(l: *mut lua_State) -> c_int {
+    let msg = lua::lua_tostring(l, -1);
+    let msg = if msg.is_null() { "(null)".into() } else { CStr::from_ptr(msg).to_string_lossy() };
+    let mut stderr = io::stderr();
+    let _ = writeln!(stderr, "PANIC: unprotected error in call to Lua API ({})", msg);
+    let _ = stderr.flush();
+    0  /* return to Lua to abort */
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_newstate() -> *mut lua_State {
+    let l = lua::lua_newstate(Some(l_alloc), ptr::null_mut());
+    if !l.is_null() { lua::lua_atpanic(l, Some(panic)); }
+    l
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaL_checkversion_(l: *mut lua_State, ver: lua_Number, sz: size_t) {
+    let v = lua::lua_version(l);
+    if sz != LUAL_NUMSIZES as size_t {  /* check numeric types */
+        lauxlib::luaL_error(l, c_str!("core and library have incompatible numeric types"));
+    }
+    if v != lua::lua_version(ptr::null_mut()) {
+        lauxlib::luaL_error(l, c_str!("multiple Lua VMs detected"));
+    } else if *v != ver {
+        lauxlib::luaL_error(l, c_str!("version mismatch: app. needs %f, Lua core provides %f"),
+                            ver, *v);
+    }
+}
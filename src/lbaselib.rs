@@ -0,0 +1,551 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Basic library.
+
+#![allow(non_snake_case)]
+
+use ffi::lua::{self, lua_State, lua_Integer, lua_Number, lua_KContext};
+use ffi::lauxlib;
+use libc::{self, c_char, c_int, c_void, size_t};
+use std::io::{self, Write};
+use std::{ptr, slice};
+use userdata;
+
+
+/*
+** {======================================================
+** Output stream for 'print'
+** =======================================================
+*/
+
+/* key, in the registry, for the output stream */
+static OUTPUTKEY: u8 = 0;
+
+
+/// Sets the stream where `print` writes its output for state `l` (and
+/// all threads sharing its registry), replacing the default, which is
+/// the standard output.
+///
+/// The stream is dropped when the state is closed or when another one
+/// is set.
+///
+/// # Safety
+///
+/// `l` must be a valid Lua state.
+pub unsafe fn set_output(l: *mut lua_State, output: Box<dyn Write>) {
+    userdata::set(l, &OUTPUTKEY, output);
+}
+
+
+/*
+** Writes 's' to the output stream of 'l'. Without a stream set by
+** 'set_output', it writes to the standard output, after flushing the
+** C streams and the output buffered by 'io.stdout', so that output
+** from all sides keeps its order.
+*/
+unsafe fn writestring(l: *mut lua_State, s: &[u8]) {
+    match userdata::get::<Box<dyn Write>>(l, &OUTPUTKEY) {
+        Some(w) => { let _ = w.write_all(s); }
+        None => {
+            libc::fflush(ptr::null_mut());
+            #[cfg(rust_liolib)]
+            ::liolib::flush_stdout(l);  /* (a C 'io' library buffers in the C streams) */
+            let _ = io::stdout().write_all(s);
+        }
+    }
+}
+
+unsafe fn writeline(l: *mut lua_State) {
+    writestring(l, b"\n");
+    match userdata::get::<Box<dyn Write>>(l, &OUTPUTKEY) {
+        Some(w) => { let _ = w.flush(); }
+        None => { let _ = io::stdout().flush(); }
+    }
+}
+
+/* }====================================================== */
+
+
+unsafe extern "C-unwind" fn luaB_print(l: *mut lua_State) -> c_int {
+    let n = lua::lua_gettop(l);  /* number of arguments */
+    lua::lua_getglobal(l, c_str!("tostring"));
+    for i in 1..(n + 1) {
+        lua::lua_pushvalue(l, -1);  /* function to be called */
+        lua::lua_pushvalue(l, i);  /* value to print */
+        lua::lua_call(l, 1, 1);
+        let mut len: size_t = 0;
+        let s = lua::lua_tolstring(l, -1, &mut len);  /* get result */
+        if s.is_null() {
+            return lauxlib::luaL_error(l, c_str!("'tostring' must return a string to 'print'"));
+        }
+        if i > 1 { writestring(l, b"\t"); }
+        writestring(l, slice::from_raw_parts(s as *const u8, len as usize));
+        lua::lua_pop(l, 1);  /* pop result */
+    }
+    writeline(l);
+    0
+}
+
+
+fn isspace(c: u8) -> bool {
+    c == b' ' || (b'\t'..=b'\r').contains(&c)  /* " \f\n\r\t\v" */
+}
+
+fn b_str2int(s: &[u8], base: u32) -> Option<lua_Integer> {
+    let mut n: u64 = 0;
+    let mut neg = false;
+    let mut i = 0;
+    while i < s.len() && isspace(s[i]) { i += 1; }  /* skip initial spaces */
+    if i < s.len() && s[i] == b'-' { i += 1; neg = true; }  /* handle signal */
+    else if i < s.len() && s[i] == b'+' { i += 1; }
+    if !(i < s.len() && (s[i] as char).is_ascii_alphanumeric()) {  /* no digit? */
+        return None;
+    }
+    while i < s.len() && (s[i] as char).is_ascii_alphanumeric() {
+        let digit = (s[i] as char).to_digit(36).unwrap();
+        if digit >= base {
+            return None;  /* invalid numeral */
+        }
+        n = n.wrapping_mul(base as u64).wrapping_add(digit as u64);
+        i += 1;
+    }
+    while i < s.len() && isspace(s[i]) { i += 1; }  /* skip trailing spaces */
+    if i != s.len() {
+        return None;
+    }
+    Some((if neg { 0u64.wrapping_sub(n) } else { n }) as lua_Integer)
+}
+
+
+unsafe extern "C-unwind" fn luaB_tonumber(l: *mut lua_State) -> c_int {
+    if lua::lua_isnoneornil(l, 2) != 0 {  /* standard conversion? */
+        lauxlib::luaL_checkany(l, 1);
+        if lua::lua_type(l, 1) == lua::LUA_TNUMBER {  /* already a number? */
+            lua::lua_settop(l, 1);  /* yes; return it */
+            return 1;
+        } else {
+            let mut len: size_t = 0;
+            let s = lua::lua_tolstring(l, 1, &mut len);
+            if !s.is_null() && lua::lua_stringtonumber(l, s) == len + 1 {
+                return 1;  /* successful conversion to number */
+            }
+            /* else not a number */
+        }
+    } else {
+        let base = lauxlib::luaL_checkinteger(l, 2);
+        lauxlib::luaL_checktype(l, 1, lua::LUA_TSTRING);  /* no numbers as strings */
+        let mut len: size_t = 0;
+        let s = lua::lua_tolstring(l, 1, &mut len);
+        lauxlib::luaL_argcheck(l, (2..=36).contains(&base) as c_int, 2, c_str!("base out of range"));
+        if let Some(n) = b_str2int(slice::from_raw_parts(s as *const u8, len as usize), base as u32) {
+            lua::lua_pushinteger(l, n);
+            return 1;
+        }  /* else not a number */
+    }  /* else not a number */
+    lua::lua_pushnil(l);  /* not a number */
+    1
+}
+
+
+unsafe extern "C-unwind" fn luaB_error(l: *mut lua_State) -> c_int {
+    let level = lauxlib::luaL_optinteger(l, 2, 1) as c_int;
+    lua::lua_settop(l, 1);
+    if lua::lua_type(l, 1) == lua::LUA_TSTRING && level > 0 {
+        lauxlib::luaL_where(l, level);  /* add extra information */
+        lua::lua_pushvalue(l, 1);
+        lua::lua_concat(l, 2);
+    }
+    lua::lua_error(l)
+}
+
+
+unsafe extern "C-unwind" fn luaB_getmetatable(l: *mut lua_State) -> c_int {
+    lauxlib::luaL_checkany(l, 1);
+    if lua::lua_getmetatable(l, 1) == 0 {
+        lua::lua_pushnil(l);
+        return 1;  /* no metatable */
+    }
+    lauxlib::luaL_getmetafield(l, 1, c_str!("__metatable"));
+    1  /* returns either __metatable field (if present) or metatable */
+}
+
+
+unsafe extern "C-unwind" fn luaB_setmetatable(l: *mut lua_State) -> c_int {
+    let t = lua::lua_type(l, 2);
+    lauxlib::luaL_checktype(l, 1, lua::LUA_TTABLE);
+    lauxlib::luaL_argcheck(l, (t == lua::LUA_TNIL || t == lua::LUA_TTABLE) as c_int, 2,
+                           c_str!("nil or table expected"));
+    if lauxlib::luaL_getmetafield(l, 1, c_str!("__metatable")) != lua::LUA_TNIL {
+        return lauxlib::luaL_error(l, c_str!("cannot change a protected metatable"));
+    }
+    lua::lua_settop(l, 2);
+    lua::lua_setmetatable(l, 1);
+    1
+}
+
+
+unsafe extern "C-unwind" fn luaB_rawequal(l: *mut lua_State) -> c_int {
+    lauxlib::luaL_checkany(l, 1);
+    lauxlib::luaL_checkany(l, 2);
+    lua::lua_pushboolean(l, lua::lua_rawequal(l, 1, 2));
+    1
+}
+
+
+unsafe extern "C-unwind" fn luaB_rawlen(l: *mut lua_State) -> c_int {
+    let t = lua::lua_type(l, 1);
+    lauxlib::luaL_argcheck(l, (t == lua::LUA_TTABLE || t == lua::LUA_TSTRING) as c_int, 1,
+                           c_str!("table or string expected"));
+    lua::lua_pushinteger(l, lua::lua_rawlen(l, 1) as lua_Integer);
+    1
+}
+
+
+unsafe extern "C-unwind" fn luaB_rawget(l: *mut lua_State) -> c_int {
+    lauxlib::luaL_checktype(l, 1, lua::LUA_TTABLE);
+    lauxlib::luaL_checkany(l, 2);
+    lua::lua_settop(l, 2);
+    lua::lua_rawget(l, 1);
+    1
+}
+
+unsafe extern "C-unwind" fn luaB_rawset(l: *mut lua_State) -> c_int {
+    lauxlib::luaL_checktype(l, 1, lua::LUA_TTABLE);
+    lauxlib::luaL_checkany(l, 2);
+    lauxlib::luaL_checkany(l, 3);
+    lua::lua_settop(l, 3);
+    lua::lua_rawset(l, 1);
+    1
+}
+
+
+unsafe extern "C-unwind" fn luaB_collectgarbage(l: *mut lua_State) -> c_int {
+    let opts = [c_str!("stop"), c_str!("restart"), c_str!("collect"),
+        c_str!("count"), c_str!("step"), c_str!("setpause"), c_str!("setstepmul"),
+        c_str!("isrunning"), ptr::null()];
+    let optsnum = [lua::LUA_GCSTOP, lua::LUA_GCRESTART, lua::LUA_GCCOLLECT,
+        lua::LUA_GCCOUNT, lua::LUA_GCSTEP, lua::LUA_GCSETPAUSE, lua::LUA_GCSETSTEPMUL,
+        lua::LUA_GCISRUNNING];
+    let o = optsnum[lauxlib::luaL_checkoption(l, 1, c_str!("collect"), opts.as_ptr()) as usize];
+    let ex = lauxlib::luaL_optinteger(l, 2, 0) as c_int;
+    let res = lua::lua_gc(l, o, ex);
+    match o {
+        lua::LUA_GCCOUNT => {
+            let b = lua::lua_gc(l, lua::LUA_GCCOUNTB, 0);
+            lua::lua_pushnumber(l, res as lua_Number + (b as lua_Number / 1024.0));
+        }
+        lua::LUA_GCSTEP | lua::LUA_GCISRUNNING => lua::lua_pushboolean(l, res),
+        _ => lua::lua_pushinteger(l, res as lua_Integer),
+    }
+    1
+}
+
+
+unsafe extern "C-unwind" fn luaB_type(l: *mut lua_State) -> c_int {
+    let t = lua::lua_type(l, 1);
+    lauxlib::luaL_argcheck(l, (t != lua::LUA_TNONE) as c_int, 1, c_str!("value expected"));
+    lua::lua_pushstring(l, lua::lua_typename(l, t));
+    1
+}
+
+
+unsafe fn pairsmeta(l: *mut lua_State, method: *const c_char, iszero: bool,
+                    iter: lua::lua_CFunction) -> c_int {
+    if lauxlib::luaL_getmetafield(l, 1, method) == lua::LUA_TNIL {  /* no metamethod? */
+        lauxlib::luaL_checktype(l, 1, lua::LUA_TTABLE);  /* argument must be a table */
+        lua::lua_pushcfunction(l, iter);  /* will return generator, */
+        lua::lua_pushvalue(l, 1);  /* state, */
+        if iszero { lua::lua_pushinteger(l, 0); }  /* and initial value */
+        else { lua::lua_pushnil(l); }
+    } else {
+        lua::lua_pushvalue(l, 1);  /* argument 'self' to metamethod */
+        lua::lua_call(l, 1, 3);  /* get 3 values from metamethod */
+    }
+    3
+}
+
+
+unsafe extern "C-unwind" fn luaB_next(l: *mut lua_State) -> c_int {
+    lauxlib::luaL_checktype(l, 1, lua::LUA_TTABLE);
+    lua::lua_settop(l, 2);  /* create a 2nd argument if there isn't one */
+    if lua::lua_next(l, 1) != 0 {
+        2
+    } else {
+        lua::lua_pushnil(l);
+        1
+    }
+}
+
+
+unsafe extern "C-unwind" fn luaB_pairs(l: *mut lua_State) -> c_int {
+    pairsmeta(l, c_str!("__pairs"), false, Some(luaB_next))
+}
+
+
+/*
+** Traversal function for 'ipairs'
+*/
+unsafe extern "C-unwind" fn ipairsaux(l: *mut lua_State) -> c_int {
+    let i = lauxlib::luaL_checkinteger(l, 2) + 1;
+    lua::lua_pushinteger(l, i);
+    if lua::lua_geti(l, 1, i) == lua::LUA_TNIL { 1 } else { 2 }
+}
+
+
+/*
+** 'ipairs' function. Returns 'ipairsaux', given "table", 0.
+** (The given "table" may not be a table.)
+*/
+unsafe extern "C-unwind" fn luaB_ipairs(l: *mut lua_State) -> c_int {
+    lauxlib::luaL_checkany(l, 1);
+    lua::lua_pushcfunction(l, Some(ipairsaux));  /* iteration function */
+    lua::lua_pushvalue(l, 1);  /* state */
+    lua::lua_pushinteger(l, 0);  /* initial value */
+    3
+}
+
+
+unsafe fn load_aux(l: *mut lua_State, status: c_int, envidx: c_int) -> c_int {
+    if status == lua::LUA_OK {
+        if envidx != 0 {  /* 'env' parameter? */
+            lua::lua_pushvalue(l, envidx);  /* environment for loaded function */
+            if lua::lua_setupvalue(l, -2, 1).is_null() {  /* set it as 1st upvalue */
+                lua::lua_pop(l, 1);  /* remove 'env' if not used by previous call */
+            }
+        }
+        1
+    } else {  /* error (message is on top of the stack) */
+        lua::lua_pushnil(l);
+        lua::lua_insert(l, -2);  /* put before error message */
+        2  /* return nil plus error message */
+    }
+}
+
+
+unsafe extern "C-unwind" fn luaB_loadfile(l: *mut lua_State) -> c_int {
+    let fname = lauxlib::luaL_optstring(l, 1, ptr::null());
+    let mode = lauxlib::luaL_optstring(l, 2, ptr::null());
+    let env = if lua::lua_isnone(l, 3) == 0 { 3 } else { 0 };  /* 'env' index or 0 if no 'env' */
+    let status = lauxlib::luaL_loadfilex(l, fname, mode);
+    load_aux(l, status, env)
+}
+
+
+/*
+** {======================================================
+** Generic Read function
+** =======================================================
+*/
+
+
+/*
+** reserved slot, above all arguments, to hold a copy of the returned
+** string to avoid it being collected while parsed. 'load' has four
+** optional arguments (chunk, source name, mode, and environment).
+*/
+const RESERVEDSLOT: c_int = 5;
+
+
+/*
+** Reader for generic 'load' function: 'lua_load' uses the
+** stack for internal stuff, so the reader cannot change the
+** stack top. Instead, it keeps its resulting string in a
+** reserved slot inside the stack.
+*/
+unsafe extern "C-unwind" fn generic_reader(l: *mut lua_State, _: *mut c_void, size: *mut size_t) -> *const c_char {
+    lauxlib::luaL_checkstack(l, 2, c_str!("too many nested functions"));
+    lua::lua_pushvalue(l, 1);  /* get function */
+    lua::lua_call(l, 0, 1);  /* call it */
+    if lua::lua_isnil(l, -1) != 0 {
+        lua::lua_pop(l, 1);  /* pop result */
+        *size = 0;
+        return ptr::null();
+    } else if lua::lua_isstring(l, -1) == 0 {
+        lauxlib::luaL_error(l, c_str!("reader function must return a string"));
+    }
+    lua::lua_replace(l, RESERVEDSLOT);  /* save string in reserved slot */
+    lua::lua_tolstring(l, RESERVEDSLOT, size)
+}
+
+
+unsafe extern "C-unwind" fn luaB_load(l: *mut lua_State) -> c_int {
+    let mut len: size_t = 0;
+    let s = lua::lua_tolstring(l, 1, &mut len);
+    let mode = lauxlib::luaL_optstring(l, 3, c_str!("bt"));
+    let env = if lua::lua_isnone(l, 4) == 0 { 4 } else { 0 };  /* 'env' index or 0 if no 'env' */
+    let status = if !s.is_null() {  /* loading a string? */
+        let chunkname = lauxlib::luaL_optstring(l, 2, s);
+        lauxlib::luaL_loadbufferx(l, s, len, chunkname, mode)
+    } else {  /* loading from a reader function */
+        let chunkname = lauxlib::luaL_optstring(l, 2, c_str!("=(load)"));
+        lauxlib::luaL_checktype(l, 1, lua::LUA_TFUNCTION);
+        lua::lua_settop(l, RESERVEDSLOT);  /* create reserved slot */
+        lua::lua_load(l, Some(generic_reader), ptr::null_mut(), chunkname, mode)
+    };
+    load_aux(l, status, env)
+}
+
+/* }====================================================== */
+
+
+unsafe extern "C-unwind" fn dofilecont(l: *mut lua_State, _: c_int, _: lua_KContext) -> c_int {
+    lua::lua_gettop(l) - 1
+}
+
+
+unsafe extern "C-unwind" fn luaB_dofile(l: *mut lua_State) -> c_int {
+    let fname = lauxlib::luaL_optstring(l, 1, ptr::null());
+    lua::lua_settop(l, 1);
+    if lauxlib::luaL_loadfile(l, fname) != lua::LUA_OK {
+        return lua::lua_error(l);
+    }
+    lua::lua_callk(l, 0, lua::LUA_MULTRET, 0, Some(dofilecont));
+    dofilecont(l, 0, 0)
+}
+
+
+unsafe extern "C-unwind" fn luaB_assert(l: *mut lua_State) -> c_int {
+    if lua::lua_toboolean(l, 1) != 0 {  /* condition is true? */
+        lua::lua_gettop(l)  /* return all arguments */
+    } else {  /* error */
+        lauxlib::luaL_checkany(l, 1);  /* there must be a condition */
+        lua::lua_remove(l, 1);  /* remove it */
+        lua::lua_pushstring(l, c_str!("assertion failed!"));  /* default message */
+        lua::lua_settop(l, 1);  /* leave only message (default if no other one) */
+        luaB_error(l)  /* call 'error' */
+    }
+}
+
+
+unsafe extern "C-unwind" fn luaB_select(l: *mut lua_State) -> c_int {
+    let n = lua::lua_gettop(l);
+    if lua::lua_type(l, 1) == lua::LUA_TSTRING && *lua::lua_tostring(l, 1) == b'#' as c_char {
+        lua::lua_pushinteger(l, (n - 1) as lua_Integer);
+        1
+    } else {
+        let mut i = lauxlib::luaL_checkinteger(l, 1);
+        if i < 0 { i += n as lua_Integer; }
+        else if i > n as lua_Integer { i = n as lua_Integer; }
+        lauxlib::luaL_argcheck(l, (1 <= i) as c_int, 1, c_str!("index out of range"));
+        n - i as c_int
+    }
+}
+
+
+/*
+** Continuation function for 'pcall' and 'xpcall'. Both functions
+** already pushed a 'true' before doing the call, so in case of success
+** 'finishpcall' only has to return everything in the stack minus
+** 'extra' values (where 'extra' is exactly the number of items to be
+** ignored).
+*/
+unsafe extern "C-unwind" fn finishpcall(l: *mut lua_State, status: c_int, extra: lua_KContext) -> c_int {
+    if status != lua::LUA_OK && status != lua::LUA_YIELD {  /* error? */
+        lua::lua_pushboolean(l, 0);  /* first result (false) */
+        lua::lua_pushvalue(l, -2);  /* error message */
+        2  /* return false, msg */
+    } else {
+        lua::lua_gettop(l) - extra as c_int  /* return all results */
+    }
+}
+
+
+unsafe extern "C-unwind" fn luaB_pcall(l: *mut lua_State) -> c_int {
+    lauxlib::luaL_checkany(l, 1);
+    lua::lua_pushboolean(l, 1);  /* first result if no errors */
+    lua::lua_insert(l, 1);  /* put it in place */
+    let status = lua::lua_pcallk(l, lua::lua_gettop(l) - 2, lua::LUA_MULTRET, 0, 0, Some(finishpcall));
+    finishpcall(l, status, 0)
+}
+
+
+/*
+** Do a protected call with error handling. After 'lua_rotate', the
+** stack will have <f, err, true, f, [args...]>; so, the function passes
+** 2 to 'finishpcall' to skip the 2 first values when returning results.
+*/
+unsafe extern "C-unwind" fn luaB_xpcall(l: *mut lua_State) -> c_int {
+    let n = lua::lua_gettop(l);
+    lauxlib::luaL_checktype(l, 2, lua::LUA_TFUNCTION);  /* check error function */
+    lua::lua_pushboolean(l, 1);  /* first result */
+    lua::lua_pushvalue(l, 1);  /* function */
+    lua::lua_rotate(l, 3, 2);  /* move them below function's arguments */
+    let status = lua::lua_pcallk(l, n - 2, lua::LUA_MULTRET, 2, 2, Some(finishpcall));
+    finishpcall(l, status, 2)
+}
+
+
+unsafe extern "C-unwind" fn luaB_tostring(l: *mut lua_State) -> c_int {
+    lauxlib::luaL_checkany(l, 1);
+    lauxlib::luaL_tolstring(l, 1, ptr::null_mut());
+    1
+}
+
+
+/// Opens the basic library in state `l`, leaving the global table on the stack.
+///
+/// # Safety
+///
+/// `l` must be a valid Lua state.
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaopen_base(l: *mut lua_State) -> c_int {
+    let base_funcs = [
+        lauxlib::luaL_Reg { name: c_str!("assert"), func: Some(luaB_assert) },
+        lauxlib::luaL_Reg { name: c_str!("collectgarbage"), func: Some(luaB_collectgarbage) },
+        lauxlib::luaL_Reg { name: c_str!("dofile"), func: Some(luaB_dofile) },
+        lauxlib::luaL_Reg { name: c_str!("error"), func: Some(luaB_error) },
+        lauxlib::luaL_Reg { name: c_str!("getmetatable"), func: Some(luaB_getmetatable) },
+        lauxlib::luaL_Reg { name: c_str!("ipairs"), func: Some(luaB_ipairs) },
+        lauxlib::luaL_Reg { name: c_str!("loadfile"), func: Some(luaB_loadfile) },
+        lauxlib::luaL_Reg { name: c_str!("load"), func: Some(luaB_load) },
+        lauxlib::luaL_Reg { name: c_str!("next"), func: Some(luaB_next) },
+        lauxlib::luaL_Reg { name: c_str!("pairs"), func: Some(luaB_pairs) },
+        lauxlib::luaL_Reg { name: c_str!("pcall"), func: Some(luaB_pcall) },
+        lauxlib::luaL_Reg { name: c_str!("print"), func: Some(luaB_print) },
+        lauxlib::luaL_Reg { name: c_str!("rawequal"), func: Some(luaB_rawequal) },
+        lauxlib::luaL_Reg { name: c_str!("rawlen"), func: Some(luaB_rawlen) },
+        lauxlib::luaL_Reg { name: c_str!("rawget"), func: Some(luaB_rawget) },
+        lauxlib::luaL_Reg { name: c_str!("rawset"), func: Some(luaB_rawset) },
+        lauxlib::luaL_Reg { name: c_str!("select"), func: Some(luaB_select) },
+        lauxlib::luaL_Reg { name: c_str!("setmetatable"), func: Some(luaB_setmetatable) },
+        lauxlib::luaL_Reg { name: c_str!("tonumber"), func: Some(luaB_tonumber) },
+        lauxlib::luaL_Reg { name: c_str!("tostring"), func: Some(luaB_tostring) },
+        lauxlib::luaL_Reg { name: c_str!("type"), func: Some(luaB_type) },
+        lauxlib::luaL_Reg { name: c_str!("xpcall"), func: Some(luaB_xpcall) },
+        /* placeholders */
+        lauxlib::luaL_Reg { name: c_str!("_G"), func: None },
+        lauxlib::luaL_Reg { name: c_str!("_VERSION"), func: None },
+        lauxlib::luaL_Reg { name: ptr::null(), func: None },
+    ];
+    /* open lib into global table */
+    lua::lua_pushglobaltable(l);
+    lauxlib::luaL_setfuncs(l, base_funcs.as_ptr(), 0);
+    /* set global _G */
+    lua::lua_pushvalue(l, -1);
+    lua::lua_setfield(l, -2, c_str!("_G"));
+    /* set global _VERSION */
+    lua::lua_pushlstring(l, lua::LUA_VERSION.as_ptr() as *const c_char, lua::LUA_VERSION.len());
+    lua::lua_setfield(l, -2, c_str!("_VERSION"));
+    1
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::b_str2int;
+
+    #[test]
+    fn test_b_str2int() {
+        assert_eq!(b_str2int(b"  ff  ", 16), Some(255));
+        assert_eq!(b_str2int(b"-zz", 36), Some(-1295));
+        assert_eq!(b_str2int(b"10", 2), Some(2));
+        assert_eq!(b_str2int(b"12", 2), None);
+        assert_eq!(b_str2int(b"", 10), None);
+        assert_eq!(b_str2int(b"1 1", 10), None);
+        assert_eq!(b_str2int(b"1\x001", 10), None);
+    }
+}
@@ -0,0 +1,1358 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Code generator for Lua.
+
+#![allow(non_camel_case_types, non_snake_case)]
+
+use ffi::lua::{self, lua_Integer, lua_Number};
+use lgc::luaC_barrier;
+use llex::luaX_syntaxerror;
+use lmem::luaM_growvector;
+use lobject::{luaO_arith, setbvalue, setfltvalue, sethvalue, setivalue, setnilvalue, setpvalue,
+              setsvalue, GCObject, TString, TValue};
+use lopcodes::*;
+use lopcodes::OpCode::*;
+use lparser::{expdesc, FuncState};
+use lparser::expkind::*;
+use lstate::lua_State;
+use ltable::luaH_set;
+use libc::{c_int, c_void, size_t};
+use std::mem;
+use std::ptr;
+
+
+/* the exported functions below, from 'lcode.c' when it is linked instead (see 'build.rs') */
+#[cfg(not(rust_lcode))]
+extern "C-unwind" {
+    pub fn luaK_nil(fs: *mut FuncState, from: c_int, n: c_int);
+    pub fn luaK_concat(fs: *mut FuncState, l1: *mut c_int, l2: c_int);
+    pub fn luaK_jump(fs: *mut FuncState) -> c_int;
+    pub fn luaK_ret(fs: *mut FuncState, first: c_int, nret: c_int);
+    pub fn luaK_getlabel(fs: *mut FuncState) -> c_int;
+    pub fn luaK_patchtohere(fs: *mut FuncState, list: c_int);
+    pub fn luaK_patchlist(fs: *mut FuncState, list: c_int, target: c_int);
+    pub fn luaK_patchclose(fs: *mut FuncState, list: c_int, level: c_int);
+    pub fn luaK_codeABC(fs: *mut FuncState, o: OpCode, a: c_int, b: c_int, c: c_int) -> c_int;
+    pub fn luaK_codeABx(fs: *mut FuncState, o: OpCode, a: c_int, bc: u32) -> c_int;
+    pub fn luaK_codek(fs: *mut FuncState, reg: c_int, k: c_int) -> c_int;
+    pub fn luaK_checkstack(fs: *mut FuncState, n: c_int);
+    pub fn luaK_reserveregs(fs: *mut FuncState, n: c_int);
+    pub fn luaK_stringK(fs: *mut FuncState, s: *mut TString) -> c_int;
+    pub fn luaK_intK(fs: *mut FuncState, n: lua_Integer) -> c_int;
+    pub fn luaK_setreturns(fs: *mut FuncState, e: *mut expdesc, nresults: c_int);
+    pub fn luaK_setoneret(fs: *mut FuncState, e: *mut expdesc);
+    pub fn luaK_dischargevars(fs: *mut FuncState, e: *mut expdesc);
+    pub fn luaK_exp2nextreg(fs: *mut FuncState, e: *mut expdesc);
+    pub fn luaK_exp2anyreg(fs: *mut FuncState, e: *mut expdesc) -> c_int;
+    pub fn luaK_exp2anyregup(fs: *mut FuncState, e: *mut expdesc);
+    pub fn luaK_exp2val(fs: *mut FuncState, e: *mut expdesc);
+    pub fn luaK_exp2RK(fs: *mut FuncState, e: *mut expdesc) -> c_int;
+    pub fn luaK_storevar(fs: *mut FuncState, var: *mut expdesc, ex: *mut expdesc);
+    pub fn luaK_self(fs: *mut FuncState, e: *mut expdesc, key: *mut expdesc);
+    pub fn luaK_goiftrue(fs: *mut FuncState, e: *mut expdesc);
+    pub fn luaK_goiffalse(fs: *mut FuncState, e: *mut expdesc);
+    pub fn luaK_indexed(fs: *mut FuncState, t: *mut expdesc, k: *mut expdesc);
+    pub fn luaK_prefix(fs: *mut FuncState, op: UnOpr, e: *mut expdesc, line: c_int);
+    pub fn luaK_infix(fs: *mut FuncState, op: BinOpr, v: *mut expdesc);
+    pub fn luaK_posfix(fs: *mut FuncState, op: BinOpr, e1: *mut expdesc, e2: *mut expdesc,
+                       line: c_int);
+    pub fn luaK_fixline(fs: *mut FuncState, line: c_int);
+    pub fn luaK_setlist(fs: *mut FuncState, base: c_int, nelems: c_int, tostore: c_int);
+}
+
+
+extern "C-unwind" {
+    fn luaV_equalobj(L: *mut lua_State, t1: *const TValue, t2: *const TValue) -> c_int;
+    fn luaV_tointeger(obj: *const TValue, p: *mut lua_Integer, mode: c_int) -> c_int;
+}
+
+
+/*
+** Marks the end of a patch list. It is an invalid value both as an absolute
+** address, and as a list link (would link an element to itself).
+*/
+pub const NO_JUMP: c_int = -1;
+
+
+/*
+** grep "ORDER OPR" if you change these enums  (ORDER OP)
+*/
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinOpr {
+    OPR_ADD, OPR_SUB, OPR_MUL, OPR_MOD, OPR_POW,
+    OPR_DIV,
+    OPR_IDIV,
+    OPR_BAND, OPR_BOR, OPR_BXOR,
+    OPR_SHL, OPR_SHR,
+    OPR_CONCAT,
+    OPR_EQ, OPR_LT, OPR_LE,
+    OPR_NE, OPR_GT, OPR_GE,
+    OPR_AND, OPR_OR,
+    OPR_NOBINOPR,
+}
+
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnOpr { OPR_MINUS, OPR_BNOT, OPR_NOT, OPR_LEN, OPR_NOUNOPR }
+
+use self::BinOpr::*;
+use self::UnOpr::*;
+
+
+/* get (pointer to) instruction of given 'expdesc' */
+pub unsafe fn getinstruction(fs: *mut FuncState, e: *const expdesc) -> *mut Instruction {
+    (*(*fs).f).code.offset((*e).u.info as isize)
+}
+
+pub unsafe fn luaK_codeAsBx(fs: *mut FuncState, o: OpCode, A: c_int, sBx: c_int) -> c_int {
+    luaK_codeABx(fs, o, A, (sBx + MAXARG_sBx) as u32)
+}
+
+pub unsafe fn luaK_setmultret(fs: *mut FuncState, e: *mut expdesc) {
+    luaK_setreturns(fs, e, lua::LUA_MULTRET)
+}
+
+pub unsafe fn luaK_jumpto(fs: *mut FuncState, t: c_int) {
+    luaK_patchlist(fs, luaK_jump(fs), t)
+}
+
+
+/* Maximum number of registers in a Lua function (must fit in 8 bits) */
+const MAXREGS: c_int = 255;
+
+
+unsafe fn hasjumps(e: *const expdesc) -> bool { (*e).t != (*e).f }
+
+
+/*
+** If expression is a numeric constant, fills 'v' with its value
+** and returns true. Otherwise, returns false.
+*/
+unsafe fn tonumeral(e: *const expdesc, v: *mut TValue) -> bool {
+    if hasjumps(e) {
+        return false;  /* not a numeral */
+    }
+    match (*e).k {
+        VKINT => {
+            if !v.is_null() { setivalue(v, (*e).u.ival); }
+            true
+        }
+        VKFLT => {
+            if !v.is_null() { setfltvalue(v, (*e).u.nval); }
+            true
+        }
+        _ => false,
+    }
+}
+
+
+/*
+** Create a OP_LOADNIL instruction, but try to optimize: if the previous
+** instruction is also OP_LOADNIL and ranges are compatible, adjust
+** range of previous instruction instead of emitting a new one. (For
+** instance, 'local a; local b' will generate a single opcode.)
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_nil(fs: *mut FuncState, mut from: c_int, n: c_int) {
+    let mut l = from + n - 1;  /* last register to set nil */
+    if (*fs).pc > (*fs).lasttarget {  /* no jumps to current position? */
+        let previous = &mut *(*(*fs).f).code.offset(((*fs).pc - 1) as isize);
+        if GET_OPCODE(*previous) == OP_LOADNIL {  /* previous is LOADNIL? */
+            let pfrom = GETARG_A(*previous);  /* get previous range */
+            let pl = pfrom + GETARG_B(*previous);
+            if (pfrom <= from && from <= pl + 1) ||
+               (from <= pfrom && pfrom <= l + 1) {  /* can connect both? */
+                if pfrom < from { from = pfrom; }  /* from = min(from, pfrom) */
+                if pl > l { l = pl; }  /* l = max(l, pl) */
+                SETARG_A(previous, from);
+                SETARG_B(previous, l - from);
+                return;
+            }
+        }  /* else go through */
+    }
+    luaK_codeABC(fs, OP_LOADNIL, from, n - 1, 0);  /* else no optimization */
+}
+
+
+/*
+** Gets the destination address of a jump instruction. Used to traverse
+** a list of jumps.
+*/
+unsafe fn getjump(fs: *mut FuncState, pc: c_int) -> c_int {
+    let offset = GETARG_sBx(*(*(*fs).f).code.offset(pc as isize));
+    if offset == NO_JUMP {  /* point to itself represents end of list */
+        NO_JUMP  /* end of list */
+    } else {
+        (pc + 1) + offset  /* turn offset into absolute position */
+    }
+}
+
+
+/*
+** Fix jump instruction at position 'pc' to jump to 'dest'.
+** (Jump addresses are relative in Lua)
+*/
+unsafe fn fixjump(fs: *mut FuncState, pc: c_int, dest: c_int) {
+    let jmp = &mut *(*(*fs).f).code.offset(pc as isize);
+    let offset = dest - (pc + 1);
+    debug_assert!(dest != NO_JUMP);
+    if offset.abs() > MAXARG_sBx {
+        luaX_syntaxerror((*fs).ls, c_str!("control structure too long"));
+    }
+    SETARG_sBx(jmp, offset);
+}
+
+
+/*
+** Concatenate jump-list 'l2' into jump-list 'l1'
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_concat(fs: *mut FuncState, l1: *mut c_int, l2: c_int) {
+    if l2 == NO_JUMP {  /* nothing to concatenate? */
+    } else if *l1 == NO_JUMP {  /* no original list? */
+        *l1 = l2;  /* 'l1' points to 'l2' */
+    } else {
+        let mut list = *l1;
+        loop {  /* find last element */
+            let next = getjump(fs, list);
+            if next == NO_JUMP { break; }
+            list = next;
+        }
+        fixjump(fs, list, l2);  /* last element links to 'l2' */
+    }
+}
+
+
+/*
+** Create a jump instruction and return its position, so its destination
+** can be fixed later (with 'fixjump'). If there are jumps to
+** this position (kept in 'jpc'), link them all together so that
+** 'patchlistaux' will fix all them directly to the final destination.
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_jump(fs: *mut FuncState) -> c_int {
+    let jpc = (*fs).jpc;  /* save list of jumps to here */
+    (*fs).jpc = NO_JUMP;  /* no more jumps to here */
+    let mut j = luaK_codeAsBx(fs, OP_JMP, 0, NO_JUMP);
+    luaK_concat(fs, &mut j, jpc);  /* keep them on hold */
+    j
+}
+
+
+/*
+** Code a 'return' instruction
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_ret(fs: *mut FuncState, first: c_int, nret: c_int) {
+    luaK_codeABC(fs, OP_RETURN, first, nret + 1, 0);
+}
+
+
+/*
+** Code a "conditional jump", that is, a test or comparison opcode
+** followed by a jump. Return jump position.
+*/
+unsafe fn condjump(fs: *mut FuncState, op: OpCode, A: c_int, B: c_int, C: c_int) -> c_int {
+    luaK_codeABC(fs, op, A, B, C);
+    luaK_jump(fs)
+}
+
+
+/*
+** returns current 'pc' and marks it as a jump target (to avoid wrong
+** optimizations with consecutive instructions not in the same basic block).
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_getlabel(fs: *mut FuncState) -> c_int {
+    (*fs).lasttarget = (*fs).pc;
+    (*fs).pc
+}
+
+
+/*
+** Returns the position of the instruction "controlling" a given
+** jump (that is, its condition), or the jump itself if it is
+** unconditional.
+*/
+unsafe fn getjumpcontrol(fs: *mut FuncState, pc: c_int) -> *mut Instruction {
+    let pi = (*(*fs).f).code.offset(pc as isize);
+    if pc >= 1 && testTMode(GET_OPCODE(*pi.offset(-1))) {
+        pi.offset(-1)
+    } else {
+        pi
+    }
+}
+
+
+/*
+** Patch destination register for a TESTSET instruction.
+** If instruction in position 'node' is not a TESTSET, return false ("fails").
+** Otherwise, if 'reg' is not 'NO_REG', set it as the destination
+** register. Otherwise, change instruction to a simple 'TEST' (produces
+** no register value)
+*/
+unsafe fn patchtestreg(fs: *mut FuncState, node: c_int, reg: c_int) -> bool {
+    let i = &mut *getjumpcontrol(fs, node);
+    if GET_OPCODE(*i) != OP_TESTSET {
+        return false;  /* cannot patch other instructions */
+    }
+    if reg != NO_REG && reg != GETARG_B(*i) {
+        SETARG_A(i, reg);
+    } else {
+        /* no register to put value or register already has the value;
+           change instruction to simple test */
+        *i = CREATE_ABC(OP_TEST, GETARG_B(*i), 0, GETARG_C(*i));
+    }
+    true
+}
+
+
+/*
+** Traverse a list of tests ensuring no one produces a value
+*/
+unsafe fn removevalues(fs: *mut FuncState, mut list: c_int) {
+    while list != NO_JUMP {
+        patchtestreg(fs, list, NO_REG);
+        list = getjump(fs, list);
+    }
+}
+
+
+/*
+** Traverse a list of tests, patching their destination address and
+** registers: tests producing values jump to 'vtarget' (and put their
+** values in 'reg'), other tests jump to 'dtarget'.
+*/
+unsafe fn patchlistaux(fs: *mut FuncState, mut list: c_int, vtarget: c_int, reg: c_int,
+                       dtarget: c_int) {
+    while list != NO_JUMP {
+        let next = getjump(fs, list);
+        if patchtestreg(fs, list, reg) {
+            fixjump(fs, list, vtarget);
+        } else {
+            fixjump(fs, list, dtarget);  /* jump to default target */
+        }
+        list = next;
+    }
+}
+
+
+/*
+** Ensure all pending jumps to current position are fixed (jumping
+** to current position with no values) and reset list of pending
+** jumps
+*/
+unsafe fn dischargejpc(fs: *mut FuncState) {
+    patchlistaux(fs, (*fs).jpc, (*fs).pc, NO_REG, (*fs).pc);
+    (*fs).jpc = NO_JUMP;
+}
+
+
+/*
+** Add elements in 'list' to list of pending jumps to "here"
+** (current position)
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_patchtohere(fs: *mut FuncState, list: c_int) {
+    luaK_getlabel(fs);  /* mark "here" as a jump target */
+    luaK_concat(fs, &mut (*fs).jpc, list);
+}
+
+
+/*
+** Path all jumps in 'list' to jump to 'target'.
+** (The assert means that we cannot fix a jump to a forward address
+** because we only know addresses once code is generated.)
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_patchlist(fs: *mut FuncState, list: c_int, target: c_int) {
+    if target == (*fs).pc {  /* 'target' is current position? */
+        luaK_patchtohere(fs, list);  /* add list to pending jumps */
+    } else {
+        debug_assert!(target < (*fs).pc);
+        patchlistaux(fs, list, target, NO_REG, target);
+    }
+}
+
+
+/*
+** Path all jumps in 'list' to close upvalues up to given 'level'
+** (The assertion checks that jumps either were closing nothing
+** or were closing higher levels, from inner blocks.)
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_patchclose(fs: *mut FuncState, mut list: c_int, mut level: c_int) {
+    level += 1;  /* argument is +1 to reserve 0 as non-op */
+    while list != NO_JUMP {
+        let i = &mut *(*(*fs).f).code.offset(list as isize);
+        debug_assert!(GET_OPCODE(*i) == OP_JMP && (GETARG_A(*i) == 0 || GETARG_A(*i) >= level));
+        SETARG_A(i, level);
+        list = getjump(fs, list);
+    }
+}
+
+
+/*
+** Emit instruction 'i', checking for array sizes and saving also its
+** line information. Return 'i' position.
+*/
+unsafe fn luaK_code(fs: *mut FuncState, i: Instruction) -> c_int {
+    let f = (*fs).f;
+    let L = (*(*fs).ls).L;
+    dischargejpc(fs);  /* 'pc' will change */
+    /* put new instruction in code array */
+    luaM_growvector(L, &mut (*f).code, (*fs).pc, &mut (*f).sizecode, c_int::MAX,
+                    c_str!("opcodes"));
+    *(*f).code.offset((*fs).pc as isize) = i;
+    /* save corresponding line information */
+    luaM_growvector(L, &mut (*f).lineinfo, (*fs).pc, &mut (*f).sizelineinfo, c_int::MAX,
+                    c_str!("opcodes"));
+    *(*f).lineinfo.offset((*fs).pc as isize) = (*(*fs).ls).lastline;
+    (*fs).pc += 1;
+    (*fs).pc - 1
+}
+
+
+/*
+** Format and emit an 'iABC' instruction. (Assertions check consistency
+** of parameters versus opcode.)
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_codeABC(fs: *mut FuncState, o: OpCode, a: c_int, b: c_int, c: c_int) -> c_int {
+    debug_assert!(getOpMode(o) == OpMode::iABC);
+    debug_assert!(getBMode(o) != OpArgMask::OpArgN || b == 0);
+    debug_assert!(getCMode(o) != OpArgMask::OpArgN || c == 0);
+    debug_assert!(a <= MAXARG_A && b <= MAXARG_B && c <= MAXARG_C);
+    luaK_code(fs, CREATE_ABC(o, a, b, c))
+}
+
+
+/*
+** Format and emit an 'iABx' instruction.
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_codeABx(fs: *mut FuncState, o: OpCode, a: c_int, bc: u32) -> c_int {
+    debug_assert!(getOpMode(o) == OpMode::iABx || getOpMode(o) == OpMode::iAsBx);
+    debug_assert!(getCMode(o) == OpArgMask::OpArgN);
+    debug_assert!(a <= MAXARG_A && bc <= MAXARG_Bx as u32);
+    luaK_code(fs, CREATE_ABx(o, a, bc))
+}
+
+
+/*
+** Emit an "extra argument" instruction (format 'iAx')
+*/
+unsafe fn codeextraarg(fs: *mut FuncState, a: c_int) -> c_int {
+    debug_assert!(a <= MAXARG_Ax);
+    luaK_code(fs, CREATE_Ax(OP_EXTRAARG, a))
+}
+
+
+/*
+** Emit a "load constant" instruction, using either 'OP_LOADK'
+** (if constant index 'k' fits in 18 bits) or an 'OP_LOADKX'
+** instruction with "extra argument".
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_codek(fs: *mut FuncState, reg: c_int, k: c_int) -> c_int {
+    if k <= MAXARG_Bx {
+        luaK_codeABx(fs, OP_LOADK, reg, k as u32)
+    } else {
+        let p = luaK_codeABx(fs, OP_LOADKX, reg, 0);
+        codeextraarg(fs, k);
+        p
+    }
+}
+
+
+/*
+** Check register-stack level, keeping track of its maximum size
+** in field 'maxstacksize'
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_checkstack(fs: *mut FuncState, n: c_int) {
+    let newstack = (*fs).freereg as c_int + n;
+    if newstack > (*(*fs).f).maxstacksize as c_int {
+        if newstack >= MAXREGS {
+            luaX_syntaxerror((*fs).ls, c_str!("function or expression needs too many registers"));
+        }
+        (*(*fs).f).maxstacksize = newstack as u8;
+    }
+}
+
+
+/*
+** Reserve 'n' registers in register stack
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_reserveregs(fs: *mut FuncState, n: c_int) {
+    luaK_checkstack(fs, n);
+    (*fs).freereg = ((*fs).freereg as c_int + n) as u8;
+}
+
+
+/*
+** Free register 'reg', if it is neither a constant index nor
+** a local variable.
+)
+*/
+unsafe fn freereg(fs: *mut FuncState, reg: c_int) {
+    if !ISK(reg) && reg >= (*fs).nactvar as c_int {
+        (*fs).freereg -= 1;
+        debug_assert!(reg == (*fs).freereg as c_int);
+    }
+}
+
+
+/*
+** Free register used by expression 'e' (if any)
+*/
+unsafe fn freeexp(fs: *mut FuncState, e: *const expdesc) {
+    if (*e).k == VNONRELOC {
+        freereg(fs, (*e).u.info);
+    }
+}
+
+
+/*
+** Free registers used by expressions 'e1' and 'e2' (if any) in proper
+** order.
+*/
+unsafe fn freeexps(fs: *mut FuncState, e1: *const expdesc, e2: *const expdesc) {
+    let r1 = if (*e1).k == VNONRELOC { (*e1).u.info } else { -1 };
+    let r2 = if (*e2).k == VNONRELOC { (*e2).u.info } else { -1 };
+    if r1 > r2 {
+        freereg(fs, r1);
+        freereg(fs, r2);
+    } else {
+        freereg(fs, r2);
+        freereg(fs, r1);
+    }
+}
+
+
+/*
+** Add constant 'v' to prototype's list of constants (field 'k').
+** Use scanner's table to cache position of constants in constant list
+** and try to reuse constants. Because some values should not be used
+** as keys (nil cannot be a key, integer keys can collapse with float
+** keys), the caller must provide a useful 'key' for indexing the cache.
+*/
+unsafe fn addk(fs: *mut FuncState, key: *mut TValue, v: *mut TValue) -> c_int {
+    let L = (*(*fs).ls).L;
+    let f = (*fs).f;
+    let idx = luaH_set(L, (*(*fs).ls).h, key);  /* index scanner table */
+    if (*idx).ttisinteger() {  /* is there an index there? */
+        let k = (*idx).ivalue() as c_int;
+        /* correct value? (warning: must distinguish floats from integers!) */
+        if k < (*fs).nk && (*(*f).k.offset(k as isize)).ttype() == (*v).ttype() &&
+                           luaV_equalobj(ptr::null_mut(), (*f).k.offset(k as isize), v) != 0 {
+            return k;  /* reuse index */
+        }
+    }
+    /* constant not found; create a new entry */
+    let mut oldsize = (*f).sizek;
+    let k = (*fs).nk;
+    /* numerical value does not need GC barrier;
+       table has no metatable, so it does not need to invalidate cache */
+    setivalue(idx, k as lua_Integer);
+    luaM_growvector(L, &mut (*f).k, k, &mut (*f).sizek, MAXARG_Ax, c_str!("constants"));
+    while oldsize < (*f).sizek {
+        setnilvalue((*f).k.offset(oldsize as isize));
+        oldsize += 1;
+    }
+    *(*f).k.offset(k as isize) = *v;
+    (*fs).nk += 1;
+    luaC_barrier(L, f as *mut GCObject, v);
+    k
+}
+
+
+/*
+** Add a string to list of constants and return its index.
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_stringK(fs: *mut FuncState, s: *mut TString) -> c_int {
+    let mut o = TValue::nil();
+    setsvalue(&mut o, s);
+    addk(fs, &mut o, &mut o)  /* use string itself as key */
+}
+
+
+/*
+** Add an integer to list of constants and return its index.
+** Integers use userdata as keys to avoid collision with floats with
+** same value; conversion to 'void*' is used only for hashing, so there
+** are no "precision" problems.
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_intK(fs: *mut FuncState, n: lua_Integer) -> c_int {
+    let (mut k, mut o) = (TValue::nil(), TValue::nil());
+    setpvalue(&mut k, n as size_t as *mut c_void);
+    setivalue(&mut o, n);
+    addk(fs, &mut k, &mut o)
+}
+
+
+/*
+** Add a float to list of constants and return its index.
+*/
+unsafe fn luaK_numberK(fs: *mut FuncState, r: lua_Number) -> c_int {
+    let mut o = TValue::nil();
+    setfltvalue(&mut o, r);
+    addk(fs, &mut o, &mut o)  /* use number itself as key */
+}
+
+
+/*
+** Add a boolean to list of constants and return its index.
+*/
+unsafe fn boolK(fs: *mut FuncState, b: c_int) -> c_int {
+    let mut o = TValue::nil();
+    setbvalue(&mut o, b);
+    addk(fs, &mut o, &mut o)  /* use boolean itself as key */
+}
+
+
+/*
+** Add nil to list of constants and return its index.
+*/
+unsafe fn nilK(fs: *mut FuncState) -> c_int {
+    let (mut k, mut v) = (TValue::nil(), TValue::nil());
+    setnilvalue(&mut v);
+    /* cannot use nil as key; instead use table itself to represent nil */
+    sethvalue(&mut k, (*(*fs).ls).h);
+    addk(fs, &mut k, &mut v)
+}
+
+
+/*
+** Fix an expression to return the number of results 'nresults'.
+** Either 'e' is a multi-ret expression (function call or vararg)
+** or 'nresults' is LUA_MULTRET (as any expression can satisfy that).
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_setreturns(fs: *mut FuncState, e: *mut expdesc, nresults: c_int) {
+    if (*e).k == VCALL {  /* expression is an open function call? */
+        SETARG_C(&mut *getinstruction(fs, e), nresults + 1);
+    } else if (*e).k == VVARARG {
+        let pc = &mut *getinstruction(fs, e);
+        SETARG_B(pc, nresults + 1);
+        SETARG_A(pc, (*fs).freereg as c_int);
+        luaK_reserveregs(fs, 1);
+    } else {
+        debug_assert!(nresults == lua::LUA_MULTRET);
+    }
+}
+
+
+/*
+** Fix an expression to return one result.
+** If expression is not a multi-ret expression (function call or
+** vararg), it already returns one result, so nothing needs to be done.
+** Function calls become VNONRELOC expressions (as its result comes
+** fixed in the base register of the call), while vararg expressions
+** become VRELOCABLE (as OP_VARARG puts its results where it wants).
+** (Calls are created returning one result, so that does not need
+** to be fixed.)
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_setoneret(fs: *mut FuncState, e: *mut expdesc) {
+    if (*e).k == VCALL {  /* expression is an open function call? */
+        /* already returns 1 value */
+        debug_assert!(GETARG_C(*getinstruction(fs, e)) == 2);
+        (*e).k = VNONRELOC;  /* result has fixed position */
+        (*e).u.info = GETARG_A(*getinstruction(fs, e));
+    } else if (*e).k == VVARARG {
+        SETARG_B(&mut *getinstruction(fs, e), 2);
+        (*e).k = VRELOCABLE;  /* can relocate its simple result */
+    }
+}
+
+
+/*
+** Ensure that expression 'e' is not a variable.
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_dischargevars(fs: *mut FuncState, e: *mut expdesc) {
+    match (*e).k {
+        VLOCAL => {  /* already in a register */
+            (*e).k = VNONRELOC;  /* becomes a non-relocatable value */
+        }
+        VUPVAL => {  /* move value to some (pending) register */
+            (*e).u.info = luaK_codeABC(fs, OP_GETUPVAL, 0, (*e).u.info, 0);
+            (*e).k = VRELOCABLE;
+        }
+        VINDEXED => {
+            freereg(fs, (*e).u.ind.idx as c_int);
+            let op = if (*e).u.ind.vt == VLOCAL as u8 {  /* is 't' in a register? */
+                freereg(fs, (*e).u.ind.t as c_int);
+                OP_GETTABLE
+            } else {
+                debug_assert!((*e).u.ind.vt == VUPVAL as u8);
+                OP_GETTABUP  /* 't' is in an upvalue */
+            };
+            (*e).u.info = luaK_codeABC(fs, op, 0, (*e).u.ind.t as c_int, (*e).u.ind.idx as c_int);
+            (*e).k = VRELOCABLE;
+        }
+        VVARARG | VCALL => {
+            luaK_setoneret(fs, e);
+        }
+        _ => {}  /* there is one value available (somewhere) */
+    }
+}
+
+
+/*
+** Ensures expression value is in register 'reg' (and therefore
+** 'e' will become a non-relocatable expression).
+*/
+unsafe fn discharge2reg(fs: *mut FuncState, e: *mut expdesc, reg: c_int) {
+    luaK_dischargevars(fs, e);
+    match (*e).k {
+        VNIL => {
+            luaK_nil(fs, reg, 1);
+        }
+        VFALSE | VTRUE => {
+            luaK_codeABC(fs, OP_LOADBOOL, reg, ((*e).k == VTRUE) as c_int, 0);
+        }
+        VK => {
+            luaK_codek(fs, reg, (*e).u.info);
+        }
+        VKFLT => {
+            let k = luaK_numberK(fs, (*e).u.nval);
+            luaK_codek(fs, reg, k);
+        }
+        VKINT => {
+            let k = luaK_intK(fs, (*e).u.ival);
+            luaK_codek(fs, reg, k);
+        }
+        VRELOCABLE => {
+            SETARG_A(&mut *getinstruction(fs, e), reg);  /* instruction will put result in 'reg' */
+        }
+        VNONRELOC => {
+            if reg != (*e).u.info {
+                luaK_codeABC(fs, OP_MOVE, reg, (*e).u.info, 0);
+            }
+        }
+        _ => {
+            debug_assert!((*e).k == VJMP);
+            return;  /* nothing to do... */
+        }
+    }
+    (*e).u.info = reg;
+    (*e).k = VNONRELOC;
+}
+
+
+/*
+** Ensures expression value is in any register.
+*/
+unsafe fn discharge2anyreg(fs: *mut FuncState, e: *mut expdesc) {
+    if (*e).k != VNONRELOC {  /* no fixed register yet? */
+        luaK_reserveregs(fs, 1);  /* get a register */
+        discharge2reg(fs, e, (*fs).freereg as c_int - 1);  /* put value there */
+    }
+}
+
+
+unsafe fn code_loadbool(fs: *mut FuncState, A: c_int, b: c_int, jump: c_int) -> c_int {
+    luaK_getlabel(fs);  /* those instructions may be jump targets */
+    luaK_codeABC(fs, OP_LOADBOOL, A, b, jump)
+}
+
+
+/*
+** check whether list has any jump that do not produce a value
+** or produce an inverted value
+*/
+unsafe fn need_value(fs: *mut FuncState, mut list: c_int) -> bool {
+    while list != NO_JUMP {
+        let i = *getjumpcontrol(fs, list);
+        if GET_OPCODE(i) != OP_TESTSET { return true; }
+        list = getjump(fs, list);
+    }
+    false  /* not found */
+}
+
+
+/*
+** Ensures final expression result (including results from its jump
+** lists) is in register 'reg'.
+** If expression has jumps, need to patch these jumps either to
+** its final position or to "load" instructions (for those tests
+** that do not produce values).
+*/
+unsafe fn exp2reg(fs: *mut FuncState, e: *mut expdesc, reg: c_int) {
+    discharge2reg(fs, e, reg);
+    if (*e).k == VJMP {  /* expression itself is a test? */
+        luaK_concat(fs, &mut (*e).t, (*e).u.info);  /* put this jump in 't' list */
+    }
+    if hasjumps(e) {
+        let mut p_f = NO_JUMP;  /* position of an eventual LOAD false */
+        let mut p_t = NO_JUMP;  /* position of an eventual LOAD true */
+        if need_value(fs, (*e).t) || need_value(fs, (*e).f) {
+            let fj = if (*e).k == VJMP { NO_JUMP } else { luaK_jump(fs) };
+            p_f = code_loadbool(fs, reg, 0, 1);
+            p_t = code_loadbool(fs, reg, 1, 0);
+            luaK_patchtohere(fs, fj);
+        }
+        let final_ = luaK_getlabel(fs);  /* position after whole expression */
+        patchlistaux(fs, (*e).f, final_, reg, p_f);
+        patchlistaux(fs, (*e).t, final_, reg, p_t);
+    }
+    (*e).t = NO_JUMP;
+    (*e).f = NO_JUMP;
+    (*e).u.info = reg;
+    (*e).k = VNONRELOC;
+}
+
+
+/*
+** Ensures final expression result (including results from its jump
+** lists) is in next available register.
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_exp2nextreg(fs: *mut FuncState, e: *mut expdesc) {
+    luaK_dischargevars(fs, e);
+    freeexp(fs, e);
+    luaK_reserveregs(fs, 1);
+    exp2reg(fs, e, (*fs).freereg as c_int - 1);
+}
+
+
+/*
+** Ensures final expression result (including results from its jump
+** lists) is in some (any) register and return that register.
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_exp2anyreg(fs: *mut FuncState, e: *mut expdesc) -> c_int {
+    luaK_dischargevars(fs, e);
+    if (*e).k == VNONRELOC {  /* expression already has a register? */
+        if !hasjumps(e) {  /* no jumps? */
+            return (*e).u.info;  /* result is already in a register */
+        }
+        if (*e).u.info >= (*fs).nactvar as c_int {  /* reg. is not a local? */
+            exp2reg(fs, e, (*e).u.info);  /* put final result in it */
+            return (*e).u.info;
+        }
+    }
+    luaK_exp2nextreg(fs, e);  /* otherwise, use next available register */
+    (*e).u.info
+}
+
+
+/*
+** Ensures final expression result is either in a register or in an
+** upvalue.
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_exp2anyregup(fs: *mut FuncState, e: *mut expdesc) {
+    if (*e).k != VUPVAL || hasjumps(e) {
+        luaK_exp2anyreg(fs, e);
+    }
+}
+
+
+/*
+** Ensures final expression result is either in a register or it is
+** a constant.
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_exp2val(fs: *mut FuncState, e: *mut expdesc) {
+    if hasjumps(e) {
+        luaK_exp2anyreg(fs, e);
+    } else {
+        luaK_dischargevars(fs, e);
+    }
+}
+
+
+/*
+** Ensures final expression result is in a valid R/K index
+** (that is, it is either in a register or in 'k' with an index
+** in the range of R/K indices).
+** Returns R/K index.
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_exp2RK(fs: *mut FuncState, e: *mut expdesc) -> c_int {
+    luaK_exp2val(fs, e);
+    let vk = match (*e).k {  /* move constants to 'k' */
+        VTRUE => { (*e).u.info = boolK(fs, 1); true }
+        VFALSE => { (*e).u.info = boolK(fs, 0); true }
+        VNIL => { (*e).u.info = nilK(fs); true }
+        VKINT => { (*e).u.info = luaK_intK(fs, (*e).u.ival); true }
+        VKFLT => { (*e).u.info = luaK_numberK(fs, (*e).u.nval); true }
+        VK => true,
+        _ => false,
+    };
+    if vk {
+        (*e).k = VK;
+        if (*e).u.info <= MAXINDEXRK {  /* constant fits in 'argC'? */
+            return RKASK((*e).u.info);
+        }
+    }
+    /* not a constant in the right range: put it in a register */
+    luaK_exp2anyreg(fs, e)
+}
+
+
+/*
+** Generate code to store result of expression 'ex' into variable 'var'.
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_storevar(fs: *mut FuncState, var: *mut expdesc, ex: *mut expdesc) {
+    match (*var).k {
+        VLOCAL => {
+            freeexp(fs, ex);
+            exp2reg(fs, ex, (*var).u.info);  /* compute 'ex' into proper place */
+            return;
+        }
+        VUPVAL => {
+            let e = luaK_exp2anyreg(fs, ex);
+            luaK_codeABC(fs, OP_SETUPVAL, e, (*var).u.info, 0);
+        }
+        VINDEXED => {
+            let op = if (*var).u.ind.vt == VLOCAL as u8 { OP_SETTABLE } else { OP_SETTABUP };
+            let e = luaK_exp2RK(fs, ex);
+            luaK_codeABC(fs, op, (*var).u.ind.t as c_int, (*var).u.ind.idx as c_int, e);
+        }
+        _ => debug_assert!(false),  /* invalid var kind to store */
+    }
+    freeexp(fs, ex);
+}
+
+
+/*
+** Emit SELF instruction (convert expression 'e' into 'e:key(e,').
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_self(fs: *mut FuncState, e: *mut expdesc, key: *mut expdesc) {
+    luaK_exp2anyreg(fs, e);
+    let ereg = (*e).u.info;  /* register where 'e' was placed */
+    freeexp(fs, e);
+    (*e).u.info = (*fs).freereg as c_int;  /* base register for op_self */
+    (*e).k = VNONRELOC;  /* self expression has a fixed register */
+    luaK_reserveregs(fs, 2);  /* function and 'self' produced by op_self */
+    let rk = luaK_exp2RK(fs, key);
+    luaK_codeABC(fs, OP_SELF, (*e).u.info, ereg, rk);
+    freeexp(fs, key);
+}
+
+
+/*
+** Negate condition 'e' (where 'e' is a comparison).
+*/
+unsafe fn negatecondition(fs: *mut FuncState, e: *mut expdesc) {
+    let pc = &mut *getjumpcontrol(fs, (*e).u.info);
+    debug_assert!(testTMode(GET_OPCODE(*pc)) && GET_OPCODE(*pc) != OP_TESTSET &&
+                                                GET_OPCODE(*pc) != OP_TEST);
+    let a = GETARG_A(*pc);
+    SETARG_A(pc, (a == 0) as c_int);
+}
+
+
+/*
+** Emit instruction to jump if 'e' is 'cond' (that is, if 'cond'
+** is true, code will jump if 'e' is true.) Return jump position.
+** Optimize when 'e' is 'not' something, inverting the condition
+** and removing the 'not'.
+*/
+unsafe fn jumponcond(fs: *mut FuncState, e: *mut expdesc, cond: c_int) -> c_int {
+    if (*e).k == VRELOCABLE {
+        let ie = *getinstruction(fs, e);
+        if GET_OPCODE(ie) == OP_NOT {
+            (*fs).pc -= 1;  /* remove previous OP_NOT */
+            return condjump(fs, OP_TEST, GETARG_B(ie), 0, (cond == 0) as c_int);
+        }
+        /* else go through */
+    }
+    discharge2anyreg(fs, e);
+    freeexp(fs, e);
+    condjump(fs, OP_TESTSET, NO_REG, (*e).u.info, cond)
+}
+
+
+/*
+** Emit code to go through if 'e' is true, jump otherwise.
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_goiftrue(fs: *mut FuncState, e: *mut expdesc) {
+    luaK_dischargevars(fs, e);
+    let pc = match (*e).k {  /* pc of new jump */
+        VJMP => {  /* condition? */
+            negatecondition(fs, e);  /* jump when it is false */
+            (*e).u.info  /* save jump position */
+        }
+        VK | VKFLT | VKINT | VTRUE => {
+            NO_JUMP  /* always true; do nothing */
+        }
+        _ => {
+            jumponcond(fs, e, 0)  /* jump when false */
+        }
+    };
+    luaK_concat(fs, &mut (*e).f, pc);  /* insert new jump in false list */
+    luaK_patchtohere(fs, (*e).t);  /* true list jumps to here (to go through) */
+    (*e).t = NO_JUMP;
+}
+
+
+/*
+** Emit code to go through if 'e' is false, jump otherwise.
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_goiffalse(fs: *mut FuncState, e: *mut expdesc) {
+    luaK_dischargevars(fs, e);
+    let pc = match (*e).k {  /* pc of new jump */
+        VJMP => {
+            (*e).u.info  /* already jump if true */
+        }
+        VNIL | VFALSE => {
+            NO_JUMP  /* always false; do nothing */
+        }
+        _ => {
+            jumponcond(fs, e, 1)  /* jump if true */
+        }
+    };
+    luaK_concat(fs, &mut (*e).t, pc);  /* insert new jump in 't' list */
+    luaK_patchtohere(fs, (*e).f);  /* false list jumps to here (to go through) */
+    (*e).f = NO_JUMP;
+}
+
+
+/*
+** Code 'not e', doing constant folding.
+*/
+unsafe fn codenot(fs: *mut FuncState, e: *mut expdesc) {
+    luaK_dischargevars(fs, e);
+    match (*e).k {
+        VNIL | VFALSE => {
+            (*e).k = VTRUE;  /* true == not nil == not false */
+        }
+        VK | VKFLT | VKINT | VTRUE => {
+            (*e).k = VFALSE;  /* false == not "x" == not 0.5 == not 1 == not true */
+        }
+        VJMP => {
+            negatecondition(fs, e);
+        }
+        VRELOCABLE | VNONRELOC => {
+            discharge2anyreg(fs, e);
+            freeexp(fs, e);
+            (*e).u.info = luaK_codeABC(fs, OP_NOT, 0, (*e).u.info, 0);
+            (*e).k = VRELOCABLE;
+        }
+        _ => debug_assert!(false),  /* cannot happen */
+    }
+    /* interchange true and false lists */
+    mem::swap(&mut (*e).f, &mut (*e).t);
+    removevalues(fs, (*e).f);  /* values are useless when negated */
+    removevalues(fs, (*e).t);
+}
+
+
+/*
+** Create expression 't[k]'. 't' must have its final result already in a
+** register or upvalue.
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_indexed(fs: *mut FuncState, t: *mut expdesc, k: *mut expdesc) {
+    debug_assert!(!hasjumps(t) && ((*t).k == VNONRELOC || (*t).k == VLOCAL || (*t).k == VUPVAL));
+    (*t).u.ind.t = (*t).u.info as u8;  /* register or upvalue index */
+    (*t).u.ind.idx = luaK_exp2RK(fs, k) as i16;  /* R/K index for key */
+    (*t).u.ind.vt = if (*t).k == VUPVAL { VUPVAL as u8 } else { VLOCAL as u8 };
+    (*t).k = VINDEXED;
+}
+
+
+/* 'tointeger' of 'lvm.h', with floor mode 'F2Ieq' */
+unsafe fn tointeger(o: *const TValue, i: *mut lua_Integer) -> bool {
+    if (*o).ttisinteger() {
+        *i = (*o).ivalue();
+        true
+    } else {
+        luaV_tointeger(o, i, 0) != 0
+    }
+}
+
+
+/*
+** Return false if folding can raise an error.
+** Bitwise operations need operands convertible to integers; division
+** operations cannot have 0 as divisor.
+*/
+unsafe fn validop(op: c_int, v1: *const TValue, v2: *const TValue) -> bool {
+    match op {
+        lua::LUA_OPBAND | lua::LUA_OPBOR | lua::LUA_OPBXOR |
+        lua::LUA_OPSHL | lua::LUA_OPSHR | lua::LUA_OPBNOT => {  /* conversion errors */
+            let mut i = 0;
+            tointeger(v1, &mut i) && tointeger(v2, &mut i)
+        }
+        lua::LUA_OPDIV | lua::LUA_OPIDIV | lua::LUA_OPMOD => {  /* division by 0 */
+            (*v2).nvalue() != 0.0
+        }
+        _ => true,  /* everything else is valid */
+    }
+}
+
+
+/*
+** Try to "constant-fold" an operation; return true iff successful.
+** (In this case, 'e1' has the final result.)
+*/
+unsafe fn constfolding(fs: *mut FuncState, op: c_int, e1: *mut expdesc, e2: *const expdesc) -> bool {
+    let (mut v1, mut v2, mut res) = (TValue::nil(), TValue::nil(), TValue::nil());
+    if !tonumeral(e1, &mut v1) || !tonumeral(e2, &mut v2) || !validop(op, &v1, &v2) {
+        return false;  /* non-numeric operands or not safe to fold */
+    }
+    luaO_arith((*(*fs).ls).L, op, &v1, &v2, &mut res);  /* does operation */
+    if res.ttisinteger() {
+        (*e1).k = VKINT;
+        (*e1).u.ival = res.ivalue();
+    } else {  /* folds neither NaN nor 0.0 (to avoid problems with -0.0) */
+        let n = res.fltvalue();
+        if n.is_nan() || n == 0.0 {
+            return false;
+        }
+        (*e1).k = VKFLT;
+        (*e1).u.nval = n;
+    }
+    true
+}
+
+
+/*
+** Emit code for unary expressions that "produce values"
+** (everything but 'not').
+** Expression to produce final result will be encoded in 'e'.
+*/
+unsafe fn codeunexpval(fs: *mut FuncState, op: OpCode, e: *mut expdesc, line: c_int) {
+    let r = luaK_exp2anyreg(fs, e);  /* opcodes operate only on registers */
+    freeexp(fs, e);
+    (*e).u.info = luaK_codeABC(fs, op, 0, r, 0);  /* generate opcode */
+    (*e).k = VRELOCABLE;  /* all those operations are relocatable */
+    luaK_fixline(fs, line);
+}
+
+
+/*
+** Emit code for binary expressions that "produce values"
+** (everything but logical operators 'and'/'or' and comparison
+** operators).
+** Expression to produce final result will be encoded in 'e1'.
+*/
+unsafe fn codebinexpval(fs: *mut FuncState, op: OpCode,
+                        e1: *mut expdesc, e2: *mut expdesc, line: c_int) {
+    let rk1 = luaK_exp2RK(fs, e1);  /* both operands are "RK" */
+    let rk2 = luaK_exp2RK(fs, e2);
+    freeexps(fs, e1, e2);
+    (*e1).u.info = luaK_codeABC(fs, op, 0, rk1, rk2);  /* generate opcode */
+    (*e1).k = VRELOCABLE;  /* all those operations are relocatable */
+    luaK_fixline(fs, line);
+}
+
+
+/*
+** Emit code for comparisons.
+** 'e1' was already put in R/K form by 'luaK_infix'.
+*/
+unsafe fn codecomp(fs: *mut FuncState, opr: BinOpr, e1: *mut expdesc, e2: *mut expdesc) {
+    let rk1 = if (*e1).k == VK { RKASK((*e1).u.info) }
+              else { debug_assert!((*e1).k == VNONRELOC); (*e1).u.info };
+    let rk2 = luaK_exp2RK(fs, e2);
+    freeexps(fs, e1, e2);
+    match opr {
+        OPR_NE => {  /* '(a ~= b)' ==> 'not (a == b)' */
+            (*e1).u.info = condjump(fs, OP_EQ, 0, rk1, rk2);
+        }
+        OPR_GT | OPR_GE => {
+            /* '(a > b)' ==> '(b < a)';  '(a >= b)' ==> '(b <= a)' */
+            let op = OpCode::from_u32((opr as u32 - OPR_NE as u32) + OP_EQ as u32);
+            (*e1).u.info = condjump(fs, op, 1, rk2, rk1);  /* invert operands */
+        }
+        _ => {  /* '==', '<', '<=' use their own opcodes */
+            let op = OpCode::from_u32((opr as u32 - OPR_EQ as u32) + OP_EQ as u32);
+            (*e1).u.info = condjump(fs, op, 1, rk1, rk2);
+        }
+    }
+    (*e1).k = VJMP;
+}
+
+
+/*
+** Aplly prefix operation 'op' to expression 'e'.
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_prefix(fs: *mut FuncState, op: UnOpr, e: *mut expdesc, line: c_int) {
+    let mut ef = expdesc::new();  /* fake 2nd operand */
+    ef.k = VKINT;
+    ef.u.ival = 0;
+    match op {
+        OPR_MINUS | OPR_BNOT => {
+            if !constfolding(fs, op as c_int + lua::LUA_OPUNM, e, &ef) {
+                codeunexpval(fs, OpCode::from_u32(op as u32 + OP_UNM as u32), e, line);
+            }
+        }
+        OPR_LEN => {
+            codeunexpval(fs, OpCode::from_u32(op as u32 + OP_UNM as u32), e, line);
+        }
+        OPR_NOT => codenot(fs, e),
+        _ => debug_assert!(false),
+    }
+}
+
+
+/*
+** Process 1st operand 'v' of binary operation 'op' before reading
+** 2nd operand.
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_infix(fs: *mut FuncState, op: BinOpr, v: *mut expdesc) {
+    match op {
+        OPR_AND => {
+            luaK_goiftrue(fs, v);  /* go ahead only if 'v' is true */
+        }
+        OPR_OR => {
+            luaK_goiffalse(fs, v);  /* go ahead only if 'v' is false */
+        }
+        OPR_CONCAT => {
+            luaK_exp2nextreg(fs, v);  /* operand must be on the 'stack' */
+        }
+        OPR_ADD | OPR_SUB |
+        OPR_MUL | OPR_DIV | OPR_IDIV |
+        OPR_MOD | OPR_POW |
+        OPR_BAND | OPR_BOR | OPR_BXOR |
+        OPR_SHL | OPR_SHR => {
+            if !tonumeral(v, ptr::null_mut()) {
+                luaK_exp2RK(fs, v);
+            }
+            /* else keep numeral, which may be folded with 2nd operand */
+        }
+        _ => {
+            luaK_exp2RK(fs, v);
+        }
+    }
+}
+
+
+/*
+** Finalize code for binary operation, after reading 2nd operand.
+** For '(a .. b .. c)' (which is '(a .. (b .. c))', because
+** concatenation is right associative), merge second CONCAT into first
+** one.
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_posfix(fs: *mut FuncState, op: BinOpr,
+                          e1: *mut expdesc, e2: *mut expdesc, line: c_int) {
+    match op {
+        OPR_AND => {
+            debug_assert!((*e1).t == NO_JUMP);  /* list closed by 'luK_infix' */
+            luaK_dischargevars(fs, e2);
+            luaK_concat(fs, &mut (*e2).f, (*e1).f);
+            *e1 = *e2;
+        }
+        OPR_OR => {
+            debug_assert!((*e1).f == NO_JUMP);  /* list closed by 'luK_infix' */
+            luaK_dischargevars(fs, e2);
+            luaK_concat(fs, &mut (*e2).t, (*e1).t);
+            *e1 = *e2;
+        }
+        OPR_CONCAT => {
+            luaK_exp2val(fs, e2);
+            if (*e2).k == VRELOCABLE && GET_OPCODE(*getinstruction(fs, e2)) == OP_CONCAT {
+                debug_assert!((*e1).u.info == GETARG_B(*getinstruction(fs, e2)) - 1);
+                freeexp(fs, e1);
+                SETARG_B(&mut *getinstruction(fs, e2), (*e1).u.info);
+                (*e1).k = VRELOCABLE;
+                (*e1).u.info = (*e2).u.info;
+            } else {
+                luaK_exp2nextreg(fs, e2);  /* operand must be on the 'stack' */
+                codebinexpval(fs, OP_CONCAT, e1, e2, line);
+            }
+        }
+        OPR_ADD | OPR_SUB | OPR_MUL | OPR_DIV |
+        OPR_IDIV | OPR_MOD | OPR_POW |
+        OPR_BAND | OPR_BOR | OPR_BXOR |
+        OPR_SHL | OPR_SHR => {
+            if !constfolding(fs, op as c_int + lua::LUA_OPADD, e1, e2) {
+                codebinexpval(fs, OpCode::from_u32(op as u32 + OP_ADD as u32), e1, e2, line);
+            }
+        }
+        OPR_EQ | OPR_LT | OPR_LE |
+        OPR_NE | OPR_GT | OPR_GE => {
+            codecomp(fs, op, e1, e2);
+        }
+        _ => debug_assert!(false),
+    }
+}
+
+
+/*
+** Change line information associated with current position.
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_fixline(fs: *mut FuncState, line: c_int) {
+    *(*(*fs).f).lineinfo.offset(((*fs).pc - 1) as isize) = line;
+}
+
+
+/*
+** Emit a SETLIST instruction.
+** 'base' is register that keeps table;
+** 'nelems' is #table plus those to be stored now;
+** 'tostore' is number of values (in registers 'base + 1',...) to add to
+** table (or LUA_MULTRET to add up to stack top).
+*/
+#[cfg(rust_lcode)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaK_setlist(fs: *mut FuncState, base: c_int, nelems: c_int, tostore: c_int) {
+    let c = (nelems - 1) / LFIELDS_PER_FLUSH + 1;
+    let b = if tostore == lua::LUA_MULTRET { 0 } else { tostore };
+    debug_assert!(tostore != 0 && tostore <= LFIELDS_PER_FLUSH);
+    if c <= MAXARG_C {
+        luaK_codeABC(fs, OP_SETLIST, base, b, c);
+    } else if c <= MAXARG_Ax {
+        luaK_codeABC(fs, OP_SETLIST, base, b, 0);
+        codeextraarg(fs, c);
+    } else {
+        luaX_syntaxerror((*fs).ls, c_str!("constructor too long"));
+    }
+    (*fs).freereg = (base + 1) as u8;  /* free registers with list values */
+}
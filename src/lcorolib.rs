@@ -0,0 +1,201 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Coroutine Library.
+
+#![allow(non_snake_case)]
+
+use ffi::lua::{self, lua_State};
+use ffi::lauxlib;
+use libc::c_int;
+use std::mem;
+
+/// The status of a coroutine, as reported by `coroutine.status`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CoroutineStatus {
+    /// The coroutine is the one asking for the status.
+    Running,
+    /// The coroutine has not started or has yielded.
+    Suspended,
+    /// The coroutine is active but not running (it resumed another one).
+    Normal,
+    /// The coroutine finished its body or stopped with an error.
+    Dead,
+}
+
+impl CoroutineStatus {
+    /// Returns the name `coroutine.status` uses for this status.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            CoroutineStatus::Running => "running",
+            CoroutineStatus::Suspended => "suspended",
+            CoroutineStatus::Normal => "normal",
+            CoroutineStatus::Dead => "dead",
+        }
+    }
+}
+
+
+/// Computes the status of coroutine 'co' as seen from thread 'l'.
+///
+/// # Safety
+///
+/// `l` and `co` must be valid threads of the same Lua state.
+pub unsafe fn status(l: *mut lua_State, co: *mut lua_State) -> CoroutineStatus {
+    if l == co {
+        return CoroutineStatus::Running;
+    }
+    match lua::lua_status(co) {
+        lua::LUA_YIELD => CoroutineStatus::Suspended,
+        lua::LUA_OK => {
+            let mut ar: lua::lua_Debug = mem::zeroed();
+            if lua::lua_getstack(co, 0, &mut ar) > 0 {  /* does it have frames? */
+                CoroutineStatus::Normal  /* it is running */
+            } else if lua::lua_gettop(co) == 0 {
+                CoroutineStatus::Dead
+            } else {
+                CoroutineStatus::Suspended  /* initial state */
+            }
+        }
+        _ => CoroutineStatus::Dead,  /* some error occurred */
+    }
+}
+
+
+unsafe fn getco(l: *mut lua_State) -> *mut lua_State {
+    let co = lua::lua_tothread(l, 1);
+    lauxlib::luaL_argcheck(l, !co.is_null() as c_int, 1, c_str!("thread expected"));
+    co
+}
+
+
+/*
+** Resumes 'co' with 'narg' arguments from 'l'. Returns the number of
+** values moved back to 'l', or Err(()) with the error message on the
+** top of 'l'.
+*/
+unsafe fn auxresume(l: *mut lua_State, co: *mut lua_State, narg: c_int) -> Result<c_int, ()> {
+    if lua::lua_checkstack(co, narg) == 0 {
+        lua::lua_pushstring(l, c_str!("too many arguments to resume"));
+        return Err(());
+    }
+    if lua::lua_status(co) == lua::LUA_OK && lua::lua_gettop(co) == 0 {
+        lua::lua_pushstring(l, c_str!("cannot resume dead coroutine"));
+        return Err(());
+    }
+    lua::lua_xmove(l, co, narg);
+    let status = lua::lua_resume(co, l, narg);
+    if status == lua::LUA_OK || status == lua::LUA_YIELD {
+        let nres = lua::lua_gettop(co);
+        if lua::lua_checkstack(l, nres + 1) == 0 {
+            lua::lua_pop(co, nres);  /* remove results anyway */
+            lua::lua_pushstring(l, c_str!("too many results to resume"));
+            return Err(());
+        }
+        lua::lua_xmove(co, l, nres);  /* move yielded values */
+        Ok(nres)
+    } else {
+        lua::lua_xmove(co, l, 1);  /* move error message */
+        Err(())
+    }
+}
+
+
+unsafe extern "C-unwind" fn luaB_coresume(l: *mut lua_State) -> c_int {
+    let co = getco(l);
+    match auxresume(l, co, lua::lua_gettop(l) - 1) {
+        Err(()) => {
+            lua::lua_pushboolean(l, 0);
+            lua::lua_insert(l, -2);
+            2  /* return false + error message */
+        }
+        Ok(r) => {
+            lua::lua_pushboolean(l, 1);
+            lua::lua_insert(l, -(r + 1));
+            r + 1  /* return true + 'resume' returns */
+        }
+    }
+}
+
+
+unsafe extern "C-unwind" fn luaB_auxwrap(l: *mut lua_State) -> c_int {
+    let co = lua::lua_tothread(l, lua::lua_upvalueindex(1));
+    match auxresume(l, co, lua::lua_gettop(l)) {
+        Ok(r) => r,
+        Err(()) => {
+            if lua::lua_type(l, -1) == lua::LUA_TSTRING {  /* error object is a string? */
+                lauxlib::luaL_where(l, 1);  /* add extra info */
+                lua::lua_insert(l, -2);
+                lua::lua_concat(l, 2);
+            }
+            lua::lua_error(l)  /* propagate error */
+        }
+    }
+}
+
+
+unsafe extern "C-unwind" fn luaB_cocreate(l: *mut lua_State) -> c_int {
+    lauxlib::luaL_checktype(l, 1, lua::LUA_TFUNCTION);
+    let nl = lua::lua_newthread(l);
+    lua::lua_pushvalue(l, 1);  /* move function to top */
+    lua::lua_xmove(l, nl, 1);  /* move function from L to NL */
+    1
+}
+
+
+unsafe extern "C-unwind" fn luaB_cowrap(l: *mut lua_State) -> c_int {
+    luaB_cocreate(l);
+    lua::lua_pushcclosure(l, Some(luaB_auxwrap), 1);
+    1
+}
+
+
+unsafe extern "C-unwind" fn luaB_yield(l: *mut lua_State) -> c_int {
+    lua::lua_yield(l, lua::lua_gettop(l))
+}
+
+
+unsafe extern "C-unwind" fn luaB_costatus(l: *mut lua_State) -> c_int {
+    let co = getco(l);
+    let name = status(l, co).name();
+    lua::lua_pushlstring(l, name.as_ptr() as *const _, name.len());
+    1
+}
+
+
+unsafe extern "C-unwind" fn luaB_yieldable(l: *mut lua_State) -> c_int {
+    lua::lua_pushboolean(l, lua::lua_isyieldable(l));
+    1
+}
+
+
+unsafe extern "C-unwind" fn luaB_corunning(l: *mut lua_State) -> c_int {
+    let ismain = lua::lua_pushthread(l);
+    lua::lua_pushboolean(l, ismain);
+    2
+}
+
+
+/// Opens the `coroutine` library in state `l`, leaving its table on the stack.
+///
+/// # Safety
+///
+/// `l` must be a valid Lua state.
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaopen_coroutine(l: *mut lua_State) -> c_int {
+    let co_funcs = [
+        lauxlib::luaL_Reg { name: c_str!("create"), func: Some(luaB_cocreate) },
+        lauxlib::luaL_Reg { name: c_str!("resume"), func: Some(luaB_coresume) },
+        lauxlib::luaL_Reg { name: c_str!("running"), func: Some(luaB_corunning) },
+        lauxlib::luaL_Reg { name: c_str!("status"), func: Some(luaB_costatus) },
+        lauxlib::luaL_Reg { name: c_str!("wrap"), func: Some(luaB_cowrap) },
+        lauxlib::luaL_Reg { name: c_str!("yield"), func: Some(luaB_yield) },
+        lauxlib::luaL_Reg { name: c_str!("isyieldable"), func: Some(luaB_yieldable) },
+        lauxlib::luaL_Reg { name: ::std::ptr::null(), func: None },
+    ];
+    lauxlib::luaL_newlib(l, co_funcs.as_ptr());
+    1
+}
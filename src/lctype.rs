@@ -0,0 +1,33 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! 'ctype' functions for Lua.
+//!
+//! These do not necessarily correspond to the similar functions of the
+//! standard C ctype.h: they are ASCII only, ignore the locale and accept
+//! EOZ (-1), for which they are all false.
+
+/* 'lalpha' (Lua alphabetic) and 'lalnum' (Lua alphanumeric) both include '_' */
+pub fn lislalpha(c: i32) -> bool {
+    (c >= 'a' as i32 && c <= 'z' as i32) || (c >= 'A' as i32 && c <= 'Z' as i32) || c == '_' as i32
+}
+
+pub fn lislalnum(c: i32) -> bool { lislalpha(c) || lisdigit(c) }
+
+pub fn lisdigit(c: i32) -> bool { c >= '0' as i32 && c <= '9' as i32 }
+
+pub fn lisprint(c: i32) -> bool { (0x20..0x7f).contains(&c) }
+
+pub fn lisspace(c: i32) -> bool { c == ' ' as i32 || (0x09..=0x0d).contains(&c) }
+
+pub fn lisxdigit(c: i32) -> bool {
+    lisdigit(c) || (c >= 'a' as i32 && c <= 'f' as i32) || (c >= 'A' as i32 && c <= 'F' as i32)
+}
+
+/*
+** this 'ltolower' only works for alphabetic characters
+*/
+pub fn ltolower(c: i32) -> i32 { c | ('A' as i32 ^ 'a' as i32) }
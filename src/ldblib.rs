@@ -0,0 +1,504 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Interface from Lua to its debug API.
+
+use ffi::lua::{self, lua_Debug, lua_State, lua_Integer};
+use ffi::lauxlib;
+use libc::{c_char, c_int, c_void};
+use std::cell::RefCell;
+use std::ffi::CStr;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+use std::{mem, ptr};
+use userdata;
+
+
+/// A hook written in Rust, called with the thread and the activation
+/// record of the event, as a `lua_Hook`.
+pub type Hook = Box<dyn FnMut(*mut lua_State, &mut lua_Debug)>;
+
+
+/*
+** The hook table at registry[&HOOKKEY] maps threads to their current
+** Lua hook function; a Rust hook is kept with the thread itself, under
+** the same key (see 'userdata.rs'). (We only need the unique address
+** of 'HOOKKEY'.)
+*/
+static HOOKKEY: u8 = 0;
+
+
+/*
+** If L1 != L, L1 can be in any state, and therefore there are no
+** guarantees about its stack space; any push in L1 must be
+** checked.
+*/
+unsafe fn checkstack(l: *mut lua_State, l1: *mut lua_State, n: c_int) {
+    if l != l1 && lua::lua_checkstack(l1, n) == 0 {
+        lauxlib::luaL_error(l, c_str!("stack overflow"));
+    }
+}
+
+
+unsafe extern "C-unwind" fn db_getregistry(l: *mut lua_State) -> c_int {
+    lua::lua_pushvalue(l, lua::LUA_REGISTRYINDEX);
+    1
+}
+
+
+unsafe extern "C-unwind" fn db_getmetatable(l: *mut lua_State) -> c_int {
+    lauxlib::luaL_checkany(l, 1);
+    if lua::lua_getmetatable(l, 1) == 0 {
+        lua::lua_pushnil(l);  /* no metatable */
+    }
+    1
+}
+
+
+unsafe extern "C-unwind" fn db_setmetatable(l: *mut lua_State) -> c_int {
+    let t = lua::lua_type(l, 2);
+    lauxlib::luaL_argcheck(l, (t == lua::LUA_TNIL || t == lua::LUA_TTABLE) as c_int, 2,
+                           c_str!("nil or table expected"));
+    lua::lua_settop(l, 2);
+    lua::lua_setmetatable(l, 1);
+    1  /* return 1st argument */
+}
+
+
+unsafe extern "C-unwind" fn db_getuservalue(l: *mut lua_State) -> c_int {
+    if lua::lua_type(l, 1) != lua::LUA_TUSERDATA {
+        lua::lua_pushnil(l);
+    } else {
+        lua::lua_getuservalue(l, 1);
+    }
+    1
+}
+
+
+unsafe extern "C-unwind" fn db_setuservalue(l: *mut lua_State) -> c_int {
+    lauxlib::luaL_checktype(l, 1, lua::LUA_TUSERDATA);
+    lauxlib::luaL_checkany(l, 2);
+    lua::lua_settop(l, 2);
+    lua::lua_setuservalue(l, 1);
+    1
+}
+
+
+/*
+** Auxiliary function used by several library functions: check for
+** an optional thread as function's first argument and return with it
+** 1 if this argument is present (so that functions can skip it to
+** access their other arguments)
+*/
+unsafe fn getthread(l: *mut lua_State) -> (*mut lua_State, c_int) {
+    if lua::lua_isthread(l, 1) != 0 {
+        (lua::lua_tothread(l, 1), 1)
+    } else {
+        (l, 0)  /* function will operate over current thread */
+    }
+}
+
+
+/*
+** Variations of 'lua_settable', used by 'db_getinfo' to put results
+** from 'lua_getinfo' into result table. Key is always a string;
+** value can be a string, an int, or a boolean.
+*/
+unsafe fn settabss(l: *mut lua_State, k: *const c_char, v: *const c_char) {
+    lua::lua_pushstring(l, v);
+    lua::lua_setfield(l, -2, k);
+}
+
+unsafe fn settabsi(l: *mut lua_State, k: *const c_char, v: c_int) {
+    lua::lua_pushinteger(l, v as lua_Integer);
+    lua::lua_setfield(l, -2, k);
+}
+
+unsafe fn settabsb(l: *mut lua_State, k: *const c_char, v: c_int) {
+    lua::lua_pushboolean(l, v);
+    lua::lua_setfield(l, -2, k);
+}
+
+
+/*
+** In function 'db_getinfo', the call to 'lua_getinfo' may push
+** results on the stack; later it creates the result table to put
+** these objects. Function 'treatstackoption' puts the result from
+** 'lua_getinfo' on top of the result table so that it can call
+** 'lua_setfield'.
+*/
+unsafe fn treatstackoption(l: *mut lua_State, l1: *mut lua_State, fname: *const c_char) {
+    if l == l1 {
+        lua::lua_rotate(l, -2, 1);  /* exchange object and table */
+    } else {
+        lua::lua_xmove(l1, l, 1);  /* move object to the "main" stack */
+    }
+    lua::lua_setfield(l, -2, fname);  /* put object into table */
+}
+
+
+/*
+** Calls 'lua_getinfo' and collects all results in a new table.
+** L1 needs stack space for an optional input (function) plus
+** two optional outputs (function and line table) from function
+** 'lua_getinfo'.
+*/
+unsafe extern "C-unwind" fn db_getinfo(l: *mut lua_State) -> c_int {
+    let mut ar: lua_Debug = mem::zeroed();
+    let (l1, arg) = getthread(l);
+    let mut options = lauxlib::luaL_optstring(l, arg + 2, c_str!("flnStu"));
+    checkstack(l, l1, 3);
+    if lua::lua_isfunction(l, arg + 1) != 0 {  /* info about a function? */
+        options = lua::lua_pushfstring(l, c_str!(">%s"), options);  /* add '>' to 'options' */
+        lua::lua_pushvalue(l, arg + 1);  /* move function to 'L1' stack */
+        lua::lua_xmove(l, l1, 1);
+    } else if lua::lua_getstack(l1, lauxlib::luaL_checkinteger(l, arg + 1) as c_int, &mut ar) == 0 {  /* stack level */
+        lua::lua_pushnil(l);  /* level out of range */
+        return 1;
+    }
+    if lua::lua_getinfo(l1, options, &mut ar) == 0 {
+        return lauxlib::luaL_argerror(l, arg + 2, c_str!("invalid option"));
+    }
+    let has = |c: u8| CStr::from_ptr(options).to_bytes().contains(&c);
+    lua::lua_createtable(l, 0, 0);  /* table to collect results */
+    if has(b'S') {
+        settabss(l, c_str!("source"), ar.source);
+        settabss(l, c_str!("short_src"), ar.short_src.as_ptr());
+        settabsi(l, c_str!("linedefined"), ar.linedefined);
+        settabsi(l, c_str!("lastlinedefined"), ar.lastlinedefined);
+        settabss(l, c_str!("what"), ar.what);
+    }
+    if has(b'l') {
+        settabsi(l, c_str!("currentline"), ar.currentline);
+    }
+    if has(b'u') {
+        settabsi(l, c_str!("nups"), ar.nups as c_int);
+        settabsi(l, c_str!("nparams"), ar.nparams as c_int);
+        settabsb(l, c_str!("isvararg"), ar.isvararg as c_int);
+    }
+    if has(b'n') {
+        settabss(l, c_str!("name"), ar.name);
+        settabss(l, c_str!("namewhat"), ar.namewhat);
+    }
+    if has(b't') {
+        settabsb(l, c_str!("istailcall"), ar.istailcall as c_int);
+    }
+    if has(b'L') {
+        treatstackoption(l, l1, c_str!("activelines"));
+    }
+    if has(b'f') {
+        treatstackoption(l, l1, c_str!("func"));
+    }
+    1  /* return table */
+}
+
+
+unsafe extern "C-unwind" fn db_getlocal(l: *mut lua_State) -> c_int {
+    let (l1, arg) = getthread(l);
+    let mut ar: lua_Debug = mem::zeroed();
+    let nvar = lauxlib::luaL_checkinteger(l, arg + 2) as c_int;  /* local-variable index */
+    if lua::lua_isfunction(l, arg + 1) != 0 {  /* function argument? */
+        lua::lua_pushvalue(l, arg + 1);  /* push function */
+        lua::lua_pushstring(l, lua::lua_getlocal(l, ptr::null(), nvar));  /* push local name */
+        1  /* return only name (there is no value) */
+    } else {  /* stack-level argument */
+        let level = lauxlib::luaL_checkinteger(l, arg + 1) as c_int;
+        if lua::lua_getstack(l1, level, &mut ar) == 0 {  /* out of range? */
+            return lauxlib::luaL_argerror(l, arg + 1, c_str!("level out of range"));
+        }
+        checkstack(l, l1, 1);
+        let name = lua::lua_getlocal(l1, &ar, nvar);
+        if !name.is_null() {
+            lua::lua_xmove(l1, l, 1);  /* move local value */
+            lua::lua_pushstring(l, name);  /* push name */
+            lua::lua_rotate(l, -2, 1);  /* re-order */
+            2
+        } else {
+            lua::lua_pushnil(l);  /* no name (nor value) */
+            1
+        }
+    }
+}
+
+
+unsafe extern "C-unwind" fn db_setlocal(l: *mut lua_State) -> c_int {
+    let (l1, arg) = getthread(l);
+    let mut ar: lua_Debug = mem::zeroed();
+    let level = lauxlib::luaL_checkinteger(l, arg + 1) as c_int;
+    let nvar = lauxlib::luaL_checkinteger(l, arg + 2) as c_int;
+    if lua::lua_getstack(l1, level, &mut ar) == 0 {  /* out of range? */
+        return lauxlib::luaL_argerror(l, arg + 1, c_str!("level out of range"));
+    }
+    lauxlib::luaL_checkany(l, arg + 3);
+    lua::lua_settop(l, arg + 3);
+    checkstack(l, l1, 1);
+    lua::lua_xmove(l, l1, 1);
+    let name = lua::lua_setlocal(l1, &ar, nvar);
+    if name.is_null() {
+        lua::lua_pop(l1, 1);  /* pop value (if not popped by 'lua_setlocal') */
+    }
+    lua::lua_pushstring(l, name);
+    1
+}
+
+
+/*
+** get (if 'get' is true) or set an upvalue from a closure
+*/
+unsafe fn auxupvalue(l: *mut lua_State, get: bool) -> c_int {
+    let n = lauxlib::luaL_checkinteger(l, 2) as c_int;  /* upvalue index */
+    lauxlib::luaL_checktype(l, 1, lua::LUA_TFUNCTION);  /* closure */
+    let name = if get { lua::lua_getupvalue(l, 1, n) } else { lua::lua_setupvalue(l, 1, n) };
+    if name.is_null() {
+        return 0;
+    }
+    lua::lua_pushstring(l, name);
+    lua::lua_insert(l, -(get as c_int + 1));  /* no-op if get is false */
+    get as c_int + 1
+}
+
+
+unsafe extern "C-unwind" fn db_getupvalue(l: *mut lua_State) -> c_int {
+    auxupvalue(l, true)
+}
+
+
+unsafe extern "C-unwind" fn db_setupvalue(l: *mut lua_State) -> c_int {
+    lauxlib::luaL_checkany(l, 3);
+    auxupvalue(l, false)
+}
+
+
+/*
+** Check whether a given upvalue from a given closure exists and
+** returns its index
+*/
+unsafe fn checkupval(l: *mut lua_State, argf: c_int, argnup: c_int) -> c_int {
+    let nup = lauxlib::luaL_checkinteger(l, argnup) as c_int;  /* upvalue index */
+    lauxlib::luaL_checktype(l, argf, lua::LUA_TFUNCTION);  /* closure */
+    lauxlib::luaL_argcheck(l, !lua::lua_getupvalue(l, argf, nup).is_null() as c_int, argnup,
+                           c_str!("invalid upvalue index"));
+    nup
+}
+
+
+unsafe extern "C-unwind" fn db_upvalueid(l: *mut lua_State) -> c_int {
+    let n = checkupval(l, 1, 2);
+    lua::lua_pushlightuserdata(l, lua::lua_upvalueid(l, 1, n));
+    1
+}
+
+
+unsafe extern "C-unwind" fn db_upvaluejoin(l: *mut lua_State) -> c_int {
+    let n1 = checkupval(l, 1, 2);
+    let n2 = checkupval(l, 3, 4);
+    lauxlib::luaL_argcheck(l, (lua::lua_iscfunction(l, 1) == 0) as c_int, 1, c_str!("Lua function expected"));
+    lauxlib::luaL_argcheck(l, (lua::lua_iscfunction(l, 3) == 0) as c_int, 3, c_str!("Lua function expected"));
+    lua::lua_upvaluejoin(l, 1, n1, 3, n2);
+    0
+}
+
+
+/*
+** Call hook function registered at hook table for the current
+** thread (if there is one)
+*/
+extern "C-unwind" fn hookf(l: *mut lua_State, ar: *mut lua_Debug) {
+    let hooknames = [c_str!("call"), c_str!("return"), c_str!("line"), c_str!("count"), c_str!("tail call")];
+    unsafe {
+        /* a clone keeps the hook alive even if it replaces itself */
+        if let Some(hook) = userdata::get_thread::<Rc<RefCell<Hook>>>(l, &HOOKKEY).cloned() {  /* is it a Rust hook? */
+            (*hook.borrow_mut())(l, &mut *ar);
+            return;
+        }
+        if lua::lua_rawgetp(l, lua::LUA_REGISTRYINDEX, &HOOKKEY as *const u8 as *const c_void) != lua::LUA_TTABLE {
+            return;  /* no hook table */
+        }
+        lua::lua_pushthread(l);
+        if lua::lua_rawget(l, -2) == lua::LUA_TFUNCTION {  /* is there a hook function? */
+            lua::lua_pushstring(l, hooknames[(*ar).event as usize]);  /* push event name */
+            if (*ar).currentline >= 0 {
+                lua::lua_pushinteger(l, (*ar).currentline as lua_Integer);  /* push current line */
+            } else {
+                lua::lua_pushnil(l);
+            }
+            let ok = lua::lua_getinfo(l, c_str!("lS"), ar);
+            debug_assert!(ok != 0);
+            lua::lua_call(l, 2, 0);  /* call hook function */
+        }
+    }
+}
+
+
+/*
+** Convert a string mask (for 'sethook') into a bit mask
+*/
+fn makemask(smask: &[u8], count: c_int) -> c_int {
+    let mut mask = 0;
+    if smask.contains(&b'c') { mask |= lua::LUA_MASKCALL; }
+    if smask.contains(&b'r') { mask |= lua::LUA_MASKRET; }
+    if smask.contains(&b'l') { mask |= lua::LUA_MASKLINE; }
+    if count > 0 { mask |= lua::LUA_MASKCOUNT; }
+    mask
+}
+
+
+/*
+** Convert a bit mask (for 'gethook') into a string mask
+*/
+fn unmakemask(mask: c_int) -> Vec<u8> {
+    let mut smask = Vec::with_capacity(3);
+    if mask & lua::LUA_MASKCALL != 0 { smask.push(b'c'); }
+    if mask & lua::LUA_MASKRET != 0 { smask.push(b'r'); }
+    if mask & lua::LUA_MASKLINE != 0 { smask.push(b'l'); }
+    smask
+}
+
+
+/// Sets a Rust function as the hook of thread `l`, as `debug.sethook`
+/// does for Lua functions; `None` turns off hooks. `debug.gethook`
+/// reports it as an "external hook".
+///
+/// # Safety
+///
+/// `l` must be a valid thread of a Lua state.
+pub unsafe fn set_hook(l: *mut lua_State, hook: Option<Hook>, mask: c_int, count: c_int) {
+    match hook {
+        Some(hook) => {
+            userdata::set_thread(l, &HOOKKEY, Rc::new(RefCell::new(hook)));
+            lua::lua_sethook(l, Some(hookf), mask, count);
+        }
+        None => {
+            userdata::remove_thread::<Rc<RefCell<Hook>>>(l, &HOOKKEY);
+            lua::lua_sethook(l, None, 0, 0);
+        }
+    }
+}
+
+
+unsafe extern "C-unwind" fn db_sethook(l: *mut lua_State) -> c_int {
+    let (l1, arg) = getthread(l);
+    let (func, mask, count): (lua::lua_Hook, c_int, c_int);
+    if lua::lua_isnoneornil(l, arg + 1) != 0 {  /* no hook? */
+        lua::lua_settop(l, arg + 1);
+        func = None; mask = 0; count = 0;  /* turn off hooks */
+    } else {
+        let smask = lauxlib::luaL_checkstring(l, arg + 2);
+        lauxlib::luaL_checktype(l, arg + 1, lua::LUA_TFUNCTION);
+        count = lauxlib::luaL_optinteger(l, arg + 3, 0) as c_int;
+        func = Some(hookf); mask = makemask(CStr::from_ptr(smask).to_bytes(), count);
+    }
+    if lua::lua_rawgetp(l, lua::LUA_REGISTRYINDEX, &HOOKKEY as *const u8 as *const c_void) == lua::LUA_TNIL {
+        lua::lua_createtable(l, 0, 2);  /* create a hook table */
+        lua::lua_pushvalue(l, -1);
+        lua::lua_rawsetp(l, lua::LUA_REGISTRYINDEX, &HOOKKEY as *const u8 as *const c_void);  /* set it in position */
+        lua::lua_pushstring(l, c_str!("k"));
+        lua::lua_setfield(l, -2, c_str!("__mode"));  /* hooktable.__mode = "k" */
+        lua::lua_pushvalue(l, -1);
+        lua::lua_setmetatable(l, -2);  /* setmetatable(hooktable) = hooktable */
+    }
+    checkstack(l, l1, 1);
+    lua::lua_pushthread(l1);
+    lua::lua_xmove(l1, l, 1);  /* key (thread) */
+    lua::lua_pushvalue(l, arg + 1);  /* value (hook function) */
+    lua::lua_rawset(l, -3);  /* hooktable[L1] = new Lua hook */
+    userdata::remove_thread::<Rc<RefCell<Hook>>>(l1, &HOOKKEY);  /* replacing any Rust hook */
+    lua::lua_sethook(l1, func, mask, count);
+    0
+}
+
+
+unsafe extern "C-unwind" fn db_gethook(l: *mut lua_State) -> c_int {
+    let (l1, _) = getthread(l);
+    let mask = lua::lua_gethookmask(l1);
+    let hook = lua::lua_gethook(l1);
+    if hook.is_none() {  /* no hook? */
+        lua::lua_pushnil(l);
+    } else if hook.map(|h| h as *const ()) != Some(hookf as *const ()) {  /* external hook? */
+        lua::lua_pushstring(l, c_str!("external hook"));
+    } else if userdata::get_thread::<Rc<RefCell<Hook>>>(l1, &HOOKKEY).is_some() {  /* Rust hook? */
+        lua::lua_pushstring(l, c_str!("external hook"));
+    } else {  /* hook table must exist */
+        lua::lua_rawgetp(l, lua::LUA_REGISTRYINDEX, &HOOKKEY as *const u8 as *const c_void);
+        checkstack(l, l1, 1);
+        lua::lua_pushthread(l1);
+        lua::lua_xmove(l1, l, 1);
+        lua::lua_rawget(l, -2);  /* 1st result = hooktable[L1] */
+        lua::lua_remove(l, -2);  /* remove hook table */
+    }
+    let smask = unmakemask(mask);
+    lua::lua_pushlstring(l, smask.as_ptr() as *const c_char, smask.len());  /* 2nd result = mask */
+    lua::lua_pushinteger(l, lua::lua_gethookcount(l1) as lua_Integer);  /* 3rd result = count */
+    3
+}
+
+
+unsafe extern "C-unwind" fn db_debug(l: *mut lua_State) -> c_int {
+    loop {
+        let mut buffer = Vec::new();
+        let _ = io::stderr().write_all(b"lua_debug> ");
+        let _ = io::stderr().flush();
+        let stdin = io::stdin();
+        let res = stdin.lock().read_until(b'\n', &mut buffer);
+        if res.map_or(true, |n| n == 0) || buffer == b"cont\n" {
+            return 0;
+        }
+        if lauxlib::luaL_loadbuffer(l, buffer.as_ptr() as *const c_char, buffer.len(),
+                                    c_str!("=(debug command)")) != 0 ||
+                lua::lua_pcall(l, 0, 0, 0) != 0 {
+            let msg = lua::lua_tostring(l, -1);
+            let msg = if msg.is_null() { &b"(error object is not a string)"[..] }
+                      else { CStr::from_ptr(msg).to_bytes() };
+            let _ = io::stderr().write_all(msg);
+            let _ = io::stderr().write_all(b"\n");
+        }
+        lua::lua_settop(l, 0);  /* remove eventual returns */
+    }
+}
+
+
+unsafe extern "C-unwind" fn db_traceback(l: *mut lua_State) -> c_int {
+    let (l1, arg) = getthread(l);
+    let msg = lua::lua_tostring(l, arg + 1);
+    if msg.is_null() && lua::lua_isnoneornil(l, arg + 1) == 0 {  /* non-string 'msg'? */
+        lua::lua_pushvalue(l, arg + 1);  /* return it untouched */
+    } else {
+        let level = lauxlib::luaL_optinteger(l, arg + 2, if l == l1 { 1 } else { 0 }) as c_int;
+        lauxlib::luaL_traceback(l, l1, msg, level);
+    }
+    1
+}
+
+
+/// Opens the `debug` library in state `l`, leaving its table on the stack.
+///
+/// # Safety
+///
+/// `l` must be a valid Lua state.
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaopen_debug(l: *mut lua_State) -> c_int {
+    let dblib = [
+        lauxlib::luaL_Reg { name: c_str!("debug"), func: Some(db_debug) },
+        lauxlib::luaL_Reg { name: c_str!("getuservalue"), func: Some(db_getuservalue) },
+        lauxlib::luaL_Reg { name: c_str!("gethook"), func: Some(db_gethook) },
+        lauxlib::luaL_Reg { name: c_str!("getinfo"), func: Some(db_getinfo) },
+        lauxlib::luaL_Reg { name: c_str!("getlocal"), func: Some(db_getlocal) },
+        lauxlib::luaL_Reg { name: c_str!("getregistry"), func: Some(db_getregistry) },
+        lauxlib::luaL_Reg { name: c_str!("getmetatable"), func: Some(db_getmetatable) },
+        lauxlib::luaL_Reg { name: c_str!("getupvalue"), func: Some(db_getupvalue) },
+        lauxlib::luaL_Reg { name: c_str!("upvaluejoin"), func: Some(db_upvaluejoin) },
+        lauxlib::luaL_Reg { name: c_str!("upvalueid"), func: Some(db_upvalueid) },
+        lauxlib::luaL_Reg { name: c_str!("setuservalue"), func: Some(db_setuservalue) },
+        lauxlib::luaL_Reg { name: c_str!("sethook"), func: Some(db_sethook) },
+        lauxlib::luaL_Reg { name: c_str!("setlocal"), func: Some(db_setlocal) },
+        lauxlib::luaL_Reg { name: c_str!("setmetatable"), func: Some(db_setmetatable) },
+        lauxlib::luaL_Reg { name: c_str!("setupvalue"), func: Some(db_setupvalue) },
+        lauxlib::luaL_Reg { name: c_str!("traceback"), func: Some(db_traceback) },
+        lauxlib::luaL_Reg { name: ptr::null(), func: None },
+    ];
+    lauxlib::luaL_newlib(l, dblib.as_ptr());
+    1
+}
@@ -0,0 +1,32 @@
+/*
+** Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+** Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+** Distributed under the MIT License.
+**    (See accompanying file LICENSE.txt or copy at
+**          http://opensource.org/licenses/MIT)
+**
+** The variadic part of 'ldebug.c', which stable Rust cannot define.
+** The message is formatted here; 'luaG_runerrormsg' in 'ldebug.rs'
+** adds the position and raises it.
+*/
+
+#include <stdarg.h>
+
+#define LUA_CORE
+
+#include "lua.h"
+
+#include "ldebug.h"
+#include "lobject.h"
+
+
+l_noret luaG_runerrormsg (lua_State *L);
+
+
+l_noret luaG_runerror (lua_State *L, const char *fmt, ...) {
+  va_list argp;
+  va_start(argp, fmt);
+  luaO_pushvfstring(L, fmt, argp);  /* format message into error message */
+  va_end(argp);
+  luaG_runerrormsg(L);
+}
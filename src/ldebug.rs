@@ -0,0 +1,822 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Debug Interface.
+//!
+//! Besides the names found by 5.3.3, the symbolic execution behind error
+//! messages also names values returned by named calls (e.g. "attempt to
+//! index a nil value (result of method 'get')") and tables built by a
+//! constructor. 'lua_getinfo' keeps reporting only the standard names.
+
+#![allow(non_snake_case)]
+
+use ffi::lua::{self, lua_Debug, lua_Hook, LUA_MASKCOUNT, LUA_MASKLINE, LUA_YIELD};
+use ffi::luaconf::LUA_IDSIZE;
+use lapi::api_incr_top;
+use ldo::{luaD_callnoyield, luaD_hook, luaD_throw, restorestack, savestack};
+use lexer::LUA_ENV;
+use lfunc::luaF_getlocalname;
+use lobject::{getstr, luaO_chunkid, pushfstring, setbvalue, sethvalue, setnilvalue, svalue,
+              tointeger, tonumber, FArg, LClosure, Proto, TString, TValue};
+use lopcodes::{GETARG_A, GETARG_Ax, GETARG_B, GETARG_Bx, GETARG_C, GETARG_sBx, GET_OPCODE, INDEXK,
+               ISK, Instruction, testAMode};
+use lopcodes::OpCode::*;
+use lstate::{isLua, lua_lock, lua_State, lua_unlock, CallInfo, StkId, G, CIST_HOOKED,
+             CIST_HOOKYIELD, CIST_TAIL};
+use ltable::{luaH_new, luaH_setint};
+use ltm::{luaT_objtypename, TMS};
+use libc::{c_char, c_int, strchr, strcmp};
+use std::ffi::CStr;
+use std::ptr;
+
+
+/* the exported functions below, from 'ldebug.c' when it is linked instead (see 'build.rs') */
+#[cfg(not(rust_ldebug))]
+extern "C-unwind" {
+    pub fn lua_sethook(L: *mut lua_State, func: lua_Hook, mask: c_int, count: c_int);
+    pub fn lua_gethook(L: *mut lua_State) -> lua_Hook;
+    pub fn lua_gethookmask(L: *mut lua_State) -> c_int;
+    pub fn lua_gethookcount(L: *mut lua_State) -> c_int;
+    pub fn lua_getstack(L: *mut lua_State, level: c_int, ar: *mut lua_Debug) -> c_int;
+    pub fn lua_getlocal(L: *mut lua_State, ar: *const lua_Debug, n: c_int) -> *const c_char;
+    pub fn lua_setlocal(L: *mut lua_State, ar: *const lua_Debug, n: c_int) -> *const c_char;
+    pub fn lua_getinfo(L: *mut lua_State, what: *const c_char, ar: *mut lua_Debug) -> c_int;
+    pub fn luaG_typeerror(L: *mut lua_State, o: *const TValue, op: *const c_char) -> !;
+    pub fn luaG_concaterror(L: *mut lua_State, p1: *const TValue, p2: *const TValue) -> !;
+    pub fn luaG_opinterror(L: *mut lua_State, p1: *const TValue, p2: *const TValue,
+                           msg: *const c_char) -> !;
+    pub fn luaG_tointerror(L: *mut lua_State, p1: *const TValue, p2: *const TValue) -> !;
+    pub fn luaG_ordererror(L: *mut lua_State, p1: *const TValue, p2: *const TValue) -> !;
+    pub fn luaG_addinfo(L: *mut lua_State, msg: *const c_char, src: *mut TString,
+                        line: c_int) -> *const c_char;
+    pub fn luaG_errormsg(L: *mut lua_State) -> !;
+    pub fn luaG_traceexec(L: *mut lua_State);
+}
+
+
+/* Active Lua function (given call info) */
+unsafe fn ci_func(ci: *mut CallInfo) -> *mut LClosure { (*(*ci).func).clLvalue() }
+
+
+pub unsafe fn pcRel(pc: *const Instruction, p: *const Proto) -> c_int {
+    pc.offset_from((*p).code) as c_int - 1
+}
+
+pub unsafe fn getfuncline(f: *const Proto, pc: c_int) -> c_int {
+    if (*f).lineinfo.is_null() { -1 } else { *(*f).lineinfo.add(pc as usize) }
+}
+
+pub unsafe fn resethookcount(L: *mut lua_State) { (*L).hookcount = (*L).basehookcount; }
+
+
+unsafe fn currentpc(ci: *mut CallInfo) -> c_int {
+    debug_assert!(isLua(ci));
+    pcRel((*ci).u.l.savedpc, (*ci_func(ci)).p)
+}
+
+
+unsafe fn currentline(ci: *mut CallInfo) -> c_int {
+    getfuncline((*ci_func(ci)).p, currentpc(ci))
+}
+
+
+/*
+** If function yielded, its 'func' can be in the 'extra' field. The
+** next function restores 'func' to its correct value for debugging
+** purposes. (It exchanges 'func' and 'extra'; so, when called again,
+** after debugging, it also "re-restores" ** 'func' to its altered value.
+*/
+unsafe fn swapextra(L: *mut lua_State) {
+    if (*L).status as c_int == LUA_YIELD {
+        let ci = (*L).ci;  /* get function that yielded */
+        let temp = (*ci).func;  /* exchange its 'func' and 'extra' values */
+        (*ci).func = restorestack(L, (*ci).extra);
+        (*ci).extra = savestack(L, temp);
+    }
+}
+
+
+/*
+** This function can be called asynchronously (e.g. during a signal).
+** Fields 'oldpc', 'basehookcount', and 'hookcount' (set by
+** 'resethookcount') are for debug only, and it is no problem if they
+** get arbitrary values (causes at most one wrong hook call). 'hookmask'
+** is an atomic value. We assume that pointers are atomic too (e.g., gcc
+** ensures that for all platforms where it runs). Moreover, 'hook' is
+** always checked before being called (see 'luaD_hook').
+*/
+#[cfg(rust_ldebug)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_sethook(L: *mut lua_State, mut func: lua_Hook, mut mask: c_int,
+                                            count: c_int) {
+    if func.is_none() || mask == 0 {  /* turn off hooks? */
+        mask = 0;
+        func = None;
+    }
+    if isLua((*L).ci) {
+        (*L).oldpc = (*(*L).ci).u.l.savedpc;
+    }
+    (*L).hook = func;
+    (*L).basehookcount = count;
+    resethookcount(L);
+    (*L).hookmask = mask as u8 as c_int;
+}
+
+
+#[cfg(rust_ldebug)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_gethook(L: *mut lua_State) -> lua_Hook {
+    (*L).hook
+}
+
+
+#[cfg(rust_ldebug)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_gethookmask(L: *mut lua_State) -> c_int {
+    (*L).hookmask
+}
+
+
+#[cfg(rust_ldebug)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_gethookcount(L: *mut lua_State) -> c_int {
+    (*L).basehookcount
+}
+
+
+#[cfg(rust_ldebug)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_getstack(L: *mut lua_State, mut level: c_int,
+                                             ar: *mut lua_Debug) -> c_int {
+    if level < 0 { return 0; }  /* invalid (negative) level */
+    lua_lock(L);
+    let base_ci: *mut CallInfo = &mut (*L).base_ci;
+    let mut ci = (*L).ci;
+    while level > 0 && ci != base_ci {
+        level -= 1;
+        ci = (*ci).previous;
+    }
+    let status = if level == 0 && ci != base_ci {  /* level found? */
+        (*ar).i_ci = ci as *mut _;
+        1
+    } else {
+        0  /* no such level */
+    };
+    lua_unlock(L);
+    status
+}
+
+
+unsafe fn upvalname(p: *const Proto, uv: c_int) -> *const c_char {
+    debug_assert!(uv < (*p).sizeupvalues);
+    let s = (*(*p).upvalues.add(uv as usize)).name;
+    if s.is_null() { c_str!("?") } else { getstr(s) }
+}
+
+
+unsafe fn findvararg(ci: *mut CallInfo, n: c_int, pos: &mut StkId) -> *const c_char {
+    let nparams = (*(*ci_func(ci)).p).numparams as c_int;
+    if n >= (*ci).u.l.base.offset_from((*ci).func) as c_int - nparams {
+        ptr::null()  /* no such vararg */
+    } else {
+        *pos = (*ci).func.add((nparams + n) as usize);
+        c_str!("(*vararg)")  /* generic name for any vararg */
+    }
+}
+
+
+unsafe fn findlocal(L: *mut lua_State, ci: *mut CallInfo, n: c_int, pos: &mut StkId) -> *const c_char {
+    let mut name = ptr::null();
+    let base;
+    if isLua(ci) {
+        if n < 0 {  /* access to vararg values? */
+            return findvararg(ci, -n, pos);
+        }
+        base = (*ci).u.l.base;
+        name = luaF_getlocalname((*ci_func(ci)).p, n, currentpc(ci));
+    } else {
+        base = (*ci).func.add(1);
+    }
+    if name.is_null() {  /* no 'standard' name? */
+        let limit = if ci == (*L).ci { (*L).top } else { (*(*ci).next).func };
+        if limit.offset_from(base) >= n as isize && n > 0 {  /* is 'n' inside 'ci' stack? */
+            name = c_str!("(*temporary)");  /* generic name for any valid slot */
+        } else {
+            return ptr::null();  /* no name */
+        }
+    }
+    *pos = base.offset(n as isize - 1);
+    name
+}
+
+
+#[cfg(rust_ldebug)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_getlocal(L: *mut lua_State, ar: *const lua_Debug,
+                                             n: c_int) -> *const c_char {
+    lua_lock(L);
+    swapextra(L);
+    let name = if ar.is_null() {  /* information about non-active function? */
+        if !(*(*L).top.sub(1)).ttisLclosure() {  /* not a Lua function? */
+            ptr::null()
+        } else {  /* consider live variables at function start (parameters) */
+            luaF_getlocalname((*(*(*L).top.sub(1)).clLvalue()).p, n, 0)
+        }
+    } else {  /* active function; get information through 'ar' */
+        let mut pos = ptr::null_mut();  /* to avoid warnings */
+        let name = findlocal(L, (*ar).i_ci as *mut CallInfo, n, &mut pos);
+        if !name.is_null() {
+            *(*L).top = *pos;
+            api_incr_top(L);
+        }
+        name
+    };
+    swapextra(L);
+    lua_unlock(L);
+    name
+}
+
+
+#[cfg(rust_ldebug)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_setlocal(L: *mut lua_State, ar: *const lua_Debug,
+                                             n: c_int) -> *const c_char {
+    let mut pos = ptr::null_mut();  /* to avoid warnings */
+    lua_lock(L);
+    swapextra(L);
+    let name = findlocal(L, (*ar).i_ci as *mut CallInfo, n, &mut pos);
+    if !name.is_null() {
+        *pos = *(*L).top.sub(1);
+        (*L).top = (*L).top.sub(1);  /* pop value */
+    }
+    swapextra(L);
+    lua_unlock(L);
+    name
+}
+
+
+/* the closure in 'func', if it is a Lua function */
+unsafe fn luaclosure(func: *const TValue) -> Option<*mut LClosure> {
+    if (*func).ttisLclosure() { Some((*func).clLvalue()) } else { None }
+}
+
+
+unsafe fn funcinfo(ar: *mut lua_Debug, func: *const TValue) {
+    match luaclosure(func) {
+        None => {
+            (*ar).source = c_str!("=[C]");
+            (*ar).linedefined = -1;
+            (*ar).lastlinedefined = -1;
+            (*ar).what = c_str!("C");
+        }
+        Some(cl) => {
+            let p = (*cl).p;
+            (*ar).source = if (*p).source.is_null() { c_str!("=?") } else { getstr((*p).source) };
+            (*ar).linedefined = (*p).linedefined;
+            (*ar).lastlinedefined = (*p).lastlinedefined;
+            (*ar).what = if (*ar).linedefined == 0 { c_str!("main") } else { c_str!("Lua") };
+        }
+    }
+    luaO_chunkid((*ar).short_src.as_mut_ptr(), (*ar).source, LUA_IDSIZE as usize);
+}
+
+
+unsafe fn collectvalidlines(L: *mut lua_State, func: *const TValue) {
+    match luaclosure(func) {
+        None => {
+            setnilvalue((*L).top);
+            api_incr_top(L);
+        }
+        Some(cl) => {
+            let p = (*cl).p;
+            let mut v = TValue::nil();
+            let t = luaH_new(L);  /* new table to store active lines */
+            sethvalue((*L).top, t);  /* push it on stack */
+            api_incr_top(L);
+            setbvalue(&mut v, 1);  /* boolean 'true' to be the value of all indices */
+            for i in 0..(*p).sizelineinfo as usize {  /* for all lines with code */
+                luaH_setint(L, t, *(*p).lineinfo.add(i) as lua::lua_Integer, &mut v);  /* table[line] = true */
+            }
+        }
+    }
+}
+
+
+unsafe fn auxgetinfo(L: *mut lua_State, what: &[u8], ar: *mut lua_Debug, func: *const TValue,
+                     ci: *mut CallInfo) -> c_int {
+    let mut status = 1;
+    for &c in what {
+        match c {
+            b'S' => {
+                funcinfo(ar, func);
+            }
+            b'l' => {
+                (*ar).currentline = if !ci.is_null() && isLua(ci) { currentline(ci) } else { -1 };
+            }
+            b'u' => {
+                (*ar).nups = if (*func).ttisLclosure() {
+                    (*(*func).clLvalue()).nupvalues
+                } else if (*func).ttisCclosure() {
+                    (*(*func).clCvalue()).nupvalues
+                } else {
+                    0  /* light C function */
+                };
+                match luaclosure(func) {
+                    None => {
+                        (*ar).isvararg = 1;
+                        (*ar).nparams = 0;
+                    }
+                    Some(cl) => {
+                        (*ar).isvararg = (*(*cl).p).is_vararg as c_char;
+                        (*ar).nparams = (*(*cl).p).numparams;
+                    }
+                }
+            }
+            b't' => {
+                (*ar).istailcall = if ci.is_null() { 0 } else { ((*ci).callstatus & CIST_TAIL) as c_char };
+            }
+            b'n' => {
+                /* calling function is a known Lua function? */
+                let found = if !ci.is_null() && (*ci).callstatus & CIST_TAIL == 0 && isLua((*ci).previous) {
+                    getfuncname(L, (*ci).previous)
+                } else {
+                    None
+                };
+                match found {
+                    Some((namewhat, name)) => {
+                        (*ar).namewhat = namewhat;
+                        (*ar).name = name;
+                    }
+                    None => {
+                        (*ar).namewhat = c_str!("");  /* not found */
+                        (*ar).name = ptr::null();
+                    }
+                }
+            }
+            b'L' | b'f' => {}  /* handled by lua_getinfo */
+            _ => status = 0,  /* invalid option */
+        }
+    }
+    status
+}
+
+
+#[cfg(rust_ldebug)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_getinfo(L: *mut lua_State, mut what: *const c_char,
+                                            ar: *mut lua_Debug) -> c_int {
+    let ci: *mut CallInfo;
+    let func: StkId;
+    lua_lock(L);
+    swapextra(L);
+    if *what == b'>' as c_char {
+        ci = ptr::null_mut();
+        func = (*L).top.sub(1);
+        debug_assert!((*func).ttisfunction(), "function expected");
+        what = what.add(1);  /* skip the '>' */
+        (*L).top = (*L).top.sub(1);  /* pop function */
+    } else {
+        ci = (*ar).i_ci as *mut CallInfo;
+        func = (*ci).func;
+        debug_assert!((*func).ttisfunction());
+    }
+    let status = auxgetinfo(L, CStr::from_ptr(what).to_bytes(), ar, func, ci);
+    if !strchr(what, b'f' as c_int).is_null() {
+        *(*L).top = *func;
+        api_incr_top(L);
+    }
+    swapextra(L);  /* correct before option 'L', which can raise a mem. error */
+    if !strchr(what, b'L' as c_int).is_null() {
+        collectvalidlines(L, func);
+    }
+    lua_unlock(L);
+    status
+}
+
+
+/*
+** {======================================================
+** Symbolic Execution
+** =======================================================
+*/
+
+
+/*
+** find a "name" for the RK value 'c'
+*/
+unsafe fn kname(p: *const Proto, pc: c_int, c: c_int) -> *const c_char {
+    if ISK(c) {  /* is 'c' a constant? */
+        let kvalue = (*p).k.add(INDEXK(c) as usize);
+        if (*kvalue).ttisstring() {  /* literal constant? */
+            return svalue(kvalue);  /* it is its own name */
+        }
+        /* else no reasonable name found */
+    } else {  /* 'c' is a register */
+        if let Some((b"constant\0", name)) = getobjname(p, pc, c, false) {
+            return name;  /* found a constant name */
+        }
+        /* else no reasonable name found */
+    }
+    c_str!("?")  /* no reasonable name found */
+}
+
+
+fn filterpc(pc: c_int, jmptarget: c_int) -> c_int {
+    if pc < jmptarget {  /* is code conditional (inside a jump)? */
+        -1  /* cannot know who sets that register */
+    } else {
+        pc  /* current position sets that register */
+    }
+}
+
+
+/*
+** try to find last instruction before 'lastpc' that modified register 'reg'
+*/
+unsafe fn findsetreg(p: *const Proto, lastpc: c_int, reg: c_int) -> c_int {
+    let mut setreg = -1;  /* keep last instruction that changed 'reg' */
+    let mut jmptarget = 0;  /* any code before this address is conditional */
+    for pc in 0..lastpc {
+        let i = *(*p).code.add(pc as usize);
+        let op = GET_OPCODE(i);
+        let a = GETARG_A(i);
+        match op {
+            OP_LOADNIL => {
+                let b = GETARG_B(i);
+                if a <= reg && reg <= a + b {  /* set registers from 'a' to 'a+b' */
+                    setreg = filterpc(pc, jmptarget);
+                }
+            }
+            OP_TFORCALL => {
+                if reg >= a + 2 {  /* affect all regs above its base */
+                    setreg = filterpc(pc, jmptarget);
+                }
+            }
+            OP_CALL | OP_TAILCALL => {
+                if reg >= a {  /* affect all registers above base */
+                    setreg = filterpc(pc, jmptarget);
+                }
+            }
+            OP_JMP => {
+                let b = GETARG_sBx(i);
+                let dest = pc + 1 + b;
+                /* jump is forward and do not skip 'lastpc'? */
+                if pc < dest && dest <= lastpc && dest > jmptarget {
+                    jmptarget = dest;  /* update 'jmptarget' */
+                }
+            }
+            _ => {
+                if testAMode(op) && reg == a {  /* any instruction that set A */
+                    setreg = filterpc(pc, jmptarget);
+                }
+            }
+        }
+    }
+    setreg
+}
+
+
+/*
+** kind of a value returned by a call, given the kind of the function
+** called (for the extended names)
+*/
+fn resultof(kind: &'static [u8]) -> Option<&'static [u8]> {
+    match kind {
+        b"global\0" => Some(b"result of global\0"),
+        b"local\0" => Some(b"result of local\0"),
+        b"method\0" => Some(b"result of method\0"),
+        b"field\0" => Some(b"result of field\0"),
+        b"upvalue\0" => Some(b"result of upvalue\0"),
+        _ => None,
+    }
+}
+
+
+/*
+** Find the kind (a C string) and name of the value in register 'reg'
+** at 'lastpc'. With 'ext', also name call results and constructed
+** tables; those kinds are only for error messages, as the kinds of
+** 'lua_getinfo' are fixed by the manual. The name of a constructed
+** table is NULL.
+*/
+unsafe fn getobjname(p: *const Proto, lastpc: c_int, reg: c_int,
+                     ext: bool) -> Option<(&'static [u8], *const c_char)> {
+    let name = luaF_getlocalname(p, reg + 1, lastpc);
+    if !name.is_null() {  /* is a local? */
+        return Some((b"local\0", name));
+    }
+    /* else try symbolic execution */
+    let pc = findsetreg(p, lastpc, reg);
+    if pc != -1 {  /* could find instruction? */
+        let i = *(*p).code.add(pc as usize);
+        let op = GET_OPCODE(i);
+        match op {
+            OP_MOVE => {
+                let b = GETARG_B(i);  /* move from 'b' to 'a' */
+                if b < GETARG_A(i) {
+                    return getobjname(p, pc, b, ext);  /* get name for 'b' */
+                }
+            }
+            OP_GETTABUP | OP_GETTABLE => {
+                let k = GETARG_C(i);  /* key index */
+                let t = GETARG_B(i);  /* table index */
+                let vn = if op == OP_GETTABLE {  /* name of indexed variable */
+                    luaF_getlocalname(p, t + 1, pc)
+                } else {
+                    upvalname(p, t)
+                };
+                let name = kname(p, pc, k);
+                let isenv = !vn.is_null() && CStr::from_ptr(vn).to_bytes() == LUA_ENV.as_bytes();
+                return Some((if isenv { b"global\0" } else { b"field\0" }, name));
+            }
+            OP_GETUPVAL => {
+                return Some((b"upvalue\0", upvalname(p, GETARG_B(i))));
+            }
+            OP_LOADK | OP_LOADKX => {
+                let b = if op == OP_LOADK {
+                    GETARG_Bx(i)
+                } else {
+                    GETARG_Ax(*(*p).code.add(pc as usize + 1))
+                };
+                let k = (*p).k.add(b as usize);
+                if (*k).ttisstring() {
+                    return Some((b"constant\0", svalue(k)));
+                }
+            }
+            OP_SELF => {
+                let k = GETARG_C(i);  /* key index */
+                return Some((b"method\0", kname(p, pc, k)));
+            }
+            OP_CALL if ext && reg == GETARG_A(i) && GETARG_C(i) != 1 => {  /* first result */
+                if let Some((kind, name)) = getobjname(p, pc, reg, false) {  /* function called */
+                    if let Some(kind) = resultof(kind) {
+                        return Some((kind, name));
+                    }
+                }
+            }
+            OP_NEWTABLE if ext => {
+                return Some((b"table constructor\0", ptr::null()));
+            }
+            _ => {}  /* go through to return None */
+        }
+    }
+    None  /* could not find reasonable name */
+}
+
+
+unsafe fn getfuncname(L: *mut lua_State, ci: *mut CallInfo) -> Option<(*const c_char, *const c_char)> {
+    let p = (*ci_func(ci)).p;  /* calling function */
+    let pc = currentpc(ci);  /* calling instruction index */
+    let i = *(*p).code.add(pc as usize);  /* calling instruction */
+    if (*ci).callstatus & CIST_HOOKED != 0 {  /* was it called inside a hook? */
+        return Some((c_str!("hook"), c_str!("?")));
+    }
+    let tm = match GET_OPCODE(i) {
+        OP_CALL | OP_TAILCALL => {  /* get function name */
+            return getobjname(p, pc, GETARG_A(i), false)
+                .map(|(kind, name)| (kind.as_ptr() as *const c_char, name));
+        }
+        OP_TFORCALL => {  /* for iterator */
+            return Some((c_str!("for iterator"), c_str!("for iterator")));
+        }
+        /* all other instructions can call only through metamethods */
+        OP_SELF | OP_GETTABUP | OP_GETTABLE => TMS::TM_INDEX,
+        OP_SETTABUP | OP_SETTABLE => TMS::TM_NEWINDEX,
+        OP_ADD => TMS::TM_ADD,
+        OP_SUB => TMS::TM_SUB,
+        OP_MUL => TMS::TM_MUL,
+        OP_MOD => TMS::TM_MOD,
+        OP_POW => TMS::TM_POW,
+        OP_DIV => TMS::TM_DIV,
+        OP_IDIV => TMS::TM_IDIV,
+        OP_BAND => TMS::TM_BAND,
+        OP_BOR => TMS::TM_BOR,
+        OP_BXOR => TMS::TM_BXOR,
+        OP_SHL => TMS::TM_SHL,
+        OP_SHR => TMS::TM_SHR,
+        OP_UNM => TMS::TM_UNM,
+        OP_BNOT => TMS::TM_BNOT,
+        OP_LEN => TMS::TM_LEN,
+        OP_CONCAT => TMS::TM_CONCAT,
+        OP_EQ => TMS::TM_EQ,
+        OP_LT => TMS::TM_LT,
+        OP_LE => TMS::TM_LE,
+        op => unreachable!("{:?} cannot call a function", op),
+    };
+    Some((c_str!("metamethod"), getstr((*G(L)).tmname[tm as usize])))
+}
+
+/* }====================================================== */
+
+
+
+/*
+** Checks whether 'o' is a slot of the frame of 'ci' (comparing
+** addresses, as 'o' may point anywhere).
+*/
+unsafe fn isinstack(ci: *mut CallInfo, o: *const TValue) -> bool {
+    let base = (*ci).u.l.base as *const TValue;
+    base <= o && o < (*ci).top
+}
+
+
+/*
+** Checks whether value 'o' came from an upvalue. (That can only happen
+** with instructions OP_GETTABUP/OP_SETTABUP, which operate directly on
+** upvalues.)
+*/
+unsafe fn getupvalname(ci: *mut CallInfo, o: *const TValue) -> Option<(&'static [u8], *const c_char)> {
+    let c = ci_func(ci);
+    for i in 0..(*c).nupvalues as usize {
+        if ptr::eq((**(*c).upvals.as_ptr().add(i)).v, o) {
+            return Some((b"upvalue\0", upvalname((*c).p, i as c_int)));
+        }
+    }
+    None
+}
+
+
+unsafe fn varinfo(L: *mut lua_State, o: *const TValue) -> *const c_char {
+    let ci = (*L).ci;
+    let mut kind = None;
+    if isLua(ci) {
+        kind = getupvalname(ci, o);  /* check whether 'o' is an upvalue */
+        if kind.is_none() && isinstack(ci, o) {  /* no? try a register */
+            kind = getobjname((*ci_func(ci)).p, currentpc(ci),
+                              o.offset_from((*ci).u.l.base) as c_int, true);
+        }
+    }
+    let kind_str = |kind: &'static [u8]| FArg::Str(&kind[..kind.len() - 1]);
+    match kind {
+        Some((kind, name)) if name.is_null() => pushfstring(L, b" (%s)", &[kind_str(kind)]),
+        Some((kind, name)) => pushfstring(L, b" (%s '%s')", &[kind_str(kind), cstr(name)]),
+        None => c_str!(""),
+    }
+}
+
+
+/* argument for a '%s' in 'pushfstring' from a C string */
+unsafe fn cstr<'a>(s: *const c_char) -> FArg<'a> { FArg::from(CStr::from_ptr(s)) }
+
+
+/*
+** Raise an error with the message formatted from 'fmt' and 'args', with
+** position information as 'luaG_runerror' (see 'pushfstring')
+*/
+pub unsafe fn runerror(L: *mut lua_State, fmt: &[u8], args: &[FArg]) -> ! {
+    pushfstring(L, fmt, args);
+    luaG_runerrormsg(L)
+}
+
+
+#[cfg(rust_ldebug)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaG_typeerror(L: *mut lua_State, o: *const TValue,
+                                               op: *const c_char) -> ! {
+    let t = luaT_objtypename(L, o);
+    let info = varinfo(L, o);
+    runerror(L, b"attempt to %s a %s value%s", &[cstr(op), cstr(t), cstr(info)])
+}
+
+
+#[cfg(rust_ldebug)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaG_concaterror(L: *mut lua_State, mut p1: *const TValue,
+                                                 p2: *const TValue) -> ! {
+    if (*p1).ttisstring() || (*p1).ttisnumber() {  /* 'cvt2str' */
+        p1 = p2;
+    }
+    luaG_typeerror(L, p1, c_str!("concatenate"))
+}
+
+
+#[cfg(rust_ldebug)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaG_opinterror(L: *mut lua_State, p1: *const TValue,
+                                                mut p2: *const TValue, msg: *const c_char) -> ! {
+    let mut temp = 0.0;
+    if !tonumber(p1, &mut temp) {  /* first operand is wrong? */
+        p2 = p1;  /* now second is wrong */
+    }
+    luaG_typeerror(L, p2, msg)
+}
+
+
+/*
+** Error when both values are convertible to numbers, but not to integers
+*/
+#[cfg(rust_ldebug)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaG_tointerror(L: *mut lua_State, p1: *const TValue,
+                                                mut p2: *const TValue) -> ! {
+    let mut temp = 0;
+    if !tointeger(p1, &mut temp) {
+        p2 = p1;
+    }
+    let info = varinfo(L, p2);
+    runerror(L, b"number%s has no integer representation", &[cstr(info)])
+}
+
+
+#[cfg(rust_ldebug)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaG_ordererror(L: *mut lua_State, p1: *const TValue,
+                                                p2: *const TValue) -> ! {
+    let t1 = luaT_objtypename(L, p1);
+    let t2 = luaT_objtypename(L, p2);
+    if strcmp(t1, t2) == 0 {
+        runerror(L, b"attempt to compare two %s values", &[cstr(t1)])
+    } else {
+        runerror(L, b"attempt to compare %s with %s", &[cstr(t1), cstr(t2)])
+    }
+}
+
+
+/* add src:line information to 'msg' */
+#[cfg(rust_ldebug)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaG_addinfo(L: *mut lua_State, msg: *const c_char, src: *mut TString,
+                                             line: c_int) -> *const c_char {
+    let mut buff = [0 as c_char; LUA_IDSIZE as usize];
+    if !src.is_null() {
+        luaO_chunkid(buff.as_mut_ptr(), getstr(src), LUA_IDSIZE as usize);
+    } else {  /* no source available; use "?" instead */
+        buff[0] = b'?' as c_char;
+    }
+    pushfstring(L, b"%s:%d: %s", &[cstr(buff.as_ptr()), line.into(), cstr(msg)])
+}
+
+
+#[cfg(rust_ldebug)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaG_errormsg(L: *mut lua_State) -> ! {
+    if (*L).errfunc != 0 {  /* is there an error handling function? */
+        let errfunc = restorestack(L, (*L).errfunc);
+        *(*L).top = *(*L).top.sub(1);  /* move argument */
+        *(*L).top.sub(1) = *errfunc;  /* push function */
+        (*L).top = (*L).top.add(1);  /* assume EXTRA_STACK */
+        luaD_callnoyield(L, (*L).top.sub(2), 1);  /* call it */
+    }
+    luaD_throw(L, lua::LUA_ERRRUN)
+}
+
+
+/*
+** Raise the error message on the top of the stack, adding source:line
+** information if the running function is a Lua function. (The variadic
+** 'luaG_runerror', in 'ldebug.c', formats the message and calls this.)
+*/
+#[no_mangle]  /* not in 'ldebug.c', so always defined */
+pub unsafe extern "C-unwind" fn luaG_runerrormsg(L: *mut lua_State) -> ! {
+    let ci = (*L).ci;
+    if isLua(ci) {  /* if Lua function, add source:line information */
+        let msg = svalue((*L).top.sub(1));
+        luaG_addinfo(L, msg, (*(*ci_func(ci)).p).source, currentline(ci));
+    }
+    luaG_errormsg(L)
+}
+
+
+#[cfg(rust_ldebug)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaG_traceexec(L: *mut lua_State) {
+    let ci = (*L).ci;
+    let mask = (*L).hookmask as u8 as c_int;
+    (*L).hookcount -= 1;
+    let counthook = (*L).hookcount == 0 && mask & LUA_MASKCOUNT != 0;
+    if counthook {
+        resethookcount(L);  /* reset count */
+    } else if mask & LUA_MASKLINE == 0 {
+        return;  /* no line hook and count != 0; nothing to be done */
+    }
+    if (*ci).callstatus & CIST_HOOKYIELD != 0 {  /* called hook last time? */
+        (*ci).callstatus &= !CIST_HOOKYIELD;  /* erase mark */
+        return;  /* do not call hook again (VM yielded, so it did not move) */
+    }
+    if counthook {
+        luaD_hook(L, lua::LUA_HOOKCOUNT, -1);  /* call count hook */
+    }
+    if mask & LUA_MASKLINE != 0 {
+        let p = (*ci_func(ci)).p;
+        let npc = pcRel((*ci).u.l.savedpc, p);
+        let newline = getfuncline(p, npc);
+        if npc == 0 ||  /* call linehook when enter a new function, */
+           (*ci).u.l.savedpc <= (*L).oldpc ||  /* when jump back (loop), or when */
+           newline != getfuncline(p, pcRel((*L).oldpc, p)) {  /* enter a new line */
+            luaD_hook(L, lua::LUA_HOOKLINE, newline);  /* call line hook */
+        }
+    }
+    (*L).oldpc = (*ci).u.l.savedpc;
+    if (*L).status as c_int == LUA_YIELD {  /* did hook yield? */
+        if counthook {
+            (*L).hookcount = 1;  /* undo decrement to zero */
+        }
+        (*ci).u.l.savedpc = (*ci).u.l.savedpc.sub(1);  /* undo increment (resume will increment it again) */
+        (*ci).callstatus |= CIST_HOOKYIELD;  /* mark that it yielded */
+        (*ci).func = (*L).top.sub(1);  /* protect stack below results */
+        luaD_throw(L, LUA_YIELD);
+    }
+}
@@ -0,0 +1,938 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Stack and Call structure of Lua.
+//!
+//! Errors do not 'longjmp': 'luaD_throw' unwinds the stack (C frames
+//! included, as the core is built with '-fexceptions') and the recover
+//! point set by 'rawrunprotected' turns the unwind back into an
+//! 'Err(status)'. Rust frames between Lua calls are thus left properly,
+//! running their destructors.
+
+#![allow(non_camel_case_types, non_snake_case)]
+
+use ffi::lua::{self, lua_Debug, lua_KContext, lua_KFunction, LUA_ERRERR, LUA_ERRMEM, LUA_ERRRUN,
+               LUA_ERRSYNTAX, LUA_MINSTACK, LUA_MULTRET, LUA_OK, LUA_YIELD};
+use ldebug::luaG_typeerror;
+use lfunc::{luaF_close, luaF_initupvals};
+use lgc::luaC_checkGC;
+use lmem::{luaM_freearray, luaM_reallocvector};
+use lobject::{luaO_nilobject, luaO_pushfstring, setnilvalue, setsvalue, Proto, TValue,
+              LUA_TCCL, LUA_TLCF, LUA_TLCL};
+use lopcodes::{GET_OPCODE, OpCode};
+use lparser::{luaY_parser, Dyndata};
+use lstate::{getoah, isLua, lua_lock, lua_State, lua_unlock, luaE_extendCI, luaE_freeCI,
+             luaE_shrinkCI, CallInfo, StkId, G, CIST_HOOKED, CIST_LUA, CIST_TAIL, CIST_YPCALL,
+             EXTRA_STACK};
+use lstring::{luaS_new, luaS_newliteral};
+use ltm::{luaT_gettmbyobj, TMS};
+use lundump::luaU_undump;
+use lzio::{luaZ_resizebuffer, zgetc, Mbuffer, Zio};
+use libc::{c_char, c_int, c_void, strchr};
+use std::panic::{self, AssertUnwindSafe};
+use std::{mem, process, ptr};
+
+
+/* the exported functions below, from 'ldo.c' when it is linked instead (see 'build.rs') */
+#[cfg(not(rust_ldo))]
+extern "C-unwind" {
+    pub fn luaD_throw(L: *mut lua_State, errcode: c_int) -> !;
+    pub fn luaD_rawrunprotected(L: *mut lua_State, f: Pfunc, ud: *mut c_void) -> c_int;
+    pub fn luaD_reallocstack(L: *mut lua_State, newsize: c_int);
+    pub fn luaD_growstack(L: *mut lua_State, n: c_int);
+    pub fn luaD_shrinkstack(L: *mut lua_State);
+    pub fn luaD_inctop(L: *mut lua_State);
+    pub fn luaD_hook(L: *mut lua_State, event: c_int, line: c_int);
+    pub fn luaD_precall(L: *mut lua_State, func: StkId, nresults: c_int) -> c_int;
+    pub fn luaD_poscall(L: *mut lua_State, ci: *mut CallInfo, firstResult: StkId,
+                        nres: c_int) -> c_int;
+    pub fn luaD_call(L: *mut lua_State, func: StkId, nResults: c_int);
+    pub fn luaD_callnoyield(L: *mut lua_State, func: StkId, nResults: c_int);
+    pub fn lua_resume(L: *mut lua_State, from: *mut lua_State, nargs: c_int) -> c_int;
+    pub fn lua_isyieldable(L: *mut lua_State) -> c_int;
+    pub fn lua_yieldk(L: *mut lua_State, nresults: c_int, ctx: lua_KContext,
+                      k: lua_KFunction) -> c_int;
+    pub fn luaD_pcall(L: *mut lua_State, func: Pfunc, u: *mut c_void, old_top: isize,
+                      ef: isize) -> c_int;
+    pub fn luaD_protectedparser(L: *mut lua_State, z: *mut Zio, name: *const c_char,
+                                mode: *const c_char) -> c_int;
+}
+
+
+extern "C-unwind" {
+    fn luaG_runerror(L: *mut lua_State, fmt: *const c_char, ...) -> !;
+    fn luaV_finishOp(L: *mut lua_State);
+    fn luaV_execute(L: *mut lua_State);
+}
+
+
+/* maximum depth for nested C calls (from 'llimits.h') */
+const LUAI_MAXCCALLS: c_int = 200;
+
+/* limit for the size of the Lua stack; must agree with the C core */
+#[cfg(ltests)]
+pub const LUAI_MAXSTACK: c_int = 50000;  /* as set by 'ltests.h' */
+#[cfg(not(ltests))]
+pub const LUAI_MAXSTACK: c_int = 1000000;
+
+
+fn errorstatus(s: c_int) -> bool { s > LUA_YIELD }
+
+
+/*
+** Macro to check stack size and grow stack if needed.
+*/
+pub unsafe fn luaD_checkstack(L: *mut lua_State, n: c_int) {
+    if (*L).stack_last.offset_from((*L).top) <= n as isize {
+        luaD_growstack(L, n);
+    }
+}
+
+pub unsafe fn savestack(L: *mut lua_State, p: *const TValue) -> isize {
+    p as *const c_char as isize - (*L).stack as *const c_char as isize
+}
+
+pub unsafe fn restorestack(L: *mut lua_State, n: isize) -> StkId {
+    ((*L).stack as *mut c_char).offset(n) as StkId
+}
+
+
+/* type of protected functions, to be ran by 'runprotected' */
+pub type Pfunc = unsafe extern "C-unwind" fn(L: *mut lua_State, ud: *mut c_void);
+
+
+/*
+** {======================================================
+** Error-recovery functions
+** =======================================================
+*/
+
+/* chain list of recover points */
+#[repr(C)]
+pub struct lua_longjmp {
+    previous: *mut lua_longjmp,
+    status: c_int,  /* error code */
+}
+
+/* payload of the unwind started by 'luaD_throw': the recover point it is for */
+struct Throw(*mut lua_longjmp);
+
+unsafe impl Send for Throw {}  /* never leaves the thread running the state */
+
+
+/* the status of a protected run: 'LUA_OK' if it ran to completion */
+pub fn status(r: Result<(), c_int>) -> c_int {
+    match r {
+        Ok(()) => LUA_OK,
+        Err(status) => status,
+    }
+}
+
+
+unsafe fn seterrorobj(L: *mut lua_State, errcode: c_int, oldtop: StkId) {
+    match errcode {
+        LUA_ERRMEM => {  /* memory error? */
+            setsvalue(oldtop, (*G(L)).memerrmsg);  /* reuse preregistered msg. */
+        }
+        LUA_ERRERR => {
+            setsvalue(oldtop, luaS_newliteral(L, "error in error handling"));
+        }
+        _ => {
+            *oldtop = *(*L).top.sub(1);  /* error message on current top */
+        }
+    }
+    (*L).top = oldtop.add(1);
+}
+
+
+#[cfg(rust_ldo)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaD_throw(L: *mut lua_State, errcode: c_int) -> ! {
+    if !(*L).errorJmp.is_null() {  /* thread has an error handler? */
+        (*(*L).errorJmp).status = errcode;  /* set status */
+        panic::resume_unwind(Box::new(Throw((*L).errorJmp)))  /* unwind to it */
+    } else {  /* thread has no error handler */
+        let g = G(L);
+        (*L).status = errcode as u8;  /* mark it as dead */
+        let mainthread = (*g).mainthread;
+        if !(*mainthread).errorJmp.is_null() {  /* main thread has a handler? */
+            *(*mainthread).top = *(*L).top.sub(1);  /* copy error obj. */
+            (*mainthread).top = (*mainthread).top.add(1);
+            luaD_throw(mainthread, errcode)  /* re-throw in main thread */
+        } else {  /* no handler at all; abort */
+            if let Some(panic) = (*g).panic {  /* panic function? */
+                seterrorobj(L, errcode, (*L).top);  /* assume EXTRA_STACK */
+                if (*(*L).ci).top < (*L).top {
+                    (*(*L).ci).top = (*L).top;  /* pushing msg. can break this invariant */
+                }
+                lua_unlock(L);
+                panic(L);  /* call panic function (last chance to jump out) */
+            }
+            process::abort()
+        }
+    }
+}
+
+
+/*
+** Runs 'f' with a new recover point for 'L'. An error thrown to it ends
+** the run with 'Err(status)'; unwinds aimed at outer recover points (or
+** that are not Lua errors at all) go on after restoring the old one.
+*/
+#[cfg(rust_ldo)]
+pub unsafe fn rawrunprotected<F: FnOnce()>(L: *mut lua_State, f: F) -> Result<(), c_int> {
+    let oldnCcalls = (*L).nCcalls;
+    let mut lj = lua_longjmp { previous: (*L).errorJmp, status: LUA_OK };
+    let lj: *mut lua_longjmp = &mut lj;
+    (*L).errorJmp = lj;  /* chain new error handler */
+    let r = panic::catch_unwind(AssertUnwindSafe(f));
+    (*L).errorJmp = (*lj).previous;  /* restore old error handler */
+    (*L).nCcalls = oldnCcalls;
+    match r {
+        Ok(()) => Ok(()),
+        Err(e) => match e.downcast_ref::<Throw>() {
+            Some(&Throw(to)) if to == lj => Err((*lj).status),
+            _ => panic::resume_unwind(e),
+        },
+    }
+}
+
+
+/*
+** With the C 'ldo.c', whose errors jump to recover points of its own,
+** 'f' runs through its 'luaD_rawrunprotected' instead.
+*/
+#[cfg(not(rust_ldo))]
+pub unsafe fn rawrunprotected<F: FnOnce()>(L: *mut lua_State, f: F) -> Result<(), c_int> {
+    unsafe extern "C-unwind" fn run<F: FnOnce()>(_: *mut lua_State, ud: *mut c_void) {
+        let f = (*(ud as *mut Option<F>)).take().unwrap();
+        f()
+    }
+    let mut f = Some(f);
+    match luaD_rawrunprotected(L, run::<F>, &mut f as *mut Option<F> as *mut c_void) {
+        LUA_OK => Ok(()),
+        status => Err(status),
+    }
+}
+
+
+#[cfg(rust_ldo)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaD_rawrunprotected(L: *mut lua_State, f: Pfunc,
+                                                      ud: *mut c_void) -> c_int {
+    status(rawrunprotected(L, || f(L, ud)))
+}
+
+/* }====================================================== */
+
+
+/*
+** {==================================================================
+** Stack reallocation
+** ===================================================================
+*/
+unsafe fn correctstack(L: *mut lua_State, oldstack: *mut TValue) {
+    let correct = move |p: StkId| restorestack(L, p as isize - oldstack as isize);
+    (*L).top = correct((*L).top);
+    let mut up = (*L).openupval;
+    while !up.is_null() {
+        (*up).v = correct((*up).v);
+        up = (*up).u.open.next;
+    }
+    let mut ci = (*L).ci;
+    while !ci.is_null() {
+        (*ci).top = correct((*ci).top);
+        (*ci).func = correct((*ci).func);
+        if isLua(ci) {
+            (*ci).u.l.base = correct((*ci).u.l.base);
+        }
+        ci = (*ci).previous;
+    }
+}
+
+
+/* some space for error handling */
+const ERRORSTACKSIZE: c_int = LUAI_MAXSTACK + 200;
+
+
+#[cfg(rust_ldo)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaD_reallocstack(L: *mut lua_State, newsize: c_int) {
+    let oldstack = (*L).stack;
+    debug_assert!(newsize <= LUAI_MAXSTACK || newsize == ERRORSTACKSIZE);
+    debug_assert!((*L).stack_last.offset_from((*L).stack) == ((*L).stacksize - EXTRA_STACK) as isize);
+    luaM_reallocvector(L, &mut (*L).stack, (*L).stacksize, newsize);
+    for lim in (*L).stacksize..newsize {
+        setnilvalue((*L).stack.offset(lim as isize));  /* erase new segment */
+    }
+    (*L).stacksize = newsize;
+    (*L).stack_last = (*L).stack.offset((newsize - EXTRA_STACK) as isize);
+    correctstack(L, oldstack);
+}
+
+
+#[cfg(rust_ldo)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaD_growstack(L: *mut lua_State, n: c_int) {
+    let size = (*L).stacksize;
+    if size > LUAI_MAXSTACK {  /* error after extra size? */
+        luaD_throw(L, LUA_ERRERR);
+    }
+    let needed = (*L).top.offset_from((*L).stack) as c_int + n + EXTRA_STACK;
+    let mut newsize = 2 * size;
+    if newsize > LUAI_MAXSTACK { newsize = LUAI_MAXSTACK; }
+    if newsize < needed { newsize = needed; }
+    if newsize > LUAI_MAXSTACK {  /* stack overflow? */
+        luaD_reallocstack(L, ERRORSTACKSIZE);
+        luaG_runerror(L, c_str!("stack overflow"));
+    } else {
+        luaD_reallocstack(L, newsize);
+    }
+}
+
+
+unsafe fn stackinuse(L: *mut lua_State) -> c_int {
+    let mut lim = (*L).top;
+    let mut ci = (*L).ci;
+    while !ci.is_null() {
+        debug_assert!((*ci).top <= (*L).stack_last);
+        if lim < (*ci).top { lim = (*ci).top; }
+        ci = (*ci).previous;
+    }
+    lim.offset_from((*L).stack) as c_int + 1  /* part of stack in use */
+}
+
+
+#[cfg(rust_ldo)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaD_shrinkstack(L: *mut lua_State) {
+    let inuse = stackinuse(L);
+    let mut goodsize = inuse + (inuse / 8) + 2 * EXTRA_STACK;
+    if goodsize > LUAI_MAXSTACK { goodsize = LUAI_MAXSTACK; }
+    if (*L).stacksize > LUAI_MAXSTACK {  /* was handling stack overflow? */
+        luaE_freeCI(L);  /* free all CIs (list grew because of an error) */
+    } else {
+        luaE_shrinkCI(L);  /* shrink list */
+    }
+    if inuse <= LUAI_MAXSTACK &&  /* not handling stack overflow? */
+       goodsize < (*L).stacksize {  /* trying to shrink? */
+        luaD_reallocstack(L, goodsize);  /* shrink it */
+    }
+}
+
+
+#[cfg(rust_ldo)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaD_inctop(L: *mut lua_State) {
+    luaD_checkstack(L, 1);
+    (*L).top = (*L).top.add(1);
+}
+
+/* }================================================================== */
+
+
+/* 'api_checknelems' of 'lapi.h' */
+unsafe fn api_checknelems(L: *mut lua_State, n: c_int) {
+    debug_assert!((n as isize) < (*L).top.offset_from((*(*L).ci).func),
+                  "not enough elements in the stack");
+}
+
+
+/*
+** Call a hook for the given event. Make sure there is a hook to be
+** called. (Both 'L->hook' and 'L->hookmask', which triggers this
+** function, can be changed asynchronously by signals.)
+*/
+#[cfg(rust_ldo)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaD_hook(L: *mut lua_State, event: c_int, line: c_int) {
+    let hook = match (*L).hook {
+        Some(hook) if (*L).allowhook != 0 => hook,  /* make sure there is a hook */
+        _ => return,
+    };
+    let ci = (*L).ci;
+    let top = savestack(L, (*L).top);
+    let ci_top = savestack(L, (*ci).top);
+    let mut ar: lua_Debug = mem::zeroed();
+    ar.event = event;
+    ar.currentline = line;
+    ar.i_ci = ci as *mut c_void;
+    luaD_checkstack(L, LUA_MINSTACK);  /* ensure minimum stack size */
+    (*ci).top = (*L).top.offset(LUA_MINSTACK as isize);
+    debug_assert!((*ci).top <= (*L).stack_last);
+    (*L).allowhook = 0;  /* cannot call hooks inside a hook */
+    (*ci).callstatus |= CIST_HOOKED;
+    lua_unlock(L);
+    hook(L, &mut ar);
+    lua_lock(L);
+    debug_assert!((*L).allowhook == 0);
+    (*L).allowhook = 1;
+    (*ci).top = restorestack(L, ci_top);
+    (*L).top = restorestack(L, top);
+    (*ci).callstatus &= !CIST_HOOKED;
+}
+
+
+unsafe fn callhook(L: *mut lua_State, ci: *mut CallInfo) {
+    let mut hook = lua::LUA_HOOKCALL;
+    (*ci).u.l.savedpc = (*ci).u.l.savedpc.add(1);  /* hooks assume 'pc' is already incremented */
+    let previous = (*ci).previous;
+    if isLua(previous) && GET_OPCODE(*(*previous).u.l.savedpc.sub(1)) == OpCode::OP_TAILCALL {
+        (*ci).callstatus |= CIST_TAIL;
+        hook = lua::LUA_HOOKTAILCALL;
+    }
+    luaD_hook(L, hook, -1);
+    (*ci).u.l.savedpc = (*ci).u.l.savedpc.sub(1);  /* correct 'pc' */
+}
+
+
+unsafe fn adjust_varargs(L: *mut lua_State, p: *mut Proto, actual: c_int) -> StkId {
+    let nfixargs = (*p).numparams as c_int;
+    /* move fixed parameters to final position */
+    let fixed = (*L).top.offset(-actual as isize);  /* first fixed argument */
+    let base = (*L).top;  /* final position of first argument */
+    let mut i = 0;
+    while i < nfixargs && i < actual {
+        *(*L).top = *fixed.offset(i as isize);
+        (*L).top = (*L).top.add(1);
+        setnilvalue(fixed.offset(i as isize));  /* erase original copy (for GC) */
+        i += 1;
+    }
+    while i < nfixargs {
+        setnilvalue((*L).top);  /* complete missing arguments */
+        (*L).top = (*L).top.add(1);
+        i += 1;
+    }
+    base
+}
+
+
+/*
+** Check whether __call metafield of 'func' is a function. If so, put
+** it in stack below original 'func' so that 'luaD_precall' can call
+** it. Raise an error if __call metafield is not a function.
+*/
+unsafe fn tryfuncTM(L: *mut lua_State, func: StkId) {
+    let tm = luaT_gettmbyobj(L, func, TMS::TM_CALL);
+    if !(*tm).ttisfunction() {
+        luaG_typeerror(L, func, c_str!("call"));
+    }
+    /* Open a hole inside the stack at 'func' */
+    let mut p = (*L).top;
+    while p > func {
+        *p = *p.sub(1);
+        p = p.sub(1);
+    }
+    (*L).top = (*L).top.add(1);  /* slot ensured by caller */
+    *func = *tm;  /* tag method is the new function to be called */
+}
+
+
+unsafe fn next_ci(L: *mut lua_State) -> *mut CallInfo {
+    let next = (*(*L).ci).next;
+    (*L).ci = if !next.is_null() { next } else { luaE_extendCI(L) };
+    (*L).ci
+}
+
+
+/* check stack size, preserving 'p' */
+unsafe fn checkstackp(L: *mut lua_State, n: c_int, p: StkId) -> StkId {
+    if (*L).stack_last.offset_from((*L).top) <= n as isize {
+        let t = savestack(L, p);  /* save 'p' */
+        luaC_checkGC(L);  /* stack grow uses memory */
+        luaD_growstack(L, n);
+        restorestack(L, t)
+    } else {
+        p
+    }
+}
+
+
+/*
+** Prepares a function call: checks the stack, creates a new CallInfo
+** entry, fills in the relevant information, calls hook if needed.
+** If function is a C function, does the call, too. (Otherwise, leave
+** the execution ('luaV_execute') to the caller, to allow stackless
+** calls.) Returns true iff function has been executed (C function).
+*/
+#[cfg(rust_ldo)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaD_precall(L: *mut lua_State, func: StkId, nresults: c_int) -> c_int {
+    let f = match (*func).ttype() {
+        LUA_TCCL => (*(*func).clCvalue()).f,  /* C closure */
+        LUA_TLCF => (*func).fvalue(),  /* light C function */
+        LUA_TLCL => {  /* Lua function: prepare its call */
+            let p = (*(*func).clLvalue()).p;
+            let mut n = (*L).top.offset_from(func) as c_int - 1;  /* number of real arguments */
+            let fsize = (*p).maxstacksize as c_int;  /* frame size */
+            let func = checkstackp(L, fsize, func);
+            let base = if (*p).is_vararg != 1 {  /* do not use vararg? */
+                while n < (*p).numparams as c_int {
+                    setnilvalue((*L).top);  /* complete missing arguments */
+                    (*L).top = (*L).top.add(1);
+                    n += 1;
+                }
+                func.add(1)
+            } else {
+                adjust_varargs(L, p, n)
+            };
+            let ci = next_ci(L);  /* now 'enter' new function */
+            (*ci).nresults = nresults as i16;
+            (*ci).func = func;
+            (*ci).u.l.base = base;
+            (*ci).top = base.offset(fsize as isize);
+            (*L).top = (*ci).top;
+            debug_assert!((*ci).top <= (*L).stack_last);
+            (*ci).u.l.savedpc = (*p).code;  /* starting point */
+            (*ci).callstatus = CIST_LUA;
+            if (*L).hookmask & lua::LUA_MASKCALL != 0 {
+                callhook(L, ci);
+            }
+            return 0;
+        }
+        _ => {  /* not a function */
+            let func = checkstackp(L, 1, func);  /* ensure space for metamethod */
+            tryfuncTM(L, func);  /* try to get '__call' metamethod */
+            return luaD_precall(L, func, nresults);  /* now it must be a function */
+        }
+    };
+    let f = f.unwrap();
+    let func = checkstackp(L, LUA_MINSTACK, func);  /* ensure minimum stack size */
+    let ci = next_ci(L);  /* now 'enter' new function */
+    (*ci).nresults = nresults as i16;
+    (*ci).func = func;
+    (*ci).top = (*L).top.offset(LUA_MINSTACK as isize);
+    debug_assert!((*ci).top <= (*L).stack_last);
+    (*ci).callstatus = 0;
+    if (*L).hookmask & lua::LUA_MASKCALL != 0 {
+        luaD_hook(L, lua::LUA_HOOKCALL, -1);
+    }
+    lua_unlock(L);
+    let n = f(L);  /* do the actual call */
+    lua_lock(L);
+    api_checknelems(L, n);
+    luaD_poscall(L, ci, (*L).top.offset(-n as isize), n);
+    1
+}
+
+
+/*
+** Given 'nres' results at 'firstResult', move 'wanted' of them to 'res'.
+** Handle most typical cases (zero results for commands, one result for
+** expressions, multiple results for tail calls/single parameters)
+** separated.
+*/
+unsafe fn moveresults(L: *mut lua_State, mut firstResult: *const TValue, res: StkId,
+                      nres: c_int, wanted: c_int) -> c_int {
+    match wanted {  /* handle typical cases separately */
+        0 => {}  /* nothing to move */
+        1 => {  /* one result needed */
+            if nres == 0 {  /* no results? */
+                firstResult = luaO_nilobject();  /* adjust with nil */
+            }
+            *res = *firstResult;  /* move it to proper place */
+        }
+        LUA_MULTRET => {
+            for i in 0..nres as usize {  /* move all results to correct place */
+                *res.add(i) = *firstResult.add(i);
+            }
+            (*L).top = res.offset(nres as isize);
+            return 0;  /* wanted == LUA_MULTRET */
+        }
+        _ => {
+            if wanted <= nres {  /* enough results? */
+                for i in 0..wanted as usize {  /* move wanted results to correct place */
+                    *res.add(i) = *firstResult.add(i);
+                }
+            } else {  /* not enough results; use all of them plus nils */
+                for i in 0..nres as usize {  /* move all results to correct place */
+                    *res.add(i) = *firstResult.add(i);
+                }
+                for i in nres as usize..wanted as usize {  /* complete wanted number of results */
+                    setnilvalue(res.add(i));
+                }
+            }
+        }
+    }
+    (*L).top = res.offset(wanted as isize);  /* top points after the last result */
+    1
+}
+
+
+/*
+** Finishes a function call: calls hook if necessary, removes CallInfo,
+** moves current number of results to proper place; returns 0 iff call
+** wanted multiple (variable number of) results.
+*/
+#[cfg(rust_ldo)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaD_poscall(L: *mut lua_State, ci: *mut CallInfo,
+                                              mut firstResult: StkId, nres: c_int) -> c_int {
+    let wanted = (*ci).nresults as c_int;
+    if (*L).hookmask & (lua::LUA_MASKRET | lua::LUA_MASKLINE) != 0 {
+        if (*L).hookmask & lua::LUA_MASKRET != 0 {
+            let fr = savestack(L, firstResult);  /* hook may change stack */
+            luaD_hook(L, lua::LUA_HOOKRET, -1);
+            firstResult = restorestack(L, fr);
+        }
+        (*L).oldpc = (*(*ci).previous).u.l.savedpc;  /* 'oldpc' for caller function */
+    }
+    let res = (*ci).func;  /* res == final position of 1st result */
+    (*L).ci = (*ci).previous;  /* back to caller */
+    /* move results to proper place */
+    moveresults(L, firstResult, res, nres, wanted)
+}
+
+
+/*
+** Check appropriate error for stack overflow ("regular" overflow or
+** overflow while handling stack overflow). If 'nCalls' is larger than
+** LUAI_MAXCCALLS (which means it is handling a "regular" overflow) but
+** smaller than 9/8 of LUAI_MAXCCALLS, does not report an error (to
+** allow overflow handling to work)
+*/
+unsafe fn stackerror(L: *mut lua_State) {
+    let nCcalls = (*L).nCcalls as c_int;
+    if nCcalls == LUAI_MAXCCALLS {
+        luaG_runerror(L, c_str!("C stack overflow"));
+    } else if nCcalls >= LUAI_MAXCCALLS + (LUAI_MAXCCALLS >> 3) {
+        luaD_throw(L, LUA_ERRERR);  /* error while handing stack error */
+    }
+}
+
+
+/*
+** Call a function (C or Lua). The function to be called is at *func.
+** The arguments are on the stack, right after the function.
+** When returns, all the results are on the stack, starting at the original
+** function position.
+*/
+#[cfg(rust_ldo)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaD_call(L: *mut lua_State, func: StkId, nResults: c_int) {
+    (*L).nCcalls += 1;
+    if (*L).nCcalls as c_int >= LUAI_MAXCCALLS {
+        stackerror(L);
+    }
+    if luaD_precall(L, func, nResults) == 0 {  /* is a Lua function? */
+        luaV_execute(L);  /* call it */
+    }
+    (*L).nCcalls -= 1;
+}
+
+
+/*
+** Similar to 'luaD_call', but does not allow yields during the call
+*/
+#[cfg(rust_ldo)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaD_callnoyield(L: *mut lua_State, func: StkId, nResults: c_int) {
+    (*L).nny += 1;
+    luaD_call(L, func, nResults);
+    (*L).nny -= 1;
+}
+
+
+/*
+** Completes the execution of an interrupted C function, calling its
+** continuation function.
+*/
+unsafe fn finishCcall(L: *mut lua_State, status: c_int) {
+    let ci = (*L).ci;
+    /* must have a continuation and must be able to call it */
+    debug_assert!((*ci).u.c.k.is_some() && (*L).nny == 0);
+    /* error status can only happen in a protected call */
+    debug_assert!((*ci).callstatus & CIST_YPCALL != 0 || status == LUA_YIELD);
+    if (*ci).callstatus & CIST_YPCALL != 0 {  /* was inside a pcall? */
+        (*ci).callstatus &= !CIST_YPCALL;  /* finish 'lua_pcall' */
+        (*L).errfunc = (*ci).u.c.old_errfunc;
+    }
+    /* finish 'lua_callk'/'lua_pcall'; CIST_YPCALL and 'errfunc' already
+       handled */
+    if (*ci).nresults as c_int == LUA_MULTRET && (*ci).top < (*L).top {  /* adjustresults */
+        (*ci).top = (*L).top;
+    }
+    /* call continuation function */
+    let k = (*ci).u.c.k.unwrap();
+    lua_unlock(L);
+    let n = k(L, status, (*ci).u.c.ctx);
+    lua_lock(L);
+    api_checknelems(L, n);
+    /* finish 'luaD_precall' */
+    luaD_poscall(L, ci, (*L).top.offset(-n as isize), n);
+}
+
+
+/*
+** Executes "full continuation" (everything in the stack) of a
+** previously interrupted coroutine until the stack is empty (or another
+** interruption unwinds out of the loop). If the coroutine is
+** recovering from an error, 'status' is the error status, which must
+** be passed to the first continuation function (otherwise the default
+** status is LUA_YIELD).
+*/
+unsafe fn unroll(L: *mut lua_State, status: Option<c_int>) {
+    if let Some(status) = status {  /* error status? */
+        finishCcall(L, status);  /* finish 'lua_pcallk' callee */
+    }
+    while !ptr::eq((*L).ci, &(*L).base_ci) {  /* something in the stack */
+        if !isLua((*L).ci) {  /* C function? */
+            finishCcall(L, LUA_YIELD);  /* complete its execution */
+        } else {  /* Lua function */
+            luaV_finishOp(L);  /* finish interrupted instruction */
+            luaV_execute(L);  /* execute down to higher C 'boundary' */
+        }
+    }
+}
+
+
+/*
+** Try to find a suspended protected call (a "recover point") for the
+** given thread.
+*/
+unsafe fn findpcall(L: *mut lua_State) -> *mut CallInfo {
+    let mut ci = (*L).ci;
+    while !ci.is_null() {  /* search for a pcall */
+        if (*ci).callstatus & CIST_YPCALL != 0 {
+            return ci;
+        }
+        ci = (*ci).previous;
+    }
+    ptr::null_mut()  /* no pending pcall */
+}
+
+
+/*
+** Recovers from an error in a coroutine. Finds a recover point (if
+** there is one) and completes the execution of the interrupted
+** 'luaD_pcall'. If there is no recover point, returns false.
+*/
+unsafe fn recover(L: *mut lua_State, status: c_int) -> bool {
+    let ci = findpcall(L);
+    if ci.is_null() { return false; }  /* no recovery point */
+    /* "finish" luaD_pcall */
+    let oldtop = restorestack(L, (*ci).extra);
+    luaF_close(L, oldtop);
+    seterrorobj(L, status, oldtop);
+    (*L).ci = ci;
+    (*L).allowhook = getoah((*ci).callstatus);  /* restore original 'allowhook' */
+    (*L).nny = 0;  /* should be zero to be yieldable */
+    luaD_shrinkstack(L);
+    (*L).errfunc = (*ci).u.c.old_errfunc;
+    true  /* continue running the coroutine */
+}
+
+
+/*
+** signal an error in the call to 'resume', not in the execution of the
+** coroutine itself. (Such errors should not be handled by any coroutine
+** error handler and should not kill the coroutine.)
+*/
+unsafe fn resume_error(L: *mut lua_State, msg: *const c_char, firstArg: StkId) -> ! {
+    (*L).top = firstArg;  /* remove args from the stack */
+    setsvalue((*L).top, luaS_new(L, msg));  /* push error message */
+    (*L).top = (*L).top.add(1);
+    debug_assert!((*L).top <= (*(*L).ci).top, "stack overflow");
+    luaD_throw(L, -1)  /* back to 'lua_resume' */
+}
+
+
+/*
+** Do the work for 'lua_resume' in protected mode. Most of the work
+** depends on the status of the coroutine: initial state, suspended
+** inside a hook, or regularly suspended (optionally with a continuation
+** function), plus erroneous cases: non-suspended coroutine or dead
+** coroutine.
+*/
+unsafe fn resume(L: *mut lua_State, mut n: c_int) {
+    let nCcalls = (*L).nCcalls;
+    let mut firstArg = (*L).top.offset(-n as isize);  /* first argument */
+    let ci = (*L).ci;
+    if nCcalls as c_int >= LUAI_MAXCCALLS {
+        resume_error(L, c_str!("C stack overflow"), firstArg);
+    }
+    if (*L).status as c_int == LUA_OK {  /* may be starting a coroutine */
+        if !ptr::eq(ci, &(*L).base_ci) {  /* not in base level? */
+            resume_error(L, c_str!("cannot resume non-suspended coroutine"), firstArg);
+        }
+        /* coroutine is in base level; start running it */
+        if luaD_precall(L, firstArg.sub(1), LUA_MULTRET) == 0 {  /* Lua function? */
+            luaV_execute(L);  /* call it */
+        }
+    } else if (*L).status as c_int != LUA_YIELD {
+        resume_error(L, c_str!("cannot resume dead coroutine"), firstArg);
+    } else {  /* resuming from previous yield */
+        (*L).status = LUA_OK as u8;  /* mark that it is running (again) */
+        (*ci).func = restorestack(L, (*ci).extra);
+        if isLua(ci) {  /* yielded inside a hook? */
+            luaV_execute(L);  /* just continue running Lua code */
+        } else {  /* 'common' yield */
+            if let Some(k) = (*ci).u.c.k {  /* does it have a continuation function? */
+                lua_unlock(L);
+                n = k(L, LUA_YIELD, (*ci).u.c.ctx);  /* call continuation */
+                lua_lock(L);
+                api_checknelems(L, n);
+                firstArg = (*L).top.offset(-n as isize);  /* yield results come from continuation */
+            }
+            luaD_poscall(L, ci, firstArg, n);  /* finish 'luaD_precall' */
+        }
+        unroll(L, None);  /* run continuation */
+    }
+    debug_assert!(nCcalls == (*L).nCcalls);
+}
+
+
+#[cfg(rust_ldo)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_resume(L: *mut lua_State, from: *mut lua_State,
+                                            nargs: c_int) -> c_int {
+    let oldnny = (*L).nny;  /* save "number of non-yieldable" calls */
+    lua_lock(L);
+    (*L).nCcalls = if !from.is_null() { (*from).nCcalls + 1 } else { 1 };
+    (*L).nny = 0;  /* allow yields */
+    api_checknelems(L, if (*L).status as c_int == LUA_OK { nargs + 1 } else { nargs });
+    let mut status = status(rawrunprotected(L, || resume(L, nargs)));
+    if status == -1 {  /* error calling 'lua_resume'? */
+        status = LUA_ERRRUN;
+    } else {  /* continue running after recoverable errors */
+        while errorstatus(status) && recover(L, status) {
+            /* unroll continuation */
+            status = self::status(rawrunprotected(L, || unroll(L, Some(status))));
+        }
+        if errorstatus(status) {  /* unrecoverable error? */
+            (*L).status = status as u8;  /* mark thread as 'dead' */
+            seterrorobj(L, status, (*L).top);  /* push error message */
+            (*(*L).ci).top = (*L).top;
+        } else {
+            debug_assert!(status == (*L).status as c_int);  /* normal end or yield */
+        }
+    }
+    (*L).nny = oldnny;  /* restore 'nny' */
+    (*L).nCcalls -= 1;
+    debug_assert!((*L).nCcalls == if !from.is_null() { (*from).nCcalls } else { 0 });
+    lua_unlock(L);
+    status
+}
+
+
+#[cfg(rust_ldo)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_isyieldable(L: *mut lua_State) -> c_int {
+    ((*L).nny == 0) as c_int
+}
+
+
+#[cfg(rust_ldo)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_yieldk(L: *mut lua_State, nresults: c_int, ctx: lua_KContext,
+                                            k: lua_KFunction) -> c_int {
+    let ci = (*L).ci;
+    lua_lock(L);
+    api_checknelems(L, nresults);
+    if (*L).nny > 0 {
+        if L != (*G(L)).mainthread {
+            luaG_runerror(L, c_str!("attempt to yield across a C-call boundary"));
+        } else {
+            luaG_runerror(L, c_str!("attempt to yield from outside a coroutine"));
+        }
+    }
+    (*L).status = LUA_YIELD as u8;
+    (*ci).extra = savestack(L, (*ci).func);  /* save current 'func' */
+    if isLua(ci) {  /* inside a hook? */
+        debug_assert!(k.is_none(), "hooks cannot continue after yielding");
+    } else {
+        (*ci).u.c.k = k;
+        if k.is_some() {  /* is there a continuation? */
+            (*ci).u.c.ctx = ctx;  /* save context */
+        }
+        (*ci).func = (*L).top.offset(-nresults as isize - 1);  /* protect stack below results */
+        luaD_throw(L, LUA_YIELD);
+    }
+    debug_assert!((*ci).callstatus & CIST_HOOKED != 0);  /* must be inside a hook */
+    lua_unlock(L);
+    0  /* return to 'luaD_hook' */
+}
+
+
+/*
+** Runs 'f' in protected mode. On errors, closes the upvalues above
+** 'old_top', leaves the error object there and restores the call
+** state; the error status comes back as 'Err'.
+*/
+pub unsafe fn pcall<F: FnOnce()>(L: *mut lua_State, f: F, old_top: isize,
+                                 ef: isize) -> Result<(), c_int> {
+    let old_ci = (*L).ci;
+    let old_allowhooks = (*L).allowhook;
+    let old_nny = (*L).nny;
+    let old_errfunc = (*L).errfunc;
+    (*L).errfunc = ef;
+    let r = rawrunprotected(L, f);
+    if let Err(status) = r {  /* an error occurred? */
+        let oldtop = restorestack(L, old_top);
+        luaF_close(L, oldtop);  /* close possible pending closures */
+        seterrorobj(L, status, oldtop);
+        (*L).ci = old_ci;
+        (*L).allowhook = old_allowhooks;
+        (*L).nny = old_nny;
+        luaD_shrinkstack(L);
+    }
+    (*L).errfunc = old_errfunc;
+    r
+}
+
+
+#[cfg(rust_ldo)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaD_pcall(L: *mut lua_State, func: Pfunc, u: *mut c_void,
+                                            old_top: isize, ef: isize) -> c_int {
+    status(pcall(L, || func(L, u), old_top, ef))
+}
+
+
+
+/*
+** Execute a protected parser.
+*/
+unsafe fn checkmode(L: *mut lua_State, mode: *const c_char, x: *const c_char) {
+    if !mode.is_null() && strchr(mode, *x as c_int).is_null() {
+        luaO_pushfstring(L, c_str!("attempt to load a %s chunk (mode is '%s')"), x, mode);
+        luaD_throw(L, LUA_ERRSYNTAX);
+    }
+}
+
+
+unsafe fn f_parser(L: *mut lua_State, z: *mut Zio, buff: *mut Mbuffer, dyd: *mut Dyndata,
+                   name: *const c_char, mode: *const c_char) {
+    let c = zgetc(z);  /* read first character */
+    let cl = if c == lua::LUA_SIGNATURE[0] as c_int {
+        checkmode(L, mode, c_str!("binary"));
+        luaU_undump(L, z, name)
+    } else {
+        checkmode(L, mode, c_str!("text"));
+        luaY_parser(L, z, buff, dyd, name, c)
+    };
+    debug_assert!((*cl).nupvalues as c_int == (*(*cl).p).sizeupvalues);
+    luaF_initupvals(L, cl);
+}
+
+
+#[cfg(rust_ldo)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaD_protectedparser(L: *mut lua_State, z: *mut Zio,
+                                                      name: *const c_char,
+                                                      mode: *const c_char) -> c_int {
+    (*L).nny += 1;  /* cannot yield during parsing */
+    let mut buff: Mbuffer = mem::zeroed();  /* dynamic structure used by the scanner */
+    let mut dyd: Dyndata = mem::zeroed();  /* dynamic structures used by the parser */
+    let status = status(pcall(L, || f_parser(L, z, &mut buff, &mut dyd, name, mode),
+                              savestack(L, (*L).top), (*L).errfunc));
+    luaZ_resizebuffer(L, &mut buff, 0);
+    luaM_freearray(L, dyd.actvar.arr, dyd.actvar.size as usize);
+    luaM_freearray(L, dyd.gt.arr, dyd.gt.size as usize);
+    luaM_freearray(L, dyd.label.arr, dyd.label.size as usize);
+    (*L).nny -= 1;
+    status
+}
@@ -0,0 +1,213 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Save precompiled Lua chunks.
+
+#![allow(non_snake_case)]
+
+use bytecode::{LUAC_DATA, LUAC_FORMAT, LUAC_INT, LUAC_NUM, LUAC_VERSION, LUA_SIGNATURE};
+use ffi::lua::{self, lua_Integer, lua_Number, lua_Writer};
+use lobject::{getstr, tsslen, Proto, TString, LUA_TLNGSTR, LUA_TNUMFLT, LUA_TNUMINT,
+              LUA_TSHRSTR};
+use lopcodes::Instruction;
+use lstate::{lua_lock, lua_State, lua_unlock};
+use libc::{c_int, c_void, size_t};
+use std::mem;
+use std::ptr;
+
+
+/* the exported functions below, from 'ldump.c' when it is linked instead (see 'build.rs') */
+#[cfg(not(rust_ldump))]
+extern "C-unwind" {
+    pub fn luaU_dump(L: *mut lua_State, f: *const Proto, w: lua_Writer, data: *mut c_void,
+                     strip: c_int) -> c_int;
+}
+
+
+struct DumpState {
+    L: *mut lua_State,
+    writer: lua_Writer,
+    data: *mut c_void,
+    strip: c_int,
+    status: c_int,
+}
+
+
+/*
+** All high-level dumps go through DumpVector; you can change it to
+** change the endianness of the result
+*/
+unsafe fn DumpVector<T>(v: *const T, n: size_t, D: *mut DumpState) {
+    DumpBlock(v as *const c_void, n * mem::size_of::<T>(), D);
+}
+
+unsafe fn DumpLiteral(s: &[u8], D: *mut DumpState) {
+    DumpBlock(s.as_ptr() as *const c_void, s.len(), D);
+}
+
+
+unsafe fn DumpBlock(b: *const c_void, size: size_t, D: *mut DumpState) {
+    if (*D).status == 0 && size > 0 {
+        let writer = (*D).writer.unwrap();
+        lua_unlock((*D).L);
+        (*D).status = writer((*D).L, b, size, (*D).data);
+        lua_lock((*D).L);
+    }
+}
+
+
+unsafe fn DumpVar<T>(x: T, D: *mut DumpState) {
+    DumpVector(&x, 1, D);
+}
+
+
+unsafe fn DumpByte(y: c_int, D: *mut DumpState) {
+    DumpVar(y as u8, D);
+}
+
+
+unsafe fn DumpInt(x: c_int, D: *mut DumpState) {
+    DumpVar(x, D);
+}
+
+
+unsafe fn DumpNumber(x: lua_Number, D: *mut DumpState) {
+    DumpVar(x, D);
+}
+
+
+unsafe fn DumpInteger(x: lua_Integer, D: *mut DumpState) {
+    DumpVar(x, D);
+}
+
+
+unsafe fn DumpString(s: *const TString, D: *mut DumpState) {
+    if s.is_null() {
+        DumpByte(0, D);
+    } else {
+        let size = tsslen(s) + 1;  /* include trailing '\0' */
+        let str = getstr(s);
+        if size < 0xFF {
+            DumpByte(size as c_int, D);
+        } else {
+            DumpByte(0xFF, D);
+            DumpVar(size, D);
+        }
+        DumpVector(str, size - 1, D);  /* no need to save '\0' */
+    }
+}
+
+
+unsafe fn DumpCode(f: *const Proto, D: *mut DumpState) {
+    DumpInt((*f).sizecode, D);
+    DumpVector::<Instruction>((*f).code, (*f).sizecode as size_t, D);
+}
+
+
+unsafe fn DumpConstants(f: *const Proto, D: *mut DumpState) {
+    let n = (*f).sizek;
+    DumpInt(n, D);
+    for i in 0..n as isize {
+        let o = &*(*f).k.offset(i);
+        DumpByte(o.ttype(), D);
+        match o.ttype() {
+            lua::LUA_TNIL => {}
+            lua::LUA_TBOOLEAN => DumpByte(o.bvalue(), D),
+            LUA_TNUMFLT => DumpNumber(o.fltvalue(), D),
+            LUA_TNUMINT => DumpInteger(o.ivalue(), D),
+            LUA_TSHRSTR | LUA_TLNGSTR => DumpString(o.tsvalue(), D),
+            _ => debug_assert!(false),
+        }
+    }
+}
+
+
+unsafe fn DumpProtos(f: *const Proto, D: *mut DumpState) {
+    let n = (*f).sizep;
+    DumpInt(n, D);
+    for i in 0..n as isize {
+        DumpFunction(*(*f).p.offset(i), (*f).source, D);
+    }
+}
+
+
+unsafe fn DumpUpvalues(f: *const Proto, D: *mut DumpState) {
+    let n = (*f).sizeupvalues;
+    DumpInt(n, D);
+    for i in 0..n as isize {
+        DumpByte((*(*f).upvalues.offset(i)).instack as c_int, D);
+        DumpByte((*(*f).upvalues.offset(i)).idx as c_int, D);
+    }
+}
+
+
+unsafe fn DumpDebug(f: *const Proto, D: *mut DumpState) {
+    let n = if (*D).strip != 0 { 0 } else { (*f).sizelineinfo };
+    DumpInt(n, D);
+    DumpVector((*f).lineinfo, n as size_t, D);
+    let n = if (*D).strip != 0 { 0 } else { (*f).sizelocvars };
+    DumpInt(n, D);
+    for i in 0..n as isize {
+        let v = (*f).locvars.offset(i);
+        DumpString((*v).varname, D);
+        DumpInt((*v).startpc, D);
+        DumpInt((*v).endpc, D);
+    }
+    let n = if (*D).strip != 0 { 0 } else { (*f).sizeupvalues };
+    DumpInt(n, D);
+    for i in 0..n as isize {
+        DumpString((*(*f).upvalues.offset(i)).name, D);
+    }
+}
+
+
+unsafe fn DumpFunction(f: *const Proto, psource: *mut TString, D: *mut DumpState) {
+    if (*D).strip != 0 || (*f).source == psource {
+        DumpString(ptr::null(), D);  /* no debug info or same source as its parent */
+    } else {
+        DumpString((*f).source, D);
+    }
+    DumpInt((*f).linedefined, D);
+    DumpInt((*f).lastlinedefined, D);
+    DumpByte((*f).numparams as c_int, D);
+    DumpByte((*f).is_vararg as c_int, D);
+    DumpByte((*f).maxstacksize as c_int, D);
+    DumpCode(f, D);
+    DumpConstants(f, D);
+    DumpUpvalues(f, D);
+    DumpProtos(f, D);
+    DumpDebug(f, D);
+}
+
+
+unsafe fn DumpHeader(D: *mut DumpState) {
+    DumpLiteral(LUA_SIGNATURE, D);
+    DumpByte(LUAC_VERSION as c_int, D);
+    DumpByte(LUAC_FORMAT as c_int, D);
+    DumpLiteral(LUAC_DATA, D);
+    DumpByte(mem::size_of::<c_int>() as c_int, D);
+    DumpByte(mem::size_of::<size_t>() as c_int, D);
+    DumpByte(mem::size_of::<Instruction>() as c_int, D);
+    DumpByte(mem::size_of::<lua_Integer>() as c_int, D);
+    DumpByte(mem::size_of::<lua_Number>() as c_int, D);
+    DumpInteger(LUAC_INT, D);
+    DumpNumber(LUAC_NUM, D);
+}
+
+
+/*
+** dump Lua function as precompiled chunk
+*/
+#[cfg(rust_ldump)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaU_dump(L: *mut lua_State, f: *const Proto, w: lua_Writer,
+                                   data: *mut c_void, strip: c_int) -> c_int {
+    let mut D = DumpState { L, writer: w, data, strip, status: 0 };
+    DumpHeader(&mut D);
+    DumpByte((*f).sizeupvalues, &mut D);
+    DumpFunction(f, ptr::null_mut(), &mut D);
+    D.status
+}
@@ -0,0 +1,838 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Lexical analyzer.
+//!
+//! The scanner here is shared by the core, which reaches it through the
+//! `luaX_*` entry points of `llex.c`, and by `Lexer`, an iterator over the
+//! tokens of a chunk with their byte spans:
+//!
+//! ```
+//! use lua_rs::lexer::{Lexer, Reserved, Token};
+//!
+//! let tokens: Vec<_> = Lexer::new(b"local x = 0x10").map(|t| t.unwrap()).collect();
+//! assert_eq!(tokens[0].token, Token::Reserved(Reserved::Local));
+//! assert_eq!(tokens[3].token, Token::Int(16));
+//! assert_eq!(tokens[3].span, 10..14);
+//! ```
+
+use lctype::{lisdigit, lislalnum, lislalpha, lisspace, lisxdigit};
+use lobject::{self, Numeral, UTF8BUFFSZ};
+use std::fmt;
+use std::mem;
+use std::ops::Range;
+
+
+pub const FIRST_RESERVED: i32 = 257;
+
+pub const LUA_ENV: &str = "_ENV";
+
+
+/// Terminal symbols that have a code of their own: reserved words,
+/// multi-byte symbols, the end of the stream and the kinds of literals.
+/*
+* WARNING: if you change the order of this enumeration,
+* grep "ORDER RESERVED"
+*/
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Reserved {
+    /* terminal symbols denoted by reserved words */
+    And = FIRST_RESERVED, Break,
+    Do, Else, Elseif, End, False, For, Function,
+    Goto, If, In, Local, Nil, Not, Or, Repeat,
+    Return, Then, True, Until, While,
+    /* other terminal symbols */
+    IDiv, Concat, Dots, Eq, Ge, Le, Ne,
+    Shl, Shr,
+    DbColon, Eos,
+    Flt, Int, Name, String,
+}
+
+/* number of reserved words */
+pub const NUM_RESERVED: usize = (Reserved::While as i32 - FIRST_RESERVED + 1) as usize;
+
+/* ORDER RESERVED */
+const RESERVED: [Reserved; 37] = [
+    Reserved::And, Reserved::Break,
+    Reserved::Do, Reserved::Else, Reserved::Elseif, Reserved::End, Reserved::False,
+    Reserved::For, Reserved::Function, Reserved::Goto, Reserved::If, Reserved::In,
+    Reserved::Local, Reserved::Nil, Reserved::Not, Reserved::Or, Reserved::Repeat,
+    Reserved::Return, Reserved::Then, Reserved::True, Reserved::Until, Reserved::While,
+    Reserved::IDiv, Reserved::Concat, Reserved::Dots, Reserved::Eq, Reserved::Ge,
+    Reserved::Le, Reserved::Ne, Reserved::Shl, Reserved::Shr, Reserved::DbColon,
+    Reserved::Eos, Reserved::Flt, Reserved::Int, Reserved::Name, Reserved::String,
+];
+
+/* ORDER RESERVED */
+pub const TOKENS: [&str; 37] = [
+    "and", "break", "do", "else", "elseif",
+    "end", "false", "for", "function", "goto", "if",
+    "in", "local", "nil", "not", "or", "repeat",
+    "return", "then", "true", "until", "while",
+    "//", "..", "...", "==", ">=", "<=", "~=",
+    "<<", ">>", "::", "<eof>",
+    "<number>", "<integer>", "<name>", "<string>",
+];
+
+impl Reserved {
+    /// The terminal symbol with the given token code, if any.
+    pub fn from_code(code: i32) -> Option<Reserved> {
+        if code >= FIRST_RESERVED { RESERVED.get((code - FIRST_RESERVED) as usize).cloned() }
+        else { None }
+    }
+
+    /// The reserved word spelled `name`, if any.
+    pub fn keyword(name: &[u8]) -> Option<Reserved> {
+        TOKENS[..NUM_RESERVED].iter().position(|w| w.as_bytes() == name).map(|i| RESERVED[i])
+    }
+
+    /// The text of the symbol, as used in error messages.
+    pub fn as_str(self) -> &'static str {
+        TOKENS[(self as i32 - FIRST_RESERVED) as usize]
+    }
+}
+
+
+/// A token, with its semantic information.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    /// A single-byte symbol, such as `+` or `(`.
+    Char(u8),
+    /// A reserved word or a multi-byte symbol.
+    Reserved(Reserved),
+    Flt(f64),
+    Int(i64),
+    Name(String),
+    /// A string literal, with its escape sequences already decoded.
+    String(Vec<u8>),
+}
+
+impl Token {
+    /// The token code of the C core: the byte itself for single-byte
+    /// symbols, a `Reserved` value for everything else.
+    pub fn code(&self) -> i32 {
+        match *self {
+            Token::Char(c) => c as i32,
+            Token::Reserved(r) => r as i32,
+            Token::Flt(_) => Reserved::Flt as i32,
+            Token::Int(_) => Reserved::Int as i32,
+            Token::Name(_) => Reserved::Name as i32,
+            Token::String(_) => Reserved::String as i32,
+        }
+    }
+}
+
+/* as 'luaX_token2str' */
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Token::Char(c @ b' '..=b'~') => write!(f, "'{}'", c as char),
+            Token::Char(c) => write!(f, "'<\\{}>'", c),  /* non-printable character */
+            Token::Reserved(r) if r != Reserved::Eos => write!(f, "'{}'", r.as_str()),
+            ref t => f.write_str(Reserved::from_code(t.code()).unwrap().as_str()),
+        }
+    }
+}
+
+
+/// A token with the range of bytes it spans in the source and the line
+/// where it starts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spanned {
+    pub token: Token,
+    pub span: Range<usize>,
+    pub line: i32,
+}
+
+
+/// A comment, with the range of bytes it spans in the source (from the
+/// leading `--` on) and the line where it starts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comment {
+    /// The text after `--`, or between the brackets of a long comment
+    /// (without a first newline, as in long strings).
+    pub text: Vec<u8>,
+    pub span: Range<usize>,
+    pub line: i32,
+}
+
+
+/// The lexical errors, with the messages of `llex.c`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    LexicalElementTooLong,
+    TooManyLines,
+    MalformedNumber,
+    /// An unfinished long string starting at the given line.
+    UnfinishedLongString(i32),
+    /// An unfinished long comment starting at the given line.
+    UnfinishedLongComment(i32),
+    UnfinishedString,
+    InvalidLongStringDelimiter,
+    HexadecimalDigitExpected,
+    MissingOpenBrace,
+    MissingCloseBrace,
+    Utf8ValueTooLarge,
+    DecimalEscapeTooLarge,
+    InvalidEscapeSequence,
+}
+
+impl ErrorKind {
+    /// The message, for kinds that need no formatting.
+    pub fn message(self) -> Option<&'static str> {
+        Some(match self {
+            ErrorKind::LexicalElementTooLong => "lexical element too long",
+            ErrorKind::TooManyLines => "chunk has too many lines",
+            ErrorKind::MalformedNumber => "malformed number",
+            ErrorKind::UnfinishedLongString(_) | ErrorKind::UnfinishedLongComment(_) => return None,
+            ErrorKind::UnfinishedString => "unfinished string",
+            ErrorKind::InvalidLongStringDelimiter => "invalid long string delimiter",
+            ErrorKind::HexadecimalDigitExpected => "hexadecimal digit expected",
+            ErrorKind::MissingOpenBrace => "missing '{'",
+            ErrorKind::MissingCloseBrace => "missing '}'",
+            ErrorKind::Utf8ValueTooLarge => "UTF-8 value too large",
+            ErrorKind::DecimalEscapeTooLarge => "decimal escape too large",
+            ErrorKind::InvalidEscapeSequence => "invalid escape sequence",
+        })
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ErrorKind::UnfinishedLongString(line) =>
+                write!(f, "unfinished long string (starting at line {})", line),
+            ErrorKind::UnfinishedLongComment(line) =>
+                write!(f, "unfinished long comment (starting at line {})", line),
+            kind => f.write_str(kind.message().unwrap()),
+        }
+    }
+}
+
+
+/// A lexical error. Its message reads as the one of the C core, as in
+/// `chunk:1: malformed number near '3.4.5'`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LexError {
+    pub kind: ErrorKind,
+    /// Name of the chunk, as given to `Lexer::with_name`.
+    pub source: String,
+    pub line: i32,
+    /// What the error is near: the quoted text read so far of the
+    /// offending token, or `<eof>`.
+    pub near: Option<String>,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.source, self.line, self.kind)?;
+        if let Some(ref near) = self.near {
+            write!(f, " near {}", near)?;
+        }
+        Ok(())
+    }
+}
+
+impl ::std::error::Error for LexError {}
+
+
+/*
+** {======================================================
+** Scanner, shared with the core
+** =======================================================
+*/
+
+/* what an error is near */
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Near {
+    Nothing,
+    Token,  /* the text of the current token, in the buffer */
+    Eos,
+}
+
+/* a token read by 'llex'; 'S' is how its strings are represented */
+pub(crate) enum Lexeme<S> {
+    Char(u8),
+    Reserved(Reserved),
+    Flt(f64),
+    Int(i64),
+    Name(S),
+    String(S),
+}
+
+/*
+** State of the scanner. Errors from the core never return (they unwind
+** the C stack), so no code here may hold values that need to be dropped
+** when raising one.
+*/
+pub(crate) trait Scan {
+    type Str;
+    type Error;
+    /* current character (charint), or EOZ */
+    fn current(&self) -> i32;
+    fn next(&mut self);
+    fn save(&mut self, c: i32) -> Result<(), Self::Error>;
+    /* token buffer */
+    fn buffer(&self) -> &[u8];
+    fn buffremove(&mut self, n: usize);
+    fn resetbuffer(&mut self);
+    /* input line counter */
+    fn linenumber(&mut self) -> &mut i32;
+    /* string made of 'buffer()[r]' */
+    fn newstring(&mut self, r: Range<usize>) -> Self::Str;
+    fn error(&mut self, kind: ErrorKind, near: Near) -> Self::Error;
+    /* called where each (possible) token starts */
+    fn token_start(&mut self) {}
+    /* called after skipping a comment; 'sep' is the level of a long one */
+    fn comment(&mut self, _sep: Option<i32>) {}
+}
+
+
+use lzio::EOZ;
+
+fn curr_is_newline<S: Scan>(ls: &S) -> bool {
+    ls.current() == '\n' as i32 || ls.current() == '\r' as i32
+}
+
+fn save_and_next<S: Scan>(ls: &mut S) -> Result<(), S::Error> {
+    let c = ls.current();
+    ls.save(c)?;
+    ls.next();
+    Ok(())
+}
+
+
+/*
+** increment line number and skips newline sequence (any of
+** \n, \r, \n\r, or \r\n)
+*/
+fn inclinenumber<S: Scan>(ls: &mut S) -> Result<(), S::Error> {
+    let old = ls.current();
+    debug_assert!(curr_is_newline(ls));
+    ls.next();  /* skip '\n' or '\r' */
+    if curr_is_newline(ls) && ls.current() != old {
+        ls.next();  /* skip '\n\r' or '\r\n' */
+    }
+    *ls.linenumber() += 1;
+    if *ls.linenumber() == i32::MAX {
+        return Err(ls.error(ErrorKind::TooManyLines, Near::Nothing));
+    }
+    Ok(())
+}
+
+
+fn check_next1<S: Scan>(ls: &mut S, c: u8) -> bool {
+    if ls.current() == c as i32 {
+        ls.next();
+        true
+    } else {
+        false
+    }
+}
+
+
+/*
+** Check whether current char is in set 'set' (with two chars) and
+** saves it
+*/
+fn check_next2<S: Scan>(ls: &mut S, set: &[u8; 2]) -> Result<bool, S::Error> {
+    if ls.current() == set[0] as i32 || ls.current() == set[1] as i32 {
+        save_and_next(ls)?;
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+
+/*
+** this function is quite liberal in what it accepts, as 'str2num'
+** will reject ill-formed numerals.
+*/
+fn read_numeral<S: Scan>(ls: &mut S) -> Result<Lexeme<S::Str>, S::Error> {
+    let mut expo = b"Ee";
+    let first = ls.current();
+    debug_assert!(lisdigit(ls.current()));
+    save_and_next(ls)?;
+    if first == '0' as i32 && check_next2(ls, b"xX")? {  /* hexadecimal? */
+        expo = b"Pp";
+    }
+    loop {
+        if check_next2(ls, expo)? {  /* exponent part? */
+            check_next2(ls, b"-+")?;  /* optional exponent sign */
+        }
+        if lisxdigit(ls.current()) || ls.current() == '.' as i32 {
+            save_and_next(ls)?;
+        } else {
+            break;
+        }
+    }
+    match lobject::str2num(ls.buffer()) {
+        Some(Numeral::Int(i)) => Ok(Lexeme::Int(i)),
+        Some(Numeral::Flt(n)) => Ok(Lexeme::Flt(n)),
+        None => Err(ls.error(ErrorKind::MalformedNumber, Near::Token)),  /* format error */
+    }
+}
+
+
+/*
+** skip a sequence '[=*[' or ']=*]'; if sequence is well formed, return
+** its number of '='s; otherwise, return a negative number (-1 iff there
+** are no '='s after initial bracket)
+*/
+fn skip_sep<S: Scan>(ls: &mut S) -> Result<i32, S::Error> {
+    let mut count = 0;
+    let s = ls.current();
+    debug_assert!(s == '[' as i32 || s == ']' as i32);
+    save_and_next(ls)?;
+    while ls.current() == '=' as i32 {
+        save_and_next(ls)?;
+        count += 1;
+    }
+    Ok(if ls.current() == s { count } else { (-count) - 1 })
+}
+
+
+/* reads a long string, or skips a long comment when not 'keep' */
+fn read_long_string<S: Scan>(ls: &mut S, keep: bool, sep: i32) -> Result<Option<S::Str>, S::Error> {
+    let line = *ls.linenumber();  /* initial line (for error message) */
+    save_and_next(ls)?;  /* skip 2nd '[' */
+    if curr_is_newline(ls) {  /* string starts with a newline? */
+        inclinenumber(ls)?;  /* skip it */
+    }
+    loop {
+        match ls.current() {
+            EOZ => {  /* error */
+                let kind = if keep { ErrorKind::UnfinishedLongString(line) }
+                           else { ErrorKind::UnfinishedLongComment(line) };
+                return Err(ls.error(kind, Near::Eos));
+            }
+            c if c == ']' as i32 => {
+                if skip_sep(ls)? == sep {
+                    save_and_next(ls)?;  /* skip 2nd ']' */
+                    break;
+                }
+            }
+            c if c == '\n' as i32 || c == '\r' as i32 => {
+                ls.save('\n' as i32)?;
+                inclinenumber(ls)?;
+                if !keep { ls.resetbuffer(); }  /* avoid wasting space */
+            }
+            _ => {
+                if keep { save_and_next(ls)?; }
+                else { ls.next(); }
+            }
+        }
+    }
+    if keep {
+        let (n, sep) = (ls.buffer().len(), (2 + sep) as usize);
+        Ok(Some(ls.newstring(sep..n - sep)))
+    } else {
+        Ok(None)
+    }
+}
+
+
+fn esccheck<S: Scan>(ls: &mut S, c: bool, kind: ErrorKind) -> Result<(), S::Error> {
+    if !c {
+        if ls.current() != EOZ {
+            save_and_next(ls)?;  /* add current to buffer for error message */
+        }
+        return Err(ls.error(kind, Near::Token));
+    }
+    Ok(())
+}
+
+
+fn gethexa<S: Scan>(ls: &mut S) -> Result<i32, S::Error> {
+    save_and_next(ls)?;
+    let c = lisxdigit(ls.current());
+    esccheck(ls, c, ErrorKind::HexadecimalDigitExpected)?;
+    Ok(lobject::luaO_hexavalue(ls.current()))
+}
+
+
+fn readhexaesc<S: Scan>(ls: &mut S) -> Result<i32, S::Error> {
+    let mut r = gethexa(ls)?;
+    r = (r << 4) + gethexa(ls)?;
+    ls.buffremove(2);  /* remove saved chars from buffer */
+    Ok(r)
+}
+
+
+fn readutf8esc<S: Scan>(ls: &mut S) -> Result<u32, S::Error> {
+    let mut i = 4;  /* chars to be removed: '\', 'u', '{', and first digit */
+    save_and_next(ls)?;  /* skip 'u' */
+    let c = ls.current() == '{' as i32;
+    esccheck(ls, c, ErrorKind::MissingOpenBrace)?;
+    let mut r = gethexa(ls)? as u32;  /* must have at least one digit */
+    loop {
+        save_and_next(ls)?;
+        if !lisxdigit(ls.current()) { break; }
+        i += 1;
+        r = (r << 4) + lobject::luaO_hexavalue(ls.current()) as u32;
+        esccheck(ls, r <= 0x10FFFF, ErrorKind::Utf8ValueTooLarge)?;
+    }
+    let c = ls.current() == '}' as i32;
+    esccheck(ls, c, ErrorKind::MissingCloseBrace)?;
+    ls.next();  /* skip '}' */
+    ls.buffremove(i);  /* remove saved chars from buffer */
+    Ok(r)
+}
+
+
+fn utf8esc<S: Scan>(ls: &mut S) -> Result<(), S::Error> {
+    let (buff, n) = lobject::luaO_utf8esc(readutf8esc(ls)?);
+    for &c in &buff[UTF8BUFFSZ - n..] {  /* add 'buff' to string */
+        ls.save(c as i32)?;
+    }
+    Ok(())
+}
+
+
+fn readdecesc<S: Scan>(ls: &mut S) -> Result<i32, S::Error> {
+    let mut r = 0;  /* result accumulator */
+    let mut i = 0;
+    while i < 3 && lisdigit(ls.current()) {  /* read up to 3 digits */
+        r = 10 * r + ls.current() - '0' as i32;
+        save_and_next(ls)?;
+        i += 1;
+    }
+    esccheck(ls, r <= u8::MAX as i32, ErrorKind::DecimalEscapeTooLarge)?;
+    ls.buffremove(i);  /* remove read digits from buffer */
+    Ok(r)
+}
+
+
+fn read_string<S: Scan>(ls: &mut S, del: i32) -> Result<S::Str, S::Error> {
+    save_and_next(ls)?;  /* keep delimiter (for error messages) */
+    while ls.current() != del {
+        match ls.current() {
+            EOZ => return Err(ls.error(ErrorKind::UnfinishedString, Near::Eos)),
+            c if c == '\n' as i32 || c == '\r' as i32 => {
+                return Err(ls.error(ErrorKind::UnfinishedString, Near::Token));
+            }
+            c if c == '\\' as i32 => {  /* escape sequences */
+                enum Esc { Read(i32), Only(i32), No }  /* 'read_save', 'only_save', 'no_save' */
+                save_and_next(ls)?;  /* keep '\\' for error messages */
+                let esc = match ls.current() {
+                    EOZ => Esc::No,  /* will raise an error next loop */
+                    c => match c as u8 {
+                        b'a' => Esc::Read(0x07),
+                        b'b' => Esc::Read(0x08),
+                        b'f' => Esc::Read(0x0c),
+                        b'n' => Esc::Read('\n' as i32),
+                        b'r' => Esc::Read('\r' as i32),
+                        b't' => Esc::Read('\t' as i32),
+                        b'v' => Esc::Read(0x0b),
+                        b'x' => Esc::Read(readhexaesc(ls)?),
+                        b'u' => { utf8esc(ls)?; Esc::No }
+                        b'\n' | b'\r' => {
+                            inclinenumber(ls)?;
+                            Esc::Only('\n' as i32)
+                        }
+                        b'\\' | b'"' | b'\'' => Esc::Read(c),
+                        b'z' => {  /* zap following span of spaces */
+                            ls.buffremove(1);  /* remove '\\' */
+                            ls.next();  /* skip the 'z' */
+                            while lisspace(ls.current()) {
+                                if curr_is_newline(ls) { inclinenumber(ls)?; }
+                                else { ls.next(); }
+                            }
+                            Esc::No
+                        }
+                        _ => {
+                            esccheck(ls, lisdigit(c), ErrorKind::InvalidEscapeSequence)?;
+                            Esc::Only(readdecesc(ls)?)  /* digital escape '\ddd' */
+                        }
+                    },
+                };
+                match esc {
+                    Esc::Read(c) => {
+                        ls.next();
+                        ls.buffremove(1);  /* remove '\\' */
+                        ls.save(c)?;
+                    }
+                    Esc::Only(c) => {
+                        ls.buffremove(1);  /* remove '\\' */
+                        ls.save(c)?;
+                    }
+                    Esc::No => {}
+                }
+            }
+            _ => save_and_next(ls)?,
+        }
+    }
+    save_and_next(ls)?;  /* skip delimiter */
+    let n = ls.buffer().len();
+    Ok(ls.newstring(1..n - 1))
+}
+
+
+pub(crate) fn llex<S: Scan>(ls: &mut S) -> Result<Lexeme<S::Str>, S::Error> {
+    ls.resetbuffer();
+    loop {
+        ls.token_start();
+        let c = ls.current();
+        if c == EOZ {
+            return Ok(Lexeme::Reserved(Reserved::Eos));
+        }
+        match c as u8 {
+            b'\n' | b'\r' => {  /* line breaks */
+                inclinenumber(ls)?;
+            }
+            b' ' | 0x0c | b'\t' | 0x0b => {  /* spaces */
+                ls.next();
+            }
+            b'-' => {  /* '-' or '--' (comment) */
+                ls.next();
+                if ls.current() != '-' as i32 { return Ok(Lexeme::Char(b'-')); }
+                /* else is a comment */
+                ls.next();
+                if ls.current() == '[' as i32 {  /* long comment? */
+                    let sep = skip_sep(ls)?;
+                    ls.resetbuffer();  /* 'skip_sep' may dirty the buffer */
+                    if sep >= 0 {
+                        read_long_string(ls, false, sep)?;  /* skip long comment */
+                        ls.resetbuffer();  /* previous call may dirty the buff. */
+                        ls.comment(Some(sep));
+                        continue;
+                    }
+                }
+                /* else short comment */
+                while !curr_is_newline(ls) && ls.current() != EOZ {
+                    ls.next();  /* skip until end of line (or end of file) */
+                }
+                ls.comment(None);
+            }
+            b'[' => {  /* long string or simply '[' */
+                let sep = skip_sep(ls)?;
+                if sep >= 0 {
+                    let s = read_long_string(ls, true, sep)?;
+                    return Ok(Lexeme::String(s.unwrap()));
+                } else if sep != -1 {  /* '[=...' missing second bracket */
+                    return Err(ls.error(ErrorKind::InvalidLongStringDelimiter, Near::Token));
+                }
+                return Ok(Lexeme::Char(b'['));
+            }
+            b'=' => {
+                ls.next();
+                return Ok(if check_next1(ls, b'=') { Lexeme::Reserved(Reserved::Eq) }
+                          else { Lexeme::Char(b'=') });
+            }
+            b'<' => {
+                ls.next();
+                return Ok(if check_next1(ls, b'=') { Lexeme::Reserved(Reserved::Le) }
+                          else if check_next1(ls, b'<') { Lexeme::Reserved(Reserved::Shl) }
+                          else { Lexeme::Char(b'<') });
+            }
+            b'>' => {
+                ls.next();
+                return Ok(if check_next1(ls, b'=') { Lexeme::Reserved(Reserved::Ge) }
+                          else if check_next1(ls, b'>') { Lexeme::Reserved(Reserved::Shr) }
+                          else { Lexeme::Char(b'>') });
+            }
+            b'/' => {
+                ls.next();
+                return Ok(if check_next1(ls, b'/') { Lexeme::Reserved(Reserved::IDiv) }
+                          else { Lexeme::Char(b'/') });
+            }
+            b'~' => {
+                ls.next();
+                return Ok(if check_next1(ls, b'=') { Lexeme::Reserved(Reserved::Ne) }
+                          else { Lexeme::Char(b'~') });
+            }
+            b':' => {
+                ls.next();
+                return Ok(if check_next1(ls, b':') { Lexeme::Reserved(Reserved::DbColon) }
+                          else { Lexeme::Char(b':') });
+            }
+            b'"' | b'\'' => {  /* short literal strings */
+                return Ok(Lexeme::String(read_string(ls, c)?));
+            }
+            b'.' => {  /* '.', '..', '...', or number */
+                save_and_next(ls)?;
+                if check_next1(ls, b'.') {
+                    return Ok(if check_next1(ls, b'.') { Lexeme::Reserved(Reserved::Dots) }  /* '...' */
+                              else { Lexeme::Reserved(Reserved::Concat) });  /* '..' */
+                } else if !lisdigit(ls.current()) {
+                    return Ok(Lexeme::Char(b'.'));
+                }
+                return read_numeral(ls);
+            }
+            b'0'..=b'9' => {
+                return read_numeral(ls);
+            }
+            _ => {
+                if lislalpha(c) {  /* identifier or reserved word? */
+                    loop {
+                        save_and_next(ls)?;
+                        if !lislalnum(ls.current()) { break; }
+                    }
+                    if let Some(r) = Reserved::keyword(ls.buffer()) {  /* reserved word? */
+                        return Ok(Lexeme::Reserved(r));
+                    }
+                    let n = ls.buffer().len();
+                    return Ok(Lexeme::Name(ls.newstring(0..n)));
+                }
+                /* single-char tokens (+ - / ...) */
+                ls.next();
+                return Ok(Lexeme::Char(c as u8));
+            }
+        }
+    }
+}
+
+/* }====================================================== */
+
+
+/// An iterator over the tokens of a Lua chunk, given as bytes.
+///
+/// It yields tokens until the end of the chunk or the first lexical
+/// error. Like the core, it does not skip a first line starting with `#`.
+/// The comments it skips are kept aside, see `take_comments`.
+pub struct Lexer<'a> {
+    source: &'a [u8],
+    name: String,  /* chunk name for error messages */
+    pos: usize,  /* position of 'current' in 'source' */
+    current: i32,
+    linenumber: i32,
+    buff: Vec<u8>,
+    start: usize,  /* position where the current token starts */
+    startline: i32,
+    comments: Vec<Comment>,
+    done: bool,
+}
+
+impl<'a> Lexer<'a> {
+    /// A lexer for `source`, whose errors refer to it as `?`.
+    pub fn new(source: &'a [u8]) -> Lexer<'a> {
+        Lexer::with_name(source, "?")
+    }
+
+    /// A lexer for `source`, whose errors refer to it as `name`.
+    pub fn with_name(source: &'a [u8], name: &str) -> Lexer<'a> {
+        Lexer {
+            source,
+            name: name.to_string(),
+            pos: 0,
+            current: source.first().map_or(EOZ, |&c| c as i32),
+            linenumber: 1,
+            buff: Vec::new(),
+            start: 0,
+            startline: 1,
+            comments: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// The current line, after the last token read.
+    pub fn line(&self) -> i32 { self.linenumber }
+
+    /// The comments skipped since the last call, in source order.
+    pub fn take_comments(&mut self) -> Vec<Comment> {
+        mem::take(&mut self.comments)
+    }
+
+    /* next token, or 'Eos' at the end of the chunk */
+    pub(crate) fn lex(&mut self) -> Result<Spanned, LexError> {
+        let token = match llex(self)? {
+            Lexeme::Char(c) => Token::Char(c),
+            Lexeme::Reserved(r) => Token::Reserved(r),
+            Lexeme::Flt(n) => Token::Flt(n),
+            Lexeme::Int(i) => Token::Int(i),
+            Lexeme::Name(s) => Token::Name(String::from_utf8(s).unwrap()),  /* names are ASCII */
+            Lexeme::String(s) => Token::String(s),
+        };
+        Ok(Spanned { token, span: self.start..self.pos, line: self.startline })
+    }
+
+    /* text of the last token read, as 'txtToken' shows it */
+    pub(crate) fn buffer_text(&self) -> String {
+        let n = self.buff.iter().position(|&c| c == 0).unwrap_or(self.buff.len());
+        String::from_utf8_lossy(&self.buff[..n]).into_owned()
+    }
+}
+
+impl<'a> Scan for Lexer<'a> {
+    type Str = Vec<u8>;
+    type Error = LexError;
+
+    fn current(&self) -> i32 { self.current }
+
+    fn next(&mut self) {
+        if self.pos < self.source.len() {
+            self.pos += 1;
+        }
+        self.current = self.source.get(self.pos).map_or(EOZ, |&c| c as i32);
+    }
+
+    fn save(&mut self, c: i32) -> Result<(), LexError> {
+        self.buff.push(c as u8);
+        Ok(())
+    }
+
+    fn buffer(&self) -> &[u8] { &self.buff }
+
+    fn buffremove(&mut self, n: usize) {
+        let len = self.buff.len();
+        self.buff.truncate(len - n);
+    }
+
+    fn resetbuffer(&mut self) { self.buff.clear(); }
+
+    fn linenumber(&mut self) -> &mut i32 { &mut self.linenumber }
+
+    fn newstring(&mut self, r: Range<usize>) -> Vec<u8> { self.buff[r].to_vec() }
+
+    fn error(&mut self, kind: ErrorKind, near: Near) -> LexError {
+        let near = match near {
+            Near::Nothing => None,
+            Near::Token => Some(format!("'{}'", self.buffer_text())),
+            Near::Eos => Some(Reserved::Eos.as_str().to_string()),
+        };
+        LexError { kind, source: self.name.clone(), line: self.linenumber, near }
+    }
+
+    fn token_start(&mut self) {
+        self.start = self.pos;
+        self.startline = self.linenumber;
+    }
+
+    fn comment(&mut self, sep: Option<i32>) {
+        let mut text = &self.source[self.start + 2..self.pos];  /* skip '--' */
+        if let Some(sep) = sep {
+            let sep = (2 + sep) as usize;
+            text = &text[sep..text.len() - sep];
+            text = match text {  /* skip first newline, as 'read_long_string' */
+                [b'\r', b'\n', rest @ ..] | [b'\n', b'\r', rest @ ..] => rest,
+                [b'\n', rest @ ..] | [b'\r', rest @ ..] => rest,
+                _ => text,
+            };
+        }
+        self.comments.push(Comment {
+            text: text.to_vec(),
+            span: self.start..self.pos,
+            line: self.startline,
+        });
+    }
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Result<Spanned, LexError>;
+
+    fn next(&mut self) -> Option<Result<Spanned, LexError>> {
+        if self.done {
+            return None;
+        }
+        match self.lex() {
+            Ok(Spanned { token: Token::Reserved(Reserved::Eos), .. }) => { self.done = true; None }
+            Err(e) => { self.done = true; Some(Err(e)) }
+            t => Some(t),
+        }
+    }
+}
@@ -0,0 +1,185 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Auxiliary functions to manipulate prototypes and closures.
+
+#![allow(non_snake_case)]
+
+use lgc::{luaC_newobj, luaC_upvalbarrier};
+use lmem::{luaM_free, luaM_freearray, luaM_new};
+use lobject::{getstr, setnilvalue, sizeCclosure, sizeLclosure, upisopen, CClosure, LClosure,
+              Proto, UpVal, LUA_TCCL, LUA_TLCL, LUA_TPROTO};
+use lstate::{isintwups, lua_State, StkId, G};
+use libc::{c_char, c_int, size_t};
+use std::mem;
+use std::ptr;
+
+
+/* the exported functions below, from 'lfunc.c' when it is linked instead (see 'build.rs') */
+#[cfg(not(rust_lfunc))]
+extern "C-unwind" {
+    pub fn luaF_newCclosure(L: *mut lua_State, n: c_int) -> *mut CClosure;
+    pub fn luaF_newLclosure(L: *mut lua_State, n: c_int) -> *mut LClosure;
+    pub fn luaF_initupvals(L: *mut lua_State, cl: *mut LClosure);
+    pub fn luaF_findupval(L: *mut lua_State, level: StkId) -> *mut UpVal;
+    pub fn luaF_close(L: *mut lua_State, level: StkId);
+    pub fn luaF_newproto(L: *mut lua_State) -> *mut Proto;
+    pub fn luaF_freeproto(L: *mut lua_State, f: *mut Proto);
+    pub fn luaF_getlocalname(f: *const Proto, local_number: c_int, pc: c_int) -> *const c_char;
+}
+
+
+/*
+** maximum number of upvalues in a closure (both C and Lua). (Value
+** must fit in a VM register.)
+*/
+pub const MAXUPVAL: c_int = 255;
+
+
+#[cfg(rust_lfunc)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaF_newCclosure(L: *mut lua_State, n: c_int) -> *mut CClosure {
+    let o = luaC_newobj(L, LUA_TCCL, sizeCclosure(n));
+    let c = o as *mut CClosure;
+    (*c).nupvalues = n as u8;
+    c
+}
+
+
+#[cfg(rust_lfunc)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaF_newLclosure(L: *mut lua_State, n: c_int) -> *mut LClosure {
+    let o = luaC_newobj(L, LUA_TLCL, sizeLclosure(n));
+    let c = o as *mut LClosure;
+    (*c).p = ptr::null_mut();
+    (*c).nupvalues = n as u8;
+    ptr::write_bytes((*c).upvals.as_mut_ptr(), 0, n as usize);
+    c
+}
+
+/*
+** fill a closure with new closed upvalues
+*/
+#[cfg(rust_lfunc)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaF_initupvals(L: *mut lua_State, cl: *mut LClosure) {
+    for i in 0..(*cl).nupvalues as usize {
+        let uv: *mut UpVal = luaM_new(L);
+        (*uv).refcount = 1;
+        (*uv).v = &mut (*uv).u.value;  /* make it closed */
+        setnilvalue((*uv).v);
+        *(*cl).upvals.as_mut_ptr().add(i) = uv;
+    }
+}
+
+
+#[cfg(rust_lfunc)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaF_findupval(L: *mut lua_State, level: StkId) -> *mut UpVal {
+    let mut pp: *mut *mut UpVal = &mut (*L).openupval;
+    debug_assert!(isintwups(L) || (*L).openupval.is_null());
+    while !(*pp).is_null() && (**pp).v >= level {
+        let p = *pp;
+        debug_assert!(upisopen(p));
+        if (*p).v == level {  /* found a corresponding upvalue? */
+            return p;  /* return it */
+        }
+        pp = &mut (*p).u.open.next;
+    }
+    /* not found: create a new upvalue */
+    let uv: *mut UpVal = luaM_new(L);
+    (*uv).refcount = 0;
+    (*uv).u.open.next = *pp;  /* link it to list of open upvalues */
+    (*uv).u.open.touched = 1;
+    *pp = uv;
+    (*uv).v = level;  /* current value lives in the stack */
+    if !isintwups(L) {  /* thread not in list of threads with upvalues? */
+        (*L).twups = (*G(L)).twups;  /* link it to the list */
+        (*G(L)).twups = L;
+    }
+    uv
+}
+
+
+#[cfg(rust_lfunc)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaF_close(L: *mut lua_State, level: StkId) {
+    while !(*L).openupval.is_null() && (*(*L).openupval).v >= level {
+        let uv = (*L).openupval;
+        debug_assert!(upisopen(uv));
+        (*L).openupval = (*uv).u.open.next;  /* remove from 'open' list */
+        if (*uv).refcount == 0 {  /* no references? */
+            luaM_free(L, uv);  /* free upvalue */
+        } else {
+            (*uv).u.value = *(*uv).v;  /* move value to upvalue slot */
+            (*uv).v = &mut (*uv).u.value;  /* now current value lives here */
+            luaC_upvalbarrier(L, uv);
+        }
+    }
+}
+
+
+#[cfg(rust_lfunc)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaF_newproto(L: *mut lua_State) -> *mut Proto {
+    let o = luaC_newobj(L, LUA_TPROTO, mem::size_of::<Proto>());
+    let f = o as *mut Proto;
+    (*f).k = ptr::null_mut();
+    (*f).sizek = 0;
+    (*f).p = ptr::null_mut();
+    (*f).sizep = 0;
+    (*f).code = ptr::null_mut();
+    (*f).cache = ptr::null_mut();
+    (*f).sizecode = 0;
+    (*f).lineinfo = ptr::null_mut();
+    (*f).sizelineinfo = 0;
+    (*f).upvalues = ptr::null_mut();
+    (*f).sizeupvalues = 0;
+    (*f).numparams = 0;
+    (*f).is_vararg = 0;
+    (*f).maxstacksize = 0;
+    (*f).locvars = ptr::null_mut();
+    (*f).sizelocvars = 0;
+    (*f).linedefined = 0;
+    (*f).lastlinedefined = 0;
+    (*f).source = ptr::null_mut();
+    f
+}
+
+
+#[cfg(rust_lfunc)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaF_freeproto(L: *mut lua_State, f: *mut Proto) {
+    luaM_freearray(L, (*f).code, (*f).sizecode as size_t);
+    luaM_freearray(L, (*f).p, (*f).sizep as size_t);
+    luaM_freearray(L, (*f).k, (*f).sizek as size_t);
+    luaM_freearray(L, (*f).lineinfo, (*f).sizelineinfo as size_t);
+    luaM_freearray(L, (*f).locvars, (*f).sizelocvars as size_t);
+    luaM_freearray(L, (*f).upvalues, (*f).sizeupvalues as size_t);
+    luaM_free(L, f);
+}
+
+
+/*
+** Look for n-th local variable at line 'line' in function 'func'.
+** Returns NULL if not found.
+*/
+#[cfg(rust_lfunc)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaF_getlocalname(f: *const Proto, mut local_number: c_int,
+                                                  pc: c_int) -> *const c_char {
+    for i in 0..(*f).sizelocvars as usize {
+        let var = &*(*f).locvars.add(i);
+        if var.startpc > pc { break; }
+        if pc < var.endpc {  /* is variable active? */
+            local_number -= 1;
+            if local_number == 0 {
+                return getstr(var.varname);
+            }
+        }
+    }
+    ptr::null()  /* not found */
+}
@@ -0,0 +1,1392 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Garbage Collector.
+
+#![allow(non_camel_case_types, non_snake_case, non_upper_case_globals)]
+
+use ffi::lua::{self, LUA_ERRGCMM, LUA_ERRRUN};
+use ldo::{luaD_callnoyield, luaD_shrinkstack, luaD_throw, pcall, savestack};
+use lfunc::luaF_freeproto;
+use lmem::{luaM_free, luaM_freemem, luaM_realloc_};
+use lobject::{getuservalue, luaO_pushfstring, setdeadvalue, setgcovalue, setnilvalue,
+              sizeCclosure, sizeLclosure, sizelstring, sizeudata, svalue, upisopen, CClosure,
+              GCObject, LClosure, LocVar, Node, Proto, TString, TValue, Table, Udata, UpVal,
+              Upvaldesc, LUA_TCCL, LUA_TLCL, LUA_TLNGSTR, LUA_TPROTO, LUA_TSHRSTR};
+use lopcodes::Instruction;
+use lstate::{gettotalbytes, global_State, isintwups, luaE_freethread, luaE_setdebt, lua_State,
+             CallInfo, G, KGC_EMERGENCY, KGC_NORMAL};
+use lstring::{luaS_clearcache, luaS_remove, luaS_resize};
+use ltable::{gkey, gnode, gval, luaH_free, sizenode, ttisdeadkey, wgkey};
+use ltm::{gfasttm, luaT_gettmbyobj, TMS};
+use libc::{c_int, size_t, strchr};
+use std::mem;
+use std::ptr;
+
+
+/* the exported functions below, from 'lgc.c' when it is linked instead (see 'build.rs') */
+#[cfg(not(rust_lgc))]
+extern "C-unwind" {
+    pub fn luaC_barrier_(L: *mut lua_State, o: *mut GCObject, v: *mut GCObject);
+    pub fn luaC_barrierback_(L: *mut lua_State, t: *mut Table);
+    pub fn luaC_upvalbarrier_(L: *mut lua_State, uv: *mut UpVal);
+    pub fn luaC_fix(L: *mut lua_State, o: *mut GCObject);
+    pub fn luaC_newobj(L: *mut lua_State, tt: c_int, sz: size_t) -> *mut GCObject;
+    pub fn luaC_upvdeccount(L: *mut lua_State, uv: *mut UpVal);
+    pub fn luaC_checkfinalizer(L: *mut lua_State, o: *mut GCObject, mt: *mut Table);
+    pub fn luaC_freeallobjects(L: *mut lua_State);
+    pub fn luaC_runtilstate(L: *mut lua_State, statesmask: c_int);
+    pub fn luaC_step(L: *mut lua_State);
+    pub fn luaC_fullgc(L: *mut lua_State, isemergency: c_int);
+}
+
+
+type lu_mem = size_t;
+type l_mem = isize;
+
+const MAX_LUMEM: lu_mem = lu_mem::MAX;
+pub const MAX_LMEM: l_mem = l_mem::MAX;
+
+
+/*
+** Collectable objects may have one of three colors: white, which
+** means the object is not marked; gray, which means the
+** object is marked, but its references may be not marked; and
+** black, which means that the object and all its references are marked.
+** The main invariant of the garbage collector, while marking objects,
+** is that a black object can never point to a white one. Moreover,
+** any gray object must be in a "gray list" (gray, grayagain, weak,
+** allweak, ephemeron) so that it can be visited again before finishing
+** the collection cycle. These lists have no meaning when the invariant
+** is not being enforced (e.g., sweep phase).
+*/
+
+
+/* how much to allocate before next GC step */
+pub const GCSTEPSIZE: l_mem = 100 * mem::size_of::<TString>() as l_mem;
+
+
+/*
+** Possible states of the Garbage Collector
+*/
+pub const GCSpropagate: u8 = 0;
+pub const GCSatomic: u8 = 1;
+pub const GCSswpallgc: u8 = 2;
+pub const GCSswpfinobj: u8 = 3;
+pub const GCSswptobefnz: u8 = 4;
+pub const GCSswpend: u8 = 5;
+pub const GCScallfin: u8 = 6;
+pub const GCSpause: u8 = 7;
+
+
+pub unsafe fn issweepphase(g: *const global_State) -> bool {
+    GCSswpallgc <= (*g).gcstate && (*g).gcstate <= GCSswpend
+}
+
+
+/*
+** macro to tell when main invariant (white objects cannot point to black
+** ones) must be kept. During a collection, the sweep
+** phase may break the invariant, as objects turned white may point to
+** still-black objects. The invariant is restored when sweep ends and
+** all objects are white again.
+*/
+pub unsafe fn keepinvariant(g: *const global_State) -> bool { (*g).gcstate <= GCSatomic }
+
+
+/*
+** Layout for bit use in 'marked' field:
+** bit 0 - object is white (type 0)
+** bit 1 - object is white (type 1)
+** bit 2 - object is black
+** bit 3 - for userdata: has been marked for finalization
+** bit 7 - object is fixed (should not be collected)
+*/
+
+pub const WHITE0BIT: u8 = 0;  /* object is white (type 0) */
+pub const WHITE1BIT: u8 = 1;  /* object is white (type 1) */
+pub const BLACKBIT: u8 = 2;  /* object is black */
+pub const FINALIZEDBIT: u8 = 3;  /* object has been marked for finalization */
+/* bit 7 is currently used by tests (luaL_checkmemory) */
+
+pub const WHITEBITS: u8 = (1 << WHITE0BIT) | (1 << WHITE1BIT);
+
+
+pub unsafe fn iswhite(x: *const GCObject) -> bool { (*x).marked & WHITEBITS != 0 }
+pub unsafe fn isblack(x: *const GCObject) -> bool { (*x).marked & (1 << BLACKBIT) != 0 }
+pub unsafe fn isgray(x: *const GCObject) -> bool {  /* neither white nor black */
+    (*x).marked & (WHITEBITS | (1 << BLACKBIT)) == 0
+}
+
+pub unsafe fn tofinalize(x: *const GCObject) -> bool { (*x).marked & (1 << FINALIZEDBIT) != 0 }
+
+pub unsafe fn otherwhite(g: *const global_State) -> u8 { (*g).currentwhite ^ WHITEBITS }
+pub fn isdeadm(ow: u8, m: u8) -> bool { (m ^ WHITEBITS) & ow == 0 }
+pub unsafe fn isdead(g: *const global_State, v: *const GCObject) -> bool {
+    isdeadm(otherwhite(g), (*v).marked)
+}
+
+pub unsafe fn gray2black(x: *mut GCObject) { (*x).marked |= 1 << BLACKBIT; }
+
+pub unsafe fn luaC_white(g: *const global_State) -> u8 { (*g).currentwhite & WHITEBITS }
+
+
+/*
+** Does one step of collection when debt becomes positive.
+*/
+pub unsafe fn luaC_checkGC(L: *mut lua_State) {
+    if (*G(L)).GCdebt > 0 {
+        luaC_step(L);
+    }
+}
+
+
+pub unsafe fn luaC_barrier(L: *mut lua_State, p: *mut GCObject, v: *const TValue) {
+    if (*v).iscollectable() && isblack(p) && iswhite((*v).value_.gc) {
+        luaC_barrier_(L, p, (*v).value_.gc);
+    }
+}
+
+
+pub unsafe fn luaC_objbarrier(L: *mut lua_State, p: *mut GCObject, o: *mut GCObject) {
+    if isblack(p) && iswhite(o) {
+        luaC_barrier_(L, p, o);
+    }
+}
+
+
+pub unsafe fn luaC_barrierback(L: *mut lua_State, p: *mut Table, v: *const TValue) {
+    if (*v).iscollectable() && isblack(p as *mut GCObject) && iswhite((*v).value_.gc) {
+        luaC_barrierback_(L, p);
+    }
+}
+
+
+pub unsafe fn luaC_upvalbarrier(L: *mut lua_State, uv: *mut UpVal) {
+    if (*(*uv).v).iscollectable() && !upisopen(uv) {
+        luaC_upvalbarrier_(L, uv);
+    }
+}
+
+
+/*
+** internal state for collector while inside the atomic phase. The
+** collector should never be in this state while running regular code.
+*/
+const GCSinsideatomic: u8 = GCSpause + 1;
+
+/*
+** cost of sweeping one element (the size of a small object divided
+** by some adjust for the sweep speed)
+*/
+const GCSWEEPCOST: lu_mem = (mem::size_of::<TString>() + 4) / 4;
+
+/* maximum number of elements to sweep in each single step */
+const GCSWEEPMAX: lu_mem = (GCSTEPSIZE as lu_mem / GCSWEEPCOST) / 4;
+
+/* cost of calling one finalizer */
+const GCFINALIZECOST: lu_mem = GCSWEEPCOST;
+
+
+/*
+** macro to adjust 'stepmul': 'stepmul' is actually used like
+** 'stepmul / STEPMULADJ' (value chosen by tests)
+*/
+const STEPMULADJ: l_mem = 200;
+
+
+/*
+** macro to adjust 'pause': 'pause' is actually used like
+** 'pause / PAUSEADJ' (value chosen by tests)
+*/
+const PAUSEADJ: l_mem = 100;
+
+
+/*
+** 'makewhite' erases all color bits then sets only the current white
+** bit
+*/
+const maskcolors: u8 = !((1 << BLACKBIT) | WHITEBITS);
+
+unsafe fn makewhite(g: *const global_State, x: *mut GCObject) {
+    (*x).marked = ((*x).marked & maskcolors) | luaC_white(g);
+}
+
+unsafe fn white2gray(x: *mut GCObject) { (*x).marked &= !WHITEBITS; }
+unsafe fn black2gray(x: *mut GCObject) { (*x).marked &= !(1 << BLACKBIT); }
+
+
+unsafe fn valiswhite(x: *const TValue) -> bool { (*x).iscollectable() && iswhite((*x).gcvalue()) }
+
+unsafe fn checkdeadkey(n: *mut Node) {
+    debug_assert!(!ttisdeadkey(gkey(n)) || (*gval(n)).ttisnil());
+}
+
+
+fn obj2gco<T>(v: *mut T) -> *mut GCObject { v as *mut GCObject }
+
+
+unsafe fn markvalue(g: *mut global_State, o: *const TValue) {
+    if valiswhite(o) {
+        reallymarkobject(g, (*o).gcvalue());
+    }
+}
+
+unsafe fn markobject<T>(g: *mut global_State, t: *mut T) {
+    if iswhite(obj2gco(t)) {
+        reallymarkobject(g, obj2gco(t));
+    }
+}
+
+/*
+** mark an object that can be NULL (either because it is really optional,
+** or it was stripped as debug info, or inside an uncompleted structure)
+*/
+unsafe fn markobjectN<T>(g: *mut global_State, t: *mut T) {
+    if !t.is_null() {
+        markobject(g, t);
+    }
+}
+
+
+/*
+** {======================================================
+** Generic functions
+** =======================================================
+*/
+
+
+/*
+** one after last element in a hash array
+*/
+unsafe fn gnodelast(h: *const Table) -> *mut Node { gnode(h, sizenode(h) as usize) }
+
+
+/*
+** link collectable object 'o' into list pointed by 'p'
+*/
+unsafe fn linkgclist(o: *mut GCObject, gclist: *mut *mut GCObject, p: &mut *mut GCObject) {
+    *gclist = *p;
+    *p = o;
+}
+
+/* the 'gclist' field of the objects that are kept in gray lists */
+unsafe fn gclist(o: *mut GCObject) -> *mut *mut GCObject {
+    match (*o).tt as c_int {
+        lua::LUA_TTABLE => &mut (*(o as *mut Table)).gclist,
+        LUA_TLCL => &mut (*(o as *mut LClosure)).gclist,
+        LUA_TCCL => &mut (*(o as *mut CClosure)).gclist,
+        lua::LUA_TTHREAD => &mut (*(o as *mut lua_State)).gclist,
+        LUA_TPROTO => &mut (*(o as *mut Proto)).gclist,
+        _ => unreachable!(),
+    }
+}
+
+unsafe fn linkgray(o: *mut GCObject, p: &mut *mut GCObject) { linkgclist(o, gclist(o), p) }
+
+
+/*
+** If key is not marked, mark its entry as dead. This allows key to be
+** collected, but keeps its entry in the table.  A dead node is needed
+** when Lua looks up for a key (it may be part of a chain) and when
+** traversing a weak table (key might be removed from the table during
+** traversal). Other places never manipulate dead keys, because its
+** associated nil value is enough to signal that the entry is logically
+** empty.
+*/
+unsafe fn removeentry(n: *mut Node) {
+    debug_assert!((*gval(n)).ttisnil());
+    if valiswhite(gkey(n)) {
+        setdeadvalue(wgkey(n));  /* unused and unmarked key; remove it */
+    }
+}
+
+
+/*
+** tells whether a key or value can be cleared from a weak
+** table. Non-collectable objects are never removed from weak
+** tables. Strings behave as 'values', so are never removed too. for
+** other objects: if really collected, cannot keep them; for objects
+** being finalized, keep them in keys, but not in values
+*/
+unsafe fn iscleared(g: *mut global_State, o: *const TValue) -> bool {
+    if !(*o).iscollectable() {
+        false
+    } else if (*o).ttisstring() {
+        markobject(g, (*o).tsvalue());  /* strings are 'values', so are never weak */
+        false
+    } else {
+        iswhite((*o).gcvalue())
+    }
+}
+
+
+/*
+** barrier that moves collector forward, that is, mark the white object
+** being pointed by a black object. (If in sweep phase, clear the black
+** object to white [sweep it] to avoid other barrier calls for this
+** same object.)
+*/
+#[cfg(rust_lgc)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaC_barrier_(L: *mut lua_State, o: *mut GCObject, v: *mut GCObject) {
+    let g = G(L);
+    debug_assert!(isblack(o) && iswhite(v) && !isdead(g, v) && !isdead(g, o));
+    if keepinvariant(g) {  /* must keep invariant? */
+        reallymarkobject(g, v);  /* restore invariant */
+    } else {  /* sweep phase */
+        debug_assert!(issweepphase(g));
+        makewhite(g, o);  /* mark main obj. as white to avoid other barriers */
+    }
+}
+
+
+/*
+** barrier that moves collector backward, that is, mark the black object
+** pointing to a white object as gray again.
+*/
+#[cfg(rust_lgc)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaC_barrierback_(L: *mut lua_State, t: *mut Table) {
+    let g = G(L);
+    debug_assert!(isblack(obj2gco(t)) && !isdead(g, obj2gco(t)));
+    black2gray(obj2gco(t));  /* make table gray (again) */
+    linkgclist(obj2gco(t), &mut (*t).gclist, &mut (*g).grayagain);
+}
+
+
+/*
+** barrier for assignments to closed upvalues. Because upvalues are
+** shared among closures, it is impossible to know the color of all
+** closures pointing to it. So, we assume that the object being assigned
+** must be marked.
+*/
+#[cfg(rust_lgc)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaC_upvalbarrier_(L: *mut lua_State, uv: *mut UpVal) {
+    let g = G(L);
+    let o = (*(*uv).v).gcvalue();
+    debug_assert!(!upisopen(uv));  /* ensured by macro luaC_upvalbarrier */
+    if keepinvariant(g) {
+        markobject(g, o);
+    }
+}
+
+
+#[cfg(rust_lgc)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaC_fix(L: *mut lua_State, o: *mut GCObject) {
+    let g = G(L);
+    debug_assert!((*g).allgc == o);  /* object must be 1st in 'allgc' list! */
+    white2gray(o);  /* they will be gray forever */
+    (*g).allgc = (*o).next;  /* remove object from 'allgc' list */
+    (*o).next = (*g).fixedgc;  /* link it to 'fixedgc' list */
+    (*g).fixedgc = o;
+}
+
+
+/*
+** create a new collectable object (with given type and size) and link
+** it to 'allgc' list.
+*/
+#[cfg(rust_lgc)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaC_newobj(L: *mut lua_State, tt: c_int, sz: size_t) -> *mut GCObject {
+    let g = G(L);
+    /* 'luaM_newobject': the "old size" of a new block tells its type */
+    let o = luaM_realloc_(L, ptr::null_mut(), (tt & 0x0F) as size_t, sz) as *mut GCObject;
+    (*o).marked = luaC_white(g);
+    (*o).tt = tt as u8;
+    (*o).next = (*g).allgc;
+    (*g).allgc = o;
+    o
+}
+
+/* }====================================================== */
+
+
+
+/*
+** {======================================================
+** Mark functions
+** =======================================================
+*/
+
+
+/*
+** mark an object. Userdata, strings, and closed upvalues are visited
+** and turned black here. Other objects are marked gray and added
+** to appropriate list to be visited (and turned black) later. (Open
+** upvalues are already linked in 'headuv' list.)
+*/
+unsafe fn reallymarkobject(g: *mut global_State, mut o: *mut GCObject) {
+    loop {
+        white2gray(o);
+        match (*o).tt as c_int {
+            LUA_TSHRSTR => {
+                gray2black(o);
+                (*g).GCmemtrav += sizelstring((*(o as *mut TString)).shrlen as size_t);
+            }
+            LUA_TLNGSTR => {
+                gray2black(o);
+                (*g).GCmemtrav += sizelstring((*(o as *mut TString)).u.lnglen);
+            }
+            lua::LUA_TUSERDATA => {
+                let u = o as *mut Udata;
+                let mut uvalue = TValue::nil();
+                markobjectN(g, (*u).metatable);  /* mark its metatable */
+                gray2black(o);
+                (*g).GCmemtrav += sizeudata(u);
+                getuservalue(u, &mut uvalue);
+                if valiswhite(&uvalue) {  /* markvalue(g, &uvalue); */
+                    o = uvalue.gcvalue();
+                    continue;
+                }
+            }
+            LUA_TLCL | LUA_TCCL | lua::LUA_TTABLE | lua::LUA_TTHREAD | LUA_TPROTO => {
+                linkgray(o, &mut (*g).gray);
+            }
+            _ => debug_assert!(false),
+        }
+        return;
+    }
+}
+
+
+/*
+** mark metamethods for basic types
+*/
+unsafe fn markmt(g: *mut global_State) {
+    for i in 0..lua::LUA_NUMTAGS as usize {
+        markobjectN(g, (*g).mt[i]);
+    }
+}
+
+
+/*
+** mark all objects in list of being-finalized
+*/
+unsafe fn markbeingfnz(g: *mut global_State) {
+    let mut o = (*g).tobefnz;
+    while !o.is_null() {
+        markobject(g, o);
+        o = (*o).next;
+    }
+}
+
+
+/*
+** Mark all values stored in marked open upvalues from non-marked threads.
+** (Values from marked threads were already marked when traversing the
+** thread.) Remove from the list threads that no longer have upvalues and
+** not-marked threads.
+*/
+unsafe fn remarkupvals(g: *mut global_State) {
+    let mut p: *mut *mut lua_State = &mut (*g).twups;
+    while !(*p).is_null() {
+        let thread = *p;
+        debug_assert!(!isblack(obj2gco(thread)));  /* threads are never black */
+        if isgray(obj2gco(thread)) && !(*thread).openupval.is_null() {
+            p = &mut (*thread).twups;  /* keep marked thread with upvalues in the list */
+        } else {  /* thread is not marked or without upvalues */
+            *p = (*thread).twups;  /* remove thread from the list */
+            (*thread).twups = thread;  /* mark that it is out of list */
+            let mut uv = (*thread).openupval;
+            while !uv.is_null() {
+                if (*uv).u.open.touched != 0 {
+                    markvalue(g, (*uv).v);  /* remark upvalue's value */
+                    (*uv).u.open.touched = 0;
+                }
+                uv = (*uv).u.open.next;
+            }
+        }
+    }
+}
+
+
+/*
+** mark root set and reset all gray lists, to start a new collection
+*/
+unsafe fn restartcollection(g: *mut global_State) {
+    (*g).gray = ptr::null_mut();
+    (*g).grayagain = ptr::null_mut();
+    (*g).weak = ptr::null_mut();
+    (*g).allweak = ptr::null_mut();
+    (*g).ephemeron = ptr::null_mut();
+    markobject(g, (*g).mainthread);
+    markvalue(g, &(*g).l_registry);
+    markmt(g);
+    markbeingfnz(g);  /* mark any finalizing object left from previous cycle */
+}
+
+/* }====================================================== */
+
+
+/*
+** {======================================================
+** Traverse functions
+** =======================================================
+*/
+
+/*
+** Traverse a table with weak values and link it to proper list. During
+** propagate phase, keep it in 'grayagain' list, to be revisited in the
+** atomic phase. In the atomic phase, if table has any white value,
+** put it in 'weak' list, to be cleared.
+*/
+unsafe fn traverseweakvalue(g: *mut global_State, h: *mut Table) {
+    let limit = gnodelast(h);
+    /* if there is array part, assume it may have white values (it is not
+       worth traversing it now just to check) */
+    let mut hasclears = (*h).sizearray > 0;
+    let mut n = gnode(h, 0);
+    while n < limit {  /* traverse hash part */
+        checkdeadkey(n);
+        if (*gval(n)).ttisnil() {  /* entry is empty? */
+            removeentry(n);  /* remove it */
+        } else {
+            debug_assert!(!(*gkey(n)).ttisnil());
+            markvalue(g, gkey(n));  /* mark key */
+            if !hasclears && iscleared(g, gval(n)) {  /* is there a white value? */
+                hasclears = true;  /* table will have to be cleared */
+            }
+        }
+        n = n.add(1);
+    }
+    if (*g).gcstate == GCSpropagate {
+        /* must retraverse it in atomic phase */
+        linkgclist(obj2gco(h), &mut (*h).gclist, &mut (*g).grayagain);
+    } else if hasclears {
+        /* has to be cleared later */
+        linkgclist(obj2gco(h), &mut (*h).gclist, &mut (*g).weak);
+    }
+}
+
+
+/*
+** Traverse an ephemeron table and link it to proper list. Returns true
+** iff any object was marked during this traversal (which implies that
+** convergence has to continue). During propagation phase, keep table
+** in 'grayagain' list, to be visited again in the atomic phase. In
+** the atomic phase, if table has any white->white entry, it has to
+** be revisited during ephemeron convergence (as that key may turn
+** black). Otherwise, if it has any white key, table has to be cleared
+** (in the atomic phase).
+*/
+unsafe fn traverseephemeron(g: *mut global_State, h: *mut Table) -> bool {
+    let mut marked = false;  /* true if an object is marked in this traversal */
+    let mut hasclears = false;  /* true if table has white keys */
+    let mut hasww = false;  /* true if table has entry "white-key -> white-value" */
+    let limit = gnodelast(h);
+    /* traverse array part */
+    for i in 0..(*h).sizearray as usize {
+        let o = (*h).array.add(i);
+        if valiswhite(o) {
+            marked = true;
+            reallymarkobject(g, (*o).gcvalue());
+        }
+    }
+    /* traverse hash part */
+    let mut n = gnode(h, 0);
+    while n < limit {
+        checkdeadkey(n);
+        if (*gval(n)).ttisnil() {  /* entry is empty? */
+            removeentry(n);  /* remove it */
+        } else if iscleared(g, gkey(n)) {  /* key is not marked (yet)? */
+            hasclears = true;  /* table must be cleared */
+            if valiswhite(gval(n)) {  /* value not marked yet? */
+                hasww = true;  /* white-white entry */
+            }
+        } else if valiswhite(gval(n)) {  /* value not marked yet? */
+            marked = true;
+            reallymarkobject(g, (*gval(n)).gcvalue());  /* mark it now */
+        }
+        n = n.add(1);
+    }
+    /* link table into proper list */
+    if (*g).gcstate == GCSpropagate {
+        /* must retraverse it in atomic phase */
+        linkgclist(obj2gco(h), &mut (*h).gclist, &mut (*g).grayagain);
+    } else if hasww {  /* table has white->white entries? */
+        /* have to propagate again */
+        linkgclist(obj2gco(h), &mut (*h).gclist, &mut (*g).ephemeron);
+    } else if hasclears {  /* table has white keys? */
+        /* may have to clean white keys */
+        linkgclist(obj2gco(h), &mut (*h).gclist, &mut (*g).allweak);
+    }
+    marked
+}
+
+
+unsafe fn traversestrongtable(g: *mut global_State, h: *mut Table) {
+    let limit = gnodelast(h);
+    for i in 0..(*h).sizearray as usize {  /* traverse array part */
+        markvalue(g, (*h).array.add(i));
+    }
+    let mut n = gnode(h, 0);
+    while n < limit {  /* traverse hash part */
+        checkdeadkey(n);
+        if (*gval(n)).ttisnil() {  /* entry is empty? */
+            removeentry(n);  /* remove it */
+        } else {
+            debug_assert!(!(*gkey(n)).ttisnil());
+            markvalue(g, gkey(n));  /* mark key */
+            markvalue(g, gval(n));  /* mark value */
+        }
+        n = n.add(1);
+    }
+}
+
+
+unsafe fn traversetable(g: *mut global_State, h: *mut Table) -> lu_mem {
+    let mode = gfasttm(g, (*h).metatable, TMS::TM_MODE);
+    markobjectN(g, (*h).metatable);
+    let (weakkey, weakvalue) = if !mode.is_null() && (*mode).ttisstring() {
+        (!strchr(svalue(mode), b'k' as c_int).is_null(),
+         !strchr(svalue(mode), b'v' as c_int).is_null())
+    } else {
+        (false, false)
+    };
+    if weakkey || weakvalue {  /* is really weak? */
+        black2gray(obj2gco(h));  /* keep table gray */
+        if !weakkey {  /* strong keys? */
+            traverseweakvalue(g, h);
+        } else if !weakvalue {  /* strong values? */
+            traverseephemeron(g, h);
+        } else {  /* all weak */
+            /* nothing to traverse now */
+            linkgclist(obj2gco(h), &mut (*h).gclist, &mut (*g).allweak);
+        }
+    } else {  /* not weak */
+        traversestrongtable(g, h);
+    }
+    mem::size_of::<Table>() + mem::size_of::<TValue>() * (*h).sizearray as size_t +
+                              mem::size_of::<Node>() * sizenode(h) as size_t
+}
+
+
+/*
+** Traverse a prototype. (While a prototype is being build, its
+** arrays can be larger than needed; the extra slots are filled with
+** NULL, so the use of 'markobjectN')
+*/
+unsafe fn traverseproto(g: *mut global_State, f: *mut Proto) -> lu_mem {
+    if !(*f).cache.is_null() && iswhite(obj2gco((*f).cache)) {
+        (*f).cache = ptr::null_mut();  /* allow cache to be collected */
+    }
+    markobjectN(g, (*f).source);
+    for i in 0..(*f).sizek as usize {  /* mark literals */
+        markvalue(g, (*f).k.add(i));
+    }
+    for i in 0..(*f).sizeupvalues as usize {  /* mark upvalue names */
+        markobjectN(g, (*(*f).upvalues.add(i)).name);
+    }
+    for i in 0..(*f).sizep as usize {  /* mark nested protos */
+        markobjectN(g, *(*f).p.add(i));
+    }
+    for i in 0..(*f).sizelocvars as usize {  /* mark local-variable names */
+        markobjectN(g, (*(*f).locvars.add(i)).varname);
+    }
+    mem::size_of::<Proto>() + mem::size_of::<Instruction>() * (*f).sizecode as size_t +
+                              mem::size_of::<*mut Proto>() * (*f).sizep as size_t +
+                              mem::size_of::<TValue>() * (*f).sizek as size_t +
+                              mem::size_of::<c_int>() * (*f).sizelineinfo as size_t +
+                              mem::size_of::<LocVar>() * (*f).sizelocvars as size_t +
+                              mem::size_of::<Upvaldesc>() * (*f).sizeupvalues as size_t
+}
+
+
+unsafe fn traverseCclosure(g: *mut global_State, cl: *mut CClosure) -> lu_mem {
+    let upvalue = (*cl).upvalue.as_mut_ptr();
+    for i in 0..(*cl).nupvalues as usize {  /* mark its upvalues */
+        markvalue(g, upvalue.add(i));
+    }
+    sizeCclosure((*cl).nupvalues as c_int)
+}
+
+/*
+** open upvalues point to values in a thread, so those values should
+** be marked when the thread is traversed except in the atomic phase
+** (because then the value cannot be changed by the thread and the
+** thread may not be traversed again)
+*/
+unsafe fn traverseLclosure(g: *mut global_State, cl: *mut LClosure) -> lu_mem {
+    markobjectN(g, (*cl).p);  /* mark its prototype */
+    let upvals = (*cl).upvals.as_mut_ptr();
+    for i in 0..(*cl).nupvalues as usize {  /* mark its upvalues */
+        let uv = *upvals.add(i);
+        if !uv.is_null() {
+            if upisopen(uv) && (*g).gcstate != GCSinsideatomic {
+                (*uv).u.open.touched = 1;  /* can be marked in 'remarkupvals' */
+            } else {
+                markvalue(g, (*uv).v);
+            }
+        }
+    }
+    sizeLclosure((*cl).nupvalues as c_int)
+}
+
+
+unsafe fn traversethread(g: *mut global_State, th: *mut lua_State) -> lu_mem {
+    let mut o = (*th).stack;
+    if o.is_null() {
+        return 1;  /* stack not completely built yet */
+    }
+    debug_assert!((*g).gcstate == GCSinsideatomic ||
+                  (*th).openupval.is_null() || isintwups(th));
+    while o < (*th).top {  /* mark live elements in the stack */
+        markvalue(g, o);
+        o = o.add(1);
+    }
+    if (*g).gcstate == GCSinsideatomic {  /* final traversal? */
+        let lim = (*th).stack.add((*th).stacksize as usize);  /* real end of stack */
+        while o < lim {  /* clear not-marked stack slice */
+            setnilvalue(o);
+            o = o.add(1);
+        }
+        /* 'remarkupvals' may have removed thread from 'twups' list */
+        if !isintwups(th) && !(*th).openupval.is_null() {
+            (*th).twups = (*g).twups;  /* link it back to the list */
+            (*g).twups = th;
+        }
+    } else if (*g).gckind != KGC_EMERGENCY {
+        luaD_shrinkstack(th);  /* do not change stack in emergency cycle */
+    }
+    mem::size_of::<lua_State>() + mem::size_of::<TValue>() * (*th).stacksize as size_t +
+                                  mem::size_of::<CallInfo>() * (*th).nci as size_t
+}
+
+
+/*
+** traverse one gray object, turning it to black (except for threads,
+** which are always gray).
+*/
+unsafe fn propagatemark(g: *mut global_State) {
+    let o = (*g).gray;
+    debug_assert!(isgray(o));
+    gray2black(o);
+    (*g).gray = *gclist(o);  /* remove from 'gray' list */
+    let size = match (*o).tt as c_int {
+        lua::LUA_TTABLE => traversetable(g, o as *mut Table),
+        LUA_TLCL => traverseLclosure(g, o as *mut LClosure),
+        LUA_TCCL => traverseCclosure(g, o as *mut CClosure),
+        lua::LUA_TTHREAD => {
+            let th = o as *mut lua_State;
+            /* insert into 'grayagain' list */
+            linkgclist(o, &mut (*th).gclist, &mut (*g).grayagain);
+            black2gray(o);
+            traversethread(g, th)
+        }
+        LUA_TPROTO => traverseproto(g, o as *mut Proto),
+        _ => { debug_assert!(false); return; }
+    };
+    (*g).GCmemtrav += size;
+}
+
+
+unsafe fn propagateall(g: *mut global_State) {
+    while !(*g).gray.is_null() {
+        propagatemark(g);
+    }
+}
+
+
+unsafe fn convergeephemerons(g: *mut global_State) {
+    loop {
+        let mut next = (*g).ephemeron;  /* get ephemeron list */
+        (*g).ephemeron = ptr::null_mut();  /* tables may return to this list when traversed */
+        let mut changed = false;
+        while !next.is_null() {
+            let w = next as *mut Table;
+            next = (*w).gclist;
+            if traverseephemeron(g, w) {  /* traverse marked some value? */
+                propagateall(g);  /* propagate changes */
+                changed = true;  /* will have to revisit all ephemeron tables */
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+}
+
+/* }====================================================== */
+
+
+/*
+** {======================================================
+** Sweep Functions
+** =======================================================
+*/
+
+
+/*
+** clear entries with unmarked keys from all weaktables in list 'l' up
+** to element 'f'
+*/
+unsafe fn clearkeys(g: *mut global_State, mut l: *mut GCObject, f: *mut GCObject) {
+    while l != f {
+        let h = l as *mut Table;
+        let limit = gnodelast(h);
+        let mut n = gnode(h, 0);
+        while n < limit {
+            if !(*gval(n)).ttisnil() && iscleared(g, gkey(n)) {
+                setnilvalue(gval(n));  /* remove value ... */
+                removeentry(n);  /* and remove entry from table */
+            }
+            n = n.add(1);
+        }
+        l = (*h).gclist;
+    }
+}
+
+
+/*
+** clear entries with unmarked values from all weaktables in list 'l' up
+** to element 'f'
+*/
+unsafe fn clearvalues(g: *mut global_State, mut l: *mut GCObject, f: *mut GCObject) {
+    while l != f {
+        let h = l as *mut Table;
+        let limit = gnodelast(h);
+        for i in 0..(*h).sizearray as usize {
+            let o = (*h).array.add(i);
+            if iscleared(g, o) {  /* value was collected? */
+                setnilvalue(o);  /* remove value */
+            }
+        }
+        let mut n = gnode(h, 0);
+        while n < limit {
+            if !(*gval(n)).ttisnil() && iscleared(g, gval(n)) {
+                setnilvalue(gval(n));  /* remove value ... */
+                removeentry(n);  /* and remove entry from table */
+            }
+            n = n.add(1);
+        }
+        l = (*h).gclist;
+    }
+}
+
+
+#[cfg(rust_lgc)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaC_upvdeccount(L: *mut lua_State, uv: *mut UpVal) {
+    debug_assert!((*uv).refcount > 0);
+    (*uv).refcount -= 1;
+    if (*uv).refcount == 0 && !upisopen(uv) {
+        luaM_free(L, uv);
+    }
+}
+
+
+unsafe fn freeLclosure(L: *mut lua_State, cl: *mut LClosure) {
+    let upvals = (*cl).upvals.as_mut_ptr();
+    for i in 0..(*cl).nupvalues as usize {
+        let uv = *upvals.add(i);
+        if !uv.is_null() {
+            luaC_upvdeccount(L, uv);
+        }
+    }
+    luaM_freemem(L, cl, sizeLclosure((*cl).nupvalues as c_int));
+}
+
+
+unsafe fn freeobj(L: *mut lua_State, o: *mut GCObject) {
+    match (*o).tt as c_int {
+        LUA_TPROTO => luaF_freeproto(L, o as *mut Proto),
+        LUA_TLCL => freeLclosure(L, o as *mut LClosure),
+        LUA_TCCL => luaM_freemem(L, o, sizeCclosure((*(o as *mut CClosure)).nupvalues as c_int)),
+        lua::LUA_TTABLE => luaH_free(L, o as *mut Table),
+        lua::LUA_TTHREAD => luaE_freethread(L, o as *mut lua_State),
+        lua::LUA_TUSERDATA => luaM_freemem(L, o, sizeudata(o as *mut Udata)),
+        LUA_TSHRSTR => {
+            luaS_remove(L, o as *mut TString);  /* remove it from hash table */
+            luaM_freemem(L, o, sizelstring((*(o as *mut TString)).shrlen as size_t));
+        }
+        LUA_TLNGSTR => luaM_freemem(L, o, sizelstring((*(o as *mut TString)).u.lnglen)),
+        _ => debug_assert!(false),
+    }
+}
+
+
+unsafe fn sweepwholelist(L: *mut lua_State, p: *mut *mut GCObject) {
+    sweeplist(L, p, MAX_LUMEM);
+}
+
+
+/*
+** sweep at most 'count' elements from a list of GCObjects erasing dead
+** objects, where a dead object is one marked with the old (non current)
+** white; change all non-dead objects back to white, preparing for next
+** collection cycle. Return where to continue the traversal or NULL if
+** list is finished.
+*/
+unsafe fn sweeplist(L: *mut lua_State, mut p: *mut *mut GCObject,
+                    mut count: lu_mem) -> *mut *mut GCObject {
+    let g = G(L);
+    let ow = otherwhite(g);
+    let white = luaC_white(g);  /* current white */
+    while !(*p).is_null() && count > 0 {
+        count -= 1;
+        let curr = *p;
+        let marked = (*curr).marked;
+        if isdeadm(ow, marked) {  /* is 'curr' dead? */
+            *p = (*curr).next;  /* remove 'curr' from list */
+            freeobj(L, curr);  /* erase 'curr' */
+        } else {  /* change mark to 'white' */
+            (*curr).marked = (marked & maskcolors) | white;
+            p = &mut (*curr).next;  /* go to next element */
+        }
+    }
+    if (*p).is_null() { ptr::null_mut() } else { p }
+}
+
+
+/*
+** sweep a list until a live object (or end of list)
+*/
+unsafe fn sweeptolive(L: *mut lua_State, mut p: *mut *mut GCObject) -> *mut *mut GCObject {
+    let old = p;
+    loop {
+        p = sweeplist(L, p, 1);
+        if p != old {
+            return p;
+        }
+    }
+}
+
+/* }====================================================== */
+
+
+/*
+** {======================================================
+** Finalization
+** =======================================================
+*/
+
+/*
+** If possible, shrink string table
+*/
+unsafe fn checkSizes(L: *mut lua_State, g: *mut global_State) {
+    if (*g).gckind != KGC_EMERGENCY {
+        let olddebt = (*g).GCdebt;
+        if (*g).strt.nuse < (*g).strt.size / 4 {  /* string table too big? */
+            luaS_resize(L, (*g).strt.size / 2);  /* shrink it a little */
+        }
+        /* update estimate */
+        (*g).GCestimate = (*g).GCestimate.wrapping_add(((*g).GCdebt - olddebt) as size_t);
+    }
+}
+
+
+unsafe fn udata2finalize(g: *mut global_State) -> *mut GCObject {
+    let o = (*g).tobefnz;  /* get first element */
+    debug_assert!(tofinalize(o));
+    (*g).tobefnz = (*o).next;  /* remove it from 'tobefnz' list */
+    (*o).next = (*g).allgc;  /* return it to 'allgc' list */
+    (*g).allgc = o;
+    (*o).marked &= !(1 << FINALIZEDBIT);  /* object is "normal" again */
+    if issweepphase(g) {
+        makewhite(g, o);  /* "sweep" object */
+    }
+    o
+}
+
+
+unsafe fn GCTM(L: *mut lua_State, propagateerrors: bool) {
+    let g = G(L);
+    let mut v = TValue::nil();
+    setgcovalue(&mut v, udata2finalize(g));
+    let tm = luaT_gettmbyobj(L, &v, TMS::TM_GC);
+    if !tm.is_null() && (*tm).ttisfunction() {  /* is there a finalizer? */
+        let oldah = (*L).allowhook;
+        let running = (*g).gcrunning;
+        (*L).allowhook = 0;  /* stop debug hooks during GC metamethod */
+        (*g).gcrunning = 0;  /* avoid GC steps */
+        *(*L).top = *tm;  /* push finalizer... */
+        *(*L).top.add(1) = v;  /* ... and its argument */
+        (*L).top = (*L).top.add(2);  /* and (next line) call the finalizer */
+        let r = pcall(L, || luaD_callnoyield(L, (*L).top.sub(2), 0),
+                      savestack(L, (*L).top.sub(2)), 0);
+        (*L).allowhook = oldah;  /* restore hooks */
+        (*g).gcrunning = running;  /* restore state */
+        if let (Err(mut status), true) = (r, propagateerrors) {  /* error while running __gc? */
+            if status == LUA_ERRRUN {  /* is there an error object? */
+                let msg = if (*(*L).top.sub(1)).ttisstring() {
+                    svalue((*L).top.sub(1))
+                } else {
+                    c_str!("no message")
+                };
+                luaO_pushfstring(L, c_str!("error in __gc metamethod (%s)"), msg);
+                status = LUA_ERRGCMM;  /* error in __gc metamethod */
+            }
+            luaD_throw(L, status);  /* re-throw error */
+        }
+    }
+}
+
+
+/*
+** call a few (up to 'g->gcfinnum') finalizers
+*/
+unsafe fn runafewfinalizers(L: *mut lua_State) -> c_int {
+    let g = G(L);
+    debug_assert!((*g).tobefnz.is_null() || (*g).gcfinnum > 0);
+    let mut i = 0;
+    while !(*g).tobefnz.is_null() && i < (*g).gcfinnum {
+        GCTM(L, true);  /* call one finalizer */
+        i += 1;
+    }
+    (*g).gcfinnum = if (*g).tobefnz.is_null() { 0 }  /* nothing more to finalize? */
+                    else { (*g).gcfinnum * 2 };  /* else call a few more next time */
+    i as c_int
+}
+
+
+/*
+** call all pending finalizers
+*/
+unsafe fn callallpendingfinalizers(L: *mut lua_State) {
+    let g = G(L);
+    while !(*g).tobefnz.is_null() {
+        GCTM(L, false);
+    }
+}
+
+
+/*
+** find last 'next' field in list 'p' list (to add elements in its end)
+*/
+unsafe fn findlast(mut p: *mut *mut GCObject) -> *mut *mut GCObject {
+    while !(*p).is_null() {
+        p = &mut (**p).next;
+    }
+    p
+}
+
+
+/*
+** move all unreachable objects (or 'all' objects) that need
+** finalization from list 'finobj' to list 'tobefnz' (to be finalized)
+*/
+unsafe fn separatetobefnz(g: *mut global_State, all: bool) {
+    let mut p: *mut *mut GCObject = &mut (*g).finobj;
+    let mut lastnext = findlast(&mut (*g).tobefnz);
+    while !(*p).is_null() {  /* traverse all finalizable objects */
+        let curr = *p;
+        debug_assert!(tofinalize(curr));
+        if !(iswhite(curr) || all) {  /* not being collected? */
+            p = &mut (*curr).next;  /* don't bother with it */
+        } else {
+            *p = (*curr).next;  /* remove 'curr' from 'finobj' list */
+            (*curr).next = *lastnext;  /* link at the end of 'tobefnz' list */
+            *lastnext = curr;
+            lastnext = &mut (*curr).next;
+        }
+    }
+}
+
+
+/*
+** if object 'o' has a finalizer, remove it from 'allgc' list (must
+** search the list to find it) and link it in 'finobj' list.
+*/
+#[cfg(rust_lgc)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaC_checkfinalizer(L: *mut lua_State, o: *mut GCObject, mt: *mut Table) {
+    let g = G(L);
+    if tofinalize(o) ||  /* obj. is already marked... */
+       gfasttm(g, mt, TMS::TM_GC).is_null() {  /* or has no finalizer? */
+        return;  /* nothing to be done */
+    }
+    /* move 'o' to 'finobj' list */
+    if issweepphase(g) {
+        makewhite(g, o);  /* "sweep" object 'o' */
+        /* should not remove 'sweepgc' object */
+        if ptr::eq((*g).sweepgc, &(*o).next) {
+            (*g).sweepgc = sweeptolive(L, (*g).sweepgc);  /* change 'sweepgc' */
+        }
+    }
+    /* search for pointer pointing to 'o' */
+    let mut p: *mut *mut GCObject = &mut (*g).allgc;
+    while *p != o {
+        p = &mut (**p).next;
+    }
+    *p = (*o).next;  /* remove 'o' from 'allgc' list */
+    (*o).next = (*g).finobj;  /* link it in 'finobj' list */
+    (*g).finobj = o;
+    (*o).marked |= 1 << FINALIZEDBIT;  /* mark it as such */
+}
+
+/* }====================================================== */
+
+
+
+/*
+** {======================================================
+** GC control
+** =======================================================
+*/
+
+
+/*
+** Set a reasonable "time" to wait before starting a new GC cycle; cycle
+** will start when memory use hits threshold. (Division by 'estimate'
+** should be OK: it cannot be zero (because Lua cannot even start with
+** less than PAUSEADJ bytes).
+*/
+unsafe fn setpause(g: *mut global_State) {
+    let estimate = (*g).GCestimate as l_mem / PAUSEADJ;  /* adjust 'estimate' */
+    debug_assert!(estimate > 0);
+    let threshold = if ((*g).gcpause as l_mem) < MAX_LMEM / estimate {  /* overflow? */
+        estimate * (*g).gcpause as l_mem  /* no overflow */
+    } else {
+        MAX_LMEM  /* overflow; truncate to maximum */
+    };
+    let debt = gettotalbytes(g) as l_mem - threshold;
+    luaE_setdebt(g, debt);
+}
+
+
+/*
+** Enter first sweep phase.
+** The call to 'sweeplist' tries to make pointer point to an object
+** inside the list (instead of to the header), so that the real sweep do
+** not need to skip objects created between "now" and the start of the
+** real sweep.
+*/
+unsafe fn entersweep(L: *mut lua_State) {
+    let g = G(L);
+    (*g).gcstate = GCSswpallgc;
+    debug_assert!((*g).sweepgc.is_null());
+    (*g).sweepgc = sweeplist(L, &mut (*g).allgc, 1);
+}
+
+
+#[cfg(rust_lgc)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaC_freeallobjects(L: *mut lua_State) {
+    let g = G(L);
+    separatetobefnz(g, true);  /* separate all objects with finalizers */
+    debug_assert!((*g).finobj.is_null());
+    callallpendingfinalizers(L);
+    debug_assert!((*g).tobefnz.is_null());
+    (*g).currentwhite = WHITEBITS;  /* this "white" makes all objects look dead */
+    (*g).gckind = KGC_NORMAL;
+    sweepwholelist(L, &mut (*g).finobj);
+    sweepwholelist(L, &mut (*g).allgc);
+    sweepwholelist(L, &mut (*g).fixedgc);  /* collect fixed objects */
+    debug_assert!((*g).strt.nuse == 0);
+}
+
+
+unsafe fn atomic(L: *mut lua_State) -> l_mem {
+    let g = G(L);
+    let grayagain = (*g).grayagain;  /* save original list */
+    debug_assert!((*g).ephemeron.is_null() && (*g).weak.is_null());
+    debug_assert!(!iswhite(obj2gco((*g).mainthread)));
+    (*g).gcstate = GCSinsideatomic;
+    (*g).GCmemtrav = 0;  /* start counting work */
+    markobject(g, L);  /* mark running thread */
+    /* registry and global metatables may be changed by API */
+    markvalue(g, &(*g).l_registry);
+    markmt(g);  /* mark global metatables */
+    /* remark occasional upvalues of (maybe) dead threads */
+    remarkupvals(g);
+    propagateall(g);  /* propagate changes */
+    let mut work = (*g).GCmemtrav as l_mem;  /* stop counting (do not recount 'grayagain') */
+    (*g).gray = grayagain;
+    propagateall(g);  /* traverse 'grayagain' list */
+    (*g).GCmemtrav = 0;  /* restart counting */
+    convergeephemerons(g);
+    /* at this point, all strongly accessible objects are marked. */
+    /* Clear values from weak tables, before checking finalizers */
+    clearvalues(g, (*g).weak, ptr::null_mut());
+    clearvalues(g, (*g).allweak, ptr::null_mut());
+    let origweak = (*g).weak;
+    let origall = (*g).allweak;
+    work += (*g).GCmemtrav as l_mem;  /* stop counting (objects being finalized) */
+    separatetobefnz(g, false);  /* separate objects to be finalized */
+    (*g).gcfinnum = 1;  /* there may be objects to be finalized */
+    markbeingfnz(g);  /* mark objects that will be finalized */
+    propagateall(g);  /* remark, to propagate 'resurrection' */
+    (*g).GCmemtrav = 0;  /* restart counting */
+    convergeephemerons(g);
+    /* at this point, all resurrected objects are marked. */
+    /* remove dead objects from weak tables */
+    clearkeys(g, (*g).ephemeron, ptr::null_mut());  /* clear keys from all ephemeron tables */
+    clearkeys(g, (*g).allweak, ptr::null_mut());  /* clear keys from all 'allweak' tables */
+    /* clear values from resurrected weak tables */
+    clearvalues(g, (*g).weak, origweak);
+    clearvalues(g, (*g).allweak, origall);
+    luaS_clearcache(g);
+    (*g).currentwhite = otherwhite(g);  /* flip current white */
+    work += (*g).GCmemtrav as l_mem;  /* complete counting */
+    work  /* estimate of memory marked by 'atomic' */
+}
+
+
+unsafe fn sweepstep(L: *mut lua_State, g: *mut global_State,
+                    nextstate: u8, nextlist: *mut *mut GCObject) -> lu_mem {
+    if !(*g).sweepgc.is_null() {
+        let olddebt = (*g).GCdebt;
+        (*g).sweepgc = sweeplist(L, (*g).sweepgc, GCSWEEPMAX);
+        /* update estimate */
+        (*g).GCestimate = (*g).GCestimate.wrapping_add(((*g).GCdebt - olddebt) as size_t);
+        if !(*g).sweepgc.is_null() {  /* is there still something to sweep? */
+            return GCSWEEPMAX * GCSWEEPCOST;
+        }
+    }
+    /* else enter next state */
+    (*g).gcstate = nextstate;
+    (*g).sweepgc = nextlist;
+    0
+}
+
+
+unsafe fn singlestep(L: *mut lua_State) -> lu_mem {
+    let g = G(L);
+    match (*g).gcstate {
+        GCSpause => {
+            (*g).GCmemtrav = (*g).strt.size as size_t * mem::size_of::<*mut GCObject>();
+            restartcollection(g);
+            (*g).gcstate = GCSpropagate;
+            (*g).GCmemtrav
+        }
+        GCSpropagate => {
+            (*g).GCmemtrav = 0;
+            debug_assert!(!(*g).gray.is_null());
+            propagatemark(g);
+            if (*g).gray.is_null() {  /* no more gray objects? */
+                (*g).gcstate = GCSatomic;  /* finish propagate phase */
+            }
+            (*g).GCmemtrav  /* memory traversed in this step */
+        }
+        GCSatomic => {
+            propagateall(g);  /* make sure gray list is empty */
+            let work = atomic(L);  /* work is what was traversed by 'atomic' */
+            entersweep(L);
+            (*g).GCestimate = gettotalbytes(g);  /* first estimate */
+            work as lu_mem
+        }
+        GCSswpallgc => {  /* sweep "regular" objects */
+            sweepstep(L, g, GCSswpfinobj, &mut (*g).finobj)
+        }
+        GCSswpfinobj => {  /* sweep objects with finalizers */
+            sweepstep(L, g, GCSswptobefnz, &mut (*g).tobefnz)
+        }
+        GCSswptobefnz => {  /* sweep objects to be finalized */
+            sweepstep(L, g, GCSswpend, ptr::null_mut())
+        }
+        GCSswpend => {  /* finish sweeps */
+            makewhite(g, obj2gco((*g).mainthread));  /* sweep main thread */
+            checkSizes(L, g);
+            (*g).gcstate = GCScallfin;
+            0
+        }
+        GCScallfin => {  /* call remaining finalizers */
+            if !(*g).tobefnz.is_null() && (*g).gckind != KGC_EMERGENCY {
+                let n = runafewfinalizers(L);
+                n as lu_mem * GCFINALIZECOST
+            } else {  /* emergency mode or no more finalizers */
+                (*g).gcstate = GCSpause;  /* finish collection */
+                0
+            }
+        }
+        _ => { debug_assert!(false); 0 }
+    }
+}
+
+
+/*
+** advances the garbage collector until it reaches a state allowed
+** by 'statemask'
+*/
+#[cfg(rust_lgc)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaC_runtilstate(L: *mut lua_State, statesmask: c_int) {
+    while statesmask & (1 << (*G(L)).gcstate) == 0 {
+        singlestep(L);
+    }
+}
+
+
+/*
+** get GC debt and convert it from Kb to 'work units' (avoid zero debt
+** and overflows)
+*/
+unsafe fn getdebt(g: *mut global_State) -> l_mem {
+    let mut debt = (*g).GCdebt;
+    let stepmul = (*g).gcstepmul as l_mem;
+    if debt <= 0 {
+        0  /* minimal debt */
+    } else {
+        debt = (debt / STEPMULADJ) + 1;
+        if debt < MAX_LMEM / stepmul { debt * stepmul } else { MAX_LMEM }
+    }
+}
+
+/*
+** performs a basic GC step when collector is running
+*/
+#[cfg(rust_lgc)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaC_step(L: *mut lua_State) {
+    let g = G(L);
+    let mut debt = getdebt(g);  /* GC deficit (be paid now) */
+    if (*g).gcrunning == 0 {  /* not running? */
+        luaE_setdebt(g, -GCSTEPSIZE * 10);  /* avoid being called too often */
+        return;
+    }
+    loop {  /* repeat until pause or enough "credit" (negative debt) */
+        let work = singlestep(L);  /* perform one single step */
+        debt -= work as l_mem;
+        if !(debt > -GCSTEPSIZE && (*g).gcstate != GCSpause) {
+            break;
+        }
+    }
+    if (*g).gcstate == GCSpause {
+        setpause(g);  /* pause until next cycle */
+    } else {
+        debt = (debt / (*g).gcstepmul as l_mem) * STEPMULADJ;  /* convert 'work units' to Kb */
+        luaE_setdebt(g, debt);
+        runafewfinalizers(L);
+    }
+}
+
+
+/*
+** Performs a full GC cycle; if 'isemergency', set a flag to avoid
+** some operations which could change the interpreter state in some
+** unexpected ways (running finalizers and shrinking some structures).
+** Before running the collection, check 'keepinvariant'; if it is true,
+** there may be some objects marked as black, so the collector has
+** to sweep all objects to turn them back to white (as white has not
+** changed, nothing will be collected).
+*/
+#[cfg(rust_lgc)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaC_fullgc(L: *mut lua_State, isemergency: c_int) {
+    let g = G(L);
+    debug_assert!((*g).gckind == KGC_NORMAL);
+    if isemergency != 0 {
+        (*g).gckind = KGC_EMERGENCY;  /* set flag */
+    }
+    if keepinvariant(g) {  /* black objects? */
+        entersweep(L);  /* sweep everything to turn them back to white */
+    }
+    /* finish any pending sweep phase to start a new cycle */
+    luaC_runtilstate(L, 1 << GCSpause);
+    luaC_runtilstate(L, !(1 << GCSpause));  /* start new collection */
+    luaC_runtilstate(L, 1 << GCScallfin);  /* run up to finalizers */
+    /* estimate must be correct after a full GC cycle */
+    debug_assert!((*g).GCestimate == gettotalbytes(g));
+    luaC_runtilstate(L, 1 << GCSpause);  /* finish collection */
+    (*g).gckind = KGC_NORMAL;
+    setpause(g);
+}
+
+/* }====================================================== */
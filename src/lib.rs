@@ -12,8 +12,97 @@
 
 extern crate libc;
 
+/// Produces a pointer to a nul-terminated C string from a string literal.
+macro_rules! c_str {
+    ($s:expr) => { concat!($s, "\0").as_ptr() as *const ::libc::c_char }
+}
+
+/// Pushes a string formatted as by `lua_pushfstring` onto the stack of a
+/// `*mut lua_State`, taking the arguments as `FArg`s instead of C varargs.
+/// The compiler only checks that each argument converts to an `FArg`; an
+/// argument missing or of the wrong kind for its option raises a Lua error
+/// when the string is formatted.
+///
+/// `push_fstring!(L, "%s: %d", "answer", 42)` pushes "answer: 42".
+#[macro_export]
+macro_rules! push_fstring {
+    ($L:expr, $fmt:expr $(, $arg:expr)*) => {
+        $crate::ffi::lua::lua_pushfargs($L, $fmt, &[$($crate::ffi::lua::FArg::from($arg)),*])
+    }
+}
+
 pub mod ffi;
 
+mod host;
+#[cfg_attr(not(rust_ldblib), allow(dead_code))]  /* the values of threads are for 'ldblib' */
+mod userdata;
+/*
+** the ports of 'puc-lua/src': the functions a module exports are only
+** defined when 'build.rs' links its port instead of the C one, and are
+** declared from C otherwise; the rest (its types and the inline parts of
+** its header) is always there, for the other ports. The parts only used
+** by the port itself, or by 'lvm', may go unused.
+*/
+#[cfg_attr(not(rust_lapi), allow(dead_code, unused_imports))]
+mod lapi;
+#[cfg_attr(not(rust_lcode), allow(dead_code, unused_imports))]
+mod lcode;
+#[cfg_attr(not(rust_ldebug), allow(dead_code, unused_imports))]
+mod ldebug;
+#[cfg_attr(not(rust_ldo), allow(dead_code, unused_imports))]
+mod ldo;
+#[cfg_attr(not(rust_ldump), allow(dead_code, unused_imports))]
+mod ldump;
+#[cfg_attr(not(rust_lfunc), allow(dead_code, unused_imports))]
+mod lfunc;
+#[cfg_attr(not(all(rust_lgc, rust_lvm)), allow(dead_code, unused_imports))]
+mod lgc;
+#[cfg_attr(not(rust_llex), allow(dead_code, unused_imports))]
+mod llex;
+#[cfg_attr(not(all(rust_lobject, rust_lvm)), allow(dead_code, unused_imports))]
+mod lobject;
+#[cfg_attr(not(rust_lopcodes), allow(dead_code, unused_imports))]
+mod lopcodes;
+#[cfg_attr(not(rust_lparser), allow(dead_code, unused_imports))]
+mod lparser;
+#[cfg_attr(not(all(rust_lstate, rust_lvm)), allow(dead_code, unused_imports))]
+mod lstate;
+#[cfg_attr(not(rust_lstring), allow(dead_code, unused_imports))]
+mod lstring;
+#[cfg_attr(not(rust_ltable), allow(dead_code, unused_imports))]
+mod ltable;
+#[cfg_attr(not(all(rust_ltm, rust_lvm)), allow(dead_code, unused_imports))]
+mod ltm;
+#[cfg_attr(not(rust_lundump), allow(dead_code, unused_imports))]
+mod lundump;
+#[cfg(rust_lvm)]
+mod lvm;
+/* bindings to, and inline parts of, modules still linked from C */
+mod lctype;
+mod lmem;
+mod lzio;
+
+#[cfg(rust_lbaselib)]
+pub mod lbaselib;
+#[cfg(rust_lcorolib)]
+pub mod lcorolib;
+#[cfg(rust_lauxlib)]
+pub mod lauxlib;
+pub mod ast;
+pub mod bytecode;
+#[cfg(rust_ldblib)]
+pub mod ldblib;
+pub mod lexer;
+#[cfg(rust_liolib)]
+pub mod liolib;
+#[cfg(rust_loadlib)]
+pub mod loadlib;
+#[cfg(rust_loslib)]
+pub mod loslib;
+pub mod opcodes;
+#[cfg(rust_lutf8lib)]
+pub mod lutf8lib;
+
 #[cfg(test)]
 mod test {
 
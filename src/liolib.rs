@@ -0,0 +1,1138 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Standard I/O (and system) library.
+//!
+//! File handles are implemented over `std::fs::File` and `std::process`,
+//! with buffering done on the Rust side. Handles still use the
+//! `LUA_FILEHANDLE` metatable and start with a `luaL_Stream`, whose
+//! `closef` closes them as in C; its `f` is always null, as there is no
+//! C stream under them. Embedders can give Lua their own files with
+//! `push_file`.
+
+use ffi::lua::{self, lua_State, lua_Integer};
+use ffi::lauxlib;
+use host;
+use libc::{self, c_char, c_int, size_t};
+use std::ffi::CStr;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::process;
+use std::{mem, ptr, slice};
+
+
+/// A file that can be handed to Lua with `push_file`.
+pub trait Stream: Read + Write + Seek {}
+
+impl<T: Read + Write + Seek> Stream for T {}
+
+
+/*
+** {======================================================
+** Buffered file streams
+** =======================================================
+*/
+
+/* what lies under a file handle */
+enum Handle {
+    File(fs::File),  /* regular files and 'tmpfile' */
+    Stdin,  /* standard files are never closed */
+    Stdout,
+    Stderr,
+    Pipe(process::Child),  /* 'popen' files */
+    Virtual(Box<dyn Stream>),  /* files given by the embedder */
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum BufMode { No, Full, Line }
+
+/* a file, with its buffers; data in 'rbuf[rpos..]' was read but not consumed */
+struct LFile {
+    handle: Handle,
+    rbuf: Vec<u8>,
+    rpos: usize,
+    wbuf: Vec<u8>,
+    mode: BufMode,
+    size: usize,
+    readable: bool,
+    writable: bool,
+}
+
+
+fn ebadf() -> io::Error { io::Error::from_raw_os_error(libc::EBADF) }
+fn espipe() -> io::Error { io::Error::from_raw_os_error(libc::ESPIPE) }
+
+
+impl Handle {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            Handle::File(ref mut f) => f.read(buf),
+            Handle::Stdin => io::stdin().read(buf),
+            Handle::Pipe(ref mut c) => match c.stdout {
+                Some(ref mut out) => out.read(buf),
+                None => Err(ebadf()),
+            },
+            Handle::Virtual(ref mut v) => v.read(buf),
+            Handle::Stdout | Handle::Stderr => Err(ebadf()),
+        }
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        match *self {
+            Handle::File(ref mut f) => f.write_all(buf),
+            Handle::Stdout => io::stdout().write_all(buf),
+            Handle::Stderr => io::stderr().write_all(buf),
+            Handle::Pipe(ref mut c) => match c.stdin {
+                Some(ref mut input) => input.write_all(buf),
+                None => Err(ebadf()),
+            },
+            Handle::Virtual(ref mut v) => v.write_all(buf),
+            Handle::Stdin => Err(ebadf()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            Handle::File(ref mut f) => f.flush(),
+            Handle::Stdout => io::stdout().flush(),
+            Handle::Stderr => io::stderr().flush(),
+            Handle::Pipe(ref mut c) => match c.stdin {
+                Some(ref mut input) => input.flush(),
+                None => Ok(()),
+            },
+            Handle::Virtual(ref mut v) => v.flush(),
+            Handle::Stdin => Ok(()),
+        }
+    }
+
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match *self {
+            Handle::File(ref mut f) => f.seek(pos),
+            Handle::Virtual(ref mut v) => v.seek(pos),
+            _ => Err(espipe()),
+        }
+    }
+}
+
+
+impl LFile {
+    fn new(handle: Handle, mode: BufMode, readable: bool, writable: bool) -> LFile {
+        LFile { handle, rbuf: Vec::new(), rpos: 0, wbuf: Vec::new(),
+                mode, size: BUFFERSIZE, readable, writable }
+    }
+
+    /* write pending output, before reading or moving */
+    fn flushw(&mut self) -> io::Result<()> {
+        if !self.wbuf.is_empty() {
+            let res = self.handle.write_all(&self.wbuf);
+            self.wbuf.clear();
+            res?;
+        }
+        Ok(())
+    }
+
+    /* give back input read ahead, before writing */
+    fn unread(&mut self) {
+        let n = self.rbuf.len() - self.rpos;
+        if n > 0 {
+            let _ = self.handle.seek(SeekFrom::Current(-(n as i64)));
+        }
+        self.rbuf.clear();
+        self.rpos = 0;
+    }
+
+    /* make sure there is input available; false means end of file */
+    fn fill(&mut self) -> io::Result<bool> {
+        if self.rpos < self.rbuf.len() {
+            return Ok(true);
+        }
+        if !self.readable {
+            return Err(ebadf());
+        }
+        self.flushw()?;
+        let size = self.size.clamp(1, MAXREADAHEAD);
+        self.rbuf.resize(size, 0);
+        self.rpos = 0;
+        loop {
+            match self.handle.read(&mut self.rbuf) {
+                Ok(n) => { self.rbuf.truncate(n); return Ok(n > 0); }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => { self.rbuf.clear(); return Err(e); }
+            }
+        }
+    }
+
+    fn peek(&mut self) -> io::Result<Option<u8>> {
+        Ok(if self.fill()? { Some(self.rbuf[self.rpos]) } else { None })
+    }
+
+    /* read up to 'n' bytes into 'b'; returns how many were read */
+    fn read(&mut self, b: &mut Vec<u8>, n: usize) -> io::Result<usize> {
+        let mut nr = 0;
+        while nr < n && self.fill()? {
+            let avail = &self.rbuf[self.rpos..];
+            let m = ::std::cmp::min(avail.len(), n - nr);
+            b.extend_from_slice(&avail[..m]);
+            self.rpos += m;
+            nr += m;
+        }
+        Ok(nr)
+    }
+
+    fn write(&mut self, s: &[u8]) -> io::Result<()> {
+        if !self.writable {
+            return Err(ebadf());
+        }
+        self.unread();
+        match self.mode {
+            BufMode::No => { self.flushw()?; self.handle.write_all(s) }
+            BufMode::Full | BufMode::Line => {
+                self.wbuf.extend_from_slice(s);
+                if self.wbuf.len() >= self.size ||
+                        (self.mode == BufMode::Line && s.contains(&b'\n')) {
+                    self.flushw()?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flushw()?;
+        self.handle.flush()
+    }
+
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.flushw()?;
+        let pos = match pos {  /* account for input read ahead */
+            SeekFrom::Current(off) => SeekFrom::Current(off - (self.rbuf.len() - self.rpos) as i64),
+            p => p,
+        };
+        let res = self.handle.seek(pos);
+        if res.is_ok() {
+            self.rbuf.clear();
+            self.rpos = 0;
+        }
+        res
+    }
+
+    fn setvbuf(&mut self, mode: BufMode, size: usize) -> io::Result<()> {
+        self.flushw()?;
+        self.mode = mode;
+        self.size = size;
+        Ok(())
+    }
+}
+
+
+/* default size of file buffers (as 'BUFSIZ') */
+const BUFFERSIZE: usize = 8192;
+
+/* most input read ahead at once, whatever the size of the buffer */
+const MAXREADAHEAD: usize = 16 * BUFFERSIZE;
+
+/* }====================================================== */
+
+
+const LUA_FILEHANDLE: *const c_char = c_str!("FILE*");
+
+const IO_INPUT: *const c_char = c_str!("_IO_input");
+const IO_OUTPUT: *const c_char = c_str!("_IO_output");
+const IOPREF_LEN: usize = 4;  /* length of "_IO_" */
+
+/* key, in the registry, for the (weak) table with all open files */
+const IO_FILES: *const c_char = c_str!("_IO_files");
+
+/* key, in the registry, for the handle of the standard output */
+const IO_STDOUT: *const c_char = c_str!("_IO_stdout");
+
+
+/*
+** Contents of a file handle: a 'luaL_Stream', for C code that reaches
+** into handles, followed by the file; 'None' marks a closed file, as
+** does a NULL 'closef'.
+*/
+#[repr(C)]
+struct LStream {
+    c: lauxlib::luaL_Stream,
+    f: Option<LFile>,
+}
+
+
+impl LStream {
+    fn open(&mut self, f: LFile) {
+        self.f = Some(f);
+        self.c.closef = Some(aux_close);
+    }
+
+    fn take(&mut self) -> Option<LFile> {
+        self.c.closef = None;
+        self.f.take()
+    }
+}
+
+
+unsafe fn tolstream<'a>(l: *mut lua_State) -> &'a mut LStream {
+    let p = lauxlib::luaL_checkudata(l, 1, LUA_FILEHANDLE);
+    if lua::lua_rawlen(l, 1) != mem::size_of::<LStream>() {  /* a 'luaL_Stream' made by C code? */
+        lauxlib::luaL_argerror(l, 1, c_str!("file not created by the io library"));
+    }
+    &mut *(p as *mut LStream)
+}
+
+
+/*
+** Pushes the results of a file operation: true, or nil plus an error
+** message and code, as 'luaL_fileresult' does.
+*/
+unsafe fn fileresult(l: *mut lua_State, res: io::Result<()>, fname: *const c_char) -> c_int {
+    match res {
+        Ok(()) => {
+            lua::lua_pushboolean(l, 1);
+            1
+        }
+        Err(e) => {
+            lua::lua_pushnil(l);
+            if !fname.is_null() {
+                lua::lua_pushstring(l, fname);
+                lua::lua_pushstring(l, c_str!(": "));
+                pusherror(l, &e);
+                lua::lua_concat(l, 3);
+            } else {
+                pusherror(l, &e);
+            }
+            lua::lua_pushinteger(l, e.raw_os_error().unwrap_or(0) as lua_Integer);
+            3
+        }
+    }
+}
+
+/* pushes the message of error 'e', using 'strerror' for system errors */
+unsafe fn pusherror(l: *mut lua_State, e: &io::Error) {
+    match e.raw_os_error() {
+        Some(code) => { lua::lua_pushstring(l, libc::strerror(code)); }
+        None => {
+            let msg = e.to_string();
+            lua::lua_pushlstring(l, msg.as_ptr() as *const c_char, msg.len());
+        }
+    }
+}
+
+
+unsafe extern "C-unwind" fn io_type(l: *mut lua_State) -> c_int {
+    lauxlib::luaL_checkany(l, 1);
+    let p = lauxlib::luaL_testudata(l, 1, LUA_FILEHANDLE) as *mut LStream;
+    if p.is_null() {
+        lua::lua_pushnil(l);  /* not a file */
+    } else if (*p).c.closef.is_none() {
+        lua::lua_pushstring(l, c_str!("closed file"));
+    } else {
+        lua::lua_pushstring(l, c_str!("file"));
+    }
+    1
+}
+
+
+unsafe extern "C-unwind" fn f_tostring(l: *mut lua_State) -> c_int {
+    let p = tolstream(l);
+    match p.f {
+        None => { lua::lua_pushstring(l, c_str!("file (closed)")); }
+        Some(ref f) => { lua::lua_pushfstring(l, c_str!("file (%p)"), f as *const LFile); }
+    }
+    1
+}
+
+
+unsafe fn tofile<'a>(l: *mut lua_State) -> &'a mut LFile {
+    let p = tolstream(l);
+    match p.f {
+        Some(ref mut f) => f,
+        None => {
+            lauxlib::luaL_error(l, c_str!("attempt to use a closed file"));
+            unreachable!()
+        }
+    }
+}
+
+
+/*
+** When creating file handles, always creates a 'closed' file handle
+** before opening the actual file; so, if there is a memory error, the
+** handle is in a consistent state.
+*/
+unsafe fn newprefile<'a>(l: *mut lua_State) -> &'a mut LStream {
+    let p = lua::lua_newuserdata(l, mem::size_of::<LStream>()) as *mut LStream;
+    /* mark file handle as 'closed' */
+    ptr::write(p, LStream { c: lauxlib::luaL_Stream { f: ptr::null_mut(), closef: None }, f: None });
+    lauxlib::luaL_setmetatable(l, LUA_FILEHANDLE);
+    /* remember it, so that 'flush_all' can find it */
+    lua::lua_getfield(l, lua::LUA_REGISTRYINDEX, IO_FILES);
+    if lua::lua_istable(l, -1) != 0 {
+        lua::lua_pushvalue(l, -2);
+        lua::lua_pushboolean(l, 1);
+        lua::lua_rawset(l, -3);
+    }
+    lua::lua_pop(l, 1);
+    &mut *p
+}
+
+
+/*
+** Closes the file handle at index 1, returning its results. This is
+** the 'closef' of every handle.
+*/
+unsafe extern "C-unwind" fn aux_close(l: *mut lua_State) -> c_int {
+    let p = tolstream(l);
+    if let Some(&LFile { handle: Handle::Stdin, .. }) = p.f.as_ref() { return noclose(l, p); }
+    if let Some(&LFile { handle: Handle::Stdout, .. }) = p.f.as_ref() { return noclose(l, p); }
+    if let Some(&LFile { handle: Handle::Stderr, .. }) = p.f.as_ref() { return noclose(l, p); }
+    let mut f = p.take().unwrap();  /* mark stream as closed */
+    let res = f.flush();
+    match f.handle {
+        Handle::Pipe(mut child) => {  /* 'popen' files */
+            drop(child.stdin.take());  /* signal end of input */
+            match child.wait() {
+                Ok(status) => lauxlib::luaL_execresult(l, rawstatus(status)),
+                Err(e) => fileresult(l, Err(e), ptr::null()),
+            }
+        }
+        _ => fileresult(l, res, ptr::null()),
+    }
+}
+
+#[cfg(unix)]
+fn rawstatus(status: process::ExitStatus) -> c_int {
+    use std::os::unix::process::ExitStatusExt;
+    status.into_raw()
+}
+
+#[cfg(not(unix))]
+fn rawstatus(status: process::ExitStatus) -> c_int {
+    status.code().unwrap_or(-1)
+}
+
+
+/*
+** The standard files stdin, stdout, and stderr are not closed.
+*/
+unsafe fn noclose(l: *mut lua_State, p: &mut LStream) -> c_int {
+    p.c.closef = Some(aux_close);  /* keep file opened, even if C code cleared it */
+    lua::lua_pushnil(l);
+    lua::lua_pushstring(l, c_str!("cannot close standard file"));
+    2
+}
+
+
+unsafe extern "C-unwind" fn io_close(l: *mut lua_State) -> c_int {
+    if lua::lua_isnone(l, 1) != 0 {  /* no argument? */
+        lua::lua_getfield(l, lua::LUA_REGISTRYINDEX, IO_OUTPUT);  /* use standard output */
+    }
+    tofile(l);  /* make sure argument is an open stream */
+    aux_close(l)
+}
+
+
+unsafe extern "C-unwind" fn f_gc(l: *mut lua_State) -> c_int {
+    let p = tolstream(l);
+    match p.f {
+        Some(LFile { handle: Handle::Stdin, .. }) | Some(LFile { handle: Handle::Stdout, .. }) |
+        Some(LFile { handle: Handle::Stderr, .. }) => {
+            let _ = p.f.as_mut().unwrap().flush();
+            p.take();  /* release buffers, but not the standard file */
+        }
+        Some(_) => { aux_close(l); }
+        None => {}  /* ignore closed files */
+    }
+    0
+}
+
+
+/*
+** Check whether 'mode' matches '[rwa]%+?b*', returning the options to
+** open a file with it.
+*/
+fn checkmode(mode: &[u8]) -> Option<fs::OpenOptions> {
+    let mut opts = fs::OpenOptions::new();
+    let (first, rest) = mode.split_first()?;
+    let (plus, rest) = match rest.split_first() {
+        Some((&b'+', rest)) => (true, rest),
+        _ => (false, rest),
+    };
+    if !rest.iter().all(|&c| c == b'b') {
+        return None;
+    }
+    match *first {
+        b'r' => { opts.read(true).write(plus); }
+        b'w' => { opts.write(true).create(true).truncate(true).read(plus); }
+        b'a' => { opts.append(true).create(true).read(plus); }
+        _ => return None,
+    }
+    Some(opts)
+}
+
+
+/* 'mode' was already checked by 'checkmode' */
+fn setfile(p: &mut LStream, f: fs::File, mode: &[u8]) {
+    let plus = mode.get(1) == Some(&b'+');
+    p.open(LFile::new(Handle::File(f), BufMode::Full, mode[0] == b'r' || plus, mode[0] != b'r' || plus));
+}
+
+
+unsafe fn opencheck(l: *mut lua_State, fname: *const c_char, mode: &[u8]) {
+    let p = newprefile(l);
+    let res = checkmode(mode).unwrap().open(host::topath(CStr::from_ptr(fname)));
+    match res {
+        Ok(f) => setfile(p, f, mode),
+        Err(e) => {
+            lauxlib::luaL_where(l, 1);
+            lua::lua_pushfstring(l, c_str!("cannot open file '%s' ("), fname);
+            pusherror(l, &e);
+            lua::lua_pushstring(l, c_str!(")"));
+            drop(e);
+            lua::lua_concat(l, 4);
+            lua::lua_error(l);
+        }
+    }
+}
+
+
+unsafe extern "C-unwind" fn io_open(l: *mut lua_State) -> c_int {
+    let filename = lauxlib::luaL_checkstring(l, 1);
+    let mode = lauxlib::luaL_optstring(l, 2, c_str!("r"));
+    let p = newprefile(l);
+    let mode = CStr::from_ptr(mode).to_bytes();
+    let opts = checkmode(mode);
+    lauxlib::luaL_argcheck(l, opts.is_some() as c_int, 2, c_str!("invalid mode"));
+    match opts.unwrap().open(host::topath(CStr::from_ptr(filename))) {
+        Ok(f) => { setfile(p, f, mode); 1 }
+        Err(e) => fileresult(l, Err(e), filename),
+    }
+}
+
+
+unsafe extern "C-unwind" fn io_popen(l: *mut lua_State) -> c_int {
+    let filename = lauxlib::luaL_checkstring(l, 1);
+    let mode = lauxlib::luaL_optstring(l, 2, c_str!("r"));
+    let p = newprefile(l);
+    let mode = CStr::from_ptr(mode).to_bytes();
+    if mode != b"r" && mode != b"w" {
+        return fileresult(l, Err(io::Error::from_raw_os_error(libc::EINVAL)), filename);
+    }
+    libc::fflush(ptr::null_mut());  /* as 'popen', let the child see earlier output */
+    let _ = io::stdout().flush();
+    let mut cmd = process::Command::new("/bin/sh");
+    cmd.arg("-c").arg(host::topath(CStr::from_ptr(filename)).as_os_str());
+    if mode == b"r" {
+        cmd.stdout(process::Stdio::piped());
+    } else {
+        cmd.stdin(process::Stdio::piped());
+    }
+    match cmd.spawn() {
+        Ok(child) => {
+            p.open(LFile::new(Handle::Pipe(child), BufMode::Full, mode == b"r", mode == b"w"));
+            1
+        }
+        Err(e) => fileresult(l, Err(e), filename),
+    }
+}
+
+
+#[cfg(unix)]
+fn tmpfile() -> io::Result<fs::File> {
+    use std::os::unix::io::FromRawFd;
+    let mut buff = *b"/tmp/lua_XXXXXX\0";
+    let fd = unsafe { libc::mkstemp(buff.as_mut_ptr() as *mut c_char) };
+    if fd == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    unsafe { libc::unlink(buff.as_ptr() as *const c_char); }  /* removed when closed */
+    Ok(unsafe { fs::File::from_raw_fd(fd) })
+}
+
+
+unsafe extern "C-unwind" fn io_tmpfile(l: *mut lua_State) -> c_int {
+    let p = newprefile(l);
+    match tmpfile() {
+        Ok(f) => { setfile(p, f, b"w+"); 1 }
+        Err(e) => fileresult(l, Err(e), ptr::null()),
+    }
+}
+
+
+/// Pushes onto the stack of `l` a new file handle for `f`, which Lua can
+/// use as any file returned by `io.open`.
+///
+/// # Safety
+///
+/// `l` must be a valid Lua state in which the `io` library is open.
+pub unsafe fn push_file(l: *mut lua_State, f: Box<dyn Stream>) {
+    let p = newprefile(l);
+    p.open(LFile::new(Handle::Virtual(f), BufMode::Full, true, true));
+}
+
+
+/// Flushes the output of all open files of state `l`, as the C library
+/// does for its streams when the process exits.
+///
+/// # Safety
+///
+/// `l` must be a valid Lua state.
+pub unsafe fn flush_all(l: *mut lua_State) {
+    if lua::lua_getfield(l, lua::LUA_REGISTRYINDEX, IO_FILES) == lua::LUA_TTABLE {
+        lua::lua_pushnil(l);
+        while lua::lua_next(l, -2) != 0 {
+            lua::lua_pop(l, 1);  /* remove value; keep key (the file) */
+            let p = lua::lua_touserdata(l, -1) as *mut LStream;
+            if let Some(ref mut f) = (*p).f {
+                let _ = f.flush();
+            }
+        }
+    }
+    lua::lua_pop(l, 1);
+}
+
+
+/*
+** Flushes the output buffered by the handle of the standard output, so
+** that 'print', which writes to the standard output directly, keeps
+** the order of both outputs.
+*/
+#[cfg(rust_lbaselib)]
+pub(crate) unsafe fn flush_stdout(l: *mut lua_State) {
+    if lua::lua_getfield(l, lua::LUA_REGISTRYINDEX, IO_STDOUT) == lua::LUA_TUSERDATA {
+        let p = lua::lua_touserdata(l, -1) as *mut LStream;
+        if let Some(ref mut f) = (*p).f {
+            let _ = f.flushw();
+        }
+    }
+    lua::lua_pop(l, 1);
+}
+
+
+unsafe fn getiofile<'a>(l: *mut lua_State, findex: *const c_char) -> &'a mut LFile {
+    lua::lua_getfield(l, lua::LUA_REGISTRYINDEX, findex);
+    let p = lua::lua_touserdata(l, -1) as *mut LStream;
+    match (*p).f {
+        Some(ref mut f) => f,
+        None => {
+            lauxlib::luaL_error(l, c_str!("standard %s file is closed"), findex.add(IOPREF_LEN));
+            unreachable!()
+        }
+    }
+}
+
+
+unsafe fn g_iofile(l: *mut lua_State, f: *const c_char, mode: &[u8]) -> c_int {
+    if lua::lua_isnoneornil(l, 1) == 0 {
+        let filename = lua::lua_tostring(l, 1);
+        if !filename.is_null() {
+            opencheck(l, filename, mode);
+        } else {
+            tofile(l);  /* check that it's a valid file handle */
+            lua::lua_pushvalue(l, 1);
+        }
+        lua::lua_setfield(l, lua::LUA_REGISTRYINDEX, f);
+    }
+    /* return current value */
+    lua::lua_getfield(l, lua::LUA_REGISTRYINDEX, f);
+    1
+}
+
+
+unsafe extern "C-unwind" fn io_input(l: *mut lua_State) -> c_int {
+    g_iofile(l, IO_INPUT, b"r")
+}
+
+
+unsafe extern "C-unwind" fn io_output(l: *mut lua_State) -> c_int {
+    g_iofile(l, IO_OUTPUT, b"w")
+}
+
+
+/*
+** maximum number of arguments to 'f:lines'/'io.lines' (it + 3 must fit
+** in the limit for upvalues of a closure)
+*/
+const MAXARGLINE: c_int = 250;
+
+unsafe fn aux_lines(l: *mut lua_State, toclose: bool) {
+    let n = lua::lua_gettop(l) - 1;  /* number of arguments to read */
+    lauxlib::luaL_argcheck(l, (n <= MAXARGLINE) as c_int, MAXARGLINE + 2, c_str!("too many arguments"));
+    lua::lua_pushinteger(l, n as lua_Integer);  /* number of arguments to read */
+    lua::lua_pushboolean(l, toclose as c_int);  /* close/not close file when finished */
+    lua::lua_rotate(l, 2, 2);  /* move 'n' and 'toclose' to their positions */
+    lua::lua_pushcclosure(l, Some(io_readline), 3 + n);
+}
+
+
+unsafe extern "C-unwind" fn f_lines(l: *mut lua_State) -> c_int {
+    tofile(l);  /* check that it's a valid file handle */
+    aux_lines(l, false);
+    1
+}
+
+
+unsafe extern "C-unwind" fn io_lines(l: *mut lua_State) -> c_int {
+    if lua::lua_isnone(l, 1) != 0 { lua::lua_pushnil(l); }  /* at least one argument */
+    let toclose = if lua::lua_isnil(l, 1) != 0 {  /* no file name? */
+        lua::lua_getfield(l, lua::LUA_REGISTRYINDEX, IO_INPUT);  /* get default input */
+        lua::lua_replace(l, 1);  /* put it at index 1 */
+        tofile(l);  /* check that it's a valid file handle */
+        false  /* do not close it after iteration */
+    } else {  /* open a new file */
+        let filename = lauxlib::luaL_checkstring(l, 1);
+        opencheck(l, filename, b"r");
+        lua::lua_replace(l, 1);  /* put file at index 1 */
+        true  /* close it after iteration */
+    };
+    aux_lines(l, toclose);
+    1
+}
+
+
+/*
+** {======================================================
+** READ
+** =======================================================
+*/
+
+
+/* maximum length of a numeral */
+const L_MAXLENNUM: usize = 200;
+
+
+/* auxiliary structure used by 'read_number' */
+struct RN<'a> {
+    f: &'a mut LFile,  /* file being read */
+    c: Option<u8>,  /* current character (look ahead) */
+    buff: Vec<u8>,
+    err: Option<io::Error>,
+}
+
+impl<'a> RN<'a> {
+    /*
+    ** Add current char to buffer (if not out of space) and read next one
+    */
+    fn nextc(&mut self) -> bool {
+        if self.buff.len() >= L_MAXLENNUM {  /* buffer overflow? */
+            self.buff.clear();  /* invalidate result */
+            self.buff.push(b'\0');
+            return false;  /* fail */
+        }
+        self.buff.push(self.c.unwrap());  /* save current char */
+        self.f.rpos += 1;
+        self.c = self.look();  /* read next one */
+        true
+    }
+
+    fn look(&mut self) -> Option<u8> {
+        match self.f.peek() {
+            Ok(c) => c,
+            Err(e) => { self.err = Some(e); None }
+        }
+    }
+
+    /*
+    ** Accept current char if it is in 'set' (of size 2)
+    */
+    fn test2(&mut self, set: &[u8; 2]) -> bool {
+        if self.c == Some(set[0]) || self.c == Some(set[1]) {
+            self.nextc()
+        } else {
+            false
+        }
+    }
+
+    /*
+    ** Read a sequence of (hex)digits
+    */
+    fn readdigits(&mut self, hex: bool) -> usize {
+        let mut count = 0;
+        while self.c.is_some_and(|c| if hex { c.is_ascii_hexdigit() } else { c.is_ascii_digit() })
+                && self.nextc() {
+            count += 1;
+        }
+        count
+    }
+}
+
+
+fn isspace(c: u8) -> bool {
+    c == b' ' || (b'\t'..=b'\r').contains(&c)
+}
+
+
+/*
+** Read a number: first reads a valid prefix of a numeral into a buffer.
+** Then it calls 'lua_stringtonumber' to check whether the format is
+** correct and to convert it to a Lua number
+*/
+unsafe fn read_number(l: *mut lua_State, f: &mut LFile) -> io::Result<bool> {
+    let decp = [*(*libc::localeconv()).decimal_point as u8,  /* get decimal point from locale */
+                b'.'];  /* always accept a dot */
+    let mut rn = RN { f, c: None, buff: Vec::with_capacity(L_MAXLENNUM + 1), err: None };
+    let mut count = 0;
+    let mut hex = false;
+    loop {  /* skip spaces */
+        rn.c = rn.look();
+        match rn.c {
+            Some(c) if isspace(c) => rn.f.rpos += 1,
+            _ => break,
+        }
+    }
+    rn.test2(b"-+");  /* optional signal */
+    if rn.test2(b"00") {
+        if rn.test2(b"xX") { hex = true; }  /* numeral is hexadecimal */
+        else { count = 1; }  /* count initial '0' as a valid digit */
+    }
+    count += rn.readdigits(hex);  /* integral part */
+    if rn.test2(&decp) {  /* decimal point? */
+        count += rn.readdigits(hex);  /* fractional part */
+    }
+    if count > 0 && rn.test2(if hex { b"pP" } else { b"eE" }) {  /* exponent mark? */
+        rn.test2(b"-+");  /* exponent signal */
+        rn.readdigits(false);  /* exponent digits */
+    }
+    /* the look-ahead char was never consumed */
+    if let Some(e) = rn.err {
+        return Err(e);
+    }
+    rn.buff.push(b'\0');  /* finish string */
+    if lua::lua_stringtonumber(l, rn.buff.as_ptr() as *const c_char) != 0 {  /* is this a valid number? */
+        Ok(true)  /* ok */
+    } else {  /* invalid format */
+        lua::lua_pushnil(l);  /* "result" to be removed */
+        Ok(false)  /* read fails */
+    }
+}
+
+
+unsafe fn test_eof(l: *mut lua_State, f: &mut LFile) -> io::Result<bool> {
+    let c = f.peek()?;
+    lua::lua_pushstring(l, c_str!(""));
+    Ok(c.is_some())
+}
+
+
+unsafe fn read_line(l: *mut lua_State, f: &mut LFile, chop: bool) -> io::Result<bool> {
+    let mut b = Vec::new();
+    let mut nl = false;
+    let res = (|| {
+        while f.fill()? {  /* repeat until end of line */
+            let avail = &f.rbuf[f.rpos..];
+            match avail.iter().position(|&c| c == b'\n') {
+                Some(i) => {
+                    b.extend_from_slice(&avail[..i]);
+                    f.rpos += i + 1;
+                    nl = true;
+                    break;
+                }
+                None => {
+                    b.extend_from_slice(avail);
+                    f.rpos = f.rbuf.len();
+                }
+            }
+        }
+        Ok(())
+    })();
+    if !chop && nl {  /* want a newline and have one? */
+        b.push(b'\n');  /* add ending newline to result */
+    }
+    lua::lua_pushlstring(l, b.as_ptr() as *const c_char, b.len());
+    res.map(|()| nl || !b.is_empty())  /* ok if read something */
+}
+
+
+unsafe fn read_all(l: *mut lua_State, f: &mut LFile) -> io::Result<bool> {
+    let mut b = Vec::new();
+    let res = f.read(&mut b, usize::MAX);
+    lua::lua_pushlstring(l, b.as_ptr() as *const c_char, b.len());
+    res.map(|_| true)  /* always success */
+}
+
+
+unsafe fn read_chars(l: *mut lua_State, f: &mut LFile, n: usize) -> io::Result<bool> {
+    let mut b = Vec::new();
+    let res = f.read(&mut b, n);  /* try to read 'n' chars */
+    lua::lua_pushlstring(l, b.as_ptr() as *const c_char, b.len());
+    res.map(|nr| nr > 0)  /* true iff read something */
+}
+
+
+unsafe fn g_read(l: *mut lua_State, f: &mut LFile, first: c_int) -> c_int {
+    let mut nargs = lua::lua_gettop(l) - 1;
+    let mut n;
+    let mut res = Ok(true);
+    if nargs == 0 {  /* no arguments? */
+        res = read_line(l, f, true);
+        n = first + 1;  /* to return 1 result */
+    } else {  /* ensure stack space for all results */
+        lauxlib::luaL_checkstack(l, nargs + lua::LUA_MINSTACK, c_str!("too many arguments"));
+        n = first;
+        while nargs > 0 && matches!(res, Ok(true)) {
+            nargs -= 1;
+            res = if lua::lua_type(l, n) == lua::LUA_TNUMBER {
+                let len = lauxlib::luaL_checkinteger(l, n) as size_t;
+                if len == 0 { test_eof(l, f) } else { read_chars(l, f, len) }
+            } else {
+                let mut p = lauxlib::luaL_checkstring(l, n);
+                if *p == b'*' as c_char { p = p.offset(1); }  /* skip optional '*' (for compatibility) */
+                match *p as u8 {
+                    b'n' => read_number(l, f),  /* number */
+                    b'l' => read_line(l, f, true),  /* line */
+                    b'L' => read_line(l, f, false),  /* line with end-of-line */
+                    b'a' => read_all(l, f),  /* file */
+                    _ => return lauxlib::luaL_argerror(l, n, c_str!("invalid format")),
+                }
+            };
+            n += 1;
+        }
+    }
+    match res {
+        Err(e) => fileresult(l, Err(e), ptr::null()),
+        Ok(success) => {
+            if !success {
+                lua::lua_pop(l, 1);  /* remove last result */
+                lua::lua_pushnil(l);  /* push nil instead */
+            }
+            n - first
+        }
+    }
+}
+
+
+unsafe extern "C-unwind" fn io_read(l: *mut lua_State) -> c_int {
+    let f = getiofile(l, IO_INPUT);
+    g_read(l, f, 1)
+}
+
+
+unsafe extern "C-unwind" fn f_read(l: *mut lua_State) -> c_int {
+    let f = tofile(l);
+    g_read(l, f, 2)
+}
+
+
+unsafe extern "C-unwind" fn io_readline(l: *mut lua_State) -> c_int {
+    let p = lua::lua_touserdata(l, lua::lua_upvalueindex(1)) as *mut LStream;
+    let mut n = lua::lua_tointeger(l, lua::lua_upvalueindex(2)) as c_int;
+    if (*p).f.is_none() {  /* file is already closed? */
+        return lauxlib::luaL_error(l, c_str!("file is already closed"));
+    }
+    lua::lua_settop(l, 1);
+    lauxlib::luaL_checkstack(l, n, c_str!("too many arguments"));
+    for i in 1..(n + 1) {  /* push arguments to 'g_read' */
+        lua::lua_pushvalue(l, lua::lua_upvalueindex(3 + i));
+    }
+    n = g_read(l, (*p).f.as_mut().unwrap(), 2);  /* 'n' is number of results */
+    if lua::lua_toboolean(l, -n) != 0 {  /* read at least one value? */
+        n  /* return them */
+    } else {  /* first result is nil: EOF or error */
+        if n > 1 {  /* is there error information? */
+            /* 2nd result is error message */
+            return lauxlib::luaL_error(l, c_str!("%s"), lua::lua_tostring(l, -n + 1));
+        }
+        if lua::lua_toboolean(l, lua::lua_upvalueindex(3)) != 0 {  /* generator created file? */
+            lua::lua_settop(l, 0);
+            lua::lua_pushvalue(l, lua::lua_upvalueindex(1));
+            aux_close(l);  /* close it */
+        }
+        0
+    }
+}
+
+/* }====================================================== */
+
+
+unsafe fn g_write(l: *mut lua_State, f: &mut LFile, mut arg: c_int) -> c_int {
+    let mut nargs = lua::lua_gettop(l) - arg;
+    let mut res = Ok(());
+    while nargs > 0 {
+        nargs -= 1;
+        if lua::lua_type(l, arg) == lua::LUA_TNUMBER {
+            let mut buff = [0u8; 64];
+            let len = if lua::lua_isinteger(l, arg) != 0 {
+                libc::snprintf(buff.as_mut_ptr() as *mut c_char, buff.len(), c_str!("%lld"),
+                               lua::lua_tointeger(l, arg) as libc::c_longlong)
+            } else {
+                libc::snprintf(buff.as_mut_ptr() as *mut c_char, buff.len(), c_str!("%.14g"),
+                               lua::lua_tonumber(l, arg))
+            };
+            if res.is_ok() {
+                res = f.write(&buff[..len as usize]);
+            }
+        } else {
+            let mut len: size_t = 0;
+            let s = lauxlib::luaL_checklstring(l, arg, &mut len);
+            if res.is_ok() {
+                res = f.write(slice::from_raw_parts(s as *const u8, len));
+            }
+        }
+        arg += 1;
+    }
+    match res {
+        Ok(()) => 1,  /* file handle already on stack top */
+        Err(e) => fileresult(l, Err(e), ptr::null()),
+    }
+}
+
+
+unsafe extern "C-unwind" fn io_write(l: *mut lua_State) -> c_int {
+    let f = getiofile(l, IO_OUTPUT);  /* leaves the file on the stack top */
+    g_write(l, f, 1)
+}
+
+
+unsafe extern "C-unwind" fn f_write(l: *mut lua_State) -> c_int {
+    let f = tofile(l);
+    lua::lua_pushvalue(l, 1);  /* push file at the stack top (to be returned) */
+    g_write(l, f, 2)
+}
+
+
+unsafe extern "C-unwind" fn f_seek(l: *mut lua_State) -> c_int {
+    let modenames = [c_str!("set"), c_str!("cur"), c_str!("end"), ptr::null()];
+    let f = tofile(l);
+    let op = lauxlib::luaL_checkoption(l, 2, c_str!("cur"), modenames.as_ptr());
+    let offset = lauxlib::luaL_optinteger(l, 3, 0);
+    let pos = match op {
+        0 => {
+            if offset < 0 {
+                return fileresult(l, Err(io::Error::from_raw_os_error(libc::EINVAL)), ptr::null());
+            }
+            SeekFrom::Start(offset as u64)
+        }
+        1 => SeekFrom::Current(offset),
+        _ => SeekFrom::End(offset),
+    };
+    match f.seek(pos) {
+        Ok(p) => {
+            lua::lua_pushinteger(l, p as lua_Integer);
+            1
+        }
+        Err(e) => fileresult(l, Err(e), ptr::null()),  /* error */
+    }
+}
+
+
+unsafe extern "C-unwind" fn f_setvbuf(l: *mut lua_State) -> c_int {
+    let mode = [BufMode::No, BufMode::Full, BufMode::Line];
+    let modenames = [c_str!("no"), c_str!("full"), c_str!("line"), ptr::null()];
+    let f = tofile(l);
+    let op = lauxlib::luaL_checkoption(l, 2, ptr::null(), modenames.as_ptr());
+    let sz = lauxlib::luaL_optinteger(l, 3, BUFFERSIZE as lua_Integer);
+    lauxlib::luaL_argcheck(l, (sz >= 0) as c_int, 3, c_str!("invalid size"));
+    let res = f.setvbuf(mode[op as usize], sz as usize);
+    fileresult(l, res, ptr::null())
+}
+
+
+
+unsafe extern "C-unwind" fn io_flush(l: *mut lua_State) -> c_int {
+    let res = getiofile(l, IO_OUTPUT).flush();
+    fileresult(l, res, ptr::null())
+}
+
+
+unsafe extern "C-unwind" fn f_flush(l: *mut lua_State) -> c_int {
+    let res = tofile(l).flush();
+    fileresult(l, res, ptr::null())
+}
+
+
+unsafe fn createmeta(l: *mut lua_State) {
+    /*
+    ** methods for file handles
+    */
+    let flib = [
+        lauxlib::luaL_Reg { name: c_str!("close"), func: Some(io_close) },
+        lauxlib::luaL_Reg { name: c_str!("flush"), func: Some(f_flush) },
+        lauxlib::luaL_Reg { name: c_str!("lines"), func: Some(f_lines) },
+        lauxlib::luaL_Reg { name: c_str!("read"), func: Some(f_read) },
+        lauxlib::luaL_Reg { name: c_str!("seek"), func: Some(f_seek) },
+        lauxlib::luaL_Reg { name: c_str!("setvbuf"), func: Some(f_setvbuf) },
+        lauxlib::luaL_Reg { name: c_str!("write"), func: Some(f_write) },
+        lauxlib::luaL_Reg { name: c_str!("__gc"), func: Some(f_gc) },
+        lauxlib::luaL_Reg { name: c_str!("__tostring"), func: Some(f_tostring) },
+        lauxlib::luaL_Reg { name: ptr::null(), func: None },
+    ];
+    lauxlib::luaL_newmetatable(l, LUA_FILEHANDLE);  /* create metatable for file handles */
+    lua::lua_pushvalue(l, -1);  /* push metatable */
+    lua::lua_setfield(l, -2, c_str!("__index"));  /* metatable.__index = metatable */
+    lauxlib::luaL_setfuncs(l, flib.as_ptr(), 0);  /* add file methods to new metatable */
+    lua::lua_pop(l, 1);  /* pop new metatable */
+}
+
+
+unsafe fn createstdfile(l: *mut lua_State, handle: Handle, k: *const c_char, fname: *const c_char) {
+    let p = newprefile(l);
+    let input = matches!(handle, Handle::Stdin);
+    p.open(LFile::new(handle, BufMode::No, input, !input));  /* Rust's standard streams do the buffering */
+    if !k.is_null() {
+        lua::lua_pushvalue(l, -1);
+        lua::lua_setfield(l, lua::LUA_REGISTRYINDEX, k);  /* add file to registry */
+    }
+    lua::lua_setfield(l, -2, fname);  /* add file to module */
+}
+
+
+/// Opens the `io` library in state `l`, leaving its table on the stack.
+///
+/// # Safety
+///
+/// `l` must be a valid Lua state.
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaopen_io(l: *mut lua_State) -> c_int {
+    /*
+    ** functions for 'io' library
+    */
+    let iolib = [
+        lauxlib::luaL_Reg { name: c_str!("close"), func: Some(io_close) },
+        lauxlib::luaL_Reg { name: c_str!("flush"), func: Some(io_flush) },
+        lauxlib::luaL_Reg { name: c_str!("input"), func: Some(io_input) },
+        lauxlib::luaL_Reg { name: c_str!("lines"), func: Some(io_lines) },
+        lauxlib::luaL_Reg { name: c_str!("open"), func: Some(io_open) },
+        lauxlib::luaL_Reg { name: c_str!("output"), func: Some(io_output) },
+        lauxlib::luaL_Reg { name: c_str!("popen"), func: Some(io_popen) },
+        lauxlib::luaL_Reg { name: c_str!("read"), func: Some(io_read) },
+        lauxlib::luaL_Reg { name: c_str!("tmpfile"), func: Some(io_tmpfile) },
+        lauxlib::luaL_Reg { name: c_str!("type"), func: Some(io_type) },
+        lauxlib::luaL_Reg { name: c_str!("write"), func: Some(io_write) },
+        lauxlib::luaL_Reg { name: ptr::null(), func: None },
+    ];
+    /* create the table of open files (weak keys) */
+    lua::lua_createtable(l, 0, 0);
+    lua::lua_createtable(l, 0, 1);
+    lua::lua_pushstring(l, c_str!("k"));
+    lua::lua_setfield(l, -2, c_str!("__mode"));
+    lua::lua_setmetatable(l, -2);
+    lua::lua_setfield(l, lua::LUA_REGISTRYINDEX, IO_FILES);
+    lauxlib::luaL_newlib(l, iolib.as_ptr());  /* new module */
+    createmeta(l);
+    /* create (and set) default files */
+    createstdfile(l, Handle::Stdin, IO_INPUT, c_str!("stdin"));
+    createstdfile(l, Handle::Stdout, IO_OUTPUT, c_str!("stdout"));
+    lua::lua_getfield(l, -1, c_str!("stdout"));
+    lua::lua_setfield(l, lua::LUA_REGISTRYINDEX, IO_STDOUT);  /* for 'flush_stdout' */
+    createstdfile(l, Handle::Stderr, ptr::null(), c_str!("stderr"));
+    1
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::checkmode;
+
+    #[test]
+    fn test_checkmode() {
+        for mode in &["r", "w", "a", "r+", "w+", "a+", "rb", "r+b", "wbbb"] {
+            assert!(checkmode(mode.as_bytes()).is_some(), "{}", mode);
+        }
+        for mode in &["", "+", "b", "rw", "r++", "x", "rb+"] {
+            assert!(checkmode(mode.as_bytes()).is_none(), "{}", mode);
+        }
+    }
+}
@@ -0,0 +1,340 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Lexical Analyzer, as seen by the C core.
+//!
+//! The scanner itself lives in `lexer`; this module keeps the `LexState`
+//! layout and the `luaX_*` entry points used by the parser.
+
+#![allow(non_snake_case, non_upper_case_globals)]
+
+use lexer::{self, ErrorKind, Lexeme, Near, Reserved, Scan, FIRST_RESERVED, NUM_RESERVED};
+use ldebug::luaG_addinfo;
+use ldo::luaD_throw;
+use lgc::{luaC_checkGC, luaC_fix};
+use lparser::{Dyndata, FuncState};
+use lobject::{keyfromval, luaO_pushfstring, setbvalue, setsvalue, GCObject, Table, TString};
+use lstate::lua_State;
+use lstring::{luaS_new, luaS_newlstr};
+use ltable::luaH_set;
+use lzio::{self, Mbuffer, Zio};
+use libc::{c_char, c_int, size_t};
+use ffi::lua::{lua_Integer, lua_Number, LUA_ERRSYNTAX};
+use std::ops::Range;
+use std::ptr;
+use std::slice;
+
+
+/* the exported functions below, from 'llex.c' when it is linked instead (see 'build.rs') */
+#[cfg(not(rust_llex))]
+extern "C-unwind" {
+    pub fn luaX_init(l: *mut lua_State);
+    pub fn luaX_token2str(ls: *mut LexState, token: c_int) -> *const c_char;
+    pub fn luaX_syntaxerror(ls: *mut LexState, msg: *const c_char) -> !;
+    pub fn luaX_newstring(ls: *mut LexState, str: *const c_char, l: size_t) -> *mut TString;
+    pub fn luaX_setinput(l: *mut lua_State, ls: *mut LexState, z: *mut Zio, source: *mut TString,
+                         firstchar: c_int);
+    pub fn luaX_next(ls: *mut LexState);
+    pub fn luaX_lookahead(ls: *mut LexState) -> c_int;
+}
+
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union SemInfo {
+    pub r: lua_Number,
+    pub i: lua_Integer,
+    pub ts: *mut TString,
+}  /* semantics information */
+
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Token {
+    pub token: c_int,
+    pub seminfo: SemInfo,
+}
+
+
+/* state of the lexer plus state of the parser when shared by all
+   functions */
+#[repr(C)]
+pub struct LexState {
+    pub current: c_int,  /* current character (charint) */
+    pub linenumber: c_int,  /* input line counter */
+    pub lastline: c_int,  /* line of last token 'consumed' */
+    pub t: Token,  /* current token */
+    pub lookahead: Token,  /* look ahead token */
+    pub fs: *mut FuncState,  /* current function (parser) */
+    pub L: *mut lua_State,
+    pub z: *mut Zio,  /* input stream */
+    pub buff: *mut Mbuffer,  /* buffer for tokens */
+    pub h: *mut Table,  /* to avoid collection/reuse strings */
+    pub dyd: *mut Dyndata,  /* dynamic structures used by the parser */
+    pub source: *mut TString,  /* current source name */
+    pub envn: *mut TString,  /* environment variable name */
+}
+
+
+const LUA_MINBUFFER: size_t = 32;
+
+/* maximum size visible for Lua (must be representable in a lua_Integer) */
+const MAX_SIZE: size_t = lua_Integer::MAX as size_t;
+
+const TK_EOS: c_int = Reserved::Eos as c_int;
+const TK_STRING: c_int = Reserved::String as c_int;
+
+
+/* ORDER RESERVED */
+static luaX_tokens: [&[u8]; 37] = [
+    b"and\0", b"break\0", b"do\0", b"else\0", b"elseif\0",
+    b"end\0", b"false\0", b"for\0", b"function\0", b"goto\0", b"if\0",
+    b"in\0", b"local\0", b"nil\0", b"not\0", b"or\0", b"repeat\0",
+    b"return\0", b"then\0", b"true\0", b"until\0", b"while\0",
+    b"//\0", b"..\0", b"...\0", b"==\0", b">=\0", b"<=\0", b"~=\0",
+    b"<<\0", b">>\0", b"::\0", b"<eof>\0",
+    b"<number>\0", b"<integer>\0", b"<name>\0", b"<string>\0",
+];
+
+
+#[cfg(rust_llex)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaX_init(l: *mut lua_State) {
+    let e = luaS_newlstr(l, c_str!("_ENV"), 4);  /* create env name */
+    luaC_fix(l, e as *mut GCObject);  /* never collect this name */
+    for (i, name) in luaX_tokens[..NUM_RESERVED].iter().enumerate() {
+        let ts = luaS_new(l, name.as_ptr() as *const c_char);
+        luaC_fix(l, ts as *mut GCObject);  /* reserved words are never collected */
+        (*ts).extra = (i + 1) as u8;  /* reserved word */
+    }
+}
+
+
+#[cfg(rust_llex)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaX_token2str(ls: *mut LexState, token: c_int) -> *const c_char {
+    if token < FIRST_RESERVED {  /* single-byte symbols? */
+        debug_assert!(token == token as u8 as c_int);
+        luaO_pushfstring((*ls).L, c_str!("'%c'"), token)
+    } else {
+        let s = luaX_tokens[(token - FIRST_RESERVED) as usize].as_ptr() as *const c_char;
+        if token < TK_EOS {  /* fixed format (symbols and reserved words)? */
+            luaO_pushfstring((*ls).L, c_str!("'%s'"), s)
+        } else {  /* names, strings, and numerals */
+            s
+        }
+    }
+}
+
+
+unsafe fn txtToken(ls: *mut LexState, token: c_int) -> *const c_char {
+    match Reserved::from_code(token) {
+        Some(Reserved::Name) | Some(Reserved::String) |
+        Some(Reserved::Flt) | Some(Reserved::Int) => {
+            save(ls, '\0' as c_int);
+            luaO_pushfstring((*ls).L, c_str!("'%s'"), (*(*ls).buff).buffer)
+        }
+        _ => luaX_token2str(ls, token),
+    }
+}
+
+
+unsafe fn lexerror(ls: *mut LexState, msg: *const c_char, token: c_int) -> ! {
+    let msg = luaG_addinfo((*ls).L, msg, (*ls).source, (*ls).linenumber);
+    if token != 0 {
+        luaO_pushfstring((*ls).L, c_str!("%s near %s"), msg, txtToken(ls, token));
+    }
+    luaD_throw((*ls).L, LUA_ERRSYNTAX)
+}
+
+
+#[cfg(rust_llex)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaX_syntaxerror(ls: *mut LexState, msg: *const c_char) -> ! {
+    lexerror(ls, msg, (*ls).t.token)
+}
+
+
+/*
+** creates a new string and anchors it in scanner's table so that
+** it will not be collected until the end of the compilation
+** (by that time it should be anchored somewhere)
+*/
+#[cfg(rust_llex)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaX_newstring(ls: *mut LexState, str: *const c_char, l: size_t) -> *mut TString {
+    let L = (*ls).L;
+    let mut ts = luaS_newlstr(L, str, l);  /* create new string */
+    setsvalue((*L).top, ts);  /* temporarily anchor it in stack */
+    (*L).top = (*L).top.add(1);
+    let o = luaH_set(L, (*ls).h, (*L).top.sub(1));  /* entry for 'str' */
+    if (*o).ttisnil() {  /* not in use yet? */
+        /* boolean value does not need GC barrier;
+           table has no metatable, so it does not need to invalidate cache */
+        setbvalue(o, 1);  /* t[string] = true */
+        luaC_checkGC(L);
+    } else {  /* string already present */
+        ts = (*keyfromval(o)).value_.gc as *mut TString;  /* re-use value previously stored */
+    }
+    (*L).top = (*L).top.sub(1);  /* remove string from stack */
+    ts
+}
+
+
+#[cfg(rust_llex)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaX_setinput(l: *mut lua_State, ls: *mut LexState, z: *mut Zio,
+                                       source: *mut TString, firstchar: c_int) {
+    (*ls).t.token = 0;
+    (*ls).L = l;
+    (*ls).current = firstchar;
+    (*ls).lookahead.token = TK_EOS;  /* no look-ahead token */
+    (*ls).z = z;
+    (*ls).fs = ptr::null_mut();
+    (*ls).linenumber = 1;
+    (*ls).lastline = 1;
+    (*ls).source = source;
+    (*ls).envn = luaS_newlstr(l, c_str!("_ENV"), 4);  /* get env name */
+    lzio::luaZ_resizebuffer((*ls).L, (*ls).buff, LUA_MINBUFFER);  /* initialize buffer */
+}
+
+
+
+/*
+** =======================================================
+** LEXICAL ANALYZER
+** =======================================================
+*/
+
+
+unsafe fn save(ls: *mut LexState, c: c_int) {
+    let b = (*ls).buff;
+    if (*b).n + 1 > (*b).buffsize {
+        if (*b).buffsize >= MAX_SIZE / 2 {
+            lexerror(ls, c_str!("lexical element too long"), 0);
+        }
+        let newsize = (*b).buffsize * 2;
+        lzio::luaZ_resizebuffer((*ls).L, b, newsize);
+    }
+    *(*b).buffer.add((*b).n) = c as c_char;
+    (*b).n += 1;
+}
+
+
+/* errors never return, as 'lexerror' unwinds the C stack */
+enum Void {}
+
+/* the scanner state of the core */
+struct CLex(*mut LexState);
+
+impl Scan for CLex {
+    type Str = *mut TString;
+    type Error = Void;
+
+    fn current(&self) -> i32 { unsafe { (*self.0).current } }
+
+    fn next(&mut self) {
+        unsafe { (*self.0).current = lzio::zgetc((*self.0).z); }
+    }
+
+    fn save(&mut self, c: i32) -> Result<(), Void> {
+        unsafe { save(self.0, c); }
+        Ok(())
+    }
+
+    fn buffer(&self) -> &[u8] {
+        unsafe {
+            let b = (*self.0).buff;
+            slice::from_raw_parts((*b).buffer as *const u8, (*b).n)
+        }
+    }
+
+    fn buffremove(&mut self, n: usize) {
+        unsafe { (*(*self.0).buff).n -= n; }
+    }
+
+    fn resetbuffer(&mut self) {
+        unsafe { (*(*self.0).buff).n = 0; }
+    }
+
+    fn linenumber(&mut self) -> &mut i32 { unsafe { &mut (*self.0).linenumber } }
+
+    fn newstring(&mut self, r: Range<usize>) -> *mut TString {
+        unsafe {
+            let b = (*(*self.0).buff).buffer.add(r.start);
+            luaX_newstring(self.0, b, r.end - r.start)
+        }
+    }
+
+    fn error(&mut self, kind: ErrorKind, near: Near) -> Void {
+        unsafe {
+            let ls = self.0;
+            let msg = match kind {
+                ErrorKind::UnfinishedLongString(line) | ErrorKind::UnfinishedLongComment(line) => {
+                    let what = if let ErrorKind::UnfinishedLongString(_) = kind { c_str!("string") }
+                               else { c_str!("comment") };
+                    luaO_pushfstring((*ls).L, c_str!("unfinished long %s (starting at line %d)"),
+                                     what, line)
+                }
+                ErrorKind::LexicalElementTooLong => c_str!("lexical element too long"),
+                ErrorKind::TooManyLines => c_str!("chunk has too many lines"),
+                ErrorKind::MalformedNumber => c_str!("malformed number"),
+                ErrorKind::UnfinishedString => c_str!("unfinished string"),
+                ErrorKind::InvalidLongStringDelimiter => c_str!("invalid long string delimiter"),
+                ErrorKind::HexadecimalDigitExpected => c_str!("hexadecimal digit expected"),
+                ErrorKind::MissingOpenBrace => c_str!("missing '{'"),
+                ErrorKind::MissingCloseBrace => c_str!("missing '}'"),
+                ErrorKind::Utf8ValueTooLarge => c_str!("UTF-8 value too large"),
+                ErrorKind::DecimalEscapeTooLarge => c_str!("decimal escape too large"),
+                ErrorKind::InvalidEscapeSequence => c_str!("invalid escape sequence"),
+            };
+            let token = match near {
+                Near::Nothing => 0,
+                Near::Token => TK_STRING,  /* any literal shows the buffer */
+                Near::Eos => TK_EOS,
+            };
+            lexerror(ls, msg, token)
+        }
+    }
+}
+
+
+unsafe fn llex(ls: *mut LexState, seminfo: *mut SemInfo) -> c_int {
+    let lexeme = match lexer::llex(&mut CLex(ls)) {
+        Ok(lexeme) => lexeme,
+        Err(void) => match void {},
+    };
+    match lexeme {
+        Lexeme::Char(c) => c as c_int,
+        Lexeme::Reserved(r) => r as c_int,
+        Lexeme::Flt(r) => { (*seminfo).r = r; Reserved::Flt as c_int }
+        Lexeme::Int(i) => { (*seminfo).i = i; Reserved::Int as c_int }
+        Lexeme::Name(ts) => { (*seminfo).ts = ts; Reserved::Name as c_int }
+        Lexeme::String(ts) => { (*seminfo).ts = ts; Reserved::String as c_int }
+    }
+}
+
+
+#[cfg(rust_llex)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaX_next(ls: *mut LexState) {
+    (*ls).lastline = (*ls).linenumber;
+    if (*ls).lookahead.token != TK_EOS {  /* is there a look-ahead token? */
+        (*ls).t = (*ls).lookahead;  /* use this one */
+        (*ls).lookahead.token = TK_EOS;  /* and discharge it */
+    } else {
+        (*ls).t.token = llex(ls, &mut (*ls).t.seminfo);  /* read next token */
+    }
+}
+
+
+#[cfg(rust_llex)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaX_lookahead(ls: *mut LexState) -> c_int {
+    debug_assert!((*ls).lookahead.token == TK_EOS);
+    (*ls).lookahead.token = llex(ls, &mut (*ls).lookahead.seminfo);
+    (*ls).lookahead.token
+}
@@ -0,0 +1,62 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Interface to Memory Manager.
+
+#![allow(non_snake_case)]
+
+use lstate::lua_State;
+use libc::{c_char, c_int, c_void, size_t};
+use std::mem;
+use std::ptr;
+
+
+extern "C-unwind" {
+    pub fn luaM_realloc_(L: *mut lua_State, block: *mut c_void, oldsize: size_t, size: size_t) -> *mut c_void;
+    pub fn luaM_growaux_(L: *mut lua_State, block: *mut c_void, size: *mut c_int,
+                         size_elem: size_t, limit: c_int, what: *const c_char) -> *mut c_void;
+    pub fn luaM_toobig(L: *mut lua_State) -> !;
+}
+
+
+pub unsafe fn luaM_growvector<T>(L: *mut lua_State, v: &mut *mut T, nelems: c_int, size: &mut c_int,
+                                 limit: c_int, e: *const c_char) {
+    if nelems + 1 > *size {
+        *v = luaM_growaux_(L, *v as *mut c_void, size, mem::size_of::<T>(), limit, e) as *mut T;
+    }
+}
+
+
+/* 'n' is an 'int', so it cannot overflow a 'size_t' product */
+pub unsafe fn luaM_reallocvector<T>(L: *mut lua_State, v: &mut *mut T, oldn: c_int, n: c_int) {
+    let e = mem::size_of::<T>();
+    *v = luaM_realloc_(L, *v as *mut c_void, oldn as size_t * e, n as size_t * e) as *mut T;
+}
+
+
+pub unsafe fn luaM_newvector<T>(L: *mut lua_State, n: size_t) -> *mut T {
+    luaM_realloc_(L, ptr::null_mut(), 0, n * mem::size_of::<T>()) as *mut T
+}
+
+
+pub unsafe fn luaM_freearray<T>(L: *mut lua_State, b: *mut T, n: size_t) {
+    luaM_realloc_(L, b as *mut c_void, n * mem::size_of::<T>(), 0);
+}
+
+
+pub unsafe fn luaM_freemem<T>(L: *mut lua_State, b: *mut T, s: size_t) {
+    luaM_realloc_(L, b as *mut c_void, s, 0);
+}
+
+
+pub unsafe fn luaM_new<T>(L: *mut lua_State) -> *mut T {
+    luaM_realloc_(L, ptr::null_mut(), 0, mem::size_of::<T>()) as *mut T
+}
+
+
+pub unsafe fn luaM_free<T>(L: *mut lua_State, b: *mut T) {
+    luaM_realloc_(L, b as *mut c_void, mem::size_of::<T>(), 0);
+}
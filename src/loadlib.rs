@@ -0,0 +1,609 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Dynamic library loader for Lua.
+//!
+//! This module contains an implementation of loadlib for Unix systems
+//! that have dlfcn, and a stub for other systems.
+
+#![allow(non_snake_case)]
+
+use ffi::lua::{self, lua_State};
+use ffi::lauxlib;
+use ffi::luaconf::{LUA_CPATH_DEFAULT, LUA_PATH_DEFAULT};
+use libc::{c_char, c_int, c_void};
+use host;
+use std::ffi::CStr;
+use std::{fs, mem, ptr};
+
+
+/*
+** LUA_PATH_VAR and LUA_CPATH_VAR are the names of the environment
+** variables that Lua check to set its paths.
+*/
+const LUA_PATH_VAR: &[u8] = b"LUA_PATH";
+const LUA_CPATH_VAR: &[u8] = b"LUA_CPATH";
+
+const LUA_PATHVARVERSION: &[u8] = b"LUA_PATH_5_3";
+const LUA_CPATHVARVERSION: &[u8] = b"LUA_CPATH_5_3";
+
+/*
+** LUA_PATH_SEP is the character that separates templates in a path.
+** LUA_PATH_MARK is the string that marks the substitution points in a
+** template.
+** LUA_EXEC_DIR in a Windows path is replaced by the executable's
+** directory.
+** LUA_IGMARK is a mark to ignore all before it when building the
+** luaopen_ function name.
+*/
+const LUA_PATH_SEP: u8 = b';';
+const LUA_PATH_MARK: *const c_char = c_str!("?");
+const LUA_IGMARK: u8 = b'-';
+
+#[cfg(windows)]
+const LUA_DIRSEP: *const c_char = c_str!("\\");
+#[cfg(not(windows))]
+const LUA_DIRSEP: *const c_char = c_str!("/");
+
+/*
+** LUA_CSUBSEP is the character that replaces dots in submodule names
+** when searching for a C loader.
+** LUA_LSUBSEP is the character that replaces dots in submodule names
+** when searching for a Lua loader.
+*/
+const LUA_CSUBSEP: *const c_char = LUA_DIRSEP;
+const LUA_LSUBSEP: *const c_char = LUA_DIRSEP;
+
+/* prefix for open functions in C libraries */
+const LUA_POF: &[u8] = b"luaopen_";
+
+/* separator for open functions in C libraries */
+const LUA_OFSEP: *const c_char = c_str!("_");
+
+
+/*
+** unique key for table in the registry that keeps handles
+** for all loaded C libraries
+*/
+static CLIBS: u8 = 0;
+
+
+/*
+** system-dependent functions
+*/
+
+/*
+** {========================================================================
+** This is an implementation of loadlib based on the dlfcn interface.
+** =========================================================================
+*/
+#[cfg(unix)]
+mod sys {
+    use ffi::lua::{self, lua_State, lua_CFunction};
+    use libc::{self, c_char, c_int, c_void};
+    use std::mem;
+
+    pub const LIB_FAIL: *const c_char = c_str!("open");
+
+    /*
+    ** unload library 'lib'
+    */
+    pub unsafe fn unloadlib(lib: *mut c_void) {
+        libc::dlclose(lib);
+    }
+
+    /*
+    ** load C library in file 'path'. If 'seeglb', load with all names in
+    ** the library global.
+    ** Returns the library; in case of error, returns NULL plus an
+    ** error string in the stack.
+    */
+    pub unsafe fn load(l: *mut lua_State, path: *const c_char, seeglb: bool) -> *mut c_void {
+        let flags: c_int = libc::RTLD_NOW | if seeglb { libc::RTLD_GLOBAL } else { libc::RTLD_LOCAL };
+        let lib = libc::dlopen(path, flags);
+        if lib.is_null() {
+            lua::lua_pushstring(l, libc::dlerror());
+        }
+        lib
+    }
+
+    /*
+    ** Try to find a function named 'sym' in library 'lib'.
+    ** Returns the function; in case of error, returns NULL plus an
+    ** error string in the stack.
+    */
+    pub unsafe fn sym(l: *mut lua_State, lib: *mut c_void, sym: *const c_char) -> lua_CFunction {
+        let f = libc::dlsym(lib, sym);
+        if f.is_null() {
+            lua::lua_pushstring(l, libc::dlerror());
+            None
+        } else {
+            Some(mem::transmute::<*mut c_void, unsafe extern "C-unwind" fn(*mut lua_State) -> c_int>(f))
+        }
+    }
+}
+/* }====================================================== */
+
+
+/*
+** {======================================================
+** Fallback for other systems
+** =======================================================
+*/
+#[cfg(not(unix))]
+mod sys {
+    use ffi::lua::{self, lua_State, lua_CFunction};
+    use libc::{c_char, c_void};
+
+    pub const LIB_FAIL: *const c_char = c_str!("absent");
+
+    const DLMSG: *const c_char = c_str!("dynamic libraries not enabled; check your Lua installation");
+
+    pub unsafe fn unloadlib(_lib: *mut c_void) {}
+
+    pub unsafe fn load(l: *mut lua_State, _path: *const c_char, _seeglb: bool) -> *mut c_void {
+        lua::lua_pushstring(l, DLMSG);
+        ::std::ptr::null_mut()
+    }
+
+    pub unsafe fn sym(l: *mut lua_State, _lib: *mut c_void, _sym: *const c_char) -> lua_CFunction {
+        lua::lua_pushstring(l, DLMSG);
+        None
+    }
+}
+/* }====================================================== */
+
+
+/*
+** return registry.CLIBS[path]
+*/
+unsafe fn checkclib(l: *mut lua_State, path: *const c_char) -> *mut c_void {
+    lua::lua_rawgetp(l, lua::LUA_REGISTRYINDEX, &CLIBS as *const u8 as *const c_void);
+    lua::lua_getfield(l, -1, path);
+    let plib = lua::lua_touserdata(l, -1);  /* plib = CLIBS[path] */
+    lua::lua_pop(l, 2);  /* pop CLIBS table and 'plib' */
+    plib
+}
+
+
+/*
+** registry.CLIBS[path] = plib        -- for queries
+** registry.CLIBS[#CLIBS + 1] = plib  -- also keep a list of all libraries
+*/
+unsafe fn addtoclib(l: *mut lua_State, path: *const c_char, plib: *mut c_void) {
+    lua::lua_rawgetp(l, lua::LUA_REGISTRYINDEX, &CLIBS as *const u8 as *const c_void);
+    lua::lua_pushlightuserdata(l, plib);
+    lua::lua_pushvalue(l, -1);
+    lua::lua_setfield(l, -3, path);  /* CLIBS[path] = plib */
+    lua::lua_rawseti(l, -2, lauxlib::luaL_len(l, -2) + 1);  /* CLIBS[#CLIBS + 1] = plib */
+    lua::lua_pop(l, 1);  /* pop CLIBS table */
+}
+
+
+/*
+** __gc tag method for CLIBS table: calls 'sys::unloadlib' for all lib
+** handles in list CLIBS
+*/
+unsafe extern "C-unwind" fn gctm(l: *mut lua_State) -> c_int {
+    let mut n = lauxlib::luaL_len(l, 1);
+    while n >= 1 {  /* for each handle, in reverse order */
+        lua::lua_rawgeti(l, 1, n);  /* get handle CLIBS[n] */
+        sys::unloadlib(lua::lua_touserdata(l, -1));
+        lua::lua_pop(l, 1);  /* pop handle */
+        n -= 1;
+    }
+    0
+}
+
+
+/* error codes for 'lookforfunc' */
+const ERRLIB: c_int = 1;
+const ERRFUNC: c_int = 2;
+
+/*
+** Look for a C function named 'sym' in a dynamically loaded library
+** 'path'.
+** First, check whether the library is already loaded; if not, try
+** to load it.
+** Then, if 'sym' is '*', return true (as library has been loaded).
+** Otherwise, look for symbol 'sym' in the library and push a
+** C function with that symbol.
+** Return 0 and 'true' or a function in the stack; in case of
+** errors, return an error code and an error message in the stack.
+*/
+unsafe fn lookforfunc(l: *mut lua_State, path: *const c_char, sym: *const c_char) -> c_int {
+    let mut reg = checkclib(l, path);  /* check loaded C libraries */
+    if reg.is_null() {  /* must load library? */
+        reg = sys::load(l, path, *sym == b'*' as c_char);  /* global symbols if 'sym'=='*' */
+        if reg.is_null() {
+            return ERRLIB;  /* unable to load library */
+        }
+        addtoclib(l, path, reg);
+    }
+    if *sym == b'*' as c_char {  /* loading only library (no function)? */
+        lua::lua_pushboolean(l, 1);  /* return 'true' */
+        0  /* no errors */
+    } else {
+        let f = sys::sym(l, reg, sym);
+        if f.is_none() {
+            return ERRFUNC;  /* unable to find function */
+        }
+        lua::lua_pushcfunction(l, f);  /* else create new function */
+        0  /* no errors */
+    }
+}
+
+
+unsafe extern "C-unwind" fn ll_loadlib(l: *mut lua_State) -> c_int {
+    let path = lauxlib::luaL_checkstring(l, 1);
+    let init = lauxlib::luaL_checkstring(l, 2);
+    let stat = lookforfunc(l, path, init);
+    if stat == 0 {  /* no errors? */
+        1  /* return the loaded function */
+    } else {  /* error; error message is on stack top */
+        lua::lua_pushnil(l);
+        lua::lua_insert(l, -2);
+        lua::lua_pushstring(l, if stat == ERRLIB { sys::LIB_FAIL } else { c_str!("init") });
+        3  /* return nil, error message, and where */
+    }
+}
+
+
+/*
+** {======================================================
+** 'require' function
+** =======================================================
+*/
+
+
+#[cfg(unix)]
+fn readable(filename: &CStr) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+    /* try to open file */
+    fs::File::open(::std::ffi::OsStr::from_bytes(filename.to_bytes())).is_ok()
+}
+
+#[cfg(not(unix))]
+fn readable(filename: &CStr) -> bool {
+    filename.to_str().map(|f| fs::File::open(f).is_ok()).unwrap_or(false)
+}
+
+
+/*
+** Pushes the next template of 'path', returning the rest of it, or
+** None when there are no more templates.
+*/
+unsafe fn pushnexttemplate(l: *mut lua_State, path: &[u8]) -> Option<&[u8]> {
+    let start = path.iter().position(|&c| c != LUA_PATH_SEP)?;  /* skip separators; none means no more templates */
+    let path = &path[start..];
+    let end = path.iter().position(|&c| c == LUA_PATH_SEP).unwrap_or(path.len());  /* find next separator */
+    lua::lua_pushlstring(l, path.as_ptr() as *const c_char, end);  /* template */
+    Some(&path[end..])
+}
+
+
+unsafe fn searchpath(l: *mut lua_State, mut name: *const c_char, path: *const c_char,
+                     sep: *const c_char, dirsep: *const c_char) -> *const c_char {
+    let mut msg: lauxlib::luaL_Buffer = mem::zeroed();  /* to build error message */
+    lauxlib::luaL_buffinit(l, &mut msg);
+    if *sep != 0 {  /* non-empty separator? */
+        name = lauxlib::luaL_gsub(l, name, sep, dirsep);  /* replace it by 'dirsep' */
+    }
+    let mut path = CStr::from_ptr(path).to_bytes();
+    while let Some(rest) = pushnexttemplate(l, path) {
+        path = rest;
+        let filename = lauxlib::luaL_gsub(l, lua::lua_tostring(l, -1), LUA_PATH_MARK, name);
+        lua::lua_remove(l, -2);  /* remove path template */
+        if readable(CStr::from_ptr(filename)) {  /* does file exist and is readable? */
+            return filename;  /* return that file name */
+        }
+        lua::lua_pushfstring(l, c_str!("\n\tno file '%s'"), filename);
+        lua::lua_remove(l, -2);  /* remove file name */
+        lauxlib::luaL_addvalue(&mut msg);  /* concatenate error msg. entry */
+    }
+    lauxlib::luaL_pushresult(&mut msg);  /* create error message */
+    ptr::null()  /* not found */
+}
+
+
+unsafe extern "C-unwind" fn ll_searchpath(l: *mut lua_State) -> c_int {
+    let f = searchpath(l, lauxlib::luaL_checkstring(l, 1),
+                       lauxlib::luaL_checkstring(l, 2),
+                       lauxlib::luaL_optstring(l, 3, c_str!(".")),
+                       lauxlib::luaL_optstring(l, 4, LUA_DIRSEP));
+    if !f.is_null() {
+        1
+    } else {  /* error message is on top of the stack */
+        lua::lua_pushnil(l);
+        lua::lua_insert(l, -2);
+        2  /* return nil + error message */
+    }
+}
+
+
+unsafe fn findfile(l: *mut lua_State, name: *const c_char, pname: *const c_char,
+                   dirsep: *const c_char) -> *const c_char {
+    lua::lua_getfield(l, lua::lua_upvalueindex(1), pname);
+    let path = lua::lua_tostring(l, -1);
+    if path.is_null() {
+        lauxlib::luaL_error(l, c_str!("'package.%s' must be a string"), pname);
+    }
+    searchpath(l, name, path, c_str!("."), dirsep)
+}
+
+
+unsafe fn checkload(l: *mut lua_State, stat: bool, filename: *const c_char) -> c_int {
+    if stat {  /* module loaded successfully? */
+        lua::lua_pushstring(l, filename);  /* will be 2nd argument to module */
+        2  /* return open function and file name */
+    } else {
+        lauxlib::luaL_error(l, c_str!("error loading module '%s' from file '%s':\n\t%s"),
+                            lua::lua_tostring(l, 1), filename, lua::lua_tostring(l, -1))
+    }
+}
+
+
+unsafe extern "C-unwind" fn searcher_Lua(l: *mut lua_State) -> c_int {
+    let name = lauxlib::luaL_checkstring(l, 1);
+    let filename = findfile(l, name, c_str!("path"), LUA_LSUBSEP);
+    if filename.is_null() {
+        return 1;  /* module not found in this path */
+    }
+    checkload(l, lauxlib::luaL_loadfile(l, filename) == lua::LUA_OK, filename)
+}
+
+
+/*
+** Try to find a load function for module 'modname' at file 'filename'.
+** First, change '.' to '_' in 'modname'; then, if 'modname' has
+** the form X-Y (that is, it has an "ignore mark"), build a function
+** name "luaopen_X" and look for it. (For compatibility, if that
+** fails, it also tries "luaopen_Y".) If there is no ignore mark,
+** look for a function named "luaopen_modname".
+*/
+unsafe fn loadfunc(l: *mut lua_State, filename: *const c_char, modname: *const c_char) -> c_int {
+    let modname = lauxlib::luaL_gsub(l, modname, c_str!("."), LUA_OFSEP);
+    let mut modname = CStr::from_ptr(modname).to_bytes();
+    if let Some(mark) = modname.iter().position(|&c| c == LUA_IGMARK) {
+        pushopenfunc(l, &modname[..mark]);
+        let stat = lookforfunc(l, filename, lua::lua_tostring(l, -1));
+        if stat != ERRFUNC {
+            return stat;
+        }
+        modname = &modname[mark + 1..];  /* else go ahead and try old-style name */
+    }
+    pushopenfunc(l, modname);
+    lookforfunc(l, filename, lua::lua_tostring(l, -1))
+}
+
+/* pushes the name of the open function for 'modname' */
+unsafe fn pushopenfunc(l: *mut lua_State, modname: &[u8]) {
+    lua::lua_pushlstring(l, LUA_POF.as_ptr() as *const c_char, LUA_POF.len());
+    lua::lua_pushlstring(l, modname.as_ptr() as *const c_char, modname.len());
+    lua::lua_concat(l, 2);
+}
+
+
+unsafe extern "C-unwind" fn searcher_C(l: *mut lua_State) -> c_int {
+    let name = lauxlib::luaL_checkstring(l, 1);
+    let filename = findfile(l, name, c_str!("cpath"), LUA_CSUBSEP);
+    if filename.is_null() {
+        return 1;  /* module not found in this path */
+    }
+    checkload(l, loadfunc(l, filename, name) == 0, filename)
+}
+
+
+unsafe extern "C-unwind" fn searcher_Croot(l: *mut lua_State) -> c_int {
+    let name = lauxlib::luaL_checkstring(l, 1);
+    let p = match CStr::from_ptr(name).to_bytes().iter().position(|&c| c == b'.') {
+        Some(p) => p,
+        None => return 0,  /* is root */
+    };
+    lua::lua_pushlstring(l, name, p);
+    let filename = findfile(l, lua::lua_tostring(l, -1), c_str!("cpath"), LUA_CSUBSEP);
+    if filename.is_null() {
+        return 1;  /* root not found */
+    }
+    let stat = loadfunc(l, filename, name);
+    if stat != 0 {
+        if stat != ERRFUNC {
+            return checkload(l, false, filename);  /* real error */
+        } else {  /* open function not found */
+            lua::lua_pushfstring(l, c_str!("\n\tno module '%s' in file '%s'"), name, filename);
+            return 1;
+        }
+    }
+    lua::lua_pushstring(l, filename);  /* will be 2nd argument to module */
+    2
+}
+
+
+unsafe extern "C-unwind" fn searcher_preload(l: *mut lua_State) -> c_int {
+    let name = lauxlib::luaL_checkstring(l, 1);
+    lua::lua_getfield(l, lua::LUA_REGISTRYINDEX, c_str!("_PRELOAD"));
+    if lua::lua_getfield(l, -1, name) == lua::LUA_TNIL {  /* not found? */
+        lua::lua_pushfstring(l, c_str!("\n\tno field package.preload['%s']"), name);
+    }
+    1
+}
+
+
+unsafe fn findloader(l: *mut lua_State, name: *const c_char) {
+    let mut msg: lauxlib::luaL_Buffer = mem::zeroed();  /* to build error message */
+    lauxlib::luaL_buffinit(l, &mut msg);
+    /* push 'package.searchers' to index 3 in the stack */
+    if lua::lua_getfield(l, lua::lua_upvalueindex(1), c_str!("searchers")) != lua::LUA_TTABLE {
+        lauxlib::luaL_error(l, c_str!("'package.searchers' must be a table"));
+    }
+    /*  iterate over available searchers to find a loader */
+    let mut i = 1;
+    loop {
+        if lua::lua_rawgeti(l, 3, i) == lua::LUA_TNIL {  /* no more searchers? */
+            lua::lua_pop(l, 1);  /* remove nil */
+            lauxlib::luaL_pushresult(&mut msg);  /* create error message */
+            lauxlib::luaL_error(l, c_str!("module '%s' not found:%s"), name, lua::lua_tostring(l, -1));
+        }
+        lua::lua_pushstring(l, name);
+        lua::lua_call(l, 1, 2);  /* call it */
+        if lua::lua_isfunction(l, -2) != 0 {  /* did it find a loader? */
+            return;  /* module loader found */
+        } else if lua::lua_isstring(l, -2) != 0 {  /* searcher returned error message? */
+            lua::lua_pop(l, 1);  /* remove extra return */
+            lauxlib::luaL_addvalue(&mut msg);  /* concatenate error message */
+        } else {
+            lua::lua_pop(l, 2);  /* remove both returns */
+        }
+        i += 1;
+    }
+}
+
+
+unsafe extern "C-unwind" fn ll_require(l: *mut lua_State) -> c_int {
+    let name = lauxlib::luaL_checkstring(l, 1);
+    lua::lua_settop(l, 1);  /* _LOADED table will be at index 2 */
+    lua::lua_getfield(l, lua::LUA_REGISTRYINDEX, c_str!("_LOADED"));
+    lua::lua_getfield(l, 2, name);  /* _LOADED[name] */
+    if lua::lua_toboolean(l, -1) != 0 {  /* is it there? */
+        return 1;  /* package is already loaded */
+    }
+    /* else must load package */
+    lua::lua_pop(l, 1);  /* remove 'getfield' result */
+    findloader(l, name);
+    lua::lua_pushstring(l, name);  /* pass name as argument to module loader */
+    lua::lua_insert(l, -2);  /* name is 1st argument (before search data) */
+    lua::lua_call(l, 2, 1);  /* run loader to load module */
+    if lua::lua_isnil(l, -1) == 0 {  /* non-nil return? */
+        lua::lua_setfield(l, 2, name);  /* _LOADED[name] = returned value */
+    }
+    if lua::lua_getfield(l, 2, name) == lua::LUA_TNIL {  /* module set no value? */
+        lua::lua_pushboolean(l, 1);  /* use true as result */
+        lua::lua_pushvalue(l, -1);  /* extra copy to be returned */
+        lua::lua_setfield(l, 2, name);  /* _LOADED[name] = true */
+    }
+    1
+}
+
+/* }====================================================== */
+
+
+/* auxiliary mark (for internal use) */
+const AUXMARK: *const c_char = c_str!("\x01");
+
+
+/*
+** return registry.LUA_NOENV as a boolean
+*/
+unsafe fn noenv(l: *mut lua_State) -> bool {
+    lua::lua_getfield(l, lua::LUA_REGISTRYINDEX, c_str!("LUA_NOENV"));
+    let b = lua::lua_toboolean(l, -1) != 0;
+    lua::lua_pop(l, 1);  /* remove value */
+    b
+}
+
+
+/*
+** Sets field 'fieldname' of the table on the stack top to the path in
+** the environment variable 'envname1' (or 'envname2'), as given by the
+** environment of the 'os' library, or to 'def' if there is none.
+*/
+unsafe fn setpath(l: *mut lua_State, fieldname: *const c_char, envname1: &[u8],
+                  envname2: &[u8], def: &str) {
+    let env = host::getenvironment(l);
+    let path = env.getenv(envname1).or_else(|| env.getenv(envname2));  /* try alternative name */
+    lua::lua_pushlstring(l, def.as_ptr() as *const c_char, def.len());
+    let path = match path {
+        Some(path) => {
+            lua::lua_pushlstring(l, path.as_ptr() as *const c_char, path.len());
+            lua::lua_tostring(l, -1)
+        }
+        None => ptr::null(),
+    };
+    if !path.is_null() && !noenv(l) {
+        /* replace ";;" by ";AUXMARK;" and then AUXMARK by default path */
+        let path = lauxlib::luaL_gsub(l, path, c_str!(";;"), c_str!(";\x01;"));
+        lauxlib::luaL_gsub(l, path, AUXMARK, lua::lua_tostring(l, -3));
+        lua::lua_replace(l, -4);  /* result takes the place of the default */
+        lua::lua_pop(l, 2);
+    } else if !path.is_null() {
+        lua::lua_pop(l, 1);  /* use default */
+    }
+    lua::lua_setfield(l, -2, fieldname);
+}
+
+
+unsafe fn createsearcherstable(l: *mut lua_State) {
+    let searchers = [searcher_preload, searcher_Lua, searcher_C, searcher_Croot];
+    /* create 'searchers' table */
+    lua::lua_createtable(l, searchers.len() as c_int, 0);
+    /* fill it with predefined searchers */
+    for (i, searcher) in searchers.iter().enumerate() {
+        lua::lua_pushvalue(l, -2);  /* set 'package' as upvalue for all searchers */
+        lua::lua_pushcclosure(l, Some(*searcher), 1);
+        lua::lua_rawseti(l, -2, i as lua::lua_Integer + 1);
+    }
+    lua::lua_setfield(l, -2, c_str!("searchers"));  /* put it in field 'searchers' */
+}
+
+
+/*
+** create table CLIBS to keep track of loaded C libraries,
+** setting a finalizer to close all libraries when closing state.
+*/
+unsafe fn createclibstable(l: *mut lua_State) {
+    lua::lua_createtable(l, 0, 0);  /* create CLIBS table */
+    lua::lua_createtable(l, 0, 1);  /* create metatable for CLIBS */
+    lua::lua_pushcfunction(l, Some(gctm));
+    lua::lua_setfield(l, -2, c_str!("__gc"));  /* set finalizer for CLIBS table */
+    lua::lua_setmetatable(l, -2);
+    lua::lua_rawsetp(l, lua::LUA_REGISTRYINDEX, &CLIBS as *const u8 as *const c_void);  /* set CLIBS table in registry */
+}
+
+
+/// Opens the `package` library in state `l`, leaving its table on the stack.
+///
+/// # Safety
+///
+/// `l` must be a valid Lua state.
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaopen_package(l: *mut lua_State) -> c_int {
+    let pk_funcs = [
+        lauxlib::luaL_Reg { name: c_str!("loadlib"), func: Some(ll_loadlib) },
+        lauxlib::luaL_Reg { name: c_str!("searchpath"), func: Some(ll_searchpath) },
+        /* placeholders */
+        lauxlib::luaL_Reg { name: c_str!("preload"), func: None },
+        lauxlib::luaL_Reg { name: c_str!("cpath"), func: None },
+        lauxlib::luaL_Reg { name: c_str!("path"), func: None },
+        lauxlib::luaL_Reg { name: c_str!("searchers"), func: None },
+        lauxlib::luaL_Reg { name: c_str!("loaded"), func: None },
+        lauxlib::luaL_Reg { name: ptr::null(), func: None },
+    ];
+    let ll_funcs = [
+        lauxlib::luaL_Reg { name: c_str!("require"), func: Some(ll_require) },
+        lauxlib::luaL_Reg { name: ptr::null(), func: None },
+    ];
+    createclibstable(l);
+    lauxlib::luaL_newlib(l, pk_funcs.as_ptr());  /* create 'package' table */
+    createsearcherstable(l);
+    /* set field 'path' */
+    setpath(l, c_str!("path"), LUA_PATHVARVERSION, LUA_PATH_VAR, LUA_PATH_DEFAULT);
+    /* set field 'cpath' */
+    setpath(l, c_str!("cpath"), LUA_CPATHVARVERSION, LUA_CPATH_VAR, LUA_CPATH_DEFAULT);
+    /* store config information */
+    lua::lua_pushfstring(l, c_str!("%s\n;\n?\n!\n-\n"), LUA_DIRSEP);
+    lua::lua_setfield(l, -2, c_str!("config"));
+    /* set field 'loaded' */
+    lauxlib::luaL_getsubtable(l, lua::LUA_REGISTRYINDEX, c_str!("_LOADED"));
+    lua::lua_setfield(l, -2, c_str!("loaded"));
+    /* set field 'preload' */
+    lauxlib::luaL_getsubtable(l, lua::LUA_REGISTRYINDEX, c_str!("_PRELOAD"));
+    lua::lua_setfield(l, -2, c_str!("preload"));
+    lua::lua_pushglobaltable(l);
+    lua::lua_pushvalue(l, -2);  /* set 'package' as upvalue for next lib */
+    lauxlib::luaL_setfuncs(l, ll_funcs.as_ptr(), 1);  /* open lib into global table */
+    lua::lua_pop(l, 1);  /* pop global table */
+    1  /* return 'package' table */
+}
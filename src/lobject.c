@@ -0,0 +1,67 @@
+/*
+** Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+** Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+** Distributed under the MIT License.
+**    (See accompanying file LICENSE.txt or copy at
+**          http://opensource.org/licenses/MIT)
+**
+** The variadic part of 'lobject.c', which stable Rust cannot define.
+** The arguments are only collected here; 'luaO_pushfargs' in
+** 'lobject.rs' does the formatting.
+*/
+
+#include <stdarg.h>
+#include <string.h>
+
+#define LUA_CORE
+
+#include "lua.h"
+
+#include "lobject.h"
+
+
+/* an argument of a '%' option; the option tells which field is set */
+typedef union FArg {
+  const char *s;
+  lua_Integer i;
+  lua_Number n;
+  const void *p;
+} FArg;
+
+
+const char *luaO_pushfargs (lua_State *L, const char *fmt, const FArg *argv);
+
+
+const char *luaO_pushvfstring (lua_State *L, const char *fmt, va_list argp) {
+  int n = 1;  /* at least one, as empty arrays are not allowed */
+  const char *e;
+  for (e = fmt; *e != '\0'; e++)  /* one argument per '%' is enough */
+    n += (*e == '%');
+  {
+    FArg argv[n];
+    n = 0;
+    for (e = strchr(fmt, '%'); e != NULL; e = strchr(e + 2, '%')) {
+      switch (*(e+1)) {
+        case 's': argv[n++].s = va_arg(argp, char *); break;
+        case 'c': case 'd': argv[n++].i = va_arg(argp, int); break;
+        case 'I': argv[n++].i = cast(lua_Integer, va_arg(argp, l_uacInt)); break;
+        case 'f': argv[n++].n = cast_num(va_arg(argp, l_uacNumber)); break;
+        case 'p': argv[n++].p = va_arg(argp, void *); break;
+        case 'U': argv[n++].i = va_arg(argp, long); break;
+        case '%': break;
+        default: return luaO_pushfargs(L, fmt, argv);  /* raises the error */
+      }
+    }
+    return luaO_pushfargs(L, fmt, argv);
+  }
+}
+
+
+const char *luaO_pushfstring (lua_State *L, const char *fmt, ...) {
+  const char *msg;
+  va_list argp;
+  va_start(argp, fmt);
+  msg = luaO_pushvfstring(L, fmt, argp);
+  va_end(argp);
+  return msg;
+}
@@ -0,0 +1,1247 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Type definitions for Lua objects, and some generic functions over them.
+//!
+//! The structures mirror `lobject.h` field by field, so Rust code can
+//! work on the objects of the C core.
+
+#![allow(non_camel_case_types, non_snake_case)]
+
+use ffi::lua::{self, lua_CFunction, lua_Integer, lua_Number};
+use ffi::luaconf;
+use lctype::{lisdigit, lisprint, lisspace, lisxdigit, ltolower};
+use ldo::luaD_inctop;
+use lopcodes::Instruction;
+use lstate::{lua_State, StkId};
+use lstring::luaS_newlstr;
+use ltm::{luaT_trybinTM, TMS};
+use libc::{c_char, c_int, c_uint, c_ulong, c_void, size_t};
+use std::cmp;
+use std::ffi::CStr;
+use std::mem;
+use std::ptr;
+
+
+/* the exported functions below, from 'lobject.c' when it is linked instead (see 'build.rs') */
+#[cfg(not(rust_lobject))]
+unsafe extern "C-unwind" {
+    pub safe fn luaO_int2fb(x: c_uint) -> c_int;
+    pub safe fn luaO_fb2int(x: c_int) -> c_int;
+    pub safe fn luaO_ceillog2(x: c_uint) -> c_int;
+    pub fn luaO_arith(L: *mut lua_State, op: c_int, p1: *const TValue, p2: *const TValue,
+                      res: *mut TValue);
+    pub fn luaO_str2num(s: *const c_char, o: *mut TValue) -> size_t;
+    pub fn luaO_tostring(L: *mut lua_State, obj: StkId);
+    pub fn luaO_chunkid(out: *mut c_char, source: *const c_char, bufflen: size_t);
+}
+
+
+pub type lu_byte = u8;
+
+
+/*
+** Common type for all collectable objects, which share its header
+*/
+#[repr(C)]
+pub struct GCObject {
+    pub next: *mut GCObject,
+    pub tt: lu_byte,
+    pub marked: lu_byte,
+}
+
+
+
+/*
+** tags for Tagged Values have the following use of bits:
+** bits 0-3: actual tag (a LUA_T* value)
+** bits 4-5: variant bits
+** bit 6: whether value is collectable
+*/
+
+/* Variant tags for functions */
+#[allow(clippy::identity_op)]  /* variant 0, spelled out as in 'lobject.h' */
+pub const LUA_TLCL: c_int = lua::LUA_TFUNCTION | (0 << 4);  /* Lua closure */
+pub const LUA_TLCF: c_int = lua::LUA_TFUNCTION | (1 << 4);  /* light C function */
+pub const LUA_TCCL: c_int = lua::LUA_TFUNCTION | (2 << 4);  /* C closure */
+
+/* Variant tags for strings */
+#[allow(clippy::identity_op)]  /* variant 0, spelled out as in 'lobject.h' */
+pub const LUA_TSHRSTR: c_int = lua::LUA_TSTRING | (0 << 4);  /* short strings */
+pub const LUA_TLNGSTR: c_int = lua::LUA_TSTRING | (1 << 4);  /* long strings */
+
+/*
+** Maximum length for short strings, that is, strings that are internalized
+** (from 'llimits.h')
+*/
+pub const LUAI_MAXSHORTLEN: usize = 40;
+
+/* Variant tags for numbers */
+#[allow(clippy::identity_op)]  /* variant 0, spelled out as in 'lobject.h' */
+pub const LUA_TNUMFLT: c_int = lua::LUA_TNUMBER | (0 << 4);  /* float numbers */
+pub const LUA_TNUMINT: c_int = lua::LUA_TNUMBER | (1 << 4);  /* integer numbers */
+
+/*
+** Extra tags for non-values
+*/
+pub const LUA_TPROTO: c_int = lua::LUA_NUMTAGS;  /* function prototypes */
+pub const LUA_TDEADKEY: c_int = lua::LUA_NUMTAGS + 1;  /* removed keys in tables */
+
+/*
+** number of all possible tags (including LUA_TNONE but excluding DEADKEY)
+*/
+pub const LUA_TOTALTAGS: usize = (LUA_TPROTO + 2) as usize;
+
+/* Bit mark for collectable types */
+pub const BIT_ISCOLLECTABLE: c_int = 1 << 6;
+
+/* mark a tag as collectable */
+pub fn ctb(t: c_int) -> c_int { t | BIT_ISCOLLECTABLE }
+
+
+/* variadic, so defined in 'src/lobject.c' on top of 'luaO_pushfargs' */
+extern "C-unwind" {
+    pub fn luaO_pushfstring(L: *mut lua_State, fmt: *const c_char, ...) -> *const c_char;
+}
+
+extern "C-unwind" {
+    fn luaV_tonumber_(obj: *const TValue, n: *mut lua_Number) -> c_int;
+    fn luaV_tointeger(obj: *const TValue, p: *mut lua_Integer, mode: c_int) -> c_int;
+    fn luaV_div(L: *mut lua_State, m: lua_Integer, n: lua_Integer) -> lua_Integer;
+    fn luaV_mod(L: *mut lua_State, m: lua_Integer, n: lua_Integer) -> lua_Integer;
+    fn luaV_shiftl(x: lua_Integer, y: lua_Integer) -> lua_Integer;
+    fn luaG_runerror(L: *mut lua_State, fmt: *const c_char, ...) -> !;
+}
+
+
+/*
+** Union of all Lua values
+*/
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union Value {
+    pub gc: *mut GCObject,  /* collectable objects */
+    pub p: *mut c_void,  /* light userdata */
+    pub b: c_int,  /* booleans */
+    pub f: lua_CFunction,  /* light C functions */
+    pub i: lua_Integer,  /* integer numbers */
+    pub n: lua_Number,  /* float numbers */
+}
+
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct TValue {
+    pub value_: Value,
+    pub tt_: c_int,
+}
+
+impl TValue {
+    pub fn nil() -> TValue {
+        TValue { value_: Value { i: 0 }, tt_: lua::LUA_TNIL }
+    }
+
+    /* type tag of a TValue with no variants (bits 0-3) and variant bits */
+    pub fn ttype(&self) -> c_int { self.tt_ & 0x3F }
+    /* type tag of a TValue with no variants (bits 0-3) */
+    pub fn ttnov(&self) -> c_int { self.tt_ & 0x0F }
+
+    pub fn ttisnumber(&self) -> bool { self.ttnov() == lua::LUA_TNUMBER }
+    pub fn ttisfloat(&self) -> bool { self.tt_ == LUA_TNUMFLT }
+    pub fn ttisnil(&self) -> bool { self.tt_ == lua::LUA_TNIL }
+    pub fn ttisinteger(&self) -> bool { self.tt_ == LUA_TNUMINT }
+    pub fn ttisboolean(&self) -> bool { self.tt_ == lua::LUA_TBOOLEAN }
+    pub fn ttisstring(&self) -> bool { self.ttnov() == lua::LUA_TSTRING }
+    pub fn ttisshrstring(&self) -> bool { self.tt_ == ctb(LUA_TSHRSTR) }
+    pub fn ttistable(&self) -> bool { self.tt_ == ctb(lua::LUA_TTABLE) }
+    pub fn ttisfunction(&self) -> bool { self.ttnov() == lua::LUA_TFUNCTION }
+    pub fn ttisCclosure(&self) -> bool { self.tt_ == ctb(LUA_TCCL) }
+    pub fn ttisLclosure(&self) -> bool { self.tt_ == ctb(LUA_TLCL) }
+    pub fn ttislcf(&self) -> bool { self.tt_ == LUA_TLCF }
+    pub fn ttisfulluserdata(&self) -> bool { self.tt_ == ctb(lua::LUA_TUSERDATA) }
+    pub fn ttislightuserdata(&self) -> bool { self.tt_ == lua::LUA_TLIGHTUSERDATA }
+    pub fn ttisthread(&self) -> bool { self.tt_ == ctb(lua::LUA_TTHREAD) }
+    pub fn iscollectable(&self) -> bool { self.tt_ & BIT_ISCOLLECTABLE != 0 }
+    pub unsafe fn ivalue(&self) -> lua_Integer { self.value_.i }
+    pub unsafe fn bvalue(&self) -> c_int { self.value_.b }
+    pub unsafe fn gcvalue(&self) -> *mut GCObject { self.value_.gc }
+    pub unsafe fn pvalue(&self) -> *mut c_void { self.value_.p }
+    pub unsafe fn fvalue(&self) -> lua_CFunction { self.value_.f }
+    pub unsafe fn tsvalue(&self) -> *mut TString { self.value_.gc as *mut TString }
+    pub unsafe fn uvalue(&self) -> *mut Udata { self.value_.gc as *mut Udata }
+    pub unsafe fn clLvalue(&self) -> *mut LClosure { self.value_.gc as *mut LClosure }
+    pub unsafe fn clCvalue(&self) -> *mut CClosure { self.value_.gc as *mut CClosure }
+    pub unsafe fn hvalue(&self) -> *mut Table { self.value_.gc as *mut Table }
+    pub unsafe fn thvalue(&self) -> *mut lua_State { self.value_.gc as *mut lua_State }
+    pub unsafe fn fltvalue(&self) -> lua_Number { self.value_.n }
+    pub unsafe fn nvalue(&self) -> lua_Number {
+        if self.ttisinteger() { self.value_.i as lua_Number } else { self.value_.n }
+    }
+}
+
+pub unsafe fn l_isfalse(o: *const TValue) -> bool {
+    (*o).ttisnil() || ((*o).ttisboolean() && (*o).bvalue() == 0)
+}
+
+pub unsafe fn setfltvalue(obj: *mut TValue, x: lua_Number) {
+    (*obj).value_.n = x;
+    (*obj).tt_ = LUA_TNUMFLT;
+}
+
+pub unsafe fn setivalue(obj: *mut TValue, x: lua_Integer) {
+    (*obj).value_.i = x;
+    (*obj).tt_ = LUA_TNUMINT;
+}
+
+pub unsafe fn setnilvalue(obj: *mut TValue) { (*obj).tt_ = lua::LUA_TNIL; }
+
+pub unsafe fn setpvalue(obj: *mut TValue, x: *mut c_void) {
+    (*obj).value_.p = x;
+    (*obj).tt_ = lua::LUA_TLIGHTUSERDATA;
+}
+
+pub unsafe fn sethvalue(obj: *mut TValue, x: *mut Table) {
+    (*obj).value_.gc = x as *mut GCObject;
+    (*obj).tt_ = ctb(lua::LUA_TTABLE);
+}
+
+pub unsafe fn setclLvalue(obj: *mut TValue, x: *mut LClosure) {
+    (*obj).value_.gc = x as *mut GCObject;
+    (*obj).tt_ = ctb(LUA_TLCL);
+}
+
+pub unsafe fn setclCvalue(obj: *mut TValue, x: *mut CClosure) {
+    (*obj).value_.gc = x as *mut GCObject;
+    (*obj).tt_ = ctb(LUA_TCCL);
+}
+
+pub unsafe fn setfvalue(obj: *mut TValue, x: lua_CFunction) {
+    (*obj).value_.f = x;
+    (*obj).tt_ = LUA_TLCF;
+}
+
+pub unsafe fn setuvalue(obj: *mut TValue, x: *mut Udata) {
+    (*obj).value_.gc = x as *mut GCObject;
+    (*obj).tt_ = ctb(lua::LUA_TUSERDATA);
+}
+
+pub unsafe fn setthvalue(obj: *mut TValue, x: *mut lua_State) {
+    (*obj).value_.gc = x as *mut GCObject;
+    (*obj).tt_ = ctb(lua::LUA_TTHREAD);
+}
+
+pub unsafe fn setbvalue(obj: *mut TValue, x: c_int) {
+    (*obj).value_.b = x;
+    (*obj).tt_ = lua::LUA_TBOOLEAN;
+}
+
+pub unsafe fn setsvalue(obj: *mut TValue, x: *mut TString) {
+    (*obj).value_.gc = x as *mut GCObject;
+    (*obj).tt_ = ctb((*x).tt as c_int);
+}
+
+pub unsafe fn setgcovalue(obj: *mut TValue, x: *mut GCObject) {
+    (*obj).value_.gc = x;
+    (*obj).tt_ = ctb((*x).tt as c_int);
+}
+
+pub unsafe fn setdeadvalue(obj: *mut TValue) { (*obj).tt_ = LUA_TDEADKEY; }
+
+
+/*
+** Header for string value; string bytes follow the end of this structure
+** (aligned according to 'UTString').
+*/
+#[repr(C)]
+pub struct TString {
+    pub next: *mut GCObject,
+    pub tt: lu_byte,
+    pub marked: lu_byte,
+    pub extra: lu_byte,  /* reserved words for short strings; "has hash" for longs */
+    pub shrlen: lu_byte,  /* length for short strings */
+    pub hash: c_uint,
+    pub u: TStringU,
+}
+
+#[repr(C)]
+pub union TStringU {
+    pub lnglen: size_t,  /* length for long strings */
+    pub hnext: *mut TString,  /* linked list for hash table */
+}
+
+/* size of 'L_Umaxalign'; must agree with the value the C core was compiled with */
+#[cfg(ltests)]
+const L_UMAXALIGN: usize = 8 * mem::size_of::<*mut c_void>();  /* as set by 'ltests.h' */
+#[cfg(not(ltests))]
+const L_UMAXALIGN: usize = 8;
+
+/*
+** Get the actual string (array of bytes) from a 'TString'.
+** (The header is 'UTString', a union of 'TString' and 'L_Umaxalign'.)
+*/
+pub unsafe fn getstr(ts: *const TString) -> *const c_char {
+    let header = cmp::max(mem::size_of::<TString>(), L_UMAXALIGN);
+    (ts as *const c_char).add(header)
+}
+
+/* size of a string with 'l' bytes, header and final '\0' included */
+pub fn sizelstring(l: size_t) -> size_t {
+    cmp::max(mem::size_of::<TString>(), L_UMAXALIGN) + (l + 1) * mem::size_of::<c_char>()
+}
+
+/* get string length from 'TString *s' */
+pub unsafe fn tsslen(s: *const TString) -> size_t {
+    if (*s).tt as c_int == LUA_TSHRSTR { (*s).shrlen as size_t } else { (*s).u.lnglen }
+}
+
+/* get the actual string (array of bytes) and its length from a 'TValue' */
+pub unsafe fn svalue(o: *const TValue) -> *const c_char { getstr((*o).tsvalue()) }
+pub unsafe fn vslen(o: *const TValue) -> size_t { tsslen((*o).tsvalue()) }
+
+/* test whether a string is a reserved word */
+pub unsafe fn isreserved(s: *const TString) -> bool {
+    (*s).tt as c_int == LUA_TSHRSTR && (*s).extra > 0
+}
+
+
+/*
+** Header for userdata; memory area follows the end of this structure
+** (aligned according to 'UUdata'; see next).
+*/
+#[repr(C)]
+pub struct Udata {
+    pub next: *mut GCObject,
+    pub tt: lu_byte,
+    pub marked: lu_byte,
+    pub ttuv_: lu_byte,  /* user value's tag */
+    pub metatable: *mut Table,
+    pub len: size_t,  /* number of bytes */
+    pub user_: Value,  /* user value */
+}
+
+/* size of a userdata with a memory area of 'l' bytes, header included */
+pub fn sizeludata(l: size_t) -> size_t {
+    cmp::max(mem::size_of::<Udata>(), L_UMAXALIGN) + l
+}
+
+pub unsafe fn sizeudata(u: *const Udata) -> size_t { sizeludata((*u).len) }
+
+/*
+** Get the address of memory block inside 'Udata'.
+*/
+pub unsafe fn getudatamem(u: *mut Udata) -> *mut c_void {
+    (u as *mut u8).add(sizeludata(0)) as *mut c_void
+}
+
+pub unsafe fn setuservalue(u: *mut Udata, o: *const TValue) {
+    (*u).user_ = (*o).value_;
+    (*u).ttuv_ = (*o).tt_ as lu_byte;
+}
+
+pub unsafe fn getuservalue(u: *const Udata, o: *mut TValue) {
+    (*o).value_ = (*u).user_;
+    (*o).tt_ = (*u).ttuv_ as c_int;
+}
+
+
+/*
+** Description of an upvalue for function prototypes
+*/
+#[repr(C)]
+pub struct Upvaldesc {
+    pub name: *mut TString,  /* upvalue name (for debug information) */
+    pub instack: lu_byte,  /* whether it is in stack (register) */
+    pub idx: lu_byte,  /* index of upvalue (in stack or in outer function's list) */
+}
+
+
+/*
+** Description of a local variable for function prototypes
+** (used for debug information)
+*/
+#[repr(C)]
+pub struct LocVar {
+    pub varname: *mut TString,
+    pub startpc: c_int,  /* first point where variable is active */
+    pub endpc: c_int,  /* first point where variable is dead */
+}
+
+
+/*
+** Function Prototypes
+*/
+#[repr(C)]
+pub struct Proto {
+    pub next: *mut GCObject,
+    pub tt: lu_byte,
+    pub marked: lu_byte,
+    pub numparams: lu_byte,  /* number of fixed parameters */
+    pub is_vararg: lu_byte,  /* 2: declared vararg; 1: uses vararg */
+    pub maxstacksize: lu_byte,  /* number of registers needed by this function */
+    pub sizeupvalues: c_int,  /* size of 'upvalues' */
+    pub sizek: c_int,  /* size of 'k' */
+    pub sizecode: c_int,
+    pub sizelineinfo: c_int,
+    pub sizep: c_int,  /* size of 'p' */
+    pub sizelocvars: c_int,
+    pub linedefined: c_int,  /* debug information  */
+    pub lastlinedefined: c_int,  /* debug information  */
+    pub k: *mut TValue,  /* constants used by the function */
+    pub code: *mut Instruction,  /* opcodes */
+    pub p: *mut *mut Proto,  /* functions defined inside the function */
+    pub lineinfo: *mut c_int,  /* map from opcodes to source lines (debug information) */
+    pub locvars: *mut LocVar,  /* information about local variables (debug information) */
+    pub upvalues: *mut Upvaldesc,  /* upvalue information */
+    pub cache: *mut LClosure,  /* last-created closure with this prototype */
+    pub source: *mut TString,  /* used for debug information */
+    pub gclist: *mut GCObject,
+}
+
+
+/*
+** Upvalues for Lua closures (from 'lfunc.h')
+*/
+#[repr(C)]
+pub struct UpVal {
+    pub v: *mut TValue,  /* points to stack or to its own value */
+    pub refcount: size_t,  /* reference counter */
+    pub u: UpValU,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union UpValU {
+    pub open: UpValOpen,  /* (when open) */
+    pub value: TValue,  /* the value (when closed) */
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct UpValOpen {
+    pub next: *mut UpVal,  /* linked list */
+    pub touched: c_int,  /* mark to avoid cycles with dead threads */
+}
+
+pub unsafe fn upisopen(up: *mut UpVal) -> bool {
+    !ptr::eq((*up).v, &(*up).u.value)
+}
+
+/*
+** Closures
+*/
+#[repr(C)]
+pub struct CClosure {
+    pub next: *mut GCObject,
+    pub tt: lu_byte,
+    pub marked: lu_byte,
+    pub nupvalues: lu_byte,
+    pub gclist: *mut GCObject,
+    pub f: lua_CFunction,
+    pub upvalue: [TValue; 1],  /* list of upvalues */
+}
+
+#[repr(C)]
+pub struct LClosure {
+    pub next: *mut GCObject,
+    pub tt: lu_byte,
+    pub marked: lu_byte,
+    pub nupvalues: lu_byte,
+    pub gclist: *mut GCObject,
+    pub p: *mut Proto,
+    pub upvals: [*mut UpVal; 1],  /* list of upvalues */
+}
+
+/* sizes of closures with 'n' upvalues (from 'lfunc.h') */
+pub fn sizeCclosure(n: c_int) -> size_t {
+    mem::size_of::<CClosure>() + mem::size_of::<TValue>() * (n as size_t)
+        - mem::size_of::<TValue>()
+}
+
+pub fn sizeLclosure(n: c_int) -> size_t {
+    mem::size_of::<LClosure>() + mem::size_of::<*mut UpVal>() * (n as size_t)
+        - mem::size_of::<*mut UpVal>()
+}
+
+
+/*
+** Tables
+*/
+
+/* the 'nk' view of the key; 'tvk' is its prefix */
+#[repr(C)]
+pub struct TKey {
+    pub value_: Value,
+    pub tt_: c_int,
+    pub next: c_int,  /* for chaining (offset for next node) */
+}
+
+#[repr(C)]
+pub struct Node {
+    pub i_val: TValue,
+    pub i_key: TKey,
+}
+
+#[repr(C)]
+pub struct Table {
+    pub next: *mut GCObject,
+    pub tt: lu_byte,
+    pub marked: lu_byte,
+    pub flags: lu_byte,  /* 1<<p means tagmethod(p) is not present */
+    pub lsizenode: lu_byte,  /* log2 of size of 'node' array */
+    pub sizearray: c_uint,  /* size of 'array' array */
+    pub array: *mut TValue,  /* array part */
+    pub node: *mut Node,
+    pub lastfree: *mut Node,  /* any free position is before this position */
+    pub metatable: *mut Table,
+    pub gclist: *mut GCObject,
+}
+
+/* the key of the node holding value 'v' */
+pub unsafe fn keyfromval(v: *mut TValue) -> *mut TValue {
+    &mut (*(v as *mut Node)).i_key as *mut TKey as *mut TValue
+}
+
+
+/* a shareable wrapper, as C reads 'luaO_nilobject_' as a plain 'TValue' */
+#[repr(transparent)]
+pub struct NilObject(TValue);
+
+unsafe impl Sync for NilObject {}
+
+#[cfg(rust_lobject)]
+#[no_mangle]
+pub static luaO_nilobject_: NilObject =
+    NilObject(TValue { value_: Value { gc: ptr::null_mut() }, tt_: lua::LUA_TNIL });
+
+/* from 'lobject.c' otherwise, as its address tells nil results apart */
+#[cfg(not(rust_lobject))]
+extern "C" {
+    static luaO_nilobject_: NilObject;
+}
+
+/*
+** (address of) a fixed nil value
+*/
+pub fn luaO_nilobject() -> *const TValue { ptr::addr_of!(luaO_nilobject_) as *const TValue }
+
+
+/*
+** converts an integer to a "floating point byte", represented as
+** (eeeeexxx), where the real value is (1xxx) * 2^(eeeee - 1) if
+** eeeee != 0 and (xxx) otherwise.
+*/
+#[cfg(rust_lobject)]
+#[no_mangle]
+pub extern "C-unwind" fn luaO_int2fb(mut x: c_uint) -> c_int {
+    let mut e = 0;  /* exponent */
+    if x < 8 { return x as c_int; }
+    while x >= (8 << 4) {  /* coarse steps */
+        x = (x + 0xf) >> 4;  /* x = ceil(x / 16) */
+        e += 4;
+    }
+    while x >= (8 << 1) {  /* fine steps */
+        x = (x + 1) >> 1;  /* x = ceil(x / 2) */
+        e += 1;
+    }
+    ((e + 1) << 3) | (x as c_int - 8)
+}
+
+
+/* converts back */
+#[cfg(rust_lobject)]
+#[no_mangle]
+pub extern "C-unwind" fn luaO_fb2int(x: c_int) -> c_int {
+    if x < 8 { x } else { ((x & 7) + 8) << ((x >> 3) - 1) }
+}
+
+
+/*
+** Computes ceil(log2(x))
+*/
+#[cfg(rust_lobject)]
+#[no_mangle]
+pub extern "C-unwind" fn luaO_ceillog2(x: c_uint) -> c_int {
+    static LOG_2: [lu_byte; 256] = {  /* log_2[i] = ceil(log2(i - 1)) */
+        let mut t = [0; 256];
+        let mut i = 1;
+        while i < 256 {
+            t[i] = (32 - (i as u32).leading_zeros()) as lu_byte;
+            i += 1;
+        }
+        t
+    };
+    let mut l = 0;
+    let mut x = x.wrapping_sub(1);
+    while x >= 256 { l += 8; x >>= 8; }
+    l + LOG_2[x as usize] as c_int
+}
+
+
+pub unsafe fn tonumber(o: *const TValue, n: &mut lua_Number) -> bool {
+    if (*o).ttisfloat() { *n = (*o).fltvalue(); true }
+    else { luaV_tonumber_(o, n) != 0 }
+}
+
+pub unsafe fn tointeger(o: *const TValue, i: &mut lua_Integer) -> bool {
+    if (*o).ttisinteger() { *i = (*o).ivalue(); true }
+    else { luaV_tointeger(o, i, 0) != 0 }
+}
+
+
+unsafe fn intarith(L: *mut lua_State, op: c_int, v1: lua_Integer,
+                                                 v2: lua_Integer) -> lua_Integer {
+    match op {
+        lua::LUA_OPADD => v1.wrapping_add(v2),
+        lua::LUA_OPSUB => v1.wrapping_sub(v2),
+        lua::LUA_OPMUL => v1.wrapping_mul(v2),
+        lua::LUA_OPMOD => luaV_mod(L, v1, v2),
+        lua::LUA_OPIDIV => luaV_div(L, v1, v2),
+        lua::LUA_OPBAND => v1 & v2,
+        lua::LUA_OPBOR => v1 | v2,
+        lua::LUA_OPBXOR => v1 ^ v2,
+        lua::LUA_OPSHL => luaV_shiftl(v1, v2),
+        lua::LUA_OPSHR => luaV_shiftl(v1, v2.wrapping_neg()),
+        lua::LUA_OPUNM => v1.wrapping_neg(),
+        lua::LUA_OPBNOT => !v1,
+        _ => { debug_assert!(false); 0 }
+    }
+}
+
+
+fn numarith(op: c_int, v1: lua_Number, v2: lua_Number) -> lua_Number {
+    match op {
+        lua::LUA_OPADD => v1 + v2,
+        lua::LUA_OPSUB => v1 - v2,
+        lua::LUA_OPMUL => v1 * v2,
+        lua::LUA_OPDIV => v1 / v2,
+        lua::LUA_OPPOW => v1.powf(v2),
+        lua::LUA_OPIDIV => (v1 / v2).floor(),
+        lua::LUA_OPUNM => -v1,
+        lua::LUA_OPMOD => {
+            let mut m = v1 % v2;  /* luai_nummod */
+            if m * v2 < 0.0 { m += v2; }
+            m
+        }
+        _ => { debug_assert!(false); 0.0 }
+    }
+}
+
+
+#[cfg(rust_lobject)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaO_arith(L: *mut lua_State, op: c_int, p1: *const TValue,
+                                           p2: *const TValue, res: *mut TValue) {
+    let (mut i1, mut i2) = (0, 0);
+    let (mut n1, mut n2) = (0.0, 0.0);
+    match op {
+        lua::LUA_OPBAND | lua::LUA_OPBOR | lua::LUA_OPBXOR |
+        lua::LUA_OPSHL | lua::LUA_OPSHR |
+        lua::LUA_OPBNOT => {  /* operate only on integers */
+            if tointeger(p1, &mut i1) && tointeger(p2, &mut i2) {
+                setivalue(res, intarith(L, op, i1, i2));
+                return;
+            }
+        }
+        lua::LUA_OPDIV | lua::LUA_OPPOW => {  /* operate only on floats */
+            if tonumber(p1, &mut n1) && tonumber(p2, &mut n2) {
+                setfltvalue(res, numarith(op, n1, n2));
+                return;
+            }
+        }
+        _ => {  /* other operations */
+            if (*p1).ttisinteger() && (*p2).ttisinteger() {
+                setivalue(res, intarith(L, op, (*p1).ivalue(), (*p2).ivalue()));
+                return;
+            } else if tonumber(p1, &mut n1) && tonumber(p2, &mut n2) {
+                setfltvalue(res, numarith(op, n1, n2));
+                return;
+            }
+        }
+    }
+    /* could not perform raw operation; try metamethod */
+    debug_assert!(!L.is_null());  /* should not fail when folding (compile time) */
+    let event = (op - lua::LUA_OPADD) + TMS::TM_ADD as c_int;
+    debug_assert!(event >= TMS::TM_ADD as c_int && event <= TMS::TM_BNOT as c_int);
+    luaT_trybinTM(L, p1, p2, res, mem::transmute::<c_int, TMS>(event));
+}
+
+
+pub const UTF8BUFFSZ: usize = 8;
+
+
+pub fn luaO_hexavalue(c: i32) -> i32 {
+    if lisdigit(c) { c - '0' as i32 }
+    else { (ltolower(c) - 'a' as i32) + 10 }
+}
+
+
+/*
+** Encodes 'x' in UTF-8 at the end of the returned buffer, also returning
+** the number of bytes used.
+*/
+pub fn luaO_utf8esc(mut x: u32) -> ([u8; UTF8BUFFSZ], usize) {
+    let mut buff = [0u8; UTF8BUFFSZ];
+    let mut n = 1;  /* number of bytes put in buffer (backwards) */
+    debug_assert!(x <= 0x10FFFF);
+    if x < 0x80 {  /* ascii? */
+        buff[UTF8BUFFSZ - 1] = x as u8;
+    } else {  /* need continuation bytes */
+        let mut mfb = 0x3fu32;  /* maximum that fits in first byte */
+        loop {  /* add continuation bytes */
+            buff[UTF8BUFFSZ - n] = (0x80 | (x & 0x3f)) as u8;
+            n += 1;
+            x >>= 6;  /* remove added bits */
+            mfb >>= 1;  /* now there is one less bit available in first byte */
+            if x <= mfb { break; }  /* still needs continuation byte? */
+        }
+        buff[UTF8BUFFSZ - n] = ((!mfb << 1) | x) as u8;  /* add first byte */
+    }
+    (buff, n)
+}
+
+
+/* the C interfaces of the two functions above, for 'llex.c' */
+#[cfg(rust_lobject)]
+#[export_name = "luaO_hexavalue"]
+extern "C-unwind" fn hexavalue(c: c_int) -> c_int {
+    luaO_hexavalue(c)
+}
+
+#[cfg(rust_lobject)]
+#[export_name = "luaO_utf8esc"]
+unsafe extern "C-unwind" fn utf8esc(buff: *mut c_char, x: c_ulong) -> c_int {
+    let (b, n) = luaO_utf8esc(x as u32);
+    ptr::copy_nonoverlapping(b.as_ptr(), buff as *mut u8, UTF8BUFFSZ);
+    n as c_int
+}
+
+
+/*
+** {==================================================================
+** Conversion of strings to numbers
+** ===================================================================
+*/
+
+/// A numeral converted by `str2num`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Numeral {
+    Int(lua_Integer),
+    Flt(lua_Number),
+}
+
+
+/*
+** 2^k, for 'k' in the range of normal floats
+*/
+fn pow2(k: i64) -> f64 {
+    debug_assert!((-1022..=1023).contains(&k));
+    f64::from_bits(((k + 1023) as u64) << 52)
+}
+
+
+/*
+** Correctly rounded value of 'm * 2^e', where 'sticky' tells whether
+** nonzero digits were dropped after 'm' (round half to even, as 'strtod').
+*/
+fn ldexp_round(mut m: u64, mut e: i64, sticky: bool) -> f64 {
+    if m == 0 { return 0.0; }
+    let lz = m.leading_zeros();
+    m <<= lz;  /* normalize: most significant bit is bit 63 */
+    e -= lz as i64;
+    let top = e + 63;  /* exponent of the most significant bit */
+    if top > 1023 { return f64::INFINITY; }
+    /* number of bits that fit in the result (fewer for subnormals) */
+    let bits = if top >= -1022 { 53 } else { 53 - (-1022 - top) };
+    if bits <= 0 {  /* below the smallest subnormal */
+        let above_half = bits == 0 && (m > 1 << 63 || sticky);
+        return if above_half { f64::from_bits(1) } else { 0.0 };
+    }
+    let shift = 64 - bits as u32;
+    let mut q = m >> shift;
+    let rem = m & ((1u64 << shift) - 1);
+    let half = 1u64 << (shift - 1);
+    if rem > half || (rem == half && (sticky || q & 1 == 1)) {
+        q += 1;  /* round up (may carry into a new bit, which is exact) */
+    }
+    let k = e + shift as i64;
+    /* q * 2^k in two exact steps, keeping the intermediate value normal */
+    if k < -1022 { (q as f64) * pow2(-1000) * pow2(k + 1000) }
+    else { (q as f64) * pow2(k) }
+}
+
+
+/*
+** Longest prefix of 's' that is a hexadecimal float after its '0x'
+** prefix, as read by 'strtod'. Returns None when there are no digits.
+*/
+fn strx2number(s: &[u8], mut i: usize) -> Option<(lua_Number, usize)> {
+    let mut m = 0u64;  /* significant digits */
+    let mut nsig = 0;  /* number of significant digits in 'm' */
+    let mut e = 0i64;  /* exponent correction */
+    let mut sticky = false;  /* dropped any nonzero digit? */
+    let mut any = false;  /* read any digit? */
+    let mut hasdot = false;
+    let c = |i: usize| if i < s.len() { s[i] as i32 } else { 0 };
+    loop {
+        if c(i) == '.' as i32 && !hasdot {
+            hasdot = true;
+        } else if lisxdigit(c(i)) {
+            let d = luaO_hexavalue(c(i)) as u64;
+            any = true;
+            if m == 0 && d == 0 {  /* leading zero? */
+                if hasdot { e -= 4; }
+            } else if nsig < 16 {  /* fits in 'm'? */
+                m = (m << 4) | d;
+                nsig += 1;
+                if hasdot { e -= 4; }
+            } else {  /* too many digits; ignore, but still count for exponent */
+                sticky |= d != 0;
+                if !hasdot { e += 4; }
+            }
+        } else {
+            break;
+        }
+        i += 1;
+    }
+    if !any { return None; }
+    if ltolower(c(i)) == 'p' as i32 {  /* exponent part? */
+        let mut j = i + 1;
+        let neg = c(j) == '-' as i32;
+        if neg || c(j) == '+' as i32 { j += 1; }
+        if lisdigit(c(j)) {
+            let mut exp = 0i64;
+            while lisdigit(c(j)) {
+                exp = (exp * 10 + (c(j) - '0' as i32) as i64).min(1 << 20);
+                j += 1;
+            }
+            e += if neg { -exp } else { exp };
+            i = j;
+        }
+    }
+    Some((ldexp_round(m, e, sticky), i))
+}
+
+
+/*
+** Conversion of a prefix of 's' to a float, following the rules of C99
+** 'strtod' (without 'inf' and 'nan'). Returns the value and the length
+** of the prefix, or None if nothing could be recognized.
+*/
+fn str2number(s: &[u8]) -> Option<(lua_Number, usize)> {
+    let c = |i: usize| if i < s.len() { s[i] as i32 } else { 0 };
+    let mut i = 0;
+    while lisspace(c(i)) { i += 1; }
+    let neg = c(i) == '-' as i32;
+    if neg || c(i) == '+' as i32 { i += 1; }
+    let (n, end) = if c(i) == '0' as i32 && ltolower(c(i + 1)) == 'x' as i32 {
+        match strx2number(s, i + 2) {
+            Some(r) => r,
+            None => (0.0, i + 1),  /* only the '0' is a numeral */
+        }
+    } else {
+        let start = i;
+        let mut ndigits = 0;
+        while lisdigit(c(i)) { i += 1; ndigits += 1; }
+        if c(i) == '.' as i32 {
+            i += 1;
+            while lisdigit(c(i)) { i += 1; ndigits += 1; }
+        }
+        if ndigits == 0 { return None; }
+        if ltolower(c(i)) == 'e' as i32 {  /* exponent part? */
+            let mut j = i + 1;
+            if c(j) == '-' as i32 || c(j) == '+' as i32 { j += 1; }
+            if lisdigit(c(j)) {
+                while lisdigit(c(j)) { j += 1; }
+                i = j;
+            }
+        }
+        /* the syntax is checked, so Rust's (correctly rounded) parser accepts it */
+        let digits = ::std::str::from_utf8(&s[start..i]).unwrap();
+        (digits.parse::<lua_Number>().unwrap(), i)
+    };
+    Some((if neg { -n } else { n }, end))
+}
+
+
+fn l_str2dloc(s: &[u8]) -> Option<lua_Number> {
+    let (n, mut end) = str2number(s)?;  /* try to convert */
+    while end < s.len() && lisspace(s[end] as i32) { end += 1; }  /* skip trailing spaces */
+    if end == s.len() { Some(n) } else { None }  /* OK if no trailing characters */
+}
+
+
+/*
+** Convert string 's' to a Lua number. This function accepts both the
+** current locale or a dot as the radix mark (Rust never uses the
+** locale, so only the dot remains).
+*/
+fn l_str2d(s: &[u8]) -> Option<lua_Number> {
+    let mode = s.iter().find(|&&c| b".xXnN".contains(&c)).map(|&c| ltolower(c as i32));
+    if mode == Some('n' as i32) {  /* reject 'inf' and 'nan' */
+        return None;
+    }
+    l_str2dloc(s)
+}
+
+
+const MAXBY10: u64 = luaconf::LUA_MAXINTEGER as u64 / 10;
+const MAXLASTD: u64 = luaconf::LUA_MAXINTEGER as u64 % 10;
+
+fn l_str2int(s: &[u8]) -> Option<lua_Integer> {
+    let c = |i: usize| if i < s.len() { s[i] as i32 } else { 0 };
+    let mut a = 0u64;
+    let mut empty = true;
+    let mut i = 0;
+    while lisspace(c(i)) { i += 1; }  /* skip initial spaces */
+    let neg = c(i) == '-' as i32;
+    if neg || c(i) == '+' as i32 { i += 1; }
+    if c(i) == '0' as i32 && (c(i + 1) == 'x' as i32 || c(i + 1) == 'X' as i32) {  /* hex? */
+        i += 2;  /* skip '0x' */
+        while lisxdigit(c(i)) {
+            a = a.wrapping_mul(16).wrapping_add(luaO_hexavalue(c(i)) as u64);
+            empty = false;
+            i += 1;
+        }
+    } else {  /* decimal */
+        while lisdigit(c(i)) {
+            let d = (c(i) - '0' as i32) as u64;
+            if a >= MAXBY10 && (a > MAXBY10 || d > MAXLASTD + neg as u64) {  /* overflow? */
+                return None;  /* do not accept it (as integer) */
+            }
+            a = a * 10 + d;
+            empty = false;
+            i += 1;
+        }
+    }
+    while lisspace(c(i)) { i += 1; }  /* skip trailing spaces */
+    if empty || i != s.len() { return None; }  /* something wrong in the numeral */
+    Some((if neg { 0u64.wrapping_sub(a) } else { a }) as lua_Integer)
+}
+
+
+/// Converts a numeral with the rules of `luaO_str2num`: integers when
+/// they fit (hexadecimal ones wrap around), floats otherwise.
+pub fn str2num(s: &[u8]) -> Option<Numeral> {
+    if let Some(i) = l_str2int(s) {
+        Some(Numeral::Int(i))
+    } else {
+        l_str2d(s).map(Numeral::Flt)
+    }
+}
+
+/* }====================================================== */
+
+
+#[cfg(rust_lobject)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaO_str2num(s: *const c_char, o: *mut TValue) -> size_t {
+    let s = CStr::from_ptr(s).to_bytes();
+    match str2num(s) {
+        Some(Numeral::Int(i)) => setivalue(o, i),
+        Some(Numeral::Flt(n)) => setfltvalue(o, n),
+        None => return 0,  /* conversion failed */
+    }
+    s.len() + 1  /* success; return string size */
+}
+
+
+/*
+** Convert a float to a string as "%.14g" ('LUAI_NUMFFORMAT') does in
+** the C locale.
+*/
+pub(crate) fn lua_number2str(n: lua_Number) -> String {
+    const PRECISION: i32 = 14;
+    if n.is_nan() {
+        return if n.is_sign_negative() { "-nan" } else { "nan" }.to_string();
+    } else if n.is_infinite() {
+        return if n < 0.0 { "-inf" } else { "inf" }.to_string();
+    }
+    /* the exponent of 'n' once rounded to PRECISION digits decides the style */
+    let sci = format!("{:.*e}", PRECISION as usize - 1, n);
+    let epos = sci.find('e').unwrap();
+    let x: i32 = sci[epos + 1..].parse().unwrap();
+    let strip = |s: &str| -> String {  /* remove trailing zeros (and a dangling dot) */
+        if s.contains('.') { s.trim_end_matches('0').trim_end_matches('.').to_string() }
+        else { s.to_string() }
+    };
+    if !(-4..PRECISION).contains(&x) {
+        format!("{}e{}{:02}", strip(&sci[..epos]), if x < 0 { '-' } else { '+' }, x.abs())
+    } else {
+        strip(&format!("{:.*}", (PRECISION - 1 - x) as usize, n))
+    }
+}
+
+
+/*
+** Convert a number to a string; floats that look like integers get a
+** '.0', always with a dot as Lua reads it back regardless of the locale
+*/
+fn tostringbuff(n: Numeral) -> String {
+    match n {
+        Numeral::Int(i) => i.to_string(),
+        Numeral::Flt(f) => {
+            let mut buff = lua_number2str(f);
+            if buff.bytes().all(|c| c == b'-' || lisdigit(c as i32)) {  /* looks like an int? */
+                buff.push_str(".0");  /* adds '.0' to result */
+            }
+            buff
+        }
+    }
+}
+
+
+/*
+** Convert a number object to a string
+*/
+#[cfg(rust_lobject)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaO_tostring(L: *mut lua_State, obj: StkId) {
+    debug_assert!((*obj).ttisnumber());
+    let buff = if (*obj).ttisinteger() { tostringbuff(Numeral::Int((*obj).ivalue())) }
+               else { tostringbuff(Numeral::Flt((*obj).fltvalue())) };
+    setsvalue(obj, luaS_newlstr(L, buff.as_ptr() as *const c_char, buff.len()));
+}
+
+
+/// An argument for a `%` option of `lua_pushfstring`.
+#[derive(Clone, Copy, Debug)]
+pub enum FArg<'a> {
+    Str(&'a [u8]),  /* '%s' */
+    Int(lua_Integer),  /* '%d', '%I', '%c' and '%U' */
+    Num(lua_Number),  /* '%f' */
+    Ptr(*const c_void),  /* '%p' */
+}
+
+impl<'a> From<&'a str> for FArg<'a> {
+    fn from(s: &'a str) -> FArg<'a> { FArg::Str(s.as_bytes()) }
+}
+
+impl<'a> From<&'a [u8]> for FArg<'a> {
+    fn from(s: &'a [u8]) -> FArg<'a> { FArg::Str(s) }
+}
+
+impl<'a> From<&'a CStr> for FArg<'a> {
+    fn from(s: &'a CStr) -> FArg<'a> { FArg::Str(s.to_bytes()) }
+}
+
+impl<'a> From<char> for FArg<'a> {
+    fn from(c: char) -> FArg<'a> { FArg::Int(c as lua_Integer) }
+}
+
+impl<'a> From<c_int> for FArg<'a> {
+    fn from(i: c_int) -> FArg<'a> { FArg::Int(i as lua_Integer) }
+}
+
+impl<'a> From<lua_Integer> for FArg<'a> {
+    fn from(i: lua_Integer) -> FArg<'a> { FArg::Int(i) }
+}
+
+impl<'a> From<lua_Number> for FArg<'a> {
+    fn from(n: lua_Number) -> FArg<'a> { FArg::Num(n) }
+}
+
+impl<'a, T> From<*const T> for FArg<'a> {
+    fn from(p: *const T) -> FArg<'a> { FArg::Ptr(p as *const c_void) }
+}
+
+impl<'a, T> From<*mut T> for FArg<'a> {
+    fn from(p: *mut T) -> FArg<'a> { FArg::Ptr(p as *const c_void) }
+}
+
+
+unsafe fn pushstr(L: *mut lua_State, str: &[u8]) {
+    setsvalue((*L).top, luaS_newlstr(L, str.as_ptr() as *const c_char, str.len()));
+    luaD_inctop(L);
+}
+
+
+/*
+** this function handles only '%d', '%c', '%f', '%p', and '%s'
+   conventional formats, plus Lua-specific '%I' and '%U'
+*/
+pub unsafe fn pushfstring(L: *mut lua_State, fmt: &[u8], args: &[FArg]) -> *const c_char {
+    let mut buff = Vec::new();
+    let mut args = args.iter();
+    let mut fmt = fmt;
+    while let Some(e) = fmt.iter().position(|&c| c == b'%') {
+        buff.extend_from_slice(&fmt[..e]);
+        let opt = fmt.get(e + 1).cloned().unwrap_or(0);
+        if opt == b'%' {
+            buff.push(b'%');
+        } else if b"scdIfpU".contains(&opt) {
+            match (opt, args.next()) {
+                (b's', Some(&FArg::Str(s))) => buff.extend_from_slice(s),
+                (b'c', Some(&FArg::Int(c))) => {  /* an 'int' as a character */
+                    let c = c as u8;
+                    if lisprint(c as i32) { buff.push(c); }
+                    else { buff.extend(format!("<\\{}>", c).bytes()); }  /* print its code */
+                }
+                (b'd', Some(&FArg::Int(i))) | (b'I', Some(&FArg::Int(i))) =>
+                    buff.extend(tostringbuff(Numeral::Int(i)).bytes()),
+                (b'f', Some(&FArg::Num(n))) => buff.extend(tostringbuff(Numeral::Flt(n)).bytes()),
+                (b'p', Some(&FArg::Ptr(p))) => buff.extend(format!("{:p}", p).bytes()),
+                (b'U', Some(&FArg::Int(x))) => {  /* an 'int' as a UTF-8 sequence */
+                    let (u, l) = luaO_utf8esc(x as u32);
+                    buff.extend_from_slice(&u[UTF8BUFFSZ - l..]);
+                }
+                _ => luaG_runerror(L, c_str!("bad argument for option '%%%c' to 'lua_pushfstring'"),
+                                   opt as c_int),
+            }
+        } else {
+            luaG_runerror(L, c_str!("invalid option '%%%c' to 'lua_pushfstring'"), opt as c_int);
+        }
+        fmt = &fmt[e + 2..];
+    }
+    buff.extend_from_slice(fmt);
+    pushstr(L, &buff);
+    svalue((*L).top.offset(-1))
+}
+
+
+/* an argument collected from a 'va_list' by 'luaO_pushvfstring' */
+#[repr(C)]
+pub union CArg {
+    s: *const c_char,
+    i: lua_Integer,
+    n: lua_Number,
+    p: *const c_void,
+}
+
+/*
+** 'luaO_pushvfstring' for the arguments in 'argv', one for each option
+** of 'fmt' up to the first invalid one
+*/
+#[no_mangle]  /* not in 'lobject.c', so always defined */
+pub unsafe extern "C-unwind" fn luaO_pushfargs(L: *mut lua_State, fmt: *const c_char,
+                                               argv: *const CArg) -> *const c_char {
+    let fmt = CStr::from_ptr(fmt).to_bytes();
+    let mut args = Vec::new();
+    let mut opts = fmt.split(|&c| c == b'%').skip(1);
+    while let Some(opt) = opts.next() {
+        let argv = argv.add(args.len());
+        args.push(match opt.first() {
+            Some(b's') if (*argv).s.is_null() => FArg::Str(b"(null)"),
+            Some(b's') => FArg::Str(CStr::from_ptr((*argv).s).to_bytes()),
+            Some(b'c') | Some(b'd') | Some(b'I') | Some(b'U') => FArg::Int((*argv).i),
+            Some(b'f') => FArg::Num((*argv).n),
+            Some(b'p') => FArg::Ptr((*argv).p),
+            None => { opts.next(); continue; }  /* '%%' */
+            Some(_) => break,
+        });
+    }
+    pushfstring(L, fmt, &args)
+}
+
+
+const RETS: &[u8] = b"...";
+const PRE: &[u8] = b"[string \"";
+const POS: &[u8] = b"\"]";
+
+/*
+** A printable version of the chunk name 'source' that fits in 'bufflen'
+** bytes (including the final '\0', which is not in the result)
+*/
+pub fn chunkid(source: &[u8], bufflen: usize) -> Vec<u8> {
+    let l = source.len();
+    let mut out = Vec::with_capacity(bufflen);
+    match source.first() {
+        Some(b'=') => {  /* 'literal' source */
+            if l <= bufflen {  /* small enough? */
+                out.extend_from_slice(&source[1..]);
+            } else {  /* truncate it */
+                out.extend_from_slice(&source[1..bufflen]);
+            }
+        }
+        Some(b'@') => {  /* file name */
+            if l <= bufflen {  /* small enough? */
+                out.extend_from_slice(&source[1..]);
+            } else {  /* add '...' before rest of name */
+                out.extend_from_slice(RETS);
+                let bufflen = bufflen - RETS.len();
+                out.extend_from_slice(&source[1 + l - bufflen..]);
+            }
+        }
+        _ => {  /* string; format as [string "source"] */
+            let nl = source.iter().position(|&c| c == b'\n');  /* find first new line (if any) */
+            out.extend_from_slice(PRE);  /* add prefix */
+            let bufflen = bufflen - (PRE.len() + RETS.len() + POS.len()) - 1;  /* save space for prefix+suffix+'\0' */
+            if l < bufflen && nl.is_none() {  /* small one-line source? */
+                out.extend_from_slice(source);  /* keep it */
+            } else {
+                let l = cmp::min(nl.unwrap_or(l), bufflen);  /* stop at first newline */
+                out.extend_from_slice(&source[..l]);
+                out.extend_from_slice(RETS);
+            }
+            out.extend_from_slice(POS);
+        }
+    }
+    out
+}
+
+#[cfg(rust_lobject)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaO_chunkid(out: *mut c_char, source: *const c_char, bufflen: size_t) {
+    let id = chunkid(CStr::from_ptr(source).to_bytes(), bufflen);
+    ptr::copy_nonoverlapping(id.as_ptr() as *const c_char, out, id.len());
+    *out.add(id.len()) = 0;
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::{chunkid, str2num, tostringbuff, Numeral};
+
+    #[test]
+    fn test_str2num() {
+        assert_eq!(str2num(b"10"), Some(Numeral::Int(10)));
+        assert_eq!(str2num(b" -0x10 "), Some(Numeral::Int(-16)));
+        assert_eq!(str2num(b"0xffffffffffffffff"), Some(Numeral::Int(-1)));
+        assert_eq!(str2num(b"9223372036854775808"), Some(Numeral::Flt(9223372036854775808.0)));
+        assert_eq!(str2num(b"-9223372036854775808"), Some(Numeral::Int(i64::MIN)));
+        assert_eq!(str2num(b".5"), Some(Numeral::Flt(0.5)));
+        assert_eq!(str2num(b"0x.8p1"), Some(Numeral::Flt(1.0)));
+        assert_eq!(str2num(b"0xA.a"), Some(Numeral::Flt(10.625)));
+        assert_eq!(str2num(b"0x1p-1074"), Some(Numeral::Flt(5e-324)));
+        assert_eq!(str2num(b"0x1p-1075"), Some(Numeral::Flt(0.0)));
+        assert_eq!(str2num(b"0x1.000000000000080000001p0"),
+                   Some(Numeral::Flt(1.0000000000000002)));  /* above half: rounds up */
+        assert_eq!(str2num(b"0x1.00000000000008p0"), Some(Numeral::Flt(1.0)));  /* tie: to even */
+        assert_eq!(str2num(b"1e400"), Some(Numeral::Flt(f64::INFINITY)));
+        for s in [&b"1e"[..], b"0x", b"0xp1", b"1..2", b"inf", b"nan", b"1 2", b"", b"3a"].iter() {
+            assert_eq!(str2num(s), None);
+        }
+    }
+
+    #[test]
+    fn test_tostring() {
+        let f = |n: f64| tostringbuff(Numeral::Flt(n));
+        assert_eq!(tostringbuff(Numeral::Int(i64::MIN)), "-9223372036854775808");
+        assert_eq!(f(1.0), "1.0");
+        assert_eq!(f(-0.0), "-0.0");
+        assert_eq!(f(0.1), "0.1");
+        assert_eq!(f(1.0 / 3.0), "0.33333333333333");
+        assert_eq!(f(1e14), "1e+14");
+        assert_eq!(f(123456789012345.0), "1.2345678901234e+14");
+        assert_eq!(f(99999999999999.9), "1e+14");
+        assert_eq!(f(0.0001), "0.0001");
+        assert_eq!(f(0.00001), "1e-05");
+        assert_eq!(f(2f64.powi(63)), "9.2233720368548e+18");
+        assert_eq!(f(1e308 * 10.0), "inf");
+        assert_eq!(f(-1e308 * 10.0), "-inf");
+        assert_eq!(f(5e-324), "4.9406564584125e-324");
+    }
+
+    #[test]
+    fn test_chunkid() {
+        assert_eq!(chunkid(b"=stdin", 60), b"stdin");
+        assert_eq!(chunkid(b"@file.lua", 60), b"file.lua");
+        assert_eq!(chunkid(b"@abcdefghij", 8), b"...ghij");
+        assert_eq!(chunkid(b"=abcdefghij", 8), b"abcdefg");
+        assert_eq!(chunkid(b"return 1", 60), &b"[string \"return 1\"]"[..]);
+        assert_eq!(chunkid(b"x = 1\ny = 2", 60), &b"[string \"x = 1...\"]"[..]);
+        assert_eq!(chunkid(b"0123456789", 20), &b"[string \"01234...\"]"[..]);
+    }
+}
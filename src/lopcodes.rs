@@ -0,0 +1,435 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Opcodes for Lua virtual machine.
+
+#![allow(dead_code, non_camel_case_types, non_snake_case, non_upper_case_globals)]
+
+use libc::c_char;
+use std::{mem, ptr};
+
+
+/*===========================================================================
+  We assume that instructions are unsigned numbers.
+  All instructions have an opcode in the first 6 bits.
+  Instructions can have the following fields:
+	'A' : 8 bits
+	'B' : 9 bits
+	'C' : 9 bits
+	'Ax' : 26 bits ('A', 'B', and 'C' together)
+	'Bx' : 18 bits ('B' and 'C' together)
+	'sBx' : signed Bx
+
+  A signed argument is represented in excess K; that is, the number
+  value is the unsigned value minus K. K is exactly the maximum value
+  for that argument (so that -max is represented by 0, and +max is
+  represented by 2*max), which is half the maximum for the corresponding
+  unsigned argument.
+===========================================================================*/
+
+pub type Instruction = u32;
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpMode { iABC, iABx, iAsBx, iAx }  /* basic instruction format */
+
+
+/*
+** size and position of opcode arguments.
+*/
+pub const SIZE_C: u32 = 9;
+pub const SIZE_B: u32 = 9;
+pub const SIZE_Bx: u32 = SIZE_C + SIZE_B;
+pub const SIZE_A: u32 = 8;
+pub const SIZE_Ax: u32 = SIZE_C + SIZE_B + SIZE_A;
+
+pub const SIZE_OP: u32 = 6;
+
+pub const POS_OP: u32 = 0;
+pub const POS_A: u32 = POS_OP + SIZE_OP;
+pub const POS_C: u32 = POS_A + SIZE_A;
+pub const POS_B: u32 = POS_C + SIZE_C;
+pub const POS_Bx: u32 = POS_C;
+pub const POS_Ax: u32 = POS_A;
+
+
+/*
+** limits for opcode arguments.
+*/
+pub const MAXARG_Bx: i32 = (1 << SIZE_Bx) - 1;
+pub const MAXARG_sBx: i32 = MAXARG_Bx >> 1;  /* 'sBx' is signed */
+pub const MAXARG_Ax: i32 = (1 << SIZE_Ax) - 1;
+pub const MAXARG_A: i32 = (1 << SIZE_A) - 1;
+pub const MAXARG_B: i32 = (1 << SIZE_B) - 1;
+pub const MAXARG_C: i32 = (1 << SIZE_C) - 1;
+
+
+/* creates a mask with 'n' 1 bits at position 'p' */
+fn MASK1(n: u32, p: u32) -> Instruction { (!((!0 as Instruction) << n)) << p }
+
+/* creates a mask with 'n' 0 bits at position 'p' */
+fn MASK0(n: u32, p: u32) -> Instruction { !MASK1(n, p) }
+
+
+/*
+** the following functions help to manipulate instructions
+*/
+
+pub fn GET_OPCODE(i: Instruction) -> OpCode { OpCode::from_u32((i >> POS_OP) & MASK1(SIZE_OP, 0)) }
+pub fn SET_OPCODE(i: &mut Instruction, o: OpCode) {
+    *i = (*i & MASK0(SIZE_OP, POS_OP)) | (((o as Instruction) << POS_OP) & MASK1(SIZE_OP, POS_OP));
+}
+
+fn getarg(i: Instruction, pos: u32, size: u32) -> i32 { ((i >> pos) & MASK1(size, 0)) as i32 }
+fn setarg(i: &mut Instruction, v: i32, pos: u32, size: u32) {
+    *i = (*i & MASK0(size, pos)) | (((v as Instruction) << pos) & MASK1(size, pos));
+}
+
+pub fn GETARG_A(i: Instruction) -> i32 { getarg(i, POS_A, SIZE_A) }
+pub fn SETARG_A(i: &mut Instruction, v: i32) { setarg(i, v, POS_A, SIZE_A) }
+
+pub fn GETARG_B(i: Instruction) -> i32 { getarg(i, POS_B, SIZE_B) }
+pub fn SETARG_B(i: &mut Instruction, v: i32) { setarg(i, v, POS_B, SIZE_B) }
+
+pub fn GETARG_C(i: Instruction) -> i32 { getarg(i, POS_C, SIZE_C) }
+pub fn SETARG_C(i: &mut Instruction, v: i32) { setarg(i, v, POS_C, SIZE_C) }
+
+pub fn GETARG_Bx(i: Instruction) -> i32 { getarg(i, POS_Bx, SIZE_Bx) }
+pub fn SETARG_Bx(i: &mut Instruction, v: i32) { setarg(i, v, POS_Bx, SIZE_Bx) }
+
+pub fn GETARG_Ax(i: Instruction) -> i32 { getarg(i, POS_Ax, SIZE_Ax) }
+pub fn SETARG_Ax(i: &mut Instruction, v: i32) { setarg(i, v, POS_Ax, SIZE_Ax) }
+
+pub fn GETARG_sBx(i: Instruction) -> i32 { GETARG_Bx(i) - MAXARG_sBx }
+pub fn SETARG_sBx(i: &mut Instruction, b: i32) { SETARG_Bx(i, b + MAXARG_sBx) }
+
+
+pub fn CREATE_ABC(o: OpCode, a: i32, b: i32, c: i32) -> Instruction {
+    ((o as Instruction) << POS_OP)
+        | ((a as Instruction) << POS_A)
+        | ((b as Instruction) << POS_B)
+        | ((c as Instruction) << POS_C)
+}
+
+pub fn CREATE_ABx(o: OpCode, a: i32, bc: u32) -> Instruction {
+    ((o as Instruction) << POS_OP)
+        | ((a as Instruction) << POS_A)
+        | (bc << POS_Bx)
+}
+
+pub fn CREATE_Ax(o: OpCode, a: i32) -> Instruction {
+    ((o as Instruction) << POS_OP)
+        | ((a as Instruction) << POS_Ax)
+}
+
+
+/*
+** Macros to operate RK indices
+*/
+
+/* this bit 1 means constant (0 means register) */
+pub const BITRK: i32 = 1 << (SIZE_B - 1);
+
+/* test whether value is a constant */
+pub fn ISK(x: i32) -> bool { x & BITRK != 0 }
+
+/* gets the index of the constant */
+pub fn INDEXK(r: i32) -> i32 { r & !BITRK }
+
+pub const MAXINDEXRK: i32 = BITRK - 1;
+
+/* code a constant index as a RK value */
+pub fn RKASK(x: i32) -> i32 { x | BITRK }
+
+
+/*
+** invalid register that fits in 8 bits
+*/
+pub const NO_REG: i32 = MAXARG_A;
+
+
+/*
+** R(x) - register
+** Kst(x) - constant (in constant table)
+** RK(x) == if ISK(x) then Kst(INDEXK(x)) else R(x)
+*/
+
+
+/*
+** grep "ORDER OP" if you change these enums
+*/
+
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpCode {
+/*----------------------------------------------------------------------
+name		args	description
+------------------------------------------------------------------------*/
+OP_MOVE,/*	A B	R(A) := R(B)					*/
+OP_LOADK,/*	A Bx	R(A) := Kst(Bx)					*/
+OP_LOADKX,/*	A 	R(A) := Kst(extra arg)				*/
+OP_LOADBOOL,/*	A B C	R(A) := (Bool)B; if (C) pc++			*/
+OP_LOADNIL,/*	A B	R(A), R(A+1), ..., R(A+B) := nil		*/
+OP_GETUPVAL,/*	A B	R(A) := UpValue[B]				*/
+
+OP_GETTABUP,/*	A B C	R(A) := UpValue[B][RK(C)]			*/
+OP_GETTABLE,/*	A B C	R(A) := R(B)[RK(C)]				*/
+
+OP_SETTABUP,/*	A B C	UpValue[A][RK(B)] := RK(C)			*/
+OP_SETUPVAL,/*	A B	UpValue[B] := R(A)				*/
+OP_SETTABLE,/*	A B C	R(A)[RK(B)] := RK(C)				*/
+
+OP_NEWTABLE,/*	A B C	R(A) := {} (size = B,C)				*/
+
+OP_SELF,/*	A B C	R(A+1) := R(B); R(A) := R(B)[RK(C)]		*/
+
+OP_ADD,/*	A B C	R(A) := RK(B) + RK(C)				*/
+OP_SUB,/*	A B C	R(A) := RK(B) - RK(C)				*/
+OP_MUL,/*	A B C	R(A) := RK(B) * RK(C)				*/
+OP_MOD,/*	A B C	R(A) := RK(B) % RK(C)				*/
+OP_POW,/*	A B C	R(A) := RK(B) ^ RK(C)				*/
+OP_DIV,/*	A B C	R(A) := RK(B) / RK(C)				*/
+OP_IDIV,/*	A B C	R(A) := RK(B) // RK(C)				*/
+OP_BAND,/*	A B C	R(A) := RK(B) & RK(C)				*/
+OP_BOR,/*	A B C	R(A) := RK(B) | RK(C)				*/
+OP_BXOR,/*	A B C	R(A) := RK(B) ~ RK(C)				*/
+OP_SHL,/*	A B C	R(A) := RK(B) << RK(C)				*/
+OP_SHR,/*	A B C	R(A) := RK(B) >> RK(C)				*/
+OP_UNM,/*	A B	R(A) := -R(B)					*/
+OP_BNOT,/*	A B	R(A) := ~R(B)					*/
+OP_NOT,/*	A B	R(A) := not R(B)				*/
+OP_LEN,/*	A B	R(A) := length of R(B)				*/
+
+OP_CONCAT,/*	A B C	R(A) := R(B).. ... ..R(C)			*/
+
+OP_JMP,/*	A sBx	pc+=sBx; if (A) close all upvalues >= R(A - 1)	*/
+OP_EQ,/*	A B C	if ((RK(B) == RK(C)) ~= A) then pc++		*/
+OP_LT,/*	A B C	if ((RK(B) <  RK(C)) ~= A) then pc++		*/
+OP_LE,/*	A B C	if ((RK(B) <= RK(C)) ~= A) then pc++		*/
+
+OP_TEST,/*	A C	if not (R(A) <=> C) then pc++			*/
+OP_TESTSET,/*	A B C	if (R(B) <=> C) then R(A) := R(B) else pc++	*/
+
+OP_CALL,/*	A B C	R(A), ... ,R(A+C-2) := R(A)(R(A+1), ... ,R(A+B-1)) */
+OP_TAILCALL,/*	A B C	return R(A)(R(A+1), ... ,R(A+B-1))		*/
+OP_RETURN,/*	A B	return R(A), ... ,R(A+B-2)	(see note)	*/
+
+OP_FORLOOP,/*	A sBx	R(A)+=R(A+2);
+			if R(A) <?= R(A+1) then { pc+=sBx; R(A+3)=R(A) }*/
+OP_FORPREP,/*	A sBx	R(A)-=R(A+2); pc+=sBx				*/
+
+OP_TFORCALL,/*	A C	R(A+3), ... ,R(A+2+C) := R(A)(R(A+1), R(A+2));	*/
+OP_TFORLOOP,/*	A sBx	if R(A+1) ~= nil then { R(A)=R(A+1); pc += sBx }*/
+
+OP_SETLIST,/*	A B C	R(A)[(C-1)*FPF+i] := R(A+i), 1 <= i <= B	*/
+
+OP_CLOSURE,/*	A Bx	R(A) := closure(KPROTO[Bx])			*/
+
+OP_VARARG,/*	A B	R(A), R(A+1), ..., R(A+B-2) = vararg		*/
+
+OP_EXTRAARG/*	Ax	extra (larger) argument for previous opcode	*/
+}
+
+
+pub const NUM_OPCODES: usize = OpCode::OP_EXTRAARG as usize + 1;
+
+impl OpCode {
+    /* the opcode numbered 'o', which must be valid */
+    pub fn from_u32(o: u32) -> OpCode {
+        assert!((o as usize) < NUM_OPCODES, "invalid opcode {}", o);
+        unsafe { mem::transmute(o as u8) }
+    }
+}
+
+
+
+/*===========================================================================
+  Notes:
+  (*) In OP_CALL, if (B == 0) then B = top. If (C == 0), then 'top' is
+  set to last_result+1, so next open instruction (OP_CALL, OP_RETURN,
+  OP_SETLIST) may use 'top'.
+
+  (*) In OP_VARARG, if (B == 0) then use actual number of varargs and
+  set top (like in OP_CALL with C == 0).
+
+  (*) In OP_RETURN, if (B == 0) then return up to 'top'.
+
+  (*) In OP_SETLIST, if (B == 0) then B = 'top'; if (C == 0) then next
+  'instruction' is EXTRAARG(real C).
+
+  (*) In OP_LOADKX, the next 'instruction' is always EXTRAARG.
+
+  (*) For comparisons, A specifies what condition the test should accept
+  (true or false).
+
+  (*) All 'skips' (pc++) assume that next instruction is a jump.
+
+===========================================================================*/
+
+
+/* a name in 'luaP_opnames', which C reads as a 'const char *' */
+#[repr(transparent)]
+pub struct OpName(*const c_char);
+
+unsafe impl Sync for OpName {}
+
+macro_rules! opnames {
+    ($($name:expr),*) => {
+        /* ORDER OP */
+        pub static OPNAMES: [&'static str; NUM_OPCODES] = [$($name),*];
+
+        #[cfg_attr(rust_lopcodes, no_mangle)]  /* the same data as the C one otherwise */
+        pub static luaP_opnames: [OpName; NUM_OPCODES + 1] = [
+            $(OpName(concat!($name, "\0").as_ptr() as *const c_char),)*
+            OpName(ptr::null())
+        ];
+    }
+}
+
+opnames!(
+  "MOVE",
+  "LOADK",
+  "LOADKX",
+  "LOADBOOL",
+  "LOADNIL",
+  "GETUPVAL",
+  "GETTABUP",
+  "GETTABLE",
+  "SETTABUP",
+  "SETUPVAL",
+  "SETTABLE",
+  "NEWTABLE",
+  "SELF",
+  "ADD",
+  "SUB",
+  "MUL",
+  "MOD",
+  "POW",
+  "DIV",
+  "IDIV",
+  "BAND",
+  "BOR",
+  "BXOR",
+  "SHL",
+  "SHR",
+  "UNM",
+  "BNOT",
+  "NOT",
+  "LEN",
+  "CONCAT",
+  "JMP",
+  "EQ",
+  "LT",
+  "LE",
+  "TEST",
+  "TESTSET",
+  "CALL",
+  "TAILCALL",
+  "RETURN",
+  "FORLOOP",
+  "FORPREP",
+  "TFORCALL",
+  "TFORLOOP",
+  "SETLIST",
+  "CLOSURE",
+  "VARARG",
+  "EXTRAARG"
+);
+
+
+/*
+** masks for instruction properties. The format is:
+** bits 0-1: op mode
+** bits 2-3: C arg mode
+** bits 4-5: B arg mode
+** bit 6: instruction set register A
+** bit 7: operator is a test (next instruction must be a jump)
+*/
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpArgMask {
+    OpArgN,  /* argument is not used */
+    OpArgU,  /* argument is used */
+    OpArgR,  /* argument is a register or a jump offset */
+    OpArgK,  /* argument is a constant or register/constant */
+}
+
+const fn opmode(t: u8, a: u8, b: OpArgMask, c: OpArgMask, m: OpMode) -> u8 {
+    (t << 7) | (a << 6) | ((b as u8) << 4) | ((c as u8) << 2) | (m as u8)
+}
+
+use self::OpArgMask::*;
+use self::OpMode::*;
+
+/* ORDER OP */
+#[cfg_attr(rust_lopcodes, no_mangle)]  /* the same data as the C one otherwise */
+pub static luaP_opmodes: [u8; NUM_OPCODES] = [
+/*       T  A    B       C     mode		   opcode	*/
+  opmode(0, 1, OpArgR, OpArgN, iABC)		/* OP_MOVE */
+ ,opmode(0, 1, OpArgK, OpArgN, iABx)		/* OP_LOADK */
+ ,opmode(0, 1, OpArgN, OpArgN, iABx)		/* OP_LOADKX */
+ ,opmode(0, 1, OpArgU, OpArgU, iABC)		/* OP_LOADBOOL */
+ ,opmode(0, 1, OpArgU, OpArgN, iABC)		/* OP_LOADNIL */
+ ,opmode(0, 1, OpArgU, OpArgN, iABC)		/* OP_GETUPVAL */
+ ,opmode(0, 1, OpArgU, OpArgK, iABC)		/* OP_GETTABUP */
+ ,opmode(0, 1, OpArgR, OpArgK, iABC)		/* OP_GETTABLE */
+ ,opmode(0, 0, OpArgK, OpArgK, iABC)		/* OP_SETTABUP */
+ ,opmode(0, 0, OpArgU, OpArgN, iABC)		/* OP_SETUPVAL */
+ ,opmode(0, 0, OpArgK, OpArgK, iABC)		/* OP_SETTABLE */
+ ,opmode(0, 1, OpArgU, OpArgU, iABC)		/* OP_NEWTABLE */
+ ,opmode(0, 1, OpArgR, OpArgK, iABC)		/* OP_SELF */
+ ,opmode(0, 1, OpArgK, OpArgK, iABC)		/* OP_ADD */
+ ,opmode(0, 1, OpArgK, OpArgK, iABC)		/* OP_SUB */
+ ,opmode(0, 1, OpArgK, OpArgK, iABC)		/* OP_MUL */
+ ,opmode(0, 1, OpArgK, OpArgK, iABC)		/* OP_MOD */
+ ,opmode(0, 1, OpArgK, OpArgK, iABC)		/* OP_POW */
+ ,opmode(0, 1, OpArgK, OpArgK, iABC)		/* OP_DIV */
+ ,opmode(0, 1, OpArgK, OpArgK, iABC)		/* OP_IDIV */
+ ,opmode(0, 1, OpArgK, OpArgK, iABC)		/* OP_BAND */
+ ,opmode(0, 1, OpArgK, OpArgK, iABC)		/* OP_BOR */
+ ,opmode(0, 1, OpArgK, OpArgK, iABC)		/* OP_BXOR */
+ ,opmode(0, 1, OpArgK, OpArgK, iABC)		/* OP_SHL */
+ ,opmode(0, 1, OpArgK, OpArgK, iABC)		/* OP_SHR */
+ ,opmode(0, 1, OpArgR, OpArgN, iABC)		/* OP_UNM */
+ ,opmode(0, 1, OpArgR, OpArgN, iABC)		/* OP_BNOT */
+ ,opmode(0, 1, OpArgR, OpArgN, iABC)		/* OP_NOT */
+ ,opmode(0, 1, OpArgR, OpArgN, iABC)		/* OP_LEN */
+ ,opmode(0, 1, OpArgR, OpArgR, iABC)		/* OP_CONCAT */
+ ,opmode(0, 0, OpArgR, OpArgN, iAsBx)		/* OP_JMP */
+ ,opmode(1, 0, OpArgK, OpArgK, iABC)		/* OP_EQ */
+ ,opmode(1, 0, OpArgK, OpArgK, iABC)		/* OP_LT */
+ ,opmode(1, 0, OpArgK, OpArgK, iABC)		/* OP_LE */
+ ,opmode(1, 0, OpArgN, OpArgU, iABC)		/* OP_TEST */
+ ,opmode(1, 1, OpArgR, OpArgU, iABC)		/* OP_TESTSET */
+ ,opmode(0, 1, OpArgU, OpArgU, iABC)		/* OP_CALL */
+ ,opmode(0, 1, OpArgU, OpArgU, iABC)		/* OP_TAILCALL */
+ ,opmode(0, 0, OpArgU, OpArgN, iABC)		/* OP_RETURN */
+ ,opmode(0, 1, OpArgR, OpArgN, iAsBx)		/* OP_FORLOOP */
+ ,opmode(0, 1, OpArgR, OpArgN, iAsBx)		/* OP_FORPREP */
+ ,opmode(0, 0, OpArgN, OpArgU, iABC)		/* OP_TFORCALL */
+ ,opmode(0, 1, OpArgR, OpArgN, iAsBx)		/* OP_TFORLOOP */
+ ,opmode(0, 0, OpArgU, OpArgU, iABC)		/* OP_SETLIST */
+ ,opmode(0, 1, OpArgU, OpArgN, iABx)		/* OP_CLOSURE */
+ ,opmode(0, 1, OpArgU, OpArgN, iABC)		/* OP_VARARG */
+ ,opmode(0, 0, OpArgU, OpArgU, iAx)		/* OP_EXTRAARG */
+];
+
+
+pub fn getOpMode(m: OpCode) -> OpMode {
+    [iABC, iABx, iAsBx, iAx][(luaP_opmodes[m as usize] & 3) as usize]
+}
+pub fn getBMode(m: OpCode) -> OpArgMask {
+    [OpArgN, OpArgU, OpArgR, OpArgK][((luaP_opmodes[m as usize] >> 4) & 3) as usize]
+}
+pub fn getCMode(m: OpCode) -> OpArgMask {
+    [OpArgN, OpArgU, OpArgR, OpArgK][((luaP_opmodes[m as usize] >> 2) & 3) as usize]
+}
+pub fn testAMode(m: OpCode) -> bool { luaP_opmodes[m as usize] & (1 << 6) != 0 }
+pub fn testTMode(m: OpCode) -> bool { luaP_opmodes[m as usize] & (1 << 7) != 0 }
+
+
+/* number of list items to accumulate before a SETLIST instruction */
+pub const LFIELDS_PER_FLUSH: i32 = 50;
@@ -0,0 +1,424 @@
+// Copyright (C) 1994-2016 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Standard Operating System library.
+//!
+//! The time source used by `os.time`, `os.date` and `os.clock` and the
+//! variables seen by `os.getenv` can be replaced per state with
+//! `set_clock` and `set_environment`.
+
+use ffi::lua::{self, lua_State, lua_Integer, lua_Number};
+use ffi::lauxlib;
+use host;
+use libc::{self, c_char, c_int, time_t};
+use std::ffi::CStr;
+use std::{mem, ptr};
+use userdata;
+
+
+/// A source of time for the `os` library.
+pub trait Clock {
+    /// Returns the current calendar time, in seconds since the epoch.
+    fn time(&self) -> i64;
+
+    /// Returns the processor time used by the program, in seconds.
+    fn clock(&self) -> f64;
+}
+
+/// The clock of the operating system.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn time(&self) -> i64 {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_secs() as i64,
+            Err(e) => -(e.duration().as_secs() as i64),
+        }
+    }
+
+    fn clock(&self) -> f64 {
+        let mut ts: libc::timespec = unsafe { mem::zeroed() };
+        unsafe { libc::clock_gettime(libc::CLOCK_PROCESS_CPUTIME_ID, &mut ts); }
+        ts.tv_sec as f64 + ts.tv_nsec as f64 / 1e9
+    }
+}
+
+
+pub use host::{Environment, ProcessEnvironment};
+
+
+/* key, in the registry, for the clock */
+static CLOCKKEY: u8 = 0;
+
+/// Sets the clock used by the `os` library of state `l`.
+///
+/// # Safety
+///
+/// `l` must be a valid Lua state.
+pub unsafe fn set_clock(l: *mut lua_State, clock: Box<dyn Clock>) {
+    userdata::set(l, &CLOCKKEY, clock);
+}
+
+/// Sets the environment used by `os.getenv` in state `l`.
+///
+/// # Safety
+///
+/// `l` must be a valid Lua state.
+pub unsafe fn set_environment(l: *mut lua_State, env: Box<dyn Environment>) {
+    userdata::set(l, &host::ENVKEY, env);
+}
+
+unsafe fn getclock<'a>(l: *mut lua_State) -> &'a dyn Clock {
+    match userdata::get::<Box<dyn Clock>>(l, &CLOCKKEY) {
+        Some(c) => &**c,
+        None => &SystemClock,
+    }
+}
+
+
+/*
+** {==================================================================
+** List of valid conversion specifiers for the 'strftime' function;
+** options are grouped by length; group of length 2 start with '||'.
+** ===================================================================
+*/
+
+/* options for ISO C 99 and POSIX */
+const LUA_STRFTIMEOPTIONS: &[u8] = b"aAbBcCdDeFgGhHIjmMnprRStTuUVwWxXyYzZ%\
+    ||EcECExEXEyEYOdOeOHOIOmOMOSOuOUOVOwOWOy";
+
+/* }================================================================== */
+
+
+unsafe fn l_checktime(l: *mut lua_State, arg: c_int) -> time_t {
+    let t = lauxlib::luaL_checkinteger(l, arg);
+    lauxlib::luaL_argcheck(l, (t as time_t as lua_Integer == t) as c_int, arg,
+                           c_str!("time out-of-bounds"));
+    t as time_t
+}
+
+
+unsafe extern "C-unwind" fn os_execute(l: *mut lua_State) -> c_int {
+    let cmd = lauxlib::luaL_optstring(l, 1, ptr::null());
+    let stat = libc::system(cmd);
+    if !cmd.is_null() {
+        lauxlib::luaL_execresult(l, stat)
+    } else {
+        lua::lua_pushboolean(l, stat);  /* true if there is a shell */
+        1
+    }
+}
+
+
+unsafe extern "C-unwind" fn os_remove(l: *mut lua_State) -> c_int {
+    let filename = lauxlib::luaL_checkstring(l, 1);
+    lauxlib::luaL_fileresult(l, (libc::remove(filename) == 0) as c_int, filename)
+}
+
+
+unsafe extern "C-unwind" fn os_rename(l: *mut lua_State) -> c_int {
+    let fromname = lauxlib::luaL_checkstring(l, 1);
+    let toname = lauxlib::luaL_checkstring(l, 2);
+    lauxlib::luaL_fileresult(l, (libc::rename(fromname, toname) == 0) as c_int, ptr::null())
+}
+
+
+unsafe extern "C-unwind" fn os_tmpname(l: *mut lua_State) -> c_int {
+    let mut buff = *b"/tmp/lua_XXXXXX\0";
+    let e = libc::mkstemp(buff.as_mut_ptr() as *mut c_char);
+    if e == -1 {
+        return lauxlib::luaL_error(l, c_str!("unable to generate a unique filename"));
+    }
+    libc::close(e);
+    lua::lua_pushstring(l, buff.as_ptr() as *const c_char);
+    1
+}
+
+
+unsafe extern "C-unwind" fn os_getenv(l: *mut lua_State) -> c_int {
+    let name = CStr::from_ptr(lauxlib::luaL_checkstring(l, 1)).to_bytes();
+    match host::getenvironment(l).getenv(name) {
+        Some(v) => { lua::lua_pushlstring(l, v.as_ptr() as *const c_char, v.len()); }
+        None => lua::lua_pushnil(l),
+    }
+    1
+}
+
+
+unsafe extern "C-unwind" fn os_clock(l: *mut lua_State) -> c_int {
+    lua::lua_pushnumber(l, getclock(l).clock() as lua_Number);
+    1
+}
+
+
+/*
+** {======================================================
+** Time/Date operations
+** { year=%Y, month=%m, day=%d, hour=%H, min=%M, sec=%S,
+**   wday=%w+1, yday=%j, isdst=? }
+** =======================================================
+*/
+
+unsafe fn setfield(l: *mut lua_State, key: *const c_char, value: c_int) {
+    lua::lua_pushinteger(l, value as lua_Integer);
+    lua::lua_setfield(l, -2, key);
+}
+
+unsafe fn setboolfield(l: *mut lua_State, key: *const c_char, value: c_int) {
+    if value < 0 {  /* undefined? */
+        return;  /* does not set field */
+    }
+    lua::lua_pushboolean(l, value);
+    lua::lua_setfield(l, -2, key);
+}
+
+
+/*
+** Set all fields from structure 'tm' in the table on top of the stack
+*/
+unsafe fn setallfields(l: *mut lua_State, stm: &libc::tm) {
+    setfield(l, c_str!("sec"), stm.tm_sec);
+    setfield(l, c_str!("min"), stm.tm_min);
+    setfield(l, c_str!("hour"), stm.tm_hour);
+    setfield(l, c_str!("day"), stm.tm_mday);
+    setfield(l, c_str!("month"), stm.tm_mon + 1);
+    setfield(l, c_str!("year"), stm.tm_year + 1900);
+    setfield(l, c_str!("wday"), stm.tm_wday + 1);
+    setfield(l, c_str!("yday"), stm.tm_yday + 1);
+    setboolfield(l, c_str!("isdst"), stm.tm_isdst);
+}
+
+
+unsafe fn getboolfield(l: *mut lua_State, key: *const c_char) -> c_int {
+    let res = if lua::lua_getfield(l, -1, key) == lua::LUA_TNIL { -1 } else { lua::lua_toboolean(l, -1) };
+    lua::lua_pop(l, 1);
+    res
+}
+
+
+/* maximum value for date fields (to avoid arithmetic overflows with 'int') */
+const L_MAXDATEFIELD: lua_Integer = (c_int::MAX / 2) as lua_Integer;
+
+unsafe fn getfield(l: *mut lua_State, key: *const c_char, d: c_int, delta: c_int) -> c_int {
+    let mut isnum = 0;
+    let t = lua::lua_getfield(l, -1, key);  /* get field and its type */
+    let mut res = lua::lua_tointegerx(l, -1, &mut isnum);
+    if isnum == 0 {  /* field is not an integer? */
+        if t != lua::LUA_TNIL {  /* some other value? */
+            return lauxlib::luaL_error(l, c_str!("field '%s' is not an integer"), key);
+        } else if d < 0 {  /* absent field; no default? */
+            return lauxlib::luaL_error(l, c_str!("field '%s' missing in date table"), key);
+        }
+        res = d as lua_Integer;
+    } else {
+        if !(-L_MAXDATEFIELD..=L_MAXDATEFIELD).contains(&res) {
+            return lauxlib::luaL_error(l, c_str!("field '%s' is out-of-bound"), key);
+        }
+        res -= delta as lua_Integer;
+    }
+    lua::lua_pop(l, 1);
+    res as c_int
+}
+
+
+/*
+** Checks that 'conv' starts with a valid conversion specifier, returning
+** its length; 'arg' is the C string holding 'conv', for error messages.
+*/
+unsafe fn checkoption(l: *mut lua_State, conv: &[u8], arg: *const c_char) -> usize {
+    let mut oplen = 1;
+    let mut option = LUA_STRFTIMEOPTIONS;
+    while !option.is_empty() && oplen <= conv.len() {
+        if option[0] == b'|' {  /* next block? */
+            oplen += 1;  /* next length */
+        } else if option[..oplen] == conv[..oplen] {  /* match? */
+            return oplen;
+        }
+        option = &option[oplen..];
+    }
+    lauxlib::luaL_argerror(l, 1,
+        lua::lua_pushfstring(l, c_str!("invalid conversion specifier '%%%s'"), arg));
+    0  /* to avoid warnings */
+}
+
+
+/* maximum size for an individual 'strftime' item */
+const SIZETIMEFMT: usize = 250;
+
+
+/*
+** Formats 'stm' according to 'format', which was already validated
+** with 'checkoption'.
+*/
+unsafe fn strftime(format: &[u8], oplens: &[usize], stm: &libc::tm) -> Vec<u8> {
+    let mut b = Vec::new();
+    let mut oplens = oplens.iter();
+    let mut i = 0;
+    while i < format.len() {
+        if format[i] != b'%' {  /* not a conversion specifier? */
+            b.push(format[i]);
+            i += 1;
+        } else {
+            let oplen = *oplens.next().unwrap();
+            let mut cc = [0u8; 4];  /* buffer for individual conversion specifiers */
+            cc[0] = b'%';
+            cc[1..oplen + 1].copy_from_slice(&format[i + 1..i + 1 + oplen]);
+            let mut buff = [0u8; SIZETIMEFMT];
+            let reslen = libc::strftime(buff.as_mut_ptr() as *mut c_char, SIZETIMEFMT,
+                                        cc.as_ptr() as *const c_char, stm);
+            b.extend_from_slice(&buff[..reslen]);
+            i += 1 + oplen;
+        }
+    }
+    b
+}
+
+
+unsafe extern "C-unwind" fn os_date(l: *mut lua_State) -> c_int {
+    let s = lauxlib::luaL_optstring(l, 1, c_str!("%c"));
+    let t = if lua::lua_isnoneornil(l, 2) != 0 { getclock(l).time() as time_t } else { l_checktime(l, 2) };
+    let mut tmr: libc::tm = mem::zeroed();
+    let mut s = CStr::from_ptr(s).to_bytes();
+    let stm = if s.first() == Some(&b'!') {  /* UTC? */
+        s = &s[1..];  /* skip '!' */
+        libc::gmtime_r(&t, &mut tmr)
+    } else {
+        libc::localtime_r(&t, &mut tmr)
+    };
+    if stm.is_null() {  /* invalid date? */
+        lauxlib::luaL_error(l, c_str!("time result cannot be represented in this installation"));
+    }
+    if s == b"*t" {
+        lua::lua_createtable(l, 0, 9);  /* 9 = number of fields */
+        setallfields(l, &tmr);
+    } else {
+        /* validate all conversions before formatting anything */
+        let mut oplens = Vec::new();
+        let mut i = 0;
+        while i < s.len() {
+            if s[i] != b'%' {
+                i += 1;
+            } else {
+                let oplen = checkoption(l, &s[i + 1..], s[i + 1..].as_ptr() as *const c_char);
+                oplens.push(oplen);
+                i += 1 + oplen;
+            }
+        }
+        let b = strftime(s, &oplens, &tmr);
+        lua::lua_pushlstring(l, b.as_ptr() as *const c_char, b.len());
+    }
+    1
+}
+
+
+unsafe extern "C-unwind" fn os_time(l: *mut lua_State) -> c_int {
+    let t = if lua::lua_isnoneornil(l, 1) != 0 {  /* called without args? */
+        getclock(l).time() as time_t  /* get current time */
+    } else {
+        let mut ts: libc::tm = mem::zeroed();
+        lauxlib::luaL_checktype(l, 1, lua::LUA_TTABLE);
+        lua::lua_settop(l, 1);  /* make sure table is at the top */
+        ts.tm_sec = getfield(l, c_str!("sec"), 0, 0);
+        ts.tm_min = getfield(l, c_str!("min"), 0, 0);
+        ts.tm_hour = getfield(l, c_str!("hour"), 12, 0);
+        ts.tm_mday = getfield(l, c_str!("day"), -1, 0);
+        ts.tm_mon = getfield(l, c_str!("month"), -1, 1);
+        ts.tm_year = getfield(l, c_str!("year"), -1, 1900);
+        ts.tm_isdst = getboolfield(l, c_str!("isdst"));
+        let t = libc::mktime(&mut ts);
+        setallfields(l, &ts);  /* update fields with normalized values */
+        t
+    };
+    if t as lua_Integer as time_t != t || t == -1 {
+        lauxlib::luaL_error(l, c_str!("time result cannot be represented in this installation"));
+    }
+    lua::lua_pushinteger(l, t as lua_Integer);
+    1
+}
+
+
+unsafe extern "C-unwind" fn os_difftime(l: *mut lua_State) -> c_int {
+    let t1 = l_checktime(l, 1);
+    let t2 = l_checktime(l, 2);
+    lua::lua_pushnumber(l, t1 as lua_Number - t2 as lua_Number);
+    1
+}
+
+/* }====================================================== */
+
+
+unsafe extern "C-unwind" fn os_setlocale(l: *mut lua_State) -> c_int {
+    let cat = [libc::LC_ALL, libc::LC_COLLATE, libc::LC_CTYPE, libc::LC_MONETARY,
+               libc::LC_NUMERIC, libc::LC_TIME];
+    let catnames = [c_str!("all"), c_str!("collate"), c_str!("ctype"), c_str!("monetary"),
+                    c_str!("numeric"), c_str!("time"), ptr::null()];
+    let loc = lauxlib::luaL_optstring(l, 1, ptr::null());
+    let op = lauxlib::luaL_checkoption(l, 2, c_str!("all"), catnames.as_ptr());
+    lua::lua_pushstring(l, libc::setlocale(cat[op as usize], loc));
+    1
+}
+
+
+unsafe extern "C-unwind" fn os_exit(l: *mut lua_State) -> c_int {
+    let status = if lua::lua_isboolean(l, 1) != 0 {
+        if lua::lua_toboolean(l, 1) != 0 { libc::EXIT_SUCCESS } else { libc::EXIT_FAILURE }
+    } else {
+        lauxlib::luaL_optinteger(l, 1, libc::EXIT_SUCCESS as lua_Integer) as c_int
+    };
+    if lua::lua_toboolean(l, 2) != 0 {
+        lua::lua_close(l);
+    } else {
+        #[cfg(rust_liolib)]
+        ::liolib::flush_all(l);  /* 'exit' would flush C streams (as it does for a C 'io' library) */
+    }
+    ::std::process::exit(status)
+}
+
+
+/// Opens the `os` library in state `l`, leaving its table on the stack.
+///
+/// # Safety
+///
+/// `l` must be a valid Lua state.
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaopen_os(l: *mut lua_State) -> c_int {
+    let syslib = [
+        lauxlib::luaL_Reg { name: c_str!("clock"), func: Some(os_clock) },
+        lauxlib::luaL_Reg { name: c_str!("date"), func: Some(os_date) },
+        lauxlib::luaL_Reg { name: c_str!("difftime"), func: Some(os_difftime) },
+        lauxlib::luaL_Reg { name: c_str!("execute"), func: Some(os_execute) },
+        lauxlib::luaL_Reg { name: c_str!("exit"), func: Some(os_exit) },
+        lauxlib::luaL_Reg { name: c_str!("getenv"), func: Some(os_getenv) },
+        lauxlib::luaL_Reg { name: c_str!("remove"), func: Some(os_remove) },
+        lauxlib::luaL_Reg { name: c_str!("rename"), func: Some(os_rename) },
+        lauxlib::luaL_Reg { name: c_str!("setlocale"), func: Some(os_setlocale) },
+        lauxlib::luaL_Reg { name: c_str!("time"), func: Some(os_time) },
+        lauxlib::luaL_Reg { name: c_str!("tmpname"), func: Some(os_tmpname) },
+        lauxlib::luaL_Reg { name: ptr::null(), func: None },
+    ];
+    lauxlib::luaL_newlib(l, syslib.as_ptr());
+    1
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::strftime;
+    use libc;
+    use std::mem;
+
+    #[test]
+    fn test_strftime() {
+        let mut tm: libc::tm = unsafe { mem::zeroed() };
+        tm.tm_year = 116;
+        tm.tm_mon = 1;
+        tm.tm_mday = 29;
+        tm.tm_hour = 13;
+        let b = unsafe { strftime(b"%Y-%m-%d %H%% x", &[1, 1, 1, 1, 1], &tm) };
+        assert_eq!(&b[..], b"2016-02-29 13% x");
+    }
+}
@@ -0,0 +1,1865 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Lua Parser.
+
+#![allow(non_camel_case_types, non_snake_case, non_upper_case_globals, clippy::upper_case_acronyms)]
+
+use ffi::lua::{self, lua_Integer, lua_Number};
+use lcode::*;
+use lcode::BinOpr::*;
+use lcode::UnOpr::*;
+use lexer::Reserved;
+use lfunc::{luaF_newLclosure, luaF_newproto, MAXUPVAL};
+use lgc::{luaC_checkGC, luaC_objbarrier};
+use llex::{luaX_lookahead, luaX_newstring, luaX_next, luaX_setinput, luaX_syntaxerror,
+           luaX_token2str, LexState};
+use lmem::{luaM_growvector, luaM_reallocvector};
+use lobject::{getstr, isreserved, luaO_int2fb, luaO_pushfstring, sethvalue, setclLvalue,
+              GCObject, LClosure, LocVar, Proto, TString, TValue, Upvaldesc};
+use lopcodes::*;
+use lopcodes::OpCode::*;
+use lstate::lua_State;
+use lstring::luaS_new;
+use ltable::luaH_new;
+use lzio::{Mbuffer, Zio};
+use libc::{c_char, c_int, c_short};
+use std::mem;
+use std::ptr;
+
+
+/* the exported functions below, from 'lparser.c' when it is linked instead (see 'build.rs') */
+#[cfg(not(rust_lparser))]
+extern "C-unwind" {
+    pub fn luaY_parser(L: *mut lua_State, z: *mut Zio, buff: *mut Mbuffer, dyd: *mut Dyndata,
+                       name: *const c_char, firstchar: c_int) -> *mut LClosure;
+}
+
+
+extern "C-unwind" {
+    fn luaD_inctop(L: *mut lua_State);
+}
+
+
+/*
+** Expression and variable descriptor.
+** Code generation for variables and expressions can be delayed to allow
+** optimizations; An 'expdesc' structure describes a potentially-delayed
+** variable/expression. It has a description of its "main" value plus a
+** list of conditional jumps that can also produce its value (generated
+** by short-circuit operators 'and'/'or').
+*/
+
+/* kinds of variables/expressions */
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum expkind {
+    VVOID,  /* when 'expdesc' describes the last expression a list,
+               this kind means an empty list (so, no expression) */
+    VNIL,  /* constant nil */
+    VTRUE,  /* constant true */
+    VFALSE,  /* constant false */
+    VK,  /* constant in 'k'; info = index of constant in 'k' */
+    VKFLT,  /* floating constant; nval = numerical float value */
+    VKINT,  /* integer constant; nval = numerical integer value */
+    VNONRELOC,  /* expression has its value in a fixed register;
+                   info = result register */
+    VLOCAL,  /* local variable; info = local register */
+    VUPVAL,  /* upvalue variable; info = index of upvalue in 'upvalues' */
+    VINDEXED,  /* indexed variable;
+                  ind.vt = whether 't' is register or upvalue;
+                  ind.t = table register or upvalue;
+                  ind.idx = key's R/K index */
+    VJMP,  /* expression is a test/comparison;
+              info = pc of corresponding jump instruction */
+    VRELOCABLE,  /* expression can put result in any register;
+                    info = instruction pc */
+    VCALL,  /* expression is a function call; info = instruction pc */
+    VVARARG,  /* vararg expression; info = instruction pc */
+}
+
+use self::expkind::*;
+
+
+fn vkisvar(k: expkind) -> bool { VLOCAL <= k && k <= VINDEXED }
+
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct expdescInd {  /* for indexed variables (VINDEXED) */
+    pub idx: c_short,  /* index (R/K) */
+    pub t: u8,  /* table (register or upvalue) */
+    pub vt: u8,  /* whether 't' is register (VLOCAL) or upvalue (VUPVAL) */
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union expdescU {
+    pub ival: lua_Integer,  /* for VKINT */
+    pub nval: lua_Number,  /* for VKFLT */
+    pub info: c_int,  /* for generic use */
+    pub ind: expdescInd,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct expdesc {
+    pub k: expkind,
+    pub u: expdescU,
+    pub t: c_int,  /* patch list of 'exit when true' */
+    pub f: c_int,  /* patch list of 'exit when false' */
+}
+
+impl expdesc {
+    pub fn new() -> expdesc {
+        expdesc { k: VVOID, u: expdescU { ival: 0 }, t: NO_JUMP, f: NO_JUMP }
+    }
+}
+
+
+/* description of active local variable */
+#[repr(C)]
+pub struct Vardesc {
+    pub idx: c_short,  /* variable index in stack */
+}
+
+
+/* description of pending goto statements and label statements */
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Labeldesc {
+    pub name: *mut TString,  /* label identifier */
+    pub pc: c_int,  /* position in code */
+    pub line: c_int,  /* line where it appeared */
+    pub nactvar: u8,  /* local level where it appears in current block */
+}
+
+
+/* list of labels or gotos */
+#[repr(C)]
+pub struct Labellist {
+    pub arr: *mut Labeldesc,  /* array */
+    pub n: c_int,  /* number of entries in use */
+    pub size: c_int,  /* array size */
+}
+
+
+/* list of active local variables */
+#[repr(C)]
+pub struct Varlist {
+    pub arr: *mut Vardesc,
+    pub n: c_int,
+    pub size: c_int,
+}
+
+
+/* dynamic structures used by the parser */
+#[repr(C)]
+pub struct Dyndata {
+    pub actvar: Varlist,  /* list of active local variables */
+    pub gt: Labellist,  /* list of pending gotos */
+    pub label: Labellist,  /* list of active labels */
+}
+
+
+/* state needed to generate code for a given function */
+#[repr(C)]  /* as in 'lparser.h', for 'lcode.c' */
+pub struct FuncState {
+    pub f: *mut Proto,  /* current function header */
+    pub prev: *mut FuncState,  /* enclosing function */
+    pub ls: *mut LexState,  /* lexical state */
+    pub bl: *mut BlockCnt,  /* chain of current blocks */
+    pub pc: c_int,  /* next position to code (equivalent to 'ncode') */
+    pub lasttarget: c_int,  /* 'label' of last 'jump label' */
+    pub jpc: c_int,  /* list of pending jumps to 'pc' */
+    pub nk: c_int,  /* number of elements in 'k' */
+    pub np: c_int,  /* number of elements in 'p' */
+    pub firstlocal: c_int,  /* index of first local var (in Dyndata array) */
+    pub nlocvars: c_short,  /* number of elements in 'f->locvars' */
+    pub nactvar: u8,  /* number of active local variables */
+    pub nups: u8,  /* number of upvalues */
+    pub freereg: u8,  /* first free register */
+}
+
+impl FuncState {
+    fn new() -> FuncState {
+        FuncState {
+            f: ptr::null_mut(), prev: ptr::null_mut(), ls: ptr::null_mut(), bl: ptr::null_mut(),
+            pc: 0, lasttarget: 0, jpc: NO_JUMP, nk: 0, np: 0, firstlocal: 0,
+            nlocvars: 0, nactvar: 0, nups: 0, freereg: 0,
+        }
+    }
+}
+
+
+/* maximum number of local variables per function (must be smaller
+   than 250, due to the bytecode format) */
+const MAXVARS: c_int = 200;
+
+/* maximum depth for nested C calls and syntactical nested non-terminals */
+const LUAI_MAXCCALLS: c_int = 200;
+
+
+const TK_AND: c_int = Reserved::And as c_int;
+const TK_BREAK: c_int = Reserved::Break as c_int;
+const TK_DO: c_int = Reserved::Do as c_int;
+const TK_ELSE: c_int = Reserved::Else as c_int;
+const TK_ELSEIF: c_int = Reserved::Elseif as c_int;
+const TK_END: c_int = Reserved::End as c_int;
+const TK_FALSE: c_int = Reserved::False as c_int;
+const TK_FOR: c_int = Reserved::For as c_int;
+const TK_FUNCTION: c_int = Reserved::Function as c_int;
+const TK_GOTO: c_int = Reserved::Goto as c_int;
+const TK_IF: c_int = Reserved::If as c_int;
+const TK_IN: c_int = Reserved::In as c_int;
+const TK_LOCAL: c_int = Reserved::Local as c_int;
+const TK_NIL: c_int = Reserved::Nil as c_int;
+const TK_NOT: c_int = Reserved::Not as c_int;
+const TK_OR: c_int = Reserved::Or as c_int;
+const TK_REPEAT: c_int = Reserved::Repeat as c_int;
+const TK_RETURN: c_int = Reserved::Return as c_int;
+const TK_THEN: c_int = Reserved::Then as c_int;
+const TK_TRUE: c_int = Reserved::True as c_int;
+const TK_UNTIL: c_int = Reserved::Until as c_int;
+const TK_WHILE: c_int = Reserved::While as c_int;
+const TK_IDIV: c_int = Reserved::IDiv as c_int;
+const TK_CONCAT: c_int = Reserved::Concat as c_int;
+const TK_DOTS: c_int = Reserved::Dots as c_int;
+const TK_EQ: c_int = Reserved::Eq as c_int;
+const TK_GE: c_int = Reserved::Ge as c_int;
+const TK_LE: c_int = Reserved::Le as c_int;
+const TK_NE: c_int = Reserved::Ne as c_int;
+const TK_SHL: c_int = Reserved::Shl as c_int;
+const TK_SHR: c_int = Reserved::Shr as c_int;
+const TK_DBCOLON: c_int = Reserved::DbColon as c_int;
+const TK_EOS: c_int = Reserved::Eos as c_int;
+const TK_FLT: c_int = Reserved::Flt as c_int;
+const TK_INT: c_int = Reserved::Int as c_int;
+const TK_NAME: c_int = Reserved::Name as c_int;
+const TK_STRING: c_int = Reserved::String as c_int;
+
+
+fn hasmultret(k: expkind) -> bool { k == VCALL || k == VVARARG }
+
+
+/*
+** nodes for block list (list of active blocks)
+*/
+#[repr(C)]  /* as in 'lparser.c' */
+pub struct BlockCnt {
+    previous: *mut BlockCnt,  /* chain */
+    firstlabel: c_int,  /* index of first label in this block */
+    firstgoto: c_int,  /* index of first pending goto in this block */
+    nactvar: u8,  /* # active locals outside the block */
+    upval: bool,  /* true if some variable in the block is an upvalue */
+    isloop: bool,  /* true if 'block' is a loop */
+}
+
+impl BlockCnt {
+    fn new() -> BlockCnt {
+        BlockCnt {
+            previous: ptr::null_mut(), firstlabel: 0, firstgoto: 0,
+            nactvar: 0, upval: false, isloop: false,
+        }
+    }
+}
+
+
+/* semantic error */
+unsafe fn semerror(ls: *mut LexState, msg: *const c_char) -> ! {
+    (*ls).t.token = 0;  /* remove "near <token>" from final message */
+    luaX_syntaxerror(ls, msg)
+}
+
+
+unsafe fn error_expected(ls: *mut LexState, token: c_int) -> ! {
+    luaX_syntaxerror(ls,
+        luaO_pushfstring((*ls).L, c_str!("%s expected"), luaX_token2str(ls, token)))
+}
+
+
+unsafe fn errorlimit(fs: *mut FuncState, limit: c_int, what: *const c_char) -> ! {
+    let L = (*(*fs).ls).L;
+    let line = (*(*fs).f).linedefined;
+    let where_ = if line == 0 {
+        c_str!("main function")
+    } else {
+        luaO_pushfstring(L, c_str!("function at line %d"), line)
+    };
+    let msg = luaO_pushfstring(L, c_str!("too many %s (limit is %d) in %s"),
+                               what, limit, where_);
+    luaX_syntaxerror((*fs).ls, msg)
+}
+
+
+unsafe fn checklimit(fs: *mut FuncState, v: c_int, l: c_int, what: *const c_char) {
+    if v > l { errorlimit(fs, l, what); }
+}
+
+
+unsafe fn testnext(ls: *mut LexState, c: c_int) -> bool {
+    if (*ls).t.token == c {
+        luaX_next(ls);
+        true
+    } else {
+        false
+    }
+}
+
+
+unsafe fn check(ls: *mut LexState, c: c_int) {
+    if (*ls).t.token != c {
+        error_expected(ls, c);
+    }
+}
+
+
+unsafe fn checknext(ls: *mut LexState, c: c_int) {
+    check(ls, c);
+    luaX_next(ls);
+}
+
+
+unsafe fn check_condition(ls: *mut LexState, c: bool, msg: *const c_char) {
+    if !c { luaX_syntaxerror(ls, msg); }
+}
+
+
+unsafe fn check_match(ls: *mut LexState, what: c_int, who: c_int, where_: c_int) {
+    if !testnext(ls, what) {
+        if where_ == (*ls).linenumber {
+            error_expected(ls, what);
+        } else {
+            luaX_syntaxerror(ls, luaO_pushfstring((*ls).L,
+                   c_str!("%s expected (to close %s at line %d)"),
+                    luaX_token2str(ls, what), luaX_token2str(ls, who), where_));
+        }
+    }
+}
+
+
+unsafe fn str_checkname(ls: *mut LexState) -> *mut TString {
+    check(ls, TK_NAME);
+    let ts = (*ls).t.seminfo.ts;
+    luaX_next(ls);
+    ts
+}
+
+
+unsafe fn init_exp(e: *mut expdesc, k: expkind, i: c_int) {
+    (*e).t = NO_JUMP;
+    (*e).f = NO_JUMP;
+    (*e).k = k;
+    (*e).u.info = i;
+}
+
+
+unsafe fn codestring(ls: *mut LexState, e: *mut expdesc, s: *mut TString) {
+    init_exp(e, VK, luaK_stringK((*ls).fs, s));
+}
+
+
+unsafe fn checkname(ls: *mut LexState, e: *mut expdesc) {
+    codestring(ls, e, str_checkname(ls));
+}
+
+
+unsafe fn registerlocalvar(ls: *mut LexState, varname: *mut TString) -> c_int {
+    let fs = (*ls).fs;
+    let f = (*fs).f;
+    let mut oldsize = (*f).sizelocvars;
+    luaM_growvector((*ls).L, &mut (*f).locvars, (*fs).nlocvars as c_int, &mut (*f).sizelocvars,
+                    c_short::MAX as c_int, c_str!("local variables"));
+    while oldsize < (*f).sizelocvars {
+        (*(*f).locvars.offset(oldsize as isize)).varname = ptr::null_mut();
+        oldsize += 1;
+    }
+    (*(*f).locvars.offset((*fs).nlocvars as isize)).varname = varname;
+    luaC_objbarrier((*ls).L, f as *mut GCObject, varname as *mut GCObject);
+    (*fs).nlocvars += 1;
+    (*fs).nlocvars as c_int - 1
+}
+
+
+unsafe fn new_localvar(ls: *mut LexState, name: *mut TString) {
+    let fs = (*ls).fs;
+    let dyd = (*ls).dyd;
+    let reg = registerlocalvar(ls, name);
+    checklimit(fs, (*dyd).actvar.n + 1 - (*fs).firstlocal,
+                    MAXVARS, c_str!("local variables"));
+    luaM_growvector((*ls).L, &mut (*dyd).actvar.arr, (*dyd).actvar.n + 1,
+                    &mut (*dyd).actvar.size, c_int::MAX, c_str!("local variables"));
+    (*(*dyd).actvar.arr.offset((*dyd).actvar.n as isize)).idx = reg as c_short;
+    (*dyd).actvar.n += 1;
+}
+
+
+unsafe fn new_localvarliteral(ls: *mut LexState, name: &'static [u8]) {
+    new_localvar(ls, luaX_newstring(ls, name.as_ptr() as *const c_char, name.len()));
+}
+
+
+unsafe fn getlocvar(fs: *mut FuncState, i: c_int) -> *mut LocVar {
+    let idx = (*(*(*(*fs).ls).dyd).actvar.arr.offset(((*fs).firstlocal + i) as isize)).idx;
+    debug_assert!(idx < (*fs).nlocvars);
+    (*(*fs).f).locvars.offset(idx as isize)
+}
+
+
+unsafe fn adjustlocalvars(ls: *mut LexState, mut nvars: c_int) {
+    let fs = (*ls).fs;
+    (*fs).nactvar = ((*fs).nactvar as c_int + nvars) as u8;
+    while nvars != 0 {
+        (*getlocvar(fs, (*fs).nactvar as c_int - nvars)).startpc = (*fs).pc;
+        nvars -= 1;
+    }
+}
+
+
+unsafe fn removevars(fs: *mut FuncState, tolevel: c_int) {
+    (*(*(*fs).ls).dyd).actvar.n -= (*fs).nactvar as c_int - tolevel;
+    while (*fs).nactvar as c_int > tolevel {
+        (*fs).nactvar -= 1;
+        (*getlocvar(fs, (*fs).nactvar as c_int)).endpc = (*fs).pc;
+    }
+}
+
+
+unsafe fn searchupvalue(fs: *mut FuncState, name: *mut TString) -> c_int {
+    let up = (*(*fs).f).upvalues;
+    for i in 0..(*fs).nups as c_int {
+        if (*up.offset(i as isize)).name == name { return i; }
+    }
+    -1  /* not found */
+}
+
+
+unsafe fn newupvalue(fs: *mut FuncState, name: *mut TString, v: *const expdesc) -> c_int {
+    let f = (*fs).f;
+    let mut oldsize = (*f).sizeupvalues;
+    checklimit(fs, (*fs).nups as c_int + 1, MAXUPVAL, c_str!("upvalues"));
+    luaM_growvector((*(*fs).ls).L, &mut (*f).upvalues, (*fs).nups as c_int,
+                    &mut (*f).sizeupvalues, MAXUPVAL, c_str!("upvalues"));
+    while oldsize < (*f).sizeupvalues {
+        (*(*f).upvalues.offset(oldsize as isize)).name = ptr::null_mut();
+        oldsize += 1;
+    }
+    let up: *mut Upvaldesc = (*f).upvalues.offset((*fs).nups as isize);
+    (*up).instack = ((*v).k == VLOCAL) as u8;
+    (*up).idx = (*v).u.info as u8;
+    (*up).name = name;
+    luaC_objbarrier((*(*fs).ls).L, f as *mut GCObject, name as *mut GCObject);
+    (*fs).nups += 1;
+    (*fs).nups as c_int - 1
+}
+
+
+unsafe fn searchvar(fs: *mut FuncState, n: *mut TString) -> c_int {
+    let mut i = (*fs).nactvar as c_int - 1;
+    while i >= 0 {
+        if n == (*getlocvar(fs, i)).varname {
+            return i;
+        }
+        i -= 1;
+    }
+    -1  /* not found */
+}
+
+
+/*
+  Mark block where variable at given level was defined
+  (to emit close instructions later).
+*/
+unsafe fn markupval(fs: *mut FuncState, level: c_int) {
+    let mut bl = (*fs).bl;
+    while (*bl).nactvar as c_int > level {
+        bl = (*bl).previous;
+    }
+    (*bl).upval = true;
+}
+
+
+/*
+  Find variable with given name 'n'. If it is an upvalue, add this
+  upvalue into all intermediate functions.
+*/
+unsafe fn singlevaraux(fs: *mut FuncState, n: *mut TString, var: *mut expdesc, base: bool) {
+    if fs.is_null() {  /* no more levels? */
+        init_exp(var, VVOID, 0);  /* default is global */
+    } else {
+        let v = searchvar(fs, n);  /* look up locals at current level */
+        if v >= 0 {  /* found? */
+            init_exp(var, VLOCAL, v);  /* variable is local */
+            if !base {
+                markupval(fs, v);  /* local will be used as an upval */
+            }
+        } else {  /* not found as local at current level; try upvalues */
+            let mut idx = searchupvalue(fs, n);  /* try existing upvalues */
+            if idx < 0 {  /* not found? */
+                singlevaraux((*fs).prev, n, var, false);  /* try upper levels */
+                if (*var).k == VVOID {  /* not found? */
+                    return;  /* it is a global */
+                }
+                /* else was LOCAL or UPVAL */
+                idx = newupvalue(fs, n, var);  /* will be a new upvalue */
+            }
+            init_exp(var, VUPVAL, idx);  /* new or old upvalue */
+        }
+    }
+}
+
+
+unsafe fn singlevar(ls: *mut LexState, var: *mut expdesc) {
+    let varname = str_checkname(ls);
+    let fs = (*ls).fs;
+    singlevaraux(fs, varname, var, true);
+    if (*var).k == VVOID {  /* global name? */
+        let mut key = expdesc::new();
+        singlevaraux(fs, (*ls).envn, var, true);  /* get environment variable */
+        debug_assert!((*var).k != VVOID);  /* this one must exist */
+        codestring(ls, &mut key, varname);  /* key is variable name */
+        luaK_indexed(fs, var, &mut key);  /* env[varname] */
+    }
+}
+
+
+unsafe fn adjust_assign(ls: *mut LexState, nvars: c_int, nexps: c_int, e: *mut expdesc) {
+    let fs = (*ls).fs;
+    let mut extra = nvars - nexps;
+    if hasmultret((*e).k) {
+        extra += 1;  /* includes call itself */
+        if extra < 0 { extra = 0; }
+        luaK_setreturns(fs, e, extra);  /* last exp. provides the difference */
+        if extra > 1 { luaK_reserveregs(fs, extra - 1); }
+    } else {
+        if (*e).k != VVOID { luaK_exp2nextreg(fs, e); }  /* close last expression */
+        if extra > 0 {
+            let reg = (*fs).freereg as c_int;
+            luaK_reserveregs(fs, extra);
+            luaK_nil(fs, reg, extra);
+        }
+    }
+    if nexps > nvars {
+        (*(*ls).fs).freereg -= (nexps - nvars) as u8;  /* remove extra values */
+    }
+}
+
+
+unsafe fn enterlevel(ls: *mut LexState) {
+    let L = (*ls).L;
+    (*L).nCcalls += 1;
+    checklimit((*ls).fs, (*L).nCcalls as c_int, LUAI_MAXCCALLS, c_str!("C levels"));
+}
+
+
+unsafe fn leavelevel(ls: *mut LexState) {
+    (*(*ls).L).nCcalls -= 1;
+}
+
+
+unsafe fn closegoto(ls: *mut LexState, g: c_int, label: *mut Labeldesc) {
+    let fs = (*ls).fs;
+    let gl = &mut (*(*ls).dyd).gt;
+    let gt = gl.arr.offset(g as isize);
+    debug_assert!((*gt).name == (*label).name);
+    if (*gt).nactvar < (*label).nactvar {
+        let vname = (*getlocvar(fs, (*gt).nactvar as c_int)).varname;
+        let msg = luaO_pushfstring((*ls).L,
+            c_str!("<goto %s> at line %d jumps into the scope of local '%s'"),
+            getstr((*gt).name), (*gt).line, getstr(vname));
+        semerror(ls, msg);
+    }
+    luaK_patchlist(fs, (*gt).pc, (*label).pc);
+    /* remove goto from pending list */
+    for i in g..gl.n - 1 {
+        *gl.arr.offset(i as isize) = *gl.arr.offset(i as isize + 1);
+    }
+    gl.n -= 1;
+}
+
+
+/*
+** try to close a goto with existing labels; this solves backward jumps
+*/
+unsafe fn findlabel(ls: *mut LexState, g: c_int) -> bool {
+    let bl = (*(*ls).fs).bl;
+    let dyd = (*ls).dyd;
+    let gt = (*dyd).gt.arr.offset(g as isize);
+    /* check labels in current block for a match */
+    for i in (*bl).firstlabel..(*dyd).label.n {
+        let lb = (*dyd).label.arr.offset(i as isize);
+        if (*lb).name == (*gt).name {  /* correct label? */
+            if (*gt).nactvar > (*lb).nactvar &&
+               ((*bl).upval || (*dyd).label.n > (*bl).firstlabel) {
+                luaK_patchclose((*ls).fs, (*gt).pc, (*lb).nactvar as c_int);
+            }
+            closegoto(ls, g, lb);  /* close it */
+            return true;
+        }
+    }
+    false  /* label not found; cannot close goto */
+}
+
+
+unsafe fn newlabelentry(ls: *mut LexState, l: *mut Labellist, name: *mut TString,
+                        line: c_int, pc: c_int) -> c_int {
+    let n = (*l).n;
+    luaM_growvector((*ls).L, &mut (*l).arr, n, &mut (*l).size,
+                    c_short::MAX as c_int, c_str!("labels/gotos"));
+    let e = (*l).arr.offset(n as isize);
+    (*e).name = name;
+    (*e).line = line;
+    (*e).nactvar = (*(*ls).fs).nactvar;
+    (*e).pc = pc;
+    (*l).n = n + 1;
+    n
+}
+
+
+/*
+** check whether new label 'lb' matches any pending gotos in current
+** block; solves forward jumps
+*/
+unsafe fn findgotos(ls: *mut LexState, lb: *mut Labeldesc) {
+    let gl = &mut (*(*ls).dyd).gt as *mut Labellist;
+    let mut i = (*(*(*ls).fs).bl).firstgoto;
+    while i < (*gl).n {
+        if (*(*gl).arr.offset(i as isize)).name == (*lb).name {
+            closegoto(ls, i, lb);
+        } else {
+            i += 1;
+        }
+    }
+}
+
+
+/*
+** export pending gotos to outer level, to check them against
+** outer labels; if the block being exited has upvalues, and
+** the goto exits the scope of any variable (which can be the
+** upvalue), close those variables being exited.
+*/
+unsafe fn movegotosout(fs: *mut FuncState, bl: *mut BlockCnt) {
+    let mut i = (*bl).firstgoto;
+    let gl = &mut (*(*(*fs).ls).dyd).gt as *mut Labellist;
+    /* correct pending gotos to current block and try to close it
+       with visible labels */
+    while i < (*gl).n {
+        let gt = (*gl).arr.offset(i as isize);
+        if (*gt).nactvar > (*bl).nactvar {
+            if (*bl).upval {
+                luaK_patchclose(fs, (*gt).pc, (*bl).nactvar as c_int);
+            }
+            (*gt).nactvar = (*bl).nactvar;
+        }
+        if !findlabel((*fs).ls, i) {
+            i += 1;  /* move to next one */
+        }
+    }
+}
+
+
+unsafe fn enterblock(fs: *mut FuncState, bl: *mut BlockCnt, isloop: bool) {
+    (*bl).isloop = isloop;
+    (*bl).nactvar = (*fs).nactvar;
+    (*bl).firstlabel = (*(*(*fs).ls).dyd).label.n;
+    (*bl).firstgoto = (*(*(*fs).ls).dyd).gt.n;
+    (*bl).upval = false;
+    (*bl).previous = (*fs).bl;
+    (*fs).bl = bl;
+    debug_assert!((*fs).freereg == (*fs).nactvar);
+}
+
+
+/*
+** create a label named 'break' to resolve break statements
+*/
+unsafe fn breaklabel(ls: *mut LexState) {
+    let n = luaS_new((*ls).L, c_str!("break"));
+    let l = newlabelentry(ls, &mut (*(*ls).dyd).label, n, 0, (*(*ls).fs).pc);
+    findgotos(ls, (*(*ls).dyd).label.arr.offset(l as isize));
+}
+
+/*
+** generates an error for an undefined 'goto'; choose appropriate
+** message when label name is a reserved word (which can only be 'break')
+*/
+unsafe fn undefgoto(ls: *mut LexState, gt: *mut Labeldesc) -> ! {
+    let msg = if isreserved((*gt).name) {
+        c_str!("<%s> at line %d not inside a loop")
+    } else {
+        c_str!("no visible label '%s' for <goto> at line %d")
+    };
+    let msg = luaO_pushfstring((*ls).L, msg, getstr((*gt).name), (*gt).line);
+    semerror(ls, msg)
+}
+
+
+unsafe fn leaveblock(fs: *mut FuncState) {
+    let bl = (*fs).bl;
+    let ls = (*fs).ls;
+    if !(*bl).previous.is_null() && (*bl).upval {
+        /* create a 'jump to here' to close upvalues */
+        let j = luaK_jump(fs);
+        luaK_patchclose(fs, j, (*bl).nactvar as c_int);
+        luaK_patchtohere(fs, j);
+    }
+    if (*bl).isloop {
+        breaklabel(ls);  /* close pending breaks */
+    }
+    (*fs).bl = (*bl).previous;
+    removevars(fs, (*bl).nactvar as c_int);
+    debug_assert!((*bl).nactvar == (*fs).nactvar);
+    (*fs).freereg = (*fs).nactvar;  /* free registers */
+    (*(*ls).dyd).label.n = (*bl).firstlabel;  /* remove local labels */
+    if !(*bl).previous.is_null() {  /* inner block? */
+        movegotosout(fs, bl);  /* update pending gotos to outer block */
+    } else if (*bl).firstgoto < (*(*ls).dyd).gt.n {  /* pending gotos in outer block? */
+        undefgoto(ls, (*(*ls).dyd).gt.arr.offset((*bl).firstgoto as isize));  /* error */
+    }
+}
+
+
+/*
+** adds a new prototype into list of prototypes
+*/
+unsafe fn addprototype(ls: *mut LexState) -> *mut Proto {
+    let L = (*ls).L;
+    let fs = (*ls).fs;
+    let f = (*fs).f;  /* prototype of current function */
+    if (*fs).np >= (*f).sizep {
+        let mut oldsize = (*f).sizep;
+        luaM_growvector(L, &mut (*f).p, (*fs).np, &mut (*f).sizep, MAXARG_Bx, c_str!("functions"));
+        while oldsize < (*f).sizep {
+            *(*f).p.offset(oldsize as isize) = ptr::null_mut();
+            oldsize += 1;
+        }
+    }
+    let clp = luaF_newproto(L);
+    *(*f).p.offset((*fs).np as isize) = clp;
+    (*fs).np += 1;
+    luaC_objbarrier(L, f as *mut GCObject, clp as *mut GCObject);
+    clp
+}
+
+
+/*
+** codes instruction to create new closure in parent function.
+** The OP_CLOSURE instruction must use the last available register,
+** so that, if it invokes the GC, the GC knows which registers
+** are in use at that time.
+*/
+unsafe fn codeclosure(ls: *mut LexState, v: *mut expdesc) {
+    let fs = (*(*ls).fs).prev;
+    init_exp(v, VRELOCABLE, luaK_codeABx(fs, OP_CLOSURE, 0, ((*fs).np - 1) as u32));
+    luaK_exp2nextreg(fs, v);  /* fix it at the last register */
+}
+
+
+unsafe fn open_func(ls: *mut LexState, fs: *mut FuncState, bl: *mut BlockCnt) {
+    (*fs).prev = (*ls).fs;  /* linked list of funcstates */
+    (*fs).ls = ls;
+    (*ls).fs = fs;
+    (*fs).pc = 0;
+    (*fs).lasttarget = 0;
+    (*fs).jpc = NO_JUMP;
+    (*fs).freereg = 0;
+    (*fs).nk = 0;
+    (*fs).np = 0;
+    (*fs).nups = 0;
+    (*fs).nlocvars = 0;
+    (*fs).nactvar = 0;
+    (*fs).firstlocal = (*(*ls).dyd).actvar.n;
+    (*fs).bl = ptr::null_mut();
+    let f = (*fs).f;
+    (*f).source = (*ls).source;
+    (*f).maxstacksize = 2;  /* registers 0/1 are always valid */
+    enterblock(fs, bl, false);
+}
+
+
+unsafe fn close_func(ls: *mut LexState) {
+    let L = (*ls).L;
+    let fs = (*ls).fs;
+    let f = (*fs).f;
+    luaK_ret(fs, 0, 0);  /* final return */
+    leaveblock(fs);
+    luaM_reallocvector(L, &mut (*f).code, (*f).sizecode, (*fs).pc);
+    (*f).sizecode = (*fs).pc;
+    luaM_reallocvector(L, &mut (*f).lineinfo, (*f).sizelineinfo, (*fs).pc);
+    (*f).sizelineinfo = (*fs).pc;
+    luaM_reallocvector::<TValue>(L, &mut (*f).k, (*f).sizek, (*fs).nk);
+    (*f).sizek = (*fs).nk;
+    luaM_reallocvector(L, &mut (*f).p, (*f).sizep, (*fs).np);
+    (*f).sizep = (*fs).np;
+    luaM_reallocvector(L, &mut (*f).locvars, (*f).sizelocvars, (*fs).nlocvars as c_int);
+    (*f).sizelocvars = (*fs).nlocvars as c_int;
+    luaM_reallocvector(L, &mut (*f).upvalues, (*f).sizeupvalues, (*fs).nups as c_int);
+    (*f).sizeupvalues = (*fs).nups as c_int;
+    debug_assert!((*fs).bl.is_null());
+    (*ls).fs = (*fs).prev;
+    luaC_checkGC(L);
+}
+
+
+
+/*============================================================*/
+/* GRAMMAR RULES */
+/*============================================================*/
+
+
+/*
+** check whether current token is in the follow set of a block.
+** 'until' closes syntactical blocks, but do not close scope,
+** so it is handled in separate.
+*/
+unsafe fn block_follow(ls: *mut LexState, withuntil: bool) -> bool {
+    match (*ls).t.token {
+        TK_ELSE | TK_ELSEIF |
+        TK_END | TK_EOS => true,
+        TK_UNTIL => withuntil,
+        _ => false,
+    }
+}
+
+
+unsafe fn statlist(ls: *mut LexState) {
+    /* statlist -> { stat [';'] } */
+    while !block_follow(ls, true) {
+        if (*ls).t.token == TK_RETURN {
+            statement(ls);
+            return;  /* 'return' must be last statement */
+        }
+        statement(ls);
+    }
+}
+
+
+unsafe fn fieldsel(ls: *mut LexState, v: *mut expdesc) {
+    /* fieldsel -> ['.' | ':'] NAME */
+    let fs = (*ls).fs;
+    let mut key = expdesc::new();
+    luaK_exp2anyregup(fs, v);
+    luaX_next(ls);  /* skip the dot or colon */
+    checkname(ls, &mut key);
+    luaK_indexed(fs, v, &mut key);
+}
+
+
+unsafe fn yindex(ls: *mut LexState, v: *mut expdesc) {
+    /* index -> '[' expr ']' */
+    luaX_next(ls);  /* skip the '[' */
+    expr(ls, v);
+    luaK_exp2val((*ls).fs, v);
+    checknext(ls, b']' as c_int);
+}
+
+
+/*
+** {======================================================================
+** Rules for Constructors
+** =======================================================================
+*/
+
+
+struct ConsControl {
+    v: expdesc,  /* last list item read */
+    t: *mut expdesc,  /* table descriptor */
+    nh: c_int,  /* total number of 'record' elements */
+    na: c_int,  /* total number of array elements */
+    tostore: c_int,  /* number of array elements pending to be stored */
+}
+
+
+unsafe fn recfield(ls: *mut LexState, cc: *mut ConsControl) {
+    /* recfield -> (NAME | '['exp1']') = exp1 */
+    let fs = (*ls).fs;
+    let reg = (*(*ls).fs).freereg;
+    let mut key = expdesc::new();
+    let mut val = expdesc::new();
+    if (*ls).t.token == TK_NAME {
+        checklimit(fs, (*cc).nh, c_int::MAX, c_str!("items in a constructor"));
+        checkname(ls, &mut key);
+    } else {  /* ls->t.token == '[' */
+        yindex(ls, &mut key);
+    }
+    (*cc).nh += 1;
+    checknext(ls, b'=' as c_int);
+    let rkkey = luaK_exp2RK(fs, &mut key);
+    expr(ls, &mut val);
+    let rkval = luaK_exp2RK(fs, &mut val);
+    luaK_codeABC(fs, OP_SETTABLE, (*(*cc).t).u.info, rkkey, rkval);
+    (*fs).freereg = reg;  /* free registers */
+}
+
+
+unsafe fn closelistfield(fs: *mut FuncState, cc: *mut ConsControl) {
+    if (*cc).v.k == VVOID { return; }  /* there is no list item */
+    luaK_exp2nextreg(fs, &mut (*cc).v);
+    (*cc).v.k = VVOID;
+    if (*cc).tostore == LFIELDS_PER_FLUSH {
+        luaK_setlist(fs, (*(*cc).t).u.info, (*cc).na, (*cc).tostore);  /* flush */
+        (*cc).tostore = 0;  /* no more items pending */
+    }
+}
+
+
+unsafe fn lastlistfield(fs: *mut FuncState, cc: *mut ConsControl) {
+    if (*cc).tostore == 0 { return; }
+    if hasmultret((*cc).v.k) {
+        luaK_setmultret(fs, &mut (*cc).v);
+        luaK_setlist(fs, (*(*cc).t).u.info, (*cc).na, lua::LUA_MULTRET);
+        (*cc).na -= 1;  /* do not count last expression (unknown number of elements) */
+    } else {
+        if (*cc).v.k != VVOID {
+            luaK_exp2nextreg(fs, &mut (*cc).v);
+        }
+        luaK_setlist(fs, (*(*cc).t).u.info, (*cc).na, (*cc).tostore);
+    }
+}
+
+
+unsafe fn listfield(ls: *mut LexState, cc: *mut ConsControl) {
+    /* listfield -> exp */
+    expr(ls, &mut (*cc).v);
+    checklimit((*ls).fs, (*cc).na, c_int::MAX, c_str!("items in a constructor"));
+    (*cc).na += 1;
+    (*cc).tostore += 1;
+}
+
+
+unsafe fn field(ls: *mut LexState, cc: *mut ConsControl) {
+    /* field -> listfield | recfield */
+    match (*ls).t.token {
+        TK_NAME => {  /* may be 'listfield' or 'recfield' */
+            if luaX_lookahead(ls) != b'=' as c_int {  /* expression? */
+                listfield(ls, cc);
+            } else {
+                recfield(ls, cc);
+            }
+        }
+        t if t == b'[' as c_int => {
+            recfield(ls, cc);
+        }
+        _ => {
+            listfield(ls, cc);
+        }
+    }
+}
+
+
+unsafe fn constructor(ls: *mut LexState, t: *mut expdesc) {
+    /* constructor -> '{' [ field { sep field } [sep] ] '}'
+       sep -> ',' | ';' */
+    let fs = (*ls).fs;
+    let line = (*ls).linenumber;
+    let pc = luaK_codeABC(fs, OP_NEWTABLE, 0, 0, 0);
+    let mut cc = ConsControl { v: expdesc::new(), t, nh: 0, na: 0, tostore: 0 };
+    init_exp(t, VRELOCABLE, pc);
+    init_exp(&mut cc.v, VVOID, 0);  /* no value (yet) */
+    luaK_exp2nextreg((*ls).fs, t);  /* fix it at stack top */
+    checknext(ls, b'{' as c_int);
+    loop {
+        debug_assert!(cc.v.k == VVOID || cc.tostore > 0);
+        if (*ls).t.token == b'}' as c_int { break; }
+        closelistfield(fs, &mut cc);
+        field(ls, &mut cc);
+        if !(testnext(ls, b',' as c_int) || testnext(ls, b';' as c_int)) { break; }
+    }
+    check_match(ls, b'}' as c_int, b'{' as c_int, line);
+    lastlistfield(fs, &mut cc);
+    let code = &mut *(*(*fs).f).code.offset(pc as isize);
+    SETARG_B(code, luaO_int2fb(cc.na as u32));  /* set initial array size */
+    SETARG_C(code, luaO_int2fb(cc.nh as u32));  /* set initial table size */
+}
+
+/* }====================================================================== */
+
+
+
+unsafe fn parlist(ls: *mut LexState) {
+    /* parlist -> [ param { ',' param } ] */
+    let fs = (*ls).fs;
+    let f = (*fs).f;
+    let mut nparams = 0;
+    (*f).is_vararg = 0;
+    if (*ls).t.token != b')' as c_int {  /* is 'parlist' not empty? */
+        loop {
+            match (*ls).t.token {
+                TK_NAME => {  /* param -> NAME */
+                    new_localvar(ls, str_checkname(ls));
+                    nparams += 1;
+                }
+                TK_DOTS => {  /* param -> '...' */
+                    luaX_next(ls);
+                    (*f).is_vararg = 2;  /* declared vararg */
+                }
+                _ => luaX_syntaxerror(ls, c_str!("<name> or '...' expected")),
+            }
+            if (*f).is_vararg != 0 || !testnext(ls, b',' as c_int) { break; }
+        }
+    }
+    adjustlocalvars(ls, nparams);
+    (*f).numparams = (*fs).nactvar;
+    luaK_reserveregs(fs, (*fs).nactvar as c_int);  /* reserve register for parameters */
+}
+
+
+unsafe fn body(ls: *mut LexState, e: *mut expdesc, ismethod: bool, line: c_int) {
+    /* body ->  '(' parlist ')' block END */
+    let mut new_fs = FuncState::new();
+    let mut bl = BlockCnt::new();
+    new_fs.f = addprototype(ls);
+    (*new_fs.f).linedefined = line;
+    open_func(ls, &mut new_fs, &mut bl);
+    checknext(ls, b'(' as c_int);
+    if ismethod {
+        new_localvarliteral(ls, b"self");  /* create 'self' parameter */
+        adjustlocalvars(ls, 1);
+    }
+    parlist(ls);
+    checknext(ls, b')' as c_int);
+    statlist(ls);
+    (*new_fs.f).lastlinedefined = (*ls).linenumber;
+    check_match(ls, TK_END, TK_FUNCTION, line);
+    codeclosure(ls, e);
+    close_func(ls);
+}
+
+
+unsafe fn explist(ls: *mut LexState, v: *mut expdesc) -> c_int {
+    /* explist -> expr { ',' expr } */
+    let mut n = 1;  /* at least one expression */
+    expr(ls, v);
+    while testnext(ls, b',' as c_int) {
+        luaK_exp2nextreg((*ls).fs, v);
+        expr(ls, v);
+        n += 1;
+    }
+    n
+}
+
+
+unsafe fn funcargs(ls: *mut LexState, f: *mut expdesc, line: c_int) {
+    let fs = (*ls).fs;
+    let mut args = expdesc::new();
+    match (*ls).t.token {
+        t if t == b'(' as c_int => {  /* funcargs -> '(' [ explist ] ')' */
+            luaX_next(ls);
+            if (*ls).t.token == b')' as c_int {  /* arg list is empty? */
+                args.k = VVOID;
+            } else {
+                explist(ls, &mut args);
+                luaK_setmultret(fs, &mut args);
+            }
+            check_match(ls, b')' as c_int, b'(' as c_int, line);
+        }
+        t if t == b'{' as c_int => {  /* funcargs -> constructor */
+            constructor(ls, &mut args);
+        }
+        TK_STRING => {  /* funcargs -> STRING */
+            codestring(ls, &mut args, (*ls).t.seminfo.ts);
+            luaX_next(ls);  /* must use 'seminfo' before 'next' */
+        }
+        _ => {
+            luaX_syntaxerror(ls, c_str!("function arguments expected"));
+        }
+    }
+    debug_assert!((*f).k == VNONRELOC);
+    let base = (*f).u.info;  /* base register for call */
+    let nparams = if hasmultret(args.k) {
+        lua::LUA_MULTRET  /* open call */
+    } else {
+        if args.k != VVOID {
+            luaK_exp2nextreg(fs, &mut args);  /* close last argument */
+        }
+        (*fs).freereg as c_int - (base + 1)
+    };
+    init_exp(f, VCALL, luaK_codeABC(fs, OP_CALL, base, nparams + 1, 2));
+    luaK_fixline(fs, line);
+    (*fs).freereg = (base + 1) as u8;  /* call remove function and arguments and leaves
+                                          (unless changed) one result */
+}
+
+
+
+
+/*
+** {======================================================================
+** Expression parsing
+** =======================================================================
+*/
+
+
+unsafe fn primaryexp(ls: *mut LexState, v: *mut expdesc) {
+    /* primaryexp -> NAME | '(' expr ')' */
+    match (*ls).t.token {
+        t if t == b'(' as c_int => {
+            let line = (*ls).linenumber;
+            luaX_next(ls);
+            expr(ls, v);
+            check_match(ls, b')' as c_int, b'(' as c_int, line);
+            luaK_dischargevars((*ls).fs, v);
+        }
+        TK_NAME => {
+            singlevar(ls, v);
+        }
+        _ => {
+            luaX_syntaxerror(ls, c_str!("unexpected symbol"));
+        }
+    }
+}
+
+
+unsafe fn suffixedexp(ls: *mut LexState, v: *mut expdesc) {
+    /* suffixedexp ->
+         primaryexp { '.' NAME | '[' exp ']' | ':' NAME funcargs | funcargs } */
+    let fs = (*ls).fs;
+    let line = (*ls).linenumber;
+    primaryexp(ls, v);
+    loop {
+        match (*ls).t.token {
+            t if t == b'.' as c_int => {  /* fieldsel */
+                fieldsel(ls, v);
+            }
+            t if t == b'[' as c_int => {  /* '[' exp1 ']' */
+                let mut key = expdesc::new();
+                luaK_exp2anyregup(fs, v);
+                yindex(ls, &mut key);
+                luaK_indexed(fs, v, &mut key);
+            }
+            t if t == b':' as c_int => {  /* ':' NAME funcargs */
+                let mut key = expdesc::new();
+                luaX_next(ls);
+                checkname(ls, &mut key);
+                luaK_self(fs, v, &mut key);
+                funcargs(ls, v, line);
+            }
+            t if t == b'(' as c_int || t == TK_STRING || t == b'{' as c_int => {  /* funcargs */
+                luaK_exp2nextreg(fs, v);
+                funcargs(ls, v, line);
+            }
+            _ => return,
+        }
+    }
+}
+
+
+unsafe fn simpleexp(ls: *mut LexState, v: *mut expdesc) {
+    /* simpleexp -> FLT | INT | STRING | NIL | TRUE | FALSE | ... |
+                    constructor | FUNCTION body | suffixedexp */
+    match (*ls).t.token {
+        TK_FLT => {
+            init_exp(v, VKFLT, 0);
+            (*v).u.nval = (*ls).t.seminfo.r;
+        }
+        TK_INT => {
+            init_exp(v, VKINT, 0);
+            (*v).u.ival = (*ls).t.seminfo.i;
+        }
+        TK_STRING => {
+            codestring(ls, v, (*ls).t.seminfo.ts);
+        }
+        TK_NIL => {
+            init_exp(v, VNIL, 0);
+        }
+        TK_TRUE => {
+            init_exp(v, VTRUE, 0);
+        }
+        TK_FALSE => {
+            init_exp(v, VFALSE, 0);
+        }
+        TK_DOTS => {  /* vararg */
+            let fs = (*ls).fs;
+            check_condition(ls, (*(*fs).f).is_vararg != 0,
+                            c_str!("cannot use '...' outside a vararg function"));
+            (*(*fs).f).is_vararg = 1;  /* function actually uses vararg */
+            init_exp(v, VVARARG, luaK_codeABC(fs, OP_VARARG, 0, 1, 0));
+        }
+        t if t == b'{' as c_int => {  /* constructor */
+            constructor(ls, v);
+            return;
+        }
+        TK_FUNCTION => {
+            luaX_next(ls);
+            body(ls, v, false, (*ls).linenumber);
+            return;
+        }
+        _ => {
+            suffixedexp(ls, v);
+            return;
+        }
+    }
+    luaX_next(ls);
+}
+
+
+fn getunopr(op: c_int) -> UnOpr {
+    match op {
+        TK_NOT => OPR_NOT,
+        _ if op == b'-' as c_int => OPR_MINUS,
+        _ if op == b'~' as c_int => OPR_BNOT,
+        _ if op == b'#' as c_int => OPR_LEN,
+        _ => OPR_NOUNOPR,
+    }
+}
+
+
+fn getbinopr(op: c_int) -> BinOpr {
+    match op {
+        TK_IDIV => OPR_IDIV,
+        TK_SHL => OPR_SHL,
+        TK_SHR => OPR_SHR,
+        TK_CONCAT => OPR_CONCAT,
+        TK_NE => OPR_NE,
+        TK_EQ => OPR_EQ,
+        TK_LE => OPR_LE,
+        TK_GE => OPR_GE,
+        TK_AND => OPR_AND,
+        TK_OR => OPR_OR,
+        _ if op >= 256 => OPR_NOBINOPR,
+        _ => match op as u8 {
+            b'+' => OPR_ADD,
+            b'-' => OPR_SUB,
+            b'*' => OPR_MUL,
+            b'%' => OPR_MOD,
+            b'^' => OPR_POW,
+            b'/' => OPR_DIV,
+            b'&' => OPR_BAND,
+            b'|' => OPR_BOR,
+            b'~' => OPR_BXOR,
+            b'<' => OPR_LT,
+            b'>' => OPR_GT,
+            _ => OPR_NOBINOPR,
+        },
+    }
+}
+
+
+struct Priority {
+    left: u8,  /* left priority for each binary operator */
+    right: u8,  /* right priority */
+}
+
+static priority: [Priority; 21] = [  /* ORDER OPR */
+    Priority { left: 10, right: 10 }, Priority { left: 10, right: 10 },  /* '+' '-' */
+    Priority { left: 11, right: 11 }, Priority { left: 11, right: 11 },  /* '*' '%' */
+    Priority { left: 14, right: 13 },                  /* '^' (right associative) */
+    Priority { left: 11, right: 11 }, Priority { left: 11, right: 11 },  /* '/' '//' */
+    Priority { left: 6, right: 6 }, Priority { left: 4, right: 4 },
+    Priority { left: 5, right: 5 },                    /* '&' '|' '~' */
+    Priority { left: 7, right: 7 }, Priority { left: 7, right: 7 },      /* '<<' '>>' */
+    Priority { left: 9, right: 8 },                    /* '..' (right associative) */
+    Priority { left: 3, right: 3 }, Priority { left: 3, right: 3 },
+    Priority { left: 3, right: 3 },                    /* ==, <, <= */
+    Priority { left: 3, right: 3 }, Priority { left: 3, right: 3 },
+    Priority { left: 3, right: 3 },                    /* ~=, >, >= */
+    Priority { left: 2, right: 2 }, Priority { left: 1, right: 1 },      /* and, or */
+];
+
+const UNARY_PRIORITY: c_int = 12;  /* priority for unary operators */
+
+
+/*
+** subexpr -> (simpleexp | unop subexpr) { binop subexpr }
+** where 'binop' is any binary operator with a priority higher than 'limit'
+*/
+unsafe fn subexpr(ls: *mut LexState, v: *mut expdesc, limit: c_int) -> BinOpr {
+    enterlevel(ls);
+    let uop = getunopr((*ls).t.token);
+    if uop != OPR_NOUNOPR {
+        let line = (*ls).linenumber;
+        luaX_next(ls);
+        subexpr(ls, v, UNARY_PRIORITY);
+        luaK_prefix((*ls).fs, uop, v, line);
+    } else {
+        simpleexp(ls, v);
+    }
+    /* expand while operators have priorities higher than 'limit' */
+    let mut op = getbinopr((*ls).t.token);
+    while op != OPR_NOBINOPR && priority[op as usize].left as c_int > limit {
+        let mut v2 = expdesc::new();
+        let line = (*ls).linenumber;
+        luaX_next(ls);
+        luaK_infix((*ls).fs, op, v);
+        /* read sub-expression with higher priority */
+        let nextop = subexpr(ls, &mut v2, priority[op as usize].right as c_int);
+        luaK_posfix((*ls).fs, op, v, &mut v2, line);
+        op = nextop;
+    }
+    leavelevel(ls);
+    op  /* return first untreated operator */
+}
+
+
+unsafe fn expr(ls: *mut LexState, v: *mut expdesc) {
+    subexpr(ls, v, 0);
+}
+
+/* }==================================================================== */
+
+
+
+/*
+** {======================================================================
+** Rules for Statements
+** =======================================================================
+*/
+
+
+unsafe fn block(ls: *mut LexState) {
+    /* block -> statlist */
+    let fs = (*ls).fs;
+    let mut bl = BlockCnt::new();
+    enterblock(fs, &mut bl, false);
+    statlist(ls);
+    leaveblock(fs);
+}
+
+
+/*
+** structure to chain all variables in the left-hand side of an
+** assignment
+*/
+struct LHS_assign {
+    prev: *mut LHS_assign,
+    v: expdesc,  /* variable (global, local, upvalue, or indexed) */
+}
+
+
+/*
+** check whether, in an assignment to an upvalue/local variable, the
+** upvalue/local variable is begin used in a previous assignment to a
+** table. If so, save original upvalue/local value in a safe place and
+** use this safe copy in the previous assignment.
+*/
+unsafe fn check_conflict(ls: *mut LexState, mut lh: *mut LHS_assign, v: *const expdesc) {
+    let fs = (*ls).fs;
+    let extra = (*fs).freereg as c_int;  /* eventual position to save local variable */
+    let mut conflict = false;
+    while !lh.is_null() {  /* check all previous assignments */
+        if (*lh).v.k == VINDEXED {  /* assigning to a table? */
+            /* table is the upvalue/local being assigned now? */
+            if (*lh).v.u.ind.vt == (*v).k as u8 && (*lh).v.u.ind.t as c_int == (*v).u.info {
+                conflict = true;
+                (*lh).v.u.ind.vt = VLOCAL as u8;
+                (*lh).v.u.ind.t = extra as u8;  /* previous assignment will use safe copy */
+            }
+            /* index is the local being assigned? (index cannot be upvalue) */
+            if (*v).k == VLOCAL && (*lh).v.u.ind.idx as c_int == (*v).u.info {
+                conflict = true;
+                (*lh).v.u.ind.idx = extra as c_short;  /* previous assignment will use safe copy */
+            }
+        }
+        lh = (*lh).prev;
+    }
+    if conflict {
+        /* copy upvalue/local value to a temporary (in position 'extra') */
+        let op = if (*v).k == VLOCAL { OP_MOVE } else { OP_GETUPVAL };
+        luaK_codeABC(fs, op, extra, (*v).u.info, 0);
+        luaK_reserveregs(fs, 1);
+    }
+}
+
+
+unsafe fn assignment(ls: *mut LexState, lh: *mut LHS_assign, nvars: c_int) {
+    let mut e = expdesc::new();
+    check_condition(ls, vkisvar((*lh).v.k), c_str!("syntax error"));
+    if testnext(ls, b',' as c_int) {  /* assignment -> ',' suffixedexp assignment */
+        let mut nv = LHS_assign { prev: lh, v: expdesc::new() };
+        suffixedexp(ls, &mut nv.v);
+        if nv.v.k != VINDEXED {
+            check_conflict(ls, lh, &nv.v);
+        }
+        checklimit((*ls).fs, nvars + (*(*ls).L).nCcalls as c_int, LUAI_MAXCCALLS,
+                        c_str!("C levels"));
+        assignment(ls, &mut nv, nvars + 1);
+    } else {  /* assignment -> '=' explist */
+        checknext(ls, b'=' as c_int);
+        let nexps = explist(ls, &mut e);
+        if nexps != nvars {
+            adjust_assign(ls, nvars, nexps, &mut e);
+        } else {
+            luaK_setoneret((*ls).fs, &mut e);  /* close last expression */
+            luaK_storevar((*ls).fs, &mut (*lh).v, &mut e);
+            return;  /* avoid default */
+        }
+    }
+    init_exp(&mut e, VNONRELOC, (*(*ls).fs).freereg as c_int - 1);  /* default assignment */
+    luaK_storevar((*ls).fs, &mut (*lh).v, &mut e);
+}
+
+
+unsafe fn cond(ls: *mut LexState) -> c_int {
+    /* cond -> exp */
+    let mut v = expdesc::new();
+    expr(ls, &mut v);  /* read condition */
+    if v.k == VNIL { v.k = VFALSE; }  /* 'falses' are all equal here */
+    luaK_goiftrue((*ls).fs, &mut v);
+    v.f
+}
+
+
+unsafe fn gotostat(ls: *mut LexState, pc: c_int) {
+    let line = (*ls).linenumber;
+    let label = if testnext(ls, TK_GOTO) {
+        str_checkname(ls)
+    } else {
+        luaX_next(ls);  /* skip break */
+        luaS_new((*ls).L, c_str!("break"))
+    };
+    let g = newlabelentry(ls, &mut (*(*ls).dyd).gt, label, line, pc);
+    findlabel(ls, g);  /* close it if label already defined */
+}
+
+
+/* check for repeated labels on the same block */
+unsafe fn checkrepeated(fs: *mut FuncState, ll: *mut Labellist, label: *mut TString) {
+    for i in (*(*fs).bl).firstlabel..(*ll).n {
+        let lb = (*ll).arr.offset(i as isize);
+        if label == (*lb).name {
+            let msg = luaO_pushfstring((*(*fs).ls).L,
+                                c_str!("label '%s' already defined on line %d"),
+                                getstr(label), (*lb).line);
+            semerror((*fs).ls, msg);
+        }
+    }
+}
+
+
+/* skip no-op statements */
+unsafe fn skipnoopstat(ls: *mut LexState) {
+    loop {
+        let t = (*ls).t.token;
+        if t != b';' as c_int && t != TK_DBCOLON { break; }
+        statement(ls);
+    }
+}
+
+
+unsafe fn labelstat(ls: *mut LexState, label: *mut TString, line: c_int) {
+    /* label -> '::' NAME '::' */
+    let fs = (*ls).fs;
+    let ll = &mut (*(*ls).dyd).label as *mut Labellist;
+    checkrepeated(fs, ll, label);  /* check for repeated labels */
+    checknext(ls, TK_DBCOLON);  /* skip double colon */
+    /* create new entry for this label */
+    let l = newlabelentry(ls, ll, label, line, luaK_getlabel(fs));  /* index of new label */
+    skipnoopstat(ls);  /* skip other no-op statements */
+    if block_follow(ls, false) {  /* label is last no-op statement in the block? */
+        /* assume that locals are already out of scope */
+        (*(*ll).arr.offset(l as isize)).nactvar = (*(*fs).bl).nactvar;
+    }
+    findgotos(ls, (*ll).arr.offset(l as isize));
+}
+
+
+unsafe fn whilestat(ls: *mut LexState, line: c_int) {
+    /* whilestat -> WHILE cond DO block END */
+    let fs = (*ls).fs;
+    let mut bl = BlockCnt::new();
+    luaX_next(ls);  /* skip WHILE */
+    let whileinit = luaK_getlabel(fs);
+    let condexit = cond(ls);
+    enterblock(fs, &mut bl, true);
+    checknext(ls, TK_DO);
+    block(ls);
+    luaK_jumpto(fs, whileinit);
+    check_match(ls, TK_END, TK_WHILE, line);
+    leaveblock(fs);
+    luaK_patchtohere(fs, condexit);  /* false conditions finish the loop */
+}
+
+
+unsafe fn repeatstat(ls: *mut LexState, line: c_int) {
+    /* repeatstat -> REPEAT block UNTIL cond */
+    let fs = (*ls).fs;
+    let repeat_init = luaK_getlabel(fs);
+    let mut bl1 = BlockCnt::new();
+    let mut bl2 = BlockCnt::new();
+    enterblock(fs, &mut bl1, true);  /* loop block */
+    enterblock(fs, &mut bl2, false);  /* scope block */
+    luaX_next(ls);  /* skip REPEAT */
+    statlist(ls);
+    check_match(ls, TK_UNTIL, TK_REPEAT, line);
+    let condexit = cond(ls);  /* read condition (inside scope block) */
+    if bl2.upval {  /* upvalues? */
+        luaK_patchclose(fs, condexit, bl2.nactvar as c_int);
+    }
+    leaveblock(fs);  /* finish scope */
+    luaK_patchlist(fs, condexit, repeat_init);  /* close the loop */
+    leaveblock(fs);  /* finish loop */
+}
+
+
+unsafe fn exp1(ls: *mut LexState) -> c_int {
+    let mut e = expdesc::new();
+    expr(ls, &mut e);
+    luaK_exp2nextreg((*ls).fs, &mut e);
+    debug_assert!(e.k == VNONRELOC);
+    e.u.info
+}
+
+
+unsafe fn forbody(ls: *mut LexState, base: c_int, line: c_int, nvars: c_int, isnum: bool) {
+    /* forbody -> DO block */
+    let mut bl = BlockCnt::new();
+    let fs = (*ls).fs;
+    adjustlocalvars(ls, 3);  /* control variables */
+    checknext(ls, TK_DO);
+    let prep = if isnum { luaK_codeAsBx(fs, OP_FORPREP, base, NO_JUMP) } else { luaK_jump(fs) };
+    enterblock(fs, &mut bl, false);  /* scope for declared variables */
+    adjustlocalvars(ls, nvars);
+    luaK_reserveregs(fs, nvars);
+    block(ls);
+    leaveblock(fs);  /* end of scope for declared variables */
+    luaK_patchtohere(fs, prep);
+    let endfor = if isnum {  /* numeric for? */
+        luaK_codeAsBx(fs, OP_FORLOOP, base, NO_JUMP)
+    } else {  /* generic for */
+        luaK_codeABC(fs, OP_TFORCALL, base, 0, nvars);
+        luaK_fixline(fs, line);
+        luaK_codeAsBx(fs, OP_TFORLOOP, base + 2, NO_JUMP)
+    };
+    luaK_patchlist(fs, endfor, prep + 1);
+    luaK_fixline(fs, line);
+}
+
+
+unsafe fn fornum(ls: *mut LexState, varname: *mut TString, line: c_int) {
+    /* fornum -> NAME = exp1,exp1[,exp1] forbody */
+    let fs = (*ls).fs;
+    let base = (*fs).freereg as c_int;
+    new_localvarliteral(ls, b"(for index)");
+    new_localvarliteral(ls, b"(for limit)");
+    new_localvarliteral(ls, b"(for step)");
+    new_localvar(ls, varname);
+    checknext(ls, b'=' as c_int);
+    exp1(ls);  /* initial value */
+    checknext(ls, b',' as c_int);
+    exp1(ls);  /* limit */
+    if testnext(ls, b',' as c_int) {
+        exp1(ls);  /* optional step */
+    } else {  /* default step = 1 */
+        let k = luaK_intK(fs, 1);
+        luaK_codek(fs, (*fs).freereg as c_int, k);
+        luaK_reserveregs(fs, 1);
+    }
+    forbody(ls, base, line, 1, true);
+}
+
+
+unsafe fn forlist(ls: *mut LexState, indexname: *mut TString) {
+    /* forlist -> NAME {,NAME} IN explist forbody */
+    let fs = (*ls).fs;
+    let mut e = expdesc::new();
+    let mut nvars = 4;  /* gen, state, control, plus at least one declared var */
+    let base = (*fs).freereg as c_int;
+    /* create control variables */
+    new_localvarliteral(ls, b"(for generator)");
+    new_localvarliteral(ls, b"(for state)");
+    new_localvarliteral(ls, b"(for control)");
+    /* create declared variables */
+    new_localvar(ls, indexname);
+    while testnext(ls, b',' as c_int) {
+        new_localvar(ls, str_checkname(ls));
+        nvars += 1;
+    }
+    checknext(ls, TK_IN);
+    let line = (*ls).linenumber;
+    let nexps = explist(ls, &mut e);
+    adjust_assign(ls, 3, nexps, &mut e);
+    luaK_checkstack(fs, 3);  /* extra space to call generator */
+    forbody(ls, base, line, nvars - 3, false);
+}
+
+
+unsafe fn forstat(ls: *mut LexState, line: c_int) {
+    /* forstat -> FOR (fornum | forlist) END */
+    let fs = (*ls).fs;
+    let mut bl = BlockCnt::new();
+    enterblock(fs, &mut bl, true);  /* scope for loop and control variables */
+    luaX_next(ls);  /* skip 'for' */
+    let varname = str_checkname(ls);  /* first variable name */
+    match (*ls).t.token {
+        t if t == b'=' as c_int => fornum(ls, varname, line),
+        t if t == b',' as c_int || t == TK_IN => forlist(ls, varname),
+        _ => luaX_syntaxerror(ls, c_str!("'=' or 'in' expected")),
+    }
+    check_match(ls, TK_END, TK_FOR, line);
+    leaveblock(fs);  /* loop scope ('break' jumps to this point) */
+}
+
+
+unsafe fn test_then_block(ls: *mut LexState, escapelist: *mut c_int) {
+    /* test_then_block -> [IF | ELSEIF] cond THEN block */
+    let mut bl = BlockCnt::new();
+    let fs = (*ls).fs;
+    let mut v = expdesc::new();
+    let jf;  /* instruction to skip 'then' code (if condition is false) */
+    luaX_next(ls);  /* skip IF or ELSEIF */
+    expr(ls, &mut v);  /* read condition */
+    checknext(ls, TK_THEN);
+    if (*ls).t.token == TK_GOTO || (*ls).t.token == TK_BREAK {
+        luaK_goiffalse((*ls).fs, &mut v);  /* will jump to label if condition is true */
+        enterblock(fs, &mut bl, false);  /* must enter block before 'goto' */
+        gotostat(ls, v.t);  /* handle goto/break */
+        skipnoopstat(ls);  /* skip other no-op statements */
+        if block_follow(ls, false) {  /* 'goto' is the entire block? */
+            leaveblock(fs);
+            return;  /* and that is it */
+        } else {  /* must skip over 'then' part if condition is false */
+            jf = luaK_jump(fs);
+        }
+    } else {  /* regular case (not goto/break) */
+        luaK_goiftrue((*ls).fs, &mut v);  /* skip over block if condition is false */
+        enterblock(fs, &mut bl, false);
+        jf = v.f;
+    }
+    statlist(ls);  /* 'then' part */
+    leaveblock(fs);
+    if (*ls).t.token == TK_ELSE ||
+       (*ls).t.token == TK_ELSEIF {  /* followed by 'else'/'elseif'? */
+        let j = luaK_jump(fs);
+        luaK_concat(fs, escapelist, j);  /* must jump over it */
+    }
+    luaK_patchtohere(fs, jf);
+}
+
+
+unsafe fn ifstat(ls: *mut LexState, line: c_int) {
+    /* ifstat -> IF cond THEN block {ELSEIF cond THEN block} [ELSE block] END */
+    let fs = (*ls).fs;
+    let mut escapelist = NO_JUMP;  /* exit list for finished parts */
+    test_then_block(ls, &mut escapelist);  /* IF cond THEN block */
+    loop {
+        if (*ls).t.token != TK_ELSEIF { break; }
+        test_then_block(ls, &mut escapelist);  /* ELSEIF cond THEN block */
+    }
+    if testnext(ls, TK_ELSE) {
+        block(ls);  /* 'else' part */
+    }
+    check_match(ls, TK_END, TK_IF, line);
+    luaK_patchtohere(fs, escapelist);  /* patch escape list to 'if' end */
+}
+
+
+unsafe fn localfunc(ls: *mut LexState) {
+    let mut b = expdesc::new();
+    let fs = (*ls).fs;
+    new_localvar(ls, str_checkname(ls));  /* new local variable */
+    adjustlocalvars(ls, 1);  /* enter its scope */
+    body(ls, &mut b, false, (*ls).linenumber);  /* function created in next register */
+    /* debug information will only see the variable after this point! */
+    (*getlocvar(fs, b.u.info)).startpc = (*fs).pc;
+}
+
+
+unsafe fn localstat(ls: *mut LexState) {
+    /* stat -> LOCAL NAME {',' NAME} ['=' explist] */
+    let mut nvars = 0;
+    let nexps;
+    let mut e = expdesc::new();
+    loop {
+        new_localvar(ls, str_checkname(ls));
+        nvars += 1;
+        if !testnext(ls, b',' as c_int) { break; }
+    }
+    if testnext(ls, b'=' as c_int) {
+        nexps = explist(ls, &mut e);
+    } else {
+        e.k = VVOID;
+        nexps = 0;
+    }
+    adjust_assign(ls, nvars, nexps, &mut e);
+    adjustlocalvars(ls, nvars);
+}
+
+
+unsafe fn funcname(ls: *mut LexState, v: *mut expdesc) -> bool {
+    /* funcname -> NAME {fieldsel} [':' NAME] */
+    let mut ismethod = false;
+    singlevar(ls, v);
+    loop {
+        if (*ls).t.token != b'.' as c_int { break; }
+        fieldsel(ls, v);
+    }
+    if (*ls).t.token == b':' as c_int {
+        ismethod = true;
+        fieldsel(ls, v);
+    }
+    ismethod
+}
+
+
+unsafe fn funcstat(ls: *mut LexState, line: c_int) {
+    /* funcstat -> FUNCTION funcname body */
+    let mut v = expdesc::new();
+    let mut b = expdesc::new();
+    luaX_next(ls);  /* skip FUNCTION */
+    let ismethod = funcname(ls, &mut v);
+    body(ls, &mut b, ismethod, line);
+    luaK_storevar((*ls).fs, &mut v, &mut b);
+    luaK_fixline((*ls).fs, line);  /* definition "happens" in the first line */
+}
+
+
+unsafe fn exprstat(ls: *mut LexState) {
+    /* stat -> func | assignment */
+    let fs = (*ls).fs;
+    let mut v = LHS_assign { prev: ptr::null_mut(), v: expdesc::new() };
+    suffixedexp(ls, &mut v.v);
+    if (*ls).t.token == b'=' as c_int || (*ls).t.token == b',' as c_int { /* stat -> assignment ? */
+        v.prev = ptr::null_mut();
+        assignment(ls, &mut v, 1);
+    } else {  /* stat -> func */
+        check_condition(ls, v.v.k == VCALL, c_str!("syntax error"));
+        SETARG_C(&mut *getinstruction(fs, &v.v), 1);  /* call statement uses no results */
+    }
+}
+
+
+unsafe fn retstat(ls: *mut LexState) {
+    /* stat -> RETURN [explist] [';'] */
+    let fs = (*ls).fs;
+    let mut e = expdesc::new();
+    let first;  /* registers with returned values */
+    let mut nret;
+    if block_follow(ls, true) || (*ls).t.token == b';' as c_int {
+        first = 0;  /* return no values */
+        nret = 0;
+    } else {
+        nret = explist(ls, &mut e);  /* optional return values */
+        if hasmultret(e.k) {
+            luaK_setmultret(fs, &mut e);
+            if e.k == VCALL && nret == 1 {  /* tail call? */
+                SET_OPCODE(&mut *getinstruction(fs, &e), OP_TAILCALL);
+                debug_assert!(GETARG_A(*getinstruction(fs, &e)) == (*fs).nactvar as c_int);
+            }
+            first = (*fs).nactvar as c_int;
+            nret = lua::LUA_MULTRET;  /* return all values */
+        } else if nret == 1 {  /* only one single value? */
+            first = luaK_exp2anyreg(fs, &mut e);
+        } else {
+            luaK_exp2nextreg(fs, &mut e);  /* values must go to the stack */
+            first = (*fs).nactvar as c_int;  /* return all active values */
+            debug_assert!(nret == (*fs).freereg as c_int - first);
+        }
+    }
+    luaK_ret(fs, first, nret);
+    testnext(ls, b';' as c_int);  /* skip optional semicolon */
+}
+
+
+unsafe fn statement(ls: *mut LexState) {
+    let line = (*ls).linenumber;  /* may be needed for error messages */
+    enterlevel(ls);
+    match (*ls).t.token {
+        t if t == b';' as c_int => {  /* stat -> ';' (empty statement) */
+            luaX_next(ls);  /* skip ';' */
+        }
+        TK_IF => {  /* stat -> ifstat */
+            ifstat(ls, line);
+        }
+        TK_WHILE => {  /* stat -> whilestat */
+            whilestat(ls, line);
+        }
+        TK_DO => {  /* stat -> DO block END */
+            luaX_next(ls);  /* skip DO */
+            block(ls);
+            check_match(ls, TK_END, TK_DO, line);
+        }
+        TK_FOR => {  /* stat -> forstat */
+            forstat(ls, line);
+        }
+        TK_REPEAT => {  /* stat -> repeatstat */
+            repeatstat(ls, line);
+        }
+        TK_FUNCTION => {  /* stat -> funcstat */
+            funcstat(ls, line);
+        }
+        TK_LOCAL => {  /* stat -> localstat */
+            luaX_next(ls);  /* skip LOCAL */
+            if testnext(ls, TK_FUNCTION) {  /* local function? */
+                localfunc(ls);
+            } else {
+                localstat(ls);
+            }
+        }
+        TK_DBCOLON => {  /* stat -> label */
+            luaX_next(ls);  /* skip double colon */
+            let label = str_checkname(ls);
+            labelstat(ls, label, line);
+        }
+        TK_RETURN => {  /* stat -> retstat */
+            luaX_next(ls);  /* skip RETURN */
+            retstat(ls);
+        }
+        TK_BREAK |  /* stat -> breakstat */
+        TK_GOTO => {  /* stat -> 'goto' NAME */
+            let pc = luaK_jump((*ls).fs);
+            gotostat(ls, pc);
+        }
+        _ => {  /* stat -> func | assignment */
+            exprstat(ls);
+        }
+    }
+    debug_assert!((*(*(*ls).fs).f).maxstacksize >= (*(*ls).fs).freereg &&
+                  (*(*ls).fs).freereg >= (*(*ls).fs).nactvar);
+    (*(*ls).fs).freereg = (*(*ls).fs).nactvar;  /* free registers */
+    leavelevel(ls);
+}
+
+/* }====================================================================== */
+
+
+/*
+** compiles the main function, which is a regular vararg function with an
+** upvalue named LUA_ENV
+*/
+unsafe fn mainfunc(ls: *mut LexState, fs: *mut FuncState) {
+    let mut bl = BlockCnt::new();
+    let mut v = expdesc::new();
+    open_func(ls, fs, &mut bl);
+    (*(*fs).f).is_vararg = 2;  /* main function is always declared vararg */
+    init_exp(&mut v, VLOCAL, 0);  /* create and... */
+    newupvalue(fs, (*ls).envn, &v);  /* ...set environment upvalue */
+    luaX_next(ls);  /* read first token */
+    statlist(ls);  /* parse main body */
+    check(ls, TK_EOS);
+    close_func(ls);
+}
+
+
+#[cfg(rust_lparser)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaY_parser(L: *mut lua_State, z: *mut Zio, buff: *mut Mbuffer,
+                                     dyd: *mut Dyndata, name: *const c_char,
+                                     firstchar: c_int) -> *mut LClosure {
+    let mut lexstate: LexState = mem::zeroed();
+    let mut funcstate = FuncState::new();
+    let cl = luaF_newLclosure(L, 1);  /* create main closure */
+    setclLvalue((*L).top, cl);  /* anchor it (to avoid being collected) */
+    luaD_inctop(L);
+    lexstate.h = luaH_new(L);  /* create table for scanner */
+    sethvalue((*L).top, lexstate.h);  /* anchor it */
+    luaD_inctop(L);
+    funcstate.f = luaF_newproto(L);
+    (*cl).p = funcstate.f;
+    (*funcstate.f).source = luaS_new(L, name);  /* create and anchor TString */
+    lexstate.buff = buff;
+    lexstate.dyd = dyd;
+    (*dyd).actvar.n = 0;
+    (*dyd).gt.n = 0;
+    (*dyd).label.n = 0;
+    luaX_setinput(L, &mut lexstate, z, (*funcstate.f).source, firstchar);
+    mainfunc(&mut lexstate, &mut funcstate);
+    debug_assert!(funcstate.prev.is_null() && funcstate.nups == 1 && lexstate.fs.is_null());
+    /* all scopes should be correctly finished */
+    debug_assert!((*dyd).actvar.n == 0 && (*dyd).gt.n == 0 && (*dyd).label.n == 0);
+    (*L).top = (*L).top.sub(1);  /* remove scanner's table */
+    cl  /* closure is on the stack, too */
+}
@@ -0,0 +1,631 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Global State.
+//!
+//! 'lua_State' and 'global_State' keep the layout of the C structures, as
+//! the C parts of the core still reach into them. States are allocated
+//! here, with room after the global state for the Rust values a state
+//! owns (see 'userdata.rs').
+
+#![allow(non_camel_case_types, non_snake_case)]
+
+use ffi::lua::{self, lua_Alloc, lua_CFunction, lua_Hook, lua_KContext, lua_KFunction, lua_Number,
+               LUA_MINSTACK, LUA_OK, LUA_RIDX_GLOBALS, LUA_RIDX_LAST, LUA_RIDX_MAINTHREAD};
+use ffi::luaconf::LUA_EXTRASPACE;
+use lapi::{api_incr_top, lua_version};
+use ldebug::resethookcount;
+use ldo::{lua_longjmp, rawrunprotected};
+use lfunc::luaF_close;
+use lgc::{luaC_checkGC, luaC_freeallobjects, luaC_white, GCSpause, MAX_LMEM, WHITE0BIT};
+use llex::luaX_init;
+use lmem::{luaM_free, luaM_freearray, luaM_new, luaM_newvector, luaM_realloc_};
+use lobject::{sethvalue, setnilvalue, setthvalue, GCObject, TString, TValue, Table, UpVal};
+use lopcodes::Instruction;
+use lstring::luaS_init;
+use ltable::{luaH_new, luaH_resize, luaH_setint};
+use ltm::{luaT_init, TM_N};
+use libc::{c_int, c_uint, c_void, size_t};
+use std::any::Any;
+use std::collections::HashMap;
+use std::{mem, ptr};
+
+
+/* the exported functions below, from 'lstate.c' when it is linked instead (see 'build.rs') */
+#[cfg(not(rust_lstate))]
+extern "C-unwind" {
+    pub fn luaE_setdebt(g: *mut global_State, debt: isize);
+    pub fn luaE_extendCI(L: *mut lua_State) -> *mut CallInfo;
+    pub fn luaE_freeCI(L: *mut lua_State);
+    pub fn luaE_shrinkCI(L: *mut lua_State);
+    pub fn lua_newthread(L: *mut lua_State) -> *mut lua_State;
+    pub fn luaE_freethread(L: *mut lua_State, L1: *mut lua_State);
+    pub fn lua_newstate(f: lua_Alloc, ud: *mut c_void) -> *mut lua_State;
+    pub fn lua_close(L: *mut lua_State);
+}
+
+
+/* default values of the collector parameters */
+const LUAI_GCPAUSE: c_int = 200;  /* 200% */
+const LUAI_GCMUL: c_int = 200;  /* GC runs 'twice the speed' of memory allocation */
+
+
+pub type StkId = *mut TValue;  /* index to stack elements */
+
+
+/* extra stack space to handle TM calls and some other extras */
+pub const EXTRA_STACK: c_int = 5;
+
+pub const BASIC_STACK_SIZE: c_int = 2 * LUA_MINSTACK;
+
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct CallInfoL {  /* only for Lua functions */
+    pub base: StkId,  /* base for this function */
+    pub savedpc: *const Instruction,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct CallInfoC {  /* only for C functions */
+    pub k: lua_KFunction,  /* continuation in case of yields */
+    pub old_errfunc: isize,
+    pub ctx: lua_KContext,  /* context info. in case of yields */
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union CallInfoU {
+    pub l: CallInfoL,
+    pub c: CallInfoC,
+}
+
+
+/*
+** Information about a call.
+*/
+#[repr(C)]
+pub struct CallInfo {
+    pub func: StkId,  /* function index in the stack */
+    pub top: StkId,  /* top for this function */
+    pub previous: *mut CallInfo,  /* dynamic call link */
+    pub next: *mut CallInfo,
+    pub u: CallInfoU,
+    pub extra: isize,
+    pub nresults: i16,  /* expected number of results from this function */
+    pub callstatus: u8,
+}
+
+
+/*
+** Bits in CallInfo status
+*/
+pub const CIST_OAH: u8 = 1 << 0;  /* original value of 'allowhook' */
+pub const CIST_LUA: u8 = 1 << 1;  /* call is running a Lua function */
+pub const CIST_HOOKED: u8 = 1 << 2;  /* call is running a debug hook */
+pub const CIST_FRESH: u8 = 1 << 3;  /* call is running on a fresh invocation
+                                       of luaV_execute */
+pub const CIST_YPCALL: u8 = 1 << 4;  /* call is a yieldable protected call */
+pub const CIST_TAIL: u8 = 1 << 5;  /* call was tail called */
+pub const CIST_HOOKYIELD: u8 = 1 << 6;  /* last hook called yielded */
+pub const CIST_LEQ: u8 = 1 << 7;  /* using __lt for __le */
+
+pub unsafe fn isLua(ci: *const CallInfo) -> bool { (*ci).callstatus & CIST_LUA != 0 }
+
+pub fn getoah(st: u8) -> u8 { st & CIST_OAH }
+
+
+/* kinds of Garbage Collection */
+pub const KGC_NORMAL: u8 = 0;
+pub const KGC_EMERGENCY: u8 = 1;  /* gc was forced by an allocation failure */
+
+
+#[repr(C)]
+pub struct stringtable {
+    pub hash: *mut *mut TString,
+    pub nuse: c_int,  /* number of elements */
+    pub size: c_int,
+}
+
+
+/* size of the API string cache; must agree with the C core */
+#[cfg(ltests)]
+pub const STRCACHE_N: usize = 23;  /* as set by 'ltests.h' */
+#[cfg(ltests)]
+pub const STRCACHE_M: usize = 5;
+#[cfg(not(ltests))]
+pub const STRCACHE_N: usize = 53;
+#[cfg(not(ltests))]
+pub const STRCACHE_M: usize = 2;
+
+
+/*
+** 'global state', shared by all threads of this state
+*/
+#[repr(C)]
+pub struct global_State {
+    pub frealloc: lua_Alloc,  /* function to reallocate memory */
+    pub ud: *mut c_void,  /* auxiliary data to 'frealloc' */
+    pub totalbytes: isize,  /* number of bytes currently allocated - GCdebt */
+    pub GCdebt: isize,  /* bytes allocated not yet compensated by the collector */
+    pub GCmemtrav: size_t,  /* memory traversed by the GC */
+    pub GCestimate: size_t,  /* an estimate of the non-garbage memory in use */
+    pub strt: stringtable,  /* hash table for strings */
+    pub l_registry: TValue,
+    pub seed: c_uint,  /* randomized seed for hashes */
+    pub currentwhite: u8,
+    pub gcstate: u8,  /* state of garbage collector */
+    pub gckind: u8,  /* kind of GC running */
+    pub gcrunning: u8,  /* true if GC is running */
+    pub allgc: *mut GCObject,  /* list of all collectable objects */
+    pub sweepgc: *mut *mut GCObject,  /* current position of sweep in list */
+    pub finobj: *mut GCObject,  /* list of collectable objects with finalizers */
+    pub gray: *mut GCObject,  /* list of gray objects */
+    pub grayagain: *mut GCObject,  /* list of objects to be traversed atomically */
+    pub weak: *mut GCObject,  /* list of tables with weak values */
+    pub ephemeron: *mut GCObject,  /* list of ephemeron tables (weak keys) */
+    pub allweak: *mut GCObject,  /* list of all-weak tables */
+    pub tobefnz: *mut GCObject,  /* list of userdata to be GC */
+    pub fixedgc: *mut GCObject,  /* list of objects not to be collected */
+    pub twups: *mut lua_State,  /* list of threads with open upvalues */
+    pub gcfinnum: c_uint,  /* number of finalizers to call in each GC step */
+    pub gcpause: c_int,  /* size of pause between successive GCs */
+    pub gcstepmul: c_int,  /* GC 'granularity' */
+    pub panic: lua_CFunction,  /* to be called in unprotected errors */
+    pub mainthread: *mut lua_State,
+    pub version: *const lua_Number,  /* pointer to version number */
+    pub memerrmsg: *mut TString,  /* memory-error message */
+    pub tmname: [*mut TString; TM_N],  /* array with tag-method names */
+    pub mt: [*mut Table; lua::LUA_NUMTAGS as usize],  /* metatables for basic types */
+    pub strcache: [[*mut TString; STRCACHE_M]; STRCACHE_N],  /* cache for strings in API */
+}
+
+
+/*
+** 'per thread' state
+*/
+#[repr(C)]
+pub struct lua_State {
+    pub next: *mut GCObject,
+    pub tt: u8,
+    pub marked: u8,
+    pub nci: u16,  /* number of items in 'ci' list */
+    pub status: u8,
+    pub top: StkId,  /* first free slot in the stack */
+    pub l_G: *mut global_State,
+    pub ci: *mut CallInfo,  /* call info for current function */
+    pub oldpc: *const Instruction,  /* last pc traced */
+    pub stack_last: StkId,  /* last free slot in the stack */
+    pub stack: StkId,  /* stack base */
+    pub openupval: *mut UpVal,  /* list of open upvalues in this stack */
+    pub gclist: *mut GCObject,
+    pub twups: *mut lua_State,  /* list of threads with open upvalues */
+    pub errorJmp: *mut lua_longjmp,  /* current error recover point */
+    pub base_ci: CallInfo,  /* CallInfo for first level (C calling Lua) */
+    pub hook: lua_Hook,
+    pub errfunc: isize,  /* current error handling function (stack index) */
+    pub stacksize: c_int,
+    pub basehookcount: c_int,
+    pub hookcount: c_int,
+    pub nny: u16,  /* number of non-yieldable calls in stack */
+    pub nCcalls: u16,  /* number of nested C calls */
+    pub hookmask: c_int,
+    pub allowhook: u8,
+}
+
+
+pub unsafe fn G(l: *mut lua_State) -> *mut global_State { (*l).l_G }
+
+/* actual number of total bytes allocated */
+pub unsafe fn gettotalbytes(g: *const global_State) -> usize {
+    ((*g).totalbytes + (*g).GCdebt) as usize
+}
+
+/* test whether thread is in 'twups' list (from 'lfunc.h') */
+pub unsafe fn isintwups(l: *mut lua_State) -> bool { (*l).twups != l }
+
+
+/*
+** thread state + extra space
+*/
+#[repr(C)]
+struct LX {
+    extra_: [u8; LUA_EXTRASPACE as usize],
+    l: lua_State,
+}
+
+
+/*
+** Main thread combines a thread state and the global state, followed by
+** the Rust values owned by the state (unknown to C, which only sees 'l'
+** and 'g' through pointers)
+*/
+#[repr(C)]
+struct LG {
+    l: LX,
+    g: global_State,
+    userdata: HashMap<usize, HashMap<usize, Box<dyn Any>>>,
+}
+
+
+unsafe fn fromstate(l: *mut lua_State) -> *mut LX {
+    (l as *mut u8).sub(mem::offset_of!(LX, l)) as *mut LX
+}
+
+
+/// The Rust values owned by the threads of the state of `l`, keyed by
+/// the address of the thread and then by that of their key (see
+/// `userdata::set`); they are dropped when their thread is freed, the
+/// main thread's when the state is closed.
+pub unsafe fn userdata<'a>(l: *mut lua_State) -> &'a mut HashMap<usize, HashMap<usize, Box<dyn Any>>> {
+    &mut (*(fromstate((*G(l)).mainthread) as *mut LG)).userdata
+}
+
+
+/*
+** set GCdebt to a new value keeping the value (totalbytes + GCdebt)
+** invariant (and avoiding underflows in 'totalbytes')
+*/
+#[cfg(rust_lstate)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaE_setdebt(g: *mut global_State, mut debt: isize) {
+    let tb = gettotalbytes(g) as isize;
+    debug_assert!(tb > 0);
+    if debt < tb - MAX_LMEM {
+        debt = tb - MAX_LMEM;  /* will make 'totalbytes == MAX_LMEM' */
+    }
+    (*g).totalbytes = tb - debt;
+    (*g).GCdebt = debt;
+}
+
+
+#[cfg(rust_lstate)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaE_extendCI(L: *mut lua_State) -> *mut CallInfo {
+    let ci: *mut CallInfo = luaM_new(L);
+    debug_assert!((*(*L).ci).next.is_null());
+    (*(*L).ci).next = ci;
+    (*ci).previous = (*L).ci;
+    (*ci).next = ptr::null_mut();
+    (*L).nci += 1;
+    ci
+}
+
+
+/*
+** free all CallInfo structures not in use by a thread
+*/
+#[cfg(rust_lstate)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaE_freeCI(L: *mut lua_State) {
+    let ci = (*L).ci;
+    let mut next = (*ci).next;
+    (*ci).next = ptr::null_mut();
+    while !next.is_null() {
+        let ci = next;
+        next = (*ci).next;
+        luaM_free(L, ci);
+        (*L).nci -= 1;
+    }
+}
+
+
+/*
+** free half of the CallInfo structures not in use by a thread
+*/
+#[cfg(rust_lstate)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaE_shrinkCI(L: *mut lua_State) {
+    let mut ci = (*L).ci;
+    /* while there are two nexts */
+    while !(*ci).next.is_null() && !(*(*ci).next).next.is_null() {
+        let next2 = (*(*ci).next).next;  /* next's next */
+        luaM_free(L, (*ci).next);  /* free next */
+        (*L).nci -= 1;
+        (*ci).next = next2;  /* remove 'next' from the list */
+        (*next2).previous = ci;
+        ci = next2;  /* keep next's next */
+    }
+}
+
+
+unsafe fn stack_init(L1: *mut lua_State, L: *mut lua_State) {
+    /* initialize stack array */
+    (*L1).stack = luaM_newvector(L, BASIC_STACK_SIZE as size_t);
+    (*L1).stacksize = BASIC_STACK_SIZE;
+    for i in 0..BASIC_STACK_SIZE as usize {
+        setnilvalue((*L1).stack.add(i));  /* erase new stack */
+    }
+    (*L1).top = (*L1).stack;
+    (*L1).stack_last = (*L1).stack.add(((*L1).stacksize - EXTRA_STACK) as usize);
+    /* initialize first ci */
+    let ci: *mut CallInfo = &mut (*L1).base_ci;
+    (*ci).next = ptr::null_mut();
+    (*ci).previous = ptr::null_mut();
+    (*ci).callstatus = 0;
+    (*ci).func = (*L1).top;
+    setnilvalue((*L1).top);  /* 'function' entry for this 'ci' */
+    (*L1).top = (*L1).top.add(1);
+    (*ci).top = (*L1).top.add(LUA_MINSTACK as usize);
+    (*L1).ci = ci;
+}
+
+
+unsafe fn freestack(L: *mut lua_State) {
+    if (*L).stack.is_null() {
+        return;  /* stack not completely built yet */
+    }
+    (*L).ci = &mut (*L).base_ci;  /* free the entire 'ci' list */
+    luaE_freeCI(L);
+    debug_assert!((*L).nci == 0);
+    luaM_freearray(L, (*L).stack, (*L).stacksize as size_t);  /* free stack array */
+}
+
+
+/*
+** Create registry table and its predefined values
+*/
+unsafe fn init_registry(L: *mut lua_State, g: *mut global_State) {
+    let mut temp = TValue::nil();
+    /* create registry */
+    let registry = luaH_new(L);
+    sethvalue(&mut (*g).l_registry, registry);
+    luaH_resize(L, registry, LUA_RIDX_LAST as c_uint, 0);
+    /* registry[LUA_RIDX_MAINTHREAD] = L */
+    setthvalue(&mut temp, L);  /* temp = L */
+    luaH_setint(L, registry, LUA_RIDX_MAINTHREAD, &mut temp);
+    /* registry[LUA_RIDX_GLOBALS] = table of globals */
+    sethvalue(&mut temp, luaH_new(L));  /* temp = new table (global table) */
+    luaH_setint(L, registry, LUA_RIDX_GLOBALS, &mut temp);
+}
+
+
+/*
+** open parts of the state that may cause memory-allocation errors.
+** ('g->version' != NULL flags that the state was completely build)
+*/
+unsafe fn f_luaopen(L: *mut lua_State) {
+    let g = G(L);
+    stack_init(L, L);  /* init stack */
+    init_registry(L, g);
+    luaS_init(L);
+    luaT_init(L);
+    luaX_init(L);
+    (*g).gcrunning = 1;  /* allow gc */
+    (*g).version = lua_version(ptr::null_mut());
+    userstateopen(L);
+}
+
+
+/*
+** preinitialize a thread with consistent values without allocating
+** any memory (to avoid errors)
+*/
+unsafe fn preinit_thread(L: *mut lua_State, g: *mut global_State) {
+    (*L).l_G = g;
+    (*L).stack = ptr::null_mut();
+    (*L).ci = ptr::null_mut();
+    (*L).nci = 0;
+    (*L).stacksize = 0;
+    (*L).twups = L;  /* thread has no upvalues */
+    (*L).errorJmp = ptr::null_mut();
+    (*L).nCcalls = 0;
+    (*L).hook = None;
+    (*L).hookmask = 0;
+    (*L).basehookcount = 0;
+    (*L).allowhook = 1;
+    resethookcount(L);
+    (*L).openupval = ptr::null_mut();
+    (*L).nny = 1;
+    (*L).status = LUA_OK as u8;
+    (*L).errfunc = 0;
+}
+
+
+unsafe fn close_state(L: *mut lua_State) {
+    let g = G(L);
+    luaF_close(L, (*L).stack);  /* close all upvalues for this thread */
+    luaC_freeallobjects(L);  /* collect all objects */
+    if !(*g).version.is_null() {  /* closing a fully built state? */
+        userstateclose(L);
+    }
+    luaM_freearray(L, (*g).strt.hash, (*g).strt.size as size_t);
+    freestack(L);
+    debug_assert!(gettotalbytes(g) == mem::size_of::<LG>());
+    let l = fromstate(L) as *mut LG;
+    /* after the finalizers, which may still use them */
+    ptr::drop_in_place(&mut (*l).userdata);
+    ((*g).frealloc.unwrap())((*g).ud, l as *mut c_void, mem::size_of::<LG>(), 0);  /* free main block */
+}
+
+
+#[cfg(rust_lstate)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_newthread(L: *mut lua_State) -> *mut lua_State {
+    let g = G(L);
+    lua_lock(L);
+    luaC_checkGC(L);
+    /* create new thread */
+    let l = luaM_realloc_(L, ptr::null_mut(), lua::LUA_TTHREAD as size_t, mem::size_of::<LX>()) as *mut LX;
+    let L1: *mut lua_State = &mut (*l).l;
+    (*L1).marked = luaC_white(g);
+    (*L1).tt = lua::LUA_TTHREAD as u8;
+    /* link it on list 'allgc' */
+    (*L1).next = (*g).allgc;
+    (*g).allgc = L1 as *mut GCObject;
+    /* anchor it on L stack */
+    setthvalue((*L).top, L1);
+    api_incr_top(L);
+    preinit_thread(L1, g);
+    (*L1).hookmask = (*L).hookmask;
+    (*L1).basehookcount = (*L).basehookcount;
+    (*L1).hook = (*L).hook;
+    resethookcount(L1);
+    /* initialize L1 extra space */
+    (*l).extra_ = (*fromstate((*g).mainthread)).extra_;
+    userstatethread(L, L1);
+    stack_init(L1, L);  /* init stack */
+    lua_unlock(L);
+    L1
+}
+
+
+#[cfg(rust_lstate)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaE_freethread(L: *mut lua_State, L1: *mut lua_State) {
+    let l = fromstate(L1);
+    luaF_close(L1, (*L1).stack);  /* close all upvalues for this thread */
+    debug_assert!((*L1).openupval.is_null());
+    userstatefree(L, L1);
+    userdata(L).remove(&(L1 as usize));  /* drop its Rust values */
+    freestack(L1);
+    luaM_free(L, l);
+}
+
+
+#[cfg(rust_lstate)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_newstate(f: lua_Alloc, ud: *mut c_void) -> *mut lua_State {
+    let l = (f.unwrap())(ud, ptr::null_mut(), lua::LUA_TTHREAD as size_t, mem::size_of::<LG>()) as *mut LG;
+    if l.is_null() { return ptr::null_mut(); }
+    let mut L: *mut lua_State = &mut (*l).l.l;
+    let g: *mut global_State = &mut (*l).g;
+    ptr::write(&mut (*l).userdata, HashMap::new());
+    (*L).next = ptr::null_mut();
+    (*L).tt = lua::LUA_TTHREAD as u8;
+    (*g).currentwhite = 1 << WHITE0BIT;
+    (*L).marked = luaC_white(g);
+    preinit_thread(L, g);
+    (*g).frealloc = f;
+    (*g).ud = ud;
+    (*g).mainthread = L;
+    (*g).seed = 0;  /* drawn by 'luaS_init' */
+    (*g).gcrunning = 0;  /* no GC while building state */
+    (*g).GCestimate = 0;
+    (*g).strt.size = 0;
+    (*g).strt.nuse = 0;
+    (*g).strt.hash = ptr::null_mut();
+    setnilvalue(&mut (*g).l_registry);
+    (*g).panic = None;
+    (*g).version = ptr::null();
+    (*g).gcstate = GCSpause;
+    (*g).gckind = KGC_NORMAL;
+    (*g).allgc = ptr::null_mut();
+    (*g).finobj = ptr::null_mut();
+    (*g).tobefnz = ptr::null_mut();
+    (*g).fixedgc = ptr::null_mut();
+    (*g).sweepgc = ptr::null_mut();
+    (*g).gray = ptr::null_mut();
+    (*g).grayagain = ptr::null_mut();
+    (*g).weak = ptr::null_mut();
+    (*g).ephemeron = ptr::null_mut();
+    (*g).allweak = ptr::null_mut();
+    (*g).twups = ptr::null_mut();
+    (*g).totalbytes = mem::size_of::<LG>() as isize;
+    (*g).GCdebt = 0;
+    (*g).gcfinnum = 0;
+    (*g).gcpause = LUAI_GCPAUSE;
+    (*g).gcstepmul = LUAI_GCMUL;
+    (*g).mt = [ptr::null_mut(); lua::LUA_NUMTAGS as usize];
+    if rawrunprotected(L, || f_luaopen(L)).is_err() {
+        /* memory allocation error: free partial state */
+        close_state(L);
+        L = ptr::null_mut();
+    }
+    L
+}
+
+
+#[cfg(rust_lstate)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn lua_close(L: *mut lua_State) {
+    let L = (*G(L)).mainthread;  /* only the main thread can be closed */
+    lua_lock(L);
+    close_state(L);
+}
+
+
+/*
+** 'lua_lock'/'lua_unlock' are no-ops, except in the test build, where
+** 'ltests.h' keeps a lock count in the extra space before each thread.
+*/
+#[cfg(ltests)]
+#[repr(C)]
+struct L_EXTRA {
+    lock: c_int,
+    plock: *mut c_int,
+}
+
+#[cfg(ltests)]
+const _: () = assert!(LUA_EXTRASPACE as usize == mem::size_of::<L_EXTRA>());
+
+#[cfg(ltests)]
+unsafe fn getlock(l: *mut lua_State) -> *mut L_EXTRA {
+    (*fromstate(l)).extra_.as_mut_ptr() as *mut L_EXTRA
+}
+
+#[cfg(ltests)]
+unsafe fn userstateopen(l: *mut lua_State) {
+    (*getlock(l)).lock = 0;
+    (*getlock(l)).plock = &mut (*getlock(l)).lock;
+}
+
+#[cfg(ltests)]
+unsafe fn userstateclose(l: *mut lua_State) {
+    assert!((*getlock(l)).lock == 1 && ptr::eq((*getlock(l)).plock, &(*getlock(l)).lock));
+}
+
+#[cfg(ltests)]
+unsafe fn userstatethread(l: *mut lua_State, l1: *mut lua_State) {
+    assert!((*getlock(l1)).plock == (*getlock(l)).plock);
+}
+
+#[cfg(ltests)]
+unsafe fn userstatefree(l: *mut lua_State, l1: *mut lua_State) {
+    assert!((*getlock(l)).plock == (*getlock(l1)).plock);
+}
+
+#[cfg(ltests)]
+pub unsafe fn lua_lock(l: *mut lua_State) {
+    let plock = (*getlock(l)).plock;
+    assert!(*plock == 0);
+    *plock += 1;
+}
+
+#[cfg(ltests)]
+pub unsafe fn lua_unlock(l: *mut lua_State) {
+    let plock = (*getlock(l)).plock;
+    *plock -= 1;
+    assert!(*plock == 0);
+}
+
+/* release the lock of an API call abandoned by a failed check, if it took it */
+#[cfg(ltests)]
+pub unsafe fn api_unlock(l: *mut lua_State) {
+    *(*getlock(l)).plock = 0;
+}
+
+#[cfg(not(ltests))]
+unsafe fn userstateopen(_: *mut lua_State) {}
+
+#[cfg(not(ltests))]
+unsafe fn userstateclose(_: *mut lua_State) {}
+
+#[cfg(not(ltests))]
+unsafe fn userstatethread(_: *mut lua_State, _: *mut lua_State) {}
+
+#[cfg(not(ltests))]
+unsafe fn userstatefree(_: *mut lua_State, _: *mut lua_State) {}
+
+#[cfg(not(ltests))]
+pub unsafe fn lua_lock(_: *mut lua_State) {}
+
+#[cfg(not(ltests))]
+pub unsafe fn lua_unlock(_: *mut lua_State) {}
+
+#[cfg(not(ltests))]
+pub unsafe fn api_unlock(_: *mut lua_State) {}
@@ -0,0 +1,402 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! String table (keeps all strings handled by Lua).
+//!
+//! Each state gets its hash seed from the operating system's randomness
+//! instead of 'luai_makeseed'. With the 'siphash' feature, long strings
+//! are hashed with SipHash-2-4 under a secret key: the plain Lua hash
+//! samples at most 32 of their bytes, so scripts can make them collide
+//! at will.
+
+#![allow(non_snake_case)]
+
+use lgc::{iswhite, isdead, luaC_fix, luaC_newobj, WHITEBITS};
+use lmem::{luaM_reallocvector, luaM_toobig};
+use lobject::{getstr, sizelstring, sizeludata, GCObject, TString, Udata, LUA_TLNGSTR,
+              LUA_TSHRSTR, LUAI_MAXSHORTLEN};
+use lstate::{global_State, lua_State, G, STRCACHE_M, STRCACHE_N};
+use libc::{c_char, c_int, c_uint, memcmp, size_t, strcmp, strlen};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::ptr;
+
+
+/* the exported functions below, from 'lstring.c' when it is linked instead (see 'build.rs') */
+#[cfg(not(rust_lstring))]
+extern "C-unwind" {
+    pub fn luaS_eqlngstr(a: *mut TString, b: *mut TString) -> c_int;
+    pub fn luaS_hash(str: *const c_char, l: size_t, seed: c_uint) -> c_uint;
+    pub fn luaS_hashlongstr(ts: *mut TString) -> c_uint;
+    pub fn luaS_resize(L: *mut lua_State, newsize: c_int);
+    pub fn luaS_clearcache(g: *mut global_State);
+    pub fn luaS_init(L: *mut lua_State);
+    pub fn luaS_createlngstrobj(L: *mut lua_State, l: size_t) -> *mut TString;
+    pub fn luaS_remove(L: *mut lua_State, ts: *mut TString);
+    pub fn luaS_newlstr(L: *mut lua_State, str: *const c_char, l: size_t) -> *mut TString;
+    pub fn luaS_new(L: *mut lua_State, str: *const c_char) -> *mut TString;
+    pub fn luaS_newudata(L: *mut lua_State, s: size_t) -> *mut Udata;
+}
+
+
+const MEMERRMSG: &str = "not enough memory";
+
+/* minimum size for the string table; must agree with the C core */
+#[cfg(ltests)]
+const MINSTRTABSIZE: c_int = 2;  /* as set by 'ltests.h' */
+#[cfg(not(ltests))]
+const MINSTRTABSIZE: c_int = 128;
+
+/*
+** Lua will use at most ~(2^LUAI_HASHLIMIT) bytes from a string to
+** compute its hash
+*/
+const LUAI_HASHLIMIT: usize = 5;
+
+const MAX_SIZE: size_t = ::ffi::lua::lua_Integer::MAX as size_t;
+
+
+/* 'luaS_newliteral' */
+pub unsafe fn luaS_newliteral(L: *mut lua_State, s: &str) -> *mut TString {
+    luaS_newlstr(L, s.as_ptr() as *const c_char, s.len())
+}
+
+
+/*
+** equality for long strings
+*/
+#[cfg(rust_lstring)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaS_eqlngstr(a: *mut TString, b: *mut TString) -> c_int {
+    let len = (*a).u.lnglen;
+    debug_assert!((*a).tt as c_int == LUA_TLNGSTR && (*b).tt as c_int == LUA_TLNGSTR);
+    (a == b ||  /* same instance or... */
+     (len == (*b).u.lnglen &&  /* equal length and ... */
+      memcmp(getstr(a) as *const _, getstr(b) as *const _, len) == 0)) as c_int  /* equal contents */
+}
+
+
+#[cfg(rust_lstring)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaS_hash(str: *const c_char, mut l: size_t, seed: c_uint) -> c_uint {
+    let mut h = seed ^ l as c_uint;
+    let step = (l >> LUAI_HASHLIMIT) + 1;
+    while l >= step {
+        h ^= (h << 5).wrapping_add(h >> 2).wrapping_add(*str.add(l - 1) as u8 as c_uint);
+        l -= step;
+    }
+    h
+}
+
+
+/*
+** SipHash-2-4 of 'data' under the key ('k0', 'k1').
+*/
+#[cfg(any(test, feature = "siphash"))]
+fn siphash24(k0: u64, k1: u64, data: &[u8]) -> u64 {
+    let mut v = [k0 ^ 0x736f6d6570736575, k1 ^ 0x646f72616e646f6d,
+                 k0 ^ 0x6c7967656e657261, k1 ^ 0x7465646279746573];
+    fn round(v: &mut [u64; 4]) {
+        v[0] = v[0].wrapping_add(v[1]); v[1] = v[1].rotate_left(13); v[1] ^= v[0];
+        v[0] = v[0].rotate_left(32);
+        v[2] = v[2].wrapping_add(v[3]); v[3] = v[3].rotate_left(16); v[3] ^= v[2];
+        v[0] = v[0].wrapping_add(v[3]); v[3] = v[3].rotate_left(21); v[3] ^= v[0];
+        v[2] = v[2].wrapping_add(v[1]); v[1] = v[1].rotate_left(17); v[1] ^= v[2];
+        v[2] = v[2].rotate_left(32);
+    }
+    let mut compress = |m: u64| {
+        v[3] ^= m;
+        round(&mut v);
+        round(&mut v);
+        v[0] ^= m;
+    };
+    let mut chunks = data.chunks_exact(8);
+    for c in &mut chunks {
+        let mut b = [0; 8];
+        b.copy_from_slice(c);
+        compress(u64::from_le_bytes(b));
+    }
+    let mut last = (data.len() as u64) << 56;  /* length and the remaining bytes */
+    for (i, &b) in chunks.remainder().iter().enumerate() {
+        last |= (b as u64) << (8 * i);
+    }
+    compress(last);
+    v[2] ^= 0xff;
+    for _ in 0..4 {
+        round(&mut v);
+    }
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
+
+/* secret key for the long-string hashes, drawn once per process */
+#[cfg(feature = "siphash")]
+fn sipkey() -> (u64, u64) {
+    static KEY: ::std::sync::OnceLock<(u64, u64)> = ::std::sync::OnceLock::new();
+    *KEY.get_or_init(|| {
+        let s = RandomState::new();
+        (s.hash_one(0u8), s.hash_one(1u8))
+    })
+}
+
+#[cfg(feature = "siphash")]
+unsafe fn hashlongstr(str: *const c_char, l: size_t, seed: c_uint) -> c_uint {
+    let (k0, k1) = sipkey();
+    let h = siphash24(k0 ^ seed as u64, k1, ::std::slice::from_raw_parts(str as *const u8, l));
+    (h ^ (h >> 32)) as c_uint
+}
+
+#[cfg(not(feature = "siphash"))]
+unsafe fn hashlongstr(str: *const c_char, l: size_t, seed: c_uint) -> c_uint {
+    luaS_hash(str, l, seed)
+}
+
+
+#[cfg(rust_lstring)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaS_hashlongstr(ts: *mut TString) -> c_uint {
+    debug_assert!((*ts).tt as c_int == LUA_TLNGSTR);
+    if (*ts).extra == 0 {  /* no hash? */
+        (*ts).hash = hashlongstr(getstr(ts), (*ts).u.lnglen, (*ts).hash);
+        (*ts).extra = 1;  /* now it has its hash */
+    }
+    (*ts).hash
+}
+
+
+fn lmod(s: c_uint, size: c_int) -> usize {
+    debug_assert!(size & (size - 1) == 0);  /* 'size' must be power of 2 */
+    (s & (size as c_uint - 1)) as usize
+}
+
+
+/*
+** resizes the string table
+*/
+#[cfg(rust_lstring)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaS_resize(L: *mut lua_State, newsize: c_int) {
+    let tb = &mut (*G(L)).strt;
+    if newsize > tb.size {  /* grow table if needed */
+        luaM_reallocvector(L, &mut tb.hash, tb.size, newsize);
+        for i in tb.size..newsize {
+            *tb.hash.offset(i as isize) = ptr::null_mut();
+        }
+    }
+    for i in 0..tb.size as usize {  /* rehash */
+        let mut p = *tb.hash.add(i);
+        *tb.hash.add(i) = ptr::null_mut();
+        while !p.is_null() {  /* for each node in the list */
+            let hnext = (*p).u.hnext;  /* save next */
+            let h = lmod((*p).hash, newsize);  /* new position */
+            (*p).u.hnext = *tb.hash.add(h);  /* chain it */
+            *tb.hash.add(h) = p;
+            p = hnext;
+        }
+    }
+    if newsize < tb.size {  /* shrink table if needed */
+        /* vanishing slice should be empty */
+        debug_assert!((*tb.hash.offset(newsize as isize)).is_null() &&
+                      (*tb.hash.offset(tb.size as isize - 1)).is_null());
+        luaM_reallocvector(L, &mut tb.hash, tb.size, newsize);
+    }
+    tb.size = newsize;
+}
+
+
+/*
+** Clear API string cache. (Entries cannot be empty, so fill them with
+** a non-collectable string.)
+*/
+#[cfg(rust_lstring)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaS_clearcache(g: *mut global_State) {
+    for i in 0..STRCACHE_N {
+        for j in 0..STRCACHE_M {
+            if iswhite((*g).strcache[i][j] as *mut GCObject) {  /* will entry be collected? */
+                (*g).strcache[i][j] = (*g).memerrmsg;  /* replace it with something fixed */
+            }
+        }
+    }
+}
+
+
+/*
+** A seed for the hashes of a new state, drawn from the randomness the
+** standard library seeds its own hash maps with.
+*/
+fn makeseed(L: *mut lua_State) -> c_uint {
+    let h = RandomState::new().hash_one(L as usize);
+    (h ^ (h >> 32)) as c_uint
+}
+
+
+/*
+** Initialize the string table and the string cache
+*/
+#[cfg(rust_lstring)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaS_init(L: *mut lua_State) {
+    let g = G(L);
+    (*g).seed = makeseed(L);  /* no string was hashed yet */
+    luaS_resize(L, MINSTRTABSIZE);  /* initial size of string table */
+    /* pre-create memory-error message */
+    (*g).memerrmsg = luaS_newliteral(L, MEMERRMSG);
+    luaC_fix(L, (*g).memerrmsg as *mut GCObject);  /* it should never be collected */
+    for i in 0..STRCACHE_N {  /* fill cache with valid strings */
+        for j in 0..STRCACHE_M {
+            (*g).strcache[i][j] = (*g).memerrmsg;
+        }
+    }
+}
+
+
+
+/*
+** creates a new string object
+*/
+unsafe fn createstrobj(L: *mut lua_State, l: size_t, tag: c_int, h: c_uint) -> *mut TString {
+    let totalsize = sizelstring(l);  /* total size of TString object */
+    let o = luaC_newobj(L, tag, totalsize);
+    let ts = o as *mut TString;
+    (*ts).hash = h;
+    (*ts).extra = 0;
+    *(getstr(ts) as *mut c_char).add(l) = 0;  /* ending 0 */
+    ts
+}
+
+
+#[cfg(rust_lstring)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaS_createlngstrobj(L: *mut lua_State, l: size_t) -> *mut TString {
+    let ts = createstrobj(L, l, LUA_TLNGSTR, (*G(L)).seed);
+    (*ts).u.lnglen = l;
+    ts
+}
+
+
+#[cfg(rust_lstring)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaS_remove(L: *mut lua_State, ts: *mut TString) {
+    let tb = &mut (*G(L)).strt;
+    let mut p = tb.hash.add(lmod((*ts).hash, tb.size));
+    while *p != ts {  /* find previous element */
+        p = &mut (**p).u.hnext;
+    }
+    *p = (**p).u.hnext;  /* remove element from its list */
+    tb.nuse -= 1;
+}
+
+
+/*
+** checks whether short string exists and reuses it or creates a new one
+*/
+unsafe fn internshrstr(L: *mut lua_State, str: *const c_char, l: size_t) -> *mut TString {
+    let g = G(L);
+    let h = luaS_hash(str, l, (*g).seed);
+    let mut list = (*g).strt.hash.add(lmod(h, (*g).strt.size));
+    debug_assert!(!str.is_null());  /* otherwise 'memcmp'/'memcpy' are undefined */
+    let mut ts = *list;
+    while !ts.is_null() {
+        if l == (*ts).shrlen as size_t && memcmp(str as *const _, getstr(ts) as *const _, l) == 0 {
+            /* found! */
+            if isdead(g, ts as *mut GCObject) {  /* dead (but not collected yet)? */
+                (*ts).marked ^= WHITEBITS;  /* resurrect it */
+            }
+            return ts;
+        }
+        ts = (*ts).u.hnext;
+    }
+    if (*g).strt.nuse >= (*g).strt.size && (*g).strt.size <= c_int::MAX / 2 {
+        luaS_resize(L, (*g).strt.size * 2);
+        list = (*g).strt.hash.add(lmod(h, (*g).strt.size));  /* recompute with new size */
+    }
+    let ts = createstrobj(L, l, LUA_TSHRSTR, h);
+    ptr::copy_nonoverlapping(str, getstr(ts) as *mut c_char, l);
+    (*ts).shrlen = l as u8;
+    (*ts).u.hnext = *list;
+    *list = ts;
+    (*g).strt.nuse += 1;
+    ts
+}
+
+
+/*
+** new string (with explicit length)
+*/
+#[cfg(rust_lstring)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaS_newlstr(L: *mut lua_State, str: *const c_char, l: size_t) -> *mut TString {
+    if l <= LUAI_MAXSHORTLEN {  /* short string? */
+        internshrstr(L, str, l)
+    } else {
+        if l >= MAX_SIZE - ::std::mem::size_of::<TString>() {
+            luaM_toobig(L);
+        }
+        let ts = luaS_createlngstrobj(L, l);
+        ptr::copy_nonoverlapping(str, getstr(ts) as *mut c_char, l);
+        ts
+    }
+}
+
+
+/*
+** Create or reuse a zero-terminated string, first checking in the
+** cache (using the string address as a key). The cache can contain
+** only zero-terminated strings, so it is safe to use 'strcmp' to
+** check hits.
+*/
+#[cfg(rust_lstring)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaS_new(L: *mut lua_State, str: *const c_char) -> *mut TString {
+    let i = (str as usize as c_uint) as usize % STRCACHE_N;  /* hash */
+    let p = &mut (*G(L)).strcache[i];
+    for &s in p.iter() {
+        if strcmp(str, getstr(s)) == 0 {  /* hit? */
+            return s;  /* that is it */
+        }
+    }
+    /* normal route */
+    for j in (1..STRCACHE_M).rev() {
+        p[j] = p[j - 1];  /* move out last element */
+    }
+    /* new element is first in the list */
+    p[0] = luaS_newlstr(L, str, strlen(str));
+    p[0]
+}
+
+
+#[cfg(rust_lstring)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaS_newudata(L: *mut lua_State, s: size_t) -> *mut Udata {
+    if s > MAX_SIZE - ::std::mem::size_of::<Udata>() {
+        luaM_toobig(L);
+    }
+    let o = luaC_newobj(L, ::ffi::lua::LUA_TUSERDATA, sizeludata(s));
+    let u = o as *mut Udata;
+    (*u).len = s;
+    (*u).metatable = ptr::null_mut();
+    (*u).user_.gc = ptr::null_mut();  /* 'setuservalue' with nil */
+    (*u).ttuv_ = ::ffi::lua::LUA_TNIL as u8;
+    u
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::siphash24;
+
+    #[test]
+    fn test_siphash24() {
+        /* vectors of the reference implementation: key 00..0f, input 00..(n-1) */
+        let (k0, k1) = (0x0706050403020100, 0x0f0e0d0c0b0a0908);
+        let input: Vec<u8> = (0..64).collect();
+        assert_eq!(siphash24(k0, k1, &input[..0]), 0x726fdb47dd0e0e31);
+        assert_eq!(siphash24(k0, k1, &input[..8]), 0x93f5f5799a932462);
+        assert_eq!(siphash24(k0, k1, &input[..15]), 0xa129ca6149be45e5);
+        assert_eq!(siphash24(k0, k1, &input[..63]), 0x958a324ceb064572);
+    }
+}
@@ -0,0 +1,765 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Lua tables (hash).
+//!
+//! Implementation of tables (aka arrays, objects, or hash tables).
+//! Tables keep its elements in two parts: an array part and a hash part.
+//! Non-negative integer keys are all candidates to be kept in the array
+//! part. The actual size of the array is the largest 'n' such that
+//! more than half the slots between 1 and n are in use.
+//! Hash uses a mix of chained scatter table with Brent's variation.
+//! A main invariant of these tables is that, if an element is not
+//! in its main position (i.e. the 'original' position that its hash gives
+//! to it), then the colliding element is in its own main position.
+//! Hence even when the load factor reaches 100%, performance remains good.
+
+#![allow(non_snake_case, non_upper_case_globals)]
+
+use ffi::lua::{self, lua_Integer, lua_Number};
+use ffi::luaconf::lua_numtointeger;
+use lgc::{luaC_barrierback, luaC_newobj};
+use lmem::{luaM_free, luaM_freearray, luaM_newvector, luaM_realloc_};
+use lobject::{luaO_ceillog2, luaO_nilobject, setivalue, setnilvalue, setsvalue, Node, TKey,
+              TString, TValue, Table, Value, LUA_TDEADKEY, LUA_TLCF, LUA_TLNGSTR, LUA_TNUMFLT, LUA_TNUMINT,
+              LUA_TSHRSTR};
+use lstate::{lua_State, StkId};
+use lstring::luaS_hashlongstr;
+use libc::{c_char, c_int, c_uint, c_void, size_t};
+use std::mem;
+use std::ptr;
+
+
+/* the exported functions below, from 'ltable.c' when it is linked instead (see 'build.rs') */
+#[cfg(not(rust_ltable))]
+extern "C-unwind" {
+    pub fn luaH_next(L: *mut lua_State, t: *mut Table, key: StkId) -> c_int;
+    pub fn luaH_resize(L: *mut lua_State, t: *mut Table, nasize: c_uint, nhsize: c_uint);
+    pub fn luaH_resizearray(L: *mut lua_State, t: *mut Table, nasize: c_uint);
+    pub fn luaH_new(L: *mut lua_State) -> *mut Table;
+    pub fn luaH_free(L: *mut lua_State, t: *mut Table);
+    pub fn luaH_newkey(L: *mut lua_State, t: *mut Table, key: *const TValue) -> *mut TValue;
+    pub fn luaH_getint(t: *mut Table, key: lua_Integer) -> *const TValue;
+    pub fn luaH_getshortstr(t: *mut Table, key: *mut TString) -> *const TValue;
+    pub fn luaH_getstr(t: *mut Table, key: *mut TString) -> *const TValue;
+    pub fn luaH_get(t: *mut Table, key: *const TValue) -> *const TValue;
+    pub fn luaH_set(L: *mut lua_State, t: *mut Table, key: *const TValue) -> *mut TValue;
+    pub fn luaH_setint(L: *mut lua_State, t: *mut Table, key: lua_Integer, value: *mut TValue);
+    pub fn luaH_getn(t: *mut Table) -> c_int;
+    pub fn luaH_mainposition(t: *const Table, key: *const TValue) -> *mut Node;
+    pub fn luaH_isdummy(n: *mut Node) -> c_int;
+}
+
+
+extern "C-unwind" {
+    fn luaG_runerror(L: *mut lua_State, fmt: *const c_char, ...) -> !;
+    fn luaV_equalobj(L: *mut lua_State, t1: *const TValue, t2: *const TValue) -> c_int;
+    fn luaV_tointeger(obj: *const TValue, p: *mut lua_Integer, mode: c_int) -> c_int;
+    fn frexp(x: lua_Number, exp: *mut c_int) -> lua_Number;
+}
+
+
+/*
+** Maximum size of array part (MAXASIZE) is 2^MAXABITS. MAXABITS is
+** the largest integer such that MAXASIZE fits in an unsigned int.
+*/
+const MAXABITS: c_int = (mem::size_of::<c_int>() * 8 - 1) as c_int;
+const MAXASIZE: c_uint = 1 << MAXABITS;
+
+/*
+** Maximum size of hash part is 2^MAXHBITS. MAXHBITS is the largest
+** integer such that 2^MAXHBITS fits in a signed int. (Note that the
+** maximum number of elements in a table, 2^MAXABITS + 2^MAXHBITS, still
+** fits comfortably in an unsigned int.)
+*/
+const MAXHBITS: c_int = MAXABITS - 1;
+
+
+pub unsafe fn gnode(t: *const Table, i: usize) -> *mut Node { (*t).node.add(i) }
+pub unsafe fn gval(n: *mut Node) -> *mut TValue { &mut (*n).i_val }
+unsafe fn gnext(n: *mut Node) -> *mut c_int { &mut (*n).i_key.next }
+
+/* 'const' to avoid wrong writings that can mess up field 'next' */
+pub unsafe fn gkey(n: *const Node) -> *const TValue { &(*n).i_key as *const TKey as *const TValue }
+
+/* writable version of 'gkey'; allows updates to individual fields */
+pub unsafe fn wgkey(n: *mut Node) -> *mut TValue { &mut (*n).i_key as *mut TKey as *mut TValue }
+
+pub unsafe fn sizenode(t: *const Table) -> c_int { 1 << (*t).lsizenode }
+
+pub unsafe fn ttisdeadkey(o: *const TValue) -> bool { (*o).tt_ == LUA_TDEADKEY }
+
+unsafe fn setobj(obj1: *mut TValue, obj2: *const TValue) { *obj1 = *obj2; }
+
+unsafe fn setnodekey(key: *mut TKey, obj: *const TValue) {
+    (*key).value_ = (*obj).value_;
+    (*key).tt_ = (*obj).tt_;
+}
+
+
+unsafe fn hashpow2(t: *const Table, n: lua_Integer) -> *mut Node {
+    gnode(t, (n & (sizenode(t) - 1) as lua_Integer) as usize)
+}
+
+unsafe fn hashstr(t: *const Table, str: *const TString) -> *mut Node {
+    hashpow2(t, (*str).hash as lua_Integer)
+}
+
+/*
+** for some types, it is better to avoid modulus by power of 2, as
+** they tend to have many 2 factors.
+*/
+unsafe fn hashmod(t: *const Table, n: c_uint) -> *mut Node {
+    gnode(t, (n % ((sizenode(t) - 1) | 1) as c_uint) as usize)
+}
+
+unsafe fn hashpointer(t: *const Table, p: *const c_void) -> *mut Node {
+    hashmod(t, (p as size_t & c_uint::MAX as size_t) as c_uint)
+}
+
+
+/* all tables with an empty hash part share this node */
+struct DummyNode(Node);
+
+unsafe impl Sync for DummyNode {}
+
+static dummynode_: DummyNode = DummyNode(Node {
+    i_val: TValue { value_: Value { gc: ptr::null_mut() }, tt_: lua::LUA_TNIL },  /* value */
+    i_key: TKey { value_: Value { gc: ptr::null_mut() }, tt_: lua::LUA_TNIL, next: 0 },  /* key */
+});
+
+fn dummynode() -> *mut Node { &dummynode_.0 as *const Node as *mut Node }
+
+fn isdummy(n: *const Node) -> bool { ptr::eq(n, dummynode()) }
+
+
+/*
+** Hash for floating-point numbers.
+** The main computation should be just
+**     n = frexp(n, &i); return (n * INT_MAX) + i
+** but there are some numerical subtleties.
+** In a two-complement representation, INT_MAX does not has an exact
+** representation as a float, but INT_MIN does; because the absolute
+** value of 'frexp' is smaller than 1 (unless 'n' is inf/NaN), the
+** absolute value of the product 'frexp * -INT_MIN' is smaller or equal
+** to INT_MAX. Next, the use of 'unsigned int' avoids overflows when
+** adding 'i'; the use of '~u' (instead of '-u') avoids problems with
+** INT_MIN.
+*/
+unsafe fn l_hashfloat(mut n: lua_Number) -> c_int {
+    let mut i: c_int = 0;
+    let mut ni: lua_Integer = 0;
+    n = frexp(n, &mut i) * -(c_int::MIN as lua_Number);
+    if lua_numtointeger(n, &mut ni) == 0 {  /* is 'n' inf/-inf/NaN? */
+        debug_assert!(n.is_nan() || n.abs() == lua_Number::INFINITY);
+        0
+    } else {  /* normal case */
+        let u = (i as c_uint).wrapping_add(ni as c_uint);
+        (if u <= c_int::MAX as c_uint { u } else { !u }) as c_int
+    }
+}
+
+
+/*
+** returns the 'main' position of an element in a table (that is, the index
+** of its hash value)
+*/
+unsafe fn mainposition(t: *const Table, key: *const TValue) -> *mut Node {
+    match (*key).ttype() {
+        LUA_TNUMINT => hashpow2(t, (*key).ivalue()),
+        LUA_TNUMFLT => hashmod(t, l_hashfloat((*key).fltvalue()) as c_uint),
+        LUA_TSHRSTR => hashstr(t, (*key).tsvalue()),
+        LUA_TLNGSTR => hashpow2(t, luaS_hashlongstr((*key).tsvalue()) as lua_Integer),
+        lua::LUA_TBOOLEAN => hashpow2(t, (*key).bvalue() as lua_Integer),
+        lua::LUA_TLIGHTUSERDATA => hashpointer(t, (*key).pvalue()),
+        LUA_TLCF => hashpointer(t, (*key).fvalue().map_or(ptr::null(), |f| f as *const c_void)),
+        _ => {
+            debug_assert!(!ttisdeadkey(key));
+            hashpointer(t, (*key).gcvalue() as *const c_void)
+        }
+    }
+}
+
+
+/*
+** returns the index for 'key' if 'key' is an appropriate key to live in
+** the array part of the table, 0 otherwise.
+*/
+unsafe fn arrayindex(key: *const TValue) -> c_uint {
+    if (*key).ttisinteger() {
+        let k = (*key).ivalue();
+        if 0 < k && k as u64 <= MAXASIZE as u64 {
+            return k as c_uint;  /* 'key' is an appropriate array index */
+        }
+    }
+    0  /* 'key' did not match some condition */
+}
+
+
+/*
+** returns the index of a 'key' for table traversals. First goes all
+** elements in the array part, then elements in the hash part. The
+** beginning of a traversal is signaled by 0.
+*/
+unsafe fn findindex(L: *mut lua_State, t: *mut Table, key: StkId) -> c_uint {
+    if (*key).ttisnil() { return 0; }  /* first iteration */
+    let i = arrayindex(key);
+    if i != 0 && i <= (*t).sizearray {  /* is 'key' inside array part? */
+        return i;  /* yes; that's the index */
+    }
+    let mut n = mainposition(t, key);
+    loop {  /* check whether 'key' is somewhere in the chain */
+        /* key may be dead already, but it is ok to use it in 'next' */
+        if luaV_equalobj(ptr::null_mut(), gkey(n), key) != 0 ||
+           (ttisdeadkey(gkey(n)) && (*key).iscollectable() &&
+            (*gkey(n)).gcvalue() == (*key).gcvalue()) {
+            let i = n.offset_from(gnode(t, 0)) as c_uint;  /* key index in hash table */
+            /* hash elements are numbered after array ones */
+            return (i + 1) + (*t).sizearray;
+        }
+        let nx = *gnext(n);
+        if nx == 0 {
+            luaG_runerror(L, c_str!("invalid key to 'next'"));  /* key not found */
+        }
+        n = n.offset(nx as isize);
+    }
+}
+
+
+#[cfg(rust_ltable)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaH_next(L: *mut lua_State, t: *mut Table, key: StkId) -> c_int {
+    let mut i = findindex(L, t, key);  /* find original element */
+    while i < (*t).sizearray {  /* try first array part */
+        let v = (*t).array.add(i as usize);
+        if !(*v).ttisnil() {  /* a non-nil value? */
+            setivalue(key, i as lua_Integer + 1);
+            setobj(key.offset(1), v);
+            return 1;
+        }
+        i += 1;
+    }
+    i -= (*t).sizearray;
+    while (i as c_int) < sizenode(t) {  /* hash part */
+        let n = gnode(t, i as usize);
+        if !(*gval(n)).ttisnil() {  /* a non-nil value? */
+            setobj(key, gkey(n));
+            setobj(key.offset(1), gval(n));
+            return 1;
+        }
+        i += 1;
+    }
+    0  /* no more elements */
+}
+
+
+/*
+** {=============================================================
+** Rehash
+** ==============================================================
+*/
+
+/*
+** Compute the optimal size for the array part of table 't'. 'nums' is a
+** "count array" where 'nums[i]' is the number of integers in the table
+** between 2^(i - 1) + 1 and 2^i. 'pna' enters with the total number of
+** integer keys in the table and leaves with the number of keys that
+** will go to the array part; return the optimal size.
+*/
+fn computesizes(nums: &[c_uint], pna: &mut c_uint) -> c_uint {
+    let mut twotoi: c_uint = 1;  /* 2^i (candidate for optimal size) */
+    let mut a = 0;  /* number of elements smaller than 2^i */
+    let mut na = 0;  /* number of elements to go to array part */
+    let mut optimal = 0;  /* optimal size for array part */
+    /* loop while keys can fill more than half of total size */
+    let mut i = 0;
+    while *pna > twotoi / 2 {
+        if nums[i] > 0 {
+            a += nums[i];
+            if a > twotoi / 2 {  /* more than half elements present? */
+                optimal = twotoi;  /* optimal size (till now) */
+                na = a;  /* all elements up to 'optimal' will go to array part */
+            }
+        }
+        i += 1;
+        twotoi = twotoi.wrapping_mul(2);
+    }
+    debug_assert!((optimal == 0 || optimal / 2 < na) && na <= optimal);
+    *pna = na;
+    optimal
+}
+
+
+unsafe fn countint(key: *const TValue, nums: &mut [c_uint]) -> c_uint {
+    let k = arrayindex(key);
+    if k != 0 {  /* is 'key' an appropriate array index? */
+        nums[luaO_ceillog2(k) as usize] += 1;  /* count as such */
+        1
+    } else {
+        0
+    }
+}
+
+
+/*
+** Count keys in array part of table 't': Fill 'nums[i]' with
+** number of keys that will go into corresponding slice and return
+** total number of non-nil keys.
+*/
+unsafe fn numusearray(t: *const Table, nums: &mut [c_uint]) -> c_uint {
+    let mut ttlg: c_uint = 1;  /* 2^lg */
+    let mut ause = 0;  /* summation of 'nums' */
+    let mut i = 1;  /* count to traverse all array keys */
+    /* traverse each slice */
+    for num in nums.iter_mut().take(MAXABITS as usize + 1) {
+        let mut lc = 0;  /* counter */
+        let mut lim = ttlg;
+        if lim > (*t).sizearray {
+            lim = (*t).sizearray;  /* adjust upper limit */
+            if i > lim {
+                break;  /* no more elements to count */
+            }
+        }
+        /* count elements in range (2^(lg - 1), 2^lg] */
+        while i <= lim {
+            if !(*(*t).array.add(i as usize - 1)).ttisnil() {
+                lc += 1;
+            }
+            i += 1;
+        }
+        *num += lc;
+        ause += lc;
+        ttlg = ttlg.wrapping_mul(2);
+    }
+    ause
+}
+
+
+unsafe fn numusehash(t: *const Table, nums: &mut [c_uint], pna: &mut c_uint) -> c_uint {
+    let mut totaluse = 0;  /* total number of elements */
+    let mut ause = 0;  /* elements added to 'nums' (can go to array part) */
+    let mut i = sizenode(t) as usize;
+    while i > 0 {
+        i -= 1;
+        let n = gnode(t, i);
+        if !(*gval(n)).ttisnil() {
+            ause += countint(gkey(n), nums);
+            totaluse += 1;
+        }
+    }
+    *pna += ause;
+    totaluse
+}
+
+
+unsafe fn reallocarray(L: *mut lua_State, t: *mut Table, oldsize: c_uint, size: c_uint) {
+    let e = mem::size_of::<TValue>();
+    (*t).array = luaM_realloc_(L, (*t).array as *mut c_void, oldsize as size_t * e,
+                               size as size_t * e) as *mut TValue;
+}
+
+
+unsafe fn setarrayvector(L: *mut lua_State, t: *mut Table, size: c_uint) {
+    reallocarray(L, t, (*t).sizearray, size);
+    for i in (*t).sizearray..size {
+        setnilvalue((*t).array.add(i as usize));
+    }
+    (*t).sizearray = size;
+}
+
+
+unsafe fn setnodevector(L: *mut lua_State, t: *mut Table, mut size: c_uint) {
+    let lsize;
+    if size == 0 {  /* no elements to hash part? */
+        (*t).node = dummynode();  /* use common 'dummynode' */
+        lsize = 0;
+    } else {
+        lsize = luaO_ceillog2(size);
+        if lsize > MAXHBITS {
+            luaG_runerror(L, c_str!("table overflow"));
+        }
+        size = 1 << lsize;
+        (*t).node = luaM_newvector(L, size as size_t);
+        for i in 0..size as usize {
+            let n = gnode(t, i);
+            *gnext(n) = 0;
+            setnilvalue(wgkey(n));
+            setnilvalue(gval(n));
+        }
+    }
+    (*t).lsizenode = lsize as u8;
+    (*t).lastfree = gnode(t, size as usize);  /* all positions are free */
+}
+
+
+#[cfg(rust_ltable)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaH_resize(L: *mut lua_State, t: *mut Table, nasize: c_uint,
+                                     nhsize: c_uint) {
+    let oldasize = (*t).sizearray;
+    let oldhsize = (*t).lsizenode as c_int;
+    let nold = (*t).node;  /* save old hash ... */
+    if nasize > oldasize {  /* array part must grow? */
+        setarrayvector(L, t, nasize);
+    }
+    /* create new hash part with appropriate size */
+    setnodevector(L, t, nhsize);
+    if nasize < oldasize {  /* array part must shrink? */
+        (*t).sizearray = nasize;
+        /* re-insert elements from vanishing slice */
+        for i in nasize..oldasize {
+            let v = (*t).array.add(i as usize);
+            if !(*v).ttisnil() {
+                luaH_setint(L, t, i as lua_Integer + 1, v);
+            }
+        }
+        /* shrink array */
+        reallocarray(L, t, oldasize, nasize);
+    }
+    /* re-insert elements from hash part */
+    for j in (0..1usize << oldhsize).rev() {
+        let old = nold.add(j);
+        if !(*gval(old)).ttisnil() {
+            /* doesn't need barrier/invalidate cache, as entry was
+               already present in the table */
+            setobj(luaH_set(L, t, gkey(old)), gval(old));
+        }
+    }
+    if !isdummy(nold) {
+        luaM_freearray(L, nold, 1 << oldhsize); /* free old hash */
+    }
+}
+
+
+#[cfg(rust_ltable)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaH_resizearray(L: *mut lua_State, t: *mut Table, nasize: c_uint) {
+    let nsize = if isdummy((*t).node) { 0 } else { sizenode(t) };
+    luaH_resize(L, t, nasize, nsize as c_uint);
+}
+
+/*
+** nums[i] = number of keys 'k' where 2^(i - 1) < k <= 2^i
+*/
+unsafe fn rehash(L: *mut lua_State, t: *mut Table, ek: *const TValue) {
+    let mut nums = [0 as c_uint; MAXABITS as usize + 1];  /* reset counts */
+    let mut na = numusearray(t, &mut nums);  /* count keys in array part */
+    let mut totaluse = na;  /* all those keys are integer keys */
+    totaluse += numusehash(t, &mut nums, &mut na);  /* count keys in hash part */
+    /* count extra key */
+    na += countint(ek, &mut nums);
+    totaluse += 1;
+    /* compute new size for array part */
+    let asize = computesizes(&nums, &mut na);
+    /* resize the table to new computed sizes */
+    luaH_resize(L, t, asize, totaluse - na);
+}
+
+
+
+/*
+** }=============================================================
+*/
+
+
+#[cfg(rust_ltable)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaH_new(L: *mut lua_State) -> *mut Table {
+    let o = luaC_newobj(L, lua::LUA_TTABLE, mem::size_of::<Table>());
+    let t = o as *mut Table;
+    (*t).metatable = ptr::null_mut();
+    (*t).flags = !0;
+    (*t).array = ptr::null_mut();
+    (*t).sizearray = 0;
+    setnodevector(L, t, 0);
+    t
+}
+
+
+#[cfg(rust_ltable)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaH_free(L: *mut lua_State, t: *mut Table) {
+    if !isdummy((*t).node) {
+        luaM_freearray(L, (*t).node, sizenode(t) as size_t);
+    }
+    luaM_freearray(L, (*t).array, (*t).sizearray as size_t);
+    luaM_free(L, t);
+}
+
+
+unsafe fn getfreepos(t: *mut Table) -> *mut Node {
+    while (*t).lastfree > (*t).node {
+        (*t).lastfree = (*t).lastfree.offset(-1);
+        if (*gkey((*t).lastfree)).ttisnil() {
+            return (*t).lastfree;
+        }
+    }
+    ptr::null_mut()  /* could not find a free place */
+}
+
+
+
+/*
+** inserts a new key into a hash table; first, check whether key's main
+** position is free. If not, check whether colliding node is in its main
+** position or not: if it is not, move colliding node to an empty place and
+** put new key in its main position; otherwise (colliding node is in its main
+** position), new key goes to an empty position.
+*/
+#[cfg(rust_ltable)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaH_newkey(L: *mut lua_State, t: *mut Table,
+                                     mut key: *const TValue) -> *mut TValue {
+    let mut aux = TValue::nil();
+    if (*key).ttisnil() {
+        luaG_runerror(L, c_str!("table index is nil"));
+    } else if (*key).ttisfloat() {
+        let mut k = 0;
+        if luaV_tointeger(key, &mut k, 0) != 0 {  /* index is int? */
+            setivalue(&mut aux, k);
+            key = &aux;  /* insert it as an integer */
+        } else if (*key).fltvalue().is_nan() {
+            luaG_runerror(L, c_str!("table index is NaN"));
+        }
+    }
+    let mut mp = mainposition(t, key);
+    if !(*gval(mp)).ttisnil() || isdummy(mp) {  /* main position is taken? */
+        let f = getfreepos(t);  /* get a free place */
+        if f.is_null() {  /* cannot find a free place? */
+            rehash(L, t, key);  /* grow table */
+            /* whatever called 'newkey' takes care of TM cache */
+            return luaH_set(L, t, key);  /* insert key into grown table */
+        }
+        debug_assert!(!isdummy(f));
+        let mut othern = mainposition(t, gkey(mp));
+        if othern != mp {  /* is colliding node out of its main position? */
+            /* yes; move colliding node into free position */
+            while othern.offset(*gnext(othern) as isize) != mp {  /* find previous */
+                othern = othern.offset(*gnext(othern) as isize);
+            }
+            *gnext(othern) = f.offset_from(othern) as c_int;  /* rechain to point to 'f' */
+            ptr::copy_nonoverlapping(mp, f, 1);  /* copy colliding node into free pos. (mp->next also goes) */
+            if *gnext(mp) != 0 {
+                *gnext(f) += mp.offset_from(f) as c_int;  /* correct 'next' */
+                *gnext(mp) = 0;  /* now 'mp' is free */
+            }
+            setnilvalue(gval(mp));
+        } else {  /* colliding node is in its own main position */
+            /* new node will go into free position */
+            if *gnext(mp) != 0 {
+                *gnext(f) = mp.offset(*gnext(mp) as isize).offset_from(f) as c_int;  /* chain new position */
+            } else {
+                debug_assert!(*gnext(f) == 0);
+            }
+            *gnext(mp) = f.offset_from(mp) as c_int;
+            mp = f;
+        }
+    }
+    setnodekey(&mut (*mp).i_key, key);
+    luaC_barrierback(L, t, key);
+    debug_assert!((*gval(mp)).ttisnil());
+    gval(mp)
+}
+
+
+/*
+** search function for integers
+*/
+#[cfg(rust_ltable)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaH_getint(t: *mut Table, key: lua_Integer) -> *const TValue {
+    /* (1 <= key && key <= t->sizearray) */
+    if (key as u64).wrapping_sub(1) < (*t).sizearray as u64 {
+        (*t).array.add(key as usize - 1)
+    } else {
+        let mut n = hashpow2(t, key);
+        loop {  /* check whether 'key' is somewhere in the chain */
+            let k = gkey(n);
+            if (*k).ttisinteger() && (*k).ivalue() == key {
+                return gval(n);  /* that's it */
+            }
+            let nx = *gnext(n);
+            if nx == 0 { break; }
+            n = n.offset(nx as isize);
+        }
+        luaO_nilobject()
+    }
+}
+
+
+/*
+** search function for short strings
+*/
+#[cfg(rust_ltable)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaH_getshortstr(t: *mut Table, key: *mut TString) -> *const TValue {
+    let mut n = hashstr(t, key);
+    debug_assert!((*key).tt as c_int == LUA_TSHRSTR);
+    loop {  /* check whether 'key' is somewhere in the chain */
+        let k = gkey(n);
+        if (*k).ttisshrstring() && (*k).tsvalue() == key {
+            return gval(n);  /* that's it */
+        }
+        let nx = *gnext(n);
+        if nx == 0 {
+            return luaO_nilobject();  /* not found */
+        }
+        n = n.offset(nx as isize);
+    }
+}
+
+
+/*
+** "Generic" get version. (Not that generic: not valid for integers,
+** which may be in array part, nor for floats with integral values.)
+*/
+unsafe fn getgeneric(t: *mut Table, key: *const TValue) -> *const TValue {
+    let mut n = mainposition(t, key);
+    loop {  /* check whether 'key' is somewhere in the chain */
+        if luaV_equalobj(ptr::null_mut(), gkey(n), key) != 0 {
+            return gval(n);  /* that's it */
+        }
+        let nx = *gnext(n);
+        if nx == 0 {
+            return luaO_nilobject();  /* not found */
+        }
+        n = n.offset(nx as isize);
+    }
+}
+
+
+#[cfg(rust_ltable)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaH_getstr(t: *mut Table, key: *mut TString) -> *const TValue {
+    if (*key).tt as c_int == LUA_TSHRSTR {
+        luaH_getshortstr(t, key)
+    } else {  /* for long strings, use generic case */
+        let mut ko = TValue::nil();
+        setsvalue(&mut ko, key);
+        getgeneric(t, &ko)
+    }
+}
+
+
+/*
+** main search function
+*/
+#[cfg(rust_ltable)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaH_get(t: *mut Table, key: *const TValue) -> *const TValue {
+    match (*key).ttype() {
+        LUA_TSHRSTR => luaH_getshortstr(t, (*key).tsvalue()),
+        LUA_TNUMINT => luaH_getint(t, (*key).ivalue()),
+        lua::LUA_TNIL => luaO_nilobject(),
+        LUA_TNUMFLT => {
+            let mut k = 0;
+            if luaV_tointeger(key, &mut k, 0) != 0 {  /* index is int? */
+                luaH_getint(t, k)  /* use specialized version */
+            } else {
+                getgeneric(t, key)
+            }
+        }
+        _ => getgeneric(t, key),
+    }
+}
+
+
+/*
+** beware: when using this function you probably need to check a GC
+** barrier and invalidate the TM cache.
+*/
+#[cfg(rust_ltable)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaH_set(L: *mut lua_State, t: *mut Table,
+                                  key: *const TValue) -> *mut TValue {
+    let p = luaH_get(t, key);
+    if p != luaO_nilobject() {
+        p as *mut TValue
+    } else {
+        luaH_newkey(L, t, key)
+    }
+}
+
+
+#[cfg(rust_ltable)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaH_setint(L: *mut lua_State, t: *mut Table, key: lua_Integer,
+                                     value: *mut TValue) {
+    let p = luaH_getint(t, key);
+    let cell = if p != luaO_nilobject() {
+        p as *mut TValue
+    } else {
+        let mut k = TValue::nil();
+        setivalue(&mut k, key);
+        luaH_newkey(L, t, &k)
+    };
+    setobj(cell, value);
+}
+
+
+unsafe fn unbound_search(t: *mut Table, mut j: c_uint) -> c_uint {
+    let mut i = j;  /* i is zero or a present index */
+    j += 1;
+    /* find 'i' and 'j' such that i is present and j is not */
+    while !(*luaH_getint(t, j as lua_Integer)).ttisnil() {
+        i = j;
+        if j > c_int::MAX as c_uint / 2 {  /* overflow? */
+            /* table was built with bad purposes: resort to linear search */
+            i = 1;
+            while !(*luaH_getint(t, i as lua_Integer)).ttisnil() { i += 1; }
+            return i - 1;
+        }
+        j *= 2;
+    }
+    /* now do a binary search between them */
+    while j - i > 1 {
+        let m = (i + j) / 2;
+        if (*luaH_getint(t, m as lua_Integer)).ttisnil() { j = m; }
+        else { i = m; }
+    }
+    i
+}
+
+
+/*
+** Try to find a boundary in table 't'. A 'boundary' is an integer index
+** such that t[i] is non-nil and t[i+1] is nil (and 0 if t[1] is nil).
+*/
+#[cfg(rust_ltable)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaH_getn(t: *mut Table) -> c_int {
+    let mut j = (*t).sizearray;
+    if j > 0 && (*(*t).array.add(j as usize - 1)).ttisnil() {
+        /* there is a boundary in the array part: (binary) search for it */
+        let mut i = 0;
+        while j - i > 1 {
+            let m = (i + j) / 2;
+            if (*(*t).array.add(m as usize - 1)).ttisnil() { j = m; }
+            else { i = m; }
+        }
+        i as c_int
+    }
+    /* else must find a boundary in hash part */
+    else if isdummy((*t).node) {  /* hash part is empty? */
+        j as c_int  /* that is easy... */
+    } else {
+        unbound_search(t, j) as c_int
+    }
+}
+
+
+
+#[cfg(ltests)]
+#[cfg(rust_ltable)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaH_mainposition(t: *const Table, key: *const TValue) -> *mut Node {
+    mainposition(t, key)
+}
+
+#[cfg(ltests)]
+#[cfg(rust_ltable)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaH_isdummy(n: *mut Node) -> c_int { isdummy(n) as c_int }
@@ -0,0 +1,255 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Tag methods.
+
+#![allow(non_camel_case_types, non_snake_case, clippy::upper_case_acronyms)]
+
+use ffi::lua;
+use ldebug::{luaG_concaterror, luaG_opinterror, luaG_tointerror};
+use ldo::{luaD_call, luaD_callnoyield, restorestack, savestack};
+use lgc::luaC_fix;
+use lobject::{getstr, l_isfalse, luaO_nilobject, tonumber, GCObject, TString, TValue, Table,
+              LUA_TOTALTAGS};
+use lstate::{global_State, isLua, lua_State, StkId, G};
+use lstring::luaS_newliteral;
+use ltable::luaH_getshortstr;
+use libc::{c_char, c_int};
+use std::ptr;
+
+
+/* the exported functions below, from 'ltm.c' when it is linked instead (see 'build.rs') */
+#[cfg(not(rust_ltm))]
+extern "C-unwind" {
+    pub fn luaT_init(L: *mut lua_State);
+    pub fn luaT_gettm(events: *mut Table, event: TMS, ename: *mut TString) -> *const TValue;
+    pub fn luaT_gettmbyobj(L: *mut lua_State, o: *const TValue, event: TMS) -> *const TValue;
+    pub fn luaT_objtypename(L: *mut lua_State, o: *const TValue) -> *const c_char;
+    pub fn luaT_callTM(L: *mut lua_State, f: *const TValue, p1: *const TValue, p2: *const TValue,
+                       p3: *mut TValue, hasres: c_int);
+    pub fn luaT_callbinTM(L: *mut lua_State, p1: *const TValue, p2: *const TValue, res: StkId,
+                          event: TMS) -> c_int;
+    pub fn luaT_trybinTM(L: *mut lua_State, p1: *const TValue, p2: *const TValue, res: StkId,
+                         event: TMS);
+    pub fn luaT_callorderTM(L: *mut lua_State, p1: *const TValue, p2: *const TValue,
+                            event: TMS) -> c_int;
+}
+
+
+/*
+* WARNING: if you change the order of this enumeration,
+* grep "ORDER TM" and "ORDER OP"
+*/
+#[repr(C)]
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TMS {
+    TM_INDEX,
+    TM_NEWINDEX,
+    TM_GC,
+    TM_MODE,
+    TM_LEN,
+    TM_EQ,  /* last tag method with fast access */
+    TM_ADD,
+    TM_SUB,
+    TM_MUL,
+    TM_MOD,
+    TM_POW,
+    TM_DIV,
+    TM_IDIV,
+    TM_BAND,
+    TM_BOR,
+    TM_BXOR,
+    TM_SHL,
+    TM_SHR,
+    TM_UNM,
+    TM_BNOT,
+    TM_LT,
+    TM_LE,
+    TM_CONCAT,
+    TM_CALL,
+}
+
+pub const TM_N: usize = TMS::TM_CALL as usize + 1;  /* number of elements in the enum */
+
+
+/* a shareable wrapper, as C reads 'luaT_typenames_' as a plain array */
+#[repr(transparent)]
+pub struct TypeNames([*const c_char; LUA_TOTALTAGS]);
+
+unsafe impl Sync for TypeNames {}
+
+const UDATATYPENAME: *const c_char = c_str!("userdata");
+
+#[cfg_attr(rust_ltm, no_mangle)]  /* the same data as the C one otherwise */
+pub static luaT_typenames_: TypeNames = TypeNames([
+    c_str!("no value"),
+    c_str!("nil"), c_str!("boolean"), UDATATYPENAME, c_str!("number"),
+    c_str!("string"), c_str!("table"), c_str!("function"), UDATATYPENAME, c_str!("thread"),
+    c_str!("proto"),  /* this last case is used for tests only */
+]);
+
+pub fn ttypename(x: c_int) -> *const c_char { luaT_typenames_.0[(x + 1) as usize] }
+
+
+#[cfg(rust_ltm)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaT_init(L: *mut lua_State) {
+    const LUAT_EVENTNAME: [&str; TM_N] = [  /* ORDER TM */
+        "__index", "__newindex",
+        "__gc", "__mode", "__len", "__eq",
+        "__add", "__sub", "__mul", "__mod", "__pow",
+        "__div", "__idiv",
+        "__band", "__bor", "__bxor", "__shl", "__shr",
+        "__unm", "__bnot", "__lt", "__le",
+        "__concat", "__call",
+    ];
+    for (i, name) in LUAT_EVENTNAME.iter().enumerate() {
+        (*G(L)).tmname[i] = luaS_newliteral(L, name);
+        luaC_fix(L, (*G(L)).tmname[i] as *mut GCObject);  /* never collect these names */
+    }
+}
+
+
+/*
+** function to be used with macro "fasttm": optimized for absence of
+** tag methods
+*/
+#[cfg(rust_ltm)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaT_gettm(events: *mut Table, event: TMS,
+                                           ename: *mut TString) -> *const TValue {
+    let tm = luaH_getshortstr(events, ename);
+    debug_assert!(event as c_int <= TMS::TM_EQ as c_int);
+    if (*tm).ttisnil() {  /* no tag method? */
+        (*events).flags |= (1u32 << event as u32) as u8;  /* cache this fact */
+        ptr::null()
+    } else {
+        tm
+    }
+}
+
+
+#[cfg(rust_ltm)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaT_gettmbyobj(L: *mut lua_State, o: *const TValue,
+                                                event: TMS) -> *const TValue {
+    let mt = match (*o).ttnov() {
+        lua::LUA_TTABLE => (*(*o).hvalue()).metatable,
+        lua::LUA_TUSERDATA => (*(*o).uvalue()).metatable,
+        _ => (*G(L)).mt[(*o).ttnov() as usize],
+    };
+    if mt.is_null() { luaO_nilobject() } else { luaH_getshortstr(mt, (*G(L)).tmname[event as usize]) }
+}
+
+
+/*
+** Return the name of the type of an object. For tables and userdata
+** with metatable, use their '__name' metafield, if present.
+*/
+#[cfg(rust_ltm)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaT_objtypename(L: *mut lua_State, o: *const TValue) -> *const c_char {
+    let mt = if (*o).ttistable() { (*(*o).hvalue()).metatable }
+             else if (*o).ttisfulluserdata() { (*(*o).uvalue()).metatable }
+             else { ptr::null_mut() };
+    if !mt.is_null() {
+        let name = luaH_getshortstr(mt, luaS_newliteral(L, "__name"));
+        if (*name).ttisstring() {  /* is '__name' a string? */
+            return getstr((*name).tsvalue());  /* use it as type name */
+        }
+    }
+    ttypename((*o).ttnov())  /* else use standard type name */
+}
+
+
+#[cfg(rust_ltm)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaT_callTM(L: *mut lua_State, f: *const TValue, p1: *const TValue,
+                                            p2: *const TValue, mut p3: *mut TValue, hasres: c_int) {
+    let result = savestack(L, p3);
+    let func = (*L).top;
+    *func = *f;  /* push function (assume EXTRA_STACK) */
+    *func.add(1) = *p1;  /* 1st argument */
+    *func.add(2) = *p2;  /* 2nd argument */
+    (*L).top = (*L).top.add(3);
+    if hasres == 0 {  /* no result? 'p3' is third argument */
+        *(*L).top = *p3;  /* 3rd argument */
+        (*L).top = (*L).top.add(1);
+    }
+    /* metamethod may yield only when called from Lua code */
+    if isLua((*L).ci) {
+        luaD_call(L, func, hasres);
+    } else {
+        luaD_callnoyield(L, func, hasres);
+    }
+    if hasres != 0 {  /* if has result, move it to its place */
+        p3 = restorestack(L, result);
+        (*L).top = (*L).top.sub(1);
+        *p3 = *(*L).top;
+    }
+}
+
+
+#[cfg(rust_ltm)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaT_callbinTM(L: *mut lua_State, p1: *const TValue, p2: *const TValue,
+                                               res: StkId, event: TMS) -> c_int {
+    let mut tm = luaT_gettmbyobj(L, p1, event);  /* try first operand */
+    if (*tm).ttisnil() {
+        tm = luaT_gettmbyobj(L, p2, event);  /* try second operand */
+    }
+    if (*tm).ttisnil() { return 0; }
+    luaT_callTM(L, tm, p1, p2, res, 1);
+    1
+}
+
+
+#[cfg(rust_ltm)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaT_trybinTM(L: *mut lua_State, p1: *const TValue, p2: *const TValue,
+                                              res: StkId, event: TMS) {
+    if luaT_callbinTM(L, p1, p2, res, event) == 0 {
+        match event {
+            TMS::TM_CONCAT => luaG_concaterror(L, p1, p2),
+            TMS::TM_BAND | TMS::TM_BOR | TMS::TM_BXOR |
+            TMS::TM_SHL | TMS::TM_SHR | TMS::TM_BNOT => {
+                let mut dummy = 0.0;
+                if tonumber(p1, &mut dummy) && tonumber(p2, &mut dummy) {
+                    luaG_tointerror(L, p1, p2);
+                } else {
+                    luaG_opinterror(L, p1, p2, c_str!("perform bitwise operation on"));
+                }
+            }
+            _ => luaG_opinterror(L, p1, p2, c_str!("perform arithmetic on")),
+        }
+    }
+}
+
+
+#[cfg(rust_ltm)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaT_callorderTM(L: *mut lua_State, p1: *const TValue, p2: *const TValue,
+                                                 event: TMS) -> c_int {
+    if luaT_callbinTM(L, p1, p2, (*L).top, event) == 0 {
+        -1  /* no metamethod */
+    } else {
+        !l_isfalse((*L).top) as c_int
+    }
+}
+
+
+pub unsafe fn gfasttm(g: *mut global_State, et: *mut Table, e: TMS) -> *const TValue {
+    if et.is_null() || (*et).flags & (1u8 << e as u32) != 0 {
+        ptr::null()
+    } else {
+        luaT_gettm(et, e, (*g).tmname[e as usize])
+    }
+}
+
+pub unsafe fn fasttm(l: *mut lua_State, et: *mut Table, e: TMS) -> *const TValue {
+    gfasttm(G(l), et, e)
+}
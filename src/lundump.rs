@@ -0,0 +1,372 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Load precompiled Lua chunks.
+//!
+//! Unlike the C undumper, the loaded functions are verified before they
+//! can run (see `bytecode::check_code`), so a malicious chunk raises an
+//! error instead of crashing the interpreter.
+
+#![allow(non_snake_case)]
+
+use bytecode::{self, Constants, Shape, LUAC_DATA, LUAC_FORMAT, LUAC_INT, LUAC_NUM, LUAC_VERSION,
+               LUA_SIGNATURE, MAXNESTING};
+use ffi::lua::{self, lua_Integer, lua_Number, LUA_ERRSYNTAX};
+use ldo::{luaD_inctop, luaD_throw};
+use lfunc::{luaF_newLclosure, luaF_newproto};
+use lmem::luaM_reallocvector;
+use lobject::{getstr, luaO_pushfstring, setbvalue, setclLvalue, setfltvalue, setivalue,
+              setnilvalue, setsvalue, LClosure, LocVar, Proto, TString, TValue, Upvaldesc,
+              LUAI_MAXSHORTLEN, LUA_TLNGSTR, LUA_TNUMFLT, LUA_TNUMINT, LUA_TSHRSTR};
+use lopcodes::Instruction;
+use lstate::lua_State;
+use lstring::{luaS_createlngstrobj, luaS_newlstr};
+use lzio::{luaZ_read, Zio};
+use libc::{c_char, c_int, c_void, size_t};
+use std::mem;
+use std::ptr;
+use std::slice;
+
+
+/* the exported functions below, from 'lundump.c' when it is linked instead (see 'build.rs') */
+#[cfg(not(rust_lundump))]
+extern "C-unwind" {
+    pub fn luaU_undump(L: *mut lua_State, Z: *mut Zio, name: *const c_char) -> *mut LClosure;
+}
+
+
+struct LoadState {
+    L: *mut lua_State,
+    Z: *mut Zio,
+    name: *const c_char,
+}
+
+
+unsafe fn error(S: *mut LoadState, why: *const c_char) -> ! {
+    luaO_pushfstring((*S).L, c_str!("%s: %s precompiled chunk"), (*S).name, why);
+    luaD_throw((*S).L, LUA_ERRSYNTAX)
+}
+
+
+/* the chunk is well formed, but running it would not be safe */
+unsafe fn badcode(S: *mut LoadState, why: *const c_char) -> ! {
+    luaO_pushfstring((*S).L, c_str!("%s: bad code in precompiled chunk (%s)"), (*S).name, why);
+    luaD_throw((*S).L, LUA_ERRSYNTAX)
+}
+
+
+/* 'badcode' with a reason from the verifier */
+unsafe fn badcode_str(S: *mut LoadState, why: &str) -> ! {
+    let mut buff = [0u8; 64];
+    let n = why.len().min(buff.len() - 1);
+    buff[..n].copy_from_slice(&why.as_bytes()[..n]);
+    badcode(S, buff.as_ptr() as *const c_char)
+}
+
+
+/*
+** All high-level loads go through LoadVector; you can change it to
+** adapt to the endianness of the input
+*/
+unsafe fn LoadVector<T>(S: *mut LoadState, b: *mut T, n: size_t) {
+    LoadBlock(S, b as *mut c_void, n * mem::size_of::<T>());
+}
+
+unsafe fn LoadBlock(S: *mut LoadState, b: *mut c_void, size: size_t) {
+    if luaZ_read((*S).Z, b, size) != 0 {
+        error(S, c_str!("truncated"));
+    }
+}
+
+
+unsafe fn LoadVar<T>(S: *mut LoadState) -> T {
+    let mut x: T = mem::zeroed();
+    LoadVector(S, &mut x, 1);
+    x
+}
+
+
+unsafe fn LoadByte(S: *mut LoadState) -> u8 {
+    LoadVar(S)
+}
+
+
+unsafe fn LoadInt(S: *mut LoadState) -> c_int {
+    LoadVar(S)
+}
+
+
+/* the size of a vector, which the C undumper does not check */
+unsafe fn LoadSize(S: *mut LoadState) -> c_int {
+    let n = LoadInt(S);
+    if n < 0 {
+        badcode(S, c_str!("negative size"));
+    }
+    n
+}
+
+
+unsafe fn LoadNumber(S: *mut LoadState) -> lua_Number {
+    LoadVar(S)
+}
+
+
+unsafe fn LoadInteger(S: *mut LoadState) -> lua_Integer {
+    LoadVar(S)
+}
+
+
+unsafe fn LoadString(S: *mut LoadState) -> *mut TString {
+    let mut size = LoadByte(S) as size_t;
+    if size == 0xFF {
+        size = LoadVar(S);
+    }
+    if size == 0 {
+        return ptr::null_mut();
+    }
+    size -= 1;
+    if size <= LUAI_MAXSHORTLEN {  /* short string? */
+        let mut buff = [0 as c_char; LUAI_MAXSHORTLEN];
+        LoadVector(S, buff.as_mut_ptr(), size);
+        luaS_newlstr((*S).L, buff.as_ptr(), size)
+    } else {  /* long string */
+        let ts = luaS_createlngstrobj((*S).L, size);
+        LoadVector(S, getstr(ts) as *mut c_char, size);  /* load directly in final place */
+        ts
+    }
+}
+
+
+unsafe fn LoadCode(S: *mut LoadState, f: *mut Proto) {
+    let n = LoadSize(S);
+    luaM_reallocvector((*S).L, &mut (*f).code, 0, n);
+    (*f).sizecode = n;
+    LoadVector(S, (*f).code, n as size_t);
+}
+
+
+unsafe fn LoadConstants(S: *mut LoadState, f: *mut Proto) {
+    let n = LoadSize(S);
+    luaM_reallocvector((*S).L, &mut (*f).k, 0, n);
+    (*f).sizek = n;
+    for i in 0..n as isize {
+        setnilvalue((*f).k.offset(i));
+    }
+    for i in 0..n as isize {
+        let o = (*f).k.offset(i);
+        let t = LoadByte(S) as c_int;
+        match t {
+            lua::LUA_TNIL => setnilvalue(o),
+            lua::LUA_TBOOLEAN => setbvalue(o, LoadByte(S) as c_int),
+            LUA_TNUMFLT => setfltvalue(o, LoadNumber(S)),
+            LUA_TNUMINT => setivalue(o, LoadInteger(S)),
+            LUA_TSHRSTR | LUA_TLNGSTR => {
+                let ts = LoadString(S);
+                if ts.is_null() {
+                    badcode(S, c_str!("missing string"));
+                }
+                setsvalue(o, ts);
+            }
+            _ => badcode(S, c_str!("invalid constant")),
+        }
+    }
+}
+
+
+unsafe fn LoadProtos(S: *mut LoadState, f: *mut Proto, level: usize) {
+    let n = LoadSize(S);
+    if n > 0 && level + 1 >= MAXNESTING {
+        badcode(S, c_str!("too many nested functions"));
+    }
+    luaM_reallocvector((*S).L, &mut (*f).p, 0, n);
+    (*f).sizep = n;
+    for i in 0..n as isize {
+        *(*f).p.offset(i) = ptr::null_mut();
+    }
+    for i in 0..n as isize {
+        *(*f).p.offset(i) = luaF_newproto((*S).L);
+        LoadFunction(S, *(*f).p.offset(i), (*f).source, level + 1);
+    }
+}
+
+
+unsafe fn LoadUpvalues(S: *mut LoadState, f: *mut Proto) {
+    let n = LoadSize(S);
+    luaM_reallocvector::<Upvaldesc>((*S).L, &mut (*f).upvalues, 0, n);
+    (*f).sizeupvalues = n;
+    for i in 0..n as isize {
+        (*(*f).upvalues.offset(i)).name = ptr::null_mut();
+    }
+    for i in 0..n as isize {
+        (*(*f).upvalues.offset(i)).instack = LoadByte(S);
+        (*(*f).upvalues.offset(i)).idx = LoadByte(S);
+    }
+}
+
+
+unsafe fn LoadDebug(S: *mut LoadState, f: *mut Proto) {
+    let n = LoadSize(S);
+    luaM_reallocvector((*S).L, &mut (*f).lineinfo, 0, n);
+    (*f).sizelineinfo = n;
+    LoadVector(S, (*f).lineinfo, n as size_t);
+    let n = LoadSize(S);
+    luaM_reallocvector::<LocVar>((*S).L, &mut (*f).locvars, 0, n);
+    (*f).sizelocvars = n;
+    for i in 0..n as isize {
+        (*(*f).locvars.offset(i)).varname = ptr::null_mut();
+    }
+    for i in 0..n as isize {
+        (*(*f).locvars.offset(i)).varname = LoadString(S);
+        (*(*f).locvars.offset(i)).startpc = LoadInt(S);
+        (*(*f).locvars.offset(i)).endpc = LoadInt(S);
+    }
+    let n = LoadSize(S);
+    if n > (*f).sizeupvalues {
+        badcode(S, c_str!("too many upvalue names"));
+    }
+    for i in 0..n as isize {
+        (*(*f).upvalues.offset(i)).name = LoadString(S);
+    }
+}
+
+
+unsafe fn LoadFunction(S: *mut LoadState, f: *mut Proto, psource: *mut TString, level: usize) {
+    (*f).source = LoadString(S);
+    if (*f).source.is_null() {  /* no source in dump? */
+        (*f).source = psource;  /* reuse parent's source */
+    }
+    (*f).linedefined = LoadInt(S);
+    (*f).lastlinedefined = LoadInt(S);
+    (*f).numparams = LoadByte(S);
+    (*f).is_vararg = LoadByte(S);
+    (*f).maxstacksize = LoadByte(S);
+    LoadCode(S, f);
+    LoadConstants(S, f);
+    LoadUpvalues(S, f);
+    LoadProtos(S, f, level);
+    LoadDebug(S, f);
+}
+
+
+unsafe fn checkliteral(S: *mut LoadState, s: &[u8], msg: *const c_char) {
+    let mut buff = [0u8; 8];  /* larger than both */
+    LoadVector(S, buff.as_mut_ptr(), s.len());
+    if &buff[..s.len()] != s {
+        error(S, msg);
+    }
+}
+
+
+unsafe fn fchecksize(S: *mut LoadState, size: usize, tname: *const c_char) {
+    if LoadByte(S) as usize != size {
+        error(S, luaO_pushfstring((*S).L, c_str!("%s size mismatch in"), tname));
+    }
+}
+
+
+macro_rules! checksize {
+    ($S:expr, $t:ty, $tname:expr) => (fchecksize($S, mem::size_of::<$t>(), c_str!($tname)))
+}
+
+unsafe fn checkHeader(S: *mut LoadState) {
+    checkliteral(S, &LUA_SIGNATURE[1..], c_str!("not a"));  /* 1st char already checked */
+    if LoadByte(S) != LUAC_VERSION {
+        error(S, c_str!("version mismatch in"));
+    }
+    if LoadByte(S) != LUAC_FORMAT {
+        error(S, c_str!("format mismatch in"));
+    }
+    checkliteral(S, LUAC_DATA, c_str!("corrupted"));
+    checksize!(S, c_int, "int");
+    checksize!(S, size_t, "size_t");
+    checksize!(S, Instruction, "Instruction");
+    checksize!(S, lua_Integer, "lua_Integer");
+    checksize!(S, lua_Number, "lua_Number");
+    if LoadInteger(S) != LUAC_INT {
+        error(S, c_str!("endianness mismatch in"));
+    }
+    if LoadNumber(S) != LUAC_NUM {
+        error(S, c_str!("float format mismatch in"));
+    }
+}
+
+
+unsafe fn shape<'a>(f: *const Proto) -> Shape<'a> {
+    let code: &[Instruction] = if (*f).sizecode > 0 {
+        slice::from_raw_parts((*f).code, (*f).sizecode as usize)
+    } else {
+        &[]
+    };
+    let k: &[TValue] = if (*f).sizek > 0 {
+        slice::from_raw_parts((*f).k, (*f).sizek as usize)
+    } else {
+        &[]
+    };
+    Shape {
+        code,
+        numparams: (*f).numparams,
+        maxstacksize: (*f).maxstacksize,
+        k: Constants::Core(k),
+        nups: (*f).sizeupvalues as usize,
+        np: (*f).sizep as usize,
+    }
+}
+
+
+/*
+** Check a loaded function and the functions nested in it before they
+** can run (what 'luai_verifycode' is for in the C core).
+*/
+unsafe fn verifycode(S: *mut LoadState, f: *const Proto) {
+    let sf = shape(f);
+    if let Err(why) = bytecode::check_code(&sf) {
+        badcode_str(S, why);
+    }
+    if (*f).sizelineinfo != 0 && (*f).sizelineinfo != (*f).sizecode {
+        badcode(S, c_str!("wrong size of line information"));
+    }
+    for i in 0..(*f).sizelocvars as isize {
+        if (*(*f).locvars.offset(i)).varname.is_null() {
+            badcode(S, c_str!("missing variable name"));
+        }
+    }
+    for i in 0..(*f).sizep as isize {
+        let p = *(*f).p.offset(i);
+        for j in 0..(*p).sizeupvalues as isize {
+            let uv = &*(*p).upvalues.offset(j);
+            if let Err(why) = bytecode::check_upvalue(uv.instack != 0, uv.idx, &sf) {
+                badcode_str(S, why);
+            }
+        }
+        verifycode(S, p);
+    }
+}
+
+
+/*
+** load precompiled chunk
+*/
+#[cfg(rust_lundump)]
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaU_undump(L: *mut lua_State, Z: *mut Zio, name: *const c_char) -> *mut LClosure {
+    let mut S = LoadState { L, Z, name };
+    if *name == b'@' as c_char || *name == b'=' as c_char {
+        S.name = name.add(1);
+    } else if *name == LUA_SIGNATURE[0] as c_char {
+        S.name = c_str!("binary string");
+    }
+    checkHeader(&mut S);
+    let cl = luaF_newLclosure(L, LoadByte(&mut S) as c_int);
+    setclLvalue((*L).top, cl);
+    luaD_inctop(L);
+    (*cl).p = luaF_newproto(L);
+    LoadFunction(&mut S, (*cl).p, ptr::null_mut(), 0);
+    if (*cl).nupvalues as c_int != (*(*cl).p).sizeupvalues {
+        badcode(&mut S, c_str!("wrong number of upvalues"));
+    }
+    verifycode(&mut S, (*cl).p);
+    cl
+}
@@ -0,0 +1,323 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Standard library for UTF-8 manipulation.
+
+use ffi::lua::{self, lua_State, lua_Integer};
+use ffi::lauxlib;
+use libc::{c_char, c_int, size_t};
+
+/*
+** Lua 5.3 limits code points to 0x10FFFF, in 'utf8.char' and when decoding
+** (see 'utf8.lua'); accepting up to 0x7FFFFFFF ('lax' mode) came with 5.4.
+*/
+const MAXUNICODE: u32 = 0x10FFFF;
+
+fn iscont(c: u8) -> bool { c & 0xC0 == 0x80 }
+
+
+/* from strlib */
+/* translate a relative string position: negative means back from end */
+fn u_posrelat(pos: lua_Integer, len: usize) -> lua_Integer {
+    if pos >= 0 { pos }
+    else if 0usize.wrapping_sub(pos as usize) > len { 0 }
+    else { len as lua_Integer + pos + 1 }
+}
+
+
+/*
+** Decode one UTF-8 sequence, returning None if byte sequence is invalid.
+** On success, returns the code point and the number of bytes it uses.
+** Bytes past the end of 's' are treated as the terminating '\0' of the
+** C version, so truncated sequences are rejected.
+*/
+pub fn utf8_decode(s: &[u8]) -> Option<(u32, usize)> {
+    const LIMITS: [u32; 4] = [0xFF, 0x7F, 0x7FF, 0xFFFF];
+    let byte = |i: usize| if i < s.len() { s[i] as u32 } else { 0 };
+    let mut c = byte(0);
+    let mut res = 0u32;  /* final result */
+    if c < 0x80 {  /* ascii? */
+        return Some((c, 1));
+    }
+    let mut count = 0;  /* to count number of continuation bytes */
+    while c & 0x40 != 0 {  /* still have continuation bytes? */
+        count += 1;
+        let cc = byte(count);  /* read next byte */
+        if cc & 0xC0 != 0x80 {  /* not a continuation byte? */
+            return None;  /* invalid byte sequence */
+        }
+        res = (res << 6) | (cc & 0x3F);  /* add lower 6 bits from cont. byte */
+        c <<= 1;  /* to test next bit */
+    }
+    if count > 3 {
+        return None;  /* invalid byte sequence */
+    }
+    res |= (c & 0x7F) << (count * 5);  /* add first byte */
+    if res > MAXUNICODE || res <= LIMITS[count] {
+        return None;  /* invalid byte sequence */
+    }
+    Some((res, count + 1))  /* +1 to include first byte */
+}
+
+
+/*
+** Encode code point 'x' as an UTF-8 sequence. Unlike 'char::encode_utf8',
+** this accepts surrogates, as does 'luaO_utf8esc'.
+*/
+pub fn utf8_encode(x: u32, buff: &mut Vec<u8>) {
+    if x < 0x80 {  /* ascii? */
+        buff.push(x as u8);
+        return;
+    }
+    let mut tail = [0u8; 4];
+    let mut n = 0;  /* number of continuation bytes */
+    let mut x = x;
+    let mut mfb = 0x3fu32;  /* maximum that fits in first byte */
+    loop {  /* add continuation bytes */
+        tail[n] = 0x80 | (x & 0x3f) as u8;
+        n += 1;
+        x >>= 6;  /* remove added bits */
+        mfb >>= 1;  /* now there is one less bit available in first byte */
+        if x <= mfb { break; }  /* still needs continuation byte? */
+    }
+    buff.push(((!mfb << 1) | x) as u8);  /* add first byte */
+    buff.extend(tail[..n].iter().rev());
+}
+
+
+unsafe fn checklstring<'a>(l: *mut lua_State, arg: c_int) -> &'a [u8] {
+    let mut len: size_t = 0;
+    let s = lauxlib::luaL_checklstring(l, arg, &mut len);
+    ::std::slice::from_raw_parts(s as *const u8, len as usize)
+}
+
+
+/*
+** utf8len(s [, i [, j]]) --> number of characters that start in the
+** range [i,j], or nil + current position if 's' is not well formed in
+** that interval
+*/
+unsafe extern "C-unwind" fn utflen(l: *mut lua_State) -> c_int {
+    let mut n = 0;
+    let s = checklstring(l, 1);
+    let len = s.len();
+    let mut posi = u_posrelat(lauxlib::luaL_optinteger(l, 2, 1), len);
+    let mut posj = u_posrelat(lauxlib::luaL_optinteger(l, 3, -1), len);
+    let ok = 1 <= posi && { posi -= 1; posi <= len as lua_Integer };
+    lauxlib::luaL_argcheck(l, ok as c_int, 2, c_str!("initial position out of string"));
+    posj -= 1;
+    lauxlib::luaL_argcheck(l, (posj < len as lua_Integer) as c_int, 3,
+                           c_str!("final position out of string"));
+    while posi <= posj {
+        match utf8_decode(&s[posi as usize..]) {
+            None => {  /* conversion error? */
+                lua::lua_pushnil(l);  /* return nil ... */
+                lua::lua_pushinteger(l, posi + 1);  /* ... and current position */
+                return 2;
+            }
+            Some((_, size)) => posi += size as lua_Integer,
+        }
+        n += 1;
+    }
+    lua::lua_pushinteger(l, n);
+    1
+}
+
+
+/*
+** codepoint(s, [i, [j]])  -> returns codepoints for all characters
+** that start in the range [i,j]
+*/
+unsafe extern "C-unwind" fn codepoint(l: *mut lua_State) -> c_int {
+    let s = checklstring(l, 1);
+    let len = s.len();
+    let posi = u_posrelat(lauxlib::luaL_optinteger(l, 2, 1), len);
+    let pose = u_posrelat(lauxlib::luaL_optinteger(l, 3, posi), len);
+    lauxlib::luaL_argcheck(l, (posi >= 1) as c_int, 2, c_str!("out of range"));
+    lauxlib::luaL_argcheck(l, (pose <= len as lua_Integer) as c_int, 3, c_str!("out of range"));
+    if posi > pose {
+        return 0;  /* empty interval; return no values */
+    }
+    if pose - posi >= c_int::MAX as lua_Integer {  /* (lua_Integer -> int) overflow? */
+        return lauxlib::luaL_error(l, c_str!("string slice too long"));
+    }
+    let n = (pose - posi) as c_int + 1;
+    lauxlib::luaL_checkstack(l, n, c_str!("string slice too long"));
+    let mut n = 0;
+    let se = pose as usize;
+    let mut p = posi as usize - 1;
+    while p < se {
+        match utf8_decode(&s[p..]) {
+            None => return lauxlib::luaL_error(l, c_str!("invalid UTF-8 code")),
+            Some((code, size)) => {
+                lua::lua_pushinteger(l, code as lua_Integer);
+                p += size;
+            }
+        }
+        n += 1;
+    }
+    n
+}
+
+
+unsafe fn pushutfchar(l: *mut lua_State, arg: c_int) {
+    let code = lauxlib::luaL_checkinteger(l, arg);
+    lauxlib::luaL_argcheck(l, (0 <= code && code <= MAXUNICODE as lua_Integer) as c_int, arg,
+                           c_str!("value out of range"));
+    let mut buff = Vec::with_capacity(4);
+    utf8_encode(code as u32, &mut buff);
+    lua::lua_pushlstring(l, buff.as_ptr() as *const c_char, buff.len() as size_t);
+}
+
+
+/*
+** utfchar(n1, n2, ...)  -> char(n1)..char(n2)...
+*/
+unsafe extern "C-unwind" fn utfchar(l: *mut lua_State) -> c_int {
+    let n = lua::lua_gettop(l);  /* number of arguments */
+    if n == 1 {  /* optimize common case of single char */
+        pushutfchar(l, 1);
+    } else {
+        let mut b: lauxlib::luaL_Buffer = ::std::mem::zeroed();
+        lauxlib::luaL_buffinit(l, &mut b);
+        for i in 1..(n + 1) {
+            pushutfchar(l, i);
+            lauxlib::luaL_addvalue(&mut b);
+        }
+        lauxlib::luaL_pushresult(&mut b);
+    }
+    1
+}
+
+
+/*
+** offset(s, n, [i])  -> index where n-th character counting from
+**   position 'i' starts; 0 means character at 'i'.
+*/
+unsafe extern "C-unwind" fn byteoffset(l: *mut lua_State) -> c_int {
+    let s = checklstring(l, 1);
+    let len = s.len() as lua_Integer;
+    /* as in C, the byte just past the end is the terminating '\0' */
+    let iscont_at = |i: lua_Integer| i < len && iscont(s[i as usize]);
+    let mut n = lauxlib::luaL_checkinteger(l, 2);
+    let posi = if n >= 0 { 1 } else { len + 1 };
+    let mut posi = u_posrelat(lauxlib::luaL_optinteger(l, 3, posi), s.len());
+    let ok = 1 <= posi && { posi -= 1; posi <= len };
+    lauxlib::luaL_argcheck(l, ok as c_int, 3, c_str!("position out of range"));
+    if n == 0 {
+        /* find beginning of current byte sequence */
+        while posi > 0 && iscont_at(posi) { posi -= 1; }
+    } else {
+        if iscont_at(posi) {
+            lauxlib::luaL_error(l, c_str!("initial position is a continuation byte"));
+        }
+        if n < 0 {
+            while n < 0 && posi > 0 {  /* move back */
+                loop {  /* find beginning of previous character */
+                    posi -= 1;
+                    if !(posi > 0 && iscont_at(posi)) { break; }
+                }
+                n += 1;
+            }
+        } else {
+            n -= 1;  /* do not move for 1st character */
+            while n > 0 && posi < len {
+                loop {  /* find beginning of next character */
+                    posi += 1;
+                    if !iscont_at(posi) { break; }  /* (cannot pass final '\0') */
+                }
+                n -= 1;
+            }
+        }
+    }
+    if n == 0 {  /* did it find given character? */
+        lua::lua_pushinteger(l, posi + 1);
+    } else {  /* no such character */
+        lua::lua_pushnil(l);
+    }
+    1
+}
+
+
+unsafe extern "C-unwind" fn iter_aux(l: *mut lua_State) -> c_int {
+    let s = checklstring(l, 1);
+    let len = s.len() as lua_Integer;
+    let iscont_at = |i: lua_Integer| i < len && iscont(s[i as usize]);
+    let mut n = lua::lua_tointeger(l, 2) - 1;
+    if n < 0 {  /* first iteration? */
+        n = 0;  /* start from here */
+    } else if n < len {
+        n += 1;  /* skip current byte */
+        while iscont_at(n) { n += 1; }  /* and its continuations */
+    }
+    if n >= len {
+        return 0;  /* no more codepoints */
+    }
+    match utf8_decode(&s[n as usize..]) {
+        Some((code, size)) if !iscont_at(n + size as lua_Integer) => {
+            lua::lua_pushinteger(l, n + 1);
+            lua::lua_pushinteger(l, code as lua_Integer);
+            2
+        }
+        _ => lauxlib::luaL_error(l, c_str!("invalid UTF-8 code")),
+    }
+}
+
+
+unsafe extern "C-unwind" fn iter_codes(l: *mut lua_State) -> c_int {
+    lauxlib::luaL_checkstring(l, 1);
+    lua::lua_pushcfunction(l, Some(iter_aux));
+    lua::lua_pushvalue(l, 1);
+    lua::lua_pushinteger(l, 0);
+    3
+}
+
+
+/* pattern to match a single UTF-8 character */
+const UTF8PATT: &[u8] = b"[\0-\x7F\xC2-\xF4][\x80-\xBF]*";
+
+
+/// Opens the `utf8` library in state `l`, leaving its table on the stack.
+///
+/// # Safety
+///
+/// `l` must be a valid Lua state.
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaopen_utf8(l: *mut lua_State) -> c_int {
+    let funcs = [
+        lauxlib::luaL_Reg { name: c_str!("offset"), func: Some(byteoffset) },
+        lauxlib::luaL_Reg { name: c_str!("codepoint"), func: Some(codepoint) },
+        lauxlib::luaL_Reg { name: c_str!("char"), func: Some(utfchar) },
+        lauxlib::luaL_Reg { name: c_str!("len"), func: Some(utflen) },
+        lauxlib::luaL_Reg { name: c_str!("codes"), func: Some(iter_codes) },
+        /* placeholders */
+        lauxlib::luaL_Reg { name: c_str!("charpattern"), func: None },
+        lauxlib::luaL_Reg { name: ::std::ptr::null(), func: None },
+    ];
+    lauxlib::luaL_newlib(l, funcs.as_ptr());
+    lua::lua_pushlstring(l, UTF8PATT.as_ptr() as *const c_char, UTF8PATT.len() as size_t);
+    lua::lua_setfield(l, -2, c_str!("charpattern"));
+    1
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{utf8_decode, utf8_encode};
+
+    #[test]
+    fn test_utf8_roundtrip() {
+        for &c in &[0u32, 0x7F, 0x80, 0x7FF, 0x800, 0xD800, 0xFFFF, 0x10000, 0x10FFFF] {
+            let mut buff = Vec::new();
+            utf8_encode(c, &mut buff);
+            assert_eq!(utf8_decode(&buff), Some((c, buff.len())));
+        }
+        assert_eq!(utf8_decode(b"\xC0\x80"), None);  /* overlong */
+        assert_eq!(utf8_decode(b"\xF4\x9F\xBF\xBF"), None);  /* too large */
+        assert_eq!(utf8_decode(b"\xE4\xB8"), None);  /* truncated */
+        assert_eq!(utf8_decode(b"\xFF"), None);
+    }
+}
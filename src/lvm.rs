@@ -0,0 +1,1410 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Lua virtual machine.
+
+#![allow(non_snake_case)]
+
+use ffi::lua::{self, lua_Integer, lua_Number};
+use ffi::luaconf::{lua_numtointeger, LUA_MAXINTEGER, LUA_MININTEGER};
+use ldebug::{luaG_ordererror, luaG_traceexec, luaG_typeerror};
+use ldo::{luaD_call, luaD_growstack, luaD_poscall, luaD_precall};
+use lfunc::{luaF_close, luaF_findupval, luaF_newLclosure};
+use lgc::{isblack, luaC_barrierback, luaC_step, luaC_upvalbarrier};
+use lobject::{getstr, l_isfalse, luaO_fb2int, luaO_nilobject, luaO_str2num, luaO_tostring,
+              setbvalue, setclLvalue, setfltvalue, sethvalue, setivalue, setnilvalue, setsvalue,
+              svalue, tsslen, vslen, GCObject, LClosure, Proto, TString, TValue, Table, UpVal,
+              LUAI_MAXSHORTLEN, LUA_TLCF, LUA_TLNGSTR, LUA_TNUMFLT, LUA_TNUMINT, LUA_TSHRSTR};
+use lopcodes::{GETARG_A, GETARG_Ax, GETARG_B, GETARG_Bx, GETARG_C, GETARG_sBx, GET_OPCODE, INDEXK,
+               ISK, Instruction, LFIELDS_PER_FLUSH};
+use lopcodes::OpCode::*;
+use lstate::{isLua, lua_lock, lua_State, lua_unlock, CallInfo, StkId, CIST_FRESH, CIST_LEQ,
+             CIST_TAIL, G};
+use lstring::{luaS_createlngstrobj, luaS_eqlngstr, luaS_newlstr};
+use ltable::{luaH_get, luaH_getn, luaH_getstr, luaH_new, luaH_newkey, luaH_resize,
+             luaH_resizearray, luaH_setint};
+use ltm::{fasttm, luaT_callTM, luaT_callorderTM, luaT_gettmbyobj, luaT_trybinTM, TMS};
+use libc::{c_char, c_int, c_uint, size_t, strcoll, strlen};
+use std::ptr;
+
+
+extern "C-unwind" {
+    fn luaG_runerror(L: *mut lua_State, fmt: *const c_char, ...) -> !;
+}
+
+
+/* limit for table tag-method chains (to avoid loops) */
+const MAXTAGLOOP: c_int = 2000;
+
+/* maximum size visible for Lua (must be representable in a lua_Integer */
+const MAX_SIZE: size_t = LUA_MAXINTEGER as size_t;
+
+
+/* number of bits in the mantissa of a float */
+const NBM: u32 = f64::MANTISSA_DIGITS;
+
+/*
+** 'l_intfitsf' checks whether a given integer can be converted to a
+** float without rounding. Used in comparisons.
+*/
+fn l_intfitsf(i: lua_Integer) -> bool {
+    (-(1 << NBM)..=(1 << NBM)).contains(&i)
+}
+
+
+/*
+** You can define LUA_FLOORN2I if you want to convert floats to integers
+** by flooring them (instead of raising an error if they are not
+** integral values)
+*/
+const LUA_FLOORN2I: c_int = 0;
+
+
+unsafe fn cvt2num(o: *const TValue) -> bool { (*o).ttisstring() }
+unsafe fn cvt2str(o: *const TValue) -> bool { (*o).ttisnumber() }
+
+
+unsafe fn tonumber(o: *const TValue, n: *mut lua_Number) -> bool {
+    if (*o).ttisfloat() {
+        *n = (*o).fltvalue();
+        true
+    } else {
+        luaV_tonumber_(o, n) != 0
+    }
+}
+
+unsafe fn tointeger(o: *const TValue, i: *mut lua_Integer) -> bool {
+    if (*o).ttisinteger() {
+        *i = (*o).ivalue();
+        true
+    } else {
+        luaV_tointeger(o, i, LUA_FLOORN2I) != 0
+    }
+}
+
+unsafe fn setobj(obj1: *mut TValue, obj2: *const TValue) { *obj1 = *obj2; }
+
+unsafe fn chgivalue(obj: *mut TValue, x: lua_Integer) { (*obj).value_.i = x; }
+unsafe fn chgfltvalue(obj: *mut TValue, x: lua_Number) { (*obj).value_.n = x; }
+
+unsafe fn invalidateTMcache(t: *mut Table) { (*t).flags = 0; }
+
+
+/*
+** fast track for 'gettable': if 't' is a table and 't[k]' is not nil,
+** return true with 'slot' pointing to 't[k]' (final result).  Otherwise,
+** return false (meaning it will have to check metamethod) with 'slot'
+** pointing to a nil 't[k]' (if 't' is a table) or NULL (otherwise).
+*/
+unsafe fn luaV_fastget(t: *const TValue, k: *const TValue, slot: &mut *const TValue) -> bool {
+    if !(*t).ttistable() {
+        *slot = ptr::null();  /* not a table; 'slot' is NULL and result is 0 */
+        false
+    } else {
+        *slot = luaH_get((*t).hvalue(), k);  /* else, do raw access */
+        !(**slot).ttisnil()  /* result not nil? */
+    }
+}
+
+
+/*
+** Fast track for set table. If 't' is a table and 't[k]' is not nil,
+** call GC barrier, do a raw 't[k]=v', and return true; otherwise,
+** return false with 'slot' equal to NULL (if 't' is not a table) or
+** 'nil'. (This is needed by 'luaV_finishget'.) Note that, if the macro
+** returns true, there is no need to 'invalidateTMcache', because the
+** call is not creating a new entry.
+*/
+unsafe fn luaV_fastset(L: *mut lua_State, t: *const TValue, k: *const TValue,
+                       slot: &mut *const TValue, v: *const TValue) -> bool {
+    if !(*t).ttistable() {
+        *slot = ptr::null();
+        false
+    } else {
+        *slot = luaH_get((*t).hvalue(), k);
+        if (**slot).ttisnil() {
+            false
+        } else {
+            luaC_barrierback(L, (*t).hvalue(), v);
+            setobj(*slot as *mut TValue, v);
+            true
+        }
+    }
+}
+
+
+/*
+** Try to convert a value to a float. The float case is already handled
+** by the macro 'tonumber'.
+*/
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaV_tonumber_(obj: *const TValue, n: *mut lua_Number) -> c_int {
+    let mut v = TValue::nil();
+    if (*obj).ttisinteger() {
+        *n = (*obj).ivalue() as lua_Number;
+        1
+    } else if cvt2num(obj) &&  /* string convertible to number? */
+              luaO_str2num(svalue(obj), &mut v) == vslen(obj) + 1 {
+        *n = v.nvalue();  /* convert result of 'luaO_str2num' to a float */
+        1
+    } else {
+        0  /* conversion failed */
+    }
+}
+
+
+/*
+** try to convert a value to an integer, rounding according to 'mode':
+** mode == 0: accepts only integral values
+** mode == 1: takes the floor of the number
+** mode == 2: takes the ceil of the number
+*/
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaV_tointeger(mut obj: *const TValue, p: *mut lua_Integer,
+                                        mode: c_int) -> c_int {
+    let mut v = TValue::nil();
+    loop {
+        if (*obj).ttisfloat() {
+            let n = (*obj).fltvalue();
+            let mut f = n.floor();
+            if n != f {  /* not an integral value? */
+                if mode == 0 {
+                    return 0;  /* fails if mode demands integral value */
+                } else if mode > 1 {  /* needs ceil? */
+                    f += 1.0;  /* convert floor to ceil (remember: n != f) */
+                }
+            }
+            return lua_numtointeger(f, p);
+        } else if (*obj).ttisinteger() {
+            *p = (*obj).ivalue();
+            return 1;
+        } else if cvt2num(obj) &&
+                  luaO_str2num(svalue(obj), &mut v) == vslen(obj) + 1 {
+            obj = &v;  /* convert result from 'luaO_str2num' to an integer */
+        } else {
+            return 0;  /* conversion failed */
+        }
+    }
+}
+
+
+/*
+** Try to convert a 'for' limit to an integer, preserving the
+** semantics of the loop.
+** (The following explanation assumes a non-negative step; it is valid
+** for negative steps mutatis mutandis.)
+** If the limit can be converted to an integer, rounding down, that is
+** it.
+** Otherwise, check whether the limit can be converted to a number.  If
+** the number is too large, it is OK to set the limit as LUA_MAXINTEGER,
+** which means no limit.  If the number is too negative, the loop
+** should not run, because any initial integer value is larger than the
+** limit. So, it sets the limit to LUA_MININTEGER. 'stopnow' corrects
+** the extreme case when the initial value is LUA_MININTEGER, in which
+** case the LUA_MININTEGER limit would still run the loop once.
+*/
+unsafe fn forlimit(obj: *const TValue, p: &mut lua_Integer, step: lua_Integer,
+                   stopnow: &mut bool) -> bool {
+    *stopnow = false;  /* usually, let loops run */
+    if luaV_tointeger(obj, p, if step < 0 { 2 } else { 1 }) == 0 {  /* not fit in integer? */
+        let mut n: lua_Number = 0.0;  /* try to convert to float */
+        if !tonumber(obj, &mut n) {  /* cannot convert to float? */
+            return false;  /* not a number */
+        }
+        if 0.0 < n {  /* if true, float is larger than max integer */
+            *p = LUA_MAXINTEGER;
+            if step < 0 { *stopnow = true; }
+        } else {  /* float is smaller than min integer */
+            *p = LUA_MININTEGER;
+            if step >= 0 { *stopnow = true; }
+        }
+    }
+    true
+}
+
+
+/*
+** Finish the table access 'val = t[key]'.
+** if 'slot' is NULL, 't' is not a table; otherwise, 'slot' points to
+** t[k] entry (which must be nil).
+*/
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaV_finishget(L: *mut lua_State, mut t: *const TValue, key: *mut TValue,
+                                        val: StkId, mut slot: *const TValue) {
+    for _ in 0..MAXTAGLOOP {  /* counter to avoid infinite loops */
+        let tm;  /* metamethod */
+        if slot.is_null() {  /* 't' is not a table? */
+            debug_assert!(!(*t).ttistable());
+            tm = luaT_gettmbyobj(L, t, TMS::TM_INDEX);
+            if (*tm).ttisnil() {
+                luaG_typeerror(L, t, c_str!("index"));  /* no metamethod */
+            }
+            /* else will try the metamethod */
+        } else {  /* 't' is a table */
+            debug_assert!((*slot).ttisnil());
+            tm = fasttm(L, (*(*t).hvalue()).metatable, TMS::TM_INDEX);  /* table's metamethod */
+            if tm.is_null() {  /* no metamethod? */
+                setnilvalue(val);  /* result is nil */
+                return;
+            }
+            /* else will try the metamethod */
+        }
+        if (*tm).ttisfunction() {  /* is metamethod a function? */
+            luaT_callTM(L, tm, t, key, val, 1);  /* call it */
+            return;
+        }
+        t = tm;  /* else try to access 'tm[key]' */
+        if luaV_fastget(t, key, &mut slot) {  /* fast track? */
+            setobj(val, slot);  /* done */
+            return;
+        }
+        /* else repeat (tail call 'luaV_finishget') */
+    }
+    luaG_runerror(L, c_str!("'__index' chain too long; possible loop"));
+}
+
+
+/*
+** Finish a table assignment 't[key] = val'.
+** If 'slot' is NULL, 't' is not a table.  Otherwise, 'slot' points
+** to the entry 't[key]', or to 'luaO_nilobject' if there is no such
+** entry.  (The value at 'slot' must be nil, otherwise 'luaV_fastset'
+** would have done the job.)
+*/
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaV_finishset(L: *mut lua_State, mut t: *const TValue, key: *mut TValue,
+                                        val: StkId, mut slot: *const TValue) {
+    for _ in 0..MAXTAGLOOP {  /* counter to avoid infinite loops */
+        let tm;  /* '__newindex' metamethod */
+        if !slot.is_null() {  /* is 't' a table? */
+            let h = (*t).hvalue();  /* save 't' table */
+            debug_assert!((*slot).ttisnil());  /* old value must be nil */
+            tm = fasttm(L, (*h).metatable, TMS::TM_NEWINDEX);  /* get metamethod */
+            if tm.is_null() {  /* no metamethod? */
+                if slot == luaO_nilobject() {  /* no previous entry? */
+                    slot = luaH_newkey(L, h, key);  /* create one */
+                }
+                /* no metamethod and (now) there is an entry with given key */
+                setobj(slot as *mut TValue, val);  /* set its new value */
+                invalidateTMcache(h);
+                luaC_barrierback(L, h, val);
+                return;
+            }
+            /* else will try the metamethod */
+        } else {  /* not a table; check metamethod */
+            tm = luaT_gettmbyobj(L, t, TMS::TM_NEWINDEX);
+            if (*tm).ttisnil() {
+                luaG_typeerror(L, t, c_str!("index"));
+            }
+        }
+        /* try the metamethod */
+        if (*tm).ttisfunction() {
+            luaT_callTM(L, tm, t, key, val, 0);
+            return;
+        }
+        t = tm;  /* else repeat assignment over 'tm' */
+        if luaV_fastset(L, t, key, &mut slot, val) {
+            return;  /* done */
+        }
+        /* else loop */
+    }
+    luaG_runerror(L, c_str!("'__newindex' chain too long; possible loop"));
+}
+
+
+/*
+** Compare two strings 'ls' x 'rs', returning an integer smaller-equal-
+** -larger than zero if 'ls' is smaller-equal-larger than 'rs'.
+** The code is a little tricky because it allows '\0' in the strings
+** and it uses 'strcoll' (to respect locales) for each segments
+** of the strings.
+*/
+unsafe fn l_strcmp(ls: *const TString, rs: *const TString) -> c_int {
+    let mut l = getstr(ls);
+    let mut ll = tsslen(ls);
+    let mut r = getstr(rs);
+    let mut lr = tsslen(rs);
+    loop {  /* for each segment */
+        let temp = strcoll(l, r);
+        if temp != 0 {  /* not equal? */
+            return temp;  /* done */
+        }
+        /* strings are equal up to a '\0' */
+        let mut len = strlen(l);  /* index of first '\0' in both strings */
+        if len == lr {  /* 'rs' is finished? */
+            return if len == ll { 0 } else { 1 };  /* check 'ls' */
+        } else if len == ll {  /* 'ls' is finished? */
+            return -1;  /* 'ls' is smaller than 'rs' ('rs' is not finished) */
+        }
+        /* both strings longer than 'len'; go on comparing after the '\0' */
+        len += 1;
+        l = l.add(len); ll -= len; r = r.add(len); lr -= len;
+    }
+}
+
+
+/*
+** Check whether integer 'i' is less than float 'f'. If 'i' has an
+** exact representation as a float ('l_intfitsf'), compare numbers as
+** floats. Otherwise, if 'f' is outside the range for integers, result
+** is trivial. Otherwise, compare them as integers. (When 'i' has no
+** float representation, either 'f' is "far away" from 'i' or 'f' has
+** no precision left for a fractional part; either way, how 'f' is
+** truncated is irrelevant.) When 'f' is NaN, comparisons must result
+** in false.
+*/
+fn LTintfloat(i: lua_Integer, f: lua_Number) -> bool {
+    if !l_intfitsf(i) {
+        if f >= -(LUA_MININTEGER as lua_Number) {  /* -minint == maxint + 1 */
+            return true;  /* f >= maxint + 1 > i */
+        } else if f > LUA_MININTEGER as lua_Number {  /* minint < f <= maxint ? */
+            return i < f as lua_Integer;  /* compare them as integers */
+        } else {  /* f <= minint <= i (or 'f' is NaN)  -->  not(i < f) */
+            return false;
+        }
+    }
+    (i as lua_Number) < f  /* compare them as floats */
+}
+
+
+/*
+** Check whether integer 'i' is less than or equal to float 'f'.
+** See comments on previous function.
+*/
+fn LEintfloat(i: lua_Integer, f: lua_Number) -> bool {
+    if !l_intfitsf(i) {
+        if f >= -(LUA_MININTEGER as lua_Number) {  /* -minint == maxint + 1 */
+            return true;  /* f >= maxint + 1 > i */
+        } else if f >= LUA_MININTEGER as lua_Number {  /* minint <= f <= maxint ? */
+            return i <= f as lua_Integer;  /* compare them as integers */
+        } else {  /* f < minint <= i (or 'f' is NaN)  -->  not(i <= f) */
+            return false;
+        }
+    }
+    (i as lua_Number) <= f  /* compare them as floats */
+}
+
+
+/*
+** Return 'l < r', for numbers.
+*/
+unsafe fn LTnum(l: *const TValue, r: *const TValue) -> bool {
+    if (*l).ttisinteger() {
+        let li = (*l).ivalue();
+        if (*r).ttisinteger() {
+            li < (*r).ivalue()  /* both are integers */
+        } else {  /* 'l' is int and 'r' is float */
+            LTintfloat(li, (*r).fltvalue())  /* l < r ? */
+        }
+    } else {
+        let lf = (*l).fltvalue();  /* 'l' must be float */
+        if (*r).ttisfloat() {
+            lf < (*r).fltvalue()  /* both are float */
+        } else if lf.is_nan() {  /* 'r' is int and 'l' is float */
+            false  /* NaN < i is always false */
+        } else {  /* without NaN, (l < r)  <-->  not(r <= l) */
+            !LEintfloat((*r).ivalue(), lf)  /* not (r <= l) ? */
+        }
+    }
+}
+
+
+/*
+** Return 'l <= r', for numbers.
+*/
+unsafe fn LEnum(l: *const TValue, r: *const TValue) -> bool {
+    if (*l).ttisinteger() {
+        let li = (*l).ivalue();
+        if (*r).ttisinteger() {
+            li <= (*r).ivalue()  /* both are integers */
+        } else {  /* 'l' is int and 'r' is float */
+            LEintfloat(li, (*r).fltvalue())  /* l <= r ? */
+        }
+    } else {
+        let lf = (*l).fltvalue();  /* 'l' must be float */
+        if (*r).ttisfloat() {
+            lf <= (*r).fltvalue()  /* both are float */
+        } else if lf.is_nan() {  /* 'r' is int and 'l' is float */
+            false  /*  NaN <= i is always false */
+        } else {  /* without NaN, (l <= r)  <-->  not(r < l) */
+            !LTintfloat((*r).ivalue(), lf)  /* not (r < l) ? */
+        }
+    }
+}
+
+
+/*
+** Main operation less than; return 'l < r'.
+*/
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaV_lessthan(L: *mut lua_State, l: *const TValue,
+                                       r: *const TValue) -> c_int {
+    if (*l).ttisnumber() && (*r).ttisnumber() {  /* both operands are numbers? */
+        LTnum(l, r) as c_int
+    } else if (*l).ttisstring() && (*r).ttisstring() {  /* both are strings? */
+        (l_strcmp((*l).tsvalue(), (*r).tsvalue()) < 0) as c_int
+    } else {
+        let res = luaT_callorderTM(L, l, r, TMS::TM_LT);
+        if res < 0 {  /* no metamethod? */
+            luaG_ordererror(L, l, r);  /* error */
+        }
+        res
+    }
+}
+
+
+/*
+** Main operation less than or equal to; return 'l <= r'. If it needs
+** a metamethod and there is no '__le', try '__lt', based on
+** l <= r iff !(r < l) (assuming a total order). If the metamethod
+** yields during this substitution, the continuation has to know
+** about it (to negate the result of r<l); bit CIST_LEQ in the call
+** status keeps that information.
+*/
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaV_lessequal(L: *mut lua_State, l: *const TValue,
+                                        r: *const TValue) -> c_int {
+    if (*l).ttisnumber() && (*r).ttisnumber() {  /* both operands are numbers? */
+        return LEnum(l, r) as c_int;
+    } else if (*l).ttisstring() && (*r).ttisstring() {  /* both are strings? */
+        return (l_strcmp((*l).tsvalue(), (*r).tsvalue()) <= 0) as c_int;
+    }
+    let res = luaT_callorderTM(L, l, r, TMS::TM_LE);
+    if res >= 0 {  /* try 'le' */
+        return res;
+    }
+    /* try 'lt': */
+    (*(*L).ci).callstatus |= CIST_LEQ;  /* mark it is doing 'lt' for 'le' */
+    let res = luaT_callorderTM(L, r, l, TMS::TM_LT);
+    (*(*L).ci).callstatus ^= CIST_LEQ;  /* clear mark */
+    if res < 0 {
+        luaG_ordererror(L, l, r);
+    }
+    (res == 0) as c_int  /* result is negated */
+}
+
+
+/*
+** Main operation for equality of Lua values; return 't1 == t2'.
+** L == NULL means raw equality (no metamethods)
+*/
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaV_equalobj(L: *mut lua_State, t1: *const TValue,
+                                       t2: *const TValue) -> c_int {
+    if (*t1).ttype() != (*t2).ttype() {  /* not the same variant? */
+        if (*t1).ttnov() != (*t2).ttnov() || (*t1).ttnov() != lua::LUA_TNUMBER {
+            return 0;  /* only numbers can be equal with different variants */
+        } else {  /* two numbers with different variants */
+            let mut i1 = 0;  /* compare them as integers */
+            let mut i2 = 0;
+            return (tointeger(t1, &mut i1) && tointeger(t2, &mut i2) && i1 == i2) as c_int;
+        }
+    }
+    /* values have same type and same variant */
+    let tm = match (*t1).ttype() {
+        lua::LUA_TNIL => return 1,
+        LUA_TNUMINT => return ((*t1).ivalue() == (*t2).ivalue()) as c_int,
+        LUA_TNUMFLT => return ((*t1).fltvalue() == (*t2).fltvalue()) as c_int,
+        lua::LUA_TBOOLEAN => return ((*t1).bvalue() == (*t2).bvalue()) as c_int,  /* true must be 1 !! */
+        lua::LUA_TLIGHTUSERDATA => return ((*t1).pvalue() == (*t2).pvalue()) as c_int,
+        LUA_TLCF => {
+            let f1 = (*t1).fvalue().map(|f| f as usize);
+            return (f1 == (*t2).fvalue().map(|f| f as usize)) as c_int;
+        }
+        LUA_TSHRSTR => return ((*t1).tsvalue() == (*t2).tsvalue()) as c_int,
+        LUA_TLNGSTR => return luaS_eqlngstr((*t1).tsvalue(), (*t2).tsvalue()),
+        lua::LUA_TUSERDATA => {
+            if (*t1).uvalue() == (*t2).uvalue() {
+                return 1;
+            } else if L.is_null() {
+                return 0;
+            }
+            let tm = fasttm(L, (*(*t1).uvalue()).metatable, TMS::TM_EQ);
+            if tm.is_null() {
+                fasttm(L, (*(*t2).uvalue()).metatable, TMS::TM_EQ)
+            } else {
+                tm
+            }  /* will try TM */
+        }
+        lua::LUA_TTABLE => {
+            if (*t1).hvalue() == (*t2).hvalue() {
+                return 1;
+            } else if L.is_null() {
+                return 0;
+            }
+            let tm = fasttm(L, (*(*t1).hvalue()).metatable, TMS::TM_EQ);
+            if tm.is_null() {
+                fasttm(L, (*(*t2).hvalue()).metatable, TMS::TM_EQ)
+            } else {
+                tm
+            }  /* will try TM */
+        }
+        _ => return ((*t1).gcvalue() == (*t2).gcvalue()) as c_int,
+    };
+    if tm.is_null() {  /* no TM? */
+        return 0;  /* objects are different */
+    }
+    luaT_callTM(L, tm, t1, t2, (*L).top, 1);  /* call TM */
+    !l_isfalse((*L).top) as c_int
+}
+
+
+/* used by 'luaV_concat' to ensure that element at 'o' is a string */
+unsafe fn tostring(L: *mut lua_State, o: StkId) -> bool {
+    (*o).ttisstring() || (cvt2str(o) && { luaO_tostring(L, o); true })
+}
+
+unsafe fn isemptystr(o: *const TValue) -> bool {
+    (*o).ttisshrstring() && (*(*o).tsvalue()).shrlen == 0
+}
+
+/* copy strings in stack from top - n up to top - 1 to buffer */
+unsafe fn copy2buff(top: StkId, mut n: c_int, buff: *mut c_char) {
+    let mut tl = 0;  /* size already copied */
+    loop {
+        let o = top.offset(-n as isize);
+        let l = vslen(o);  /* length of string being copied */
+        ptr::copy_nonoverlapping(svalue(o), buff.add(tl), l);
+        tl += l;
+        n -= 1;
+        if n == 0 { break; }
+    }
+}
+
+
+/*
+** Main operation for concatenation: concat 'total' values in the stack,
+** from 'L->top - total' up to 'L->top - 1'.
+*/
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaV_concat(L: *mut lua_State, mut total: c_int) {
+    debug_assert!(total >= 2);
+    loop {
+        let top = (*L).top;
+        let mut n = 2;  /* number of elements handled in this pass (at least 2) */
+        if !((*top.offset(-2)).ttisstring() || cvt2str(top.offset(-2))) ||
+           !tostring(L, top.offset(-1)) {
+            luaT_trybinTM(L, top.offset(-2), top.offset(-1), top.offset(-2), TMS::TM_CONCAT);
+        } else if isemptystr(top.offset(-1)) {  /* second operand is empty? */
+            tostring(L, top.offset(-2));  /* result is first operand */
+        } else if isemptystr(top.offset(-2)) {  /* first operand is an empty string? */
+            setobj(top.offset(-2), top.offset(-1));  /* result is second op. */
+        } else {
+            /* at least two non-empty string values; get as many as possible */
+            let mut tl = vslen(top.offset(-1));
+            /* collect total length and number of strings */
+            n = 1;
+            while n < total && tostring(L, top.offset(-(n as isize) - 1)) {
+                let l = vslen(top.offset(-(n as isize) - 1));
+                if l >= MAX_SIZE - tl {
+                    luaG_runerror(L, c_str!("string length overflow"));
+                }
+                tl += l;
+                n += 1;
+            }
+            let ts;
+            if tl <= LUAI_MAXSHORTLEN {  /* is result a short string? */
+                let mut buff = [0 as c_char; LUAI_MAXSHORTLEN];
+                copy2buff(top, n, buff.as_mut_ptr());  /* copy strings to buffer */
+                ts = luaS_newlstr(L, buff.as_ptr(), tl);
+            } else {  /* long string; copy strings directly to final result */
+                ts = luaS_createlngstrobj(L, tl);
+                copy2buff(top, n, getstr(ts) as *mut c_char);
+            }
+            setsvalue(top.offset(-n as isize), ts);  /* create result */
+        }
+        total -= n - 1;  /* got 'n' strings to create 1 new */
+        (*L).top = (*L).top.offset(-(n as isize - 1));  /* popped 'n' strings and pushed one */
+        if total <= 1 { break; }  /* repeat until only 1 result left */
+    }
+}
+
+
+/*
+** Main operation 'ra' = #rb'.
+*/
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaV_objlen(L: *mut lua_State, ra: StkId, rb: *const TValue) {
+    let tm = match (*rb).ttype() {
+        lua::LUA_TTABLE => {
+            let h = (*rb).hvalue();
+            let tm = fasttm(L, (*h).metatable, TMS::TM_LEN);
+            if tm.is_null() {
+                setivalue(ra, luaH_getn(h) as lua_Integer);  /* else primitive len */
+                return;
+            }
+            tm  /* metamethod? break switch to call it */
+        }
+        LUA_TSHRSTR => {
+            setivalue(ra, (*(*rb).tsvalue()).shrlen as lua_Integer);
+            return;
+        }
+        LUA_TLNGSTR => {
+            setivalue(ra, (*(*rb).tsvalue()).u.lnglen as lua_Integer);
+            return;
+        }
+        _ => {  /* try metamethod */
+            let tm = luaT_gettmbyobj(L, rb, TMS::TM_LEN);
+            if (*tm).ttisnil() {  /* no metamethod? */
+                luaG_typeerror(L, rb, c_str!("get length of"));
+            }
+            tm
+        }
+    };
+    luaT_callTM(L, tm, rb, rb, ra, 1);
+}
+
+
+/*
+** Integer division; return 'm // n', that is, floor(m/n).
+** C division truncates its result (rounds towards zero).
+** 'floor(q) == trunc(q)' when 'q >= 0' or when 'q' is integer,
+** otherwise 'floor(q) == trunc(q) - 1'.
+*/
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaV_div(L: *mut lua_State, m: lua_Integer,
+                                  n: lua_Integer) -> lua_Integer {
+    if (n as u64).wrapping_add(1) <= 1 {  /* special cases: -1 or 0 */
+        if n == 0 {
+            luaG_runerror(L, c_str!("attempt to divide by zero"));
+        }
+        0i64.wrapping_sub(m)  /* n==-1; avoid overflow with 0x80000...//-1 */
+    } else {
+        let mut q = m / n;  /* perform C division */
+        if (m ^ n) < 0 && m % n != 0 {  /* 'm/n' would be negative non-integer? */
+            q -= 1;  /* correct result for different rounding */
+        }
+        q
+    }
+}
+
+
+/*
+** Integer modulus; return 'm % n'. (Assume that C '%' with
+** negative operands follows C99 behavior. See previous comment
+** about luaV_div.)
+*/
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaV_mod(L: *mut lua_State, m: lua_Integer,
+                                  n: lua_Integer) -> lua_Integer {
+    if (n as u64).wrapping_add(1) <= 1 {  /* special cases: -1 or 0 */
+        if n == 0 {
+            luaG_runerror(L, c_str!("attempt to perform 'n%%0'"));
+        }
+        0  /* m % -1 == 0; avoid overflow with 0x80000...%-1 */
+    } else {
+        let mut r = m % n;
+        if r != 0 && (m ^ n) < 0 {  /* 'm/n' would be non-integer negative? */
+            r += n;  /* correct result for different rounding */
+        }
+        r
+    }
+}
+
+
+/* number of bits in an integer */
+const NBITS: lua_Integer = lua_Integer::BITS as lua_Integer;
+
+/*
+** Shift left operation. (Shift right just negates 'y'.)
+*/
+#[no_mangle]
+pub extern "C-unwind" fn luaV_shiftl(x: lua_Integer, y: lua_Integer) -> lua_Integer {
+    if y < 0 {  /* shift right? */
+        if y <= -NBITS { 0 }
+        else { ((x as u64) >> -y) as lua_Integer }
+    } else {  /* shift left */
+        if y >= NBITS { 0 }
+        else { ((x as u64) << y) as lua_Integer }
+    }
+}
+
+
+/*
+** check whether cached closure in prototype 'p' may be reused, that is,
+** whether there is a cached closure with the same upvalues needed by
+** new closure to be created.
+*/
+unsafe fn getcached(p: *mut Proto, encup: *mut *mut UpVal, base: StkId) -> *mut LClosure {
+    let c = (*p).cache;
+    if !c.is_null() {  /* is there a cached closure? */
+        let nup = (*p).sizeupvalues;
+        let uv = (*p).upvalues;
+        for i in 0..nup as usize {  /* check whether it has right upvalues */
+            let uvi = &*uv.add(i);
+            let v = if uvi.instack != 0 {
+                base.add(uvi.idx as usize)
+            } else {
+                (**encup.add(uvi.idx as usize)).v
+            };
+            if (**(*c).upvals.as_ptr().add(i)).v != v {
+                return ptr::null_mut();  /* wrong upvalue; cannot reuse closure */
+            }
+        }
+    }
+    c  /* return cached closure (or NULL if no cached closure) */
+}
+
+
+/*
+** create a new Lua closure, push it in the stack, and initialize
+** its upvalues. Note that the closure is not cached if prototype is
+** already black (which means that 'cache' was already cleared by the
+** GC).
+*/
+unsafe fn pushclosure(L: *mut lua_State, p: *mut Proto, encup: *mut *mut UpVal, base: StkId,
+                      ra: StkId) {
+    let nup = (*p).sizeupvalues;
+    let uv = (*p).upvalues;
+    let ncl = luaF_newLclosure(L, nup);
+    (*ncl).p = p;
+    setclLvalue(ra, ncl);  /* anchor new closure in stack */
+    for i in 0..nup as usize {  /* fill in its upvalues */
+        let uvi = &*uv.add(i);
+        let up = (*ncl).upvals.as_mut_ptr().add(i);
+        if uvi.instack != 0 {  /* upvalue refers to local variable? */
+            *up = luaF_findupval(L, base.add(uvi.idx as usize));
+        } else {  /* get upvalue from enclosing function */
+            *up = *encup.add(uvi.idx as usize);
+        }
+        (**up).refcount += 1;
+        /* new closure is white, so we do not need a barrier here */
+    }
+    if !isblack(p as *mut GCObject) {  /* cache will not break GC invariant? */
+        (*p).cache = ncl;  /* save it on cache for reuse */
+    }
+}
+
+
+/*
+** finish execution of an opcode interrupted by an yield
+*/
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaV_finishOp(L: *mut lua_State) {
+    let ci = (*L).ci;
+    let base = (*ci).u.l.base;
+    let inst = *(*ci).u.l.savedpc.offset(-1);  /* interrupted instruction */
+    let op = GET_OPCODE(inst);
+    match op {  /* finish its execution */
+        OP_ADD | OP_SUB | OP_MUL | OP_DIV | OP_IDIV |
+        OP_BAND | OP_BOR | OP_BXOR | OP_SHL | OP_SHR |
+        OP_MOD | OP_POW |
+        OP_UNM | OP_BNOT | OP_LEN |
+        OP_GETTABUP | OP_GETTABLE | OP_SELF => {
+            (*L).top = (*L).top.offset(-1);
+            setobj(base.offset(GETARG_A(inst) as isize), (*L).top);
+        }
+        OP_LE | OP_LT | OP_EQ => {
+            let mut res = !l_isfalse((*L).top.offset(-1));
+            (*L).top = (*L).top.offset(-1);
+            if (*ci).callstatus & CIST_LEQ != 0 {  /* "<=" using "<" instead? */
+                debug_assert!(op == OP_LE);
+                (*ci).callstatus ^= CIST_LEQ;  /* clear mark */
+                res = !res;  /* negate result */
+            }
+            debug_assert!(GET_OPCODE(*(*ci).u.l.savedpc) == OP_JMP);
+            if res as c_int != GETARG_A(inst) {  /* condition failed? */
+                (*ci).u.l.savedpc = (*ci).u.l.savedpc.offset(1);  /* skip jump instruction */
+            }
+        }
+        OP_CONCAT => {
+            let top = (*L).top.offset(-1);  /* top when 'luaT_trybinTM' was called */
+            let b = GETARG_B(inst);  /* first element to concatenate */
+            let total = top.offset(-1).offset_from(base.offset(b as isize)) as c_int;  /* yet to concatenate */
+            setobj(top.offset(-2), top);  /* put TM result in proper position */
+            if total > 1 {  /* are there elements to concat? */
+                (*L).top = top.offset(-1);  /* top is one after last element (at top-2) */
+                luaV_concat(L, total);  /* concat them (may yield again) */
+            }
+            /* move final result to final position */
+            setobj((*ci).u.l.base.offset(GETARG_A(inst) as isize), (*L).top.offset(-1));
+            (*L).top = (*ci).top;  /* restore top */
+        }
+        OP_TFORCALL => {
+            debug_assert!(GET_OPCODE(*(*ci).u.l.savedpc) == OP_TFORLOOP);
+            (*L).top = (*ci).top;  /* correct top */
+        }
+        OP_CALL => {
+            if GETARG_C(inst) > 0 {  /* nresults >= 0? */
+                (*L).top = (*ci).top;  /* adjust results */
+            }
+        }
+        OP_TAILCALL | OP_SETTABUP | OP_SETTABLE => {}
+        _ => debug_assert!(false),
+    }
+}
+
+
+
+
+/*
+** {==================================================================
+** Function 'luaV_execute': main interpreter loop
+** ===================================================================
+*/
+
+
+/*
+** some helpers for common tasks in 'luaV_execute'
+*/
+
+unsafe fn RK(base: StkId, k: *mut TValue, x: c_int) -> *mut TValue {
+    if ISK(x) { k.offset(INDEXK(x) as isize) } else { base.offset(x as isize) }
+}
+
+
+/* execute a jump instruction */
+unsafe fn dojump(L: *mut lua_State, ci: *mut CallInfo, i: Instruction, e: c_int) {
+    let a = GETARG_A(i);
+    if a != 0 {
+        luaF_close(L, (*ci).u.l.base.offset(a as isize - 1));
+    }
+    (*ci).u.l.savedpc = (*ci).u.l.savedpc.offset((GETARG_sBx(i) + e) as isize);
+}
+
+/* for test instructions, execute the jump instruction that follows it */
+unsafe fn donextjump(L: *mut lua_State, ci: *mut CallInfo) {
+    let i = *(*ci).u.l.savedpc;
+    dojump(L, ci, i, 1);
+}
+
+
+/*
+** Does one step of collection when debt becomes positive, with 'c' as
+** the limit of live values (callers must reload 'base' afterwards).
+*/
+unsafe fn checkGC(L: *mut lua_State, ci: *mut CallInfo, c: StkId) {
+    if (*G(L)).GCdebt > 0 {
+        (*L).top = c;  /* limit of live values */
+        luaC_step(L);
+        (*L).top = (*ci).top;  /* restore top */
+    }
+    lua_unlock(L); lua_lock(L);  /* luai_threadyield */
+}
+
+
+#[no_mangle]
+pub unsafe extern "C-unwind" fn luaV_execute(L: *mut lua_State) {
+    let mut ci = (*L).ci;
+    (*ci).callstatus |= CIST_FRESH;  /* fresh invocation of 'luaV_execute" */
+    'newframe: loop {  /* reentry point when frame changes (call/return) */
+        debug_assert!(ci == (*L).ci);
+        let cl = (*(*ci).func).clLvalue();  /* local reference to function's closure */
+        let k = (*(*cl).p).k;  /* local reference to function's constant table */
+        let mut base = (*ci).u.l.base;  /* local copy of function's base */
+        let upvals = (*cl).upvals.as_mut_ptr();
+        /* main loop of interpreter */
+        loop {
+            /* fetch an instruction and prepare its execution */
+            let mut i = *(*ci).u.l.savedpc;
+            (*ci).u.l.savedpc = (*ci).u.l.savedpc.offset(1);
+            if (*L).hookmask & (lua::LUA_MASKLINE | lua::LUA_MASKCOUNT) != 0 {
+                luaG_traceexec(L);
+                base = (*ci).u.l.base;
+            }
+            let mut ra = base.offset(GETARG_A(i) as isize);  /* WARNING: any stack reallocation invalidates 'ra' */
+            debug_assert!(base == (*ci).u.l.base);
+            debug_assert!(base <= (*L).top && (*L).top < (*L).stack.offset((*L).stacksize as isize));
+            macro_rules! RB { () => { base.offset(GETARG_B(i) as isize) } }
+            macro_rules! RKB { () => { RK(base, k, GETARG_B(i)) } }
+            macro_rules! RKC { () => { RK(base, k, GETARG_C(i)) } }
+            macro_rules! Protect {
+                () => { base = (*ci).u.l.base };
+                ($x:expr) => {{ $x; base = (*ci).u.l.base; }};
+            }
+            match GET_OPCODE(i) {
+                OP_MOVE => {
+                    setobj(ra, RB!());
+                }
+                OP_LOADK => {
+                    let rb = k.offset(GETARG_Bx(i) as isize);
+                    setobj(ra, rb);
+                }
+                OP_LOADKX => {
+                    debug_assert!(GET_OPCODE(*(*ci).u.l.savedpc) == OP_EXTRAARG);
+                    let rb = k.offset(GETARG_Ax(*(*ci).u.l.savedpc) as isize);
+                    (*ci).u.l.savedpc = (*ci).u.l.savedpc.offset(1);
+                    setobj(ra, rb);
+                }
+                OP_LOADBOOL => {
+                    setbvalue(ra, GETARG_B(i));
+                    if GETARG_C(i) != 0 {  /* skip next instruction (if C) */
+                        (*ci).u.l.savedpc = (*ci).u.l.savedpc.offset(1);
+                    }
+                }
+                OP_LOADNIL => {
+                    for j in 0..=GETARG_B(i) as isize {
+                        setnilvalue(ra.offset(j));
+                    }
+                }
+                OP_GETUPVAL => {
+                    let b = GETARG_B(i);
+                    setobj(ra, (**upvals.offset(b as isize)).v);
+                }
+                OP_GETTABUP => {
+                    let upval = (**upvals.offset(GETARG_B(i) as isize)).v;
+                    let rc = RKC!();
+                    let mut slot = ptr::null();
+                    if luaV_fastget(upval, rc, &mut slot) { setobj(ra, slot); }
+                    else { Protect!(luaV_finishget(L, upval, rc, ra, slot)); }
+                }
+                OP_GETTABLE => {
+                    let rb = RB!();
+                    let rc = RKC!();
+                    let mut slot = ptr::null();
+                    if luaV_fastget(rb, rc, &mut slot) { setobj(ra, slot); }
+                    else { Protect!(luaV_finishget(L, rb, rc, ra, slot)); }
+                }
+                OP_SETTABUP => {
+                    let upval = (**upvals.offset(GETARG_A(i) as isize)).v;
+                    let rb = RKB!();
+                    let rc = RKC!();
+                    let mut slot = ptr::null();
+                    if !luaV_fastset(L, upval, rb, &mut slot, rc) {
+                        Protect!(luaV_finishset(L, upval, rb, rc, slot));
+                    }
+                }
+                OP_SETUPVAL => {
+                    let uv = *upvals.offset(GETARG_B(i) as isize);
+                    setobj((*uv).v, ra);
+                    luaC_upvalbarrier(L, uv);
+                }
+                OP_SETTABLE => {
+                    let rb = RKB!();
+                    let rc = RKC!();
+                    let mut slot = ptr::null();
+                    if !luaV_fastset(L, ra, rb, &mut slot, rc) {
+                        Protect!(luaV_finishset(L, ra, rb, rc, slot));
+                    }
+                }
+                OP_NEWTABLE => {
+                    let b = GETARG_B(i);
+                    let c = GETARG_C(i);
+                    let t = luaH_new(L);
+                    sethvalue(ra, t);
+                    if b != 0 || c != 0 {
+                        luaH_resize(L, t, luaO_fb2int(b) as c_uint, luaO_fb2int(c) as c_uint);
+                    }
+                    Protect!(checkGC(L, ci, ra.offset(1)));
+                }
+                OP_SELF => {
+                    let rb = RB!();
+                    let rc = RKC!();
+                    let key = (*rc).tsvalue();  /* key must be a string */
+                    setobj(ra.offset(1), rb);
+                    let aux = if (*rb).ttistable() {
+                        luaH_getstr((*rb).hvalue(), key)
+                    } else {
+                        ptr::null()
+                    };
+                    if !aux.is_null() && !(*aux).ttisnil() {
+                        setobj(ra, aux);
+                    } else {
+                        Protect!(luaV_finishget(L, rb, rc, ra, aux));
+                    }
+                }
+                OP_ADD => {
+                    let rb = RKB!();
+                    let rc = RKC!();
+                    let mut nb = 0.0; let mut nc = 0.0;
+                    if (*rb).ttisinteger() && (*rc).ttisinteger() {
+                        let ib = (*rb).ivalue(); let ic = (*rc).ivalue();
+                        setivalue(ra, ib.wrapping_add(ic));
+                    } else if tonumber(rb, &mut nb) && tonumber(rc, &mut nc) {
+                        setfltvalue(ra, nb + nc);
+                    } else { Protect!(luaT_trybinTM(L, rb, rc, ra, TMS::TM_ADD)); }
+                }
+                OP_SUB => {
+                    let rb = RKB!();
+                    let rc = RKC!();
+                    let mut nb = 0.0; let mut nc = 0.0;
+                    if (*rb).ttisinteger() && (*rc).ttisinteger() {
+                        let ib = (*rb).ivalue(); let ic = (*rc).ivalue();
+                        setivalue(ra, ib.wrapping_sub(ic));
+                    } else if tonumber(rb, &mut nb) && tonumber(rc, &mut nc) {
+                        setfltvalue(ra, nb - nc);
+                    } else { Protect!(luaT_trybinTM(L, rb, rc, ra, TMS::TM_SUB)); }
+                }
+                OP_MUL => {
+                    let rb = RKB!();
+                    let rc = RKC!();
+                    let mut nb = 0.0; let mut nc = 0.0;
+                    if (*rb).ttisinteger() && (*rc).ttisinteger() {
+                        let ib = (*rb).ivalue(); let ic = (*rc).ivalue();
+                        setivalue(ra, ib.wrapping_mul(ic));
+                    } else if tonumber(rb, &mut nb) && tonumber(rc, &mut nc) {
+                        setfltvalue(ra, nb * nc);
+                    } else { Protect!(luaT_trybinTM(L, rb, rc, ra, TMS::TM_MUL)); }
+                }
+                OP_DIV => {  /* float division (always with floats) */
+                    let rb = RKB!();
+                    let rc = RKC!();
+                    let mut nb = 0.0; let mut nc = 0.0;
+                    if tonumber(rb, &mut nb) && tonumber(rc, &mut nc) {
+                        setfltvalue(ra, nb / nc);
+                    } else { Protect!(luaT_trybinTM(L, rb, rc, ra, TMS::TM_DIV)); }
+                }
+                OP_BAND => {
+                    let rb = RKB!();
+                    let rc = RKC!();
+                    let mut ib = 0; let mut ic = 0;
+                    if tointeger(rb, &mut ib) && tointeger(rc, &mut ic) {
+                        setivalue(ra, ib & ic);
+                    } else { Protect!(luaT_trybinTM(L, rb, rc, ra, TMS::TM_BAND)); }
+                }
+                OP_BOR => {
+                    let rb = RKB!();
+                    let rc = RKC!();
+                    let mut ib = 0; let mut ic = 0;
+                    if tointeger(rb, &mut ib) && tointeger(rc, &mut ic) {
+                        setivalue(ra, ib | ic);
+                    } else { Protect!(luaT_trybinTM(L, rb, rc, ra, TMS::TM_BOR)); }
+                }
+                OP_BXOR => {
+                    let rb = RKB!();
+                    let rc = RKC!();
+                    let mut ib = 0; let mut ic = 0;
+                    if tointeger(rb, &mut ib) && tointeger(rc, &mut ic) {
+                        setivalue(ra, ib ^ ic);
+                    } else { Protect!(luaT_trybinTM(L, rb, rc, ra, TMS::TM_BXOR)); }
+                }
+                OP_SHL => {
+                    let rb = RKB!();
+                    let rc = RKC!();
+                    let mut ib = 0; let mut ic = 0;
+                    if tointeger(rb, &mut ib) && tointeger(rc, &mut ic) {
+                        setivalue(ra, luaV_shiftl(ib, ic));
+                    } else { Protect!(luaT_trybinTM(L, rb, rc, ra, TMS::TM_SHL)); }
+                }
+                OP_SHR => {
+                    let rb = RKB!();
+                    let rc = RKC!();
+                    let mut ib = 0; let mut ic: lua_Integer = 0;
+                    if tointeger(rb, &mut ib) && tointeger(rc, &mut ic) {
+                        setivalue(ra, luaV_shiftl(ib, ic.wrapping_neg()));
+                    } else { Protect!(luaT_trybinTM(L, rb, rc, ra, TMS::TM_SHR)); }
+                }
+                OP_MOD => {
+                    let rb = RKB!();
+                    let rc = RKC!();
+                    let mut nb: lua_Number = 0.0; let mut nc = 0.0;
+                    if (*rb).ttisinteger() && (*rc).ttisinteger() {
+                        let ib = (*rb).ivalue(); let ic = (*rc).ivalue();
+                        setivalue(ra, luaV_mod(L, ib, ic));
+                    } else if tonumber(rb, &mut nb) && tonumber(rc, &mut nc) {
+                        let mut m = nb % nc;  /* luai_nummod */
+                        if m * nc < 0.0 { m += nc; }
+                        setfltvalue(ra, m);
+                    } else { Protect!(luaT_trybinTM(L, rb, rc, ra, TMS::TM_MOD)); }
+                }
+                OP_IDIV => {  /* floor division */
+                    let rb = RKB!();
+                    let rc = RKC!();
+                    let mut nb: lua_Number = 0.0; let mut nc = 0.0;
+                    if (*rb).ttisinteger() && (*rc).ttisinteger() {
+                        let ib = (*rb).ivalue(); let ic = (*rc).ivalue();
+                        setivalue(ra, luaV_div(L, ib, ic));
+                    } else if tonumber(rb, &mut nb) && tonumber(rc, &mut nc) {
+                        setfltvalue(ra, (nb / nc).floor());
+                    } else { Protect!(luaT_trybinTM(L, rb, rc, ra, TMS::TM_IDIV)); }
+                }
+                OP_POW => {
+                    let rb = RKB!();
+                    let rc = RKC!();
+                    let mut nb: lua_Number = 0.0; let mut nc = 0.0;
+                    if tonumber(rb, &mut nb) && tonumber(rc, &mut nc) {
+                        setfltvalue(ra, nb.powf(nc));
+                    } else { Protect!(luaT_trybinTM(L, rb, rc, ra, TMS::TM_POW)); }
+                }
+                OP_UNM => {
+                    let rb = RB!();
+                    let mut nb: lua_Number = 0.0;
+                    if (*rb).ttisinteger() {
+                        let ib = (*rb).ivalue();
+                        setivalue(ra, ib.wrapping_neg());
+                    } else if tonumber(rb, &mut nb) {
+                        setfltvalue(ra, -nb);
+                    } else {
+                        Protect!(luaT_trybinTM(L, rb, rb, ra, TMS::TM_UNM));
+                    }
+                }
+                OP_BNOT => {
+                    let rb = RB!();
+                    let mut ib = 0;
+                    if tointeger(rb, &mut ib) {
+                        setivalue(ra, !ib);
+                    } else {
+                        Protect!(luaT_trybinTM(L, rb, rb, ra, TMS::TM_BNOT));
+                    }
+                }
+                OP_NOT => {
+                    let rb = RB!();
+                    let res = l_isfalse(rb);  /* next assignment may change this value */
+                    setbvalue(ra, res as c_int);
+                }
+                OP_LEN => {
+                    Protect!(luaV_objlen(L, ra, RB!()));
+                }
+                OP_CONCAT => {
+                    let b = GETARG_B(i);
+                    let c = GETARG_C(i);
+                    (*L).top = base.offset(c as isize + 1);  /* mark the end of concat operands */
+                    Protect!(luaV_concat(L, c - b + 1));
+                    ra = base.offset(GETARG_A(i) as isize);  /* 'luaV_concat' may invoke TMs and move the stack */
+                    let rb = base.offset(b as isize);
+                    setobj(ra, rb);
+                    Protect!(checkGC(L, ci, if ra >= rb { ra.offset(1) } else { rb }));
+                    (*L).top = (*ci).top;  /* restore top */
+                }
+                OP_JMP => {
+                    dojump(L, ci, i, 0);
+                }
+                OP_EQ => {
+                    let rb = RKB!();
+                    let rc = RKC!();
+                    Protect!(
+                        if luaV_equalobj(L, rb, rc) != GETARG_A(i) {
+                            (*ci).u.l.savedpc = (*ci).u.l.savedpc.offset(1);
+                        } else {
+                            donextjump(L, ci);
+                        }
+                    );
+                }
+                OP_LT => {
+                    Protect!(
+                        if luaV_lessthan(L, RKB!(), RKC!()) != GETARG_A(i) {
+                            (*ci).u.l.savedpc = (*ci).u.l.savedpc.offset(1);
+                        } else {
+                            donextjump(L, ci);
+                        }
+                    );
+                }
+                OP_LE => {
+                    Protect!(
+                        if luaV_lessequal(L, RKB!(), RKC!()) != GETARG_A(i) {
+                            (*ci).u.l.savedpc = (*ci).u.l.savedpc.offset(1);
+                        } else {
+                            donextjump(L, ci);
+                        }
+                    );
+                }
+                OP_TEST => {
+                    if if GETARG_C(i) != 0 { l_isfalse(ra) } else { !l_isfalse(ra) } {
+                        (*ci).u.l.savedpc = (*ci).u.l.savedpc.offset(1);
+                    } else {
+                        donextjump(L, ci);
+                    }
+                }
+                OP_TESTSET => {
+                    let rb = RB!();
+                    if if GETARG_C(i) != 0 { l_isfalse(rb) } else { !l_isfalse(rb) } {
+                        (*ci).u.l.savedpc = (*ci).u.l.savedpc.offset(1);
+                    } else {
+                        setobj(ra, rb);
+                        donextjump(L, ci);
+                    }
+                }
+                OP_CALL => {
+                    let b = GETARG_B(i);
+                    let nresults = GETARG_C(i) - 1;
+                    if b != 0 { (*L).top = ra.offset(b as isize); }  /* else previous instruction set top */
+                    if luaD_precall(L, ra, nresults) != 0 {  /* C function? */
+                        if nresults >= 0 {
+                            (*L).top = (*ci).top;  /* adjust results */
+                        }
+                        Protect!();  /* update 'base' */
+                    } else {  /* Lua function */
+                        ci = (*L).ci;
+                        continue 'newframe;  /* restart luaV_execute over new Lua function */
+                    }
+                }
+                OP_TAILCALL => {
+                    let b = GETARG_B(i);
+                    if b != 0 { (*L).top = ra.offset(b as isize); }  /* else previous instruction set top */
+                    debug_assert!(GETARG_C(i) - 1 == lua::LUA_MULTRET);
+                    if luaD_precall(L, ra, lua::LUA_MULTRET) != 0 {  /* C function? */
+                        Protect!();  /* update 'base' */
+                    } else {
+                        /* tail call: put called frame (n) in place of caller one (o) */
+                        let nci = (*L).ci;  /* called frame */
+                        let oci = (*nci).previous;  /* caller frame */
+                        let nfunc = (*nci).func;  /* called function */
+                        let ofunc = (*oci).func;  /* caller function */
+                        /* last stack slot filled by 'precall' */
+                        let lim = (*nci).u.l.base
+                                        .offset((*(*(*nfunc).clLvalue()).p).numparams as isize);
+                        /* close all upvalues from previous call */
+                        if (*(*cl).p).sizep > 0 { luaF_close(L, (*oci).u.l.base); }
+                        /* move new frame into old one */
+                        let mut aux = 0;
+                        while nfunc.offset(aux) < lim {
+                            setobj(ofunc.offset(aux), nfunc.offset(aux));
+                            aux += 1;
+                        }
+                        (*oci).u.l.base = ofunc.offset((*nci).u.l.base.offset_from(nfunc));  /* correct base */
+                        (*L).top = ofunc.offset((*L).top.offset_from(nfunc));  /* correct top */
+                        (*oci).top = (*L).top;
+                        (*oci).u.l.savedpc = (*nci).u.l.savedpc;
+                        (*oci).callstatus |= CIST_TAIL;  /* function was tail called */
+                        (*L).ci = oci;  /* remove new frame */
+                        ci = oci;
+                        debug_assert!((*L).top == (*oci).u.l.base.offset(
+                            (*(*(*ofunc).clLvalue()).p).maxstacksize as isize));
+                        continue 'newframe;  /* restart luaV_execute over new Lua function */
+                    }
+                }
+                OP_RETURN => {
+                    let b = GETARG_B(i);
+                    if (*(*cl).p).sizep > 0 { luaF_close(L, base); }
+                    let nres = if b != 0 { b - 1 } else { (*L).top.offset_from(ra) as c_int };
+                    let b = luaD_poscall(L, ci, ra, nres);
+                    if (*ci).callstatus & CIST_FRESH != 0 {  /* local 'ci' still from callee */
+                        return;  /* external invocation: return */
+                    } else {  /* invocation via reentry: continue execution */
+                        ci = (*L).ci;
+                        if b != 0 { (*L).top = (*ci).top; }
+                        debug_assert!(isLua(ci));
+                        debug_assert!(GET_OPCODE(*(*ci).u.l.savedpc.offset(-1)) == OP_CALL);
+                        continue 'newframe;  /* restart luaV_execute over new Lua function */
+                    }
+                }
+                OP_FORLOOP => {
+                    if (*ra).ttisinteger() {  /* integer loop? */
+                        let step = (*ra.offset(2)).ivalue();
+                        let idx = (*ra).ivalue().wrapping_add(step);  /* increment index */
+                        let limit = (*ra.offset(1)).ivalue();
+                        if if 0 < step { idx <= limit } else { limit <= idx } {
+                            (*ci).u.l.savedpc = (*ci).u.l.savedpc.offset(GETARG_sBx(i) as isize);  /* jump back */
+                            chgivalue(ra, idx);  /* update internal index... */
+                            setivalue(ra.offset(3), idx);  /* ...and external index */
+                        }
+                    } else {  /* floating loop */
+                        let step = (*ra.offset(2)).fltvalue();
+                        let idx = (*ra).fltvalue() + step;  /* inc. index */
+                        let limit = (*ra.offset(1)).fltvalue();
+                        if if 0.0 < step { idx <= limit } else { limit <= idx } {
+                            (*ci).u.l.savedpc = (*ci).u.l.savedpc.offset(GETARG_sBx(i) as isize);  /* jump back */
+                            chgfltvalue(ra, idx);  /* update internal index... */
+                            setfltvalue(ra.offset(3), idx);  /* ...and external index */
+                        }
+                    }
+                }
+                OP_FORPREP => {
+                    let init = ra;
+                    let plimit = ra.offset(1);
+                    let pstep = ra.offset(2);
+                    let mut ilimit = 0;
+                    let mut stopnow = false;
+                    if (*init).ttisinteger() && (*pstep).ttisinteger() &&
+                       forlimit(plimit, &mut ilimit, (*pstep).ivalue(), &mut stopnow) {
+                        /* all values are integer */
+                        let initv = if stopnow { 0 } else { (*init).ivalue() };
+                        setivalue(plimit, ilimit);
+                        setivalue(init, initv.wrapping_sub((*pstep).ivalue()));
+                    } else {  /* try making all values floats */
+                        let mut ninit = 0.0; let mut nlimit = 0.0; let mut nstep = 0.0;
+                        if !tonumber(plimit, &mut nlimit) {
+                            luaG_runerror(L, c_str!("'for' limit must be a number"));
+                        }
+                        setfltvalue(plimit, nlimit);
+                        if !tonumber(pstep, &mut nstep) {
+                            luaG_runerror(L, c_str!("'for' step must be a number"));
+                        }
+                        setfltvalue(pstep, nstep);
+                        if !tonumber(init, &mut ninit) {
+                            luaG_runerror(L, c_str!("'for' initial value must be a number"));
+                        }
+                        setfltvalue(init, ninit - nstep);
+                    }
+                    (*ci).u.l.savedpc = (*ci).u.l.savedpc.offset(GETARG_sBx(i) as isize);
+                }
+                OP_TFORCALL | OP_TFORLOOP => {
+                    if GET_OPCODE(i) == OP_TFORCALL {
+                        let cb = ra.offset(3);  /* call base */
+                        setobj(cb.offset(2), ra.offset(2));
+                        setobj(cb.offset(1), ra.offset(1));
+                        setobj(cb, ra);
+                        (*L).top = cb.offset(3);  /* func. + 2 args (state and index) */
+                        Protect!(luaD_call(L, cb, GETARG_C(i)));
+                        (*L).top = (*ci).top;
+                        i = *(*ci).u.l.savedpc;  /* go to next instruction */
+                        (*ci).u.l.savedpc = (*ci).u.l.savedpc.offset(1);
+                        ra = base.offset(GETARG_A(i) as isize);
+                        debug_assert!(GET_OPCODE(i) == OP_TFORLOOP);
+                    }
+                    /* l_tforloop: */
+                    if !(*ra.offset(1)).ttisnil() {  /* continue loop? */
+                        setobj(ra, ra.offset(1));  /* save control variable */
+                        (*ci).u.l.savedpc = (*ci).u.l.savedpc.offset(GETARG_sBx(i) as isize);  /* jump back */
+                    }
+                }
+                OP_SETLIST => {
+                    let mut n = GETARG_B(i);
+                    let mut c = GETARG_C(i);
+                    if n == 0 { n = (*L).top.offset_from(ra) as c_int - 1; }
+                    if c == 0 {
+                        debug_assert!(GET_OPCODE(*(*ci).u.l.savedpc) == OP_EXTRAARG);
+                        c = GETARG_Ax(*(*ci).u.l.savedpc);
+                        (*ci).u.l.savedpc = (*ci).u.l.savedpc.offset(1);
+                    }
+                    let h = (*ra).hvalue();
+                    let mut last = ((c - 1) * LFIELDS_PER_FLUSH + n) as c_uint;
+                    if last > (*h).sizearray {  /* needs more space? */
+                        luaH_resizearray(L, h, last);  /* preallocate it at once */
+                    }
+                    while n > 0 {
+                        let val = ra.offset(n as isize);
+                        luaH_setint(L, h, last as lua_Integer, val);
+                        last -= 1;
+                        luaC_barrierback(L, h, val);
+                        n -= 1;
+                    }
+                    (*L).top = (*ci).top;  /* correct top (in case of previous open call) */
+                }
+                OP_CLOSURE => {
+                    let p = *(*(*cl).p).p.offset(GETARG_Bx(i) as isize);
+                    let ncl = getcached(p, upvals, base);  /* cached closure */
+                    if ncl.is_null() {  /* no match? */
+                        pushclosure(L, p, upvals, base, ra);  /* create a new one */
+                    } else {
+                        setclLvalue(ra, ncl);  /* push cashed closure */
+                    }
+                    Protect!(checkGC(L, ci, ra.offset(1)));
+                }
+                OP_VARARG => {
+                    let mut b = GETARG_B(i) - 1;  /* required results */
+                    let mut n = base.offset_from((*ci).func) as c_int -
+                                (*(*cl).p).numparams as c_int - 1;
+                    if n < 0 {  /* less arguments than parameters? */
+                        n = 0;  /* no vararg arguments */
+                    }
+                    if b < 0 {  /* B == 0? */
+                        b = n;  /* get all var. arguments */
+                        if (*L).stack_last.offset_from((*L).top) <= n as isize {
+                            Protect!(luaD_growstack(L, n));  /* luaD_checkstack */
+                        }
+                        ra = base.offset(GETARG_A(i) as isize);  /* previous call may change the stack */
+                        (*L).top = ra.offset(n as isize);
+                    }
+                    let mut j = 0;
+                    while j < b && j < n {
+                        setobj(ra.offset(j as isize), base.offset((j - n) as isize));
+                        j += 1;
+                    }
+                    while j < b {  /* complete required results with nil */
+                        setnilvalue(ra.offset(j as isize));
+                        j += 1;
+                    }
+                }
+                OP_EXTRAARG => {
+                    debug_assert!(false);
+                }
+            }
+        }
+    }
+}
+
+/* }================================================================== */
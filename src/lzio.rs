@@ -0,0 +1,61 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Buffered streams.
+
+#![allow(non_snake_case)]
+
+use ffi::lua::lua_Reader;
+use lmem::luaM_realloc_;
+use lstate::lua_State;
+use libc::{c_char, c_int, c_void, size_t};
+
+
+pub const EOZ: c_int = -1;  /* end of stream */
+
+
+#[repr(C)]
+pub struct Mbuffer {
+    pub buffer: *mut c_char,
+    pub n: size_t,
+    pub buffsize: size_t,
+}
+
+
+#[repr(C)]
+pub struct Zio {
+    pub n: size_t,  /* bytes still unread */
+    pub p: *const c_char,  /* current position in buffer */
+    pub reader: lua_Reader,  /* reader function */
+    pub data: *mut c_void,  /* additional data */
+    pub L: *mut lua_State,  /* Lua state (for reader) */
+}
+
+
+extern "C-unwind" {
+    pub fn luaZ_init(L: *mut lua_State, z: *mut Zio, reader: lua_Reader, data: *mut c_void);
+    pub fn luaZ_fill(z: *mut Zio) -> c_int;
+    pub fn luaZ_read(z: *mut Zio, b: *mut c_void, n: size_t) -> size_t;  /* read next n bytes */
+}
+
+
+pub unsafe fn zgetc(z: *mut Zio) -> c_int {
+    let n = (*z).n;
+    (*z).n = n.wrapping_sub(1);
+    if n > 0 {
+        let c = *(*z).p as u8;
+        (*z).p = (*z).p.add(1);
+        c as c_int
+    } else {
+        luaZ_fill(z)
+    }
+}
+
+
+pub unsafe fn luaZ_resizebuffer(l: *mut lua_State, buff: *mut Mbuffer, size: size_t) {
+    (*buff).buffer = luaM_realloc_(l, (*buff).buffer as *mut c_void, (*buff).buffsize, size) as *mut c_char;
+    (*buff).buffsize = size;
+}
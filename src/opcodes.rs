@@ -0,0 +1,351 @@
+// Copyright (C) 1994-2015 Lua.org, PUC-Rio.
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Instructions of the Lua virtual machine.
+//!
+//! `Instruction` encodes and decodes the 32-bit instructions of
+//! `lopcodes.h`, and `OpCode` tells the format and operand modes of each
+//! opcode. `disassemble` lists the functions of a `bytecode::Proto` as
+//! `luac -l` does:
+//!
+//! ```
+//! use lua_rs::bytecode::{Constant, Proto};
+//! use lua_rs::opcodes::{disassemble, Instruction, OpCode, OpMode};
+//!
+//! let i = Instruction::new_abx(OpCode::OP_LOADK, 0, 0);
+//! assert_eq!(i, Instruction(0x0000_0001));
+//! assert_eq!((i.opcode(), i.a(), i.bx()), (Some(OpCode::OP_LOADK), 0, 0));
+//! assert_eq!(OpCode::OP_LOADK.mode(), OpMode::iABx);
+//!
+//! let mut main = Proto::default();
+//! main.source = Some(b"=example".to_vec());
+//! main.is_vararg = 2;
+//! main.maxstacksize = 2;
+//! main.code = vec![i.0, Instruction::new_abc(OpCode::OP_RETURN, 0, 2, 0).0];
+//! main.constants = vec![Constant::Integer(42)];
+//! let mut listing = Vec::new();
+//! disassemble(&mut listing, &main, false).unwrap();
+//! let listing = String::from_utf8(listing).unwrap();
+//! assert!(listing.ends_with("\
+//!     0+ params, 2 slots, 0 upvalues, 0 locals, 1 constant, 0 functions\n\
+//!     \t1\t[-]\tLOADK    \t0 -1\t; 42\n\
+//!     \t2\t[-]\tRETURN   \t0 2\n"));
+//! ```
+
+use bytecode::{Constant, Proto, LUA_SIGNATURE};
+use lobject::lua_number2str;
+use lopcodes::{self, getBMode, getCMode, getOpMode, testAMode, testTMode, BITRK, MAXARG_A, MAXARG_Ax,
+               MAXARG_B, MAXARG_Bx, MAXARG_C, MAXARG_sBx, NUM_OPCODES, POS_A, POS_Ax, POS_B, POS_Bx,
+               POS_C, POS_OP, SIZE_A, SIZE_Ax, SIZE_B, SIZE_Bx, SIZE_C, SIZE_OP};
+use lopcodes::OpCode::*;
+use std::fmt;
+use std::io::{self, Write};
+
+pub use lopcodes::{OpArgMask, OpCode, OpMode};
+
+
+impl OpCode {
+    /// The opcode numbered `o`, if there is one.
+    pub fn decode(o: u32) -> Option<OpCode> {
+        if (o as usize) < NUM_OPCODES { Some(OpCode::from_u32(o)) } else { None }
+    }
+
+    /// The name of the opcode, as listings show it (e.g. "MOVE").
+    pub fn name(self) -> &'static str { lopcodes::OPNAMES[self as usize] }
+
+    /// The format of the operands of the opcode.
+    pub fn mode(self) -> OpMode { getOpMode(self) }
+
+    /// How the opcode uses operand B (or Bx).
+    pub fn b_mode(self) -> OpArgMask { getBMode(self) }
+
+    /// How the opcode uses operand C.
+    pub fn c_mode(self) -> OpArgMask { getCMode(self) }
+
+    /// Whether the opcode sets register A.
+    pub fn sets_a(self) -> bool { testAMode(self) }
+
+    /// Whether the opcode is a test, so the next instruction must be a jump.
+    pub fn is_test(self) -> bool { testTMode(self) }
+}
+
+
+/// Whether the RK operand `x` refers to a constant rather than a register.
+pub fn is_k(x: i32) -> bool { x & BITRK != 0 }
+
+/// The index of the constant referred to by the RK operand `x`.
+pub fn index_k(x: i32) -> i32 { x & !BITRK }
+
+
+/// An encoded instruction.
+///
+/// Every operand can be read from any instruction, but only those of the
+/// format of its opcode (see `OpCode::mode`) mean something.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Instruction(pub u32);
+
+/* the 'size' bits of 'i' at 'pos' */
+fn field(i: u32, pos: u32, size: u32) -> i32 { ((i >> pos) & !(!0 << size)) as i32 }
+
+/* 'v' placed at 'pos', if it fits in 'max' */
+fn place(v: i32, max: i32, pos: u32, name: &str) -> u32 {
+    assert!(0 <= v && v <= max, "operand {} out of range: {}", name, v);
+    (v as u32) << pos
+}
+
+impl Instruction {
+    /// Encodes an instruction of format `iABC`.
+    pub fn new_abc(o: OpCode, a: i32, b: i32, c: i32) -> Instruction {
+        assert_eq!(o.mode(), OpMode::iABC, "{} is not iABC", o.name());
+        Instruction((o as u32) << POS_OP | place(a, MAXARG_A, POS_A, "A") |
+                    place(b, MAXARG_B, POS_B, "B") | place(c, MAXARG_C, POS_C, "C"))
+    }
+
+    /// Encodes an instruction of format `iABx`.
+    pub fn new_abx(o: OpCode, a: i32, bx: i32) -> Instruction {
+        assert_eq!(o.mode(), OpMode::iABx, "{} is not iABx", o.name());
+        Instruction((o as u32) << POS_OP | place(a, MAXARG_A, POS_A, "A") |
+                    place(bx, MAXARG_Bx, POS_Bx, "Bx"))
+    }
+
+    /// Encodes an instruction of format `iAsBx`.
+    pub fn new_asbx(o: OpCode, a: i32, sbx: i32) -> Instruction {
+        assert_eq!(o.mode(), OpMode::iAsBx, "{} is not iAsBx", o.name());
+        Instruction((o as u32) << POS_OP | place(a, MAXARG_A, POS_A, "A") |
+                    place(sbx + MAXARG_sBx, MAXARG_Bx, POS_Bx, "sBx"))
+    }
+
+    /// Encodes an instruction of format `iAx`.
+    pub fn new_ax(o: OpCode, ax: i32) -> Instruction {
+        assert_eq!(o.mode(), OpMode::iAx, "{} is not iAx", o.name());
+        Instruction((o as u32) << POS_OP | place(ax, MAXARG_Ax, POS_Ax, "Ax"))
+    }
+
+    /// The opcode, unless the instruction has an invalid one.
+    pub fn opcode(self) -> Option<OpCode> { OpCode::decode(field(self.0, POS_OP, SIZE_OP) as u32) }
+
+    pub fn a(self) -> i32 { field(self.0, POS_A, SIZE_A) }
+
+    pub fn b(self) -> i32 { field(self.0, POS_B, SIZE_B) }
+
+    pub fn c(self) -> i32 { field(self.0, POS_C, SIZE_C) }
+
+    pub fn bx(self) -> i32 { field(self.0, POS_Bx, SIZE_Bx) }
+
+    pub fn sbx(self) -> i32 { self.bx() - MAXARG_sBx }
+
+    pub fn ax(self) -> i32 { field(self.0, POS_Ax, SIZE_Ax) }
+}
+
+/* constant index as 'luac' shows it */
+fn myk(x: i32) -> i32 { -1 - x }
+
+/// Shows the opcode and operands as `luac -l` does, constants as negative
+/// numbers (`-1` for the first one).
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let o = match self.opcode() {
+            Some(o) => o,
+            None => return write!(f, "{:<9}\t{:#010x}", "?", self.0),
+        };
+        write!(f, "{:<9}\t", o.name())?;
+        let rk = |x: i32| if is_k(x) { myk(index_k(x)) } else { x };
+        match o.mode() {
+            OpMode::iABC => {
+                write!(f, "{}", self.a())?;
+                if o.b_mode() != OpArgMask::OpArgN { write!(f, " {}", rk(self.b()))?; }
+                if o.c_mode() != OpArgMask::OpArgN { write!(f, " {}", rk(self.c()))?; }
+                Ok(())
+            }
+            OpMode::iABx => {
+                write!(f, "{}", self.a())?;
+                match o.b_mode() {
+                    OpArgMask::OpArgK => write!(f, " {}", myk(self.bx())),
+                    OpArgMask::OpArgU => write!(f, " {}", self.bx()),
+                    _ => Ok(()),
+                }
+            }
+            OpMode::iAsBx => write!(f, "{} {}", self.a(), self.sbx()),
+            OpMode::iAx => write!(f, "{}", myk(self.ax())),
+        }
+    }
+}
+
+
+/*
+** {======================================================
+** Listing, as 'luac -l' prints it
+** =======================================================
+*/
+
+/// Writes the listing of `f` and the functions nested in it, as `luac -l`
+/// does; with `full` (`luac -l -l`) also lists constants, locals and
+/// upvalues. The addresses are those of the functions in `f`.
+pub fn disassemble<W: Write>(w: &mut W, f: &Proto, full: bool) -> io::Result<()> {
+    list_function(w, f, b"=?", full)
+}
+
+fn list_string<W: Write>(w: &mut W, s: &[u8]) -> io::Result<()> {
+    w.write_all(b"\"")?;
+    for &c in s {
+        match c {
+            b'"' => w.write_all(b"\\\"")?,
+            b'\\' => w.write_all(b"\\\\")?,
+            0x07 => w.write_all(b"\\a")?,
+            0x08 => w.write_all(b"\\b")?,
+            0x0C => w.write_all(b"\\f")?,
+            b'\n' => w.write_all(b"\\n")?,
+            b'\r' => w.write_all(b"\\r")?,
+            b'\t' => w.write_all(b"\\t")?,
+            0x0B => w.write_all(b"\\v")?,
+            b' '..=b'~' => w.write_all(&[c])?,
+            _ => write!(w, "\\{:03}", c)?,
+        }
+    }
+    w.write_all(b"\"")
+}
+
+fn list_constant<W: Write>(w: &mut W, f: &Proto, i: i32) -> io::Result<()> {
+    match f.constants.get(i as usize) {
+        Some(&Constant::Nil) => write!(w, "nil"),
+        Some(&Constant::Boolean(b)) => write!(w, "{}", b),
+        Some(&Constant::Number(n)) => {
+            let buff = lua_number2str(n);
+            write!(w, "{}", buff)?;
+            if buff.bytes().all(|c| c == b'-' || c.is_ascii_digit()) {
+                write!(w, ".0")?;
+            }
+            Ok(())
+        }
+        Some(&Constant::Integer(i)) => write!(w, "{}", i),
+        Some(Constant::String(s)) => list_string(w, s),
+        None => write!(w, "? index={}", i),  /* cannot happen in a verified chunk */
+    }
+}
+
+fn upvalname(f: &Proto, x: i32) -> String {
+    match f.upvalue_names.get(x as usize) {
+        Some(Some(name)) => String::from_utf8_lossy(name).into_owned(),
+        _ => "-".to_string(),
+    }
+}
+
+fn list_code<W: Write>(w: &mut W, f: &Proto) -> io::Result<()> {
+    let mut pc = 0;
+    while pc < f.code.len() {
+        let i = Instruction(f.code[pc]);
+        let line = f.lineinfo.get(pc).cloned().unwrap_or(-1);
+        write!(w, "\t{}\t", pc + 1)?;
+        if line > 0 { write!(w, "[{}]\t", line)?; } else { write!(w, "[-]\t")?; }
+        write!(w, "{}", i)?;
+        let (a, b, c) = (i.a(), i.b(), i.c());
+        match i.opcode() {
+            Some(OP_LOADK) => {
+                write!(w, "\t; ")?;
+                list_constant(w, f, i.bx())?;
+            }
+            Some(OP_GETUPVAL) | Some(OP_SETUPVAL) => write!(w, "\t; {}", upvalname(f, b))?,
+            Some(OP_GETTABUP) => {
+                write!(w, "\t; {}", upvalname(f, b))?;
+                if is_k(c) { write!(w, " ")?; list_constant(w, f, index_k(c))?; }
+            }
+            Some(OP_SETTABUP) => {
+                write!(w, "\t; {}", upvalname(f, a))?;
+                if is_k(b) { write!(w, " ")?; list_constant(w, f, index_k(b))?; }
+                if is_k(c) { write!(w, " ")?; list_constant(w, f, index_k(c))?; }
+            }
+            Some(OP_GETTABLE) | Some(OP_SELF) if is_k(c) => {
+                write!(w, "\t; ")?;
+                list_constant(w, f, index_k(c))?;
+            }
+            Some(OP_SETTABLE) | Some(OP_ADD) | Some(OP_SUB) | Some(OP_MUL) | Some(OP_POW) |
+            Some(OP_DIV) | Some(OP_IDIV) | Some(OP_BAND) | Some(OP_BOR) | Some(OP_BXOR) |
+            Some(OP_SHL) | Some(OP_SHR) | Some(OP_EQ) | Some(OP_LT) | Some(OP_LE) if is_k(b) || is_k(c) => {
+                write!(w, "\t; ")?;
+                if is_k(b) { list_constant(w, f, index_k(b))?; } else { write!(w, "-")?; }
+                write!(w, " ")?;
+                if is_k(c) { list_constant(w, f, index_k(c))?; } else { write!(w, "-")?; }
+            }
+            Some(OP_JMP) | Some(OP_FORLOOP) | Some(OP_FORPREP) | Some(OP_TFORLOOP) => {
+                write!(w, "\t; to {}", i.sbx() + pc as i32 + 2)?;
+            }
+            Some(OP_CLOSURE) => match f.protos.get(i.bx() as usize) {
+                Some(p) => write!(w, "\t; {:p}", p)?,
+                None => write!(w, "\t; ?")?,
+            },
+            Some(OP_SETLIST) => {
+                if c == 0 && pc + 1 < f.code.len() {
+                    pc += 1;
+                    write!(w, "\t; {}", f.code[pc] as i32)?;
+                } else {
+                    write!(w, "\t; {}", c)?;
+                }
+            }
+            Some(OP_EXTRAARG) => {
+                write!(w, "\t; ")?;
+                list_constant(w, f, i.ax())?;
+            }
+            _ => {}
+        }
+        writeln!(w)?;
+        pc += 1;
+    }
+    Ok(())
+}
+
+fn ss(x: usize) -> &'static str { if x == 1 { "" } else { "s" } }
+
+fn list_header<W: Write>(w: &mut W, f: &Proto, source: &[u8]) -> io::Result<()> {
+    let s = match source.first() {
+        Some(&b'@') | Some(&b'=') => String::from_utf8_lossy(&source[1..]).into_owned(),
+        Some(&c) if c == LUA_SIGNATURE[0] => "(bstring)".to_string(),
+        _ => "(string)".to_string(),
+    };
+    writeln!(w, "\n{} <{}:{},{}> ({} instruction{} at {:p})",
+             if f.linedefined == 0 { "main" } else { "function" }, s,
+             f.linedefined, f.lastlinedefined, f.code.len(), ss(f.code.len()), f)?;
+    write!(w, "{}{} param{}, {} slot{}, {} upvalue{}, ",
+           f.numparams, if f.is_vararg != 0 { "+" } else { "" }, ss(f.numparams as usize),
+           f.maxstacksize, ss(f.maxstacksize as usize), f.upvalues.len(), ss(f.upvalues.len()))?;
+    writeln!(w, "{} local{}, {} constant{}, {} function{}",
+             f.locvars.len(), ss(f.locvars.len()), f.constants.len(), ss(f.constants.len()),
+             f.protos.len(), ss(f.protos.len()))
+}
+
+fn list_debug<W: Write>(w: &mut W, f: &Proto) -> io::Result<()> {
+    writeln!(w, "constants ({}) for {:p}:", f.constants.len(), f)?;
+    for i in 0..f.constants.len() {
+        write!(w, "\t{}\t", i + 1)?;
+        list_constant(w, f, i as i32)?;
+        writeln!(w)?;
+    }
+    writeln!(w, "locals ({}) for {:p}:", f.locvars.len(), f)?;
+    for (i, v) in f.locvars.iter().enumerate() {
+        let name = v.name.as_ref().map(|s| String::from_utf8_lossy(s)).unwrap_or_default();
+        writeln!(w, "\t{}\t{}\t{}\t{}", i, name, v.startpc + 1, v.endpc + 1)?;
+    }
+    writeln!(w, "upvalues ({}) for {:p}:", f.upvalues.len(), f)?;
+    for (i, u) in f.upvalues.iter().enumerate() {
+        writeln!(w, "\t{}\t{}\t{}\t{}", i, upvalname(f, i as i32), u.instack as u8, u.idx)?;
+    }
+    Ok(())
+}
+
+/* 'psource' is the source of the enclosing function, which 'f' shares if it has none */
+fn list_function<W: Write>(w: &mut W, f: &Proto, psource: &[u8], full: bool) -> io::Result<()> {
+    let source = f.source.as_ref().map_or(psource, |s| &s[..]);
+    list_header(w, f, source)?;
+    list_code(w, f)?;
+    if full {
+        list_debug(w, f)?;
+    }
+    for p in &f.protos {
+        list_function(w, p, source, full)?;
+    }
+    Ok(())
+}
+
+/* }====================================================== */
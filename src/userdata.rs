@@ -0,0 +1,148 @@
+// Copyright (C) 2016 Ahmed Charles - acharles@outlook.com
+// Distributed under the MIT License.
+//    (See accompanying file LICENSE.txt or copy at
+//          http://opensource.org/licenses/MIT)
+
+//! Rust values owned by a Lua state or by one of its threads.
+//!
+//! Values are kept next to the global state, keyed by their thread and
+//! by the address of a static. Values of a thread are dropped when the
+//! thread is freed; values of the state belong to its main thread, and
+//! are dropped when the state is closed, after the last finalizer ran.
+//!
+//! With the C 'lstate.c' the global state has no room for them; the
+//! values of each thread are then kept by a full userdata, in a table
+//! with weak keys in the registry, and dropped by its finalizer.
+
+use ffi::lua::lua_State;
+#[cfg(not(rust_lstate))]
+use ffi::{lauxlib, lua};
+#[cfg(not(rust_lstate))]
+use libc::{c_int, c_void};
+#[cfg(rust_lstate)]
+use lstate;
+use std::any::Any;
+use std::collections::HashMap;
+#[cfg(not(rust_lstate))]
+use std::{mem, ptr};
+
+
+/* the values of a thread, keyed by the address of their key */
+type Values = HashMap<usize, Box<dyn Any>>;
+
+
+/// Stores `value` in the state of `l` under `key`, dropping any value
+/// previously stored there.
+pub unsafe fn set<T: Any>(l: *mut lua_State, key: &'static u8, value: T) {
+    values(l, true, true).unwrap().insert(key as *const u8 as usize, Box::new(value));
+}
+
+
+/// Returns the value stored in the state of `l` under `key`, if any.
+///
+/// The value stays alive while the state keeps it, that is, until it
+/// is replaced or the state is closed.
+pub unsafe fn get<'a, T: Any>(l: *mut lua_State, key: &'static u8) -> Option<&'a mut T> {
+    values(l, true, false)
+        .and_then(|values| values.get_mut(&(key as *const u8 as usize)))
+        .and_then(|v| v.downcast_mut())
+}
+
+
+/// Stores `value` in thread `l` under `key`, dropping any value
+/// previously stored there.
+pub unsafe fn set_thread<T: Any>(l: *mut lua_State, key: &'static u8, value: T) {
+    values(l, false, true).unwrap().insert(key as *const u8 as usize, Box::new(value));
+}
+
+
+/// Returns the value stored in thread `l` under `key`, if any.
+///
+/// The value stays alive until it is replaced or removed, or the
+/// thread is freed.
+pub unsafe fn get_thread<'a, T: Any>(l: *mut lua_State, key: &'static u8) -> Option<&'a mut T> {
+    values(l, false, false)
+        .and_then(|values| values.get_mut(&(key as *const u8 as usize)))
+        .and_then(|v| v.downcast_mut())
+}
+
+
+/// Removes the value stored in thread `l` under `key`, returning it.
+pub unsafe fn remove_thread<T: Any>(l: *mut lua_State, key: &'static u8) -> Option<T> {
+    values(l, false, false)
+        .and_then(|values| values.remove(&(key as *const u8 as usize)))
+        .and_then(|v| v.downcast().ok())
+        .map(|v| *v)
+}
+
+
+/*
+** The values of thread 'l', or of the main thread of its state if
+** 'main', created if 'create' and missing.
+*/
+#[cfg(rust_lstate)]
+unsafe fn values<'a>(l: *mut lua_State, main: bool, create: bool) -> Option<&'a mut Values> {
+    let thread = if main { (*lstate::G(l)).mainthread } else { l };
+    let values = lstate::userdata(l);
+    if create {
+        Some(values.entry(thread as usize).or_default())
+    } else {
+        values.get_mut(&(thread as usize))
+    }
+}
+
+
+/* key, in the registry, for the table of the values of each thread */
+#[cfg(not(rust_lstate))]
+static VALUESKEY: u8 = 0;
+
+#[cfg(not(rust_lstate))]
+unsafe fn values<'a>(l: *mut lua_State, main: bool, create: bool) -> Option<&'a mut Values> {
+    lauxlib::luaL_checkstack(l, 4, c_str!("too many values"));
+    if lua::lua_rawgetp(l, lua::LUA_REGISTRYINDEX, &VALUESKEY as *const u8 as *const c_void) == lua::LUA_TNIL {
+        if !create {
+            lua::lua_pop(l, 1);
+            return None;
+        }
+        lua::lua_pop(l, 1);
+        lua::lua_createtable(l, 0, 1);  /* create the table of values */
+        lua::lua_pushvalue(l, -1);
+        lua::lua_rawsetp(l, lua::LUA_REGISTRYINDEX, &VALUESKEY as *const u8 as *const c_void);
+        lua::lua_createtable(l, 0, 1);
+        lua::lua_pushstring(l, c_str!("k"));
+        lua::lua_setfield(l, -2, c_str!("__mode"));  /* metatable.__mode = "k" */
+        lua::lua_setmetatable(l, -2);
+    }
+    if main {
+        lua::lua_rawgeti(l, lua::LUA_REGISTRYINDEX, lua::LUA_RIDX_MAINTHREAD);
+    } else {
+        lua::lua_pushthread(l);
+    }
+    let mut values = ptr::null_mut();
+    if lua::lua_rawget(l, -2) == lua::LUA_TUSERDATA {
+        values = lua::lua_touserdata(l, -1) as *mut Values;
+    } else if create {
+        lua::lua_pop(l, 1);
+        values = lua::lua_newuserdata(l, mem::size_of::<Values>()) as *mut Values;
+        ptr::write(values, HashMap::new());
+        lua::lua_createtable(l, 0, 1);
+        lua::lua_pushcfunction(l, Some(values_gc));
+        lua::lua_setfield(l, -2, c_str!("__gc"));  /* metatable.__gc = values_gc */
+        lua::lua_setmetatable(l, -2);
+        if main {
+            lua::lua_rawgeti(l, lua::LUA_REGISTRYINDEX, lua::LUA_RIDX_MAINTHREAD);
+        } else {
+            lua::lua_pushthread(l);
+        }
+        lua::lua_pushvalue(l, -2);
+        lua::lua_rawset(l, -4);  /* table[thread] = values */
+    }
+    lua::lua_pop(l, 2);  /* remove values and table */
+    values.as_mut()
+}
+
+#[cfg(not(rust_lstate))]
+unsafe extern "C-unwind" fn values_gc(l: *mut lua_State) -> c_int {
+    ptr::drop_in_place(lua::lua_touserdata(l, 1) as *mut Values);
+    0
+}
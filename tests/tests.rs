@@ -3,4 +3,545 @@
 //    (See accompanying file LICENSE.txt or copy at
 //          http://opensource.org/licenses/MIT)
 
+extern crate libc;
+#[macro_use]
 extern crate lua_rs;
+
+use lua_rs::ffi;
+use std::cell::RefCell;
+use std::io;
+#[cfg(rust_lbaselib)]
+use std::io::Write;
+use std::rc::Rc;
+
+#[cfg(rust_lbaselib)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+#[cfg(rust_lbaselib)]
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.0.borrow_mut().write(buf) }
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+fn dostring(l: *mut ffi::lua::lua_State, s: &str) -> libc::c_int {
+    let s = std::ffi::CString::new(s).unwrap();
+    unsafe { ffi::lauxlib::luaL_dostring(l, s.as_ptr()) }
+}
+
+#[cfg(rust_lbaselib)]
+#[test]
+fn test_print_output() {
+    let l = unsafe { ffi::lauxlib::luaL_newstate() };
+    unsafe { ffi::lualib::luaL_openlibs(l); }
+    let buffer = Rc::new(RefCell::new(Vec::new()));
+    unsafe { lua_rs::lbaselib::set_output(l, Box::new(SharedBuffer(buffer.clone()))); }
+    assert_eq!(dostring(l, "print(1, 'a', nil, 2.5)"), ffi::lua::LUA_OK);
+    assert_eq!(dostring(l, "print()"), ffi::lua::LUA_OK);
+    assert_eq!(&buffer.borrow()[..], b"1\ta\tnil\t2.5\n\n");
+    assert_eq!(dostring(l, "setmetatable({}, {__gc = function() print('bye') end})"), ffi::lua::LUA_OK);
+    unsafe { ffi::lua::lua_close(l); }
+    assert_eq!(&buffer.borrow()[..], b"1\ta\tnil\t2.5\n\nbye\n");  /* finalizers still print */
+    assert_eq!(Rc::strong_count(&buffer), 1);  /* stream dropped with the state */
+    /* on the standard output, 'print' comes after what 'io.stdout' buffered */
+    let out = std::process::Command::new(env!("CARGO_BIN_EXE_lua"))
+                  .args(["-e", "io.stdout:setvbuf('full') io.write('z') print('w')"]).output().unwrap();
+    assert_eq!(&out.stdout[..], b"zw\n");
+}
+
+#[cfg(rust_loslib)]
+struct FrozenClock;
+
+#[cfg(rust_loslib)]
+impl lua_rs::loslib::Clock for FrozenClock {
+    fn time(&self) -> i64 { 86400 }
+    fn clock(&self) -> f64 { 1.5 }
+}
+
+#[cfg(rust_loslib)]
+struct FakeEnvironment;
+
+#[cfg(rust_loslib)]
+impl lua_rs::loslib::Environment for FakeEnvironment {
+    fn getenv(&self, name: &[u8]) -> Option<Vec<u8>> {
+        if name == b"LUA_RS_TEST" { Some(b"value".to_vec()) } else { None }
+    }
+}
+
+#[cfg(rust_loslib)]
+#[test]
+fn test_os_clock_and_environment() {
+    let l = unsafe { ffi::lauxlib::luaL_newstate() };
+    unsafe { ffi::lualib::luaL_openlibs(l); }
+    unsafe { lua_rs::loslib::set_clock(l, Box::new(FrozenClock)); }
+    unsafe { lua_rs::loslib::set_environment(l, Box::new(FakeEnvironment)); }
+    assert_eq!(dostring(l, "assert(os.time() == 86400 and os.clock() == 1.5)"), ffi::lua::LUA_OK);
+    assert_eq!(dostring(l, "assert(os.date('!%Y-%m-%d') == '1970-01-02')"), ffi::lua::LUA_OK);
+    assert_eq!(dostring(l, "assert(os.getenv('LUA_RS_TEST') == 'value')"), ffi::lua::LUA_OK);
+    assert_eq!(dostring(l, "assert(os.getenv('HOME') == nil)"), ffi::lua::LUA_OK);
+    unsafe { ffi::lua::lua_close(l); }
+}
+
+#[cfg(rust_liolib)]
+#[test]
+fn test_virtual_file() {
+    let l = unsafe { ffi::lauxlib::luaL_newstate() };
+    unsafe { ffi::lualib::luaL_openlibs(l); }
+    let file = io::Cursor::new(b"10 hello\nworld".to_vec());
+    unsafe { lua_rs::liolib::push_file(l, Box::new(file)); }
+    unsafe { ffi::lua::lua_setglobal(l, b"f\0".as_ptr() as *const libc::c_char); }
+    assert_eq!(dostring(l, "assert(io.type(f) == 'file')"), ffi::lua::LUA_OK);
+    assert_eq!(dostring(l, "local n, s = f:read('n', 'l'); assert(n == 10 and s == ' hello')"), ffi::lua::LUA_OK);
+    assert_eq!(dostring(l, "assert(f:read('a') == 'world' and f:read('l') == nil)"), ffi::lua::LUA_OK);
+    assert_eq!(dostring(l, "assert(f:write('!') == f and f:seek('set', 9) == 9)"), ffi::lua::LUA_OK);
+    assert_eq!(dostring(l, "assert(not pcall(f.setvbuf, f, 'full', -1))"), ffi::lua::LUA_OK);
+    assert_eq!(dostring(l, "f:setvbuf('full', math.maxinteger); f:seek('set', 9)"), ffi::lua::LUA_OK);
+    assert_eq!(dostring(l, "assert(f:read('a') == 'world!' and f:close())"), ffi::lua::LUA_OK);
+    assert_eq!(dostring(l, "assert(io.type(f) == 'closed file')"), ffi::lua::LUA_OK);
+    /* handles start with a 'luaL_Stream', which C code can close */
+    unsafe {
+        lua_rs::liolib::push_file(l, Box::new(io::Cursor::new(Vec::new())));
+        let p = ffi::lua::lua_touserdata(l, -1) as *mut ffi::lauxlib::luaL_Stream;
+        assert!((*p).f.is_null());
+        ffi::lua::lua_pushcfunction(l, (*p).closef);
+        ffi::lua::lua_pushvalue(l, -2);
+        ffi::lua::lua_call(l, 1, 1);
+        assert_eq!(ffi::lua::lua_toboolean(l, -1), 1);
+        assert!((*p).closef.is_none());  /* marked as closed */
+        ffi::lua::lua_pop(l, 2);
+    }
+    unsafe { ffi::lua::lua_close(l); }
+}
+
+#[cfg(rust_ldblib)]
+#[test]
+fn test_rust_hook() {
+    let l = unsafe { ffi::lauxlib::luaL_newstate() };
+    unsafe { ffi::lualib::luaL_openlibs(l); }
+    let lines = Rc::new(RefCell::new(Vec::new()));
+    let record = lines.clone();
+    let hook: lua_rs::ldblib::Hook = Box::new(move |_, ar| record.borrow_mut().push(ar.currentline));
+    unsafe { lua_rs::ldblib::set_hook(l, Some(hook), ffi::lua::LUA_MASKLINE, 0); }
+    assert_eq!(dostring(l, "assert(debug.gethook() == 'external hook')\nlocal a = 1\n"), ffi::lua::LUA_OK);
+    unsafe { lua_rs::ldblib::set_hook(l, None, 0, 0); }
+    assert_eq!(&lines.borrow()[..], &[1, 2]);
+    assert_eq!(dostring(l, "assert(debug.gethook() == nil)"), ffi::lua::LUA_OK);
+    unsafe { ffi::lua::lua_close(l); }
+    assert_eq!(Rc::strong_count(&lines), 1);  /* hook dropped with the state */
+
+    /* a hook that removes itself keeps running to its end */
+    let l = unsafe { ffi::lauxlib::luaL_newstate() };
+    unsafe { ffi::lualib::luaL_openlibs(l); }
+    let record = lines.clone();
+    let hook: lua_rs::ldblib::Hook = Box::new(move |l, ar| unsafe {
+        lua_rs::ldblib::set_hook(l, None, 0, 0);
+        ffi::lua::lua_gc(l, ffi::lua::LUA_GCCOLLECT, 0);
+        record.borrow_mut().push(ar.currentline);
+    });
+    unsafe { lua_rs::ldblib::set_hook(l, Some(hook), ffi::lua::LUA_MASKLINE, 0); }
+    assert_eq!(dostring(l, "local a = 1\nlocal b = 2\n"), ffi::lua::LUA_OK);
+    assert_eq!(&lines.borrow()[..], &[1, 2, 1]);
+    unsafe { ffi::lua::lua_gc(l, ffi::lua::LUA_GCCOLLECT, 0); }
+    assert_eq!(Rc::strong_count(&lines), 1);  /* dropped once the hook returned */
+
+    /* the hook of a thread is dropped when the thread is collected */
+    let record = lines.clone();
+    unsafe {
+        let co = ffi::lua::lua_newthread(l);
+        lua_rs::ldblib::set_hook(co, Some(Box::new(move |_, ar| record.borrow_mut().push(ar.currentline))), ffi::lua::LUA_MASKLINE, 0);
+        ffi::lua::lua_pop(l, 1);
+        ffi::lua::lua_gc(l, ffi::lua::LUA_GCCOLLECT, 0);
+    }
+    assert_eq!(Rc::strong_count(&lines), 1);
+    unsafe { ffi::lua::lua_close(l); }
+}
+
+#[cfg(all(rust_loslib, rust_loadlib))]
+struct PathEnvironment;
+
+#[cfg(all(rust_loslib, rust_loadlib))]
+impl lua_rs::loslib::Environment for PathEnvironment {
+    fn getenv(&self, name: &[u8]) -> Option<Vec<u8>> {
+        if name == b"LUA_PATH" { Some(b"/x/?.lua;;".to_vec()) } else { None }
+    }
+}
+
+#[cfg(all(target_os = "linux", rust_loadlib))]  /* the C 'loadlib.c' here has no dlopen loader */
+#[test]
+fn test_c_library() {
+    use std::process::Command;
+    let dir = std::env::temp_dir().join(format!("clib-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let lib = dir.join("lib2.so");
+    let status = Command::new("cc").current_dir(env!("CARGO_MANIFEST_DIR"))
+                     .args(["-std=gnu99", "-O2", "-Ipuc-lua/src", "-fPIC", "-shared", "-o"]).arg(&lib)
+                     .arg("puc-lua/src/tests/libs/lib2.c").status().unwrap();
+    assert!(status.success());
+    let path = std::ffi::CString::new(lib.to_str().unwrap()).unwrap();
+    let loaded = || unsafe {
+        let h = libc::dlopen(path.as_ptr(), libc::RTLD_NOW | libc::RTLD_NOLOAD);
+        if !h.is_null() { libc::dlclose(h); }
+        !h.is_null()
+    };
+    let l = unsafe { ffi::lauxlib::luaL_newstate() };
+    unsafe { ffi::lualib::luaL_openlibs(l); }
+    let code = format!("package.cpath = '{}/?.so'; local lib2 = require 'lib2'; \
+                        assert(x == 'lib2' and lib2.id('a') == 'a')", dir.display());
+    assert_eq!(dostring(l, &code), ffi::lua::LUA_OK);
+    assert!(loaded());
+    unsafe { ffi::lua::lua_close(l); }
+    assert!(!loaded());  /* unloaded by the finalizer of the loaded libraries */
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(all(rust_loslib, rust_loadlib))]
+#[test]
+fn test_package_path() {
+    let l = unsafe { ffi::lauxlib::luaL_newstate() };
+    unsafe { lua_rs::loslib::set_environment(l, Box::new(PathEnvironment)); }
+    unsafe { ffi::lualib::luaL_openlibs(l); }
+    assert_eq!(dostring(l, "assert(package.path:find('/x/?.lua;/', 1, true) == 1)"), ffi::lua::LUA_OK);
+    assert_eq!(dostring(l, "assert(not package.cpath:find('/x/', 1, true))"), ffi::lua::LUA_OK);
+    unsafe { ffi::lua::lua_close(l); }
+}
+
+#[test]
+fn test_buffer_growth() {
+    let l = unsafe { ffi::lauxlib::luaL_newstate() };
+    let mut b: ffi::lauxlib::luaL_Buffer = unsafe { std::mem::zeroed() };
+    unsafe {
+        ffi::lauxlib::luaL_buffinit(l, &mut b);
+        for i in 0..20000 {  /* well past the initial buffer */
+            ffi::lauxlib::luaL_addchar(&mut b, (b'a' + (i % 26) as u8) as libc::c_char);
+        }
+        ffi::lauxlib::luaL_addstring(&mut b, b"!\0".as_ptr() as *const libc::c_char);
+        ffi::lauxlib::luaL_pushresult(&mut b);
+        let mut len = 0;
+        let s = ffi::lua::lua_tolstring(l, -1, &mut len);
+        let s = std::slice::from_raw_parts(s as *const u8, len);
+        assert_eq!(len, 20001);
+        assert!(s[..20000].iter().enumerate().all(|(i, &c)| c == b'a' + (i % 26) as u8));
+        assert_eq!(s[20000], b'!');
+        assert_eq!(ffi::lua::lua_gettop(l), 1);  /* the boxed buffer is gone */
+        ffi::lua::lua_close(l);
+    }
+}
+
+#[test]
+fn test_lexer() {
+    use lua_rs::lexer::{ErrorKind, Lexer, Reserved, Token};
+    let src = b"x = [==[\nlong]==] .. 'a\\z\n  \\x41\\u{48}\\65' -- done\n--[[ c\n]] return 0x.8p1, 3 // 2";
+    let tokens: Vec<_> = Lexer::new(src).map(|t| t.unwrap()).collect();
+    let kinds: Vec<_> = tokens.iter().map(|t| t.token.clone()).collect();
+    assert_eq!(kinds, vec![Token::Name("x".to_string()), Token::Char(b'='),
+                           Token::String(b"long".to_vec()), Token::Reserved(Reserved::Concat),
+                           Token::String(b"aAHA".to_vec()), Token::Reserved(Reserved::Return),
+                           Token::Flt(1.0), Token::Char(b','), Token::Int(3),
+                           Token::Reserved(Reserved::IDiv), Token::Int(2)]);
+    assert_eq!(&src[tokens[2].span.clone()], b"[==[\nlong]==]");
+    assert_eq!((tokens[4].line, tokens[5].line), (2, 5));
+    let err = Lexer::with_name(b"x = 3.4.5", "chunk").last().unwrap().unwrap_err();
+    assert_eq!(err.kind, ErrorKind::MalformedNumber);
+    assert_eq!(err.to_string(), "chunk:1: malformed number near '3.4.5'");
+    let err = Lexer::new(b"\n'abc").last().unwrap().unwrap_err();
+    assert_eq!(err.to_string(), "?:2: unfinished string near <eof>");
+}
+
+#[test]
+fn test_compiler() {
+    let l = unsafe { ffi::lauxlib::luaL_newstate() };
+    unsafe { ffi::lualib::luaL_openlibs(l); }
+    let code = r#"
+        local t = {}
+        for i = 1, 3 do t[i] = function() return i end end
+        assert(t[1]() + t[3]() == 4)
+        local k = 0
+        ::top:: k = k + 1
+        if k < 10 then goto top end
+        assert(k == 10)
+        local function dump(s) return string.dump(load(s, '=x')) end
+        assert(dump('return 2 * 3 + 1') == dump('return 7'))  -- folded
+        assert(dump('return 1 // 0') ~= dump('return 0'))  -- not folded
+        local _, msg = load('goto nowhere', '=x')
+        assert(msg == "x:1: no visible label 'nowhere' for <goto> at line 1")
+        _, msg = load('local a <= 1', '=x')
+        assert(msg == "x:1: unexpected symbol near '<='")
+    "#;
+    assert_eq!(dostring(l, code), ffi::lua::LUA_OK);
+    unsafe { ffi::lua::lua_close(l); }
+}
+
+#[test]
+fn test_ast() {
+    use lua_rs::ast::{self, BinOp, ExprKind, StatKind};
+    let src = b"-- sum\nlocal s = a // 2 | b << 1  -- mixed\nfor i = 1, 3 do goto skip end\n::skip::";
+    let chunk = ast::parse(src).unwrap();
+    let stats = &chunk.block.stats;
+    assert_eq!(stats.len(), 3);
+    assert_eq!(stats[0].comments.iter().map(|c| &c.text[..]).collect::<Vec<_>>(),
+               vec![&b" sum"[..], &b" mixed"[..]]);
+    match stats[0].kind {
+        StatKind::Local { ref exprs, .. } => match exprs[0].kind {
+            ExprKind::Binary { op, ref lhs, .. } => {
+                assert_eq!(op, BinOp::BOr);
+                assert_eq!(&src[lhs.span.clone()], b"a // 2");
+            }
+            ref e => panic!("unexpected {:?}", e),
+        },
+        ref s => panic!("unexpected {:?}", s),
+    }
+    assert_eq!((stats[1].line, stats[2].line), (3, 4));
+    let err = ast::parse_with_name(b"local function f()\n  return 1\n", "stdin").unwrap_err();
+    assert_eq!(err.to_string(), "stdin:3: 'end' expected (to close 'function' at line 1) near <eof>");
+    assert!(err.is_incomplete());
+    let err = ast::parse(b"do goto l end").unwrap_err();
+    assert_eq!(err.to_string(), "?:1: no visible label 'l' for <goto> at line 1");
+    assert!(!err.is_incomplete());
+}
+
+#[test]
+fn test_bytecode() {
+    use lua_rs::bytecode::{Chunk, Constant, Error};
+    use lua_rs::opcodes::{Instruction, OpCode};
+    let l = unsafe { ffi::lauxlib::luaL_newstate() };
+    unsafe { ffi::lualib::luaL_openlibs(l); }
+    let code = "return string.dump(load('local x = 42 return function() return x + 1.5 end', '=c'))";
+    assert_eq!(dostring(l, code), ffi::lua::LUA_OK);
+    let bytes = unsafe {
+        let mut len = 0;
+        let s = ffi::lua::lua_tolstring(l, -1, &mut len);
+        std::slice::from_raw_parts(s as *const u8, len).to_vec()
+    };
+    let mut chunk = Chunk::read(&bytes).unwrap();
+    assert_eq!(chunk.to_bytes(), bytes);
+    assert_eq!(chunk.main.source, Some(b"=c".to_vec()));
+    assert_eq!(chunk.main.constants, vec![Constant::Integer(42)]);
+    assert_eq!(chunk.main.protos[0].constants, vec![Constant::Number(1.5)]);
+    chunk.strip();
+    assert!(chunk.to_bytes().len() < bytes.len());
+    assert_eq!(Chunk::read(&bytes[..bytes.len() - 1]).unwrap_err(), Error::Truncated);
+
+    chunk.main.protos[0].upvalues[0].idx = 7;  /* the parent has fewer registers */
+    let bad = chunk.to_bytes();
+    let err = Chunk::read(&bad).unwrap_err();
+    assert_eq!(err, Error::BadCode("upvalue out of range"));
+    #[cfg(rust_lundump)]  /* the C 'lundump.c' does not check the code it loads */
+    {
+        unsafe {
+            ffi::lua::lua_pushlstring(l, bad.as_ptr() as *const libc::c_char, bad.len());
+            ffi::lua::lua_setglobal(l, b"bad\0".as_ptr() as *const libc::c_char);
+        }
+        let code = "local f, msg = load(bad, 'bad', 'b')
+                    assert(not f and msg == 'bad: bad code in precompiled chunk (upvalue out of range)')";
+        assert_eq!(dostring(l, code), ffi::lua::LUA_OK);
+    }
+
+    assert_eq!(dostring(l, "return string.dump(load('local t = {} return t(t)'))"), ffi::lua::LUA_OK);
+    let bytes = unsafe {
+        let mut len = 0;
+        let s = ffi::lua::lua_tolstring(l, -1, &mut len);
+        std::slice::from_raw_parts(s as *const u8, len).to_vec()
+    };
+    let tamper = |op: OpCode, b: i32, c: i32| {
+        let mut chunk = Chunk::read(&bytes).unwrap();
+        let i = chunk.main.code.iter_mut().find(|i| Instruction(**i).opcode() == Some(op)).unwrap();
+        *i = Instruction::new_abc(op, Instruction(*i).a(), b, c).0;
+        Chunk::read(&chunk.to_bytes()).unwrap_err()
+    };
+    assert_eq!(tamper(OpCode::OP_NEWTABLE, 511, 0), Error::BadCode("table size out of range"));
+    assert_eq!(tamper(OpCode::OP_NEWTABLE, 0, 232), Error::BadCode("table size out of range"));
+    assert_eq!(tamper(OpCode::OP_TAILCALL, 2, 2), Error::BadCode("bad tail call"));
+    unsafe { ffi::lua::lua_close(l); }
+}
+
+#[cfg(rust_ldo)]  /* the C 'ldo.c' jumps over Rust frames, dropping nothing */
+thread_local!(static DROPPED: std::cell::Cell<usize> = const { std::cell::Cell::new(0) });
+
+#[cfg(rust_ldo)]
+struct Guard;
+
+#[cfg(rust_ldo)]
+impl Drop for Guard {
+    fn drop(&mut self) { DROPPED.with(|d| d.set(d.get() + 1)); }
+}
+
+/* calls its argument with a live Rust value on the stack in between */
+#[cfg(rust_ldo)]
+unsafe extern "C-unwind" fn call_through(l: *mut ffi::lua::lua_State) -> libc::c_int {
+    let _guard = Guard;
+    ffi::lua::lua_call(l, 0, 0);
+    0
+}
+
+#[cfg(rust_ldo)]
+#[test]
+fn test_error_through_rust_frame() {
+    let l = unsafe { ffi::lauxlib::luaL_newstate() };
+    unsafe { ffi::lualib::luaL_openlibs(l); }
+    unsafe { ffi::lua::lua_register(l, b"call\0".as_ptr() as *const libc::c_char, Some(call_through)); }
+    let code = "local ok, msg = pcall(call, function() error('boom', 0) end)
+                assert(not ok and msg == 'boom')";
+    assert_eq!(dostring(l, code), ffi::lua::LUA_OK);
+    assert_eq!(DROPPED.with(|d| d.get()), 1);
+    let code = "local co = coroutine.wrap(function() call(coroutine.yield) end)
+                local ok, msg = pcall(co)
+                assert(not ok and string.find(msg, 'C%-call boundary'))";
+    assert_eq!(dostring(l, code), ffi::lua::LUA_OK);
+    assert_eq!(DROPPED.with(|d| d.get()), 2);
+    assert_eq!(dostring(l, "call(function() error('uncaught') end)"), ffi::lua::LUA_ERRRUN);
+    assert_eq!(DROPPED.with(|d| d.get()), 3);
+    unsafe { ffi::lua::lua_close(l); }
+}
+
+fn tostr(l: *mut ffi::lua::lua_State, idx: libc::c_int) -> String {
+    let s = unsafe { std::ffi::CStr::from_ptr(ffi::lua::lua_tolstring(l, idx, std::ptr::null_mut())) };
+    s.to_str().unwrap().to_string()
+}
+
+#[test]
+fn test_push_fstring() {
+    let l = unsafe { ffi::lauxlib::luaL_newstate() };
+    unsafe { push_fstring!(l, "%s=%d %I %f %c%c %U %%", "x", 42, 1i64 << 40, 1.0, 'a', 10, 0x20AC); }
+    assert_eq!(tostr(l, -1), "x=42 1099511627776 1.0 a<\\10> \u{20AC} %");
+    unsafe { push_fstring!(l, "%f %f %f", 0.1, 1e15, -1e-5); }
+    assert_eq!(tostr(l, -1), "0.1 1e+15 -1e-05");
+    unsafe {
+        ffi::lua::lua_pushfstring(l, b"%s %d %f %%s\0".as_ptr() as *const libc::c_char,
+                                  std::ptr::null::<libc::c_char>(), 3 as libc::c_int, 2.5f64);
+    }
+    assert_eq!(tostr(l, -1), "(null) 3 2.5 %s");
+    unsafe extern "C-unwind" fn bad(l: *mut ffi::lua::lua_State) -> libc::c_int {
+        push_fstring!(l, "%d", "x");
+        1
+    }
+    unsafe {
+        ffi::lua::lua_pushcfunction(l, Some(bad));
+        assert_eq!(ffi::lua::lua_pcall(l, 0, 1, 0), ffi::lua::LUA_ERRRUN);
+    }
+    assert_eq!(tostr(l, -1), "bad argument for option '%d' to 'lua_pushfstring'");
+    unsafe { ffi::lua::lua_close(l); }
+}
+
+#[cfg(all(debug_assertions, rust_lapi))]
+#[test]
+fn test_api_check() {
+    /* a failed check unlocks its state, which can still be closed */
+    let l = unsafe { ffi::lauxlib::luaL_newstate() };
+    unsafe { ffi::lua::lua_pushinteger(l, 1); }
+    let err = std::panic::catch_unwind(|| unsafe { ffi::lua::lua_rawget(l, 1); }).unwrap_err();
+    assert_eq!(err.downcast_ref::<&str>(), Some(&"table expected"));
+    unsafe { ffi::lua::lua_close(l); }
+    let l = unsafe { ffi::lauxlib::luaL_newstate() };
+    let err = std::panic::catch_unwind(|| unsafe { ffi::lua::lua_settop(l, 1 << 20); }).unwrap_err();
+    assert_eq!(err.downcast_ref::<String>().map(|s| &s[..]), Some("new top 1048576 too large"));
+    unsafe { ffi::lua::lua_close(l); }
+}
+
+#[test]
+fn test_closure_suite() {
+    let l = unsafe { ffi::lauxlib::luaL_newstate() };
+    unsafe { ffi::lualib::luaL_openlibs(l); }
+    let file = b"puc-lua/src/tests/closure.lua\0".as_ptr() as *const libc::c_char;
+    assert_eq!(unsafe { ffi::lauxlib::luaL_dofile(l, file) }, ffi::lua::LUA_OK);
+    unsafe { ffi::lua::lua_close(l); }
+}
+
+#[test]
+fn test_upvalue_join() {
+    let l = unsafe { ffi::lauxlib::luaL_newstate() };
+    unsafe { ffi::lualib::luaL_openlibs(l); }
+    let code = "local a, b = 1, 2
+                f = function() return a end
+                g = function() return b end
+                h = function() a = a + 10; return a end";
+    assert_eq!(dostring(l, code), ffi::lua::LUA_OK);
+    unsafe {
+        ffi::lua::lua_getglobal(l, b"f\0".as_ptr() as *const libc::c_char);
+        ffi::lua::lua_getglobal(l, b"g\0".as_ptr() as *const libc::c_char);
+        ffi::lua::lua_getglobal(l, b"h\0".as_ptr() as *const libc::c_char);
+        assert!(ffi::lua::lua_upvalueid(l, 1, 1) == ffi::lua::lua_upvalueid(l, 3, 1));
+        assert!(ffi::lua::lua_upvalueid(l, 1, 1) != ffi::lua::lua_upvalueid(l, 2, 1));
+        ffi::lua::lua_upvaluejoin(l, 2, 1, 1, 1);  /* 'g' now shares 'a' with 'f' */
+        assert!(ffi::lua::lua_upvalueid(l, 1, 1) == ffi::lua::lua_upvalueid(l, 2, 1));
+        ffi::lua::lua_settop(l, 0);
+    }
+    assert_eq!(dostring(l, "assert(h() == 11 and f() == 11 and g() == 11)"), ffi::lua::LUA_OK);
+    assert_eq!(dostring(l, "collectgarbage(); assert(h() == 21 and g() == 21)"), ffi::lua::LUA_OK);
+    unsafe { ffi::lua::lua_close(l); }
+}
+
+#[cfg(rust_ldebug)]
+#[test]
+fn test_error_names() {
+    let l = unsafe { ffi::lauxlib::luaL_newstate() };
+    unsafe { ffi::lualib::luaL_openlibs(l); }
+    let code = "local o = {get = function() end}
+                local function check(f, name)
+                  local ok, msg = pcall(f)
+                  assert(not ok and string.find(msg, name, 1, true), msg)
+                end
+                check(function() return o:get().x end, \"(result of method 'get')\")
+                check(function() return o.get().x end, \"(result of field 'get')\")
+                check(function() return #{} + {} end, 'table value (table constructor)')
+                check(function() return math.huge | 1 end, \"number (field 'huge') has\")
+                local function f() return debug.getinfo(1, 'n').namewhat end
+                assert(o.get(f()) == nil and f() == 'local')";
+    assert_eq!(dostring(l, code), ffi::lua::LUA_OK);
+    unsafe { ffi::lua::lua_close(l); }
+}
+
+#[test]
+fn test_luac() {
+    use std::process::Command;
+    let dir = std::env::temp_dir().join(format!("luac-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("a.lua"), "x = 'a\\tb'\n").unwrap();
+    std::fs::write(dir.join("b.lua"), "print(x, 2^53)\n").unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_luac")).current_dir(&dir)
+                      .args(["-l", "-l", "-s", "-o", "ab.out", "a.lua", "b.lua"]).output().unwrap();
+    assert!(out.status.success());
+    let listing = String::from_utf8(out.stdout).unwrap();
+    assert!(listing.starts_with("\nmain <(luac):0,0> (5 instructions at "));
+    assert!(listing.contains("\nmain <a.lua:0,0> (2 instructions at "));
+    assert!(listing.contains("\t1\t[1]\tSETTABUP \t0 -1 -2\t; _ENV \"x\" \"a\\tb\"\n"));
+    assert!(listing.contains("\t1\t\"print\"\n\t2\t\"x\"\n\t3\t9.007199254741e+15\n"));
+    let out = Command::new(env!("CARGO_BIN_EXE_lua")).current_dir(&dir).arg("ab.out").output().unwrap();
+    assert_eq!(String::from_utf8(out.stdout).unwrap(), "a\tb\t9.007199254741e+15\n");
+    let out = Command::new(env!("CARGO_BIN_EXE_luac")).current_dir(&dir).arg("c.lua").output().unwrap();
+    assert!(!out.status.success());
+    assert!(String::from_utf8(out.stderr).unwrap().ends_with(": cannot open c.lua: No such file or directory\n"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_opcodes() {
+    use lua_rs::bytecode::{Chunk, Proto};
+    use lua_rs::opcodes::{Instruction, OpMode};
+    fn reencode(f: &Proto) -> usize {
+        for &i in &f.code {
+            let i = Instruction(i);
+            let o = i.opcode().unwrap();
+            let j = match o.mode() {
+                OpMode::iABC => Instruction::new_abc(o, i.a(), i.b(), i.c()),
+                OpMode::iABx => Instruction::new_abx(o, i.a(), i.bx()),
+                OpMode::iAsBx => Instruction::new_asbx(o, i.a(), i.sbx()),
+                OpMode::iAx => Instruction::new_ax(o, i.ax()),
+            };
+            assert_eq!(i, j, "{}", i);
+        }
+        f.code.len() + f.protos.iter().map(reencode).sum::<usize>()
+    }
+    let l = unsafe { ffi::lauxlib::luaL_newstate() };
+    unsafe { ffi::lualib::luaL_openlibs(l); }
+    let code = "return string.dump(loadfile('puc-lua/src/tests/constructs.lua'))";
+    assert_eq!(dostring(l, code), ffi::lua::LUA_OK);
+    let bytes = unsafe {
+        let mut len = 0;
+        let s = ffi::lua::lua_tolstring(l, -1, &mut len);
+        std::slice::from_raw_parts(s as *const u8, len).to_vec()
+    };
+    let chunk = Chunk::read(&bytes).unwrap();
+    assert!(reencode(&chunk.main) > 1000);
+    assert_eq!(Instruction(0x3F).opcode(), None);
+    assert_eq!(Instruction(0x3F).to_string(), "?        \t0x0000003f");
+    unsafe { ffi::lua::lua_close(l); }
+}